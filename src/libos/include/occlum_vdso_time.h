@@ -0,0 +1,104 @@
+#ifndef __OCCLUM_VDSO_TIME_H__
+#define __OCCLUM_VDSO_TIME_H__
+
+/*
+ * The user-side reader of the vDSO-style time page provided by Occlum LibOS.
+ *
+ * The address of the time page is given by the auxiliary vector entry
+ * AT_OCCLUM_VDSO_TIME. CLOCK_REALTIME and CLOCK_MONOTONIC can be read from the
+ * page with RDTSC, without entering the LibOS. For other clocks, or when the
+ * page is not valid (e.g., RDTSC is not supported inside the enclave), the
+ * reader falls back to the real system call.
+ *
+ * Note: the layout of the time page must be in sync with
+ * src/libos/src/time/vdso.rs.
+ */
+
+#include <stdint.h>
+#include <stddef.h>
+#include <time.h>
+#include <sys/auxv.h>
+#include <sys/syscall.h>
+#include <sys/time.h>
+#include <unistd.h>
+
+#define AT_OCCLUM_VDSO_TIME         49
+
+struct occlum_vdso_time_base {
+    uint64_t tsc;
+    uint64_t sec;
+    uint64_t nsec;
+};
+
+struct occlum_vdso_time_data {
+    volatile uint32_t seq;
+    uint32_t valid;
+    uint32_t shift;
+    uint32_t padding;
+    uint64_t mult;
+    /* Indexed by the clock ID: CLOCK_REALTIME and CLOCK_MONOTONIC */
+    struct occlum_vdso_time_base bases[2];
+};
+
+static inline uint64_t occlum_vdso_rdtsc(void) {
+    uint32_t hi, lo;
+    __asm__ __volatile__("rdtsc" : "=a"(lo), "=d"(hi));
+    return ((uint64_t)lo) | (((uint64_t)hi) << 32);
+}
+
+static inline const struct occlum_vdso_time_data *occlum_vdso_time_data(void) {
+    return (const struct occlum_vdso_time_data *)getauxval(AT_OCCLUM_VDSO_TIME);
+}
+
+/* Return 0 on success, or -1 if the fast path cannot be used */
+static inline int occlum_vdso_clock_gettime_fast(clockid_t clockid, struct timespec *ts) {
+    const struct occlum_vdso_time_data *data = occlum_vdso_time_data();
+    if (data == NULL || (clockid != CLOCK_REALTIME && clockid != CLOCK_MONOTONIC)) {
+        return -1;
+    }
+
+    uint32_t seq;
+    uint64_t tsc, mult, shift;
+    struct occlum_vdso_time_base base;
+    do {
+        seq = __atomic_load_n(&data->seq, __ATOMIC_ACQUIRE);
+        if (seq & 1) {
+            continue;
+        }
+        if (!data->valid) {
+            return -1;
+        }
+        base = data->bases[clockid];
+        mult = data->mult;
+        shift = data->shift;
+        tsc = occlum_vdso_rdtsc();
+        __atomic_thread_fence(__ATOMIC_ACQUIRE);
+    } while ((seq & 1) || seq != data->seq);
+
+    /* The base may be sampled by the LibOS on another CPU after the RDTSC */
+    uint64_t delta_tsc = tsc > base.tsc ? tsc - base.tsc : 0;
+    uint64_t delta_ns = (uint64_t)(((unsigned __int128)delta_tsc * mult) >> shift);
+    uint64_t nsec = base.nsec + delta_ns;
+    ts->tv_sec = base.sec + nsec / 1000000000UL;
+    ts->tv_nsec = nsec % 1000000000UL;
+    return 0;
+}
+
+static inline int occlum_vdso_clock_gettime(clockid_t clockid, struct timespec *ts) {
+    if (occlum_vdso_clock_gettime_fast(clockid, ts) == 0) {
+        return 0;
+    }
+    return syscall(__NR_clock_gettime, clockid, ts);
+}
+
+static inline int occlum_vdso_gettimeofday(struct timeval *tv, void *tz) {
+    struct timespec ts;
+    if (tz == NULL && occlum_vdso_clock_gettime_fast(CLOCK_REALTIME, &ts) == 0) {
+        tv->tv_sec = ts.tv_sec;
+        tv->tv_usec = ts.tv_nsec / 1000;
+        return 0;
+    }
+    return syscall(__NR_gettimeofday, tv, tz);
+}
+
+#endif /* __OCCLUM_VDSO_TIME_H__ */
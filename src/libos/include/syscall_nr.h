@@ -134,6 +134,8 @@
 #define SYS_io_getevents __NR_io_getevents
 #define SYS_io_setup __NR_io_setup
 #define SYS_io_submit __NR_io_submit
+#define SYS_io_uring_enter __NR_io_uring_enter
+#define SYS_io_uring_setup __NR_io_uring_setup
 #define SYS_ioctl __NR_ioctl
 #define SYS_ioperm __NR_ioperm
 #define SYS_iopl __NR_iopl
@@ -323,6 +325,7 @@
 #define SYS_stat64 __NR_stat64
 #define SYS_statfs __NR_statfs
 #define SYS_statfs64 __NR_statfs64
+#define SYS_statx __NR_statx
 #define SYS_stime __NR_stime
 #define SYS_stty __NR_stty
 #define SYS_swapoff __NR_swapoff
@@ -378,6 +381,8 @@
 #define SYS_writev __NR_writev
 
 #define SYS_spawn __NR_spawn
+#define SYS_set_syscall_filter __NR_set_syscall_filter
+#define SYS_spawn_with_attr __NR_spawn_with_attr
 
 #endif /* _SYSCALL_H */
 
@@ -711,6 +716,15 @@
 #define __NR_membarrier 324
 #define __NR_mlock2 325
 
+#define __NR_statx 332
+
 #define __NR_spawn 360
+#define __NR_set_syscall_filter 361
+#define __NR_spawn_with_attr 362
+
+#define __NR_io_uring_setup 425
+#define __NR_io_uring_enter 426
+
+#define __NR_epoll_pwait2 441
 
 #endif /* __OCCLUM_SYSCALL_NR_H__ */
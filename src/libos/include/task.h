@@ -23,6 +23,9 @@ struct Task {
     uint64_t            user_fs;
     uint64_t            user_entry_addr;
     jmp_buf*            saved_state;
+    uint64_t            sig_handler;
+    uint64_t            sig_num;
+    uint64_t            syscall_user_rsp;
 };
 
 void __set_current_task(struct Task* task);
@@ -53,6 +56,13 @@ void do_exit_task(void);
 #define TASK_USER_STACK_LIMIT       (8 * 6)
 #define TASK_USER_FS                (8 * 7)
 #define TASK_USER_ENTRY_ADDR        (8 * 8)
+#define TASK_SIG_HANDLER            (8 * 10)
+#define TASK_SIG_NUM                (8 * 11)
+#define TASK_SYSCALL_USER_RSP       (8 * 12)
+
+/* The size of siginfo_t and the si_code of the signals sent by tkill */
+#define SIGINFO_SIZE                128
+#define SI_TKILL                    (-6)
 
 /* arch_prctl syscall number and parameter */
 #define ARCH_PRCTL                  0x9E
@@ -1,4 +1,6 @@
 use super::*;
+use fs::{AtimePolicy, PIPE_BUF};
+use misc::EntropySource;
 use serde::{Deserialize, Serialize};
 use std::ffi::CString;
 use std::io::Read;
@@ -79,6 +81,8 @@ fn parse_mac(mac_str: &str) -> Result<sgx_aes_gcm_128bit_tag_t> {
 pub struct Config {
     pub vm: ConfigVM,
     pub process: ConfigProcess,
+    pub random: ConfigRandom,
+    pub fs: ConfigFs,
     pub env: Vec<CString>,
     pub entry_points: Vec<PathBuf>,
     pub mount: Vec<ConfigMount>,
@@ -87,6 +91,7 @@ pub struct Config {
 #[derive(Debug)]
 pub struct ConfigVM {
     pub user_space_size: usize,
+    pub enforce_wx: bool,
 }
 
 #[derive(Debug)]
@@ -94,6 +99,19 @@ pub struct ConfigProcess {
     pub default_stack_size: usize,
     pub default_heap_size: usize,
     pub default_mmap_size: usize,
+    pub record_last_error: bool,
+    pub allow_namespaces: bool,
+    pub max_num_of_threads: usize,
+}
+
+#[derive(Debug)]
+pub struct ConfigRandom {
+    pub entropy_source: EntropySource,
+}
+
+#[derive(Debug)]
+pub struct ConfigFs {
+    pub max_io_size: usize,
 }
 
 #[derive(Debug)]
@@ -104,7 +122,7 @@ pub struct ConfigMount {
     pub options: ConfigMountOptions,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[allow(non_camel_case_types)]
 pub enum ConfigMountFsType {
     TYPE_SEFS,
@@ -116,12 +134,17 @@ pub enum ConfigMountFsType {
 pub struct ConfigMountOptions {
     pub integrity_only: bool,
     pub mac: Option<sgx_aes_gcm_128bit_tag_t>,
+    pub size: Option<usize>,
+    pub atime: Option<AtimePolicy>,
+    pub read_only: bool,
 }
 
 impl Config {
     fn from_input(input: &InputConfig) -> Result<Config> {
         let vm = ConfigVM::from_input(&input.vm)?;
         let process = ConfigProcess::from_input(&input.process)?;
+        let random = ConfigRandom::from_input(&input.random)?;
+        let fs = ConfigFs::from_input(&input.fs)?;
         let env = {
             let mut env = Vec::new();
             for input_env in &input.env {
@@ -150,6 +173,8 @@ impl Config {
         Ok(Config {
             vm,
             process,
+            random,
+            fs,
             env,
             entry_points,
             mount,
@@ -160,7 +185,11 @@ impl Config {
 impl ConfigVM {
     fn from_input(input: &InputConfigVM) -> Result<ConfigVM> {
         let user_space_size = parse_memory_size(&input.user_space_size)?;
-        Ok(ConfigVM { user_space_size })
+        let enforce_wx = input.enforce_wx;
+        Ok(ConfigVM {
+            user_space_size,
+            enforce_wx,
+        })
     }
 }
 
@@ -169,14 +198,44 @@ impl ConfigProcess {
         let default_stack_size = parse_memory_size(&input.default_stack_size)?;
         let default_heap_size = parse_memory_size(&input.default_heap_size)?;
         let default_mmap_size = parse_memory_size(&input.default_mmap_size)?;
+        let record_last_error = input.record_last_error;
+        let allow_namespaces = input.allow_namespaces;
+        let max_num_of_threads = input.max_num_of_threads;
+        if max_num_of_threads == 0 {
+            return_errno!(EINVAL, "max_num_of_threads must not be zero");
+        }
         Ok(ConfigProcess {
             default_stack_size,
             default_heap_size,
             default_mmap_size,
+            record_last_error,
+            allow_namespaces,
+            max_num_of_threads,
         })
     }
 }
 
+impl ConfigRandom {
+    fn from_input(input: &InputConfigRandom) -> Result<ConfigRandom> {
+        let entropy_source = match &input.entropy_source {
+            Some(entropy_source) => EntropySource::from_str(entropy_source)?,
+            None => EntropySource::default(),
+        };
+        Ok(ConfigRandom { entropy_source })
+    }
+}
+
+impl ConfigFs {
+    fn from_input(input: &InputConfigFs) -> Result<ConfigFs> {
+        let max_io_size = parse_memory_size(&input.max_io_size)?;
+        // The writes of at most PIPE_BUF bytes must not be split to be atomic
+        if max_io_size < PIPE_BUF {
+            return_errno!(EINVAL, "max_io_size must be at least PIPE_BUF");
+        }
+        Ok(ConfigFs { max_io_size })
+    }
+}
+
 impl ConfigMount {
     fn from_input(input: &InputConfigMount) -> Result<ConfigMount> {
         const ALL_FS_TYPES: [&str; 3] = ["sefs", "hostfs", "ramfs"];
@@ -217,9 +276,20 @@ impl ConfigMountOptions {
             }
             (true, Some(parse_mac(&input.mac.as_ref().unwrap())?))
         };
+        let size = match &input.size {
+            Some(size) => Some(parse_memory_size(size)?),
+            None => None,
+        };
+        let atime = match &input.atime {
+            Some(atime) => Some(AtimePolicy::from_str(atime)?),
+            None => None,
+        };
         Ok(ConfigMountOptions {
             integrity_only,
             mac,
+            size,
+            atime,
+            read_only: input.read_only,
         })
     }
 }
@@ -257,6 +327,10 @@ struct InputConfig {
     #[serde(default)]
     pub process: InputConfigProcess,
     #[serde(default)]
+    pub random: InputConfigRandom,
+    #[serde(default)]
+    pub fs: InputConfigFs,
+    #[serde(default)]
     pub env: Vec<String>,
     #[serde(default)]
     pub entry_points: Vec<String>,
@@ -269,6 +343,8 @@ struct InputConfig {
 struct InputConfigVM {
     #[serde(default = "InputConfigVM::get_user_space_size")]
     pub user_space_size: String,
+    #[serde(default)]
+    pub enforce_wx: bool,
 }
 
 impl InputConfigVM {
@@ -281,6 +357,7 @@ impl Default for InputConfigVM {
     fn default() -> InputConfigVM {
         InputConfigVM {
             user_space_size: InputConfigVM::get_user_space_size(),
+            enforce_wx: false,
         }
     }
 }
@@ -293,6 +370,12 @@ struct InputConfigProcess {
     pub default_heap_size: String,
     #[serde(default = "InputConfigProcess::get_default_mmap_size")]
     pub default_mmap_size: String,
+    #[serde(default)]
+    pub record_last_error: bool,
+    #[serde(default)]
+    pub allow_namespaces: bool,
+    #[serde(default = "InputConfigProcess::get_max_num_of_threads")]
+    pub max_num_of_threads: usize,
 }
 
 impl InputConfigProcess {
@@ -307,6 +390,11 @@ impl InputConfigProcess {
     fn get_default_mmap_size() -> String {
         "32MB".to_string()
     }
+
+    // The same as TCSNum of the Enclave.xml template
+    fn get_max_num_of_threads() -> usize {
+        32
+    }
 }
 
 impl Default for InputConfigProcess {
@@ -315,6 +403,37 @@ impl Default for InputConfigProcess {
             default_stack_size: InputConfigProcess::get_default_stack_size(),
             default_heap_size: InputConfigProcess::get_default_heap_size(),
             default_mmap_size: InputConfigProcess::get_default_mmap_size(),
+            record_last_error: false,
+            allow_namespaces: false,
+            max_num_of_threads: InputConfigProcess::get_max_num_of_threads(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct InputConfigRandom {
+    #[serde(default)]
+    pub entropy_source: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct InputConfigFs {
+    #[serde(default = "InputConfigFs::get_max_io_size")]
+    pub max_io_size: String,
+}
+
+impl InputConfigFs {
+    fn get_max_io_size() -> String {
+        "64MB".to_string()
+    }
+}
+
+impl Default for InputConfigFs {
+    fn default() -> InputConfigFs {
+        InputConfigFs {
+            max_io_size: InputConfigFs::get_max_io_size(),
         }
     }
 }
@@ -338,4 +457,10 @@ struct InputConfigMountOptions {
     #[serde(rename = "MAC")]
     #[serde(default)]
     pub mac: Option<String>,
+    #[serde(default)]
+    pub size: Option<String>,
+    #[serde(default)]
+    pub atime: Option<String>,
+    #[serde(default)]
+    pub read_only: bool,
 }
@@ -110,10 +110,17 @@ fn do_new_process(program_path: &PathBuf, argv: &Vec<CString>) -> Result<pid_t>
 
     let envp = &config::LIBOS_CONFIG.env;
     let file_actions = Vec::new();
+    let spawn_attr = Default::default();
     let parent = &process::IDLE_PROCESS;
     let program_path_str = program_path.to_str().unwrap();
-    let new_tid =
-        process::do_spawn_without_exec(&program_path_str, argv, envp, &file_actions, parent)?;
+    let new_tid = process::do_spawn_without_exec(
+        &program_path_str,
+        argv,
+        envp,
+        &file_actions,
+        &spawn_attr,
+        parent,
+    )?;
     Ok(new_tid)
 }
 
@@ -3,6 +3,8 @@ use self::rdtsc::*;
 use super::*;
 use sgx_types::*;
 
+pub use self::rdtsc::is_rdtsc_emulated;
+
 pub fn register_exception_handlers() {
     setup_cpuid_info();
     unsafe {
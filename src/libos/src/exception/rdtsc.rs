@@ -1,9 +1,17 @@
 use super::*;
 use sgx_types::*;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 const RDTSC_OPCODE: u16 = 0x310F;
 static mut FAKE_RDTSC_VALUE: u64 = 0;
 static FAKE_RDTSC_INC_VALUE: u64 = 1000;
+static RDTSC_EMULATED: AtomicBool = AtomicBool::new(false);
+
+/// Whether any rdtsc instruction has been emulated, i.e., rdtsc is not
+/// supported inside the enclave and its value is fake
+pub fn is_rdtsc_emulated() -> bool {
+    RDTSC_EMULATED.load(Ordering::Relaxed)
+}
 
 #[no_mangle]
 pub extern "C" fn handle_rdtsc_exception(info: *mut sgx_exception_info_t) -> u32 {
@@ -16,6 +24,7 @@ pub extern "C" fn handle_rdtsc_exception(info: *mut sgx_exception_info_t) -> u32
         return EXCEPTION_CONTINUE_SEARCH;
     }
     // rdtsc support here is temporary, only for SKL, later CPU's will support this inside enclave
+    RDTSC_EMULATED.store(true, Ordering::Relaxed);
     unsafe {
         FAKE_RDTSC_VALUE += FAKE_RDTSC_INC_VALUE;
         info.cpu_context.rax = (FAKE_RDTSC_VALUE & 0xFFFFFFFF);
@@ -3,19 +3,10 @@ use super::*;
 #[derive(Debug)]
 pub struct DevRandom;
 
-extern "C" {
-    fn sgx_read_rand(rand_buf: *mut u8, buf_size: usize) -> sgx_status_t;
-}
-
 impl File for DevRandom {
     fn read(&self, _buf: &mut [u8]) -> Result<usize> {
-        let buf = _buf.as_mut_ptr();
-        let size = _buf.len();
-        let status = unsafe { sgx_read_rand(buf, size) };
-        if status != sgx_status_t::SGX_SUCCESS {
-            return_errno!(EAGAIN, "failed to get random number from sgx");
-        }
-        Ok(size)
+        misc::get_random(_buf)?;
+        Ok(_buf.len())
     }
 
     fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> Result<usize> {
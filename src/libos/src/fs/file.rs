@@ -1,4 +1,6 @@
 use super::*;
+use process::pid_t;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 macro_rules! return_op_unsupported_error {
     ($op_name: expr, $errno: expr) => {{
@@ -55,6 +57,12 @@ pub trait File: Debug + Sync + Send + Any {
         return_op_unsupported_error!("read_entry", ENOTDIR)
     }
 
+    /// Get the inode number of the entry of the directory, which is the same
+    /// as what stat returns for the entry
+    fn entry_ino(&self, name: &str) -> Result<u64> {
+        return_op_unsupported_error!("entry_ino", ENOTDIR)
+    }
+
     fn sync_all(&self) -> Result<()> {
         Ok(())
     }
@@ -79,19 +87,78 @@ pub trait File: Debug + Sync + Send + Any {
         return_op_unsupported_error!("set_status_flags")
     }
 
-    fn test_advisory_lock(&self, lock: &mut Flock) -> Result<()> {
+    /// Busy poll the file until it is ready for reading, which is requested by
+    /// RWF_HIPRI. Only the files that support busy polling, e.g., sockets, do it.
+    fn busy_poll(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn test_advisory_lock(&self, lock: &mut Flock, kind: FileLockKind) -> Result<()> {
         return_op_unsupported_error!("test_advisory_lock")
     }
 
-    fn set_advisory_lock(&self, lock: &Flock) -> Result<()> {
+    fn set_advisory_lock(&self, lock: &Flock, kind: FileLockKind, wait: bool) -> Result<()> {
         return_op_unsupported_error!("set_advisory_lock")
     }
 
+    fn set_lease(&self, type_: FlockType, pid: pid_t) -> Result<()> {
+        return_op_unsupported_error!("set_lease", EINVAL)
+    }
+
+    fn get_lease(&self) -> Result<FlockType> {
+        return_op_unsupported_error!("get_lease", EINVAL)
+    }
+
+    /// Get the count of the file descriptors of the file, if the operations on
+    /// the file may block, so that they are interrupted when the last file
+    /// descriptor is closed
+    fn fd_count(&self) -> Option<&FdCount> {
+        None
+    }
+
+    /// Interrupt the operations blocked on the file, which is done when the
+    /// last file descriptor of the file is closed, e.g., by another thread.
+    /// The interrupted operations fail with EBADF.
+    fn interrupt_blocked_ops(&self) {}
+
     fn as_any(&self) -> &dyn Any;
 }
 
 pub type FileRef = Arc<Box<dyn File>>;
 
+/// The count of the file descriptors of a file in all the file tables, which
+/// tells whether the file is closed, i.e., its last file descriptor is closed.
+/// Unlike the count of the references, it does not count the references that
+/// are held by the ongoing operations, e.g., those blocked on the file.
+#[derive(Debug, Default)]
+pub struct FdCount {
+    num_fds: AtomicUsize,
+    closed: AtomicBool,
+}
+
+impl FdCount {
+    pub fn new() -> FdCount {
+        Default::default()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    pub(super) fn inc(&self) {
+        self.num_fds.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Return whether the last file descriptor is closed
+    pub(super) fn dec(&self) -> bool {
+        if self.num_fds.fetch_sub(1, Ordering::SeqCst) != 1 {
+            return false;
+        }
+        self.closed.store(true, Ordering::SeqCst);
+        true
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 struct FileOpNotSupportedError {
     errno: Errno,
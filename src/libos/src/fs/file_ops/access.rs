@@ -1,4 +1,5 @@
 use super::*;
+use process::{gid_t, uid_t, Capabilities};
 
 bitflags! {
     pub struct AccessibilityCheckMode : u32 {
@@ -39,19 +40,87 @@ pub fn do_faccessat(
         "faccessat: dirfd: {:?}, path: {:?}, mode: {:?}, flags: {:?}",
         dirfd, path, mode, flags
     );
-    match dirfd {
+    if dirfd.is_some() && !path.starts_with('/') {
         // TODO: handle dirfd
-        Some(dirfd) => return_errno!(ENOSYS, "cannot accept dirfd"),
-        None => do_access(path, mode),
+        return_errno!(ENOSYS, "cannot accept dirfd");
     }
+
+    // A trailing slash requires the path to be a directory, so the symlink is
+    // always followed then as in Linux
+    let must_be_dir = path.ends_with('/');
+    let (inode, credentials) = {
+        let current_ref = process::get_current();
+        let current = current_ref.lock().unwrap();
+        let inode = if flags.contains(AccessibilityCheckFlags::AT_SYMLINK_NOFOLLOW) && !must_be_dir
+        {
+            current.lookup_inode(path)?
+        } else {
+            current.lookup_inode_follow(path)?
+        };
+        let credentials = current.get_credentials().lock().unwrap().clone();
+        (inode, credentials)
+    };
+    let info = inode.metadata()?;
+    if must_be_dir && info.type_ != FileType::Dir {
+        return_errno!(ENOTDIR, "the path with a trailing slash is not a directory");
+    }
+
+    // The real ids are used unless AT_EACCESS is given, with which the
+    // capabilities are the permitted ones of root, like Linux
+    let (uid, gid, dac_override) = if flags.contains(AccessibilityCheckFlags::AT_EACCESS) {
+        let dac_override = credentials.has_capability(Capabilities::DAC_OVERRIDE);
+        (credentials.euid(), credentials.egid(), dac_override)
+    } else {
+        let dac_override = credentials.ruid() == 0
+            && credentials
+                .capabilities()
+                .permitted
+                .contains(Capabilities::DAC_OVERRIDE);
+        (credentials.ruid(), credentials.rgid(), dac_override)
+    };
+    check_mode(&info, mode, uid, gid, credentials.groups(), dac_override)
 }
 
 pub fn do_access(path: &str, mode: AccessibilityCheckMode) -> Result<()> {
-    info!("access: path: {:?}, mode: {:?}", path, mode);
-    let current_ref = process::get_current();
-    let mut current = current_ref.lock().unwrap();
-    let inode = current.lookup_inode(path)?;
-    //let metadata = inode.get_metadata();
-    // TODO: check metadata.mode with mode
+    do_faccessat(None, path, mode, AccessibilityCheckFlags::empty())
+}
+
+/// Check the mode against the permission bits of the owner, the group or the
+/// others, whichever the ids fall in, with the gid or the supplementary groups
+/// being the group. For a directory, X_OK means the permission to search it.
+///
+/// With CAP_DAC_OVERRIDE, i.e., `dac_override`, the permission bits of the
+/// group and the others are bypassed, except that X_OK on a non-directory
+/// still requires one of the execute bits. Unlike Linux, the bits of the owner
+/// are never bypassed, so that the read-only files of root, e.g., the ones in
+/// /bin and /lib, stay read-only.
+pub fn check_mode(
+    info: &Metadata,
+    mode: AccessibilityCheckMode,
+    uid: uid_t,
+    gid: gid_t,
+    groups: &[gid_t],
+    dac_override: bool,
+) -> Result<()> {
+    let perms = info.mode as u32;
+    let is_owner = info.uid as uid_t == uid;
+    if dac_override && !is_owner {
+        let can_exec = info.type_ == FileType::Dir || perms & 0o111 != 0;
+        if !mode.contains(AccessibilityCheckMode::X_OK) || can_exec {
+            return Ok(());
+        }
+        return_errno!(EACCES, "the file is not executable");
+    }
+    let file_gid = info.gid as gid_t;
+    let class_perms = if is_owner {
+        perms >> 6
+    } else if file_gid == gid || groups.contains(&file_gid) {
+        perms >> 3
+    } else {
+        perms
+    };
+    if !AccessibilityCheckMode::from_bits_truncate(class_perms & 0o7).contains(mode) {
+        return_errno!(EACCES, "the access is not permitted");
+    }
     Ok(())
 }
@@ -0,0 +1,278 @@
+/// The notifications of the changes to inodes, which can be checked cheaply
+/// by the watchers of an inode, e.g., when an epoll is polled.
+///
+/// The operations that modify an inode notify the change after they are done,
+/// which wakes the waiter queue of the inode if it is watched. Whether an
+/// inode may be watched is told by a count of the watches per slot of the
+/// inodes that hash to it, so the unwatched inodes cost only an atomic load
+/// on each modification, without any lock or metadata.
+///
+/// The changes to the entries of the directories are notified to the dnotify
+/// watches of the directories (see F_NOTIFY of fcntl) by SIGIO. The operations
+/// may be done with the current process locked, so the changes are recorded
+/// by the current thread, and the signals are sent at the exit of the syscall.
+use super::fcntl::DnotifyEvents;
+use super::file_lock::InodeKey;
+use super::*;
+use process::pid_t;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Weak;
+use util::waiter::WaiterQueue;
+
+/// The number of the slots of the watch counts
+const NUM_WATCH_SLOTS: usize = 64;
+
+lazy_static! {
+    static ref WATCHED_INODES: SgxMutex<BTreeMap<InodeKey, WatchedInode>> =
+        SgxMutex::new(BTreeMap::new());
+    static ref WATCH_COUNTS: Vec<AtomicUsize> =
+        (0..NUM_WATCH_SLOTS).map(|_| AtomicUsize::new(0)).collect();
+    static ref DNOTIFY_WATCHES: SgxMutex<BTreeMap<InodeKey, Vec<DnotifyWatch>>> =
+        SgxMutex::new(BTreeMap::new());
+}
+
+/// The number of the dnotify watches, which lets the changes skip recording
+/// when there is no watch
+static NUM_DNOTIFY_WATCHES: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // The changes made by the current thread in the syscall, which are sent to
+    // the watches at the exit of the syscall
+    static DIR_CHANGES: RefCell<Vec<DirChange>> = RefCell::new(Vec::new());
+}
+
+fn watch_count(key: &InodeKey) -> &'static AtomicUsize {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    &WATCH_COUNTS[hasher.finish() as usize % NUM_WATCH_SLOTS]
+}
+
+#[derive(Debug)]
+struct WatchedInode {
+    num_watches: usize,
+    // The size of the inode when it was last checked
+    size: usize,
+    // The number of the size changes since the inode is watched
+    size_seq: u64,
+    // The waiters for the size changes
+    waiters: Arc<WaiterQueue>,
+}
+
+/// A watch of the size changes of an inode, e.g., by writes that extend the
+/// file or by truncates, which stops when it is dropped
+#[derive(Debug)]
+pub struct SizeWatch {
+    key: InodeKey,
+    waiters: Arc<WaiterQueue>,
+}
+
+impl SizeWatch {
+    pub fn new(inode: &Arc<dyn INode>) -> Result<SizeWatch> {
+        let key = InodeKey::new(inode)?;
+        let size = inode.metadata()?.size;
+        let mut watched_inodes = WATCHED_INODES.lock().unwrap();
+        let watched = watched_inodes.entry(key).or_insert_with(|| WatchedInode {
+            num_watches: 0,
+            size,
+            size_seq: 0,
+            waiters: Arc::new(WaiterQueue::new()),
+        });
+        watched.num_watches += 1;
+        watch_count(&key).fetch_add(1, Ordering::SeqCst);
+        Ok(SizeWatch {
+            key,
+            waiters: watched.waiters.clone(),
+        })
+    }
+
+    /// Get the sequence number of the size changes, which differs from the
+    /// one got before if and only if the size has changed since then
+    pub fn size_seq(&self) -> u64 {
+        WATCHED_INODES.lock().unwrap()[&self.key].size_seq
+    }
+
+    /// The waiter queue that is woken when the size changes
+    pub fn waiters(&self) -> &Arc<WaiterQueue> {
+        &self.waiters
+    }
+}
+
+impl Drop for SizeWatch {
+    fn drop(&mut self) {
+        let mut watched_inodes = WATCHED_INODES.lock().unwrap();
+        let watched = watched_inodes.get_mut(&self.key).unwrap();
+        watched.num_watches -= 1;
+        if watched.num_watches == 0 {
+            watched_inodes.remove(&self.key);
+        }
+        watch_count(&self.key).fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Notify the watchers that the inode of the key is modified, e.g., by a
+/// write or a truncate
+pub fn notify_modified(inode: &Arc<dyn INode>, key: &InodeKey) {
+    if watch_count(key).load(Ordering::SeqCst) == 0 {
+        return;
+    }
+    let size = match inode.metadata() {
+        Ok(metadata) => metadata.size,
+        Err(_) => return,
+    };
+    let waiters = {
+        let mut watched_inodes = WATCHED_INODES.lock().unwrap();
+        let watched = match watched_inodes.get_mut(key) {
+            Some(watched) => watched,
+            None => return,
+        };
+        if watched.size == size {
+            return;
+        }
+        watched.size = size;
+        watched.size_seq += 1;
+        watched.waiters.clone()
+    };
+    waiters.dequeue_and_wake_all();
+}
+
+/// A dnotify watch of a directory, which is removed when the file of the
+/// watch is closed
+#[derive(Debug)]
+struct DnotifyWatch {
+    file: Weak<Box<dyn File>>,
+    // The process that is signaled on the events
+    pid: pid_t,
+    events: DnotifyEvents,
+}
+
+#[derive(Debug)]
+enum DirChange {
+    // The entries of the directory are changed
+    Entries(InodeKey, DnotifyEvents),
+    // The file at the absolute path is changed, which is resolved to the
+    // directory of the file when the change is sent
+    File(String, DnotifyEvents),
+}
+
+/// Add the events to the dnotify watch of the file, which must be a
+/// directory, as Linux does. No event removes the watch.
+pub fn set_dnotify(file: &FileRef, pid: pid_t, events: DnotifyEvents) -> Result<()> {
+    let key = InodeKey::new(file.as_inode_file()?.get_inode())?;
+    let mut dnotify_watches = DNOTIFY_WATCHES.lock().unwrap();
+    let watches = dnotify_watches.entry(key).or_insert_with(Vec::new);
+    let watch_idx = watches.iter().position(|watch| {
+        watch
+            .file
+            .upgrade()
+            .map_or(false, |watch_file| Arc::ptr_eq(&watch_file, file))
+    });
+    match watch_idx {
+        Some(idx) if events.is_empty() => {
+            watches.remove(idx);
+            NUM_DNOTIFY_WATCHES.fetch_sub(1, Ordering::SeqCst);
+        }
+        Some(idx) => {
+            watches[idx].pid = pid;
+            watches[idx].events |= events;
+        }
+        None if events.is_empty() => {}
+        None => {
+            watches.push(DnotifyWatch {
+                file: Arc::downgrade(file),
+                pid,
+                events,
+            });
+            NUM_DNOTIFY_WATCHES.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+    if watches.is_empty() {
+        dnotify_watches.remove(&key);
+    }
+    Ok(())
+}
+
+/// Notify the dnotify watches of the directory that its entries are created,
+/// deleted or renamed
+pub fn notify_dir_changed(dir_inode: &Arc<dyn INode>, events: DnotifyEvents) {
+    if NUM_DNOTIFY_WATCHES.load(Ordering::SeqCst) == 0 {
+        return;
+    }
+    if let Ok(key) = InodeKey::new(dir_inode) {
+        DIR_CHANGES.with(|changes| changes.borrow_mut().push(DirChange::Entries(key, events)));
+    }
+}
+
+/// Notify the dnotify watches of the directory of the file at the absolute
+/// path that the file is accessed, modified or has its attributes changed
+pub fn notify_file_changed(abs_path: &str, events: DnotifyEvents) {
+    if NUM_DNOTIFY_WATCHES.load(Ordering::SeqCst) == 0 {
+        return;
+    }
+    DIR_CHANGES.with(|changes| {
+        changes
+            .borrow_mut()
+            .push(DirChange::File(abs_path.to_owned(), events))
+    });
+}
+
+/// Send SIGIO to the processes of the dnotify watches of the changes made by
+/// the current thread, which is done at the exit of every syscall, when the
+/// current process is not locked. A watch without DN_MULTISHOT is removed
+/// after it is signaled.
+pub fn send_dnotify_signals() {
+    let changes =
+        DIR_CHANGES.with(|changes| std::mem::replace(&mut *changes.borrow_mut(), Vec::new()));
+    if changes.is_empty() {
+        return;
+    }
+    let mut pids = Vec::new();
+    for change in changes {
+        let (key, events) = match change {
+            DirChange::Entries(key, events) => (key, events),
+            DirChange::File(abs_path, events) => {
+                let (dir_path, _) = split_path(&abs_path);
+                let dir_inode = {
+                    let current_ref = process::get_current();
+                    let current = current_ref.lock().unwrap();
+                    current.lookup_inode_follow(dir_path)
+                };
+                // The file may have been removed or renamed since the change
+                match dir_inode.and_then(|dir_inode| InodeKey::new(&dir_inode)) {
+                    Ok(key) => (key, events),
+                    Err(_) => continue,
+                }
+            }
+        };
+        let mut dnotify_watches = DNOTIFY_WATCHES.lock().unwrap();
+        let watches = match dnotify_watches.get_mut(&key) {
+            Some(watches) => watches,
+            None => continue,
+        };
+        let old_len = watches.len();
+        watches.retain(|watch| {
+            // The watch of a closed file is dropped, too
+            if watch.file.upgrade().is_none() {
+                return false;
+            }
+            if !watch.events.intersects(events) {
+                return true;
+            }
+            if !pids.contains(&watch.pid) {
+                pids.push(watch.pid);
+            }
+            watch.events.contains(DnotifyEvents::DN_MULTISHOT)
+        });
+        NUM_DNOTIFY_WATCHES.fetch_sub(old_len - watches.len(), Ordering::SeqCst);
+        if watches.is_empty() {
+            dnotify_watches.remove(&key);
+        }
+    }
+    for pid in pids {
+        if let Err(e) = process::do_kill(pid, Some(process::SIGIO)) {
+            warn!("failed to send the dnotify signal to {}: {}", pid, e);
+        }
+    }
+}
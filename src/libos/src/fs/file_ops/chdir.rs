@@ -1,15 +1,170 @@
+use super::file_lock::InodeKey;
 use super::*;
 
+/// The current working directory of a process.
+///
+/// The cwd holds a reference to the inode of the directory, so that it is
+/// still resolvable after the directory is renamed or unlinked. The path is
+/// the last known path of the directory, which is refreshed by getcwd, and
+/// the relative paths are converted to be absolute with the current path.
+#[derive(Clone)]
+pub struct Cwd {
+    path: String,
+    inode: Arc<dyn INode>,
+}
+
+impl Cwd {
+    pub fn root() -> Cwd {
+        Cwd {
+            path: "/".to_owned(),
+            inode: ROOT_INODE.clone(),
+        }
+    }
+
+    pub fn new(abs_path: &str, inode: Arc<dyn INode>) -> Cwd {
+        Cwd {
+            path: abs_path.to_owned(),
+            inode,
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn inode(&self) -> &Arc<dyn INode> {
+        &self.inode
+    }
+
+    /// Get the path of the cwd, reconstructing it if the directory has been
+    /// moved since the path is known. Return None if the directory is not
+    /// reachable from the root, e.g., it has been removed.
+    pub fn resolve_path(&mut self, root_inode: &Arc<dyn INode>) -> Result<Option<&str>> {
+        match self.find_path(root_inode)? {
+            Some(path) => {
+                self.path = path;
+                Ok(Some(&self.path))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Get the current path of the cwd as `resolve_path` does, but without
+    /// refreshing the known path. The known path is returned if the directory
+    /// is not reachable from the root.
+    pub fn current_path(&self, root_inode: &Arc<dyn INode>) -> String {
+        match self.find_path(root_inode) {
+            Ok(Some(path)) => path,
+            _ => self.path.clone(),
+        }
+    }
+
+    fn find_path(&self, root_inode: &Arc<dyn INode>) -> Result<Option<String>> {
+        let key = InodeKey::new(&self.inode)?;
+        if let Ok(path_inode) = root_inode.lookup(self.path.trim_start_matches('/')) {
+            if InodeKey::new(&path_inode)? == key {
+                return Ok(Some(self.path.clone()));
+            }
+        }
+
+        // Walk up to the root, finding the name of each directory in its parent
+        let root_key = InodeKey::new(root_inode)?;
+        let mut names = Vec::new();
+        let mut inode = self.inode.clone();
+        let mut key = key;
+        while key != root_key {
+            let parent = match inode.lookup("..") {
+                Ok(parent) => parent,
+                Err(_) => return Ok(None),
+            };
+            let parent_key = InodeKey::new(&parent)?;
+            if parent_key == key {
+                // The root of a detached file system
+                return Ok(None);
+            }
+            match find_entry_name(&parent, key)? {
+                Some(name) => names.push(name),
+                None => return Ok(None),
+            }
+            inode = parent;
+            key = parent_key;
+        }
+
+        let path = names
+            .iter()
+            .rev()
+            .fold(String::new(), |path, name| path + "/" + name);
+        if path.is_empty() {
+            return Ok(Some("/".to_owned()));
+        }
+        Ok(Some(path))
+    }
+}
+
+impl Debug for Cwd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Cwd {{ path: {:?}, inode: ??? }}", self.path)
+    }
+}
+
+/// Find the name of the entry of `dir_inode` whose inode is `key`
+fn find_entry_name(dir_inode: &Arc<dyn INode>, key: InodeKey) -> Result<Option<String>> {
+    for name in snapshot_dir_entries(dir_inode)? {
+        if name == "." || name == ".." {
+            continue;
+        }
+        // The entry may be removed after the snapshot
+        let entry_key = match dir_inode.lookup(&name) {
+            Ok(inode) => InodeKey::new(&inode)?,
+            Err(_) => continue,
+        };
+        if entry_key == key {
+            return Ok(Some(name));
+        }
+    }
+    Ok(None)
+}
+
 pub fn do_chdir(path: &str) -> Result<()> {
     let current_ref = process::get_current();
     let mut current_process = current_ref.lock().unwrap();
     info!("chdir: path: {:?}", path);
 
-    let inode = current_process.lookup_inode(path)?;
+    let inode = current_process.lookup_inode_follow(path)?;
     let info = inode.metadata()?;
     if info.type_ != FileType::Dir {
         return_errno!(ENOTDIR, "");
     }
-    current_process.change_cwd(path);
+    current_process.change_cwd(path, inode);
+    Ok(())
+}
+
+pub fn do_fchdir(fd: FileDesc) -> Result<()> {
+    let current_ref = process::get_current();
+    let mut current_process = current_ref.lock().unwrap();
+    info!("fchdir: fd: {}", fd);
+
+    let file_ref = current_process.get_files().lock().unwrap().get(fd)?;
+    let inode_file = file_ref
+        .as_inode_file()
+        .map_err(|_| errno!(ENOTDIR, "not a directory"))?;
+    let inode = inode_file.get_inode().clone();
+    if inode.metadata()?.type_ != FileType::Dir {
+        return_errno!(ENOTDIR, "not a directory");
+    }
+    current_process.change_cwd(inode_file.get_abs_path(), inode);
     Ok(())
 }
+
+/// Get the path of the cwd, which is prefixed with "(unreachable)" if the
+/// directory is not reachable from the root as in Linux
+pub fn do_getcwd() -> Result<String> {
+    let current_ref = process::get_current();
+    let mut current_process = current_ref.lock().unwrap();
+    let root_inode = current_process.get_mnt_ns().root_inode();
+    let cwd = current_process.get_cwd_mut();
+    if let Some(path) = cwd.resolve_path(&root_inode)? {
+        return Ok(path.to_owned());
+    }
+    Ok(format!("(unreachable){}", cwd.path()))
+}
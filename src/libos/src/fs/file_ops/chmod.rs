@@ -0,0 +1,53 @@
+use super::*;
+use process::{gid_t, Capabilities, Credentials};
+
+pub fn do_chmod(path: &str, mode: u16) -> Result<()> {
+    info!("chmod: path: {:?}, mode: {:#o}", path, mode);
+    let (inode, abs_path, credentials) = {
+        let current_ref = process::get_current();
+        let current = current_ref.lock().unwrap();
+        let inode = current.lookup_inode_follow(path)?;
+        sefs::check_not_read_only(&inode)?;
+        let credentials = current.get_credentials().lock().unwrap().clone();
+        (inode, current.convert_to_abs_path(path), credentials)
+    };
+    set_mode(&inode, mode, &credentials)?;
+    notify_file_changed(&abs_path, DnotifyEvents::DN_ATTRIB);
+    Ok(())
+}
+
+pub fn do_fchmod(fd: FileDesc, mode: u16) -> Result<()> {
+    info!("fchmod: fd: {}, mode: {:#o}", fd, mode);
+    let (file_ref, credentials) = {
+        let current_ref = process::get_current();
+        let current = current_ref.lock().unwrap();
+        let file_ref = current.get_files().lock().unwrap().get(fd)?;
+        let credentials = current.get_credentials().lock().unwrap().clone();
+        (file_ref, credentials)
+    };
+    let inode_file = file_ref.as_inode_file()?;
+    sefs::check_not_read_only(inode_file.get_inode())?;
+    set_mode(inode_file.get_inode(), mode, &credentials)?;
+    notify_file_changed(inode_file.get_abs_path(), DnotifyEvents::DN_ATTRIB);
+    Ok(())
+}
+
+/// Set the permission bits of the inode, which is allowed for the owner only
+/// without CAP_FOWNER. The set-group-ID bit is cleared, unless the caller is
+/// in the group of the inode or has CAP_FSETID.
+fn set_mode(inode: &Arc<dyn INode>, mode: u16, credentials: &Credentials) -> Result<()> {
+    let mut info = inode.metadata()?;
+    let mut mode = mode & 0o7777;
+    if !credentials.has_capability(Capabilities::FOWNER) && info.uid != credentials.euid() as usize
+    {
+        return_errno!(EPERM, "the mode can only be changed by the owner");
+    }
+    if !credentials.has_capability(Capabilities::FSETID)
+        && !credentials.is_in_group(info.gid as gid_t)
+    {
+        mode &= !(StatMode::SET_GID.bits() as u16);
+    }
+    info.mode = mode;
+    inode.set_metadata(&info)?;
+    Ok(())
+}
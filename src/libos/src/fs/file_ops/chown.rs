@@ -0,0 +1,104 @@
+use super::*;
+use process::{gid_t, uid_t, Capabilities, Credentials};
+
+pub fn do_chown(path: &str, uid: Option<uid_t>, gid: Option<gid_t>) -> Result<()> {
+    info!("chown: path: {:?}, uid: {:?}, gid: {:?}", path, uid, gid);
+    do_chown_inode(path, uid, gid, true)
+}
+
+/// Like `do_chown`, but the symlink at the end of the path is not followed
+pub fn do_lchown(path: &str, uid: Option<uid_t>, gid: Option<gid_t>) -> Result<()> {
+    info!("lchown: path: {:?}, uid: {:?}, gid: {:?}", path, uid, gid);
+    do_chown_inode(path, uid, gid, false)
+}
+
+pub fn do_fchown(fd: FileDesc, uid: Option<uid_t>, gid: Option<gid_t>) -> Result<()> {
+    info!("fchown: fd: {}, uid: {:?}, gid: {:?}", fd, uid, gid);
+    let (file_ref, credentials) = {
+        let current_ref = process::get_current();
+        let current = current_ref.lock().unwrap();
+        let file_ref = current.get_files().lock().unwrap().get(fd)?;
+        let credentials = current.get_credentials().lock().unwrap().clone();
+        (file_ref, credentials)
+    };
+    let inode_file = file_ref.as_inode_file()?;
+    sefs::check_not_read_only(inode_file.get_inode())?;
+    set_owner(inode_file.get_inode(), uid, gid, &credentials)?;
+    notify_file_changed(inode_file.get_abs_path(), DnotifyEvents::DN_ATTRIB);
+    Ok(())
+}
+
+fn do_chown_inode(
+    path: &str,
+    uid: Option<uid_t>,
+    gid: Option<gid_t>,
+    follow_symlink: bool,
+) -> Result<()> {
+    let (inode, abs_path, credentials) = {
+        let current_ref = process::get_current();
+        let current = current_ref.lock().unwrap();
+        let inode = if follow_symlink {
+            current.lookup_inode_follow(path)?
+        } else {
+            current.lookup_inode(path)?
+        };
+        sefs::check_not_read_only(&inode)?;
+        let credentials = current.get_credentials().lock().unwrap().clone();
+        (inode, current.convert_to_abs_path(path), credentials)
+    };
+    set_owner(&inode, uid, gid, &credentials)?;
+    notify_file_changed(&abs_path, DnotifyEvents::DN_ATTRIB);
+    Ok(())
+}
+
+/// Change the owner and the group of the inode, each of which is unchanged
+/// if None. Without CAP_CHOWN, the owner cannot be changed, and the group can
+/// only be changed by the owner to one of its groups.
+///
+/// Like Linux, a change clears the set-user-ID bit of a non-directory, and
+/// the set-group-ID bit too if it is group-executable.
+fn set_owner(
+    inode: &Arc<dyn INode>,
+    uid: Option<uid_t>,
+    gid: Option<gid_t>,
+    credentials: &Credentials,
+) -> Result<()> {
+    let mut info = inode.metadata()?;
+    if !credentials.has_capability(Capabilities::CHOWN) {
+        let is_owner = info.uid == credentials.euid() as usize;
+        let changes_uid = uid.map_or(false, |uid| uid as usize != info.uid);
+        let changes_gid_to_other = gid.map_or(false, |gid| {
+            gid as usize != info.gid && !credentials.is_in_group(gid)
+        });
+        if !is_owner || changes_uid || changes_gid_to_other {
+            return_errno!(EPERM, "the owner cannot be changed without privilege");
+        }
+    }
+    if uid.is_none() && gid.is_none() {
+        return Ok(());
+    }
+
+    info.uid = uid.map_or(info.uid, |uid| uid as usize);
+    info.gid = gid.map_or(info.gid, |gid| gid as usize);
+    if info.type_ != FileType::Dir {
+        info.mode &= !(StatMode::SET_UID.bits() as u16);
+        if info.mode & StatMode::GROUP_EXEC.bits() as u16 != 0 {
+            info.mode &= !(StatMode::SET_GID.bits() as u16);
+        }
+    }
+    inode.set_metadata(&info)?;
+    Ok(())
+}
+
+/// Make the new inode owned by the effective ids of the creator, given that
+/// the inodes are created as owned by root
+pub fn set_new_inode_owner(inode: &Arc<dyn INode>, credentials: &Credentials) -> Result<()> {
+    if credentials.euid() == 0 && credentials.egid() == 0 {
+        return Ok(());
+    }
+    let mut info = inode.metadata()?;
+    info.uid = credentials.euid() as usize;
+    info.gid = credentials.egid() as usize;
+    inode.set_metadata(&info)?;
+    Ok(())
+}
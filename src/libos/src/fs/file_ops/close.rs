@@ -6,6 +6,16 @@ pub fn do_close(fd: FileDesc) -> Result<()> {
     let current_process = current_ref.lock().unwrap();
     let file_table_ref = current_process.get_files();
     let mut file_table = file_table_ref.lock().unwrap();
-    file_table.del(fd)?;
+    let file = file_table.del(fd)?;
+    release_posix_locks_on_close(&file, current_process.get_pid());
     Ok(())
 }
+
+/// Closing any file descriptor of an inode, by close or by dup2 and dup3 over
+/// the file descriptor, releases all the POSIX locks of the process on the
+/// inode
+pub fn release_posix_locks_on_close(file: &FileRef, pid: pid_t) {
+    if let Ok(inode_file) = file.as_inode_file() {
+        inode_file.release_posix_locks(pid);
+    }
+}
@@ -1,4 +1,80 @@
+use super::super::hostfs;
 use super::*;
+use std::collections::HashMap;
+use std::sync::{SgxRwLockReadGuard, Weak};
+
+lazy_static! {
+    /// The locks of the entries of the directories, which serialize the
+    /// modifications of the entries of a directory and the snapshots of them.
+    /// The modifications, which may run concurrently, take the lock shared,
+    /// while a snapshot takes it exclusively. A lock is dropped from the table
+    /// once no one holds it.
+    static ref DIR_ENTRIES_LOCKS: SgxMutex<HashMap<InodeKey, Weak<SgxRwLock<()>>>> =
+        SgxMutex::new(HashMap::new());
+}
+
+/// The locks of the entries of the directories updated by an operation,
+/// i.e., create, unlink, link or rename, so that no snapshot of the entries is
+/// taken in the middle of it
+pub struct DirEntriesLocks(Vec<Arc<SgxRwLock<()>>>);
+
+impl DirEntriesLocks {
+    pub fn of(dir_inodes: &[&Arc<dyn INode>]) -> Result<DirEntriesLocks> {
+        let mut keys = dir_inodes
+            .iter()
+            .map(|dir_inode| InodeKey::new(dir_inode))
+            .collect::<Result<Vec<_>>>()?;
+        // The locks are always taken in the same order, e.g., by two renames
+        // between the same two directories in opposite directions
+        keys.sort();
+        keys.dedup();
+        Ok(DirEntriesLocks(
+            keys.into_iter().map(dir_entries_lock).collect(),
+        ))
+    }
+
+    pub fn lock_for_update(&self) -> Vec<SgxRwLockReadGuard<()>> {
+        self.0.iter().map(|lock| lock.read().unwrap()).collect()
+    }
+}
+
+fn dir_entries_lock(key: InodeKey) -> Arc<SgxRwLock<()>> {
+    let mut locks = DIR_ENTRIES_LOCKS.lock().unwrap();
+    if let Some(lock) = locks.get(&key).and_then(|lock| lock.upgrade()) {
+        return lock;
+    }
+    locks.retain(|_, lock| lock.upgrade().is_some());
+    let lock = Arc::new(SgxRwLock::new(()));
+    locks.insert(key, Arc::downgrade(&lock));
+    lock
+}
+
+/// Take a snapshot of the entries of a directory.
+///
+/// The entries are read by their indexes, which are shifted by a concurrent
+/// update. So the updates of the directory are held off until the snapshot is
+/// taken.
+///
+/// The entries of a directory of HostFS are listed at once, since reading an
+/// entry of it by the index reads the whole host directory, which makes the
+/// snapshot quadratic. Note that the updates made by the host to a directory
+/// of HostFS are not held off.
+pub fn snapshot_dir_entries(dir_inode: &Arc<dyn INode>) -> Result<Vec<String>> {
+    let lock = dir_entries_lock(InodeKey::new(dir_inode)?);
+    let _guard = lock.write().unwrap();
+    if let Some(entries) = hostfs::list_entries(dir_inode)? {
+        return Ok(entries);
+    }
+    let mut entries = Vec::new();
+    loop {
+        match dir_inode.get_entry(entries.len()) {
+            Ok(name) => entries.push(name),
+            Err(FsError::EntryNotFound) => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(entries)
+}
 
 #[repr(packed)] // Don't use 'C'. Or its size will align up to 8 bytes.
 struct LinuxDirent64 {
@@ -29,7 +105,7 @@ impl<'a> DirentBufWriter<'a> {
             written_size: 0,
         }
     }
-    fn try_write(&mut self, inode: u64, type_: u8, name: &str) -> Result<()> {
+    fn try_write(&mut self, inode: u64, offset: u64, type_: u8, name: &str) -> Result<()> {
         let len = ::core::mem::size_of::<LinuxDirent64>() + name.len() + 1;
         let len = (len + 7) / 8 * 8; // align up
         if self.rest_size < len {
@@ -37,7 +113,7 @@ impl<'a> DirentBufWriter<'a> {
         }
         let dent = LinuxDirent64 {
             ino: inode,
-            offset: 0,
+            offset,
             reclen: len as u16,
             type_,
             name: [],
@@ -86,8 +162,15 @@ pub fn do_getdents64(fd: FileDesc, buf: &mut [u8]) -> Result<usize> {
             }
             Ok(name) => name,
         };
-        // TODO: get ino from dirent
-        if let Err(e) = writer.try_write(0, 0, &name) {
+        // The offset of the entry is the cookie to seek to the next entry
+        let next_offset = file_ref.seek(SeekFrom::Current(0))?;
+        let ino = match file_ref.entry_ino(&name) {
+            Ok(ino) => ino,
+            // The entry is removed after the snapshot of the entries is taken
+            Err(e) if e.errno() == ENOENT => continue,
+            Err(e) => return Err(e),
+        };
+        if let Err(e) = writer.try_write(ino, next_offset as u64, 0, &name) {
             file_ref.seek(SeekFrom::Current(-1))?;
             if writer.written_size == 0 {
                 return Err(e);
@@ -1,3 +1,4 @@
+use super::close::release_posix_locks_on_close;
 use super::*;
 
 pub fn do_dup(old_fd: FileDesc) -> Result<FileDesc> {
@@ -6,7 +7,7 @@ pub fn do_dup(old_fd: FileDesc) -> Result<FileDesc> {
     let file_table_ref = current.get_files();
     let mut file_table = file_table_ref.lock().unwrap();
     let file = file_table.get(old_fd)?;
-    let new_fd = file_table.put(file, false);
+    let new_fd = file_table.put(file, false)?;
     Ok(new_fd)
 }
 
@@ -17,7 +18,9 @@ pub fn do_dup2(old_fd: FileDesc, new_fd: FileDesc) -> Result<FileDesc> {
     let mut file_table = file_table_ref.lock().unwrap();
     let file = file_table.get(old_fd)?;
     if old_fd != new_fd {
-        file_table.put_at(new_fd, file, false);
+        if let Some(old_file) = file_table.put_at(new_fd, file, false)? {
+            release_posix_locks_on_close(&old_file, current.get_pid());
+        }
     }
     Ok(new_fd)
 }
@@ -32,6 +35,9 @@ pub fn do_dup3(old_fd: FileDesc, new_fd: FileDesc, flags: u32) -> Result<FileDes
     if old_fd == new_fd {
         return_errno!(EINVAL, "old_fd must not be equal to new_fd");
     }
-    file_table.put_at(new_fd, file, creation_flags.must_close_on_spawn());
+    let close_on_spawn = creation_flags.must_close_on_spawn();
+    if let Some(old_file) = file_table.put_at(new_fd, file, close_on_spawn)? {
+        release_posix_locks_on_close(&old_file, current.get_pid());
+    }
     Ok(new_fd)
 }
@@ -0,0 +1,74 @@
+use super::*;
+
+bitflags! {
+    pub struct FallocateFlags: u32 {
+        /// Do not change the file size, even if the range is beyond the end
+        const FALLOC_FL_KEEP_SIZE = 0x01;
+        /// Deallocate the range, which must be given with FALLOC_FL_KEEP_SIZE
+        const FALLOC_FL_PUNCH_HOLE = 0x02;
+        const FALLOC_FL_COLLAPSE_RANGE = 0x08;
+        const FALLOC_FL_ZERO_RANGE = 0x10;
+        const FALLOC_FL_INSERT_RANGE = 0x20;
+        const FALLOC_FL_UNSHARE_RANGE = 0x40;
+    }
+}
+
+impl FallocateFlags {
+    pub fn from_u32(bits: u32) -> Result<FallocateFlags> {
+        let flags =
+            FallocateFlags::from_bits(bits).ok_or_else(|| errno!(EOPNOTSUPP, "unknown mode"))?;
+        if !(flags - (FallocateFlags::FALLOC_FL_KEEP_SIZE | FallocateFlags::FALLOC_FL_PUNCH_HOLE))
+            .is_empty()
+        {
+            return_errno!(EOPNOTSUPP, "the mode is not supported");
+        }
+        if flags.contains(FallocateFlags::FALLOC_FL_PUNCH_HOLE)
+            && !flags.contains(FallocateFlags::FALLOC_FL_KEEP_SIZE)
+        {
+            return_errno!(EOPNOTSUPP, "FALLOC_FL_PUNCH_HOLE needs FALLOC_FL_KEEP_SIZE");
+        }
+        Ok(flags)
+    }
+
+    /// Whether the file is extended if the range is beyond the end
+    pub fn may_extend(&self) -> bool {
+        !self.contains(FallocateFlags::FALLOC_FL_KEEP_SIZE)
+    }
+}
+
+/// Allocate or deallocate the space of `[offset, offset + len)` of a file.
+///
+/// The allocation is charged against the size limit of RamFS and, if the file
+/// is extended, RLIMIT_FSIZE, beyond which SIGXFSZ is raised.
+pub fn do_fallocate(fd: FileDesc, flags: FallocateFlags, offset: usize, len: usize) -> Result<()> {
+    info!(
+        "fallocate: fd: {}, flags: {:?}, offset: {}, len: {}",
+        fd, flags, offset, len
+    );
+    if len == 0 {
+        return_errno!(EINVAL, "len must be positive");
+    }
+    let end = offset
+        .checked_add(len)
+        .ok_or_else(|| errno!(EFBIG, "the range is too large"))?;
+    let current_ref = process::get_current();
+    let current_process = current_ref.lock().unwrap();
+    let file_ref = current_process.get_files().lock().unwrap().get(fd)?;
+    let inode_file = file_ref
+        .as_inode_file()
+        .map_err(|_| errno!(ENODEV, "not a regular file"))?;
+    let metadata = inode_file.metadata()?;
+    match metadata.type_ {
+        FileType::File => {}
+        FileType::Dir => return_errno!(EISDIR, "cannot fallocate a directory"),
+        _ => return_errno!(ENODEV, "not a regular file"),
+    }
+    if flags.may_extend() && end > metadata.size {
+        current_process
+            .get_rlimits()
+            .lock()
+            .unwrap()
+            .check_file_size(end)?;
+    }
+    inode_file.fallocate(flags, offset, len)
+}
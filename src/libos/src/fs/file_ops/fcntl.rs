@@ -1,5 +1,7 @@
+use super::change_notify::set_dnotify;
 use super::flock::flock;
 use super::*;
+use process::pid_t;
 use util::mem_util::from_user;
 
 #[derive(Debug)]
@@ -22,6 +24,44 @@ pub enum FcntlCmd<'a> {
     GetLk(&'a mut flock),
     /// Acquire or release a file lock
     SetLk(&'a flock),
+    /// As for `SetLk`, but wait for the conflicting locks to be released
+    SetLkw(&'a flock),
+    /// Test an open file description lock
+    OfdGetLk(&'a mut flock),
+    /// Acquire or release an open file description lock
+    OfdSetLk(&'a flock),
+    /// As for `OfdSetLk`, but wait for the conflicting locks to be released
+    OfdSetLkw(&'a flock),
+    /// Set or remove a file lease
+    SetLease(i32),
+    /// Get the type of the file lease
+    GetLease(),
+    /// Arm or disarm the notification of the changes in a directory
+    Notify(u32),
+}
+
+/// Linux-specific commands for open file description locks
+const F_OFD_GETLK: c_int = 36;
+const F_OFD_SETLK: c_int = 37;
+const F_OFD_SETLKW: c_int = 38;
+
+/// Linux-specific commands for file leases and directory notification
+const F_SETLEASE: c_int = 1024;
+const F_GETLEASE: c_int = 1025;
+const F_NOTIFY: c_int = 1026;
+
+bitflags! {
+    /// The events of directory notification (dnotify)
+    pub struct DnotifyEvents : u32 {
+        const DN_ACCESS = 0x00000001;
+        const DN_MODIFY = 0x00000002;
+        const DN_CREATE = 0x00000004;
+        const DN_DELETE = 0x00000008;
+        const DN_RENAME = 0x00000010;
+        const DN_ATTRIB = 0x00000020;
+        /// Keep the notification armed after an event
+        const DN_MULTISHOT = 0x80000000;
+    }
 }
 
 impl<'a> FcntlCmd<'a> {
@@ -34,25 +74,50 @@ impl<'a> FcntlCmd<'a> {
             libc::F_SETFD => FcntlCmd::SetFd(arg as u32),
             libc::F_GETFL => FcntlCmd::GetFl(),
             libc::F_SETFL => FcntlCmd::SetFl(arg as u32),
-            libc::F_GETLK => {
-                let flock_mut_ptr = arg as *mut flock;
-                from_user::check_mut_ptr(flock_mut_ptr)?;
-                let flock_mut_c = unsafe { &mut *flock_mut_ptr };
-                FcntlCmd::GetLk(flock_mut_c)
-            }
-            libc::F_SETLK => {
-                let flock_ptr = arg as *const flock;
-                from_user::check_ptr(flock_ptr)?;
-                let flock_c = unsafe { &*flock_ptr };
-                FcntlCmd::SetLk(flock_c)
-            }
+            libc::F_GETLK => FcntlCmd::GetLk(flock_mut_from_user(arg)?),
+            libc::F_SETLK => FcntlCmd::SetLk(flock_from_user(arg)?),
+            libc::F_SETLKW => FcntlCmd::SetLkw(flock_from_user(arg)?),
+            F_OFD_GETLK => FcntlCmd::OfdGetLk(flock_mut_from_user(arg)?),
+            F_OFD_SETLK => FcntlCmd::OfdSetLk(flock_from_user(arg)?),
+            F_OFD_SETLKW => FcntlCmd::OfdSetLkw(flock_from_user(arg)?),
+            F_SETLEASE => FcntlCmd::SetLease(arg as i32),
+            F_GETLEASE => FcntlCmd::GetLease(),
+            F_NOTIFY => FcntlCmd::Notify(arg as u32),
             _ => return_errno!(EINVAL, "unsupported command"),
         })
     }
+
+    fn is_lock_cmd(&self) -> bool {
+        match self {
+            FcntlCmd::GetLk(_)
+            | FcntlCmd::SetLk(_)
+            | FcntlCmd::SetLkw(_)
+            | FcntlCmd::OfdGetLk(_)
+            | FcntlCmd::OfdSetLk(_)
+            | FcntlCmd::OfdSetLkw(_) => true,
+            _ => false,
+        }
+    }
+}
+
+fn flock_from_user<'a>(arg: u64) -> Result<&'a flock> {
+    let flock_ptr = arg as *const flock;
+    from_user::check_ptr(flock_ptr)?;
+    Ok(unsafe { &*flock_ptr })
+}
+
+fn flock_mut_from_user<'a>(arg: u64) -> Result<&'a mut flock> {
+    let flock_mut_ptr = arg as *mut flock;
+    from_user::check_mut_ptr(flock_mut_ptr)?;
+    Ok(unsafe { &mut *flock_mut_ptr })
 }
 
 pub fn do_fcntl(fd: FileDesc, cmd: &mut FcntlCmd) -> Result<isize> {
     info!("fcntl: fd: {:?}, cmd: {:?}", &fd, cmd);
+    if cmd.is_lock_cmd() {
+        return do_fcntl_lock(fd, cmd);
+    }
+
     let current_ref = process::get_current();
     let mut current = current_ref.lock().unwrap();
     let file_table_ref = current.get_files();
@@ -89,25 +154,113 @@ pub fn do_fcntl(fd: FileDesc, cmd: &mut FcntlCmd) -> Result<isize> {
         FcntlCmd::SetFl(flags) => {
             let file = file_table.get(fd)?;
             let status_flags = StatusFlags::from_bits_truncate(*flags);
+            if status_flags.is_noatime() && !file.get_status_flags()?.is_noatime() {
+                if let Ok(inode_file) = file.as_inode_file() {
+                    let credentials = current.get_credentials().lock().unwrap();
+                    if !inode_file.get_inode().allow_noatime(&credentials)? {
+                        return_errno!(EPERM, "O_NOATIME is only allowed for the owner of the file");
+                    }
+                }
+            }
             file.set_status_flags(status_flags)?;
             0
         }
-        FcntlCmd::GetLk(flock_mut_c) => {
-            let file = file_table.get(fd)?;
-            let mut lock = Flock::from_c(*flock_mut_c)?;
-            if let FlockType::F_UNLCK = lock.l_type {
-                return_errno!(EINVAL, "invalid flock type for getlk");
+        FcntlCmd::SetLease(lease_type) => {
+            if *lease_type < 0 || *lease_type > u16::max_value() as i32 {
+                return_errno!(EINVAL, "invalid lease type");
             }
-            file.test_advisory_lock(&mut lock)?;
-            (*flock_mut_c).copy_from_safe(&lock);
+            let file = file_table.get(fd)?;
+            let lease_type = FlockType::from_u16(*lease_type as u16)?;
+            file.set_lease(lease_type, current.get_pid())?;
             0
         }
-        FcntlCmd::SetLk(flock_c) => {
+        FcntlCmd::GetLease() => {
+            let file = file_table.get(fd)?;
+            file.get_lease()? as isize
+        }
+        FcntlCmd::Notify(events) => {
             let file = file_table.get(fd)?;
-            let lock = Flock::from_c(*flock_c)?;
-            file.set_advisory_lock(&lock)?;
+            let is_dir = file
+                .metadata()
+                .map_or(false, |metadata| metadata.type_ == FileType::Dir);
+            if !is_dir {
+                return_errno!(EINVAL, "dnotify is only supported by directories");
+            }
+            let events = DnotifyEvents::from_bits(*events)
+                .ok_or_else(|| errno!(EINVAL, "unknown dnotify events"))?;
+            set_dnotify(&file, current.get_pid(), events)?;
             0
         }
+        _ => unreachable!(),
     };
     Ok(ret)
 }
+
+/// Do the lock commands, which may wait for the conflicting locks to be
+/// released. So the current process and its file table are not kept locked.
+fn do_fcntl_lock(fd: FileDesc, cmd: &mut FcntlCmd) -> Result<isize> {
+    let (file, pid) = {
+        let current_ref = process::get_current();
+        let current = current_ref.lock().unwrap();
+        let file = current.get_files().lock().unwrap().get(fd)?;
+        (file, current.get_pid())
+    };
+    match cmd {
+        FcntlCmd::GetLk(flock_mut_c) => {
+            test_lock(&file, flock_mut_c, FileLockKind::Posix, pid)?;
+        }
+        FcntlCmd::SetLk(flock_c) => {
+            set_lock(&file, flock_c, FileLockKind::Posix, false, pid)?;
+        }
+        FcntlCmd::SetLkw(flock_c) => {
+            set_lock(&file, flock_c, FileLockKind::Posix, true, pid)?;
+        }
+        FcntlCmd::OfdGetLk(flock_mut_c) => {
+            test_lock(&file, flock_mut_c, FileLockKind::Ofd, pid)?;
+        }
+        FcntlCmd::OfdSetLk(flock_c) => {
+            set_lock(&file, flock_c, FileLockKind::Ofd, false, pid)?;
+        }
+        FcntlCmd::OfdSetLkw(flock_c) => {
+            set_lock(&file, flock_c, FileLockKind::Ofd, true, pid)?;
+        }
+        _ => unreachable!(),
+    }
+    Ok(0)
+}
+
+fn test_lock(
+    file: &FileRef,
+    flock_mut_c: &mut flock,
+    kind: FileLockKind,
+    pid: pid_t,
+) -> Result<()> {
+    let mut lock = lock_from_c(flock_mut_c, kind, pid)?;
+    if let FlockType::F_UNLCK = lock.l_type {
+        return_errno!(EINVAL, "invalid flock type for getlk");
+    }
+    file.test_advisory_lock(&mut lock, kind)?;
+    flock_mut_c.copy_from_safe(&lock);
+    Ok(())
+}
+
+fn set_lock(
+    file: &FileRef,
+    flock_c: &flock,
+    kind: FileLockKind,
+    wait: bool,
+    pid: pid_t,
+) -> Result<()> {
+    let lock = lock_from_c(flock_c, kind, pid)?;
+    file.set_advisory_lock(&lock, kind, wait)
+}
+
+fn lock_from_c(flock_c: &flock, kind: FileLockKind, pid: pid_t) -> Result<Flock> {
+    // The pid must be zero for the OFD locks, which are not owned by processes
+    if kind == FileLockKind::Ofd && flock_c.l_pid != 0 {
+        return_errno!(EINVAL, "the pid of an OFD lock must be zero");
+    }
+    let mut lock = Flock::from_c(flock_c)?;
+    lock.l_pid = pid;
+    Ok(lock)
+}
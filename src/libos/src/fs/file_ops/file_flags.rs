@@ -55,7 +55,9 @@ bitflags! {
         /// close on exec
         const O_CLOEXEC = 1 << 19;
         /// create an unnamed temporary regular file
-        const _O_TMPFILE = 1 << 22;
+        const O_TMPFILE = 1 << 22;
+        /// create an integrity-only file on an encrypted SEFS (Occlum-specific)
+        const O_INTEGRITY_ONLY = 1 << 24;
     }
 }
 
@@ -71,6 +73,22 @@ impl CreationFlags {
     pub fn is_exclusive(&self) -> bool {
         self.contains(CreationFlags::O_EXCL)
     }
+
+    pub fn is_tmpfile(&self) -> bool {
+        self.contains(CreationFlags::O_TMPFILE)
+    }
+
+    pub fn is_integrity_only(&self) -> bool {
+        self.contains(CreationFlags::O_INTEGRITY_ONLY)
+    }
+
+    pub fn must_be_dir(&self) -> bool {
+        self.contains(CreationFlags::O_DIRECTORY)
+    }
+
+    pub fn no_follow_symlink(&self) -> bool {
+        self.contains(CreationFlags::O_NOFOLLOW)
+    }
 }
 
 bitflags! {
@@ -99,4 +117,26 @@ impl StatusFlags {
     pub fn always_append(&self) -> bool {
         self.contains(StatusFlags::O_APPEND)
     }
+
+    pub fn is_direct(&self) -> bool {
+        self.contains(StatusFlags::O_DIRECT)
+    }
+
+    /// Whether each write syncs the data, which is also set by O_SYNC
+    pub fn is_dsync(&self) -> bool {
+        self.contains(StatusFlags::O_DSYNC)
+    }
+
+    /// Whether each write syncs the data and the metadata, i.e., O_SYNC
+    pub fn is_sync(&self) -> bool {
+        self.contains(StatusFlags::_O_SYNC)
+    }
+
+    pub fn is_noatime(&self) -> bool {
+        self.contains(StatusFlags::O_NOATIME)
+    }
+
+    pub fn is_path_only(&self) -> bool {
+        self.contains(StatusFlags::O_PATH)
+    }
 }
@@ -0,0 +1,227 @@
+/// File handles, i.e., name_to_handle_at and open_by_handle_at
+use super::*;
+use process::pid_t;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// The type of the handles, which encode a 64-bit inode number and a generation
+pub const FILEID_INO64_GEN: i32 = 0x81;
+/// The maximum size of a handle, as defined by Linux
+pub const MAX_HANDLE_SZ: usize = 128;
+
+/// Since open_by_handle_at bypasses the path-based permission checks, only the
+/// privileged process, i.e., the init process started by `occlum run`, and its
+/// threads are allowed to use it.
+const PRIVILEGED_PID: pid_t = 1;
+
+bitflags! {
+    pub struct NameToHandleFlags : u32 {
+        const AT_SYMLINK_FOLLOW = 0x400;
+        const AT_EMPTY_PATH = 0x1000;
+    }
+}
+
+impl NameToHandleFlags {
+    pub fn from_u32(raw: u32) -> Result<Self> {
+        let flags =
+            Self::from_bits(raw).ok_or_else(|| errno!(EINVAL, "invalid flags for handle"))?;
+        Ok(flags)
+    }
+}
+
+/// The opaque content of a file handle.
+///
+/// The inode number identifies the file in its mount, while the generation
+/// tells the inode apart from a deleted inode of the same number.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct FileHandle {
+    ino: u64,
+    generation: u64,
+}
+
+lazy_static! {
+    /// The inodes which handles have been given out for, indexed by the mount
+    /// IDs and the inode numbers
+    static ref HANDLE_TABLE: SgxMutex<BTreeMap<(i32, usize), HandleEntry>> =
+        SgxMutex::new(BTreeMap::new());
+}
+
+#[derive(Debug)]
+struct HandleEntry {
+    generation: u64,
+    // The current path of the inode, or None if the inode is deleted
+    abs_path: Option<String>,
+}
+
+pub fn do_name_to_handle_at(
+    dirfd: Option<FileDesc>,
+    path: &str,
+    flags: NameToHandleFlags,
+) -> Result<(FileHandle, i32)> {
+    info!(
+        "name_to_handle_at: dirfd: {:?}, path: {:?}, flags: {:?}",
+        dirfd, path, flags
+    );
+    let current_ref = process::get_current();
+    let current_process = current_ref.lock().unwrap();
+    let (inode, abs_path) = if path.is_empty() {
+        if !flags.contains(NameToHandleFlags::AT_EMPTY_PATH) {
+            return_errno!(ENOENT, "path is an empty string");
+        }
+        let fd = dirfd.ok_or_else(|| errno!(EBADF, "dirfd is not an fd"))?;
+        let file_ref = current_process.get_files().lock().unwrap().get(fd)?;
+        let inode_file = file_ref.as_inode_file()?;
+        (
+            inode_file.get_inode().clone(),
+            inode_file.get_abs_path().to_owned(),
+        )
+    } else {
+        if dirfd.is_some() && !path.starts_with('/') {
+            // TODO: handle dirfd
+            return_errno!(ENOSYS, "cannot accept dirfd");
+        }
+        let inode = if flags.contains(NameToHandleFlags::AT_SYMLINK_FOLLOW) {
+            current_process.lookup_inode_follow(path)?
+        } else {
+            current_process.lookup_inode(path)?
+        };
+        (inode, current_process.convert_to_abs_path(path))
+    };
+
+    let mount_id = mount_id_of(&abs_path)?;
+    let ino = inode.metadata()?.inode;
+    let mut table = HANDLE_TABLE.lock().unwrap();
+    let entry = table.entry((mount_id, ino)).or_insert(HandleEntry {
+        generation: 0,
+        abs_path: None,
+    });
+    entry.abs_path = Some(abs_path);
+    let handle = FileHandle {
+        ino: ino as u64,
+        generation: entry.generation,
+    };
+    Ok((handle, mount_id))
+}
+
+pub fn do_open_by_handle_at(
+    mount_fd: Option<FileDesc>,
+    handle: &FileHandle,
+    flags: u32,
+) -> Result<FileDesc> {
+    info!(
+        "open_by_handle_at: mount_fd: {:?}, handle: {:?}, flags: {:#o}",
+        mount_fd, handle, flags
+    );
+    let current_ref = process::get_current();
+    let current_process = current_ref.lock().unwrap();
+    if current_process.get_pid() != PRIVILEGED_PID {
+        return_errno!(
+            EPERM,
+            "open_by_handle_at is only allowed for the privileged process"
+        );
+    }
+
+    let mount_path = match mount_fd {
+        Some(fd) => {
+            let file_ref = current_process.get_files().lock().unwrap().get(fd)?;
+            let inode_file = file_ref.as_inode_file()?;
+            inode_file.get_abs_path().to_owned()
+        }
+        None => current_process.get_cwd_path(),
+    };
+    let mount_id = mount_id_of(&mount_path)?;
+    let abs_path = {
+        let table = HANDLE_TABLE.lock().unwrap();
+        match table.get(&(mount_id, handle.ino as usize)) {
+            Some(entry) if entry.generation == handle.generation => entry.abs_path.clone(),
+            _ => None,
+        }
+    }
+    .ok_or_else(|| errno!(ESTALE, "the file handle is stale"))?;
+
+    // Make sure the path still refers to the inode of the handle
+    let is_same_inode = current_process
+        .lookup_inode(&abs_path)
+        .and_then(|inode| Ok(inode.metadata()?.inode as u64 == handle.ino))
+        .unwrap_or(false);
+    if !is_same_inode {
+        return_errno!(ESTALE, "the file handle is stale");
+    }
+
+    let creation_flags = CreationFlags::from_bits_truncate(flags);
+    if creation_flags.can_create() {
+        return_errno!(EINVAL, "O_CREAT is not allowed");
+    }
+    let pending_open = current_process.prepare_open(&abs_path, flags, 0)?;
+    // The process is not kept locked while finishing the open, see do_open
+    drop(current_process);
+    let file = pending_open.finish()?;
+    let file_ref: Arc<Box<dyn File>> = Arc::new(file);
+    let fd = current_ref
+        .lock()
+        .unwrap()
+        .get_files()
+        .lock()
+        .unwrap()
+        .put(file_ref, creation_flags.must_close_on_spawn())?;
+    Ok(fd)
+}
+
+/// Invalidate the handles of an inode whose entry at the path is removed.
+///
+/// The handles stay valid as long as the inode has other links.
+pub fn invalidate_handles(abs_path: &str, metadata: &Metadata) {
+    if metadata.type_ != FileType::Dir && metadata.nlinks > 1 {
+        return;
+    }
+    let mount_id = match mount_id_of(abs_path) {
+        Ok(mount_id) => mount_id,
+        Err(_) => return,
+    };
+    let mut table = HANDLE_TABLE.lock().unwrap();
+    if let Some(entry) = table.get_mut(&(mount_id, metadata.inode)) {
+        entry.generation += 1;
+        entry.abs_path = None;
+    }
+}
+
+/// Update the paths of the inodes with handles after a rename
+pub fn rename_handles(old_abs_path: &str, new_abs_path: &str) {
+    let mount_id = match mount_id_of(old_abs_path) {
+        Ok(mount_id) => mount_id,
+        Err(_) => return,
+    };
+    let mut table = HANDLE_TABLE.lock().unwrap();
+    for ((entry_mount_id, _), entry) in table.iter_mut() {
+        if *entry_mount_id != mount_id {
+            continue;
+        }
+        let new_path = match &entry.abs_path {
+            Some(path) => match Path::new(path).strip_prefix(old_abs_path) {
+                Ok(suffix) => Path::new(new_abs_path)
+                    .join(suffix)
+                    .to_string_lossy()
+                    .trim_end_matches('/')
+                    .to_owned(),
+                Err(_) => continue,
+            },
+            None => continue,
+        };
+        entry.abs_path = Some(new_path);
+    }
+}
+
+/// Get the ID of the innermost mount that contains the path, which is the
+/// index of the mount in the config
+fn mount_id_of(abs_path: &str) -> Result<i32> {
+    let path = Path::new(abs_path);
+    config::LIBOS_CONFIG
+        .mount
+        .iter()
+        .enumerate()
+        .filter(|(_, mount)| path.starts_with(&mount.target))
+        .max_by_key(|(_, mount)| mount.target.components().count())
+        .map(|(mount_id, _)| mount_id as i32)
+        .ok_or_else(|| errno!(ENOENT, "the path is not on any mounted file system"))
+}
@@ -0,0 +1,472 @@
+/// File locks, i.e., the record locks of fcntl (POSIX and OFD locks) and the
+/// whole-file locks of flock
+use super::lease;
+use super::*;
+use process::pid_t;
+use std::collections::BTreeMap;
+use util::waiter::{self, WaiterQueue};
+
+/// The operations of flock
+const LOCK_SH: u32 = 1;
+const LOCK_EX: u32 = 2;
+const LOCK_NB: u32 = 4;
+const LOCK_UN: u32 = 8;
+
+/// The max number of the processes in a cycle of the waits for the POSIX
+/// locks that is detected as a deadlock, like Linux
+const MAX_DEADLOCK_DEPTH: usize = 10;
+
+/// The end of a lock that extends to the end of the file, however large the
+/// file grows
+const OFFSET_MAX: usize = usize::max_value();
+
+lazy_static! {
+    static ref LOCK_TABLE: SgxMutex<BTreeMap<InodeKey, InodeLocks>> =
+        SgxMutex::new(BTreeMap::new());
+    // The POSIX locks being waited for, by the tids of the waiting threads,
+    // which are only accessed with the lock table locked
+    static ref BLOCKED_POSIX_LOCKS: SgxMutex<BTreeMap<pid_t, (InodeKey, FileLock)>> =
+        SgxMutex::new(BTreeMap::new());
+    // The threads waiting for the locks, which are woken whenever any lock is
+    // released or changed
+    static ref LOCK_WAITERS: WaiterQueue = WaiterQueue::new();
+}
+
+/// Identifies an inode by its file system and its inode number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InodeKey {
+    fs: usize,
+    ino: usize,
+}
+
+impl InodeKey {
+    pub fn new(inode: &Arc<dyn INode>) -> Result<InodeKey> {
+        let fs = &*inode.fs() as *const dyn FileSystem as *const u8 as usize;
+        let ino = inode.metadata()?.inode;
+        Ok(InodeKey { fs, ino })
+    }
+
+    pub fn ino(&self) -> usize {
+        self.ino
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileLockKind {
+    /// The record locks owned by a process, i.e., F_SETLK of fcntl
+    Posix,
+    /// The record locks owned by an open file, i.e., F_OFD_SETLK of fcntl
+    Ofd,
+    /// The whole-file locks owned by an open file, i.e., flock
+    Flock,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LockOwner {
+    Process(pid_t),
+    File(usize),
+}
+
+#[derive(Debug, Clone)]
+struct FileLock {
+    kind: FileLockKind,
+    owner: LockOwner,
+    // The process that placed the lock
+    pid: pid_t,
+    type_: FlockType,
+    // The locked range is [start, end)
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug, Default)]
+struct InodeLocks {
+    dev: usize,
+    locks: Vec<FileLock>,
+}
+
+impl FileLock {
+    fn new(
+        kind: FileLockKind,
+        file_id: usize,
+        lock: &Flock,
+        offset: usize,
+        inode: &Arc<dyn INode>,
+    ) -> Result<FileLock> {
+        let owner = match kind {
+            FileLockKind::Posix => LockOwner::Process(lock.l_pid),
+            FileLockKind::Ofd | FileLockKind::Flock => LockOwner::File(file_id),
+        };
+        let (start, end) = {
+            let base = match lock.l_whence {
+                FlockWhence::SEEK_SET => 0,
+                FlockWhence::SEEK_CUR => offset as off_t,
+                FlockWhence::SEEK_END => inode.metadata()?.size as off_t,
+            };
+            let start = base
+                .checked_add(lock.l_start)
+                .ok_or_else(|| errno!(EOVERFLOW, "the start of the lock overflows"))?;
+            let (start, end) = if lock.l_len > 0 {
+                let end = start
+                    .checked_add(lock.l_len)
+                    .ok_or_else(|| errno!(EOVERFLOW, "the end of the lock overflows"))?;
+                (start, end as usize)
+            } else if lock.l_len < 0 {
+                (start + lock.l_len, start as usize)
+            } else {
+                (start, OFFSET_MAX)
+            };
+            if start < 0 {
+                return_errno!(EINVAL, "the start of the lock is negative");
+            }
+            (start as usize, end)
+        };
+        Ok(FileLock {
+            kind,
+            owner,
+            pid: lock.l_pid,
+            type_: lock.l_type,
+            start,
+            end,
+        })
+    }
+
+    // The record locks and the flock locks do not interact with each other
+    fn is_in_same_space(&self, other: &FileLock) -> bool {
+        (self.kind == FileLockKind::Flock) == (other.kind == FileLockKind::Flock)
+    }
+
+    fn overlaps(&self, other: &FileLock) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+
+    fn conflicts_with(&self, other: &FileLock) -> bool {
+        self.is_in_same_space(other)
+            && self.owner != other.owner
+            && self.overlaps(other)
+            && (self.type_ == FlockType::F_WRLCK || other.type_ == FlockType::F_WRLCK)
+    }
+
+    // Whether the lock is replaced by the other lock of the same owner
+    fn is_covered_by(&self, other: &FileLock) -> bool {
+        self.is_in_same_space(other) && self.owner == other.owner && self.overlaps(other)
+    }
+}
+
+impl InodeLocks {
+    // Remove the range of the lock from the locks of the same owner, so that
+    // the locks are split if necessary
+    fn remove_range_of(&mut self, lock: &FileLock) {
+        let mut remaining_locks = Vec::new();
+        for old_lock in self.locks.drain(..) {
+            if !old_lock.is_covered_by(lock) {
+                remaining_locks.push(old_lock);
+                continue;
+            }
+            if old_lock.start < lock.start {
+                let mut head = old_lock.clone();
+                head.end = lock.start;
+                remaining_locks.push(head);
+            }
+            if old_lock.end > lock.end {
+                let mut tail = old_lock;
+                tail.start = lock.end;
+                remaining_locks.push(tail);
+            }
+        }
+        self.locks = remaining_locks;
+    }
+
+    // Insert the lock, merging it with the adjacent locks of the same owner
+    // and the same type
+    fn insert(&mut self, mut lock: FileLock) {
+        self.remove_range_of(&lock);
+        let (adjacent_locks, other_locks): (Vec<FileLock>, Vec<FileLock>) =
+            self.locks.drain(..).partition(|old_lock| {
+                old_lock.is_in_same_space(&lock)
+                    && old_lock.owner == lock.owner
+                    && old_lock.type_ == lock.type_
+                    && (old_lock.end == lock.start || old_lock.start == lock.end)
+            });
+        for adjacent_lock in adjacent_locks {
+            lock.start = lock.start.min(adjacent_lock.start);
+            lock.end = lock.end.max(adjacent_lock.end);
+        }
+        self.locks = other_locks;
+        self.locks.push(lock);
+    }
+}
+
+/// Test whether the lock could be placed. If not, the lock is changed to be
+/// one of the conflicting locks. Otherwise, its type is set to F_UNLCK.
+pub fn test_file_lock(
+    inode: &Arc<dyn INode>,
+    kind: FileLockKind,
+    file_id: usize,
+    offset: usize,
+    lock: &mut Flock,
+) -> Result<()> {
+    let key = InodeKey::new(inode)?;
+    let new_lock = FileLock::new(kind, file_id, lock, offset, inode)?;
+    let table = LOCK_TABLE.lock().unwrap();
+    let conflicting_lock = table.get(&key).and_then(|inode_locks| {
+        inode_locks
+            .locks
+            .iter()
+            .find(|old_lock| old_lock.conflicts_with(&new_lock))
+    });
+    match conflicting_lock {
+        Some(conflicting_lock) => {
+            lock.l_type = conflicting_lock.type_;
+            lock.l_whence = FlockWhence::SEEK_SET;
+            lock.l_start = conflicting_lock.start as off_t;
+            lock.l_len = if conflicting_lock.end == OFFSET_MAX {
+                0
+            } else {
+                (conflicting_lock.end - conflicting_lock.start) as off_t
+            };
+            // The OFD locks are not owned by any process
+            lock.l_pid = match conflicting_lock.kind {
+                FileLockKind::Ofd => -1i32 as pid_t,
+                _ => conflicting_lock.pid,
+            };
+        }
+        None => lock.l_type = FlockType::F_UNLCK,
+    }
+    Ok(())
+}
+
+/// Acquire or release the lock.
+///
+/// If the lock conflicts with the locks held by others, it waits until the
+/// conflicting locks are released if `wait` is true, or fails with EAGAIN.
+/// Waiting for a POSIX lock fails with EDEADLK if it would deadlock. The wait
+/// fails with EINTR if a signal interrupts it, and is no longer taken as
+/// blocked in the deadlock detection then.
+pub fn set_file_lock(
+    inode: &Arc<dyn INode>,
+    kind: FileLockKind,
+    file_id: usize,
+    offset: usize,
+    lock: &Flock,
+    wait: bool,
+) -> Result<()> {
+    let key = InodeKey::new(inode)?;
+    let dev = inode.metadata()?.dev;
+    let new_lock = FileLock::new(kind, file_id, lock, offset, inode)?;
+    if new_lock.type_ == FlockType::F_UNLCK {
+        {
+            let mut table = LOCK_TABLE.lock().unwrap();
+            if let Some(inode_locks) = table.get_mut(&key) {
+                inode_locks.remove_range_of(&new_lock);
+                if inode_locks.locks.is_empty() {
+                    table.remove(&key);
+                }
+            }
+        }
+        LOCK_WAITERS.dequeue_and_wake_all();
+        return Ok(());
+    }
+
+    let tid = process::do_gettid();
+    let ret = waiter::wait_until(&[&LOCK_WAITERS], None, || {
+        let mut table = LOCK_TABLE.lock().unwrap();
+        let mut blocked_locks = BLOCKED_POSIX_LOCKS.lock().unwrap();
+        let has_conflicts = table.get(&key).map_or(false, |inode_locks| {
+            inode_locks
+                .locks
+                .iter()
+                .any(|old_lock| old_lock.conflicts_with(&new_lock))
+        });
+        if !has_conflicts {
+            let inode_locks = table.entry(key).or_default();
+            inode_locks.dev = dev;
+            inode_locks.insert(new_lock.clone());
+            return Ok(Some(()));
+        }
+        if !wait {
+            return_errno!(EAGAIN, "the lock is held by others");
+        }
+        if new_lock.kind == FileLockKind::Posix {
+            if would_deadlock(&table, &blocked_locks, &key, &new_lock) {
+                return_errno!(EDEADLK, "the lock would deadlock");
+            }
+            blocked_locks.insert(tid, (key, new_lock.clone()));
+        }
+        Ok(None)
+    });
+    if new_lock.kind == FileLockKind::Posix {
+        let _table = LOCK_TABLE.lock().unwrap();
+        BLOCKED_POSIX_LOCKS.lock().unwrap().remove(&tid);
+    }
+    if ret.is_ok() {
+        // The lock may replace the locks of the owner that others wait for
+        LOCK_WAITERS.dequeue_and_wake_all();
+    }
+    ret
+}
+
+/// Whether waiting for the POSIX lock would deadlock, i.e., the owner of a
+/// lock that blocks it is waiting, directly or through the other waiting
+/// processes, for a lock that is blocked by the lock owner itself
+fn would_deadlock(
+    table: &BTreeMap<InodeKey, InodeLocks>,
+    blocked_locks: &BTreeMap<pid_t, (InodeKey, FileLock)>,
+    key: &InodeKey,
+    lock: &FileLock,
+) -> bool {
+    let mut waiting = (*key, lock.clone());
+    for _ in 0..MAX_DEADLOCK_DEPTH {
+        let blocker = table.get(&waiting.0).and_then(|inode_locks| {
+            inode_locks
+                .locks
+                .iter()
+                .find(|old_lock| old_lock.conflicts_with(&waiting.1))
+        });
+        let blocker_owner = match blocker {
+            Some(blocker) if blocker.kind == FileLockKind::Posix => blocker.owner,
+            _ => return false,
+        };
+        if blocker_owner == lock.owner {
+            return true;
+        }
+        waiting = match blocked_locks
+            .values()
+            .find(|(_, blocked_lock)| blocked_lock.owner == blocker_owner)
+        {
+            Some(blocked) => blocked.clone(),
+            None => return false,
+        };
+    }
+    false
+}
+
+pub fn do_flock(fd: FileDesc, operation: u32) -> Result<()> {
+    info!("flock: fd: {}, operation: {:#x}", fd, operation);
+    let (file, pid) = {
+        let current_ref = process::get_current();
+        let current = current_ref.lock().unwrap();
+        let file = current.get_files().lock().unwrap().get(fd)?;
+        (file, current.get_pid())
+    };
+    let l_type = match operation & !LOCK_NB {
+        LOCK_SH => FlockType::F_RDLCK,
+        LOCK_EX => FlockType::F_WRLCK,
+        LOCK_UN => FlockType::F_UNLCK,
+        _ => return_errno!(EINVAL, "invalid flock operation"),
+    };
+    let lock = Flock {
+        l_type,
+        l_whence: FlockWhence::SEEK_SET,
+        l_start: 0,
+        l_len: 0,
+        l_pid: pid,
+    };
+    let wait = operation & LOCK_NB == 0;
+    file.set_advisory_lock(&lock, FileLockKind::Flock, wait)
+}
+
+/// Release the OFD and flock locks owned by an open file, which is closed
+pub fn release_file_locks(inode: &Arc<dyn INode>, file_id: usize) {
+    release_locks_if(Some(inode), |lock| lock.owner == LockOwner::File(file_id));
+}
+
+/// Release the POSIX locks of a process on the inode, which are released when
+/// any file descriptor of the inode is closed by the process
+pub fn release_posix_locks(inode: &Arc<dyn INode>, pid: pid_t) {
+    release_locks_if(Some(inode), |lock| lock.owner == LockOwner::Process(pid));
+}
+
+/// Release all the POSIX locks of a process, which exits
+pub fn release_all_posix_locks(pid: pid_t) {
+    release_locks_if(None, |lock| lock.owner == LockOwner::Process(pid));
+}
+
+fn release_locks_if<F>(inode: Option<&Arc<dyn INode>>, should_release: F)
+where
+    F: Fn(&FileLock) -> bool,
+{
+    let mut table = LOCK_TABLE.lock().unwrap();
+    match inode {
+        Some(inode) => {
+            let key = match InodeKey::new(inode) {
+                Ok(key) => key,
+                Err(_) => return,
+            };
+            if let Some(inode_locks) = table.get_mut(&key) {
+                inode_locks.locks.retain(|lock| !should_release(lock));
+            }
+        }
+        None => {
+            for inode_locks in table.values_mut() {
+                inode_locks.locks.retain(|lock| !should_release(lock));
+            }
+        }
+    }
+    table.retain(|_, inode_locks| !inode_locks.locks.is_empty());
+    drop(table);
+    LOCK_WAITERS.dequeue_and_wake_all();
+}
+
+/// Format the active locks and leases like /proc/locks of Linux
+pub fn format_proc_locks() -> String {
+    let mut lines = Vec::new();
+    {
+        let table = LOCK_TABLE.lock().unwrap();
+        for (key, inode_locks) in table.iter() {
+            for lock in inode_locks.locks.iter() {
+                let class = match lock.kind {
+                    FileLockKind::Posix => "POSIX  ADVISORY ",
+                    FileLockKind::Ofd => "OFDLCK ADVISORY ",
+                    FileLockKind::Flock => "FLOCK  ADVISORY ",
+                };
+                // The OFD locks are not owned by any process
+                let pid = match lock.kind {
+                    FileLockKind::Ofd => -1,
+                    _ => lock.pid as i64,
+                };
+                let range = match (lock.kind, lock.end) {
+                    (FileLockKind::Flock, _) => "0 EOF".to_string(),
+                    (_, OFFSET_MAX) => format!("{} EOF", lock.start),
+                    (_, end) => format!("{} {}", lock.start, end - 1),
+                };
+                lines.push(format_proc_lock_line(
+                    class,
+                    lock.type_,
+                    pid,
+                    inode_locks.dev,
+                    key.ino(),
+                    &range,
+                ));
+            }
+        }
+    }
+    lines.extend(lease::format_proc_leases());
+
+    let mut proc_locks = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        proc_locks += &format!("{}: {}\n", i + 1, line);
+    }
+    proc_locks
+}
+
+/// Format a line of /proc/locks without the leading lock number
+pub fn format_proc_lock_line(
+    class: &str,
+    type_: FlockType,
+    pid: i64,
+    dev: usize,
+    ino: usize,
+    range: &str,
+) -> String {
+    let type_str = match type_ {
+        FlockType::F_RDLCK => "READ ",
+        FlockType::F_WRLCK => "WRITE",
+        FlockType::F_UNLCK => "UNLCK",
+    };
+    let (major, minor) = (dev >> 20, dev & 0xfffff);
+    format!(
+        "{} {} {} {:02x}:{:02x}:{} {}",
+        class, type_str, pid, major, minor, ino, range
+    )
+}
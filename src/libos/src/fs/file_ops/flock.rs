@@ -48,7 +48,7 @@ impl Flock {
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 #[repr(u16)]
 pub enum FlockType {
     F_RDLCK = 0,
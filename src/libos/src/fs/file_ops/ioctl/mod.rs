@@ -37,6 +37,8 @@ impl_ioctl_nums_and_cmds! {
     TIOCNOTTY => (0x5422, ()),
     // Get the number of bytes in the input buffer
     FIONREAD => (0x541B, mut i32),
+    // Get whether the socket is at the mark of the out-of-band data
+    SIOCATMARK => (0x8905, mut i32),
 }
 
 /// This is the centralized place to add sanity checks for the argument values
@@ -58,6 +60,11 @@ impl<'a> IoctlCmd<'a> {
                     return_errno!(EINVAL, "invalid data from host");
                 }
             }
+            IoctlCmd::SIOCATMARK(at_mark_ref) => {
+                if **at_mark_ref != 0 && **at_mark_ref != 1 {
+                    return_errno!(EINVAL, "invalid data from host");
+                }
+            }
             _ => {}
         }
         Ok(())
@@ -0,0 +1,309 @@
+/// File leases, i.e., F_SETLEASE and F_GETLEASE of fcntl
+use super::file_lock::{format_proc_lock_line, InodeKey};
+use super::*;
+use process::pid_t;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use time::{timespec_t, ClockID};
+
+/// The time given to a lease holder to release or downgrade its lease, after
+/// which the lease is broken forcibly. This is the default value of
+/// /proc/sys/fs/lease-break-time on Linux.
+const LEASE_BREAK_TIME: Duration = Duration::from_secs(45);
+/// The interval to check whether a lease being broken has been released
+const LEASE_BREAK_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+lazy_static! {
+    static ref LEASE_TABLE: SgxMutex<BTreeMap<InodeKey, InodeLeases>> =
+        SgxMutex::new(BTreeMap::new());
+}
+
+/// An open of an inode, which is taken into account by leases.
+///
+/// Every INodeFile holds a `LeaseRef`. It is also the owner of the lease set on
+/// the INodeFile, which is released when the INodeFile is closed.
+#[derive(Debug)]
+pub struct LeaseRef {
+    key: InodeKey,
+    id: usize,
+    writable: bool,
+}
+
+#[derive(Debug, Default)]
+struct InodeLeases {
+    dev: usize,
+    opens: usize,
+    writers: usize,
+    leases: Vec<Lease>,
+}
+
+#[derive(Debug)]
+struct Lease {
+    owner: usize,
+    pid: pid_t,
+    type_: FlockType,
+    breaking: Option<LeaseBreak>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LeaseBreak {
+    target: FlockType,
+    deadline: Duration,
+}
+
+impl Lease {
+    fn conflicts_with(&self, writable: bool) -> bool {
+        writable || self.type_ == FlockType::F_WRLCK
+    }
+}
+
+impl LeaseRef {
+    /// Open an inode. The conflicting leases held by others are broken first.
+    ///
+    /// A conflicting lease is one of write lease, or any lease if the inode is
+    /// opened for writing. The open is blocked until the lease holders release
+    /// or downgrade their leases, or the lease break time elapses. If the open
+    /// is non-blocking, it fails with EWOULDBLOCK instead.
+    pub fn new(inode: &Arc<dyn INode>, writable: bool, nonblocking: bool) -> Result<LeaseRef> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+        let key = InodeKey::new(inode)?;
+        let dev = inode.metadata()?.dev;
+        // The open is counted in the same critical section where the leases
+        // are found broken, so that no new lease can be set in between
+        break_leases(&key, writable, nonblocking, |table| {
+            let inode_leases = table.entry(key).or_default();
+            inode_leases.dev = dev;
+            inode_leases.opens += 1;
+            if writable {
+                inode_leases.writers += 1;
+            }
+        })?;
+        Ok(LeaseRef {
+            key,
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            writable,
+        })
+    }
+
+    /// Break the leases on the inode for a truncate by path
+    pub fn break_for_truncate(inode: &Arc<dyn INode>) -> Result<()> {
+        let key = InodeKey::new(inode)?;
+        break_leases(&key, true, false, |_| {})
+    }
+
+    /// The key of the inode opened
+    pub fn key(&self) -> &InodeKey {
+        &self.key
+    }
+
+    /// The unique ID of the open, which also identifies the owner of the
+    /// locks that belong to the open file, i.e., OFD locks and flock locks
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn set_lease(&self, type_: FlockType, pid: pid_t) -> Result<()> {
+        let mut table = LEASE_TABLE.lock().unwrap();
+        let inode_leases = table.get_mut(&self.key).unwrap();
+        let own_lease_idx = inode_leases
+            .leases
+            .iter()
+            .position(|lease| lease.owner == self.id);
+
+        match type_ {
+            FlockType::F_UNLCK => {
+                if let Some(idx) = own_lease_idx {
+                    inode_leases.leases.remove(idx);
+                }
+                return Ok(());
+            }
+            FlockType::F_RDLCK => {
+                if inode_leases.writers > 0 {
+                    return_errno!(EAGAIN, "the file is opened for writing");
+                }
+            }
+            FlockType::F_WRLCK => {
+                if inode_leases.opens > 1 {
+                    return_errno!(EAGAIN, "the file is opened by others");
+                }
+            }
+        }
+
+        match own_lease_idx {
+            Some(idx) => {
+                let lease = &mut inode_leases.leases[idx];
+                if let Some(lease_break) = lease.breaking {
+                    if type_ == FlockType::F_WRLCK {
+                        return_errno!(EAGAIN, "the lease is being broken");
+                    }
+                    if type_ == lease_break.target {
+                        lease.breaking = None;
+                    }
+                }
+                lease.type_ = type_;
+                lease.pid = pid;
+            }
+            None => inode_leases.leases.push(Lease {
+                owner: self.id,
+                pid,
+                type_,
+                breaking: None,
+            }),
+        }
+        Ok(())
+    }
+
+    /// Get the type of the lease. If the lease is being broken, the type that
+    /// the lease will be downgraded to is returned.
+    pub fn get_lease(&self) -> FlockType {
+        let table = LEASE_TABLE.lock().unwrap();
+        table
+            .get(&self.key)
+            .and_then(|inode_leases| {
+                inode_leases
+                    .leases
+                    .iter()
+                    .find(|lease| lease.owner == self.id)
+            })
+            .map_or(FlockType::F_UNLCK, |lease| match lease.breaking {
+                Some(lease_break) => lease_break.target,
+                None => lease.type_,
+            })
+    }
+}
+
+impl Drop for LeaseRef {
+    fn drop(&mut self) {
+        let mut table = LEASE_TABLE.lock().unwrap();
+        let inode_leases = table.get_mut(&self.key).unwrap();
+        inode_leases.opens -= 1;
+        if self.writable {
+            inode_leases.writers -= 1;
+        }
+        let id = self.id;
+        inode_leases.leases.retain(|lease| lease.owner != id);
+        if inode_leases.opens == 0 {
+            table.remove(&self.key);
+        }
+    }
+}
+
+/// Format the leases as the lines of /proc/locks without the leading numbers
+pub fn format_proc_leases() -> Vec<String> {
+    let table = LEASE_TABLE.lock().unwrap();
+    let mut lines = Vec::new();
+    for (key, inode_leases) in table.iter() {
+        for lease in inode_leases.leases.iter() {
+            // A lease being broken is shown with the type it is downgraded to
+            let (class, type_) = match lease.breaking {
+                Some(lease_break) => ("LEASE  BREAKING ", lease_break.target),
+                None => ("LEASE  ACTIVE   ", lease.type_),
+            };
+            lines.push(format_proc_lock_line(
+                class,
+                type_,
+                lease.pid as i64,
+                inode_leases.dev,
+                key.ino(),
+                "0 EOF",
+            ));
+        }
+    }
+    lines
+}
+
+/// Break the conflicting leases on the inode, and then call `on_broken` with
+/// the lease table still locked.
+///
+/// The lease-break signal, i.e., SIGIO, is sent to the lease holders when
+/// their leases start to be broken. The wait for the holders can be
+/// interrupted by signals.
+fn break_leases<F, R>(key: &InodeKey, writable: bool, nonblocking: bool, on_broken: F) -> Result<R>
+where
+    F: FnOnce(&mut BTreeMap<InodeKey, InodeLeases>) -> R,
+{
+    let target = if writable {
+        FlockType::F_UNLCK
+    } else {
+        FlockType::F_RDLCK
+    };
+    loop {
+        let now = time::do_clock_gettime(ClockID::CLOCK_MONOTONIC)?.as_duration();
+        let holders_to_notify = {
+            let mut table = LEASE_TABLE.lock().unwrap();
+            let mut holders_to_notify = Vec::new();
+            let has_conflicts = match table.get_mut(key) {
+                Some(inode_leases) => {
+                    inode_leases.break_conflicts(writable, target, now, &mut holders_to_notify)
+                }
+                None => false,
+            };
+            if !has_conflicts {
+                return Ok(on_broken(&mut table));
+            }
+            holders_to_notify
+        };
+
+        // The signals are sent without the lease table locked
+        for pid in holders_to_notify {
+            if let Err(e) = process::do_kill(pid, Some(process::SIGIO)) {
+                warn!("failed to send the lease-break signal to {}: {}", pid, e);
+            }
+        }
+        if nonblocking {
+            return_errno!(EWOULDBLOCK, "the file has a lease being broken");
+        }
+        if process::is_sig_pending() {
+            return_errno!(EINTR, "the lease break is interrupted by a signal");
+        }
+        time::do_nanosleep(&timespec_t::from_duration(LEASE_BREAK_CHECK_INTERVAL))?;
+    }
+}
+
+impl InodeLeases {
+    /// Start or continue to break the leases that conflict with an open, and
+    /// return whether any of them is still being broken. The holders of the
+    /// leases that start to be broken are added to `holders_to_notify`.
+    fn break_conflicts(
+        &mut self,
+        writable: bool,
+        target: FlockType,
+        now: Duration,
+        holders_to_notify: &mut Vec<pid_t>,
+    ) -> bool {
+        let mut has_conflicts = false;
+        for lease in self.leases.iter_mut() {
+            if !lease.conflicts_with(writable) {
+                continue;
+            }
+            let lease_break = match lease.breaking {
+                Some(lease_break) if lease_break.target == FlockType::F_UNLCK => lease_break,
+                Some(lease_break) => LeaseBreak {
+                    target,
+                    deadline: lease_break.deadline,
+                },
+                None => {
+                    if !holders_to_notify.contains(&lease.pid) {
+                        holders_to_notify.push(lease.pid);
+                    }
+                    LeaseBreak {
+                        target,
+                        deadline: now + LEASE_BREAK_TIME,
+                    }
+                }
+            };
+            if now >= lease_break.deadline {
+                lease.type_ = lease_break.target;
+                lease.breaking = None;
+            } else {
+                lease.breaking = Some(lease_break);
+                has_conflicts = true;
+            }
+        }
+        self.leases
+            .retain(|lease| lease.type_ != FlockType::F_UNLCK);
+        has_conflicts
+    }
+}
@@ -7,7 +7,11 @@ pub fn do_link(oldpath: &str, newpath: &str) -> Result<()> {
 
     let (new_dir_path, new_file_name) = split_path(&newpath);
     let inode = current_process.lookup_inode(&oldpath)?;
-    let new_dir_inode = current_process.lookup_inode(new_dir_path)?;
+    let new_dir_inode = current_process.lookup_inode_follow(new_dir_path)?;
+    sefs::check_not_read_only(&new_dir_inode)?;
+    let dir_locks = DirEntriesLocks::of(&[&new_dir_inode])?;
+    let _dir_guards = dir_locks.lock_for_update();
     new_dir_inode.link(new_file_name, &inode)?;
+    notify_dir_changed(&new_dir_inode, DnotifyEvents::DN_CREATE);
     Ok(())
 }
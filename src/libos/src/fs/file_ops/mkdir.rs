@@ -7,13 +7,20 @@ pub fn do_mkdir(path: &str, mode: usize) -> Result<()> {
     info!("mkdir: path: {:?}, mode: {:#o}", path, mode);
 
     let (dir_path, file_name) = split_path(&path);
-    let inode = current_process.lookup_inode(dir_path)?;
+    let inode = current_process.lookup_inode_follow(dir_path)?;
     if inode.find(file_name).is_ok() {
-        return_errno!(EEXIST, "");
+        return_errno!(EEXIST, "the file exists");
     }
-    if !inode.allow_write()? {
+    sefs::check_not_read_only(&inode)?;
+    let credentials = current_process.get_credentials().lock().unwrap();
+    if !inode.allow_write(&credentials)? {
         return_errno!(EPERM, "dir cannot be written");
     }
-    inode.create(file_name, FileType::Dir, mode as u32)?;
+    let dir_locks = DirEntriesLocks::of(&[&inode])?;
+    let _dir_guards = dir_locks.lock_for_update();
+    let mode = mode as u32 & !current_process.get_umask();
+    let dir_inode = inode.create(file_name, FileType::Dir, mode)?;
+    set_new_inode_owner(&dir_inode, &credentials)?;
+    notify_dir_changed(&inode, DnotifyEvents::DN_CREATE);
     Ok(())
 }
@@ -0,0 +1,40 @@
+use super::*;
+
+const S_IFMT: u32 = 0o170000;
+const S_IFSOCK: u32 = 0o140000;
+const S_IFREG: u32 = 0o100000;
+const S_IFBLK: u32 = 0o060000;
+const S_IFCHR: u32 = 0o020000;
+const S_IFIFO: u32 = 0o010000;
+
+pub fn do_mknod(path: &str, mode: u32, dev: usize) -> Result<()> {
+    let current_ref = process::get_current();
+    let current_process = current_ref.lock().unwrap();
+    info!("mknod: path: {:?}, mode: {:#o}, dev: {:?}", path, mode, dev);
+
+    let type_ = match mode & S_IFMT {
+        0 | S_IFREG => FileType::File,
+        S_IFIFO => FileType::NamedPipe,
+        S_IFCHR | S_IFBLK | S_IFSOCK => {
+            return_errno!(EPERM, "device or socket files cannot be created");
+        }
+        _ => return_errno!(EINVAL, "invalid file type"),
+    };
+    let (dir_path, file_name) = split_path(&path);
+    let inode = current_process.lookup_inode_follow(dir_path)?;
+    if inode.find(file_name).is_ok() {
+        return_errno!(EEXIST, "the file exists");
+    }
+    sefs::check_not_read_only(&inode)?;
+    let credentials = current_process.get_credentials().lock().unwrap();
+    if !inode.allow_write(&credentials)? {
+        return_errno!(EPERM, "dir cannot be written");
+    }
+    let dir_locks = DirEntriesLocks::of(&[&inode])?;
+    let _dir_guards = dir_locks.lock_for_update();
+    let mode = mode & !S_IFMT & !current_process.get_umask();
+    let file_inode = inode.create(file_name, type_, mode)?;
+    set_new_inode_owner(&file_inode, &credentials)?;
+    notify_dir_changed(&inode, DnotifyEvents::DN_CREATE);
+    Ok(())
+}
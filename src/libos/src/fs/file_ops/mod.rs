@@ -1,50 +1,81 @@
 use super::dev_fs::{DevNull, DevRandom, DevSgx, DevZero};
+use super::proc_fs::{
+    lstat_fd_path, LastError, Locks, MountNamespaceFile, ProcFdDir, ProcFile, ProcPidStat,
+    ProcPidStatus,
+};
 use super::*;
-use process::Process;
+use process::{pid_t, Credentials, Process};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 pub use self::access::{
-    do_access, do_faccessat, AccessibilityCheckFlags, AccessibilityCheckMode, AT_FDCWD,
+    check_mode, do_access, do_faccessat, AccessibilityCheckFlags, AccessibilityCheckMode, AT_FDCWD,
+};
+pub use self::change_notify::{
+    notify_dir_changed, notify_file_changed, notify_modified, send_dnotify_signals, SizeWatch,
 };
-pub use self::chdir::do_chdir;
+pub use self::chdir::{do_chdir, do_fchdir, do_getcwd, Cwd};
+pub use self::chmod::{do_chmod, do_fchmod};
+pub use self::chown::{do_chown, do_fchown, do_lchown, set_new_inode_owner};
 pub use self::close::do_close;
-pub use self::dirent::do_getdents64;
+pub use self::dirent::{do_getdents64, snapshot_dir_entries, DirEntriesLocks};
 pub use self::dup::{do_dup, do_dup2, do_dup3};
-pub use self::fcntl::{do_fcntl, FcntlCmd};
+pub use self::fallocate::{do_fallocate, FallocateFlags};
+pub use self::fcntl::{do_fcntl, DnotifyEvents, FcntlCmd};
 pub use self::file_flags::{AccessMode, CreationFlags, StatusFlags};
-pub use self::flock::{Flock, FlockType};
+pub use self::file_handle::{
+    do_name_to_handle_at, do_open_by_handle_at, FileHandle, NameToHandleFlags, FILEID_INO64_GEN,
+    MAX_HANDLE_SZ,
+};
+pub use self::file_lock::{
+    do_flock, format_proc_locks, release_all_posix_locks, release_file_locks, release_posix_locks,
+    set_file_lock, test_file_lock, FileLockKind, InodeKey,
+};
+pub use self::flock::{Flock, FlockType, FlockWhence};
 pub use self::fsync::{do_fdatasync, do_fsync};
 pub use self::ioctl::{do_ioctl, IoctlCmd, StructuredIoctlArgType, StructuredIoctlNum};
+pub use self::lease::LeaseRef;
 pub use self::link::do_link;
 pub use self::lseek::do_lseek;
 pub use self::mkdir::do_mkdir;
+pub use self::mknod::do_mknod;
 pub use self::open::do_open;
-pub use self::read::{do_pread, do_read, do_readv};
+pub use self::read::{do_pread, do_preadv, do_preadv2, do_read, do_readv};
 pub use self::rename::do_rename;
 pub use self::rmdir::do_rmdir;
+pub use self::rw_flags::RwfFlags;
 pub use self::sendfile::do_sendfile;
-pub use self::stat::{do_fstat, do_lstat, do_stat, Stat};
+pub use self::stat::{do_fstat, do_lstat, do_stat, do_statx, Stat, StatMode, Statx, StatxFlags};
 pub use self::symlink::do_readlink;
 pub use self::truncate::{do_ftruncate, do_truncate};
 pub use self::unlink::do_unlink;
-pub use self::write::{do_pwrite, do_write, do_writev};
+pub use self::write::{do_pwrite, do_pwritev, do_write, do_writev};
 
 mod access;
+mod change_notify;
 mod chdir;
+mod chmod;
+mod chown;
 mod close;
 mod dirent;
 mod dup;
+mod fallocate;
 mod fcntl;
 mod file_flags;
+mod file_handle;
+mod file_lock;
 mod flock;
 mod fsync;
 mod ioctl;
+mod lease;
 mod link;
 mod lseek;
 mod mkdir;
+mod mknod;
 mod open;
 mod read;
 mod rename;
 mod rmdir;
+mod rw_flags;
 mod sendfile;
 mod stat;
 mod symlink;
@@ -52,25 +83,101 @@ mod truncate;
 mod unlink;
 mod write;
 
+/// An open that is prepared by `Process::prepare_open`
+pub enum PendingOpen {
+    File(Box<dyn File>),
+    INode {
+        inode: Arc<dyn INode>,
+        abs_path: String,
+        flags: u32,
+        credentials: Credentials,
+    },
+}
+
+impl PendingOpen {
+    /// Finish the open. The leases on the inode are broken if needed.
+    pub fn finish(self) -> Result<Box<dyn File>> {
+        match self {
+            PendingOpen::File(file) => Ok(file),
+            PendingOpen::INode {
+                inode,
+                abs_path,
+                flags,
+                credentials,
+            } => Ok(Box::new(INodeFile::open(
+                inode,
+                &abs_path,
+                flags,
+                &credentials,
+            )?)),
+        }
+    }
+}
+
 impl Process {
     /// Open a file on the process. But DO NOT add it to file table.
     pub fn open_file(&self, path: &str, flags: u32, mode: u32) -> Result<Box<dyn File>> {
+        self.prepare_open(path, flags, mode)?.finish()
+    }
+
+    /// Do the part of an open that needs the process, i.e., resolve the path
+    /// and create the file if needed. The rest is done by `PendingOpen::finish`,
+    /// which may wait for the lease holders, so it should be called without
+    /// the process locked.
+    pub fn prepare_open(&self, path: &str, flags: u32, mode: u32) -> Result<PendingOpen> {
         if path == "/dev/null" {
-            return Ok(Box::new(DevNull));
+            return Ok(PendingOpen::File(Box::new(DevNull)));
         }
         if path == "/dev/zero" {
-            return Ok(Box::new(DevZero));
+            return Ok(PendingOpen::File(Box::new(DevZero)));
         }
         if path == "/dev/random" || path == "/dev/urandom" || path == "/dev/arandom" {
-            return Ok(Box::new(DevRandom));
+            return Ok(PendingOpen::File(Box::new(DevRandom)));
         }
         if path == "/dev/sgx" {
-            return Ok(Box::new(DevSgx));
+            return Ok(PendingOpen::File(Box::new(DevSgx)));
+        }
+        if path == "/proc/self/occlum_last_error" {
+            return Ok(PendingOpen::File(Box::new(ProcFile::open(
+                LastError, flags,
+            )?)));
+        }
+        if path == "/proc/locks" {
+            return Ok(PendingOpen::File(Box::new(ProcFile::open(Locks, flags)?)));
+        }
+        if path.trim_end_matches('/') == "/proc/self/fd" {
+            return Ok(PendingOpen::File(Box::new(ProcFdDir::open(self, flags)?)));
+        }
+        if let Some(pid_str) = proc_pid_entry(path, "status") {
+            let pid = self.get_pid_of(pid_str)?;
+            return Ok(PendingOpen::File(Box::new(ProcFile::open(
+                ProcPidStatus::new(pid),
+                flags,
+            )?)));
+        }
+        if let Some(pid_str) = proc_pid_entry(path, "stat") {
+            let pid = self.get_pid_of(pid_str)?;
+            return Ok(PendingOpen::File(Box::new(ProcFile::open(
+                ProcPidStat::new(pid),
+                flags,
+            )?)));
+        }
+        if path.starts_with("/proc/") && path.ends_with("/ns/mnt") {
+            let mnt_ns = self.get_mnt_ns_of(&path["/proc/".len()..path.len() - "/ns/mnt".len()])?;
+            return Ok(PendingOpen::File(Box::new(MountNamespaceFile::open(
+                mnt_ns, flags,
+            )?)));
         }
         let creation_flags = CreationFlags::from_bits_truncate(flags);
+        if creation_flags.is_tmpfile() {
+            return self.open_tmpfile(path, flags, mode);
+        }
+        let credentials = self.get_credentials().lock().unwrap().clone();
         let inode = if creation_flags.can_create() {
             let (dir_path, file_name) = split_path(&path);
-            let dir_inode = self.lookup_inode(dir_path)?;
+            let dir_inode = self.lookup_inode_follow(dir_path)?;
+            let dir_locks = DirEntriesLocks::of(&[&dir_inode])?;
+            let _dir_guards = dir_locks.lock_for_update();
             match dir_inode.find(file_name) {
                 Ok(file_inode) => {
                     if creation_flags.is_exclusive() {
@@ -79,34 +186,179 @@ impl Process {
                     file_inode
                 }
                 Err(FsError::EntryNotFound) => {
-                    if !dir_inode.allow_write()? {
+                    sefs::check_not_read_only(&dir_inode)?;
+                    let abs_path = self.convert_to_abs_path(&path);
+                    if !dir_inode.allow_write(&credentials)? {
                         return_errno!(EPERM, "file cannot be created");
                     }
-                    dir_inode.create(file_name, FileType::File, mode)?
+                    let mode = mode & !self.get_umask();
+                    let create_file = || -> Result<Arc<dyn INode>> {
+                        Ok(dir_inode.create(file_name, FileType::File, mode)?)
+                    };
+                    let file_inode = if creation_flags.is_integrity_only() {
+                        sefs::create_integrity_only(&abs_path, create_file)?
+                    } else {
+                        create_file()?
+                    };
+                    set_new_inode_owner(&file_inode, &credentials)?;
+                    notify_dir_changed(&dir_inode, DnotifyEvents::DN_CREATE);
+                    file_inode
                 }
                 Err(e) => return Err(Error::from(e)),
             }
         } else {
             self.lookup_inode(&path)?
         };
-        let abs_path = self.convert_to_abs_path(&path);
-        Ok(Box::new(INodeFile::open(inode, &abs_path, flags)?))
+        // Only the symlink at the end of the path is not followed with
+        // O_NOFOLLOW, as the ones in the middle are resolved by the lookup
+        let inode = if inode.metadata()?.type_ == FileType::SymLink {
+            if !creation_flags.no_follow_symlink() {
+                self.lookup_inode_follow(&path)?
+            } else if StatusFlags::from_bits_truncate(flags).is_path_only() {
+                // O_PATH with O_NOFOLLOW refers to the symlink itself
+                inode
+            } else {
+                return_errno!(ELOOP, "the file is a symlink with O_NOFOLLOW");
+            }
+        } else {
+            inode
+        };
+        if creation_flags.must_be_dir() && inode.metadata()?.type_ != FileType::Dir {
+            return_errno!(ENOTDIR, "the file is not a directory with O_DIRECTORY");
+        }
+        Ok(PendingOpen::INode {
+            inode,
+            abs_path: self.convert_to_abs_path(&path),
+            flags,
+            credentials,
+        })
+    }
+
+    /// Get the pid of the process given by the pid in procfs, i.e., a number
+    /// or "self". The pid of a thread is the thread group ID.
+    fn get_pid_of(&self, pid_str: &str) -> Result<pid_t> {
+        if pid_str == "self" {
+            return Ok(self.get_pid());
+        }
+        let pid = pid_str
+            .parse::<pid_t>()
+            .map_err(|_| errno!(ENOENT, "no such file in procfs"))?;
+        if pid == self.get_pid() || pid == self.get_tid() {
+            return Ok(self.get_pid());
+        }
+        let process_ref = process::get(pid)?;
+        let process = process_ref.lock().unwrap();
+        Ok(process.get_pid())
+    }
+
+    /// Get the mount namespace of the process given by the pid in procfs,
+    /// i.e., a number or "self"
+    fn get_mnt_ns_of(&self, pid_str: &str) -> Result<Arc<MountNamespace>> {
+        if pid_str == "self" {
+            return Ok(self.get_mnt_ns().clone());
+        }
+        let pid = pid_str
+            .parse::<pid_t>()
+            .map_err(|_| errno!(ENOENT, "no such file in procfs"))?;
+        if pid == self.get_pid() || pid == self.get_tid() {
+            return Ok(self.get_mnt_ns().clone());
+        }
+        let process_ref = process::get(pid)?;
+        let process = process_ref.lock().unwrap();
+        Ok(process.get_mnt_ns().clone())
     }
 
-    /// Lookup INode from the cwd of the process
+    // Create an unnamed file in the directory. The file is created with a
+    // unique name, which is then unlinked while the file is still open.
+    fn open_tmpfile(&self, dir_path: &str, flags: u32, mode: u32) -> Result<PendingOpen> {
+        static NEXT_TMPFILE_ID: AtomicUsize = AtomicUsize::new(0);
+
+        if !AccessMode::from_u32(flags)?.writable() {
+            return_errno!(EINVAL, "O_TMPFILE must be opened for write");
+        }
+        let dir_inode = self.lookup_inode_follow(dir_path)?;
+        if dir_inode.metadata()?.type_ != FileType::Dir {
+            return_errno!(ENOTDIR, "O_TMPFILE must be in a directory");
+        }
+        let dir_locks = DirEntriesLocks::of(&[&dir_inode])?;
+        let _dir_guards = dir_locks.lock_for_update();
+        sefs::check_not_read_only(&dir_inode)?;
+        let abs_path = self.convert_to_abs_path(dir_path);
+        let credentials = self.get_credentials().lock().unwrap().clone();
+        if !dir_inode.allow_write(&credentials)? {
+            return_errno!(EPERM, "file cannot be created");
+        }
+        let file_name = format!(
+            ".occlum_tmpfile.{}",
+            NEXT_TMPFILE_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        let mode = mode & !self.get_umask();
+        let inode = dir_inode.create(&file_name, FileType::File, mode)?;
+        set_new_inode_owner(&inode, &credentials)?;
+        dir_inode.unlink(&file_name)?;
+        Ok(PendingOpen::INode {
+            inode,
+            abs_path,
+            flags,
+            credentials,
+        })
+    }
+
+    /// Lookup INode from the cwd of the process, following the symlinks in
+    /// the middle of the path, but not the one at the end
     pub fn lookup_inode(&self, path: &str) -> Result<Arc<dyn INode>> {
         debug!("lookup_inode: cwd: {:?}, path: {:?}", self.get_cwd(), path);
-        if path.len() > 0 && path.as_bytes()[0] == b'/' {
-            // absolute path
-            let abs_path = path.trim_start_matches('/');
-            let inode = ROOT_INODE.lookup(abs_path)?;
-            Ok(inode)
+        let mut symlinks_left = MAX_SYMLINKS;
+        self.lookup_inode_from(self.get_cwd_inode(), path, false, &mut symlinks_left)
+    }
+
+    /// Lookup INode from the cwd of the process, following the symlinks at
+    /// the end of the path
+    pub fn lookup_inode_follow(&self, path: &str) -> Result<Arc<dyn INode>> {
+        debug!(
+            "lookup_inode_follow: cwd: {:?}, path: {:?}",
+            self.get_cwd(),
+            path
+        );
+        let mut symlinks_left = MAX_SYMLINKS;
+        self.lookup_inode_from(self.get_cwd_inode(), path, true, &mut symlinks_left)
+    }
+
+    /// Lookup INode from the directory one component by one, so that the
+    /// symlinks are resolved in the namespace of the process. A relative
+    /// target of a symlink is looked up from the directory of the symlink.
+    fn lookup_inode_from(
+        &self,
+        dir_inode: &Arc<dyn INode>,
+        path: &str,
+        follow_last: bool,
+        symlinks_left: &mut usize,
+    ) -> Result<Arc<dyn INode>> {
+        let mut inode = if path.starts_with('/') {
+            self.get_mnt_ns().root_inode()
         } else {
-            // relative path
-            let cwd = self.get_cwd().trim_start_matches('/');
-            let inode = ROOT_INODE.lookup(cwd)?.lookup(path)?;
-            Ok(inode)
+            dir_inode.clone()
+        };
+        let names: Vec<&str> = path.split('/').filter(|name| !name.is_empty()).collect();
+        for (idx, name) in names.iter().enumerate() {
+            if inode.metadata()?.type_ != FileType::Dir {
+                return_errno!(ENOTDIR, "a component of the path is not a directory");
+            }
+            let next_inode = inode.find(name)?;
+            let is_last = idx == names.len() - 1;
+            if next_inode.metadata()?.type_ != FileType::SymLink || (is_last && !follow_last) {
+                inode = next_inode;
+                continue;
+            }
+            if *symlinks_left == 0 {
+                return_errno!(ELOOP, "too many levels of symbolic links");
+            }
+            *symlinks_left -= 1;
+            let target = String::from_utf8(next_inode.read_as_vec()?)
+                .map_err(|_| errno!(EINVAL, "the target of the symlink is not valid UTF-8"))?;
+            inode = self.lookup_inode_from(&inode, &target, true, symlinks_left)?;
         }
+        Ok(inode)
     }
 
     /// Convert the path to be absolute
@@ -121,16 +373,20 @@ impl Process {
             return path.to_owned();
         }
         let cwd = {
-            if !self.get_cwd().ends_with("/") {
-                self.get_cwd().to_owned() + "/"
+            let cwd_path = self.get_cwd_path();
+            if !cwd_path.ends_with("/") {
+                cwd_path + "/"
             } else {
-                self.get_cwd().to_owned()
+                cwd_path
             }
         };
         cwd + path
     }
 }
 
+/// The max number of symlinks followed in a lookup, as MAXSYMLINKS of Linux
+const MAX_SYMLINKS: usize = 40;
+
 /// Split a `path` str to `(base_path, file_name)`
 pub fn split_path(path: &str) -> (&str, &str) {
     let mut split = path.trim_end_matches('/').rsplitn(2, '/');
@@ -141,3 +397,28 @@ pub fn split_path(path: &str) -> (&str, &str) {
     }
     (dir_path, file_name)
 }
+
+/// Get the file of the fd of the current process. The process is not kept
+/// locked, so that the operation on the file may block, or lock the process
+/// again, e.g., to show its state in procfs.
+pub fn get_file(fd: FileDesc) -> Result<FileRef> {
+    let current_ref = process::get_current();
+    let current_process = current_ref.lock().unwrap();
+    let file_ref = current_process.get_files().lock().unwrap().get(fd)?;
+    Ok(file_ref)
+}
+
+/// Get the pid part of a path of /proc/[pid]/`entry`, or None if the path is
+/// not one
+fn proc_pid_entry<'a>(path: &'a str, entry: &str) -> Option<&'a str> {
+    let path = path.trim_end_matches('/');
+    if !path.starts_with("/proc/") {
+        return None;
+    }
+    let mut split = path["/proc/".len()..].splitn(2, '/');
+    let pid_str = split.next().unwrap();
+    if split.next() != Some(entry) {
+        return None;
+    }
+    Some(pid_str)
+}
@@ -7,17 +7,22 @@ pub fn do_open(path: &str, flags: u32, mode: u32) -> Result<FileDesc> {
     );
 
     let current_ref = process::get_current();
-    let mut proc = current_ref.lock().unwrap();
-
-    let file = proc.open_file(path, flags, mode)?;
+    let pending_open = current_ref
+        .lock()
+        .unwrap()
+        .prepare_open(path, flags, mode)?;
+    // Finishing the open may wait for the lease holders, which may be the
+    // other threads of the process, so the process is not kept locked
+    let file = pending_open.finish()?;
     let file_ref: Arc<Box<dyn File>> = Arc::new(file);
 
     let fd = {
         let creation_flags = CreationFlags::from_bits_truncate(flags);
+        let proc = current_ref.lock().unwrap();
         proc.get_files()
             .lock()
             .unwrap()
-            .put(file_ref, creation_flags.must_close_on_spawn())
+            .put(file_ref, creation_flags.must_close_on_spawn())?
     };
     Ok(fd)
 }
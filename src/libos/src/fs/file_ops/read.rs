@@ -2,24 +2,154 @@ use super::*;
 
 pub fn do_read(fd: FileDesc, buf: &mut [u8]) -> Result<usize> {
     info!("read: fd: {}", fd);
-    let current_ref = process::get_current();
-    let current_process = current_ref.lock().unwrap();
-    let file_ref = current_process.get_files().lock().unwrap().get(fd)?;
-    file_ref.read(buf)
+    let file_ref = get_file(fd)?;
+    let is_regular = file_ref.as_inode_file().is_ok();
+    read_in_chunks(buf, is_regular, |chunk, _| file_ref.read(chunk))
 }
 
 pub fn do_readv(fd: FileDesc, bufs: &mut [&mut [u8]]) -> Result<usize> {
     info!("readv: fd: {}", fd);
-    let current_ref = process::get_current();
-    let current_process = current_ref.lock().unwrap();
-    let file_ref = current_process.get_files().lock().unwrap().get(fd)?;
-    file_ref.readv(bufs)
+    let file_ref = get_file(fd)?;
+    let is_regular = file_ref.as_inode_file().is_ok();
+    readv_in_chunks(bufs, is_regular, |chunk_bufs, _| file_ref.readv(chunk_bufs))
 }
 
 pub fn do_pread(fd: FileDesc, buf: &mut [u8], offset: usize) -> Result<usize> {
     info!("pread: fd: {}, offset: {}", fd, offset);
-    let current_ref = process::get_current();
-    let current_process = current_ref.lock().unwrap();
-    let file_ref = current_process.get_files().lock().unwrap().get(fd)?;
-    file_ref.read_at(offset, buf)
+    let file_ref = get_file(fd)?;
+    let is_regular = file_ref.as_inode_file().is_ok();
+    read_in_chunks(buf, is_regular, |chunk, done_len| {
+        file_ref.read_at(offset + done_len, chunk)
+    })
+}
+
+/// Read the file into the buffers at the offset, without changing the file
+/// offset
+pub fn do_preadv(fd: FileDesc, bufs: &mut [&mut [u8]], offset: usize) -> Result<usize> {
+    info!("preadv: fd: {}, offset: {}", fd, offset);
+    let file_ref = get_file(fd)?;
+    let inode_file = file_ref
+        .as_inode_file()
+        .map_err(|_| errno!(ESPIPE, "the file does not support positional I/O"))?;
+    readv_in_chunks(bufs, true, |chunk_bufs, done_len| {
+        inode_file.readv_at(offset + done_len, chunk_bufs)
+    })
+}
+
+/// Read the file into the buffers at the offset, or at the file offset if it
+/// is None, with the per-call flags
+pub fn do_preadv2(
+    fd: FileDesc,
+    bufs: &mut [&mut [u8]],
+    offset: Option<usize>,
+    flags: RwfFlags,
+) -> Result<usize> {
+    info!(
+        "preadv2: fd: {}, offset: {:?}, flags: {:?}",
+        fd, offset, flags
+    );
+    let file_ref = get_file(fd)?;
+    // RWF_HIPRI busy polls the file instead of sleeping, e.g., for sockets
+    // with SO_BUSY_POLL
+    if flags.contains(RwfFlags::RWF_HIPRI) {
+        file_ref.busy_poll()?;
+    }
+    let is_regular = file_ref.as_inode_file().is_ok();
+    let offset = match offset {
+        Some(offset) => offset,
+        None => {
+            return readv_in_chunks(bufs, is_regular, |chunk_bufs, _| file_ref.readv(chunk_bufs))
+        }
+    };
+    readv_in_chunks(bufs, is_regular, |chunk_bufs, done_len| {
+        read_bufs_at(&file_ref, offset + done_len, chunk_bufs)
+    })
+}
+
+/// Read the file into the buffers one by one at the offset, until a short read
+fn read_bufs_at(file_ref: &FileRef, mut offset: usize, bufs: &mut [&mut [u8]]) -> Result<usize> {
+    let mut total_len = 0;
+    for buf in bufs {
+        match file_ref.read_at(offset, buf) {
+            Ok(len) => {
+                total_len += len;
+                offset += len;
+                if len < buf.len() {
+                    break;
+                }
+            }
+            Err(_) if total_len != 0 => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(total_len)
+}
+
+/// Read into the buffer in chunks of at most `max_io_size` bytes of the
+/// config, so that a huge read does not take a bounce buffer of the same size
+/// at once, e.g., in the OCall to the host. The function reads a chunk at the
+/// offset in the buffer.
+///
+/// A regular file is read chunk by chunk until a short read, as the file is
+/// expected to be read fully before EOF. The other files, e.g., pipes and
+/// sockets, are read for the first chunk only, since reading more may block
+/// after some data is read, while a short read is expected for them anyway.
+fn read_in_chunks<F>(buf: &mut [u8], is_regular: bool, mut read_fn: F) -> Result<usize>
+where
+    F: FnMut(&mut [u8], usize) -> Result<usize>,
+{
+    let max_io_size = config::LIBOS_CONFIG.fs.max_io_size;
+    if buf.len() <= max_io_size || !is_regular {
+        let len = min(buf.len(), max_io_size);
+        return read_fn(&mut buf[..len], 0);
+    }
+    let mut total_len = 0;
+    for chunk in buf.chunks_mut(max_io_size) {
+        let chunk_len = chunk.len();
+        match read_fn(chunk, total_len) {
+            Ok(len) => {
+                total_len += len;
+                if len < chunk_len {
+                    break;
+                }
+            }
+            // The data that is read is returned
+            Err(_) if total_len != 0 => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(total_len)
+}
+
+/// Read into the buffers in chunks of at most `max_io_size` bytes in total,
+/// as `read_in_chunks` does for a single buffer. The buffers of a chunk are
+/// read by one call of `read_fn`.
+fn readv_in_chunks<F>(bufs: &mut [&mut [u8]], is_regular: bool, mut read_fn: F) -> Result<usize>
+where
+    F: FnMut(&mut [&mut [u8]], usize) -> Result<usize>,
+{
+    let max_io_size = config::LIBOS_CONFIG.fs.max_io_size;
+    let total_bytes: usize = bufs.iter().map(|buf| buf.len()).sum();
+    if total_bytes <= max_io_size {
+        return read_fn(bufs, 0);
+    }
+    let mut chunks = net::split_iovs_mut(bufs, max_io_size);
+    if !is_regular {
+        return read_fn(&mut chunks[0], 0);
+    }
+    let mut total_len = 0;
+    for chunk in chunks.iter_mut() {
+        let chunk_len: usize = chunk.iter().map(|buf| buf.len()).sum();
+        match read_fn(chunk, total_len) {
+            Ok(len) => {
+                total_len += len;
+                if len < chunk_len {
+                    break;
+                }
+            }
+            Err(_) if total_len != 0 => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(total_len)
 }
@@ -1,3 +1,4 @@
+use super::file_handle::{invalidate_handles, rename_handles};
 use super::*;
 
 pub fn do_rename(oldpath: &str, newpath: &str) -> Result<()> {
@@ -7,9 +8,27 @@ pub fn do_rename(oldpath: &str, newpath: &str) -> Result<()> {
 
     let (old_dir_path, old_file_name) = split_path(&oldpath);
     let (new_dir_path, new_file_name) = split_path(&newpath);
-    let old_dir_inode = current_process.lookup_inode(old_dir_path)?;
-    let new_dir_inode = current_process.lookup_inode(new_dir_path)?;
+    let old_dir_inode = current_process.lookup_inode_follow(old_dir_path)?;
+    let new_dir_inode = current_process.lookup_inode_follow(new_dir_path)?;
+    // The file at the new path, if any, is replaced by the rename
+    let replaced_metadata = new_dir_inode
+        .find(new_file_name)
+        .and_then(|inode| inode.metadata())
+        .ok();
+    let old_abs_path = current_process.convert_to_abs_path(oldpath);
+    let new_abs_path = current_process.convert_to_abs_path(newpath);
+    sefs::check_not_read_only(&old_dir_inode)?;
+    sefs::check_not_read_only(&new_dir_inode)?;
     // TODO: support to modify file's absolute path
+    let dir_locks = DirEntriesLocks::of(&[&old_dir_inode, &new_dir_inode])?;
+    let _dir_guards = dir_locks.lock_for_update();
     old_dir_inode.move_(old_file_name, &new_dir_inode, new_file_name)?;
+    notify_dir_changed(&old_dir_inode, DnotifyEvents::DN_RENAME);
+    notify_dir_changed(&new_dir_inode, DnotifyEvents::DN_RENAME);
+
+    if let Some(metadata) = replaced_metadata {
+        invalidate_handles(&new_abs_path, &metadata);
+    }
+    rename_handles(&old_abs_path, &new_abs_path);
     Ok(())
 }
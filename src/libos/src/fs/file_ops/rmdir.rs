@@ -1,3 +1,4 @@
+use super::file_handle::invalidate_handles;
 use super::*;
 
 pub fn do_rmdir(path: &str) -> Result<()> {
@@ -6,11 +7,18 @@ pub fn do_rmdir(path: &str) -> Result<()> {
     info!("rmdir: path: {:?}", path);
 
     let (dir_path, file_name) = split_path(&path);
-    let dir_inode = current_process.lookup_inode(dir_path)?;
+    let dir_inode = current_process.lookup_inode_follow(dir_path)?;
     let file_inode = dir_inode.find(file_name)?;
-    if file_inode.metadata()?.type_ != FileType::Dir {
+    let metadata = file_inode.metadata()?;
+    if metadata.type_ != FileType::Dir {
         return_errno!(ENOTDIR, "rmdir on not directory");
     }
+    sefs::check_not_read_only(&dir_inode)?;
+    let abs_path = current_process.convert_to_abs_path(path);
+    let dir_locks = DirEntriesLocks::of(&[&dir_inode])?;
+    let _dir_guards = dir_locks.lock_for_update();
     dir_inode.unlink(file_name)?;
+    notify_dir_changed(&dir_inode, DnotifyEvents::DN_DELETE);
+    invalidate_handles(&abs_path, &metadata);
     Ok(())
 }
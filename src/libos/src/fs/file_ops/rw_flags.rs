@@ -0,0 +1,28 @@
+use super::*;
+
+bitflags! {
+    /// The per-call flags of preadv2 and pwritev2
+    pub struct RwfFlags: u32 {
+        /// High priority request, which polls the file for completion
+        const RWF_HIPRI = 0x00000001;
+        /// Per-IO O_DSYNC
+        const RWF_DSYNC = 0x00000002;
+        /// Per-IO O_SYNC
+        const RWF_SYNC = 0x00000004;
+        /// Per-IO, return EAGAIN if the operation would block
+        const RWF_NOWAIT = 0x00000008;
+        /// Per-IO O_APPEND
+        const RWF_APPEND = 0x00000010;
+    }
+}
+
+impl RwfFlags {
+    pub fn from_u32(raw: u32) -> Result<RwfFlags> {
+        let flags = RwfFlags::from_bits(raw)
+            .ok_or_else(|| errno!(EOPNOTSUPP, "unknown per-call read/write flags"))?;
+        if flags.contains(RwfFlags::RWF_NOWAIT) {
+            return_errno!(EOPNOTSUPP, "RWF_NOWAIT is not supported");
+        }
+        Ok(flags)
+    }
+}
@@ -1,4 +1,9 @@
 use super::*;
+use net::{AsSocket, MsgFlags};
+use untrusted::UntrustedSliceAlloc;
+
+/// The size of the chunks in which the data is transferred
+const SENDFILE_CHUNK_SIZE: usize = 64 * 1024;
 
 pub fn do_sendfile(
     out_fd: FileDesc,
@@ -11,42 +16,90 @@ pub fn do_sendfile(
         "sendfile: out: {}, in: {}, offset: {:?}, count: {}",
         out_fd, in_fd, offset, count
     );
-    let current_ref = process::get_current();
-    let current_process = current_ref.lock().unwrap();
-    let file_table_ref = current_process.get_files();
-    let mut file_table = file_table_ref.lock().unwrap();
+    // The transfer may block, so the current process is not kept locked
+    let (in_file, out_file) = {
+        let current_ref = process::get_current();
+        let current_process = current_ref.lock().unwrap();
+        let file_table = current_process.get_files().lock().unwrap();
+        (file_table.get(in_fd)?, file_table.get(out_fd)?)
+    };
 
-    let in_file = file_table.get(in_fd)?;
-    let out_file = file_table.get(out_fd)?;
-    let mut buffer: [u8; 1024 * 11] = unsafe { MaybeUninit::uninit().assume_init() };
+    let is_regular_file = in_file.as_inode_file().is_ok()
+        && in_file
+            .metadata()
+            .map_or(false, |metadata| metadata.type_ == FileType::File);
+    if !is_regular_file {
+        return_errno!(EINVAL, "in_fd is not a seekable regular file");
+    }
+    let read_offset = match offset {
+        Some(offset) if offset < 0 => return_errno!(EINVAL, "offset is negative"),
+        Some(offset) => offset as usize,
+        None => in_file.seek(SeekFrom::Current(0))? as usize,
+    };
+    if count == 0 {
+        return Ok((0, read_offset));
+    }
 
-    let mut read_offset = match offset {
-        Some(offset) => offset,
-        None => in_file.seek(SeekFrom::Current(0))?,
-    } as usize;
+    let chunk_size = min(SENDFILE_CHUNK_SIZE, count);
+    let bytes_sent = if let Ok(socket) = out_file.as_socket() {
+        // Read the file directly into an untrusted buffer, from which the data
+        // is sent without being copied again
+        let u_slice_alloc = UntrustedSliceAlloc::new(chunk_size)?;
+        let u_buf = u_slice_alloc.new_slice_mut(chunk_size)?;
+        let ret = transfer(&in_file, read_offset, count, u_buf, |buf| {
+            socket.send_untrusted(buf)
+        });
+        net::raise_sigpipe_on_epipe(ret, MsgFlags::empty())?
+    } else {
+        let mut buf = vec![0; chunk_size];
+        transfer(&in_file, read_offset, count, &mut buf, |buf| {
+            out_file.write(buf)
+        })?
+    };
 
-    // read from specified offset and write new offset back
-    let mut bytes_read = 0;
-    while bytes_read < count {
-        let len = min(buffer.len(), count - bytes_read);
-        let read_len = in_file.read_at(read_offset, &mut buffer[..len])?;
-        if read_len == 0 {
-            break;
-        }
-        bytes_read += read_len;
-        read_offset += read_len;
+    let new_offset = read_offset + bytes_sent;
+    if offset.is_none() {
+        in_file.seek(SeekFrom::Start(new_offset as u64))?;
+    }
+    Ok((bytes_sent, new_offset))
+}
+
+/// Transfer up to `count` bytes from the file at the offset by `write`, using
+/// the buffer. The number of bytes transferred is returned.
+///
+/// If an error happens after some bytes are transferred, e.g., the output
+/// would block, the partial count is returned instead of the error.
+fn transfer<F>(
+    in_file: &FileRef,
+    offset: usize,
+    count: usize,
+    buf: &mut [u8],
+    mut write: F,
+) -> Result<usize>
+where
+    F: FnMut(&[u8]) -> Result<usize>,
+{
+    let mut bytes_sent = 0;
+    while bytes_sent < count {
+        let len = min(buf.len(), count - bytes_sent);
+        let read_len = match in_file.read_at(offset + bytes_sent, &mut buf[..len]) {
+            Ok(0) => break,
+            Ok(read_len) => read_len,
+            Err(_) if bytes_sent > 0 => break,
+            Err(e) => return Err(e),
+        };
         let mut bytes_written = 0;
         while bytes_written < read_len {
-            let write_len = out_file.write(&buffer[bytes_written..])?;
-            if write_len == 0 {
-                return_errno!(EBADF, "sendfile write return 0");
+            match write(&buf[bytes_written..read_len]) {
+                Ok(0) => return Ok(bytes_sent + bytes_written),
+                Ok(write_len) => bytes_written += write_len,
+                Err(_) if bytes_sent + bytes_written > 0 => {
+                    return Ok(bytes_sent + bytes_written);
+                }
+                Err(e) => return Err(e),
             }
-            bytes_written += write_len;
         }
+        bytes_sent += read_len;
     }
-
-    if offset.is_none() {
-        in_file.seek(SeekFrom::Current(bytes_read as i64))?;
-    }
-    Ok((bytes_read, read_offset))
+    Ok(bytes_sent)
 }
@@ -127,8 +127,16 @@ impl From<Metadata> for Stat {
 }
 
 pub fn do_stat(path: &str) -> Result<Stat> {
-    warn!("stat is partial implemented as lstat");
-    do_lstat(path)
+    info!("stat: path: {}", path);
+    let current_ref = process::get_current();
+    let current_process = current_ref.lock().unwrap();
+    if let Some(metadata) = lstat_fd_path(&current_process, path) {
+        // TODO: follow the links in /proc/self/fd to the open files
+        return Ok(Stat::from(metadata?));
+    }
+    let inode = current_process.lookup_inode_follow(&path)?;
+    let stat = Stat::from(inode.metadata()?);
+    Ok(stat)
 }
 
 pub fn do_fstat(fd: u32) -> Result<Stat> {
@@ -145,7 +153,166 @@ pub fn do_lstat(path: &str) -> Result<Stat> {
     info!("lstat: path: {}", path);
     let current_ref = process::get_current();
     let current_process = current_ref.lock().unwrap();
+    if let Some(metadata) = lstat_fd_path(&current_process, path) {
+        return Ok(Stat::from(metadata?));
+    }
     let inode = current_process.lookup_inode(&path)?;
     let stat = Stat::from(inode.metadata()?);
     Ok(stat)
 }
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct StatxTimestamp {
+    tv_sec: i64,
+    tv_nsec: u32,
+    _reserved: i32,
+}
+
+impl From<Timespec> for StatxTimestamp {
+    fn from(ts: Timespec) -> Self {
+        StatxTimestamp {
+            tv_sec: ts.sec,
+            tv_nsec: ts.nsec as u32,
+            _reserved: 0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Statx {
+    /// mask of bits indicating filled fields
+    stx_mask: u32,
+    /// block size for filesystem I/O
+    stx_blksize: u32,
+    /// extra file attribute indicators
+    stx_attributes: u64,
+    /// number of hard links
+    stx_nlink: u32,
+    /// user ID of owner
+    stx_uid: u32,
+    /// group ID of owner
+    stx_gid: u32,
+    /// file type and mode
+    stx_mode: u16,
+    _spare0: u16,
+    /// inode number
+    stx_ino: u64,
+    /// total size, in bytes
+    stx_size: u64,
+    /// number of 512B blocks allocated
+    stx_blocks: u64,
+    /// mask to show what is supported in stx_attributes
+    stx_attributes_mask: u64,
+    /// last access time
+    stx_atime: StatxTimestamp,
+    /// creation time
+    stx_btime: StatxTimestamp,
+    /// last status change time
+    stx_ctime: StatxTimestamp,
+    /// last modification time
+    stx_mtime: StatxTimestamp,
+    /// device ID (if special file)
+    stx_rdev_major: u32,
+    stx_rdev_minor: u32,
+    /// ID of device containing file
+    stx_dev_major: u32,
+    stx_dev_minor: u32,
+    _spare2: [u64; 14],
+}
+
+bitflags! {
+    pub struct StatxFlags: u32 {
+        const AT_SYMLINK_NOFOLLOW = 0x100;
+        const AT_NO_AUTOMOUNT = 0x800;
+        const AT_EMPTY_PATH = 0x1000;
+        const AT_STATX_FORCE_SYNC = 0x2000;
+        const AT_STATX_DONT_SYNC = 0x4000;
+    }
+}
+
+impl StatxFlags {
+    pub fn from_u32(bits: u32) -> Result<StatxFlags> {
+        StatxFlags::from_bits(bits).ok_or_else(|| errno!(EINVAL, "invalid flags"))
+    }
+}
+
+bitflags! {
+    pub struct StatxAttributes: u64 {
+        /// file is encrypted
+        const STATX_ATTR_ENCRYPTED = 0x800;
+        /// file is protected by integrity checks only, e.g., MACs
+        const STATX_ATTR_VERITY = 0x100000;
+    }
+}
+
+/// The fields filled in by statx: STATX_BASIC_STATS
+const STATX_BASIC_STATS: u32 = 0x7ff;
+
+impl Statx {
+    fn new(info: Metadata, attributes: StatxAttributes) -> Self {
+        Statx {
+            stx_mask: STATX_BASIC_STATS,
+            stx_blksize: info.blk_size as u32,
+            stx_attributes: attributes.bits(),
+            stx_nlink: info.nlinks as u32,
+            stx_uid: info.uid as u32,
+            stx_gid: info.gid as u32,
+            stx_mode: StatMode::from_type_mode(info.type_, info.mode as u16).bits() as u16,
+            stx_ino: info.inode as u64,
+            stx_size: info.size as u64,
+            stx_blocks: info.blocks as u64,
+            stx_attributes_mask: StatxAttributes::all().bits(),
+            stx_atime: info.atime.into(),
+            stx_ctime: info.ctime.into(),
+            stx_mtime: info.mtime.into(),
+            stx_dev_major: (info.dev >> 8) as u32,
+            stx_dev_minor: (info.dev & 0xff) as u32,
+            ..Default::default()
+        }
+    }
+}
+
+fn get_statx_attributes(abs_path: &str, info: &Metadata) -> StatxAttributes {
+    match sefs::get_file_protection(abs_path, info.inode as u64) {
+        Some(sefs::FileProtection::Encrypted) => StatxAttributes::STATX_ATTR_ENCRYPTED,
+        Some(sefs::FileProtection::IntegrityOnly) => StatxAttributes::STATX_ATTR_VERITY,
+        None => StatxAttributes::empty(),
+    }
+}
+
+pub fn do_statx(dirfd: Option<FileDesc>, path: &str, flags: StatxFlags) -> Result<Statx> {
+    info!(
+        "statx: dirfd: {:?}, path: {:?}, flags: {:?}",
+        dirfd, path, flags
+    );
+    let current_ref = process::get_current();
+    let current_process = current_ref.lock().unwrap();
+    if path.is_empty() {
+        if !flags.contains(StatxFlags::AT_EMPTY_PATH) {
+            return_errno!(ENOENT, "path is an empty string");
+        }
+        let fd = dirfd.ok_or_else(|| errno!(EBADF, "dirfd is not an fd"))?;
+        let file_ref = current_process.get_files().lock().unwrap().get(fd)?;
+        let info = file_ref.metadata()?;
+        let attributes = match file_ref.as_inode_file() {
+            Ok(inode_file) => get_statx_attributes(inode_file.get_abs_path(), &info),
+            Err(_) => StatxAttributes::empty(),
+        };
+        return Ok(Statx::new(info, attributes));
+    }
+    if dirfd.is_some() && !path.starts_with('/') {
+        // TODO: handle dirfd
+        return_errno!(ENOSYS, "cannot accept dirfd");
+    }
+    let inode = if flags.contains(StatxFlags::AT_SYMLINK_NOFOLLOW) {
+        current_process.lookup_inode(path)?
+    } else {
+        current_process.lookup_inode_follow(path)?
+    };
+    let info = inode.metadata()?;
+    let abs_path = current_process.convert_to_abs_path(path);
+    let attributes = get_statx_attributes(&abs_path, &info);
+    Ok(Statx::new(info, attributes))
+}
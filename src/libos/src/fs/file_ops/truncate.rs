@@ -2,9 +2,29 @@ use super::*;
 
 pub fn do_truncate(path: &str, len: usize) -> Result<()> {
     info!("truncate: path: {:?}, len: {}", path, len);
-    let current_ref = process::get_current();
-    let current_process = current_ref.lock().unwrap();
-    current_process.lookup_inode(&path)?.resize(len)?;
+    let inode = {
+        let current_ref = process::get_current();
+        let current_process = current_ref.lock().unwrap();
+        let inode = current_process.lookup_inode_follow(&path)?;
+        if inode.metadata()?.type_ == FileType::Dir {
+            return_errno!(EISDIR, "cannot truncate a directory");
+        }
+        sefs::check_not_read_only(&inode)?;
+        if !inode.allow_write(&current_process.get_credentials().lock().unwrap())? {
+            return_errno!(EACCES, "File not writable. Can't truncate.");
+        }
+        current_process
+            .get_rlimits()
+            .lock()
+            .unwrap()
+            .check_file_size(len)?;
+        inode
+    };
+    // The break may wait for the lease holders, which may be the other
+    // threads of the process, so the process is not kept locked
+    LeaseRef::break_for_truncate(&inode)?;
+    inode.resize(len)?;
+    notify_modified(&inode, &InodeKey::new(&inode)?);
     Ok(())
 }
 
@@ -13,6 +33,11 @@ pub fn do_ftruncate(fd: FileDesc, len: usize) -> Result<()> {
     let current_ref = process::get_current();
     let current_process = current_ref.lock().unwrap();
     let file_ref = current_process.get_files().lock().unwrap().get(fd)?;
+    current_process
+        .get_rlimits()
+        .lock()
+        .unwrap()
+        .check_file_size(len)?;
     file_ref.set_len(len as u64)?;
     Ok(())
 }
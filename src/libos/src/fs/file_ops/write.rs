@@ -2,24 +2,139 @@ use super::*;
 
 pub fn do_write(fd: FileDesc, buf: &[u8]) -> Result<usize> {
     info!("write: fd: {}", fd);
-    let current_ref = process::get_current();
-    let current_process = current_ref.lock().unwrap();
-    let file_ref = current_process.get_files().lock().unwrap().get(fd)?;
-    file_ref.write(buf)
+    let file_ref = get_file(fd)?;
+    // Only the regular files are subject to RLIMIT_FSIZE
+    if let Ok(inode_file) = file_ref.as_inode_file() {
+        let size_limit = get_file_size_limit();
+        return raise_sigxfsz_on_efbig(write_in_chunks(buf, |chunk, _| {
+            inode_file.write_with_limit(chunk, size_limit)
+        }));
+    }
+    write_in_chunks(buf, |chunk, _| file_ref.write(chunk))
 }
 
 pub fn do_writev(fd: FileDesc, bufs: &[&[u8]]) -> Result<usize> {
     info!("writev: fd: {}", fd);
-    let current_ref = process::get_current();
-    let current_process = current_ref.lock().unwrap();
-    let file_ref = current_process.get_files().lock().unwrap().get(fd)?;
-    file_ref.writev(bufs)
+    let file_ref = get_file(fd)?;
+    if let Ok(inode_file) = file_ref.as_inode_file() {
+        let size_limit = get_file_size_limit();
+        return raise_sigxfsz_on_efbig(writev_in_chunks(bufs, |chunk_bufs, _| {
+            inode_file.writev_with_limit(chunk_bufs, size_limit)
+        }));
+    }
+    writev_in_chunks(bufs, |chunk_bufs, _| file_ref.writev(chunk_bufs))
 }
 
 pub fn do_pwrite(fd: FileDesc, buf: &[u8], offset: usize) -> Result<usize> {
     info!("pwrite: fd: {}, offset: {}", fd, offset);
+    let file_ref = get_file(fd)?;
+    if let Ok(inode_file) = file_ref.as_inode_file() {
+        let size_limit = get_file_size_limit();
+        return raise_sigxfsz_on_efbig(write_in_chunks(buf, |chunk, done_len| {
+            inode_file.write_at_with_limit(offset + done_len, chunk, size_limit)
+        }));
+    }
+    write_in_chunks(buf, |chunk, done_len| {
+        file_ref.write_at(offset + done_len, chunk)
+    })
+}
+
+/// Write the buffers to the file at the offset, without changing the file
+/// offset
+pub fn do_pwritev(fd: FileDesc, bufs: &[&[u8]], offset: usize) -> Result<usize> {
+    info!("pwritev: fd: {}, offset: {}", fd, offset);
+    let file_ref = get_file(fd)?;
+    let inode_file = file_ref
+        .as_inode_file()
+        .map_err(|_| errno!(ESPIPE, "the file does not support positional I/O"))?;
+    let size_limit = get_file_size_limit();
+    raise_sigxfsz_on_efbig(writev_in_chunks(bufs, |chunk_bufs, done_len| {
+        inode_file.writev_at_with_limit(offset + done_len, chunk_bufs, size_limit)
+    }))
+}
+
+/// Write the buffer in chunks of at most `max_io_size` bytes of the config,
+/// so that a huge write does not take a bounce buffer of the same size at
+/// once, e.g., in the OCall to the host. The function writes a chunk at the
+/// offset in the buffer.
+///
+/// The chunks are written until a short write. The writes of at most
+/// PIPE_BUF bytes are never split, so they stay atomic for pipes. If a chunk
+/// fails after some data is written, the length of the data is returned as
+/// the result, like a short write.
+fn write_in_chunks<F>(buf: &[u8], mut write_fn: F) -> Result<usize>
+where
+    F: FnMut(&[u8], usize) -> Result<usize>,
+{
+    let max_io_size = config::LIBOS_CONFIG.fs.max_io_size;
+    if buf.len() <= max_io_size {
+        return write_fn(buf, 0);
+    }
+    let mut total_len = 0;
+    for chunk in buf.chunks(max_io_size) {
+        match write_fn(chunk, total_len) {
+            Ok(len) => {
+                total_len += len;
+                if len < chunk.len() {
+                    break;
+                }
+            }
+            Err(_) if total_len != 0 => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(total_len)
+}
+
+/// Write the buffers in chunks of at most `max_io_size` bytes in total, as
+/// `write_in_chunks` does for a single buffer. The buffers of a chunk are
+/// written by one call of `write_fn`.
+fn writev_in_chunks<F>(bufs: &[&[u8]], mut write_fn: F) -> Result<usize>
+where
+    F: FnMut(&[&[u8]], usize) -> Result<usize>,
+{
+    let max_io_size = config::LIBOS_CONFIG.fs.max_io_size;
+    let total_bytes: usize = bufs.iter().map(|buf| buf.len()).sum();
+    if total_bytes <= max_io_size {
+        return write_fn(bufs, 0);
+    }
+    let mut total_len = 0;
+    for chunk in net::split_iovs(bufs, max_io_size) {
+        let chunk_len: usize = chunk.iter().map(|buf| buf.len()).sum();
+        match write_fn(&chunk, total_len) {
+            Ok(len) => {
+                total_len += len;
+                if len < chunk_len {
+                    break;
+                }
+            }
+            Err(_) if total_len != 0 => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(total_len)
+}
+
+/// Raise SIGXFSZ if a write to a regular file fails for RLIMIT_FSIZE, i.e.,
+/// with EFBIG. A write that is cut short by the limit is not signaled.
+fn raise_sigxfsz_on_efbig(res: Result<usize>) -> Result<usize> {
+    if let Err(ref e) = res {
+        if e.errno() == EFBIG {
+            process::raise_signal(process::SIGXFSZ);
+        }
+    }
+    res
+}
+
+/// Get RLIMIT_FSIZE of the current process, which is not kept locked during
+/// the write, as a write to a pipe or a socket may block
+fn get_file_size_limit() -> usize {
     let current_ref = process::get_current();
     let current_process = current_ref.lock().unwrap();
-    let file_ref = current_process.get_files().lock().unwrap().get(fd)?;
-    file_ref.write_at(offset, buf)
+    let size_limit = current_process
+        .get_rlimits()
+        .lock()
+        .unwrap()
+        .get_file_size_limit();
+    size_limit
 }
@@ -2,21 +2,37 @@ use super::*;
 
 pub type FileDesc = u32;
 
-#[derive(Debug, Default, Clone)]
+/// The max number of the file descriptors of a process, like nr_open of Linux,
+/// which caps RLIMIT_NOFILE so that a large fd cannot grow the table unbounded
+pub const MAX_NUM_FDS: usize = 1 << 20;
+
+/// The file table grows on demand, up to the soft limit of RLIMIT_NOFILE, and
+/// the lowest free file descriptor is found by the bitmaps of the slots instead
+/// of scanning the table.
+#[derive(Debug, Clone)]
 #[repr(C)]
 pub struct FileTable {
     table: Vec<Option<FileTableEntry>>,
-    num_fds: usize,
+    slots: SlotBitmap,
+    max_fds: usize,
 }
 
 impl FileTable {
     pub fn new() -> FileTable {
         FileTable {
             table: Vec::with_capacity(4),
-            num_fds: 0,
+            slots: Default::default(),
+            max_fds: MAX_NUM_FDS,
         }
     }
 
+    /// Set the max number of the file descriptors, i.e., the soft limit of
+    /// RLIMIT_NOFILE. The file descriptors beyond the limit are kept, but no
+    /// more are allocated beyond it.
+    pub fn set_max_fds(&mut self, max_fds: usize) {
+        self.max_fds = max_fds.min(MAX_NUM_FDS);
+    }
+
     pub fn dup(
         &mut self,
         fd: FileDesc,
@@ -24,64 +40,35 @@ impl FileTable {
         close_on_spawn: bool,
     ) -> Result<FileDesc> {
         let file_ref = self.get(fd)?;
-
-        let min_fd = min_fd as usize;
-        let min_free_fd = {
-            let mut table = &mut self.table;
-
-            // Make sure that min_fd does not exceed the capacity of the table
-            if min_fd >= table.len() {
-                let expand_size = min_fd - table.len() + 1;
-                for _ in 0..expand_size {
-                    table.push(None)
-                }
-            }
-
-            table
-                .iter()
-                .enumerate()
-                .skip(min_fd as usize)
-                .find(|&(idx, opt)| opt.is_none())
-                .unwrap()
-                .0
-        } as FileDesc;
-
-        self.put_at(min_free_fd, file_ref, close_on_spawn);
-
-        Ok(min_free_fd)
+        if min_fd as usize >= self.max_fds {
+            return_errno!(EINVAL, "min_fd exceeds RLIMIT_NOFILE");
+        }
+        let min_free_fd = self.find_free_fd(min_fd as usize)?;
+        let entry = FileTableEntry::new(file_ref, close_on_spawn);
+        self.set_entry(min_free_fd, Some(entry));
+        Ok(min_free_fd as FileDesc)
     }
 
-    pub fn put(&mut self, file: FileRef, close_on_spawn: bool) -> FileDesc {
-        let mut table = &mut self.table;
-
-        let min_free_fd = if self.num_fds < table.len() {
-            table
-                .iter()
-                .enumerate()
-                .find(|&(idx, opt)| opt.is_none())
-                .unwrap()
-                .0
-        } else {
-            table.push(None);
-            table.len() - 1
-        };
-
-        table[min_free_fd as usize] = Some(FileTableEntry::new(file, close_on_spawn));
-        self.num_fds += 1;
-
-        min_free_fd as FileDesc
+    pub fn put(&mut self, file: FileRef, close_on_spawn: bool) -> Result<FileDesc> {
+        let min_free_fd = self.find_free_fd(0)?;
+        self.set_entry(min_free_fd, Some(FileTableEntry::new(file, close_on_spawn)));
+        Ok(min_free_fd as FileDesc)
     }
 
-    pub fn put_at(&mut self, fd: FileDesc, file: FileRef, close_on_spawn: bool) {
-        let mut table = &mut self.table;
-        let mut table_entry = Some(FileTableEntry::new(file, close_on_spawn));
-        if fd as usize >= table.len() {
-            table.resize(fd as usize + 1, None);
-        }
-        std::mem::swap(&mut table_entry, &mut table[fd as usize]);
-        if table_entry.is_none() {
-            self.num_fds += 1;
+    /// Put the file at the fd, returning the file that was open at the fd, if
+    /// any, which is closed
+    pub fn put_at(
+        &mut self,
+        fd: FileDesc,
+        file: FileRef,
+        close_on_spawn: bool,
+    ) -> Result<Option<FileRef>> {
+        if fd as usize >= self.max_fds {
+            return_errno!(EBADF, "the fd exceeds RLIMIT_NOFILE");
         }
+        let old_entry =
+            self.set_entry(fd as usize, Some(FileTableEntry::new(file, close_on_spawn)));
+        Ok(old_entry.map(|old_entry| old_entry.file))
     }
 
     pub fn get(&self, fd: FileDesc) -> Result<FileRef> {
@@ -89,6 +76,16 @@ impl FileTable {
         Ok(entry.file.clone())
     }
 
+    /// Get the file descriptors in use, in ascending order
+    pub fn get_fds(&self) -> Vec<FileDesc> {
+        self.table
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.is_some())
+            .map(|(fd, _)| fd as FileDesc)
+            .collect()
+    }
+
     pub fn get_entry(&self, fd: FileDesc) -> Result<&FileTableEntry> {
         if fd as usize >= self.table.len() {
             return_errno!(EBADF, "Invalid file descriptor");
@@ -118,35 +115,166 @@ impl FileTable {
             return_errno!(EBADF, "Invalid file descriptor");
         }
 
-        let mut del_table_entry = None;
-        let table = &mut self.table;
-        std::mem::swap(&mut del_table_entry, &mut table[fd as usize]);
-        match del_table_entry {
-            Some(del_table_entry) => {
-                self.num_fds -= 1;
-                Ok(del_table_entry.file)
-            }
+        match self.set_entry(fd as usize, None) {
+            Some(del_table_entry) => Ok(del_table_entry.file.clone()),
             None => return_errno!(EBADF, "Invalid file descriptor"),
         }
     }
 
+    /// Clone the file table without the file descriptors that are
+    /// close-on-spawn, which is cheaper than cloning the whole table and then
+    /// closing them
+    pub fn clone_for_spawn(&self) -> FileTable {
+        let table_len = self
+            .table
+            .iter()
+            .rposition(|entry| match entry {
+                Some(entry) => !entry.close_on_spawn,
+                None => false,
+            })
+            .map_or(0, |last_fd| last_fd + 1);
+        let mut file_table = FileTable {
+            table: Vec::with_capacity(table_len),
+            slots: Default::default(),
+            max_fds: self.max_fds,
+        };
+        for (fd, entry) in self.table[..table_len].iter().enumerate() {
+            match entry {
+                Some(entry) if !entry.close_on_spawn => {
+                    file_table.set_entry(fd, Some(entry.clone()));
+                }
+                _ => {}
+            }
+        }
+        file_table
+    }
+
     /// Remove file descriptors that are close-on-spawn
     pub fn close_on_spawn(&mut self) {
-        for entry in self.table.iter_mut() {
-            let need_close = if let Some(entry) = entry {
-                entry.close_on_spawn
-            } else {
-                false
+        for fd in 0..self.table.len() {
+            let need_close = match &self.table[fd] {
+                Some(entry) => entry.close_on_spawn,
+                None => false,
             };
             if need_close {
-                *entry = None;
-                self.num_fds -= 1;
+                self.set_entry(fd, None);
             }
         }
     }
+
+    fn find_free_fd(&self, min_fd: usize) -> Result<usize> {
+        let free_fd = self.slots.find_free(min_fd);
+        if free_fd >= self.max_fds {
+            return_errno!(EMFILE, "no free fd below RLIMIT_NOFILE");
+        }
+        Ok(free_fd)
+    }
+
+    /// Set the entry of the slot, growing the table if needed, and return the
+    /// old entry
+    fn set_entry(
+        &mut self,
+        fd: usize,
+        mut entry: Option<FileTableEntry>,
+    ) -> Option<FileTableEntry> {
+        if fd >= self.table.len() {
+            self.table.resize(fd + 1, None);
+        }
+        if entry.is_some() {
+            self.slots.set(fd);
+        } else {
+            self.slots.clear(fd);
+        }
+        std::mem::swap(&mut entry, &mut self.table[fd]);
+        entry
+    }
 }
 
-#[derive(Debug, Clone)]
+impl Default for FileTable {
+    fn default() -> FileTable {
+        FileTable::new()
+    }
+}
+
+/// The slots in use in the file table, as Linux keeps them by two levels of
+/// bitmaps: a bit for each slot, and a bit for each word of the slots, which
+/// is set if all the slots of the word are in use. So the lowest free slot is
+/// found by looking at a word of the second level for every 4096 slots.
+#[derive(Debug, Default, Clone)]
+struct SlotBitmap {
+    used_slots: Vec<u64>,
+    full_words: Vec<u64>,
+}
+
+const BITS_PER_WORD: usize = 64;
+
+impl SlotBitmap {
+    fn set(&mut self, slot: usize) {
+        let word_idx = slot / BITS_PER_WORD;
+        if word_idx >= self.used_slots.len() {
+            self.used_slots.resize(word_idx + 1, 0);
+            let num_full_words = (word_idx + BITS_PER_WORD) / BITS_PER_WORD;
+            self.full_words.resize(num_full_words, 0);
+        }
+        self.used_slots[word_idx] |= 1 << (slot % BITS_PER_WORD);
+        if self.used_slots[word_idx] == !0 {
+            self.full_words[word_idx / BITS_PER_WORD] |= 1 << (word_idx % BITS_PER_WORD);
+        }
+    }
+
+    fn clear(&mut self, slot: usize) {
+        let word_idx = slot / BITS_PER_WORD;
+        if word_idx >= self.used_slots.len() {
+            return;
+        }
+        self.used_slots[word_idx] &= !(1 << (slot % BITS_PER_WORD));
+        self.full_words[word_idx / BITS_PER_WORD] &= !(1 << (word_idx % BITS_PER_WORD));
+    }
+
+    /// Find the lowest free slot that is not below `min_slot`, which may be
+    /// beyond all the slots in the bitmap
+    fn find_free(&self, min_slot: usize) -> usize {
+        let num_slots = self.used_slots.len() * BITS_PER_WORD;
+        if min_slot >= num_slots {
+            return min_slot;
+        }
+
+        // The slots below min_slot are seen as in use
+        let word_idx = min_slot / BITS_PER_WORD;
+        let word = self.used_slots[word_idx] | low_bits(min_slot % BITS_PER_WORD);
+        if word != !0 {
+            return word_idx * BITS_PER_WORD + (!word).trailing_zeros() as usize;
+        }
+
+        // Find the next word that is not full
+        let mut next_word_idx = word_idx + 1;
+        while next_word_idx < self.used_slots.len() {
+            let full_word_idx = next_word_idx / BITS_PER_WORD;
+            let full_word =
+                self.full_words[full_word_idx] | low_bits(next_word_idx % BITS_PER_WORD);
+            if full_word != !0 {
+                let free_word_idx =
+                    full_word_idx * BITS_PER_WORD + (!full_word).trailing_zeros() as usize;
+                if free_word_idx >= self.used_slots.len() {
+                    break;
+                }
+                let free_word = self.used_slots[free_word_idx];
+                return free_word_idx * BITS_PER_WORD + (!free_word).trailing_zeros() as usize;
+            }
+            next_word_idx = (full_word_idx + 1) * BITS_PER_WORD;
+        }
+        num_slots
+    }
+}
+
+/// The word of which the lowest `num_bits` bits are set
+fn low_bits(num_bits: usize) -> u64 {
+    (1 << num_bits) - 1
+}
+
+/// An entry of the file table, i.e., a file descriptor of the file, which is
+/// counted by the file if the operations on it may block (see `FdCount`)
+#[derive(Debug)]
 pub struct FileTableEntry {
     file: FileRef,
     close_on_spawn: bool,
@@ -154,6 +282,9 @@ pub struct FileTableEntry {
 
 impl FileTableEntry {
     pub fn new(file: FileRef, close_on_spawn: bool) -> FileTableEntry {
+        if let Some(fd_count) = file.fd_count() {
+            fd_count.inc();
+        }
         FileTableEntry {
             file,
             close_on_spawn,
@@ -168,11 +299,25 @@ impl FileTableEntry {
         self.close_on_spawn
     }
 
-    pub fn get_file_mut(&mut self) -> &mut FileRef {
-        &mut self.file
-    }
-
     pub fn set_close_on_spawn(&mut self, close_on_spawn: bool) {
         self.close_on_spawn = close_on_spawn;
     }
 }
+
+impl Clone for FileTableEntry {
+    fn clone(&self) -> FileTableEntry {
+        FileTableEntry::new(self.file.clone(), self.close_on_spawn)
+    }
+}
+
+impl Drop for FileTableEntry {
+    fn drop(&mut self) {
+        // Closing the last file descriptor of a file interrupts the operations
+        // blocked on it, which would never return otherwise
+        if let Some(fd_count) = self.file.fd_count() {
+            if fd_count.dec() {
+                self.file.interrupt_blocked_ops();
+            }
+        }
+    }
+}
@@ -1,5 +1,14 @@
+use super::proc_fs::MountNamespaceFile;
+use super::ramfs::LimitedRamFS;
 use super::*;
+use process::Process;
 
+pub use self::mount::{do_mount, MountFlags};
+pub use self::namespace::{do_setns, do_unshare};
+pub use self::statfs::{do_fstatfs, do_statfs, Statfs};
 pub use self::sync::do_sync;
 
+mod mount;
+mod namespace;
+mod statfs;
 mod sync;
@@ -0,0 +1,118 @@
+use super::*;
+use process::Capabilities;
+use std::path::Path;
+
+bitflags! {
+    pub struct MountFlags: u32 {
+        const MS_RDONLY         = 1;
+        const MS_NOSUID         = 2;
+        const MS_NODEV          = 4;
+        const MS_NOEXEC         = 8;
+        const MS_SYNCHRONOUS    = 16;
+        const MS_REMOUNT        = 32;
+        const MS_MANDLOCK       = 64;
+        const MS_DIRSYNC        = 128;
+        const MS_NOATIME        = 1024;
+        const MS_NODIRATIME     = 2048;
+        const MS_BIND           = 4096;
+        const MS_MOVE           = 8192;
+        const MS_REC            = 16384;
+        const MS_SILENT         = 32768;
+        const MS_UNBINDABLE     = 1 << 17;
+        const MS_PRIVATE        = 1 << 18;
+        const MS_SLAVE          = 1 << 19;
+        const MS_SHARED         = 1 << 20;
+        const MS_RELATIME       = 1 << 21;
+        const MS_STRICTATIME    = 1 << 24;
+        const MS_LAZYTIME       = 1 << 25;
+    }
+}
+
+impl MountFlags {
+    pub fn from_u32(bits: u32) -> Result<MountFlags> {
+        MountFlags::from_bits(bits).ok_or_else(|| errno!(EINVAL, "invalid mount flags"))
+    }
+
+    /// The flags that do not matter in Occlum, e.g., there are no device files
+    /// or setuid programs on the file systems
+    fn ignored() -> MountFlags {
+        MountFlags::MS_NOSUID | MountFlags::MS_NODEV | MountFlags::MS_SILENT
+    }
+
+    fn atime_flags() -> MountFlags {
+        MountFlags::MS_NOATIME | MountFlags::MS_RELATIME | MountFlags::MS_STRICTATIME
+    }
+
+    fn propagation_types() -> MountFlags {
+        MountFlags::MS_UNBINDABLE
+            | MountFlags::MS_PRIVATE
+            | MountFlags::MS_SLAVE
+            | MountFlags::MS_SHARED
+    }
+}
+
+/// Mount a file system at the target in the mount namespace of the current
+/// process, which requires CAP_SYS_ADMIN. Only RamFS can be mounted.
+///
+/// Since all the mounts are private, i.e., not propagated to the other mount
+/// namespaces, changing the propagation type to MS_PRIVATE is a no-op.
+///
+/// A mount point can be remounted (MS_REMOUNT) to change its atime policy,
+/// which is relatime unless MS_NOATIME or MS_STRICTATIME is given, as in Linux.
+/// It is also made read-only by the remount with MS_RDONLY, and writable by
+/// the one without it.
+pub fn do_mount(source: &str, target: &str, fs_type: &str, flags: MountFlags) -> Result<()> {
+    info!(
+        "mount: source: {:?}, target: {:?}, fs_type: {:?}, flags: {:?}",
+        source, target, fs_type, flags
+    );
+    let current_ref = process::get_current();
+    let current = current_ref.lock().unwrap();
+    if !current
+        .get_credentials()
+        .lock()
+        .unwrap()
+        .has_capability(Capabilities::SYS_ADMIN)
+    {
+        return_errno!(EPERM, "mount requires CAP_SYS_ADMIN");
+    }
+    let abs_target = current.convert_to_abs_path(target);
+    // Check that the target exists, as the propagation is changed on it
+    current.lookup_inode(&abs_target)?;
+
+    let flags = flags - MountFlags::ignored();
+    if flags.contains(MountFlags::MS_REMOUNT) {
+        let changeable_flags =
+            MountFlags::MS_REMOUNT | MountFlags::MS_RDONLY | MountFlags::atime_flags();
+        if !changeable_flags.contains(flags) {
+            return_errno!(EINVAL, "only the atime policy and read-only can be changed");
+        }
+        let atime_policy = if flags.contains(MountFlags::MS_NOATIME) {
+            AtimePolicy::No
+        } else if flags.contains(MountFlags::MS_STRICTATIME) {
+            AtimePolicy::Strict
+        } else {
+            AtimePolicy::Relative
+        };
+        let read_only = flags.contains(MountFlags::MS_RDONLY);
+        // TODO: remount per mount namespace, as the SEFS mounts are global
+        return sefs::remount(Path::new(&abs_target), atime_policy, read_only);
+    }
+    if flags.intersects(MountFlags::propagation_types()) {
+        if flags - MountFlags::MS_REC != MountFlags::MS_PRIVATE {
+            return_errno!(EINVAL, "only the private propagation type is supported");
+        }
+        return Ok(());
+    }
+    if !flags.is_empty() {
+        return_errno!(EINVAL, "unsupported mount flags");
+    }
+
+    let fs: Arc<dyn FileSystem> = match fs_type {
+        "ramfs" => LimitedRamFS::new(None),
+        _ => return_errno!(ENODEV, "unsupported file system type"),
+    };
+    // TODO: register the mount to SEFS, which does not know the mount points
+    // of the mount namespaces yet
+    current.get_mnt_ns().mount(fs, Path::new(&abs_target))
+}
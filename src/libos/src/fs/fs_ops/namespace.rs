@@ -0,0 +1,76 @@
+use super::*;
+use process::CloneFlags;
+
+/// Move the current process to a copy of its namespaces, so that the changes
+/// made later, e.g., mounts, are not seen by the other processes.
+///
+/// Only the mount namespace, i.e., CLONE_NEWNS, is supported.
+pub fn do_unshare(flags: CloneFlags) -> Result<()> {
+    info!("unshare: flags: {:?}", flags);
+    if !CloneFlags::CLONE_NEWNS.contains(flags) {
+        return_errno!(EINVAL, "only the mount namespace can be unshared");
+    }
+    if flags.is_empty() {
+        return Ok(());
+    }
+    check_namespaces_allowed()?;
+
+    let current_ref = process::get_current();
+    let mut current = current_ref.lock().unwrap();
+    let new_mnt_ns = Arc::new(current.get_mnt_ns().copy()?);
+    let cwd_path = current.get_cwd_path();
+    enter_mnt_ns(&mut current, new_mnt_ns, &cwd_path);
+    Ok(())
+}
+
+/// Move the current process to the namespace that the fd refers to, i.e., a
+/// file of /proc/[pid]/ns/mnt.
+///
+/// `nstype` is CLONE_NEWNS, or 0 to allow any type of namespace.
+pub fn do_setns(fd: FileDesc, nstype: CloneFlags) -> Result<()> {
+    info!("setns: fd: {}, nstype: {:?}", fd, nstype);
+    if !nstype.is_empty() && nstype != CloneFlags::CLONE_NEWNS {
+        return_errno!(EINVAL, "only the mount namespace can be joined");
+    }
+    check_namespaces_allowed()?;
+
+    let current_ref = process::get_current();
+    let mut current = current_ref.lock().unwrap();
+    let file_ref = current.get_files().lock().unwrap().get(fd)?;
+    let mnt_ns = file_ref
+        .as_any()
+        .downcast_ref::<MountNamespaceFile>()
+        .ok_or_else(|| errno!(EINVAL, "the fd does not refer to a namespace"))?
+        .get_mnt_ns()
+        .clone();
+    // The cwd is reset to the root of the joined namespace, as in Linux
+    enter_mnt_ns(&mut current, mnt_ns, "/");
+    Ok(())
+}
+
+fn check_namespaces_allowed() -> Result<()> {
+    if !config::LIBOS_CONFIG.process.allow_namespaces {
+        return_errno!(EPERM, "namespaces are not allowed by the config");
+    }
+    Ok(())
+}
+
+/// Move the process to the mount namespace, and change the cwd to the path
+/// in the namespace, or the root if the path cannot be found there
+fn enter_mnt_ns(process: &mut Process, mnt_ns: Arc<MountNamespace>, cwd_path: &str) {
+    process.set_mnt_ns(mnt_ns);
+    match process.lookup_inode(cwd_path) {
+        Ok(inode) if is_dir(&inode) => process.change_cwd(cwd_path, inode),
+        _ => {
+            let root_inode = process.get_mnt_ns().root_inode();
+            process.change_cwd("/", root_inode);
+        }
+    }
+}
+
+fn is_dir(inode: &Arc<dyn INode>) -> bool {
+    inode
+        .metadata()
+        .map(|info| info.type_ == FileType::Dir)
+        .unwrap_or(false)
+}
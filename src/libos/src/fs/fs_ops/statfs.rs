@@ -0,0 +1,125 @@
+use super::*;
+use config::ConfigMountFsType;
+use rcore_fs::vfs::FsInfo;
+use std::path::Path;
+
+/// The magic numbers of the file systems, as reported by f_type.
+///
+/// SEFS is specific to Occlum, so it uses a magic made up of "SEFS" in ASCII.
+/// The others are the same as Linux.
+pub const SEFS_MAGIC: i64 = 0x5345_4653;
+pub const HOSTFS_MAGIC: i64 = 0x00c0_ffee;
+pub const RAMFS_MAGIC: i64 = 0x8584_58f6;
+
+/// The mount flag of f_flags for a read-only file system
+const ST_RDONLY: i64 = 1;
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Statfs {
+    /// type of filesystem
+    f_type: i64,
+    /// optimal transfer block size
+    f_bsize: i64,
+    /// total data blocks in filesystem
+    f_blocks: u64,
+    /// free blocks in filesystem
+    f_bfree: u64,
+    /// free blocks available to unprivileged user
+    f_bavail: u64,
+    /// total file nodes in filesystem
+    f_files: u64,
+    /// free file nodes in filesystem
+    f_ffree: u64,
+    /// filesystem ID
+    f_fsid: [i32; 2],
+    /// maximum length of filenames
+    f_namelen: i64,
+    /// fragment size
+    f_frsize: i64,
+    /// mount flags of filesystem
+    f_flags: i64,
+    /// padding
+    f_spare: [i64; 4],
+}
+
+impl Statfs {
+    pub fn from_fs_info(f_type: i64, info: &FsInfo) -> Statfs {
+        Statfs {
+            f_type,
+            f_bsize: info.bsize as i64,
+            f_blocks: info.blocks as u64,
+            f_bfree: info.bfree as u64,
+            f_bavail: info.bavail as u64,
+            f_files: info.files as u64,
+            f_ffree: info.ffree as u64,
+            f_namelen: info.namemax as i64,
+            f_frsize: info.frsize as i64,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<Statfs> for FsInfo {
+    fn from(statfs: Statfs) -> FsInfo {
+        FsInfo {
+            bsize: statfs.f_bsize as usize,
+            frsize: statfs.f_frsize as usize,
+            blocks: statfs.f_blocks as usize,
+            bfree: statfs.f_bfree as usize,
+            bavail: statfs.f_bavail as usize,
+            files: statfs.f_files as usize,
+            ffree: statfs.f_ffree as usize,
+            namemax: statfs.f_namelen as usize,
+        }
+    }
+}
+
+pub fn do_statfs(path: &str) -> Result<Statfs> {
+    info!("statfs: path: {}", path);
+    let (inode, abs_path) = {
+        let current_ref = process::get_current();
+        let current = current_ref.lock().unwrap();
+        let inode = current.lookup_inode_follow(path)?;
+        (inode, current.convert_to_abs_path(path))
+    };
+    statfs_of_inode(&inode, &abs_path)
+}
+
+pub fn do_fstatfs(fd: FileDesc) -> Result<Statfs> {
+    info!("fstatfs: fd: {}", fd);
+    let file_ref = {
+        let current_ref = process::get_current();
+        let current = current_ref.lock().unwrap();
+        current.get_files().lock().unwrap().get(fd)?
+    };
+    let inode_file = file_ref
+        .as_inode_file()
+        .map_err(|_| errno!(EINVAL, "not a file on a file system"))?;
+    statfs_of_inode(inode_file.get_inode(), inode_file.get_abs_path())
+}
+
+fn statfs_of_inode(inode: &Arc<dyn INode>, abs_path: &str) -> Result<Statfs> {
+    let mut statfs = match mount_type_of(abs_path)? {
+        ConfigMountFsType::TYPE_SEFS => Statfs::from_fs_info(SEFS_MAGIC, &inode.fs().info()),
+        ConfigMountFsType::TYPE_HOSTFS => Statfs::from_fs_info(HOSTFS_MAGIC, &inode.fs().info()),
+        ConfigMountFsType::TYPE_RAMFS => Statfs::from_fs_info(RAMFS_MAGIC, &inode.fs().info()),
+    };
+    if sefs::is_read_only(inode) {
+        statfs.f_flags |= ST_RDONLY;
+    }
+    Ok(statfs)
+}
+
+/// Find the type of the file system that the path is on, i.e., the type of
+/// the innermost mount point that contains the path.
+fn mount_type_of(abs_path: &str) -> Result<ConfigMountFsType> {
+    let path = Path::new(abs_path);
+    config::LIBOS_CONFIG
+        .mount
+        .iter()
+        .filter(|mount| path.starts_with(&mount.target))
+        .max_by_key(|mount| mount.target.components().count())
+        .map(|mount| mount.type_)
+        .ok_or_else(|| errno!(ENOENT, "the path is not on any mounted file system"))
+}
@@ -1,13 +1,20 @@
 use alloc::string::String;
 use alloc::sync::{Arc, Weak};
 use core::any::Any;
+use core::ffi::c_void;
 use rcore_fs::vfs::*;
+use rcore_fs_mountfs::MNode;
+use sgx_trts::libc::{c_char, size_t};
+use sgx_types::sgx_status_t;
+use std::ffi::CString;
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use std::sync::{SgxMutex as Mutex, SgxMutexGuard as MutexGuard};
 use std::untrusted::fs;
 use std::untrusted::path::PathEx;
 
+use super::Statfs;
+
 /// Untrusted file system at host
 pub struct HostFS {
     path: PathBuf,
@@ -19,6 +26,9 @@ pub struct HNode {
     path: PathBuf,
     file: Mutex<Option<fs::File>>,
     fs: Arc<HostFS>,
+    // Whether the path is a symlink, which is known when the node is found,
+    // so that the reads need not ask the host for the type each time
+    is_symlink: bool,
 }
 
 impl FileSystem for HostFS {
@@ -32,12 +42,89 @@ impl FileSystem for HostFS {
             path: self.path.clone(),
             file: Mutex::new(None),
             fs: self.self_ref.upgrade().unwrap(),
+            is_symlink: false,
         })
     }
 
     fn info(&self) -> FsInfo {
-        unimplemented!()
+        match host_statfs(&self.path) {
+            Ok(statfs) => statfs.into(),
+            Err(e) => {
+                warn!("HostFS: failed to get the info of the host FS: {:?}", e);
+                Statfs::default().into()
+            }
+        }
+    }
+}
+
+fn host_statfs(path: &Path) -> Result<Statfs> {
+    let path = path.to_str().ok_or(FsError::InvalidParam)?;
+    let path = CString::new(path).map_err(|_| FsError::InvalidParam)?;
+    let mut statfs = Statfs::default();
+    let (sgx_status, retval) = unsafe {
+        let mut retval = 0;
+        let sgx_status = occlum_ocall_statfs(
+            &mut retval,
+            path.as_ptr(),
+            &mut statfs as *mut Statfs as *mut c_void,
+            core::mem::size_of::<Statfs>(),
+        );
+        (sgx_status, retval)
+    };
+    assert!(sgx_status == sgx_status_t::SGX_SUCCESS);
+    if retval < 0 {
+        return Err(FsError::DeviceError);
     }
+    Ok(statfs)
+}
+
+/// Read the names of the entries in a host directory
+pub(super) fn read_host_dir(path: &Path) -> Result<Vec<String>> {
+    let path = path.to_str().ok_or(FsError::InvalidParam)?;
+    let path = CString::new(path).map_err(|_| FsError::InvalidParam)?;
+    let mut buf = vec![0u8; 4096];
+    loop {
+        let mut retval: i64 = 0;
+        let sgx_status = unsafe {
+            occlum_ocall_read_dir(
+                &mut retval,
+                path.as_ptr(),
+                buf.as_mut_ptr() as *mut c_char,
+                buf.len(),
+            )
+        };
+        assert!(sgx_status == sgx_status_t::SGX_SUCCESS);
+        if retval < 0 {
+            return Err(FsError::DeviceError);
+        }
+        let total_len = retval as usize;
+        if total_len > buf.len() {
+            // The directory may grow between two calls, so try again
+            buf.resize(total_len, 0);
+            continue;
+        }
+        let names = buf[..total_len]
+            .split(|b| *b == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .collect();
+        return Ok(names);
+    }
+}
+
+extern "C" {
+    fn occlum_ocall_read_dir(
+        ret: *mut i64,
+        path: *const c_char,
+        buf: *mut c_char,
+        buf_len: size_t,
+    ) -> sgx_status_t;
+    fn occlum_ocall_statfs(
+        ret: *mut i32,
+        path: *const c_char,
+        buf: *mut c_void,
+        buf_len: size_t,
+    ) -> sgx_status_t;
 }
 
 impl HostFS {
@@ -74,6 +161,9 @@ macro_rules! try_std {
 
 impl INode for HNode {
     fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        if self.is_symlink {
+            return self.read_link_at(offset, buf);
+        }
         let mut guard = self.open_file()?;
         let file = guard.as_mut().unwrap();
         try_std!(file.seek(SeekFrom::Start(offset as u64)));
@@ -94,7 +184,8 @@ impl INode for HNode {
     }
 
     fn metadata(&self) -> Result<Metadata> {
-        let metadata = try_std!(self.path.metadata());
+        // The symlink is not followed, so that it can be seen, e.g., by lstat
+        let metadata = try_std!(self.path.symlink_metadata());
         Ok(metadata.into_fs_metadata())
     }
 
@@ -133,6 +224,7 @@ impl INode for HNode {
             path: new_path,
             file: Mutex::new(None),
             fs: self.fs.clone(),
+            is_symlink: false,
         }))
     }
 
@@ -161,13 +253,16 @@ impl INode for HNode {
 
     fn find(&self, name: &str) -> Result<Arc<dyn INode>> {
         let new_path = self.path.join(name);
-        if !new_path.exists() {
-            return Err(FsError::EntryNotFound);
-        }
+        // A dangling symlink is still an entry
+        let is_symlink = match new_path.symlink_metadata() {
+            Ok(metadata) => metadata.file_type().is_symlink(),
+            Err(_) => return Err(FsError::EntryNotFound),
+        };
         Ok(Arc::new(HNode {
             path: new_path,
             file: Mutex::new(None),
             fs: self.fs.clone(),
+            is_symlink,
         }))
     }
 
@@ -175,17 +270,10 @@ impl INode for HNode {
         if !self.path.is_dir() {
             return Err(FsError::NotDir);
         }
-        unimplemented!("no read_dir in sgx_std?")
-        // FIXME: read_dir
-
-        // self.path
-        //     .read_dir()
-        //     .map_err(|_| FsError::NotDir)?
-        //     .nth(id)
-        //     .map_err(|_| FsError::EntryNotFound)?
-        //     .file_name()
-        //     .into_string()
-        //     .map_err(|_| FsError::InvalidParam)
+        read_host_dir(&self.path)?
+            .into_iter()
+            .nth(id)
+            .ok_or(FsError::EntryNotFound)
     }
 
     fn io_control(&self, cmd: u32, data: usize) -> Result<()> {
@@ -203,6 +291,18 @@ impl INode for HNode {
 }
 
 impl HNode {
+    /// Read the target of the symlink as its content
+    fn read_link_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let target = try_std!(self.path.read_link());
+        let target = target.to_str().ok_or(FsError::InvalidParam)?.as_bytes();
+        if offset >= target.len() {
+            return Ok(0);
+        }
+        let len = buf.len().min(target.len() - offset);
+        buf[..len].copy_from_slice(&target[offset..offset + len]);
+        Ok(len)
+    }
+
     /// Ensure to open the file and store a `File` into `self.file`,
     /// return the `MutexGuard`.
     /// If the type of `self.path` is not file, then return Err
@@ -287,3 +387,25 @@ impl IntoFsMetadata for fs::Metadata {
         }
     }
 }
+
+/// List the entries of the directory at once if it is of HostFS, returning
+/// None if not. Unlike `get_entry`, which reads the whole host directory for
+/// each entry, the host directory is read only once.
+///
+/// Only HostFS lists its entries at once. The entries of the other file
+/// systems, e.g., SEFS and ramfs, are still read one by one by their indexes.
+pub fn list_entries(dir_inode: &Arc<dyn INode>) -> Result<Option<Vec<String>>> {
+    // The inodes looked up from the root are wrapped by MountFS
+    let inode = match dir_inode.downcast_ref::<MNode>() {
+        Some(mnode) => &mnode.inode,
+        None => dir_inode,
+    };
+    let hnode = match inode.downcast_ref::<HNode>() {
+        Some(hnode) => hnode,
+        None => return Ok(None),
+    };
+    if !hnode.path.is_dir() {
+        return Err(FsError::NotDir);
+    }
+    read_host_dir(&hnode.path).map(Some)
+}
@@ -1,6 +1,16 @@
+use super::file_ops::{
+    check_mode, notify_file_changed, notify_modified, release_file_locks, release_posix_locks,
+    set_file_lock, snapshot_dir_entries, test_file_lock, AccessibilityCheckMode, DnotifyEvents,
+    FallocateFlags, LeaseRef,
+};
+use super::ramfs::{self, FallocateOp};
 use super::*;
+use process::{pid_t, Capabilities, Credentials};
 use rcore_fs_sefs::dev::SefsMac;
-use sgx_trts::libc::{S_IRUSR, S_IWUSR};
+
+/// The alignment required for the buffer address, the length and the offset
+/// of direct I/O, which is the logical block size on Linux
+const DIRECT_IO_ALIGNMENT: usize = 512;
 
 pub struct INodeFile {
     inode: Arc<dyn INode>,
@@ -8,6 +18,10 @@ pub struct INodeFile {
     offset: SgxMutex<usize>,
     access_mode: AccessMode,
     status_flags: SgxRwLock<StatusFlags>,
+    lease_ref: LeaseRef,
+    // The snapshot of the entries of the directory, which are read by their
+    // indexes in the snapshot, i.e., the offset
+    dir_entries: SgxMutex<Option<Vec<String>>>,
 }
 
 impl File for INodeFile {
@@ -16,39 +30,29 @@ impl File for INodeFile {
             return_errno!(EBADF, "File not readable");
         }
         let mut offset = self.offset.lock().unwrap();
+        self.check_direct_io(buf.as_ptr() as usize, buf.len(), *offset)?;
         let len = self.inode.read_at(*offset, buf).map_err(|e| errno!(e))?;
         *offset += len;
+        self.touch_atime();
         Ok(len)
     }
 
     fn write(&self, buf: &[u8]) -> Result<usize> {
-        if !self.access_mode.writable() {
-            return_errno!(EBADF, "File not writable");
-        }
-        let mut offset = self.offset.lock().unwrap();
-        if self.status_flags.read().unwrap().always_append() {
-            let info = self.inode.metadata()?;
-            *offset = info.size;
-        }
-        let len = self.inode.write_at(*offset, buf)?;
-        *offset += len;
-        Ok(len)
+        self.write_with_limit(buf, usize::max_value())
     }
 
     fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
         if !self.access_mode.readable() {
             return_errno!(EBADF, "File not readable");
         }
+        self.check_direct_io(buf.as_ptr() as usize, buf.len(), offset)?;
         let len = self.inode.read_at(offset, buf)?;
+        self.touch_atime();
         Ok(len)
     }
 
     fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
-        if !self.access_mode.writable() {
-            return_errno!(EBADF, "File not writable");
-        }
-        let len = self.inode.write_at(offset, buf)?;
-        Ok(len)
+        self.write_at_with_limit(offset, buf, usize::max_value())
     }
 
     fn readv(&self, bufs: &mut [&mut [u8]]) -> Result<usize> {
@@ -56,6 +60,9 @@ impl File for INodeFile {
             return_errno!(EBADF, "File not readable");
         }
         let mut offset = self.offset.lock().unwrap();
+        for buf in bufs.iter() {
+            self.check_direct_io(buf.as_ptr() as usize, buf.len(), *offset)?;
+        }
         let mut total_len = 0;
         for buf in bufs {
             match self.inode.read_at(*offset, buf) {
@@ -67,30 +74,12 @@ impl File for INodeFile {
                 Err(e) => return Err(e.into()),
             }
         }
+        self.touch_atime();
         Ok(total_len)
     }
 
     fn writev(&self, bufs: &[&[u8]]) -> Result<usize> {
-        if !self.access_mode.writable() {
-            return_errno!(EBADF, "File not writable");
-        }
-        let mut offset = self.offset.lock().unwrap();
-        if self.status_flags.read().unwrap().always_append() {
-            let info = self.inode.metadata()?;
-            *offset = info.size;
-        }
-        let mut total_len = 0;
-        for buf in bufs {
-            match self.inode.write_at(*offset, buf) {
-                Ok(len) => {
-                    total_len += len;
-                    *offset += len;
-                }
-                Err(_) if total_len != 0 => break,
-                Err(e) => return Err(e.into()),
-            }
-        }
-        Ok(total_len)
+        self.writev_with_limit(bufs, usize::max_value())
     }
 
     fn seek(&self, pos: SeekFrom) -> Result<off_t> {
@@ -120,7 +109,10 @@ impl File for INodeFile {
         if !self.access_mode.writable() {
             return_errno!(EBADF, "File not writable. Can't set len.");
         }
+        sefs::check_not_read_only(&self.inode)?;
+        sefs::check_not_rekeying(&self.abs_path)?;
         self.inode.resize(len as usize)?;
+        self.notify_modified();
         Ok(())
     }
 
@@ -139,11 +131,30 @@ impl File for INodeFile {
             return_errno!(EBADF, "File not readable. Can't read entry.");
         }
         let mut offset = self.offset.lock().unwrap();
-        let name = self.inode.get_entry(*offset)?;
+        let mut dir_entries = self.dir_entries.lock().unwrap();
+        // A read from the start takes a new snapshot, so that the entries
+        // are not skipped or repeated due to concurrent updates. The offset in
+        // the snapshot can be restored by seek, like seekdir.
+        if *offset == 0 || dir_entries.is_none() {
+            *dir_entries = Some(snapshot_dir_entries(&self.inode)?);
+        }
+        let name = dir_entries
+            .as_ref()
+            .unwrap()
+            .get(*offset)
+            .cloned()
+            .ok_or_else(|| errno!(ENOENT, "no more entries"))?;
         *offset += 1;
         Ok(name)
     }
 
+    fn entry_ino(&self, name: &str) -> Result<u64> {
+        // The entry is found by the inode of the directory, so a mount point
+        // reports the root of the file system mounted on it, as stat does
+        let inode = self.inode.find(name)?;
+        Ok(inode.metadata()?.inode as u64)
+    }
+
     fn get_access_mode(&self) -> Result<AccessMode> {
         Ok(self.access_mode.clone())
     }
@@ -155,40 +166,74 @@ impl File for INodeFile {
 
     fn set_status_flags(&self, new_status_flags: StatusFlags) -> Result<()> {
         let mut status_flags = self.status_flags.write().unwrap();
-        // Currently, F_SETFL can change only the O_APPEND,
-        // O_ASYNC, O_NOATIME, and O_NONBLOCK flags
+        // Currently, F_SETFL can change only the O_APPEND, O_ASYNC, O_DIRECT,
+        // O_NOATIME and O_NONBLOCK flags, and the O_DSYNC and O_SYNC flags,
+        // which are ignored by F_SETFL of Linux
         let valid_flags_mask = StatusFlags::O_APPEND
             | StatusFlags::O_ASYNC
+            | StatusFlags::O_DIRECT
             | StatusFlags::O_NOATIME
-            | StatusFlags::O_NONBLOCK;
+            | StatusFlags::O_NONBLOCK
+            | StatusFlags::O_DSYNC
+            | StatusFlags::_O_SYNC;
         status_flags.remove(valid_flags_mask);
         status_flags.insert(new_status_flags & valid_flags_mask);
         Ok(())
     }
 
-    fn test_advisory_lock(&self, lock: &mut Flock) -> Result<()> {
-        // Let the advisory lock could be placed
-        // TODO: Implement the real advisory lock
-        lock.l_type = FlockType::F_UNLCK;
-        Ok(())
+    fn test_advisory_lock(&self, lock: &mut Flock, kind: FileLockKind) -> Result<()> {
+        let offset = *self.offset.lock().unwrap();
+        test_file_lock(&self.inode, kind, self.lease_ref.id(), offset, lock)
     }
 
-    fn set_advisory_lock(&self, lock: &Flock) -> Result<()> {
-        match lock.l_type {
-            FlockType::F_RDLCK => {
-                if !self.access_mode.readable() {
-                    return_errno!(EBADF, "File not readable");
+    fn set_advisory_lock(&self, lock: &Flock, kind: FileLockKind, wait: bool) -> Result<()> {
+        // The flock locks can be placed regardless of the access mode
+        if kind != FileLockKind::Flock {
+            match lock.l_type {
+                FlockType::F_RDLCK => {
+                    if !self.access_mode.readable() {
+                        return_errno!(EBADF, "File not readable");
+                    }
                 }
-            }
-            FlockType::F_WRLCK => {
-                if !self.access_mode.writable() {
-                    return_errno!(EBADF, "File not writable");
+                FlockType::F_WRLCK => {
+                    if !self.access_mode.writable() {
+                        return_errno!(EBADF, "File not writable");
+                    }
                 }
+                _ => (),
             }
-            _ => (),
         }
-        // Let the advisory lock could be acquired or released
-        // TODO: Implement the real advisory lock
+        let offset = *self.offset.lock().unwrap();
+        set_file_lock(&self.inode, kind, self.lease_ref.id(), offset, lock, wait)
+    }
+
+    fn set_lease(&self, type_: FlockType, pid: pid_t) -> Result<()> {
+        if self.inode.metadata()?.type_ != FileType::File {
+            return_errno!(EINVAL, "lease is only supported by regular files");
+        }
+        self.lease_ref.set_lease(type_, pid)
+    }
+
+    fn get_lease(&self) -> Result<FlockType> {
+        Ok(self.lease_ref.get_lease())
+    }
+
+    fn ioctl(&self, cmd: &mut IoctlCmd) -> Result<()> {
+        let nonbuiltin_cmd = match cmd {
+            IoctlCmd::NonBuiltin(nonbuiltin_cmd) => nonbuiltin_cmd,
+            _ => return_errno!(ENOTTY, "the ioctl is not supported by the file"),
+        };
+        let cmd_num = nonbuiltin_cmd.cmd_num().as_u32();
+        if sefs::is_sefs_ioctl(cmd_num) {
+            return sefs::do_ioctl(&self.abs_path, cmd);
+        }
+        // The commands used inside the LibOS take the pointers to the LibOS
+        // structures, which must not come from the user
+        if cmd_num == ramfs::IOC_FALLOCATE {
+            return_errno!(ENOTTY, "the ioctl is not supported by the file");
+        }
+        self.inode
+            .io_control(cmd_num, nonbuiltin_cmd.arg_ptr() as usize)?;
         Ok(())
     }
 
@@ -198,27 +243,320 @@ impl File for INodeFile {
 }
 
 impl INodeFile {
-    pub fn open(inode: Arc<dyn INode>, abs_path: &str, flags: u32) -> Result<Self> {
+    pub fn open(
+        inode: Arc<dyn INode>,
+        abs_path: &str,
+        flags: u32,
+        credentials: &Credentials,
+    ) -> Result<Self> {
         let access_mode = AccessMode::from_u32(flags)?;
-        if (access_mode.readable() && !inode.allow_read()?) {
-            return_errno!(EBADF, "File not readable");
+        if access_mode.writable() {
+            sefs::check_not_read_only(&inode)?;
         }
-        if (access_mode.writable() && !inode.allow_write()?) {
-            return_errno!(EBADF, "File not writable");
+        if (access_mode.readable() && !inode.allow_read(credentials)?) {
+            return_errno!(EACCES, "File not readable");
+        }
+        if (access_mode.writable() && !inode.allow_write(credentials)?) {
+            return_errno!(EACCES, "File not writable");
         }
         let status_flags = StatusFlags::from_bits_truncate(flags);
+        if status_flags.is_noatime() && !inode.allow_noatime(credentials)? {
+            return_errno!(EPERM, "O_NOATIME is only allowed for the owner of the file");
+        }
+        let lease_ref = LeaseRef::new(
+            &inode,
+            access_mode.writable(),
+            status_flags.contains(StatusFlags::O_NONBLOCK),
+        )?;
         Ok(INodeFile {
             inode,
             abs_path: abs_path.to_owned(),
             offset: SgxMutex::new(0),
             access_mode,
             status_flags: SgxRwLock::new(status_flags),
+            lease_ref,
+            dir_entries: SgxMutex::new(None),
         })
     }
 
+    /// Release the POSIX locks of the process on the inode, which happens on
+    /// closing any file descriptor of the inode
+    pub fn release_posix_locks(&self, pid: pid_t) {
+        release_posix_locks(&self.inode, pid);
+    }
+
+    /// Write the file, which can only be extended up to the size limit.
+    ///
+    /// The write is cut short at the limit. If no byte can be written, it
+    /// fails with EFBIG.
+    pub fn write_with_limit(&self, buf: &[u8], size_limit: usize) -> Result<usize> {
+        if !self.access_mode.writable() {
+            return_errno!(EBADF, "File not writable");
+        }
+        sefs::check_not_read_only(&self.inode)?;
+        sefs::check_not_rekeying(&self.abs_path)?;
+        let mut offset = self.offset.lock().unwrap();
+        if self.status_flags.read().unwrap().always_append() {
+            let info = self.inode.metadata()?;
+            *offset = info.size;
+        }
+        self.check_direct_io(buf.as_ptr() as usize, buf.len(), *offset)?;
+        let buf = limit_write_buf(buf, *offset, size_limit)?;
+        let len = self.inode.write_at(*offset, buf)?;
+        self.notify_modified();
+        // The offset is not advanced by a write that fails to sync
+        self.sync_written()?;
+        *offset += len;
+        Ok(len)
+    }
+
+    pub fn write_at_with_limit(
+        &self,
+        offset: usize,
+        buf: &[u8],
+        size_limit: usize,
+    ) -> Result<usize> {
+        if !self.access_mode.writable() {
+            return_errno!(EBADF, "File not writable");
+        }
+        sefs::check_not_read_only(&self.inode)?;
+        sefs::check_not_rekeying(&self.abs_path)?;
+        self.check_direct_io(buf.as_ptr() as usize, buf.len(), offset)?;
+        let buf = limit_write_buf(buf, offset, size_limit)?;
+        let len = self.inode.write_at(offset, buf)?;
+        self.notify_modified();
+        self.sync_written()?;
+        Ok(len)
+    }
+
+    pub fn writev_with_limit(&self, bufs: &[&[u8]], size_limit: usize) -> Result<usize> {
+        if !self.access_mode.writable() {
+            return_errno!(EBADF, "File not writable");
+        }
+        sefs::check_not_read_only(&self.inode)?;
+        sefs::check_not_rekeying(&self.abs_path)?;
+        let mut offset = self.offset.lock().unwrap();
+        if self.status_flags.read().unwrap().always_append() {
+            let info = self.inode.metadata()?;
+            *offset = info.size;
+        }
+        for buf in bufs {
+            self.check_direct_io(buf.as_ptr() as usize, buf.len(), *offset)?;
+        }
+        let mut total_len = 0;
+        for buf in bufs {
+            let write_offset = *offset + total_len;
+            let res = limit_write_buf(buf, write_offset, size_limit).and_then(|limited_buf| {
+                let len = self.inode.write_at(write_offset, limited_buf)?;
+                Ok((len, limited_buf.len() < buf.len()))
+            });
+            match res {
+                Ok((len, is_limited)) => {
+                    total_len += len;
+                    if is_limited {
+                        break;
+                    }
+                }
+                Err(_) if total_len != 0 => break,
+                Err(e) => return Err(e),
+            }
+        }
+        self.notify_modified();
+        // As for write, the offset is advanced only after the sync succeeds
+        self.sync_written()?;
+        *offset += total_len;
+        Ok(total_len)
+    }
+
+    /// Read the file into the buffers at the offset, without changing the file
+    /// offset. The buffers are read under the lock of the file offset, so that
+    /// the vector is not interleaved with the other vectored I/O on the file.
+    pub fn readv_at(&self, mut offset: usize, bufs: &mut [&mut [u8]]) -> Result<usize> {
+        if !self.access_mode.readable() {
+            return_errno!(EBADF, "File not readable");
+        }
+        let _offset_guard = self.offset.lock().unwrap();
+        for buf in bufs.iter() {
+            self.check_direct_io(buf.as_ptr() as usize, buf.len(), offset)?;
+        }
+        let mut total_len = 0;
+        for buf in bufs {
+            match self.inode.read_at(offset, buf) {
+                Ok(len) => {
+                    total_len += len;
+                    offset += len;
+                    if len < buf.len() {
+                        break;
+                    }
+                }
+                Err(_) if total_len != 0 => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        self.touch_atime();
+        Ok(total_len)
+    }
+
+    /// Write the buffers to the file at the offset, without changing the file
+    /// offset, like `readv_at`
+    pub fn writev_at_with_limit(
+        &self,
+        mut offset: usize,
+        bufs: &[&[u8]],
+        size_limit: usize,
+    ) -> Result<usize> {
+        if !self.access_mode.writable() {
+            return_errno!(EBADF, "File not writable");
+        }
+        sefs::check_not_read_only(&self.inode)?;
+        sefs::check_not_rekeying(&self.abs_path)?;
+        let _offset_guard = self.offset.lock().unwrap();
+        for buf in bufs {
+            self.check_direct_io(buf.as_ptr() as usize, buf.len(), offset)?;
+        }
+        let mut total_len = 0;
+        for buf in bufs {
+            let res = limit_write_buf(buf, offset, size_limit).and_then(|limited_buf| {
+                let len = self.inode.write_at(offset, limited_buf)?;
+                Ok((len, limited_buf.len() < buf.len()))
+            });
+            match res {
+                Ok((len, is_limited)) => {
+                    total_len += len;
+                    offset += len;
+                    if is_limited {
+                        break;
+                    }
+                }
+                Err(_) if total_len != 0 => break,
+                Err(e) => return Err(e),
+            }
+        }
+        self.notify_modified();
+        self.sync_written()?;
+        Ok(total_len)
+    }
+
+    /// Check the alignment of the buffer address, the length and the offset
+    /// of an I/O, if the file is opened for direct I/O.
+    ///
+    /// Only the write-through of direct I/O is provided, i.e., the data
+    /// written is synced before the write returns. The reads are still served
+    /// from the caches of the file systems, e.g., the cache of the SGX
+    /// protected files of SEFS, which cannot be bypassed.
+    fn check_direct_io(&self, buf_addr: usize, len: usize, offset: usize) -> Result<()> {
+        if !self.status_flags.read().unwrap().is_direct() {
+            return Ok(());
+        }
+        if buf_addr % DIRECT_IO_ALIGNMENT != 0
+            || len % DIRECT_IO_ALIGNMENT != 0
+            || offset % DIRECT_IO_ALIGNMENT != 0
+        {
+            return_errno!(EINVAL, "direct I/O must be block-aligned");
+        }
+        Ok(())
+    }
+
+    /// Update the access time after a read, unless the file is opened with
+    /// O_NOATIME. A failure to update is not an error of the read.
+    fn touch_atime(&self) {
+        notify_file_changed(&self.abs_path, DnotifyEvents::DN_ACCESS);
+        if self.status_flags.read().unwrap().is_noatime() {
+            return;
+        }
+        if let Err(e) = sefs::touch_atime(&self.abs_path, &self.inode) {
+            warn!("failed to update the access time: {}", e);
+        }
+    }
+
+    /// Notify the watchers of the file and of its directory that the file is
+    /// modified
+    fn notify_modified(&self) {
+        notify_modified(&self.inode, self.lease_ref.key());
+        notify_file_changed(&self.abs_path, DnotifyEvents::DN_MODIFY);
+    }
+
+    /// Write the data written through to the backing store, if the file is
+    /// opened with O_SYNC, O_DSYNC or O_DIRECT. O_SYNC syncs the metadata as
+    /// well, while the others sync the data only, with the metadata needed to
+    /// read it. A failure to sync fails the write with EIO, as the data may
+    /// not be durable.
+    ///
+    /// The reads and writes of all opens of the inode go through the same
+    /// inode, so direct and buffered I/O are coherent.
+    fn sync_written(&self) -> Result<()> {
+        let status_flags = *self.status_flags.read().unwrap();
+        let sync_result = if status_flags.is_sync() {
+            self.inode.sync_all()
+        } else if status_flags.is_dsync() || status_flags.is_direct() {
+            self.inode.sync_data()
+        } else {
+            return Ok(());
+        };
+        sync_result.map_err(|_| errno!(EIO, "failed to sync the written data"))
+    }
+
+    /// Allocate or deallocate the space of `[offset, offset + len)`
+    pub fn fallocate(&self, flags: FallocateFlags, offset: usize, len: usize) -> Result<()> {
+        if !self.access_mode.writable() {
+            return_errno!(EBADF, "File not writable. Can't fallocate.");
+        }
+        sefs::check_not_read_only(&self.inode)?;
+        sefs::check_not_rekeying(&self.abs_path)?;
+        let op = if flags.contains(FallocateFlags::FALLOC_FL_PUNCH_HOLE) {
+            FallocateOp::PunchHole
+        } else {
+            FallocateOp::Allocate {
+                keep_size: !flags.may_extend(),
+            }
+        };
+        if ramfs::fallocate(&self.inode, op, offset, len)? {
+            self.notify_modified();
+            return Ok(());
+        }
+        // The other file systems allocate the space on writes, so only the
+        // size of the file is changed
+        match op {
+            FallocateOp::PunchHole => {
+                return_errno!(EOPNOTSUPP, "punching holes is only supported by RamFS")
+            }
+            FallocateOp::Allocate { keep_size } => {
+                if !keep_size && offset + len > self.inode.metadata()?.size {
+                    self.inode.resize(offset + len)?;
+                    self.notify_modified();
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn get_abs_path(&self) -> &str {
         &self.abs_path
     }
+
+    pub fn get_inode(&self) -> &Arc<dyn INode> {
+        &self.inode
+    }
+}
+
+/// Cut the buffer to write at the offset short, so that the file is not
+/// extended beyond the size limit. If nothing can be written, it fails with
+/// EFBIG, for which the caller raises SIGXFSZ once the whole write fails.
+fn limit_write_buf(buf: &[u8], offset: usize, size_limit: usize) -> Result<&[u8]> {
+    if buf.len() == 0 || offset.saturating_add(buf.len()) <= size_limit {
+        return Ok(buf);
+    }
+    if offset >= size_limit {
+        return_errno!(EFBIG, "the file size exceeds the limit");
+    }
+    Ok(&buf[..size_limit - offset])
+}
+
+impl Drop for INodeFile {
+    fn drop(&mut self) {
+        // The OFD locks and the flock locks are owned by the open file
+        release_file_locks(&self.inode, self.lease_ref.id());
+    }
 }
 
 impl Debug for INodeFile {
@@ -236,8 +574,9 @@ impl Debug for INodeFile {
 
 pub trait INodeExt {
     fn read_as_vec(&self) -> Result<Vec<u8>>;
-    fn allow_write(&self) -> Result<bool>;
-    fn allow_read(&self) -> Result<bool>;
+    fn allow_write(&self, credentials: &Credentials) -> Result<bool>;
+    fn allow_read(&self, credentials: &Credentials) -> Result<bool>;
+    fn allow_noatime(&self, credentials: &Credentials) -> Result<bool>;
 }
 
 impl INodeExt for dyn INode {
@@ -251,19 +590,43 @@ impl INodeExt for dyn INode {
         Ok(buf)
     }
 
-    fn allow_write(&self) -> Result<bool> {
+    /// Whether the inode is writable by the effective ids
+    fn allow_write(&self, credentials: &Credentials) -> Result<bool> {
         let info = self.metadata()?;
-        let perms = info.mode as u32;
-        let writable = (perms & S_IWUSR) == S_IWUSR;
+        let writable = check_mode(
+            &info,
+            AccessibilityCheckMode::W_OK,
+            credentials.euid(),
+            credentials.egid(),
+            credentials.groups(),
+            credentials.has_capability(Capabilities::DAC_OVERRIDE),
+        )
+        .is_ok();
         Ok(writable)
     }
 
-    fn allow_read(&self) -> Result<bool> {
+    /// Whether the inode is readable by the effective ids
+    fn allow_read(&self, credentials: &Credentials) -> Result<bool> {
         let info = self.metadata()?;
-        let perms = info.mode as u32;
-        let readable = (perms & S_IRUSR) == S_IRUSR;
+        let readable = check_mode(
+            &info,
+            AccessibilityCheckMode::R_OK,
+            credentials.euid(),
+            credentials.egid(),
+            credentials.groups(),
+            credentials.has_capability(Capabilities::DAC_OVERRIDE),
+        )
+        .is_ok();
         Ok(readable)
     }
+
+    /// Whether the inode can be opened with O_NOATIME, which is allowed for
+    /// the owner only without CAP_FOWNER
+    fn allow_noatime(&self, credentials: &Credentials) -> Result<bool> {
+        let info = self.metadata()?;
+        Ok(info.uid == credentials.euid() as usize
+            || credentials.has_capability(Capabilities::FOWNER))
+    }
 }
 
 pub trait AsINodeFile {
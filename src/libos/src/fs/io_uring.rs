@@ -0,0 +1,569 @@
+//! io_uring, i.e., the asynchronous I/O through a submission queue (SQ) and a
+//! completion queue (CQ) shared by the user and the LibOS.
+//!
+//! The rings are allocated in the user space of the process when an io_uring
+//! is set up. So the mmap of an io_uring fd returns the rings that are already
+//! allocated, instead of a new mapping. The rings are pinned, i.e., owned by
+//! the LibOS, so the munmap of them by the user does nothing, and they are
+//! only unmapped when the io_uring is closed.
+//!
+//! The operations submitted are done synchronously in io_uring_enter, except
+//! that IORING_OP_POLL_ADD completes when the file becomes ready. A single
+//! io_uring_enter can submit many operations and reap their completions, which
+//! costs only one syscall.
+use super::file_ops;
+use super::*;
+use net::io_multiplexing;
+use process::ProcessVMRef;
+use std::sync::atomic::{AtomicU32, Ordering};
+use util::mem_util::from_user;
+use vm::{VMPerms, PAGE_SIZE};
+
+/// The offsets to mmap the rings and the SQE array
+pub const IORING_OFF_SQ_RING: usize = 0;
+pub const IORING_OFF_CQ_RING: usize = 0x8000000;
+pub const IORING_OFF_SQES: usize = 0x10000000;
+
+/// The SQ and CQ rings can be mapped by a single mmap
+const IORING_FEAT_SINGLE_MMAP: u32 = 1 << 0;
+/// The size of CQ is given by the user
+const IORING_SETUP_CQSIZE: u32 = 1 << 3;
+/// Wait for the completions in io_uring_enter
+const IORING_ENTER_GETEVENTS: u32 = 1 << 0;
+/// Start the SQE after all the previous SQEs are completed
+const IOSQE_IO_DRAIN: u8 = 1 << 1;
+/// Do fdatasync instead of fsync
+const IORING_FSYNC_DATASYNC: u32 = 1 << 0;
+
+const IORING_MAX_ENTRIES: u32 = 4096;
+const IORING_MAX_CQ_ENTRIES: u32 = 2 * IORING_MAX_ENTRIES;
+
+const IORING_OP_NOP: u8 = 0;
+const IORING_OP_FSYNC: u8 = 3;
+const IORING_OP_POLL_ADD: u8 = 6;
+const IORING_OP_READ: u8 = 22;
+const IORING_OP_WRITE: u8 = 23;
+
+#[repr(C)]
+#[allow(non_camel_case_types)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct io_uring_params_t {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: io_sqring_offsets_t,
+    cq_off: io_cqring_offsets_t,
+}
+
+#[repr(C)]
+#[allow(non_camel_case_types)]
+#[derive(Debug, Default, Clone, Copy)]
+struct io_sqring_offsets_t {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    resv2: u64,
+}
+
+#[repr(C)]
+#[allow(non_camel_case_types)]
+#[derive(Debug, Default, Clone, Copy)]
+struct io_cqring_offsets_t {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    resv2: u64,
+}
+
+/// Submission queue entry
+#[repr(C)]
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy)]
+struct io_uring_sqe_t {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    /// The flags of the operation, e.g., fsync_flags and poll_events
+    op_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    pad: [u64; 2],
+}
+
+/// Completion queue entry
+#[repr(C)]
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy)]
+struct io_uring_cqe_t {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+/// The layout of the rings in the user space.
+///
+/// The SQ ring, the CQ ring and the SQE array are placed in one memory region
+/// as follows:
+///
+/// ```text
+/// +-----------------+-----------+-----------------+--------+-----------+
+/// | SQ ring header  | SQ array  | CQ ring header  | CQEs   | SQE array |
+/// +-----------------+-----------+-----------------+--------+-----------+
+/// ```
+///
+/// The offsets in the SQ and CQ rings are relative to the start of the region,
+/// which is returned by the mmaps of both rings.
+#[derive(Debug)]
+struct Rings {
+    vm: ProcessVMRef,
+    addr: usize,
+    size: usize,
+    sq_entries: u32,
+    cq_entries: u32,
+    cq_off: usize,
+    sqes_off: usize,
+}
+
+// The offsets of the fields in the ring headers
+const RING_HEAD: usize = 0;
+const RING_TAIL: usize = 4;
+const RING_MASK: usize = 8;
+const RING_ENTRIES: usize = 12;
+const RING_FLAGS: usize = 16;
+const RING_DROPPED_OR_OVERFLOW: usize = 20;
+const RING_HEADER_SIZE: usize = 64;
+
+impl Rings {
+    fn new(sq_entries: u32, cq_entries: u32) -> Result<Rings> {
+        let cq_off = align_up(
+            RING_HEADER_SIZE + sq_entries as usize * std::mem::size_of::<u32>(),
+            RING_HEADER_SIZE,
+        );
+        let sqes_off = align_up(
+            cq_off + RING_HEADER_SIZE + cq_entries as usize * std::mem::size_of::<io_uring_cqe_t>(),
+            PAGE_SIZE,
+        );
+        let size = align_up(
+            sqes_off + sq_entries as usize * std::mem::size_of::<io_uring_sqe_t>(),
+            PAGE_SIZE,
+        );
+
+        let vm = {
+            let current_ref = process::get_current();
+            let current = current_ref.lock().unwrap();
+            current.get_vm().clone()
+        };
+        let addr = vm
+            .lock()
+            .unwrap()
+            .mmap_pinned(size, VMPerms::READ | VMPerms::WRITE)?;
+        let rings = Rings {
+            vm,
+            addr,
+            size,
+            sq_entries,
+            cq_entries,
+            cq_off,
+            sqes_off,
+        };
+        rings
+            .u32_at(RING_MASK)
+            .store(sq_entries - 1, Ordering::Relaxed);
+        rings
+            .u32_at(RING_ENTRIES)
+            .store(sq_entries, Ordering::Relaxed);
+        rings
+            .u32_at(cq_off + RING_MASK)
+            .store(cq_entries - 1, Ordering::Relaxed);
+        rings
+            .u32_at(cq_off + RING_ENTRIES)
+            .store(cq_entries, Ordering::Relaxed);
+        Ok(rings)
+    }
+
+    fn u32_at(&self, offset: usize) -> &AtomicU32 {
+        debug_assert!(offset + std::mem::size_of::<u32>() <= self.size);
+        unsafe { &*((self.addr + offset) as *const AtomicU32) }
+    }
+
+    fn sq_array_off(&self) -> usize {
+        RING_HEADER_SIZE
+    }
+
+    fn cqes_off(&self) -> usize {
+        self.cq_off + RING_HEADER_SIZE
+    }
+
+    /// Pop an SQE from the SQ, or None if the SQ is empty
+    fn pop_sqe(&self) -> Option<Result<io_uring_sqe_t>> {
+        let head = self.u32_at(RING_HEAD).load(Ordering::Relaxed);
+        let tail = self.u32_at(RING_TAIL).load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        let array_idx = (head & (self.sq_entries - 1)) as usize;
+        let sqe_idx = self
+            .u32_at(self.sq_array_off() + array_idx * std::mem::size_of::<u32>())
+            .load(Ordering::Relaxed);
+        self.u32_at(RING_HEAD)
+            .store(head.wrapping_add(1), Ordering::Release);
+        if sqe_idx >= self.sq_entries {
+            self.u32_at(RING_DROPPED_OR_OVERFLOW)
+                .fetch_add(1, Ordering::Relaxed);
+            return Some(Err(errno!(EINVAL, "invalid index of SQE")));
+        }
+        let sqe_addr =
+            self.addr + self.sqes_off + sqe_idx as usize * std::mem::size_of::<io_uring_sqe_t>();
+        let sqe = unsafe { std::ptr::read_volatile(sqe_addr as *const io_uring_sqe_t) };
+        Some(Ok(sqe))
+    }
+
+    /// Push a CQE to the CQ. If the CQ is full, the CQE is dropped and the
+    /// overflow counter is increased.
+    fn push_cqe(&self, cqe: io_uring_cqe_t) {
+        let head = self.u32_at(self.cq_off + RING_HEAD).load(Ordering::Acquire);
+        let tail = self.u32_at(self.cq_off + RING_TAIL).load(Ordering::Relaxed);
+        if tail.wrapping_sub(head) >= self.cq_entries {
+            self.u32_at(self.cq_off + RING_DROPPED_OR_OVERFLOW)
+                .fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let cqe_addr = self.addr
+            + self.cqes_off()
+            + (tail & (self.cq_entries - 1)) as usize * std::mem::size_of::<io_uring_cqe_t>();
+        unsafe {
+            std::ptr::write_volatile(cqe_addr as *mut io_uring_cqe_t, cqe);
+        }
+        self.u32_at(self.cq_off + RING_TAIL)
+            .store(tail.wrapping_add(1), Ordering::Release);
+    }
+
+    /// The number of CQEs that have not been reaped by the user
+    fn num_ready_cqes(&self) -> u32 {
+        let head = self.u32_at(self.cq_off + RING_HEAD).load(Ordering::Acquire);
+        let tail = self.u32_at(self.cq_off + RING_TAIL).load(Ordering::Relaxed);
+        tail.wrapping_sub(head)
+    }
+
+    fn fill_params(&self, params: &mut io_uring_params_t) {
+        params.sq_entries = self.sq_entries;
+        params.cq_entries = self.cq_entries;
+        params.features = IORING_FEAT_SINGLE_MMAP;
+        params.sq_off = io_sqring_offsets_t {
+            head: RING_HEAD as u32,
+            tail: RING_TAIL as u32,
+            ring_mask: RING_MASK as u32,
+            ring_entries: RING_ENTRIES as u32,
+            flags: RING_FLAGS as u32,
+            dropped: RING_DROPPED_OR_OVERFLOW as u32,
+            array: self.sq_array_off() as u32,
+            ..Default::default()
+        };
+        params.cq_off = io_cqring_offsets_t {
+            head: (self.cq_off + RING_HEAD) as u32,
+            tail: (self.cq_off + RING_TAIL) as u32,
+            ring_mask: (self.cq_off + RING_MASK) as u32,
+            ring_entries: (self.cq_off + RING_ENTRIES) as u32,
+            overflow: (self.cq_off + RING_DROPPED_OR_OVERFLOW) as u32,
+            cqes: self.cqes_off() as u32,
+            flags: (self.cq_off + RING_FLAGS) as u32,
+            ..Default::default()
+        };
+    }
+}
+
+impl Drop for Rings {
+    fn drop(&mut self) {
+        // The rings are pinned, so they are still mapped
+        self.vm
+            .lock()
+            .unwrap()
+            .munmap_pinned(self.addr, self.size)
+            .expect("the rings of io_uring must be pinned");
+    }
+}
+
+#[derive(Debug)]
+pub struct IoUring {
+    rings: Rings,
+    // The POLL_ADD operations that are not completed yet, which also
+    // serializes the submissions
+    pending_polls: SgxMutex<Vec<PendingPoll>>,
+    // Serialize the pushes of CQEs, which may be done by the threads waiting
+    // for the polls without the lock of the pending polls
+    cq_lock: SgxMutex<()>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PendingPoll {
+    fd: FileDesc,
+    events: i16,
+    user_data: u64,
+}
+
+impl IoUring {
+    fn new(sq_entries: u32, cq_entries: u32) -> Result<IoUring> {
+        Ok(IoUring {
+            rings: Rings::new(sq_entries, cq_entries)?,
+            pending_polls: SgxMutex::new(Vec::new()),
+            cq_lock: SgxMutex::new(()),
+        })
+    }
+
+    /// Get the address of the rings or the SQE array to mmap
+    pub fn get_mmap_addr(&self, offset: usize, size: usize) -> Result<usize> {
+        let (start, end) = match offset {
+            IORING_OFF_SQ_RING | IORING_OFF_CQ_RING => (0, self.rings.sqes_off),
+            IORING_OFF_SQES => (self.rings.sqes_off, self.rings.size),
+            _ => return_errno!(EINVAL, "invalid offset to mmap io_uring"),
+        };
+        if size > end - start {
+            return_errno!(EINVAL, "the size to mmap io_uring is too large");
+        }
+        Ok(self.rings.addr + start)
+    }
+
+    /// Submit the SQEs and wait for the completions. Return the number of SQEs
+    /// submitted.
+    fn enter(&self, to_submit: u32, min_complete: u32, flags: u32) -> Result<u32> {
+        let num_submitted = self.submit(to_submit);
+        if flags & IORING_ENTER_GETEVENTS != 0 {
+            // Only the pending polls can complete later. They are taken out
+            // to be waited for, so that the io_uring is not locked while
+            // waiting, and the ones that are not completed are put back.
+            while self.rings.num_ready_cqes() < min_complete {
+                let mut polls = {
+                    let mut pending_polls = self.pending_polls.lock().unwrap();
+                    std::mem::replace(&mut *pending_polls, Vec::new())
+                };
+                if polls.is_empty() {
+                    break;
+                }
+                self.complete_polls(&mut polls, -1);
+                self.pending_polls.lock().unwrap().extend(polls);
+            }
+        }
+        Ok(num_submitted)
+    }
+
+    /// Submit at most `to_submit` SQEs, and complete the pending polls that
+    /// are ready
+    fn submit(&self, to_submit: u32) -> u32 {
+        let mut pending_polls = self.pending_polls.lock().unwrap();
+        let mut num_submitted = 0;
+        while num_submitted < to_submit {
+            let sqe = match self.rings.pop_sqe() {
+                Some(Ok(sqe)) => sqe,
+                Some(Err(_)) => {
+                    num_submitted += 1;
+                    continue;
+                }
+                None => break,
+            };
+            num_submitted += 1;
+            match self.execute(&sqe) {
+                Ok(Some(res)) => self.complete(sqe.user_data, res),
+                Ok(None) => pending_polls.push(PendingPoll {
+                    fd: sqe.fd as FileDesc,
+                    events: sqe.op_flags as i16,
+                    user_data: sqe.user_data,
+                }),
+                Err(e) => self.complete(sqe.user_data, -(e.errno() as i32)),
+            }
+        }
+
+        self.complete_polls(&mut pending_polls, 0);
+        num_submitted
+    }
+
+    /// Do the operation of the SQE. Return None if the operation is pending.
+    fn execute(&self, sqe: &io_uring_sqe_t) -> Result<Option<i32>> {
+        if sqe.flags & !IOSQE_IO_DRAIN != 0 {
+            return_errno!(EINVAL, "unsupported flags of SQE");
+        }
+        // The operations are done in order, so IOSQE_IO_DRAIN is always met
+        let fd = sqe.fd as FileDesc;
+        let res = match sqe.opcode {
+            IORING_OP_NOP => 0,
+            IORING_OP_READ => {
+                let buf = {
+                    let ptr = sqe.addr as *mut u8;
+                    let len = sqe.len as usize;
+                    from_user::check_mut_array(ptr, len)?;
+                    unsafe { std::slice::from_raw_parts_mut(ptr, len) }
+                };
+                let len = if sqe.off == u64::max_value() {
+                    file_ops::do_read(fd, buf)?
+                } else {
+                    file_ops::do_pread(fd, buf, sqe.off as usize)?
+                };
+                len as i32
+            }
+            IORING_OP_WRITE => {
+                let buf = {
+                    let ptr = sqe.addr as *const u8;
+                    let len = sqe.len as usize;
+                    from_user::check_array(ptr, len)?;
+                    unsafe { std::slice::from_raw_parts(ptr, len) }
+                };
+                let len = if sqe.off == u64::max_value() {
+                    file_ops::do_write(fd, buf)?
+                } else {
+                    file_ops::do_pwrite(fd, buf, sqe.off as usize)?
+                };
+                len as i32
+            }
+            IORING_OP_FSYNC => {
+                if sqe.op_flags & IORING_FSYNC_DATASYNC != 0 {
+                    file_ops::do_fdatasync(fd)?;
+                } else {
+                    file_ops::do_fsync(fd)?;
+                }
+                0
+            }
+            IORING_OP_POLL_ADD => return Ok(None),
+            _ => return_errno!(EINVAL, "unsupported io_uring operation"),
+        };
+        Ok(Some(res))
+    }
+
+    fn complete(&self, user_data: u64, res: i32) {
+        let _cq_lock = self.cq_lock.lock().unwrap();
+        self.rings.push_cqe(io_uring_cqe_t {
+            user_data,
+            res,
+            flags: 0,
+        });
+    }
+
+    /// Complete the pending polls that are ready or fail, waiting for at most
+    /// the timeout in milliseconds (-1 means indefinitely)
+    fn complete_polls(&self, pending_polls: &mut Vec<PendingPoll>, timeout: c_int) {
+        if pending_polls.is_empty() {
+            return;
+        }
+        let mut pollfds: Vec<libc::pollfd> = pending_polls
+            .iter()
+            .map(|poll| libc::pollfd {
+                fd: poll.fd as c_int,
+                events: poll.events,
+                revents: 0,
+            })
+            .collect();
+        if io_multiplexing::do_poll(&mut pollfds, timeout).is_err() {
+            // Find out the polls that fail by polling them one by one
+            for (poll, pollfd) in pending_polls.iter().zip(pollfds.iter_mut()) {
+                let mut single_pollfd = [libc::pollfd {
+                    fd: poll.fd as c_int,
+                    events: poll.events,
+                    revents: 0,
+                }];
+                pollfd.revents = match io_multiplexing::do_poll(&mut single_pollfd, 0) {
+                    Ok(_) => single_pollfd[0].revents,
+                    Err(e) => {
+                        self.complete(poll.user_data, -(e.errno() as i32));
+                        // Mark the poll as completed
+                        -1
+                    }
+                };
+            }
+        }
+
+        let mut i = 0;
+        pending_polls.retain(|poll| {
+            let revents = pollfds[i].revents;
+            i += 1;
+            if revents == 0 {
+                return true;
+            }
+            if revents != -1 {
+                self.complete(poll.user_data, revents as u16 as i32);
+            }
+            false
+        });
+    }
+}
+
+impl File for IoUring {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub fn do_io_uring_setup(entries: u32, params: &mut io_uring_params_t) -> Result<FileDesc> {
+    info!("io_uring_setup: entries: {}, params: {:?}", entries, params);
+    if params.flags & !IORING_SETUP_CQSIZE != 0 {
+        return_errno!(EINVAL, "unsupported flags for io_uring");
+    }
+    if params.resv.iter().any(|resv| *resv != 0) {
+        return_errno!(EINVAL, "reserved fields must be zero");
+    }
+    if entries == 0 || entries > IORING_MAX_ENTRIES {
+        return_errno!(EINVAL, "invalid number of entries");
+    }
+    let sq_entries = entries.next_power_of_two();
+    let cq_entries = if params.flags & IORING_SETUP_CQSIZE != 0 {
+        if params.cq_entries < sq_entries || params.cq_entries > IORING_MAX_CQ_ENTRIES {
+            return_errno!(EINVAL, "invalid number of CQ entries");
+        }
+        params.cq_entries.next_power_of_two()
+    } else {
+        2 * sq_entries
+    };
+
+    let io_uring = IoUring::new(sq_entries, cq_entries)?;
+    io_uring.rings.fill_params(params);
+    let file_ref: Arc<Box<dyn File>> = Arc::new(Box::new(io_uring));
+    let current_ref = process::get_current();
+    let current = current_ref.lock().unwrap();
+    // Like Linux, an io_uring fd is close-on-exec
+    let fd = current.get_files().lock().unwrap().put(file_ref, true)?;
+    Ok(fd)
+}
+
+pub fn do_io_uring_enter(
+    fd: FileDesc,
+    to_submit: u32,
+    min_complete: u32,
+    flags: u32,
+) -> Result<u32> {
+    info!(
+        "io_uring_enter: fd: {}, to_submit: {}, min_complete: {}, flags: {:#x}",
+        fd, to_submit, min_complete, flags
+    );
+    if flags & !IORING_ENTER_GETEVENTS != 0 {
+        return_errno!(EINVAL, "unsupported flags for io_uring_enter");
+    }
+    let file_ref = {
+        let current_ref = process::get_current();
+        let current = current_ref.lock().unwrap();
+        let file_ref = current.get_files().lock().unwrap().get(fd)?;
+        file_ref
+    };
+    let io_uring = file_ref
+        .as_any()
+        .downcast_ref::<IoUring>()
+        .ok_or_else(|| errno!(EOPNOTSUPP, "not an io_uring fd"))?;
+    io_uring.enter(to_submit, min_complete, flags)
+}
@@ -9,14 +9,20 @@ use std::io::{Read, Seek, SeekFrom, Write};
 use std::mem::MaybeUninit;
 
 pub use self::dev_fs::AsDevRandom;
-pub use self::file::{File, FileRef};
-pub use self::file_ops::{AccessMode, CreationFlags, Stat, StatusFlags};
-pub use self::file_ops::{Flock, FlockType};
-pub use self::file_ops::{IoctlCmd, StructuredIoctlArgType, StructuredIoctlNum};
-pub use self::file_table::{FileDesc, FileTable};
+pub use self::file::{FdCount, File, FileRef};
+pub use self::file_ops::get_file;
+pub use self::file_ops::send_dnotify_signals;
+pub use self::file_ops::{release_all_posix_locks, FileLockKind, Flock, FlockType};
+pub use self::file_ops::{AccessMode, CreationFlags, Cwd, Stat, StatMode, StatusFlags, Statx};
+pub use self::file_ops::{IoctlCmd, SizeWatch, StructuredIoctlArgType, StructuredIoctlNum};
+pub use self::file_table::{FileDesc, FileTable, MAX_NUM_FDS};
+pub use self::fs_ops::Statfs;
 pub use self::inode_file::{AsINodeFile, INodeExt, INodeFile};
-pub use self::pipe::Pipe;
-pub use self::rootfs::ROOT_INODE;
+pub use self::io_uring::{io_uring_params_t, IoUring};
+pub use self::mount_ns::MountNamespace;
+pub use self::pipe::{Pipe, PipeReader, PipeWriter, PIPE_BUF};
+pub use self::rootfs::{INIT_MOUNT_NS, ROOT_INODE};
+pub use self::sefs::AtimePolicy;
 pub use self::stdio::{StdinFile, StdoutFile};
 pub use self::syscalls::*;
 
@@ -27,7 +33,11 @@ mod file_table;
 mod fs_ops;
 mod hostfs;
 mod inode_file;
+mod io_uring;
+mod mount_ns;
 mod pipe;
+mod proc_fs;
+mod ramfs;
 mod rootfs;
 mod sefs;
 mod stdio;
@@ -0,0 +1,85 @@
+use super::*;
+use std::path::{Component, Path, PathBuf};
+
+use rcore_fs_mountfs::{MNode, MountFS};
+
+/// A mount namespace, i.e., a view of the mounted file systems.
+///
+/// The file systems are shared by all the namespaces, but each namespace
+/// wraps them in MountFS of its own. So a mount in one namespace is not seen
+/// in the others, i.e., all the mounts are private.
+pub struct MountNamespace {
+    root_fs: Arc<dyn FileSystem>,
+    root_inode: Arc<MNode>,
+    /// The mounted file systems and their mount points, in the order they are
+    /// mounted, so that the mounts can be done again in a copy
+    mounts: SgxMutex<Vec<(PathBuf, Arc<dyn FileSystem>)>>,
+}
+
+impl MountNamespace {
+    pub fn new(root_fs: Arc<dyn FileSystem>) -> MountNamespace {
+        let root_inode = MountFS::new(root_fs.clone()).root_inode();
+        MountNamespace {
+            root_fs,
+            root_inode,
+            mounts: SgxMutex::new(Vec::new()),
+        }
+    }
+
+    pub fn root_inode(&self) -> Arc<dyn INode> {
+        self.root_inode.clone()
+    }
+
+    /// Mount the file system at `target`, which is an absolute path to an
+    /// existing directory
+    pub fn mount(&self, fs: Arc<dyn FileSystem>, target: &Path) -> Result<()> {
+        let mut mounts = self.mounts.lock().unwrap();
+        let mount_dir = self.find_mount_dir(target)?;
+        mount_dir.mount(fs.clone())?;
+        mounts.push((target.to_path_buf(), fs));
+        Ok(())
+    }
+
+    /// Copy the namespace, with the same file systems mounted at the same
+    /// places. The mounts made later in either one are not seen in the other.
+    pub fn copy(&self) -> Result<MountNamespace> {
+        let new_ns = MountNamespace::new(self.root_fs.clone());
+        for (target, fs) in self.mounts.lock().unwrap().iter() {
+            new_ns.mount(fs.clone(), target)?;
+        }
+        Ok(new_ns)
+    }
+
+    fn find_mount_dir(&self, target: &Path) -> Result<Arc<MNode>> {
+        if !target.is_absolute() {
+            return_errno!(EINVAL, "the mount point must be an absolute path");
+        }
+        let mut dir = self.root_inode.clone();
+        for component in target.components() {
+            match component {
+                Component::RootDir => continue,
+                Component::Normal(name) => {
+                    let name = name
+                        .to_str()
+                        .ok_or_else(|| errno!(EINVAL, "the mount point is not valid UTF-8"))?;
+                    dir = dir
+                        .find(false, name)
+                        .map_err(|_| errno!(ENOENT, "the mount point does not exist"))?;
+                }
+                _ => return_errno!(EINVAL, "the mount point must be a normalized path"),
+            }
+        }
+        if dir.metadata()?.type_ != FileType::Dir {
+            return_errno!(ENOTDIR, "the mount point is not a directory");
+        }
+        Ok(dir)
+    }
+}
+
+impl Debug for MountNamespace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mounts = self.mounts.lock().unwrap();
+        let targets: Vec<&PathBuf> = mounts.iter().map(|(target, _)| target).collect();
+        write!(f, "MountNamespace {{ mounts: {:?} }}", targets)
+    }
+}
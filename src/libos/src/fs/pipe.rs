@@ -1,10 +1,13 @@
 use super::*;
 use util::ring_buf::*;
-
-// TODO: Use Waiter and WaitQueue infrastructure to sleep when blocking
+use util::waiter::{self, WaiterQueue};
 
 pub const PIPE_BUF_SIZE: usize = 2 * 1024 * 1024;
 
+/// The max size of a write that is atomic, i.e., not interleaved with the
+/// other writes. A pipe is writable only if it has room for such a write.
+pub const PIPE_BUF: usize = 4096;
+
 #[derive(Debug)]
 pub struct Pipe {
     pub reader: PipeReader,
@@ -16,14 +19,19 @@ impl Pipe {
         let mut ring_buf = RingBuf::new(PIPE_BUF_SIZE);
         // Only O_NONBLOCK and O_DIRECT can be applied during pipe creation
         let valid_flags = flags & (StatusFlags::O_NONBLOCK | StatusFlags::O_DIRECT);
+        let waiters = Arc::new(WaiterQueue::new());
         Ok(Pipe {
             reader: PipeReader {
                 inner: SgxMutex::new(ring_buf.reader),
                 status_flags: SgxRwLock::new(valid_flags),
+                waiters: waiters.clone(),
+                fd_count: FdCount::new(),
             },
             writer: PipeWriter {
                 inner: SgxMutex::new(ring_buf.writer),
                 status_flags: SgxRwLock::new(valid_flags),
+                waiters: waiters,
+                fd_count: FdCount::new(),
             },
         })
     }
@@ -33,12 +41,39 @@ impl Pipe {
 pub struct PipeReader {
     inner: SgxMutex<RingBufReader>,
     status_flags: SgxRwLock<StatusFlags>,
+    // The waiters for the readiness of both ends, which are woken by the reads,
+    // the writes and the closes
+    waiters: Arc<WaiterQueue>,
+    fd_count: FdCount,
+}
+
+impl PipeReader {
+    /// Get the poll events that are ready
+    pub fn poll(&self) -> i16 {
+        let ringbuf = self.inner.lock().unwrap();
+        let mut revents = 0;
+        if ringbuf.can_read() {
+            revents |= libc::POLLIN;
+        }
+        if ringbuf.is_peer_closed() {
+            revents |= libc::POLLHUP;
+        }
+        revents
+    }
+
+    /// Get the waiters that are woken when the readiness of the pipe changes
+    pub fn waiters(&self) -> &Arc<WaiterQueue> {
+        &self.waiters
+    }
 }
 
 impl File for PipeReader {
     fn read(&self, buf: &mut [u8]) -> Result<usize> {
-        let ringbuf = self.inner.lock().unwrap();
-        ringbuf.read(buf)
+        let read_bytes = self.inner.lock().unwrap().read(buf)?;
+        if read_bytes > 0 {
+            self.waiters.dequeue_and_wake_all();
+        }
+        Ok(read_bytes)
     }
 
     fn readv(&self, bufs: &mut [&mut [u8]]) -> Result<usize> {
@@ -62,6 +97,9 @@ impl File for PipeReader {
                 }
             }
         }
+        if total_bytes > 0 {
+            self.waiters.dequeue_and_wake_all();
+        }
         Ok(total_bytes)
     }
 
@@ -82,11 +120,29 @@ impl File for PipeReader {
         Ok(())
     }
 
+    fn fd_count(&self) -> Option<&FdCount> {
+        Some(&self.fd_count)
+    }
+
+    /// Close the pipe when the last fd of the end is closed, even if the end
+    /// is still referred to by an ongoing operation
+    fn interrupt_blocked_ops(&self) {
+        self.inner.lock().unwrap().close();
+        self.waiters.dequeue_and_wake_all();
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
 }
 
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        self.inner.lock().unwrap().close();
+        self.waiters.dequeue_and_wake_all();
+    }
+}
+
 unsafe impl Send for PipeReader {}
 unsafe impl Sync for PipeReader {}
 
@@ -94,19 +150,92 @@ unsafe impl Sync for PipeReader {}
 pub struct PipeWriter {
     inner: SgxMutex<RingBufWriter>,
     status_flags: SgxRwLock<StatusFlags>,
+    waiters: Arc<WaiterQueue>,
+    fd_count: FdCount,
+}
+
+impl PipeWriter {
+    /// Get the poll events that are ready
+    pub fn poll(&self) -> i16 {
+        let ringbuf = self.inner.lock().unwrap();
+        if ringbuf.is_peer_closed() {
+            libc::POLLERR
+        } else if ringbuf.bytes_to_write() >= PIPE_BUF {
+            libc::POLLOUT
+        } else {
+            0
+        }
+    }
+
+    /// Get the waiters that are woken when the readiness of the pipe changes
+    pub fn waiters(&self) -> &Arc<WaiterQueue> {
+        &self.waiters
+    }
+
+    fn do_write(&self, buf: &[u8]) -> Result<usize> {
+        let is_nonblocking = self.get_status_flags()?.contains(StatusFlags::O_NONBLOCK);
+        let is_atomic = buf.len() <= PIPE_BUF;
+        let mut written_bytes = 0;
+        let mut try_write = || -> Result<Option<usize>> {
+            if self.fd_count.is_closed() {
+                if written_bytes > 0 {
+                    return Ok(Some(written_bytes));
+                }
+                return_errno!(EBADF, "the pipe is closed");
+            }
+            let ringbuf = self.inner.lock().unwrap();
+            if ringbuf.is_peer_closed() {
+                if written_bytes > 0 {
+                    return Ok(Some(written_bytes));
+                }
+                return_errno!(EPIPE, "all the readers of the pipe are closed");
+            }
+            if !is_atomic || ringbuf.bytes_to_write() >= buf.len() {
+                let this_len = ringbuf.write(&buf[written_bytes..])?;
+                if this_len > 0 {
+                    self.waiters.dequeue_and_wake_all();
+                }
+                written_bytes += this_len;
+            }
+            if written_bytes == buf.len() {
+                return Ok(Some(written_bytes));
+            }
+            if is_nonblocking {
+                if written_bytes == 0 {
+                    return_errno!(EAGAIN, "the pipe is full");
+                }
+                return Ok(Some(written_bytes));
+            }
+            Ok(None)
+        };
+        if let Some(written_bytes) = try_write()? {
+            return Ok(written_bytes);
+        }
+        // Wait for the room made by the reads, or for the close of the reader.
+        // A wait interrupted by a signal after some bytes are written ends in
+        // a short write.
+        match waiter::wait_until(&[&*self.waiters], None, &mut try_write) {
+            Err(ref e) if e.errno() == EINTR && written_bytes > 0 => Ok(written_bytes),
+            ret => ret,
+        }
+    }
 }
 
 impl File for PipeWriter {
+    /// Write to the pipe. A blocking write waits until all the bytes are
+    /// written, while a non-blocking one writes as many bytes as there is room
+    /// for. A write of at most PIPE_BUF bytes is done all at once or not at all.
+    ///
+    /// A write that fails with EPIPE, i.e., after all the readers are closed,
+    /// raises SIGPIPE on the writer.
     fn write(&self, buf: &[u8]) -> Result<usize> {
-        let ringbuf = self.inner.lock().unwrap();
-        ringbuf.write(buf)
+        raise_sigpipe_on_epipe(self.do_write(buf))
     }
 
     fn writev(&self, bufs: &[&[u8]]) -> Result<usize> {
-        let ringbuf = self.inner.lock().unwrap();
         let mut total_bytes = 0;
         for buf in bufs {
-            match ringbuf.write(buf) {
+            match self.do_write(buf) {
                 Ok(this_len) => {
                     total_bytes += this_len;
                     if this_len < buf.len() {
@@ -116,7 +245,7 @@ impl File for PipeWriter {
                 Err(e) => {
                     match total_bytes {
                         // a complete failure
-                        0 => return Err(e),
+                        0 => return raise_sigpipe_on_epipe(Err(e)),
                         // a partially failure
                         _ => break,
                     }
@@ -147,11 +276,39 @@ impl File for PipeWriter {
         Ok(())
     }
 
+    fn fd_count(&self) -> Option<&FdCount> {
+        Some(&self.fd_count)
+    }
+
+    /// Close the pipe when the last fd of the end is closed, which wakes the
+    /// writes blocked on it to fail with EBADF
+    fn interrupt_blocked_ops(&self) {
+        self.inner.lock().unwrap().close();
+        self.waiters.dequeue_and_wake_all();
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
 }
 
+/// Raise SIGPIPE on the writer if the write fails with EPIPE
+fn raise_sigpipe_on_epipe(res: Result<usize>) -> Result<usize> {
+    if let Err(ref e) = res {
+        if e.errno() == EPIPE {
+            process::raise_signal(process::SIGPIPE);
+        }
+    }
+    res
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        self.inner.lock().unwrap().close();
+        self.waiters.dequeue_and_wake_all();
+    }
+}
+
 unsafe impl Send for PipeWriter {}
 unsafe impl Sync for PipeWriter {}
 
@@ -165,8 +322,14 @@ pub fn do_pipe2(flags: u32) -> Result<[FileDesc; 2]> {
     let file_table_ref = current.get_files();
     let mut file_table = file_table_ref.lock().unwrap();
     let close_on_spawn = creation_flags.must_close_on_spawn();
-    let reader_fd = file_table.put(Arc::new(Box::new(pipe.reader)), close_on_spawn);
-    let writer_fd = file_table.put(Arc::new(Box::new(pipe.writer)), close_on_spawn);
+    let reader_fd = file_table.put(Arc::new(Box::new(pipe.reader)), close_on_spawn)?;
+    let writer_fd = match file_table.put(Arc::new(Box::new(pipe.writer)), close_on_spawn) {
+        Ok(writer_fd) => writer_fd,
+        Err(e) => {
+            file_table.del(reader_fd);
+            return Err(e);
+        }
+    };
     info!("pipe2: reader_fd: {}, writer_fd: {}", reader_fd, writer_fd);
     Ok([reader_fd, writer_fd])
 }
@@ -0,0 +1,161 @@
+use super::*;
+use process::{pid_t, Process};
+use std::sync::Weak;
+
+/// The inode numbers in procfs are synthetic, but stable and unique: the pid
+/// is in the high bits and the index of the node in the process in the low
+/// bits. The base keeps them apart from the inode numbers of the other file
+/// systems.
+const PROC_INO_BASE: usize = 1 << 62;
+
+fn proc_ino(pid: pid_t, node_idx: usize) -> usize {
+    PROC_INO_BASE | ((pid as usize) << 32) | node_idx
+}
+
+/// The inode number of /proc/[pid]/fd
+fn fd_dir_ino(pid: pid_t) -> usize {
+    proc_ino(pid, 0)
+}
+
+/// The inode number of /proc/[pid]/fd/[fd]
+fn fd_link_ino(pid: pid_t, fd: FileDesc) -> usize {
+    proc_ino(pid, fd as usize + 1)
+}
+
+/// The directory of /proc/self/fd, which has an entry for each file
+/// descriptor of the process.
+#[derive(Debug)]
+pub struct ProcFdDir {
+    pid: pid_t,
+    // The file table is not kept alive by the directory, or the file table of
+    // an exited process would never be dropped
+    files: Weak<SgxMutex<FileTable>>,
+    offset: SgxMutex<usize>,
+    // The snapshot of the file descriptors, which are read by their indexes
+    // in the snapshot, i.e., the offset
+    fds: SgxMutex<Option<Vec<FileDesc>>>,
+}
+
+impl ProcFdDir {
+    pub fn open(process: &Process, flags: u32) -> Result<ProcFdDir> {
+        if AccessMode::from_u32(flags)?.writable() {
+            return_errno!(EISDIR, "directories in procfs cannot be written");
+        }
+        Ok(ProcFdDir {
+            pid: process.get_pid(),
+            files: Arc::downgrade(process.get_files()),
+            offset: SgxMutex::new(0),
+            fds: SgxMutex::new(None),
+        })
+    }
+}
+
+impl File for ProcFdDir {
+    fn seek(&self, pos: SeekFrom) -> Result<off_t> {
+        let mut offset = self.offset.lock().unwrap();
+        let new_offset = match pos {
+            SeekFrom::Start(off) => off as i64,
+            SeekFrom::Current(off) => (*offset as i64)
+                .checked_add(off)
+                .ok_or_else(|| errno!(EOVERFLOW, "file offset overflow"))?,
+            SeekFrom::End(_) => return_errno!(EINVAL, "directories in procfs have no end"),
+        };
+        if new_offset < 0 {
+            return_errno!(EINVAL, "file offset is negative");
+        }
+        *offset = new_offset as usize;
+        Ok(*offset as i64)
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        Ok(fd_dir_metadata(self.pid))
+    }
+
+    fn read_entry(&self) -> Result<String> {
+        let mut offset = self.offset.lock().unwrap();
+        let mut fds = self.fds.lock().unwrap();
+        if *offset == 0 || fds.is_none() {
+            *fds = Some(match self.files.upgrade() {
+                Some(files) => files.lock().unwrap().get_fds(),
+                None => Vec::new(),
+            });
+        }
+        let fd = fds
+            .as_ref()
+            .unwrap()
+            .get(*offset)
+            .cloned()
+            .ok_or_else(|| errno!(ENOENT, "no more entries"))?;
+        *offset += 1;
+        Ok(fd.to_string())
+    }
+
+    fn entry_ino(&self, name: &str) -> Result<u64> {
+        let fd = name
+            .parse::<FileDesc>()
+            .map_err(|_| errno!(ENOENT, "no such file in procfs"))?;
+        Ok(fd_link_ino(self.pid, fd) as u64)
+    }
+
+    fn get_access_mode(&self) -> Result<AccessMode> {
+        Ok(AccessMode::O_RDONLY)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Get the metadata of /proc/self/fd or /proc/self/fd/[fd] without following
+/// the links, or None if the path is not one of them
+pub fn lstat_fd_path(process: &Process, path: &str) -> Option<Result<Metadata>> {
+    let path = path.trim_end_matches('/');
+    let pid = process.get_pid();
+    if path == "/proc/self/fd" {
+        return Some(Ok(fd_dir_metadata(pid)));
+    }
+    if !path.starts_with("/proc/self/fd/") {
+        return None;
+    }
+    let fd_link_metadata = || -> Result<Metadata> {
+        let fd = path["/proc/self/fd/".len()..]
+            .parse::<FileDesc>()
+            .map_err(|_| errno!(ENOENT, "no such file in procfs"))?;
+        process
+            .get_files()
+            .lock()
+            .unwrap()
+            .get(fd)
+            .map_err(|_| errno!(ENOENT, "no such file descriptor"))?;
+        Ok(proc_metadata(
+            fd_link_ino(pid, fd),
+            FileType::SymLink,
+            0o700,
+            1,
+        ))
+    };
+    Some(fd_link_metadata())
+}
+
+fn fd_dir_metadata(pid: pid_t) -> Metadata {
+    proc_metadata(fd_dir_ino(pid), FileType::Dir, 0o500, 2)
+}
+
+fn proc_metadata(ino: usize, type_: FileType, mode: u16, nlinks: usize) -> Metadata {
+    Metadata {
+        dev: 0,
+        inode: ino,
+        size: 0,
+        blk_size: 0,
+        blocks: 0,
+        atime: Timespec { sec: 0, nsec: 0 },
+        mtime: Timespec { sec: 0, nsec: 0 },
+        ctime: Timespec { sec: 0, nsec: 0 },
+        type_,
+        mode,
+        nlinks,
+        uid: 0,
+        gid: 0,
+        rdev: 0,
+    }
+}
@@ -0,0 +1,17 @@
+use super::*;
+
+/// The detailed error of the last failed syscall of the calling thread.
+///
+/// The error is recorded only if `record_last_error` is enabled in the config.
+/// It is kept until the next failed syscall of the thread.
+#[derive(Debug)]
+pub struct LastError;
+
+impl ProcContent for LastError {
+    fn generate(&self) -> Result<Vec<u8>> {
+        let current_ref = process::get_current();
+        let current = current_ref.lock().unwrap();
+        let last_error = current.get_last_error().unwrap_or("");
+        Ok(last_error.as_bytes().to_vec())
+    }
+}
@@ -0,0 +1,12 @@
+use super::*;
+
+/// The file locks and leases that are currently held, in the format of
+/// /proc/locks of Linux.
+#[derive(Debug)]
+pub struct Locks;
+
+impl ProcContent for Locks {
+    fn generate(&self) -> Result<Vec<u8>> {
+        Ok(file_ops::format_proc_locks().into_bytes())
+    }
+}
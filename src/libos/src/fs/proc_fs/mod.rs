@@ -0,0 +1,111 @@
+use super::*;
+
+pub use self::fd::{lstat_fd_path, ProcFdDir};
+pub use self::last_error::LastError;
+pub use self::locks::Locks;
+pub use self::ns::MountNamespaceFile;
+pub use self::pid_stat::{ProcPidStat, ProcPidStatus};
+
+mod fd;
+mod last_error;
+mod locks;
+mod ns;
+mod pid_stat;
+
+/// The content of a file in procfs, which reflects the state of the LibOS
+/// at the time the file is read.
+pub trait ProcContent: Debug + Sync + Send + 'static {
+    fn generate(&self) -> Result<Vec<u8>>;
+}
+
+/// A read-only file in procfs.
+///
+/// The content is generated when the file is read from the beginning, so the
+/// reader sees the live state. The content is kept for the following reads
+/// until the file is read from the beginning again, so that a reader that
+/// reads the file in pieces sees a consistent snapshot of the state.
+#[derive(Debug)]
+pub struct ProcFile<T: ProcContent> {
+    content: T,
+    offset: SgxMutex<usize>,
+    snapshot: SgxMutex<Option<Vec<u8>>>,
+}
+
+impl<T: ProcContent> ProcFile<T> {
+    pub fn open(content: T, flags: u32) -> Result<ProcFile<T>> {
+        if AccessMode::from_u32(flags)?.writable() {
+            return_errno!(EACCES, "files in procfs are read-only");
+        }
+        Ok(ProcFile {
+            content,
+            offset: SgxMutex::new(0),
+            snapshot: SgxMutex::new(None),
+        })
+    }
+
+    /// Read the content at the offset from the snapshot, which is generated
+    /// again if read from the beginning
+    fn read_snapshot(&self, offset: usize, bufs: &mut [&mut [u8]]) -> Result<usize> {
+        let mut snapshot = self.snapshot.lock().unwrap();
+        if offset == 0 || snapshot.is_none() {
+            *snapshot = Some(self.content.generate()?);
+        }
+        let content = snapshot.as_ref().unwrap();
+        let mut offset = offset;
+        let mut total_len = 0;
+        for buf in bufs {
+            if offset >= content.len() {
+                break;
+            }
+            let len = buf.len().min(content.len() - offset);
+            buf[..len].copy_from_slice(&content[offset..offset + len]);
+            offset += len;
+            total_len += len;
+        }
+        Ok(total_len)
+    }
+}
+
+impl<T: ProcContent> File for ProcFile<T> {
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        let mut offset = self.offset.lock().unwrap();
+        let len = self.read_snapshot(*offset, &mut [buf])?;
+        *offset += len;
+        Ok(len)
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        self.read_snapshot(offset, &mut [buf])
+    }
+
+    fn readv(&self, bufs: &mut [&mut [u8]]) -> Result<usize> {
+        let mut offset = self.offset.lock().unwrap();
+        let len = self.read_snapshot(*offset, bufs)?;
+        *offset += len;
+        Ok(len)
+    }
+
+    fn seek(&self, pos: SeekFrom) -> Result<off_t> {
+        let mut offset = self.offset.lock().unwrap();
+        let new_offset = match pos {
+            SeekFrom::Start(off) => off as i64,
+            SeekFrom::Current(off) => (*offset as i64)
+                .checked_add(off)
+                .ok_or_else(|| errno!(EOVERFLOW, "file offset overflow"))?,
+            SeekFrom::End(_) => return_errno!(EINVAL, "files in procfs have no end"),
+        };
+        if new_offset < 0 {
+            return_errno!(EINVAL, "file offset is negative");
+        }
+        *offset = new_offset as usize;
+        Ok(*offset as i64)
+    }
+
+    fn get_access_mode(&self) -> Result<AccessMode> {
+        Ok(AccessMode::O_RDONLY)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
@@ -0,0 +1,31 @@
+use super::*;
+
+/// The file of /proc/[pid]/ns/mnt, which refers to the mount namespace of the
+/// process. The file can be given to setns to join the namespace.
+#[derive(Debug)]
+pub struct MountNamespaceFile {
+    mnt_ns: Arc<MountNamespace>,
+}
+
+impl MountNamespaceFile {
+    pub fn open(mnt_ns: Arc<MountNamespace>, flags: u32) -> Result<MountNamespaceFile> {
+        if AccessMode::from_u32(flags)?.writable() {
+            return_errno!(EACCES, "files in procfs are read-only");
+        }
+        Ok(MountNamespaceFile { mnt_ns })
+    }
+
+    pub fn get_mnt_ns(&self) -> &Arc<MountNamespace> {
+        &self.mnt_ns
+    }
+}
+
+impl File for MountNamespaceFile {
+    fn get_access_mode(&self) -> Result<AccessMode> {
+        Ok(AccessMode::O_RDONLY)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
@@ -0,0 +1,290 @@
+use super::*;
+use misc::resource_t;
+use process::{pid_t, SigSet, Status};
+use std::time::Duration;
+
+/// The file of /proc/[pid]/status, which shows the state of the process in a
+/// human readable format, one field per line.
+#[derive(Debug)]
+pub struct ProcPidStatus {
+    pid: pid_t,
+}
+
+impl ProcPidStatus {
+    pub fn new(pid: pid_t) -> ProcPidStatus {
+        ProcPidStatus { pid }
+    }
+}
+
+impl ProcContent for ProcPidStatus {
+    fn generate(&self) -> Result<Vec<u8>> {
+        let snapshot = ProcessSnapshot::take(self.pid)?;
+        let status = format!(
+            "Name:\t{}\n\
+             State:\t{} ({})\n\
+             Tgid:\t{}\n\
+             Pid:\t{}\n\
+             PPid:\t{}\n\
+             VmSize:\t{:8} kB\n\
+             Threads:\t{}\n\
+             SigQ:\t0/{}\n\
+             SigPnd:\t{:016x}\n\
+             ShdPnd:\t{:016x}\n\
+             SigBlk:\t{:016x}\n\
+             SigIgn:\t{:016x}\n\
+             SigCgt:\t{:016x}\n",
+            snapshot.comm,
+            snapshot.state.as_char(),
+            snapshot.state.as_str(),
+            snapshot.pid,
+            snapshot.pid,
+            snapshot.ppid,
+            snapshot.vm_size / 1024,
+            snapshot.num_threads,
+            snapshot.sigpending_limit,
+            // No signals are pending, ignored or caught, as the signals that
+            // take effect are only handled by the default actions
+            0,
+            0,
+            snapshot.sig_mask.as_u64(),
+            0,
+            0,
+        );
+        Ok(status.into_bytes())
+    }
+}
+
+/// The file of /proc/[pid]/stat, which shows the state of the process in one
+/// line of space-separated fields, in the same order as Linux.
+#[derive(Debug)]
+pub struct ProcPidStat {
+    pid: pid_t,
+}
+
+impl ProcPidStat {
+    pub fn new(pid: pid_t) -> ProcPidStat {
+        ProcPidStat { pid }
+    }
+}
+
+impl ProcContent for ProcPidStat {
+    fn generate(&self) -> Result<Vec<u8>> {
+        let s = ProcessSnapshot::take(self.pid)?;
+        let fields: Vec<String> = vec![
+            s.pid.to_string(),
+            format!("({})", s.comm),
+            s.state.as_char().to_string(),
+            s.ppid.to_string(),
+            s.pgid.to_string(),
+            // There are no sessions, so the session is the process group
+            s.pgid.to_string(),
+            // tty_nr, tpgid, flags, minflt, cminflt, majflt and cmajflt
+            "0".to_string(),
+            "-1".to_string(),
+            "0".to_string(),
+            "0".to_string(),
+            "0".to_string(),
+            "0".to_string(),
+            "0".to_string(),
+            // The user and system time are not told apart by the host, so
+            // all the CPU time is accounted as the user time
+            as_clock_ticks(s.cputime).to_string(),
+            "0".to_string(),
+            // cutime and cstime
+            "0".to_string(),
+            "0".to_string(),
+            // priority and nice
+            "20".to_string(),
+            "0".to_string(),
+            s.num_threads.to_string(),
+            // itrealvalue
+            "0".to_string(),
+            as_clock_ticks(s.start_time).to_string(),
+            s.vm_size.to_string(),
+            // rss, which is unknown, as the pages of the enclave may be
+            // paged out by the host without the LibOS knowing it
+            "0".to_string(),
+            s.rss_limit.to_string(),
+            s.code_range.0.to_string(),
+            s.code_range.1.to_string(),
+            s.stack_base.to_string(),
+            // kstkesp and kstkeip
+            "0".to_string(),
+            "0".to_string(),
+            // signal, blocked, sigignore and sigcatch
+            "0".to_string(),
+            s.sig_mask.as_u64().to_string(),
+            "0".to_string(),
+            "0".to_string(),
+            // wchan, nswap and cnswap
+            "0".to_string(),
+            "0".to_string(),
+            "0".to_string(),
+            // exit_signal, i.e., SIGCHLD
+            "17".to_string(),
+            // processor, rt_priority, policy, delayacct_blkio_ticks,
+            // guest_time and cguest_time
+            "0".to_string(),
+            "0".to_string(),
+            "0".to_string(),
+            "0".to_string(),
+            "0".to_string(),
+            "0".to_string(),
+            // start_data, end_data and start_brk
+            "0".to_string(),
+            "0".to_string(),
+            s.heap_start.to_string(),
+            // arg_start, arg_end, env_start and env_end
+            "0".to_string(),
+            "0".to_string(),
+            "0".to_string(),
+            "0".to_string(),
+            s.exit_code.to_string(),
+        ];
+        Ok(format!("{}\n", fields.join(" ")).into_bytes())
+    }
+}
+
+/// The clock ticks per second of the times in /proc/[pid]/stat, i.e., USER_HZ
+const CLOCK_TICKS_PER_SEC: u64 = 100;
+
+fn as_clock_ticks(time: Duration) -> u64 {
+    time.as_secs() * CLOCK_TICKS_PER_SEC
+        + time.subsec_nanos() as u64 / (1_000_000_000 / CLOCK_TICKS_PER_SEC)
+}
+
+/// The state of a process as shown in procfs
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ProcessState {
+    Running,
+    Sleeping,
+    Stopped,
+    Zombie,
+}
+
+impl ProcessState {
+    fn as_char(&self) -> char {
+        match self {
+            ProcessState::Running => 'R',
+            ProcessState::Sleeping => 'S',
+            ProcessState::Stopped => 'T',
+            ProcessState::Zombie => 'Z',
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            ProcessState::Running => "running",
+            ProcessState::Sleeping => "sleeping",
+            ProcessState::Stopped => "stopped",
+            ProcessState::Zombie => "zombie",
+        }
+    }
+}
+
+/// The state of a process, which is taken once for each generation of a file
+/// in procfs, so that the fields of the file agree with each other
+struct ProcessSnapshot {
+    pid: pid_t,
+    comm: String,
+    state: ProcessState,
+    ppid: pid_t,
+    pgid: pid_t,
+    num_threads: usize,
+    cputime: Duration,
+    start_time: Duration,
+    vm_size: usize,
+    rss_limit: u64,
+    code_range: (usize, usize),
+    stack_base: usize,
+    heap_start: usize,
+    sig_mask: SigSet,
+    sigpending_limit: u64,
+    exit_code: i32,
+}
+
+/// The max length of the name of a process, excluding the null terminator
+const MAX_COMM_LEN: usize = 15;
+
+impl ProcessSnapshot {
+    fn take(pid: pid_t) -> Result<ProcessSnapshot> {
+        let process_ref = process::get(pid).map_err(|_| errno!(ESRCH, "no such process"))?;
+        // The parent and the threads are read after the process is unlocked,
+        // as a process must not be locked before its parent
+        let process = process_ref.lock().unwrap();
+        let comm: String = process
+            .get_elf_path()
+            .rsplit('/')
+            .next()
+            .unwrap_or("")
+            .chars()
+            .take(MAX_COMM_LEN)
+            .collect();
+        let (state, exit_code) = match process.get_status() {
+            Status::ZOMBIE => (
+                ProcessState::Zombie,
+                process.get_term_status().as_wait_status(),
+            ),
+            _ if process.get_job_control().is_stopped() => (ProcessState::Stopped, 0),
+            Status::INTERRUPTIBLE => (ProcessState::Sleeping, 0),
+            _ => (ProcessState::Running, 0),
+        };
+        let pgid = process.get_pgid();
+        let start_time = process.get_start_time();
+        let sig_mask = process.get_sig_mask();
+        let parent_ref = process.get_parent().clone();
+        let vm_ref = process.get_vm().clone();
+        let rlimits_ref = process.get_rlimits().clone();
+        drop(process);
+
+        let ppid = parent_ref.lock().unwrap().get_pid();
+        let num_threads = process::get_all()
+            .iter()
+            .filter(|thread_ref| {
+                let thread = thread_ref.lock().unwrap();
+                thread.get_pid() == pid && thread.get_status() != Status::ZOMBIE
+            })
+            .count();
+        // The same CPU time as CLOCK_PROCESS_CPUTIME_ID of the process
+        let cputime = time::get_process_cputime(pid)?;
+        let (vm_size, code_range, stack_base, heap_start) = {
+            let vm = vm_ref.lock().unwrap();
+            let code_range = vm
+                .get_elf_ranges()
+                .first()
+                .map(|range| (range.start(), range.end()))
+                .unwrap_or((0, 0));
+            (
+                vm.get_process_range().size(),
+                code_range,
+                vm.get_stack_base(),
+                vm.get_heap_range().start(),
+            )
+        };
+        let (rss_limit, sigpending_limit) = {
+            let rlimits = rlimits_ref.lock().unwrap();
+            (
+                rlimits.get(resource_t::RLIMIT_RSS).get_cur(),
+                rlimits.get(resource_t::RLIMIT_SIGPENDING).get_cur(),
+            )
+        };
+        Ok(ProcessSnapshot {
+            pid,
+            comm,
+            state,
+            ppid,
+            pgid,
+            num_threads,
+            cputime,
+            start_time,
+            vm_size,
+            rss_limit,
+            code_range,
+            stack_base,
+            heap_start,
+            sig_mask,
+            sigpending_limit,
+            exit_code,
+        })
+    }
+}
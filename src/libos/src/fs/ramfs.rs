@@ -0,0 +1,500 @@
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use core::any::Any;
+use rcore_fs::vfs::*;
+use rcore_fs_ramfs::RamFS;
+use std::cmp::{max, min};
+use std::collections::BTreeMap;
+use std::sync::{SgxMutex as Mutex, SgxMutexGuard as MutexGuard};
+use vm::PAGE_SIZE;
+
+/// The space of `LimitedRamFS` is accounted in blocks of this size
+const BLOCK_SIZE: usize = PAGE_SIZE;
+/// The maximum length of a file name
+const NAME_MAX: usize = 255;
+/// The command of `io_control` by which fallocate reaches `LRNode` through the
+/// layers that wrap it, e.g., MountFS
+pub(super) const IOC_FALLOCATE: u32 = 0x4c52_0001;
+
+/// RamFS with an optional limit on its size.
+///
+/// Only the blocks that are actually written or allocated by fallocate are
+/// accounted, so the holes of sparse files, e.g., the ones extended by
+/// truncate, take no space. Writes, allocations and creations that would
+/// exceed the limit fail with ENOSPC, while truncation, hole punching and
+/// deletion free the accounted blocks.
+pub struct LimitedRamFS {
+    inner: Arc<RamFS>,
+    // None if there is no limit, in which case nothing is accounted
+    usage: Option<Mutex<Usage>>,
+    self_ref: Weak<LimitedRamFS>,
+}
+
+/// INode for `LimitedRamFS`
+pub struct LRNode {
+    inner: Arc<dyn INode>,
+    fs: Arc<LimitedRamFS>,
+}
+
+#[derive(Debug, Default)]
+struct Usage {
+    limit_blocks: usize,
+    used_blocks: usize,
+    // The charged blocks of the files, indexed by the inode numbers
+    files: BTreeMap<usize, Extents>,
+}
+
+impl Usage {
+    /// Account the blocks of `[offset, offset + len)` of a file in order,
+    /// until the limit is reached. Return the length that is accounted, and
+    /// the ranges of the blocks that are newly accounted, by which the charge
+    /// can be refunded.
+    fn charge(&mut self, ino: usize, offset: usize, len: usize) -> (usize, Vec<(usize, usize)>) {
+        let extents = self.files.entry(ino).or_default();
+        let (first_block, end_block) = blocks_of(offset, len);
+        let mut num_free_blocks = self.limit_blocks.saturating_sub(self.used_blocks);
+        let mut charged_end = offset + len;
+        let mut charged = Vec::new();
+        for (gap_start, gap_end) in extents.gaps(first_block, end_block) {
+            let num_blocks = min(gap_end - gap_start, num_free_blocks);
+            if num_blocks > 0 {
+                extents.insert(gap_start, gap_start + num_blocks);
+                charged.push((gap_start, gap_start + num_blocks));
+                self.used_blocks += num_blocks;
+                num_free_blocks -= num_blocks;
+            }
+            if num_blocks < gap_end - gap_start {
+                charged_end = min((gap_start + num_blocks) * BLOCK_SIZE, charged_end);
+                break;
+            }
+        }
+        (charged_end.saturating_sub(offset), charged)
+    }
+
+    /// Free the blocks that are newly accounted by a charge
+    fn refund(&mut self, ino: usize, charged: &[(usize, usize)]) {
+        for &(first_block, end_block) in charged {
+            self.release_range(ino, first_block, end_block);
+        }
+    }
+
+    /// Account all the blocks of `[offset, offset + len)` of a file, or none of
+    /// them if the limit would be exceeded. Return whether they are accounted.
+    fn charge_all(&mut self, ino: usize, offset: usize, len: usize) -> bool {
+        let extents = self.files.entry(ino).or_default();
+        let (first_block, end_block) = blocks_of(offset, len);
+        let gaps = extents.gaps(first_block, end_block);
+        let num_new_blocks: usize = gaps.iter().map(|(start, end)| end - start).sum();
+        if self.used_blocks + num_new_blocks > self.limit_blocks {
+            return false;
+        }
+        for (gap_start, gap_end) in gaps {
+            extents.insert(gap_start, gap_end);
+        }
+        self.used_blocks += num_new_blocks;
+        true
+    }
+
+    /// Free the blocks of a file in `[first_block, end_block)`
+    fn release_range(&mut self, ino: usize, first_block: usize, end_block: usize) {
+        if let Some(extents) = self.files.get_mut(&ino) {
+            self.used_blocks -= extents.remove(first_block, end_block);
+        }
+    }
+
+    /// Free the blocks of a file from the block `first_block`
+    fn release_from(&mut self, ino: usize, first_block: usize) {
+        self.release_range(ino, first_block, usize::max_value());
+    }
+
+    /// Free all the blocks of a file
+    fn release_all(&mut self, ino: usize) {
+        if let Some(extents) = self.files.remove(&ino) {
+            self.used_blocks -= extents.num_blocks();
+        }
+    }
+}
+
+/// The blocks of a file as extents, i.e., `[start, end)` indexed by `start`.
+/// The extents are neither overlapped nor adjacent, so a large allocation
+/// takes one entry rather than one per block.
+#[derive(Debug, Default)]
+struct Extents(BTreeMap<usize, usize>);
+
+impl Extents {
+    /// Return the ranges of the blocks in `[start, end)` that are not in the
+    /// extents
+    fn gaps(&self, start: usize, end: usize) -> Vec<(usize, usize)> {
+        let mut gaps = Vec::new();
+        let mut pos = start;
+        if let Some((_, &prev_end)) = self.0.range(..start).next_back() {
+            pos = max(pos, prev_end);
+        }
+        for (&ext_start, &ext_end) in self.0.range(start..end) {
+            if pos < ext_start {
+                gaps.push((pos, ext_start));
+            }
+            pos = max(pos, ext_end);
+        }
+        if pos < end {
+            gaps.push((pos, end));
+        }
+        gaps
+    }
+
+    /// Add the blocks in `[start, end)`, merging the extents that overlap or
+    /// adjoin them
+    fn insert(&mut self, mut start: usize, mut end: usize) {
+        if let Some((&prev_start, &prev_end)) = self.0.range(..start).next_back() {
+            if prev_end >= start {
+                start = prev_start;
+                end = max(end, prev_end);
+            }
+        }
+        let merged: Vec<usize> = self.0.range(start..=end).map(|(&s, _)| s).collect();
+        for ext_start in merged {
+            let ext_end = self.0.remove(&ext_start).unwrap();
+            end = max(end, ext_end);
+        }
+        self.0.insert(start, end);
+    }
+
+    /// Remove the blocks in `[start, end)`, returning the number of them that
+    /// were in the extents
+    fn remove(&mut self, start: usize, end: usize) -> usize {
+        // Split the extent across `start`, so that the part after it is removed
+        // below
+        if let Some((&prev_start, &prev_end)) = self.0.range(..start).next_back() {
+            if prev_end > start {
+                self.0.insert(prev_start, start);
+                self.0.insert(start, prev_end);
+            }
+        }
+        let removed: Vec<(usize, usize)> =
+            self.0.range(start..end).map(|(&s, &e)| (s, e)).collect();
+        let mut num_removed = 0;
+        for (ext_start, ext_end) in removed {
+            self.0.remove(&ext_start);
+            if ext_end > end {
+                self.0.insert(end, ext_end);
+            }
+            num_removed += min(ext_end, end) - ext_start;
+        }
+        num_removed
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.0.iter().map(|(start, end)| end - start).sum()
+    }
+}
+
+/// Return the range of the blocks that `[offset, offset + len)` touches
+fn blocks_of(offset: usize, len: usize) -> (usize, usize) {
+    (
+        offset / BLOCK_SIZE,
+        (offset + len + BLOCK_SIZE - 1) / BLOCK_SIZE,
+    )
+}
+
+impl FileSystem for LimitedRamFS {
+    fn sync(&self) -> Result<()> {
+        self.inner.sync()
+    }
+
+    fn root_inode(&self) -> Arc<dyn INode> {
+        self.wrap_inode(self.inner.root_inode())
+    }
+
+    fn info(&self) -> FsInfo {
+        // Like Linux's ramfs, report zeros if there is no limit on the size
+        let (blocks, free) = match self.usage() {
+            Some(usage) => (
+                usage.limit_blocks,
+                usage.limit_blocks.saturating_sub(usage.used_blocks),
+            ),
+            None => (0, 0),
+        };
+        FsInfo {
+            bsize: BLOCK_SIZE,
+            frsize: BLOCK_SIZE,
+            blocks,
+            bfree: free,
+            bavail: free,
+            files: 0,
+            ffree: 0,
+            namemax: NAME_MAX,
+        }
+    }
+}
+
+impl LimitedRamFS {
+    /// Create a new `LimitedRamFS`, whose size is limited to `limit` bytes
+    /// if it is given
+    pub fn new(limit: Option<usize>) -> Arc<LimitedRamFS> {
+        LimitedRamFS {
+            inner: RamFS::new(),
+            usage: limit.map(|limit| {
+                Mutex::new(Usage {
+                    limit_blocks: limit / BLOCK_SIZE,
+                    ..Usage::default()
+                })
+            }),
+            self_ref: Weak::default(),
+        }
+        .wrap()
+    }
+
+    /// Wrap pure `LimitedRamFS` with Arc
+    /// Used in constructors
+    fn wrap(self) -> Arc<Self> {
+        // Create an Arc, make a Weak from it, then put it into the struct.
+        // It's a little tricky.
+        let fs = Arc::new(self);
+        let weak = Arc::downgrade(&fs);
+        let ptr = Arc::into_raw(fs) as *mut Self;
+        unsafe {
+            (*ptr).self_ref = weak;
+        }
+        unsafe { Arc::from_raw(ptr) }
+    }
+
+    fn wrap_inode(&self, inner: Arc<dyn INode>) -> Arc<dyn INode> {
+        Arc::new(LRNode {
+            inner,
+            fs: self.self_ref.upgrade().unwrap(),
+        })
+    }
+
+    /// Lock the accounting of the space, which is None if there is no limit
+    fn usage(&self) -> Option<MutexGuard<Usage>> {
+        self.usage.as_ref().map(|usage| usage.lock().unwrap())
+    }
+
+    fn is_full(&self) -> bool {
+        match self.usage() {
+            Some(usage) => usage.used_blocks >= usage.limit_blocks,
+            None => false,
+        }
+    }
+}
+
+impl INode for LRNode {
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        self.inner.read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        let mut usage = match self.fs.usage() {
+            Some(usage) if buf.len() > 0 => usage,
+            _ => return self.inner.write_at(offset, buf),
+        };
+        let metadata = self.inner.metadata()?;
+        // The gap between the end of the file and the offset is filled with
+        // zeros, which take space as well
+        let charge_start = min(offset, metadata.size);
+        let (charged_len, charged) = usage.charge(
+            metadata.inode,
+            charge_start,
+            offset + buf.len() - charge_start,
+        );
+        drop(usage);
+        let len = (charge_start + charged_len).saturating_sub(offset);
+        let result = if len > 0 {
+            self.inner.write_at(offset, &buf[..len])
+        } else {
+            Err(FsError::NoDeviceSpace)
+        };
+        if result.is_err() {
+            if let Some(mut usage) = self.fs.usage() {
+                usage.refund(metadata.inode, &charged);
+            }
+        }
+        result
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        self.inner.poll()
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        self.inner.metadata()
+    }
+
+    fn set_metadata(&self, metadata: &Metadata) -> Result<()> {
+        self.inner.set_metadata(metadata)
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all()
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        self.inner.sync_data()
+    }
+
+    fn resize(&self, len: usize) -> Result<()> {
+        self.inner.resize(len)?;
+        // An extended range is a hole, which takes no space until written
+        if let Some(mut usage) = self.fs.usage() {
+            let ino = self.inner.metadata()?.inode;
+            let first_freed_block = (len + BLOCK_SIZE - 1) / BLOCK_SIZE;
+            usage.release_from(ino, first_freed_block);
+        }
+        Ok(())
+    }
+
+    fn create(&self, name: &str, type_: FileType, mode: u32) -> Result<Arc<dyn INode>> {
+        if self.fs.is_full() {
+            return Err(FsError::NoDeviceSpace);
+        }
+        let inode = self.inner.create(name, type_, mode)?;
+        Ok(self.fs.wrap_inode(inode))
+    }
+
+    fn link(&self, name: &str, other: &Arc<dyn INode>) -> Result<()> {
+        let other = other.downcast_ref::<Self>().ok_or(FsError::NotSameFs)?;
+        self.inner.link(name, &other.inner)
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        let metadata = self.inner.find(name)?.metadata()?;
+        self.inner.unlink(name)?;
+        self.release_if_last_link(&metadata);
+        Ok(())
+    }
+
+    fn move_(&self, old_name: &str, target: &Arc<dyn INode>, new_name: &str) -> Result<()> {
+        let target = target.downcast_ref::<Self>().ok_or(FsError::NotSameFs)?;
+        // The file at the new name, if any, is replaced by the move
+        let moved_ino = self.inner.find(old_name)?.metadata()?.inode;
+        let replaced = target
+            .inner
+            .find(new_name)
+            .and_then(|inode| inode.metadata())
+            .ok()
+            .filter(|metadata| metadata.inode != moved_ino);
+        self.inner.move_(old_name, &target.inner, new_name)?;
+        if let Some(metadata) = replaced {
+            self.release_if_last_link(&metadata);
+        }
+        Ok(())
+    }
+
+    fn find(&self, name: &str) -> Result<Arc<dyn INode>> {
+        let inode = self.inner.find(name)?;
+        Ok(self.fs.wrap_inode(inode))
+    }
+
+    fn get_entry(&self, id: usize) -> Result<String> {
+        self.inner.get_entry(id)
+    }
+
+    fn io_control(&self, cmd: u32, data: usize) -> Result<()> {
+        if cmd == IOC_FALLOCATE {
+            let args = unsafe { &mut *(data as *mut FallocateArgs) };
+            args.is_done = true;
+            return match args.op {
+                FallocateOp::Allocate { keep_size } => {
+                    self.allocate(args.offset, args.len, keep_size)
+                }
+                FallocateOp::PunchHole => self.punch_hole(args.offset, args.len),
+            };
+        }
+        self.inner.io_control(cmd, data)
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.clone()
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl LRNode {
+    /// Allocate the space of `[offset, offset + len)`, extending the file
+    /// unless `keep_size` is given
+    fn allocate(&self, offset: usize, len: usize, keep_size: bool) -> Result<()> {
+        let metadata = self.inner.metadata()?;
+        let is_charged = match self.fs.usage() {
+            Some(mut usage) => usage.charge_all(metadata.inode, offset, len),
+            None => true,
+        };
+        if !is_charged {
+            return Err(FsError::NoDeviceSpace);
+        }
+        if !keep_size && offset + len > metadata.size {
+            self.inner.resize(offset + len)?;
+        }
+        Ok(())
+    }
+
+    /// Deallocate the space of `[offset, offset + len)`, which reads as zeros
+    /// afterwards. Only the blocks that are entirely in the range are freed.
+    fn punch_hole(&self, offset: usize, len: usize) -> Result<()> {
+        let metadata = self.inner.metadata()?;
+        let end = min(offset + len, metadata.size);
+        let zeros = [0u8; BLOCK_SIZE];
+        let mut pos = offset;
+        while pos < end {
+            let zero_len = min(end - pos, zeros.len());
+            pos += self.inner.write_at(pos, &zeros[..zero_len])?;
+        }
+        let first_block = (offset + BLOCK_SIZE - 1) / BLOCK_SIZE;
+        let end_block = (offset + len) / BLOCK_SIZE;
+        if let Some(mut usage) = self.fs.usage() {
+            if first_block < end_block {
+                usage.release_range(metadata.inode, first_block, end_block);
+            }
+        }
+        Ok(())
+    }
+
+    /// Free the blocks of a file whose last link has just been removed
+    fn release_if_last_link(&self, metadata: &Metadata) {
+        if metadata.type_ != FileType::File || metadata.nlinks > 1 {
+            return;
+        }
+        if let Some(mut usage) = self.fs.usage() {
+            usage.release_all(metadata.inode);
+        }
+    }
+}
+
+/// The operations of fallocate that LimitedRamFS supports
+#[derive(Clone, Copy, Debug)]
+pub enum FallocateOp {
+    Allocate { keep_size: bool },
+    PunchHole,
+}
+
+#[derive(Debug)]
+struct FallocateArgs {
+    op: FallocateOp,
+    offset: usize,
+    len: usize,
+    // Set by `LRNode`, so that the other file systems, which may ignore the
+    // command silently, are told apart
+    is_done: bool,
+}
+
+/// Do fallocate on the inode if it is of LimitedRamFS, returning false if not
+pub fn fallocate(
+    inode: &Arc<dyn INode>,
+    op: FallocateOp,
+    offset: usize,
+    len: usize,
+) -> Result<bool> {
+    let mut args = FallocateArgs {
+        op,
+        offset,
+        len,
+        is_done: false,
+    };
+    let result = inode.io_control(IOC_FALLOCATE, &mut args as *mut FallocateArgs as usize);
+    if !args.is_done {
+        return Ok(false);
+    }
+    result.map(|_| true)
+}
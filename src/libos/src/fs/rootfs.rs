@@ -1,36 +1,38 @@
 use super::hostfs::HostFS;
-use super::sefs::{SgxStorage, SgxUuidProvider};
+use super::ramfs::LimitedRamFS;
+use super::sefs::{self, AtimePolicy, SgxStorage, SgxUuidProvider};
 use super::*;
 use config::{ConfigMount, ConfigMountFsType};
 use std::path::{Path, PathBuf};
 
-use rcore_fs_mountfs::{MNode, MountFS};
-use rcore_fs_ramfs::RamFS;
 use rcore_fs_sefs::dev::*;
 use rcore_fs_sefs::SEFS;
 
 lazy_static! {
-    /// The root of file system
-    pub static ref ROOT_INODE: Arc<dyn INode> = {
-        fn init_root_inode() -> Result<Arc<dyn INode>> {
+    /// The mount namespace the processes are in, unless they unshare it
+    pub static ref INIT_MOUNT_NS: Arc<MountNamespace> = {
+        fn init_mount_ns() -> Result<Arc<MountNamespace>> {
             let mount_config = &config::LIBOS_CONFIG.mount;
-            let root_inode = {
+            let mount_ns = {
                 let rootfs = open_root_fs_according_to(mount_config)?;
-                rootfs.root_inode()
+                MountNamespace::new(rootfs)
             };
-            mount_nonroot_fs_according_to(mount_config, &root_inode)?;
-            Ok(root_inode)
+            mount_nonroot_fs_according_to(mount_config, &mount_ns)?;
+            Ok(Arc::new(mount_ns))
         }
 
-        init_root_inode().unwrap_or_else(|e| {
+        init_mount_ns().unwrap_or_else(|e| {
             error!("failed to init root inode: {}", e.backtrace());
             panic!();
         })
     };
+
+    /// The root of file system in the initial mount namespace
+    pub static ref ROOT_INODE: Arc<dyn INode> = INIT_MOUNT_NS.root_inode();
 }
 
-fn open_root_fs_according_to(mount_config: &Vec<ConfigMount>) -> Result<Arc<MountFS>> {
-    let (root_sefs_mac, root_sefs_source) = {
+fn open_root_fs_according_to(mount_config: &Vec<ConfigMount>) -> Result<Arc<dyn FileSystem>> {
+    let (root_sefs_mac, root_sefs_source, root_read_only) = {
         let root_mount_config = mount_config
             .iter()
             .find(|m| m.target == Path::new("/"))
@@ -42,6 +44,10 @@ fn open_root_fs_according_to(mount_config: &Vec<ConfigMount>) -> Result<Arc<Moun
         if !root_mount_config.options.integrity_only {
             return_errno!(EINVAL, "The root SEFS at / must be integrity-only");
         }
+        // Updating the access time would change the MAC of the root SEFS
+        if root_mount_config.options.atime.is_some() {
+            return_errno!(EINVAL, "The atime option is not supported by the root SEFS");
+        }
         if root_mount_config.source.is_none() {
             return_errno!(
                 EINVAL,
@@ -51,19 +57,30 @@ fn open_root_fs_according_to(mount_config: &Vec<ConfigMount>) -> Result<Arc<Moun
         (
             root_mount_config.options.mac,
             root_mount_config.source.as_ref().unwrap(),
+            root_mount_config.options.read_only,
         )
     };
 
+    let root_storage = SgxStorage::new(root_sefs_source, true, root_sefs_mac);
     let root_sefs = SEFS::open(
-        Box::new(SgxStorage::new(root_sefs_source, true, root_sefs_mac)),
+        Box::new(root_storage.clone()),
         &time::OcclumTimeProvider,
         &SgxUuidProvider,
     )?;
-    let root_mountable_sefs = MountFS::new(root_sefs);
-    Ok(root_mountable_sefs)
+    sefs::register_mount(
+        Path::new("/"),
+        root_sefs.clone(),
+        Some(&root_storage),
+        AtimePolicy::No,
+        root_read_only,
+    );
+    Ok(root_sefs)
 }
 
-fn mount_nonroot_fs_according_to(mount_config: &Vec<ConfigMount>, root: &MNode) -> Result<()> {
+fn mount_nonroot_fs_according_to(
+    mount_config: &Vec<ConfigMount>,
+    mount_ns: &MountNamespace,
+) -> Result<()> {
     for mc in mount_config {
         if mc.target == Path::new("/") {
             continue;
@@ -75,9 +92,14 @@ fn mount_nonroot_fs_according_to(mount_config: &Vec<ConfigMount>, root: &MNode)
         if mc.target.parent().unwrap() != Path::new("/") {
             return_errno!(EINVAL, "The target mount point must be under /");
         }
-        let target_dirname = mc.target.file_name().unwrap().to_str().unwrap();
 
         use self::ConfigMountFsType::*;
+        if mc.options.size.is_some() && mc.type_ != TYPE_RAMFS {
+            return_errno!(EINVAL, "The size option is only supported by RamFS");
+        }
+        if mc.options.atime.is_some() && mc.type_ != TYPE_SEFS {
+            return_errno!(EINVAL, "The atime option is only supported by SEFS");
+        }
         match mc.type_ {
             TYPE_SEFS => {
                 if mc.options.integrity_only {
@@ -87,21 +109,29 @@ fn mount_nonroot_fs_according_to(mount_config: &Vec<ConfigMount>, root: &MNode)
                     return_errno!(EINVAL, "Source is expected for SEFS");
                 }
                 let source_path = mc.source.as_ref().unwrap();
+                let storage = SgxStorage::new(source_path, false, None);
                 let sefs = {
                     SEFS::open(
-                        Box::new(SgxStorage::new(source_path, false, None)),
+                        Box::new(storage.clone()),
                         &time::OcclumTimeProvider,
                         &SgxUuidProvider,
                     )
                 }
                 .or_else(|_| {
                     SEFS::create(
-                        Box::new(SgxStorage::new(source_path, false, None)),
+                        Box::new(storage.clone()),
                         &time::OcclumTimeProvider,
                         &SgxUuidProvider,
                     )
                 })?;
-                mount_fs_at(sefs, &root, target_dirname)?;
+                mount_ns.mount(sefs.clone(), &mc.target)?;
+                sefs::register_mount(
+                    &mc.target,
+                    sefs,
+                    Some(&storage),
+                    mc.options.atime.unwrap_or_default(),
+                    mc.options.read_only,
+                );
             }
             TYPE_HOSTFS => {
                 if mc.source.is_none() {
@@ -110,27 +140,27 @@ fn mount_nonroot_fs_according_to(mount_config: &Vec<ConfigMount>, root: &MNode)
                 let source_path = mc.source.as_ref().unwrap();
 
                 let hostfs = HostFS::new(source_path);
-                mount_fs_at(hostfs, &root, target_dirname)?;
+                mount_ns.mount(hostfs.clone(), &mc.target)?;
+                sefs::register_mount(
+                    &mc.target,
+                    hostfs,
+                    None,
+                    AtimePolicy::No,
+                    mc.options.read_only,
+                );
             }
             TYPE_RAMFS => {
-                let ramfs = RamFS::new();
-                mount_fs_at(ramfs, &root, target_dirname)?;
+                let ramfs = LimitedRamFS::new(mc.options.size);
+                mount_ns.mount(ramfs.clone(), &mc.target)?;
+                sefs::register_mount(
+                    &mc.target,
+                    ramfs,
+                    None,
+                    AtimePolicy::No,
+                    mc.options.read_only,
+                );
             }
         }
     }
     Ok(())
 }
-
-fn mount_fs_at(fs: Arc<dyn FileSystem>, parent_inode: &MNode, dirname: &str) -> Result<()> {
-    let mount_dir = match parent_inode.find(false, dirname) {
-        Ok(existing_dir) => {
-            if existing_dir.metadata()?.type_ != FileType::Dir {
-                return_errno!(EIO, "not a directory");
-            }
-            existing_dir
-        }
-        Err(_) => return_errno!(ENOENT, "Mount point does not exist"),
-    };
-    mount_dir.mount(fs);
-    Ok(())
-}
@@ -0,0 +1,70 @@
+//! Update the access time of the files of SEFS on reads.
+//!
+//! Updating the access time makes a read also write the metadata of the file,
+//! so it is done according to the policy of the mount:
+//!
+//! * `strictatime`: update the access time on every read.
+//! * `relatime`: update the access time only if it is not later than the
+//!   modification or the status change time, or it is older than a day, as in
+//!   Linux. So it still tells whether a file has been read since modified.
+//! * `noatime`: never update the access time, which is the default.
+
+use super::mounts::{find_atime_policy, is_read_only};
+use super::*;
+use rcore_fs::dev::TimeProvider;
+
+/// The access time older than this is updated under relatime
+const RELATIME_MAX_AGE_SECS: i64 = 24 * 60 * 60;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AtimePolicy {
+    Strict,
+    Relative,
+    No,
+}
+
+impl AtimePolicy {
+    /// Parse the policy from the name of the mount option
+    pub fn from_str(option: &str) -> Result<AtimePolicy> {
+        match option {
+            "strictatime" => Ok(AtimePolicy::Strict),
+            "relatime" => Ok(AtimePolicy::Relative),
+            "noatime" => Ok(AtimePolicy::No),
+            _ => return_errno!(EINVAL, "unknown atime option"),
+        }
+    }
+}
+
+impl Default for AtimePolicy {
+    fn default() -> AtimePolicy {
+        AtimePolicy::No
+    }
+}
+
+/// Update the access time of the inode at `abs_path`, which has been read,
+/// according to the policy of its mount. It is never updated on a read-only
+/// mount.
+pub fn touch_atime(abs_path: &str, inode: &Arc<dyn INode>) -> Result<()> {
+    let policy = find_atime_policy(abs_path);
+    if policy == AtimePolicy::No || is_read_only(inode) {
+        return Ok(());
+    }
+
+    let mut metadata = inode.metadata()?;
+    let now = time::OcclumTimeProvider.current_time();
+    let need_update = match policy {
+        AtimePolicy::Strict => true,
+        AtimePolicy::Relative => {
+            let atime = (metadata.atime.sec, metadata.atime.nsec);
+            atime <= (metadata.mtime.sec, metadata.mtime.nsec)
+                || atime <= (metadata.ctime.sec, metadata.ctime.nsec)
+                || now.sec - metadata.atime.sec >= RELATIME_MAX_AGE_SECS
+        }
+        AtimePolicy::No => false,
+    };
+    if need_update {
+        metadata.atime = now;
+        inode.set_metadata(&metadata)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,272 @@
+//! Integrity-only files in an encrypted SEFS.
+//!
+//! A file created with `O_INTEGRITY_ONLY` in an encrypted SEFS is stored in
+//! plaintext and only authenticated by MACs, which saves the cost of
+//! encryption for files that are public but must be tamper-evident. Such files
+//! can be mixed with encrypted files in one SEFS.
+//!
+//! Since the MACs of integrity-only files can be computed by anyone, they are
+//! recorded in the integrity table of the storage, which is kept in an
+//! encrypted file. An integrity-only file whose MAC does not match the table
+//! fails to open, and a tampered block fails to read, both with EIO.
+
+use super::mounts::find_mount;
+use super::rekey::{StorageKeys, TMP_FILE_SUFFIX};
+use super::sgx_storage::{fsync_host_file, open_sgx_file};
+use super::*;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sgxfs::OpenOptions;
+use std::untrusted::fs as host_fs;
+use std::untrusted::path::PathEx;
+
+pub const INTEGRITY_TABLE_FILE: &str = "integrity_table";
+
+/// The MAC of a file that has not been flushed yet
+const UNSET_MAC: sgx_aes_gcm_128bit_tag_t = [0; 16];
+
+#[derive(Debug, Copy, Clone)]
+struct IntegrityEntry {
+    ino: u64,
+    mac: sgx_aes_gcm_128bit_tag_t,
+}
+
+/// The integrity-only files of an encrypted storage, indexed by file IDs
+pub struct IntegrityTable {
+    path: PathBuf,
+    keys: Arc<SgxRwLock<StorageKeys>>,
+    entries: SgxMutex<BTreeMap<String, IntegrityEntry>>,
+}
+
+impl IntegrityTable {
+    pub fn load(dir: &Path, keys: Arc<SgxRwLock<StorageKeys>>) -> IntegrityTable {
+        let path = dir.join(INTEGRITY_TABLE_FILE);
+        let entries = if path.exists() {
+            let candidates = keys.read().unwrap().candidates();
+            read_entries(&path, &candidates).unwrap_or_else(|e| {
+                error!("failed to load the integrity table: {}", e.backtrace());
+                BTreeMap::new()
+            })
+        } else {
+            BTreeMap::new()
+        };
+        IntegrityTable {
+            path,
+            keys,
+            entries: SgxMutex::new(entries),
+        }
+    }
+
+    pub fn contains(&self, file_id: &str) -> bool {
+        self.entries.lock().unwrap().contains_key(file_id)
+    }
+
+    pub fn contains_ino(&self, ino: u64) -> bool {
+        self.entries
+            .lock()
+            .unwrap()
+            .values()
+            .any(|entry| entry.ino == ino)
+    }
+
+    /// Get the recorded MAC of a file, if it has been flushed
+    pub fn get_mac(&self, file_id: &str) -> Option<sgx_aes_gcm_128bit_tag_t> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(file_id)
+            .map(|entry| entry.mac)
+            .filter(|mac| *mac != UNSET_MAC)
+    }
+
+    pub fn insert(&self, file_id: &str) -> Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = IntegrityEntry {
+            ino: 0,
+            mac: UNSET_MAC,
+        };
+        entries.insert(file_id.to_string(), entry);
+        self.persist(&entries)
+    }
+
+    pub fn set_ino(&self, file_id: &str, ino: u64) -> Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries
+            .get_mut(file_id)
+            .ok_or_else(|| errno!(ENOENT, "not an integrity-only file"))?;
+        entry.ino = ino;
+        self.persist(&entries)
+    }
+
+    pub fn update_mac(&self, file_id: &str, mac: &sgx_aes_gcm_128bit_tag_t) -> Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries
+            .get_mut(file_id)
+            .ok_or_else(|| errno!(ENOENT, "not an integrity-only file"))?;
+        if entry.mac == *mac {
+            return Ok(());
+        }
+        entry.mac = *mac;
+        self.persist(&entries)
+    }
+
+    pub fn remove(&self, file_id: &str) -> Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.remove(file_id).is_some() {
+            self.persist(&entries)?;
+        }
+        Ok(())
+    }
+
+    /// Write the table again with the newest key
+    pub fn rekey(&self) -> Result<()> {
+        let entries = self.entries.lock().unwrap();
+        self.persist(&entries)
+    }
+
+    /// Write the table to a temporary file and rename it over the table file,
+    /// so that the table is always complete on the host.
+    fn persist(&self, entries: &BTreeMap<String, IntegrityEntry>) -> Result<()> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        for (file_id, entry) in entries.iter() {
+            buf.extend_from_slice(&(file_id.len() as u32).to_le_bytes());
+            buf.extend_from_slice(file_id.as_bytes());
+            buf.extend_from_slice(&entry.ino.to_le_bytes());
+            buf.extend_from_slice(&entry.mac);
+        }
+
+        let mut tmp_path = self.path.as_os_str().to_owned();
+        tmp_path.push(TMP_FILE_SUFFIX);
+        let tmp_path = PathBuf::from(tmp_path);
+        {
+            let key = self.keys.read().unwrap().newest();
+            let mut options = OpenOptions::new();
+            options.write(true).update(true);
+            let mut file = open_sgx_file(&options, &tmp_path, false, &[key])?;
+            file.write_all(&buf)?;
+            file.flush()?;
+        }
+        fsync_host_file(&tmp_path).map_err(|_| errno!(EIO, "failed to fsync the host file"))?;
+        host_fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+fn read_entries(
+    path: &Path,
+    keys: &[Option<sgx_key_128bit_t>],
+) -> Result<BTreeMap<String, IntegrityEntry>> {
+    let buf = {
+        let mut options = OpenOptions::new();
+        options.read(true);
+        let mut file = open_sgx_file(&options, path, false, keys)?;
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+        buf
+    };
+
+    let mut reader = ByteReader(&buf);
+    let mut entries = BTreeMap::new();
+    let num_entries = reader.read_u32()?;
+    for _ in 0..num_entries {
+        let file_id_len = reader.read_u32()? as usize;
+        let file_id = String::from_utf8(reader.read_bytes(file_id_len)?.to_vec())
+            .map_err(|_| errno!(EINVAL, "invalid file ID in the integrity table"))?;
+        let ino = reader.read_u64()?;
+        let mut mac = UNSET_MAC;
+        mac.copy_from_slice(reader.read_bytes(mac.len())?);
+        entries.insert(file_id, IntegrityEntry { ino, mac });
+    }
+    Ok(entries)
+}
+
+struct ByteReader<'a>(&'a [u8]);
+
+impl<'a> ByteReader<'a> {
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            return_errno!(EINVAL, "the integrity table is truncated");
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.read_bytes(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.read_bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+thread_local! {
+    // Whether the file being created by the current thread is integrity-only
+    static CREATE_INTEGRITY_ONLY: Cell<bool> = Cell::new(false);
+    // The ID of the integrity-only file created by the current thread
+    static CREATED_FILE_ID: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Create an integrity-only file with the given function, which creates the
+/// file at the path.
+///
+/// SEFS asks its storage to create the underlying file without telling which
+/// inode it is for. So the storage is told by a thread-local flag to create an
+/// integrity-only file, and the inode number is recorded after it is created.
+pub fn create_integrity_only(
+    abs_path: &str,
+    create_fn: impl FnOnce() -> Result<Arc<dyn INode>>,
+) -> Result<Arc<dyn INode>> {
+    let storage = find_mount(abs_path)
+        .ok_or_else(|| errno!(EINVAL, "integrity-only files are only supported by SEFS"))?;
+    if storage.is_integrity_only() {
+        return create_fn();
+    }
+
+    CREATE_INTEGRITY_ONLY.with(|flag| flag.set(true));
+    let inode_res = create_fn();
+    CREATE_INTEGRITY_ONLY.with(|flag| flag.set(false));
+    let file_id = CREATED_FILE_ID.with(|file_id| file_id.borrow_mut().take());
+
+    let inode = inode_res?;
+    if let Some(file_id) = file_id {
+        let ino = inode.metadata()?.inode as u64;
+        storage.set_integrity_only_ino(&file_id, ino)?;
+    }
+    Ok(inode)
+}
+
+/// Called by the storage to check whether the file to be created is
+/// integrity-only
+pub fn should_create_integrity_only() -> bool {
+    CREATE_INTEGRITY_ONLY.with(|flag| flag.get())
+}
+
+/// Called by the storage after an integrity-only file is created
+pub fn set_created_file_id(file_id: &str) {
+    CREATED_FILE_ID.with(|created| *created.borrow_mut() = Some(file_id.to_string()));
+}
+
+/// The protection of a file on SEFS
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FileProtection {
+    Encrypted,
+    IntegrityOnly,
+}
+
+/// Get the protection of a file, or None if the file is not on SEFS
+pub fn get_file_protection(abs_path: &str, ino: u64) -> Option<FileProtection> {
+    let storage = find_mount(abs_path)?;
+    if storage.is_integrity_only() || storage.is_integrity_only_ino(ino) {
+        Some(FileProtection::IntegrityOnly)
+    } else {
+        Some(FileProtection::Encrypted)
+    }
+}
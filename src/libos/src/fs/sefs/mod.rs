@@ -1,7 +1,15 @@
-use super::sgx_aes_gcm_128bit_tag_t;
+use super::*;
 
+pub use self::atime::{touch_atime, AtimePolicy};
+pub use self::integrity::{create_integrity_only, get_file_protection, FileProtection};
+pub use self::mounts::{check_not_read_only, is_read_only, register_mount, remount};
+pub use self::rekey::{check_not_rekeying, do_ioctl, is_sefs_ioctl};
 pub use self::sgx_storage::SgxStorage;
 pub use self::sgx_uuid_provider::SgxUuidProvider;
 
+mod atime;
+mod integrity;
+mod mounts;
+mod rekey;
 mod sgx_storage;
 mod sgx_uuid_provider;
@@ -0,0 +1,125 @@
+//! The registry of mount points, which finds the SEFS storage of a path, and
+//! the options of the mount that the path or the inode is on.
+
+use super::*;
+use rcore_fs_mountfs::MNode;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+lazy_static! {
+    static ref MOUNTS: SgxRwLock<Vec<Mount>> = Default::default();
+}
+
+/// The number of the read-only mounts, so that the writes are not slowed down
+/// by looking for the mount if there is none
+static NUM_READ_ONLY_MOUNTS: AtomicUsize = AtomicUsize::new(0);
+
+struct Mount {
+    target: PathBuf,
+    fs: Arc<dyn FileSystem>,
+    // The storage of an SEFS, or None for the other file systems
+    storage: Option<SgxStorage>,
+    atime_policy: AtimePolicy,
+    read_only: bool,
+}
+
+pub fn register_mount(
+    target: &Path,
+    fs: Arc<dyn FileSystem>,
+    storage: Option<&SgxStorage>,
+    atime_policy: AtimePolicy,
+    read_only: bool,
+) {
+    let mut mounts = MOUNTS.write().unwrap();
+    mounts.push(Mount {
+        target: target.to_path_buf(),
+        fs,
+        storage: storage.cloned(),
+        atime_policy,
+        read_only,
+    });
+    if read_only {
+        NUM_READ_ONLY_MOUNTS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Change the atime policy of the mount point at `target`, and whether it is
+/// read-only
+pub fn remount(target: &Path, atime_policy: AtimePolicy, read_only: bool) -> Result<()> {
+    let mut mounts = MOUNTS.write().unwrap();
+    let mount = mounts
+        .iter_mut()
+        .find(|mount| mount.target == target)
+        .ok_or_else(|| errno!(EINVAL, "not a mount point"))?;
+    mount.atime_policy = atime_policy;
+    if mount.read_only != read_only {
+        mount.read_only = read_only;
+        if read_only {
+            NUM_READ_ONLY_MOUNTS.fetch_add(1, Ordering::SeqCst);
+        } else {
+            NUM_READ_ONLY_MOUNTS.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+    Ok(())
+}
+
+/// Find the storage of the SEFS that the path is on, i.e., the innermost mount
+/// point that contains the path is an SEFS.
+pub fn find_mount(abs_path: &str) -> Option<SgxStorage> {
+    find_innermost_mount(abs_path, |mount| mount.storage.clone())
+}
+
+/// Find the atime policy of the SEFS that the path is on. The access time is
+/// never updated for the other file systems.
+pub fn find_atime_policy(abs_path: &str) -> AtimePolicy {
+    find_innermost_mount(abs_path, |mount| {
+        mount.storage.as_ref().map(|_| mount.atime_policy)
+    })
+    .unwrap_or(AtimePolicy::No)
+}
+
+/// Whether the inode is on a read-only mount.
+///
+/// The mount is told by the file system of the inode, not by the path to it,
+/// so that a path through `..` or a symlink cannot escape from the mount.
+pub fn is_read_only(inode: &Arc<dyn INode>) -> bool {
+    if NUM_READ_ONLY_MOUNTS.load(Ordering::SeqCst) == 0 {
+        return false;
+    }
+    // The inodes looked up from the root are wrapped by MountFS
+    let fs = match inode.downcast_ref::<MNode>() {
+        Some(mnode) => mnode.inode.fs(),
+        None => inode.fs(),
+    };
+    MOUNTS
+        .read()
+        .unwrap()
+        .iter()
+        .any(|mount| mount.read_only && is_same_fs(&mount.fs, &fs))
+}
+
+fn is_same_fs(fs: &Arc<dyn FileSystem>, other: &Arc<dyn FileSystem>) -> bool {
+    // Compare the addresses only, as the vtables of the same type may differ
+    &**fs as *const dyn FileSystem as *const u8 == &**other as *const dyn FileSystem as *const u8
+}
+
+/// Modifications to anything on a read-only mount are rejected with EROFS,
+/// before the file system is touched. For the modifications to the entries of
+/// a directory, e.g., creation and unlink, the inode is the directory.
+pub fn check_not_read_only(inode: &Arc<dyn INode>) -> Result<()> {
+    if is_read_only(inode) {
+        return_errno!(EROFS, "the file system is mounted read-only");
+    }
+    Ok(())
+}
+
+fn find_innermost_mount<T>(abs_path: &str, f: impl Fn(&Mount) -> Option<T>) -> Option<T> {
+    let path = Path::new(abs_path);
+    MOUNTS
+        .read()
+        .unwrap()
+        .iter()
+        .filter(|mount| path.starts_with(&mount.target))
+        .max_by_key(|mount| mount.target.components().count())
+        .and_then(f)
+}
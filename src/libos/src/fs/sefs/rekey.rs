@@ -0,0 +1,246 @@
+//! Rotate the encryption key of a mounted SEFS.
+//!
+//! By default, the files of an SEFS are encrypted with a key derived from the
+//! sealing key of the enclave. With the `SEFS_IOC_REKEY` ioctl on any file of
+//! the mount, the files can be re-encrypted with a new key given by the user,
+//! without re-creating the image. The keys are kept in the image directory in
+//! two files, which are sealed with the default key:
+//!
+//! * `key`: the current key. If it does not exist, the default key is used.
+//! * `key.new`: the new key of an ongoing rekey, which serves as the journal.
+//!
+//! A rekey first writes `key.new`, then re-encrypts the files one by one, each
+//! of which is written to a temporary file and renamed over the original one.
+//! At last, `key.new` is renamed over `key`, which switches the key atomically.
+//! If the rekey is interrupted, every file is complete under either the old or
+//! the new key, and files are opened with the new key first and then the old
+//! key. Issuing the rekey with the same key again completes the rotation.
+//!
+//! During a rekey, opening, creating, and removing files of the SEFS wait for
+//! the file that is being re-encrypted at the moment, and writing to files
+//! fail with EBUSY. The progress can be monitored by
+//! `SEFS_IOC_GET_REKEY_PROGRESS`.
+
+use super::mounts::find_mount;
+use super::sgx_storage::fsync_host_file;
+use super::*;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sgxfs::OpenOptions;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::untrusted::fs as host_fs;
+use std::untrusted::path::PathEx;
+
+pub const KEY_FILE: &str = "key";
+pub const NEW_KEY_FILE: &str = "key.new";
+/// The suffix of the temporary files written by a rekey
+pub const TMP_FILE_SUFFIX: &str = ".rekey";
+
+const SEFS_MAGIC_CHAR: u8 = b'f';
+
+/// Ioctl to re-encrypt the SEFS with a new key
+pub const SEFS_IOC_REKEY: u32 =
+    StructuredIoctlNum::new::<SefsRekeyArg>(0x80, SEFS_MAGIC_CHAR, StructuredIoctlArgType::Input)
+        .as_u32();
+/// Ioctl to get the progress of the rekey
+pub const SEFS_IOC_GET_REKEY_PROGRESS: u32 = StructuredIoctlNum::new::<SefsRekeyProgress>(
+    0x81,
+    SEFS_MAGIC_CHAR,
+    StructuredIoctlArgType::Output,
+)
+.as_u32();
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct SefsRekeyArg {
+    pub new_key: sgx_key_128bit_t,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SefsRekeyProgress {
+    pub in_progress: u32,
+    pub padding: u32,
+    /// The number of files to be re-encrypted
+    pub total_files: u64,
+    /// The number of files that have been re-encrypted
+    pub done_files: u64,
+}
+
+/// The keys of an SEFS storage
+#[derive(Debug, Default, Copy, Clone)]
+pub struct StorageKeys {
+    // None means the default key
+    current: Option<sgx_key_128bit_t>,
+    // The new key of an ongoing (or interrupted) rekey
+    new: Option<sgx_key_128bit_t>,
+}
+
+impl StorageKeys {
+    pub fn load(dir: &Path) -> StorageKeys {
+        StorageKeys {
+            current: read_key_file(&dir.join(KEY_FILE)),
+            new: read_key_file(&dir.join(NEW_KEY_FILE)),
+        }
+    }
+
+    pub fn is_key_file(file_id: &str) -> bool {
+        file_id == KEY_FILE || file_id == NEW_KEY_FILE
+    }
+
+    /// The keys to open an existing file with, in order
+    pub fn candidates(&self) -> Vec<Option<sgx_key_128bit_t>> {
+        let mut keys = Vec::new();
+        if self.new.is_some() {
+            keys.push(self.new);
+        }
+        keys.push(self.current);
+        keys
+    }
+
+    /// The key to create a new file with
+    pub fn newest(&self) -> Option<sgx_key_128bit_t> {
+        self.new.or(self.current)
+    }
+
+    pub fn pending(&self) -> Option<sgx_key_128bit_t> {
+        self.new
+    }
+
+    pub fn begin_rekey(&mut self, dir: &Path, new_key: &sgx_key_128bit_t) -> Result<()> {
+        write_key_file(&dir.join(NEW_KEY_FILE), new_key)?;
+        self.new = Some(*new_key);
+        Ok(())
+    }
+
+    pub fn commit_rekey(&mut self, dir: &Path) -> Result<()> {
+        host_fs::rename(dir.join(NEW_KEY_FILE), dir.join(KEY_FILE))?;
+        // Make the rename durable
+        fsync_host_file(dir).map_err(|_| errno!(EIO, "failed to fsync the SEFS directory"))?;
+        self.current = self.new.take();
+        Ok(())
+    }
+}
+
+fn read_key_file(path: &Path) -> Option<sgx_key_128bit_t> {
+    if !path.exists() {
+        return None;
+    }
+    let read_key = || -> Result<sgx_key_128bit_t> {
+        let mut key: sgx_key_128bit_t = Default::default();
+        let mut file = OpenOptions::new().read(true).open(path)?;
+        file.read_exact(&mut key)?;
+        Ok(key)
+    };
+    match read_key() {
+        Ok(key) => Some(key),
+        Err(e) => {
+            error!(
+                "failed to read the SEFS key file {:?}: {}",
+                path,
+                e.backtrace()
+            );
+            None
+        }
+    }
+}
+
+fn write_key_file(path: &Path, key: &sgx_key_128bit_t) -> Result<()> {
+    {
+        let mut file = OpenOptions::new().write(true).open(path)?;
+        file.write_all(key)?;
+        file.flush()?;
+    }
+    fsync_host_file(path).map_err(|_| errno!(EIO, "failed to fsync the SEFS key file"))?;
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+pub struct RekeyStatus {
+    in_progress: AtomicBool,
+    total_files: AtomicUsize,
+    done_files: AtomicUsize,
+}
+
+impl RekeyStatus {
+    /// Return false if a rekey is already in progress
+    pub fn start(&self) -> bool {
+        if self
+            .in_progress
+            .compare_and_swap(false, true, Ordering::SeqCst)
+        {
+            return false;
+        }
+        REKEYS_IN_PROGRESS.fetch_add(1, Ordering::SeqCst);
+        self.total_files.store(0, Ordering::SeqCst);
+        self.done_files.store(0, Ordering::SeqCst);
+        true
+    }
+
+    pub fn finish(&self) {
+        REKEYS_IN_PROGRESS.fetch_sub(1, Ordering::SeqCst);
+        self.in_progress.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_in_progress(&self) -> bool {
+        self.in_progress.load(Ordering::SeqCst)
+    }
+
+    pub fn set_total(&self, total_files: usize) {
+        self.total_files.store(total_files, Ordering::SeqCst);
+    }
+
+    pub fn inc_done(&self) {
+        self.done_files.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn progress(&self) -> SefsRekeyProgress {
+        SefsRekeyProgress {
+            in_progress: self.is_in_progress() as u32,
+            padding: 0,
+            total_files: self.total_files.load(Ordering::SeqCst) as u64,
+            done_files: self.done_files.load(Ordering::SeqCst) as u64,
+        }
+    }
+}
+
+/// The number of rekeys in progress, which makes checking for ongoing rekeys
+/// cheap in the common case
+static REKEYS_IN_PROGRESS: AtomicUsize = AtomicUsize::new(0);
+
+/// Writes to an SEFS under rekey are rejected with EBUSY.
+pub fn check_not_rekeying(abs_path: &str) -> Result<()> {
+    if REKEYS_IN_PROGRESS.load(Ordering::SeqCst) == 0 {
+        return Ok(());
+    }
+    if find_mount(abs_path).map_or(false, |storage| storage.is_rekeying()) {
+        return_errno!(EBUSY, "the SEFS is being rekeyed");
+    }
+    Ok(())
+}
+
+/// Whether the ioctl is one of SEFS, which is handled by `do_ioctl`
+pub fn is_sefs_ioctl(cmd_num: u32) -> bool {
+    cmd_num == SEFS_IOC_REKEY || cmd_num == SEFS_IOC_GET_REKEY_PROGRESS
+}
+
+pub fn do_ioctl(abs_path: &str, cmd: &mut IoctlCmd) -> Result<()> {
+    let nonbuiltin_cmd = match cmd {
+        IoctlCmd::NonBuiltin(nonbuiltin_cmd) => nonbuiltin_cmd,
+        _ => return_errno!(ENOTTY, "unknown ioctl cmd for SEFS"),
+    };
+    let storage = find_mount(abs_path).ok_or_else(|| errno!(ENOTTY, "the file is not on SEFS"))?;
+    let cmd_num = nonbuiltin_cmd.cmd_num().as_u32();
+    match cmd_num {
+        SEFS_IOC_REKEY => {
+            let new_key = nonbuiltin_cmd.arg::<SefsRekeyArg>()?.new_key;
+            storage.rekey(&new_key)?;
+        }
+        SEFS_IOC_GET_REKEY_PROGRESS => {
+            let arg = nonbuiltin_cmd.arg_mut::<SefsRekeyProgress>()?;
+            *arg = storage.rekey_progress();
+        }
+        _ => return_errno!(ENOTTY, "unknown ioctl cmd for SEFS"),
+    }
+    Ok(())
+}
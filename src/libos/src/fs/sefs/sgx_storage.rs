@@ -1,19 +1,39 @@
 use super::*;
 use rcore_fs_sefs::dev::{DevResult, DeviceError, File, SefsMac, Storage};
+use sgx_trts::libc::c_char;
+use sgx_types::sgx_status_t;
 use std::boxed::Box;
 use std::collections::hash_map::DefaultHasher;
 use std::collections::BTreeMap;
+use std::ffi::CString;
 use std::hash::{Hash, Hasher};
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use std::sgxfs::{remove, OpenOptions, SgxFile};
-use std::sync::{Arc, SgxMutex as Mutex};
+use std::sync::{Arc, SgxMutex as Mutex, SgxRwLock as RwLock};
+use std::untrusted::fs as host_fs;
+use std::untrusted::path::PathEx;
 
+use super::super::hostfs::read_host_dir;
+use super::integrity::{
+    set_created_file_id, should_create_integrity_only, IntegrityTable, INTEGRITY_TABLE_FILE,
+};
+use super::rekey::{RekeyStatus, SefsRekeyProgress, StorageKeys, TMP_FILE_SUFFIX};
+
+/// The storage of SEFS on the host.
+///
+/// The storage can be cloned, and all the clones share the same state. This
+/// allows the storage to be rekeyed while it is owned by a mounted SEFS.
+#[derive(Clone)]
 pub struct SgxStorage {
     path: PathBuf,
     integrity_only: bool,
-    file_cache: Mutex<BTreeMap<u64, LockedFile>>,
+    file_cache: Arc<Mutex<BTreeMap<u64, LockedFile>>>,
     root_mac: Option<sgx_aes_gcm_128bit_tag_t>,
+    keys: Arc<RwLock<StorageKeys>>,
+    rekey_status: Arc<RekeyStatus>,
+    // The integrity-only files of an encrypted storage
+    integrity_table: Option<Arc<IntegrityTable>>,
 }
 
 impl SgxStorage {
@@ -23,11 +43,25 @@ impl SgxStorage {
         file_mac: Option<sgx_aes_gcm_128bit_tag_t>,
     ) -> Self {
         //        assert!(path.as_ref().is_dir());
+        let keys = if !integrity_only {
+            StorageKeys::load(path.as_ref())
+        } else {
+            StorageKeys::default()
+        };
+        let keys = Arc::new(RwLock::new(keys));
+        let integrity_table = if !integrity_only {
+            Some(Arc::new(IntegrityTable::load(path.as_ref(), keys.clone())))
+        } else {
+            None
+        };
         SgxStorage {
             path: path.as_ref().to_path_buf(),
             integrity_only: integrity_only,
-            file_cache: Mutex::new(BTreeMap::new()),
+            file_cache: Arc::new(Mutex::new(BTreeMap::new())),
             root_mac: file_mac,
+            keys,
+            rekey_status: Arc::new(RekeyStatus::default()),
+            integrity_table,
         }
     }
     /// Get file by `file_id`.
@@ -76,10 +110,245 @@ impl SgxStorage {
         self.root_mac = Some(mac);
         Ok(())
     }
+
+    pub fn is_integrity_only(&self) -> bool {
+        self.integrity_only
+    }
+
+    /// Whether the file of the inode is an integrity-only file in an encrypted
+    /// storage
+    pub fn is_integrity_only_ino(&self, ino: u64) -> bool {
+        self.integrity_table
+            .as_ref()
+            .map_or(false, |table| table.contains_ino(ino))
+    }
+
+    pub fn set_integrity_only_ino(&self, file_id: &str, ino: u64) -> Result<()> {
+        match &self.integrity_table {
+            Some(table) => table.set_ino(file_id, ino),
+            None => Ok(()),
+        }
+    }
+
+    fn is_integrity_only_file(&self, file_id: &str) -> bool {
+        self.integrity_table
+            .as_ref()
+            .map_or(false, |table| table.contains(file_id))
+    }
+
+    /// Open an integrity-only file in an encrypted storage, whose MAC must match
+    /// the one recorded in the integrity table.
+    ///
+    /// The file is not cached, so that the tampering on the host can be
+    /// detected when the file is opened again.
+    fn open_integrity_only_file(&self, file_id: &str, create: bool) -> DevResult<LockedFile> {
+        let table = self.integrity_table.as_ref().unwrap();
+        let path = self.path.join(file_id);
+        let mut options = OpenOptions::new();
+        if create {
+            options.write(true).update(true);
+        } else {
+            options.read(true).update(true);
+        }
+        let file = options.open_integrity_only(&path).map_err(|e| {
+            error!("failed to open integrity-only file {:?}: {:?}", path, e);
+            DeviceError
+        })?;
+        if let Some(expected_mac) = table.get_mac(file_id) {
+            let actual_mac = file.get_mac().map_err(|_| DeviceError)?;
+            if actual_mac != expected_mac {
+                error!("MAC validation for integrity-only file {:?} failed", path);
+                return Err(DeviceError);
+            }
+        }
+        Ok(LockedFile {
+            file: Arc::new(Mutex::new(file)),
+            path: Arc::new(path),
+            integrity_only: true,
+            keys: self.keys.clone(),
+            integrity: Some((table.clone(), file_id.to_string())),
+        })
+    }
+
+    pub fn is_rekeying(&self) -> bool {
+        self.rekey_status.is_in_progress()
+    }
+
+    pub fn rekey_progress(&self) -> SefsRekeyProgress {
+        self.rekey_status.progress()
+    }
+
+    /// Re-encrypt all files of the storage with a new key.
+    ///
+    /// No file can be opened or created while the storage is being rekeyed.
+    /// See the `rekey` module for how the rekey is made crash-safe.
+    pub fn rekey(&self, new_key: &sgx_key_128bit_t) -> Result<()> {
+        if self.integrity_only {
+            return_errno!(EINVAL, "integrity-only SEFS is not encrypted");
+        }
+        if !self.rekey_status.start() {
+            return_errno!(EBUSY, "the SEFS is being rekeyed");
+        }
+        let res = self.do_rekey(new_key);
+        self.rekey_status.finish();
+        res
+    }
+
+    fn do_rekey(&self, new_key: &sgx_key_128bit_t) -> Result<()> {
+        let file_ids = self.list_file_ids()?;
+        // The integrity-only files are not encrypted, so there is nothing to do
+        let file_ids: Vec<String> = file_ids
+            .into_iter()
+            .filter(|file_id| !self.is_integrity_only_file(file_id))
+            .collect();
+        self.rekey_status.set_total(file_ids.len());
+
+        // Write the new key as the journal before any file is re-encrypted
+        let old_keys = {
+            let mut keys = self.keys.write().unwrap();
+            if keys.pending().map_or(false, |key| key != *new_key) {
+                return_errno!(
+                    EINVAL,
+                    "the interrupted rekey must be resumed with the same key"
+                );
+            }
+            let old_keys = keys.candidates();
+            keys.begin_rekey(&self.path, new_key)?;
+            old_keys
+        };
+
+        for file_id in file_ids.iter() {
+            self.rekey_file(file_id, new_key, &old_keys)?;
+            self.rekey_status.inc_done();
+        }
+        if let Some(table) = &self.integrity_table {
+            table.rekey()?;
+        }
+
+        // Switch to the new key atomically
+        self.keys.write().unwrap().commit_rekey(&self.path)?;
+        Ok(())
+    }
+
+    /// List the IDs of all files in the storage, and remove the temporary
+    /// files that are left by an interrupted rekey.
+    fn list_file_ids(&self) -> Result<Vec<String>> {
+        let mut file_ids = Vec::new();
+        for file_id in read_host_dir(&self.path)? {
+            if file_id.ends_with(TMP_FILE_SUFFIX) {
+                host_fs::remove_file(self.path.join(&file_id))?;
+                continue;
+            }
+            if StorageKeys::is_key_file(&file_id) || file_id == INTEGRITY_TABLE_FILE {
+                continue;
+            }
+            file_ids.push(file_id);
+        }
+        Ok(file_ids)
+    }
+
+    /// Re-encrypt a file with the new key. The cache is locked only while the
+    /// file is rekeyed, so the other files can be opened, created and removed
+    /// meanwhile.
+    fn rekey_file(
+        &self,
+        file_id: &str,
+        new_key: &sgx_key_128bit_t,
+        old_keys: &[Option<sgx_key_128bit_t>],
+    ) -> Result<()> {
+        let caches = self.file_cache.lock().unwrap();
+        let path = self.path.join(file_id);
+        // The file may have been removed since the files were listed
+        if !path.exists() {
+            return Ok(());
+        }
+        let read_options = {
+            let mut options = OpenOptions::new();
+            options.read(true).update(true);
+            options
+        };
+
+        // The cached file is shared by the SEFS inodes, so it is rekeyed in
+        // place by replacing the underlying SgxFile
+        let key = self.calculate_hash(file_id);
+        if let Some(locked_file) = caches.get(&key) {
+            let mut file = locked_file.file.lock().unwrap();
+            let content = read_sgx_file(&mut file)?;
+            self.write_rekeyed_file(&path, &content, new_key)?;
+            *file = open_sgx_file(&read_options, &path, false, &[Some(*new_key)])?;
+        } else {
+            let content = {
+                let mut file = open_sgx_file(&read_options, &path, false, old_keys)?;
+                read_sgx_file(&mut file)?
+            };
+            self.write_rekeyed_file(&path, &content, new_key)?;
+        }
+        Ok(())
+    }
+
+    /// Write the content to a temporary file with the new key, and then rename
+    /// it over the original file. So a file is always complete under either
+    /// the old or the new key.
+    fn write_rekeyed_file(
+        &self,
+        path: &Path,
+        content: &[u8],
+        new_key: &sgx_key_128bit_t,
+    ) -> Result<()> {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(TMP_FILE_SUFFIX);
+        let tmp_path = PathBuf::from(tmp_path);
+        {
+            let mut options = OpenOptions::new();
+            options.write(true).update(true);
+            let mut tmp_file = options.open_ex(&tmp_path, new_key)?;
+            tmp_file.write_all(content)?;
+            tmp_file.flush()?;
+        }
+        fsync_host_file(&tmp_path).map_err(|_| errno!(EIO, "failed to fsync the host file"))?;
+        host_fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+/// Open an SgxFile with the first key that works. A key of None means the
+/// default key, which is derived from the sealing key of the enclave.
+pub(super) fn open_sgx_file(
+    options: &OpenOptions,
+    path: &Path,
+    integrity_only: bool,
+    keys: &[Option<sgx_key_128bit_t>],
+) -> std::io::Result<SgxFile> {
+    if integrity_only {
+        return options.open_integrity_only(path);
+    }
+    let mut last_err = None;
+    for key in keys {
+        let open_res = match key {
+            None => options.open(path),
+            Some(key) => options.open_ex(path, key),
+        };
+        match open_res {
+            Ok(file) => return Ok(file),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.unwrap())
+}
+
+fn read_sgx_file(file: &mut SgxFile) -> Result<Vec<u8>> {
+    let mut content = Vec::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_end(&mut content)?;
+    Ok(content)
 }
 
 impl Storage for SgxStorage {
     fn open(&self, file_id: &str) -> DevResult<Box<dyn File>> {
+        if self.is_integrity_only_file(file_id) {
+            let _caches = self.file_cache.lock().unwrap();
+            return Ok(Box::new(self.open_integrity_only_file(file_id, false)?));
+        }
         let locked_file = self.get(file_id, |this| {
             let mut path = this.path.to_path_buf();
             path.push(file_id);
@@ -89,11 +358,8 @@ impl Storage for SgxStorage {
                 options
             };
             let file = {
-                let open_res = if !self.integrity_only {
-                    options.open(path)
-                } else {
-                    options.open_integrity_only(path)
-                };
+                let keys = this.keys.read().unwrap().candidates();
+                let open_res = open_sgx_file(&options, &path, this.integrity_only, &keys);
                 if open_res.is_err() {
                     return Err(DeviceError);
                 }
@@ -113,12 +379,25 @@ impl Storage for SgxStorage {
                 }
             }
 
-            Ok(LockedFile(Arc::new(Mutex::new(file))))
+            Ok(LockedFile::new(
+                file,
+                path,
+                this.integrity_only,
+                this.keys.clone(),
+            ))
         })?;
         Ok(Box::new(locked_file))
     }
 
     fn create(&self, file_id: &str) -> DevResult<Box<dyn File>> {
+        if self.integrity_table.is_some() && should_create_integrity_only() {
+            let _caches = self.file_cache.lock().unwrap();
+            let table = self.integrity_table.as_ref().unwrap();
+            table.insert(file_id).map_err(|_| DeviceError)?;
+            let locked_file = self.open_integrity_only_file(file_id, true)?;
+            set_created_file_id(file_id);
+            return Ok(Box::new(locked_file));
+        }
         let locked_file = self.get(file_id, |this| {
             let mut path = this.path.to_path_buf();
             path.push(file_id);
@@ -128,29 +407,35 @@ impl Storage for SgxStorage {
                 options
             };
             let file = {
-                let open_res = if !self.integrity_only {
-                    options.open(path)
-                } else {
-                    options.open_integrity_only(path)
-                };
+                let key = this.keys.read().unwrap().newest();
+                let open_res = open_sgx_file(&options, &path, this.integrity_only, &[key]);
                 if open_res.is_err() {
                     return Err(DeviceError);
                 }
                 open_res.unwrap()
             };
-            Ok(LockedFile(Arc::new(Mutex::new(file))))
+            Ok(LockedFile::new(
+                file,
+                path,
+                this.integrity_only,
+                this.keys.clone(),
+            ))
         })?;
         Ok(Box::new(locked_file))
     }
 
     fn remove(&self, file_id: &str) -> DevResult<()> {
+        // Lock the cache first so that no file is removed during a rekey
+        let mut caches = self.file_cache.lock().unwrap();
         let mut path = self.path.to_path_buf();
         path.push(file_id);
         remove(path).expect("failed to remove SgxFile");
         // remove from cache
         let key = self.calculate_hash(file_id);
-        let mut caches = self.file_cache.lock().unwrap();
         caches.remove(&key);
+        if let Some(table) = &self.integrity_table {
+            table.remove(file_id).map_err(|_| DeviceError)?;
+        }
         Ok(())
     }
 
@@ -160,7 +445,98 @@ impl Storage for SgxStorage {
 }
 
 #[derive(Clone)]
-pub struct LockedFile(Arc<Mutex<SgxFile>>);
+pub struct LockedFile {
+    file: Arc<Mutex<SgxFile>>,
+    // The path of the underlying host file
+    path: Arc<PathBuf>,
+    // Whether the SgxFile is integrity-only, and the keys to recreate it with
+    integrity_only: bool,
+    keys: Arc<RwLock<StorageKeys>>,
+    // The integrity table and the file ID, if this is an integrity-only file in
+    // an encrypted storage
+    integrity: Option<(Arc<IntegrityTable>, String)>,
+}
+
+impl LockedFile {
+    fn new(
+        file: SgxFile,
+        path: PathBuf,
+        integrity_only: bool,
+        keys: Arc<RwLock<StorageKeys>>,
+    ) -> LockedFile {
+        LockedFile {
+            file: Arc::new(Mutex::new(file)),
+            path: Arc::new(path),
+            integrity_only,
+            keys,
+            integrity: None,
+        }
+    }
+
+    /// Append null bytes to the end of the file
+    fn write_zeros(&self, file: &mut SgxFile, len: usize) -> DevResult<()> {
+        static ZEROS: [u8; 0x1000] = [0; 0x1000];
+        file.seek(SeekFrom::End(0)).expect("failed to seek SgxFile");
+        let mut rest_len = len;
+        while rest_len != 0 {
+            let l = rest_len.min(0x1000);
+            let len = file.write(&ZEROS[..l]).map_err(|e| {
+                error!("failed to write SgxFile {:?}: {:?}", self.path, e);
+                DeviceError
+            })?;
+            rest_len -= len;
+        }
+        Ok(())
+    }
+
+    /// Shrink the file to the given length.
+    ///
+    /// SgxFile can not be shrunk, and the length of a file must survive a
+    /// remount, which only knows the length of the SgxFile. So the data before
+    /// the length is copied to a new SgxFile, which is renamed over the
+    /// original one. This costs no copy for the common truncation to zero.
+    fn shrink(&self, file: &mut SgxFile, len: usize) -> DevResult<()> {
+        let options = {
+            let mut options = OpenOptions::new();
+            options.write(true).update(true);
+            options
+        };
+        let key = self.keys.read().unwrap().newest();
+        let mut tmp_path = self.path.as_os_str().to_owned();
+        tmp_path.push(TMP_FILE_SUFFIX);
+        let tmp_path = PathBuf::from(tmp_path);
+        let copy_res = (|| -> std::io::Result<()> {
+            let mut tmp_file = open_sgx_file(&options, &tmp_path, self.integrity_only, &[key])?;
+            let mut buf = [0; 0x1000];
+            let mut rest_len = len;
+            file.seek(SeekFrom::Start(0))?;
+            while rest_len != 0 {
+                let l = rest_len.min(buf.len());
+                file.read_exact(&mut buf[..l])?;
+                tmp_file.write_all(&buf[..l])?;
+                rest_len -= l;
+            }
+            tmp_file.flush()
+        })();
+        if let Err(e) = copy_res {
+            error!("failed to shrink SgxFile {:?}: {:?}", self.path, e);
+            return Err(DeviceError);
+        }
+        fsync_host_file(&tmp_path)?;
+        host_fs::rename(&tmp_path, self.path.as_ref()).map_err(|_| DeviceError)?;
+
+        let options = {
+            let mut options = OpenOptions::new();
+            options.read(true).update(true);
+            options
+        };
+        *file = open_sgx_file(&options, &self.path, self.integrity_only, &[key]).map_err(|e| {
+            error!("failed to reopen SgxFile {:?}: {:?}", self.path, e);
+            DeviceError
+        })?;
+        Ok(())
+    }
+}
 
 // `sgx_tstd::sgxfs::SgxFile` not impl Send ...
 unsafe impl Send for LockedFile {}
@@ -171,19 +547,23 @@ impl File for LockedFile {
         if buf.len() == 0 {
             return Ok(0);
         }
-        let mut file = self.0.lock().unwrap();
+        let mut file = self.file.lock().unwrap();
 
-        // SgxFile does not support to seek a position beyond the end.
-        // So check if file_size < offset and return zero(indicates end of file).
+        // The SgxFile is always as long as the file, so there is no data
+        // beyond its end
         let file_size = file.seek(SeekFrom::End(0)).expect("failed to tell SgxFile") as usize;
-        if file_size < offset {
+        if file_size <= offset {
             return Ok(0);
         }
 
-        let offset = offset as u64;
-        file.seek(SeekFrom::Start(offset))
+        let read_len = buf.len().min(file_size - offset);
+        file.seek(SeekFrom::Start(offset as u64))
             .expect("failed to seek SgxFile");
-        let len = file.read(buf).expect("failed to read SgxFile");
+        // The read fails if the data does not pass the integrity check
+        let len = file.read(&mut buf[..read_len]).map_err(|e| {
+            error!("failed to read SgxFile {:?}: {:?}", self.path, e);
+            DeviceError
+        })?;
         Ok(len)
     }
 
@@ -191,19 +571,13 @@ impl File for LockedFile {
         if buf.len() == 0 {
             return Ok(0);
         }
-        let mut file = self.0.lock().unwrap();
+        let mut file = self.file.lock().unwrap();
 
         // SgxFile does not support to seek a position beyond the end.
         // So check if file_size < offset and padding null bytes.
         let file_size = file.seek(SeekFrom::End(0)).expect("failed to tell SgxFile") as usize;
         if file_size < offset {
-            static ZEROS: [u8; 0x1000] = [0; 0x1000];
-            let mut rest_len = offset - file_size;
-            while rest_len != 0 {
-                let l = rest_len.min(0x1000);
-                let len = file.write(&ZEROS[..l]).expect("failed to write SgxFile");
-                rest_len -= len;
-            }
+            self.write_zeros(&mut file, offset - file_size)?;
         }
 
         let offset = offset as u64;
@@ -214,18 +588,64 @@ impl File for LockedFile {
     }
 
     fn set_len(&self, len: usize) -> DevResult<()> {
-        // NOTE: do nothing ??
+        let mut file = self.file.lock().unwrap();
+        let file_size = file.seek(SeekFrom::End(0)).expect("failed to tell SgxFile") as usize;
+        if file_size < len {
+            // SgxFile can not be extended without writing
+            self.write_zeros(&mut file, len - file_size)?;
+        } else if len < file_size {
+            self.shrink(&mut file, len)?;
+        }
         Ok(())
     }
 
+    /// Flush the file and make it durable on the host.
+    ///
+    /// SEFS flushes the data files before the metadata file that holds their
+    /// MACs. The flush of SgxFile only writes its cached nodes to the host's
+    /// page cache, which the host may write back in any order. So the host file
+    /// is fsync-ed here to serve as a write barrier: once the flush returns,
+    /// the data is durable before any metadata that references it is written.
+    /// Otherwise, a crash may leave a metadata file with MACs that do not match
+    /// the data files, which fails the integrity check on reopen.
     fn flush(&self) -> DevResult<()> {
-        let mut file = self.0.lock().unwrap();
-        file.flush().expect("failed to flush SgxFile");
+        let mut file = self.file.lock().unwrap();
+        if let Err(e) = file.flush() {
+            error!("failed to flush SgxFile {:?}: {:?}", self.path, e);
+            return Err(DeviceError);
+        }
+        if fsync_host_file(&self.path).is_err() {
+            error!("failed to fsync the host file {:?}", self.path);
+            return Err(DeviceError);
+        }
+        // Record the new MAC after the file is durable
+        if let Some((table, file_id)) = &self.integrity {
+            let mac = file.get_mac().map_err(|_| DeviceError)?;
+            table.update_mac(file_id, &mac).map_err(|_| DeviceError)?;
+        }
         Ok(())
     }
 
     fn get_file_mac(&self) -> DevResult<SefsMac> {
-        let file = self.0.lock().unwrap();
+        let file = self.file.lock().unwrap();
         Ok(SefsMac(file.get_mac().unwrap()))
     }
 }
+
+pub(super) fn fsync_host_file(path: &Path) -> DevResult<()> {
+    let path = path
+        .to_str()
+        .and_then(|path| CString::new(path).ok())
+        .ok_or(DeviceError)?;
+    let mut retval = 0;
+    let sgx_status = unsafe { occlum_ocall_fsync_host_file(&mut retval, path.as_ptr()) };
+    assert!(sgx_status == sgx_status_t::SGX_SUCCESS);
+    if retval < 0 {
+        return Err(DeviceError);
+    }
+    Ok(())
+}
+
+extern "C" {
+    fn occlum_ocall_fsync_host_file(ret: *mut i32, path: *const c_char) -> sgx_status_t;
+}
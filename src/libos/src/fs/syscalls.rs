@@ -1,7 +1,13 @@
 use super::file_ops;
-use super::file_ops::{AccessibilityCheckFlags, AccessibilityCheckMode, FcntlCmd, AT_FDCWD};
+use super::file_ops::{
+    AccessibilityCheckFlags, AccessibilityCheckMode, FallocateFlags, FcntlCmd, FileHandle,
+    NameToHandleFlags, RwfFlags, StatxFlags, AT_FDCWD, FILEID_INO64_GEN, MAX_HANDLE_SZ,
+};
 use super::fs_ops;
+use super::fs_ops::MountFlags;
+use super::io_uring;
 use super::*;
+use process::{do_rt_sigprocmask, id_or_unchanged, SigMaskHow, SigSet};
 use util::mem_util::from_user;
 
 #[allow(non_camel_case_types)]
@@ -18,6 +24,89 @@ pub fn do_open(path: *const i8, flags: u32, mode: u32) -> Result<isize> {
     Ok(fd as isize)
 }
 
+/// The header of `struct file_handle`, which is followed by the opaque handle
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub struct file_handle_t {
+    handle_bytes: u32,
+    handle_type: i32,
+}
+
+pub fn do_name_to_handle_at(
+    dirfd: i32,
+    path: *const i8,
+    handle_u: *mut file_handle_t,
+    mount_id_u: *mut i32,
+    flags: u32,
+) -> Result<isize> {
+    let dirfd = if dirfd >= 0 {
+        Some(dirfd as FileDesc)
+    } else if dirfd == AT_FDCWD {
+        None
+    } else {
+        return_errno!(EINVAL, "invalid dirfd");
+    };
+    let path = from_user::clone_cstring_safely(path)?
+        .to_string_lossy()
+        .into_owned();
+    let flags = NameToHandleFlags::from_u32(flags)?;
+    from_user::check_mut_ptr(handle_u)?;
+    from_user::check_mut_ptr(mount_id_u)?;
+    let handle_bytes = unsafe { (*handle_u).handle_bytes } as usize;
+    if handle_bytes > MAX_HANDLE_SZ {
+        return_errno!(EINVAL, "handle_bytes is too large");
+    }
+
+    let (handle, mount_id) = file_ops::do_name_to_handle_at(dirfd, &path, flags)?;
+    // The required size is reported even if the buffer is too small
+    let handle_size = std::mem::size_of::<FileHandle>();
+    unsafe {
+        (*handle_u).handle_bytes = handle_size as u32;
+    }
+    if handle_bytes < handle_size {
+        return_errno!(EOVERFLOW, "the buffer for the handle is too small");
+    }
+    let f_handle = unsafe { (handle_u as *mut u8).add(std::mem::size_of::<file_handle_t>()) };
+    from_user::check_mut_array(f_handle, handle_size)?;
+    unsafe {
+        (*handle_u).handle_type = FILEID_INO64_GEN;
+        (f_handle as *mut FileHandle).write_unaligned(handle);
+        mount_id_u.write(mount_id);
+    }
+    Ok(0)
+}
+
+pub fn do_open_by_handle_at(
+    mount_fd: i32,
+    handle_u: *const file_handle_t,
+    flags: u32,
+) -> Result<isize> {
+    let mount_fd = if mount_fd >= 0 {
+        Some(mount_fd as FileDesc)
+    } else if mount_fd == AT_FDCWD {
+        None
+    } else {
+        return_errno!(EBADF, "invalid mount_fd");
+    };
+    from_user::check_ptr(handle_u)?;
+    let (handle_bytes, handle_type) =
+        unsafe { ((*handle_u).handle_bytes as usize, (*handle_u).handle_type) };
+    if handle_bytes == 0 || handle_bytes > MAX_HANDLE_SZ {
+        return_errno!(EINVAL, "invalid handle_bytes");
+    }
+    // The handle is not given out by Occlum
+    let handle_size = std::mem::size_of::<FileHandle>();
+    if handle_type != FILEID_INO64_GEN || handle_bytes != handle_size {
+        return_errno!(ESTALE, "unknown file handle");
+    }
+    let f_handle = unsafe { (handle_u as *const u8).add(std::mem::size_of::<file_handle_t>()) };
+    from_user::check_array(f_handle, handle_size)?;
+    let handle = unsafe { (f_handle as *const FileHandle).read_unaligned() };
+
+    let fd = file_ops::do_open_by_handle_at(mount_fd, &handle, flags)?;
+    Ok(fd as isize)
+}
+
 pub fn do_close(fd: FileDesc) -> Result<isize> {
     file_ops::do_close(fd)?;
     Ok(0)
@@ -91,6 +180,103 @@ pub fn do_readv(fd: FileDesc, iov: *mut iovec_t, count: i32) -> Result<isize> {
     Ok(len as isize)
 }
 
+pub fn do_preadv2(
+    fd: FileDesc,
+    iov: *mut iovec_t,
+    count: i32,
+    offset: off_t,
+    flags: u32,
+) -> Result<isize> {
+    let count = {
+        if count < 0 {
+            return_errno!(EINVAL, "Invalid count of iovec");
+        }
+        count as usize
+    };
+    // The offset of -1 means the file offset
+    let offset = match offset {
+        -1 => None,
+        offset if offset < 0 => return_errno!(EINVAL, "Invalid offset"),
+        offset => Some(offset as usize),
+    };
+    let flags = RwfFlags::from_u32(flags)?;
+
+    from_user::check_array(iov, count)?;
+    let mut bufs_vec = {
+        let mut bufs_vec = Vec::with_capacity(count);
+        for iov_i in 0..count {
+            let iov_ptr = unsafe { iov.offset(iov_i as isize) };
+            let iov = unsafe { &*iov_ptr };
+            from_user::check_mut_array(iov.base as *mut u8, iov.len)?;
+            let buf = unsafe { std::slice::from_raw_parts_mut(iov.base as *mut u8, iov.len) };
+            bufs_vec.push(buf);
+        }
+        bufs_vec
+    };
+    let bufs = &mut bufs_vec[..];
+
+    let len = file_ops::do_preadv2(fd, bufs, offset, flags)?;
+    Ok(len as isize)
+}
+
+pub fn do_preadv(fd: FileDesc, iov: *mut iovec_t, count: i32, offset: off_t) -> Result<isize> {
+    let count = {
+        if count < 0 {
+            return_errno!(EINVAL, "Invalid count of iovec");
+        }
+        count as usize
+    };
+    if offset < 0 {
+        return_errno!(EINVAL, "Invalid offset");
+    }
+
+    from_user::check_array(iov, count)?;
+    let mut bufs_vec = {
+        let mut bufs_vec = Vec::with_capacity(count);
+        for iov_i in 0..count {
+            let iov_ptr = unsafe { iov.offset(iov_i as isize) };
+            let iov = unsafe { &*iov_ptr };
+            from_user::check_mut_array(iov.base as *mut u8, iov.len)?;
+            let buf = unsafe { std::slice::from_raw_parts_mut(iov.base as *mut u8, iov.len) };
+            bufs_vec.push(buf);
+        }
+        bufs_vec
+    };
+    let bufs = &mut bufs_vec[..];
+
+    let len = file_ops::do_preadv(fd, bufs, offset as usize)?;
+    Ok(len as isize)
+}
+
+pub fn do_pwritev(fd: FileDesc, iov: *const iovec_t, count: i32, offset: off_t) -> Result<isize> {
+    let count = {
+        if count < 0 {
+            return_errno!(EINVAL, "Invalid count of iovec");
+        }
+        count as usize
+    };
+    if offset < 0 {
+        return_errno!(EINVAL, "Invalid offset");
+    }
+
+    from_user::check_array(iov, count)?;
+    let bufs_vec = {
+        let mut bufs_vec = Vec::with_capacity(count);
+        for iov_i in 0..count {
+            let iov_ptr = unsafe { iov.offset(iov_i as isize) };
+            let iov = unsafe { &*iov_ptr };
+            from_user::check_array(iov.base as *const u8, iov.len)?;
+            let buf = unsafe { std::slice::from_raw_parts(iov.base as *const u8, iov.len) };
+            bufs_vec.push(buf);
+        }
+        bufs_vec
+    };
+    let bufs = &bufs_vec[..];
+
+    let len = file_ops::do_pwritev(fd, bufs, offset as usize)?;
+    Ok(len as isize)
+}
+
 pub fn do_pread(fd: FileDesc, buf: *mut u8, size: usize, offset: usize) -> Result<isize> {
     let safe_buf = {
         from_user::check_mut_array(buf, size)?;
@@ -132,6 +318,34 @@ pub fn do_fstat(fd: FileDesc, stat_buf: *mut Stat) -> Result<isize> {
     Ok(0)
 }
 
+pub fn do_statx(
+    dirfd: i32,
+    path: *const i8,
+    flags: u32,
+    mask: u32,
+    statx_buf: *mut Statx,
+) -> Result<isize> {
+    let dirfd = if dirfd >= 0 {
+        Some(dirfd as FileDesc)
+    } else if dirfd == AT_FDCWD {
+        None
+    } else {
+        return_errno!(EINVAL, "invalid dirfd");
+    };
+    let path = from_user::clone_cstring_safely(path)?
+        .to_string_lossy()
+        .into_owned();
+    let flags = StatxFlags::from_u32(flags)?;
+    from_user::check_mut_ptr(statx_buf)?;
+
+    // The mask is only a hint, and all the basic stats are returned anyway
+    let statx = file_ops::do_statx(dirfd, &path, flags)?;
+    unsafe {
+        statx_buf.write(statx);
+    }
+    Ok(0)
+}
+
 pub fn do_lstat(path: *const i8, stat_buf: *mut Stat) -> Result<isize> {
     let path = from_user::clone_cstring_safely(path)?
         .to_string_lossy()
@@ -218,6 +432,15 @@ pub fn do_ftruncate(fd: FileDesc, len: usize) -> Result<isize> {
     Ok(0)
 }
 
+pub fn do_fallocate(fd: FileDesc, mode: u32, offset: off_t, len: off_t) -> Result<isize> {
+    if offset < 0 || len <= 0 {
+        return_errno!(EINVAL, "offset or len is invalid");
+    }
+    let flags = FallocateFlags::from_u32(mode)?;
+    file_ops::do_fallocate(fd, flags, offset as usize, len as usize)?;
+    Ok(0)
+}
+
 pub fn do_getdents64(fd: FileDesc, buf: *mut u8, buf_size: usize) -> Result<isize> {
     let safe_buf = {
         from_user::check_mut_array(buf, buf_size)?;
@@ -232,6 +455,71 @@ pub fn do_sync() -> Result<isize> {
     Ok(0)
 }
 
+pub fn do_statfs(path: *const i8, statfs_buf: *mut Statfs) -> Result<isize> {
+    let path = from_user::clone_cstring_safely(path)?
+        .to_string_lossy()
+        .into_owned();
+    from_user::check_mut_ptr(statfs_buf)?;
+
+    let statfs = fs_ops::do_statfs(&path)?;
+    unsafe {
+        statfs_buf.write(statfs);
+    }
+    Ok(0)
+}
+
+pub fn do_fstatfs(fd: FileDesc, statfs_buf: *mut Statfs) -> Result<isize> {
+    from_user::check_mut_ptr(statfs_buf)?;
+
+    let statfs = fs_ops::do_fstatfs(fd)?;
+    unsafe {
+        statfs_buf.write(statfs);
+    }
+    Ok(0)
+}
+
+pub fn do_mount(
+    source: *const i8,
+    target: *const i8,
+    fs_type: *const i8,
+    flags: u32,
+    data: *const c_void,
+) -> Result<isize> {
+    // The source and the type are ignored when the propagation is changed, so
+    // they can be NULL
+    let clone_cstring_or_empty = |s: *const i8| -> Result<String> {
+        if s.is_null() {
+            return Ok(String::new());
+        }
+        Ok(from_user::clone_cstring_safely(s)?
+            .to_string_lossy()
+            .into_owned())
+    };
+    let source = clone_cstring_or_empty(source)?;
+    let target = from_user::clone_cstring_safely(target)?
+        .to_string_lossy()
+        .into_owned();
+    let fs_type = clone_cstring_or_empty(fs_type)?;
+    let flags = MountFlags::from_u32(flags)?;
+    // TODO: support the options of the file systems in data
+    fs_ops::do_mount(&source, &target, &fs_type, flags)?;
+    Ok(0)
+}
+
+pub fn do_unshare(flags: u32) -> Result<isize> {
+    let flags =
+        process::CloneFlags::from_bits(flags).ok_or_else(|| errno!(EINVAL, "invalid flags"))?;
+    fs_ops::do_unshare(flags)?;
+    Ok(0)
+}
+
+pub fn do_setns(fd: FileDesc, nstype: u32) -> Result<isize> {
+    let nstype = process::CloneFlags::from_bits(nstype)
+        .ok_or_else(|| errno!(EINVAL, "invalid namespace type"))?;
+    fs_ops::do_setns(fd, nstype)?;
+    Ok(0)
+}
+
 pub fn do_pipe2(fds_u: *mut i32, flags: u32) -> Result<isize> {
     from_user::check_mut_array(fds_u, 2)?;
     // TODO: how to deal with open flags???
@@ -266,6 +554,25 @@ pub fn do_chdir(path: *const i8) -> Result<isize> {
     Ok(0)
 }
 
+pub fn do_fchdir(fd: FileDesc) -> Result<isize> {
+    file_ops::do_fchdir(fd)?;
+    Ok(0)
+}
+
+pub fn do_getcwd(buf: *mut u8, size: usize) -> Result<isize> {
+    let safe_buf = {
+        from_user::check_mut_array(buf, size)?;
+        unsafe { std::slice::from_raw_parts_mut(buf, size) }
+    };
+    let cwd = file_ops::do_getcwd()?;
+    if cwd.len() + 1 > safe_buf.len() {
+        return_errno!(ERANGE, "buf is not long enough");
+    }
+    safe_buf[..cwd.len()].copy_from_slice(cwd.as_bytes());
+    safe_buf[cwd.len()] = 0;
+    Ok(buf as isize)
+}
+
 pub fn do_rename(oldpath: *const i8, newpath: *const i8) -> Result<isize> {
     let oldpath = from_user::clone_cstring_safely(oldpath)?
         .to_string_lossy()
@@ -285,6 +592,48 @@ pub fn do_mkdir(path: *const i8, mode: usize) -> Result<isize> {
     Ok(0)
 }
 
+pub fn do_mknod(path: *const i8, mode: u32, dev: usize) -> Result<isize> {
+    let path = from_user::clone_cstring_safely(path)?
+        .to_string_lossy()
+        .into_owned();
+    file_ops::do_mknod(&path, mode, dev)?;
+    Ok(0)
+}
+
+pub fn do_chmod(path: *const i8, mode: u16) -> Result<isize> {
+    let path = from_user::clone_cstring_safely(path)?
+        .to_string_lossy()
+        .into_owned();
+    file_ops::do_chmod(&path, mode)?;
+    Ok(0)
+}
+
+pub fn do_fchmod(fd: FileDesc, mode: u16) -> Result<isize> {
+    file_ops::do_fchmod(fd, mode)?;
+    Ok(0)
+}
+
+pub fn do_chown(path: *const i8, uid: u32, gid: u32) -> Result<isize> {
+    let path = from_user::clone_cstring_safely(path)?
+        .to_string_lossy()
+        .into_owned();
+    file_ops::do_chown(&path, id_or_unchanged(uid), id_or_unchanged(gid))?;
+    Ok(0)
+}
+
+pub fn do_fchown(fd: FileDesc, uid: u32, gid: u32) -> Result<isize> {
+    file_ops::do_fchown(fd, id_or_unchanged(uid), id_or_unchanged(gid))?;
+    Ok(0)
+}
+
+pub fn do_lchown(path: *const i8, uid: u32, gid: u32) -> Result<isize> {
+    let path = from_user::clone_cstring_safely(path)?
+        .to_string_lossy()
+        .into_owned();
+    file_ops::do_lchown(&path, id_or_unchanged(uid), id_or_unchanged(gid))?;
+    Ok(0)
+}
+
 pub fn do_rmdir(path: *const i8) -> Result<isize> {
     let path = from_user::clone_cstring_safely(path)?
         .to_string_lossy()
@@ -346,11 +695,52 @@ pub fn do_sendfile(
     Ok(len as isize)
 }
 
+pub fn do_io_uring_setup(entries: u32, params: *mut io_uring_params_t) -> Result<isize> {
+    let params = {
+        from_user::check_mut_ptr(params)?;
+        unsafe { &mut *params }
+    };
+    let fd = io_uring::do_io_uring_setup(entries, params)?;
+    Ok(fd as isize)
+}
+
+pub fn do_io_uring_enter(
+    fd: FileDesc,
+    to_submit: u32,
+    min_complete: u32,
+    flags: u32,
+    sigmask: *const u64,
+    sigsetsize: usize,
+) -> Result<isize> {
+    // Like pselect, the signal mask is replaced while waiting
+    let old_sigmask = if !sigmask.is_null() {
+        if sigsetsize != std::mem::size_of::<u64>() {
+            return_errno!(EINVAL, "sigsetsize is invalid");
+        }
+        from_user::check_ptr(sigmask)?;
+        let sigmask = SigSet::from_u64(unsafe { *sigmask });
+        Some(do_rt_sigprocmask(SigMaskHow::SIG_SETMASK, Some(sigmask))?)
+    } else {
+        None
+    };
+    let ret = io_uring::do_io_uring_enter(fd, to_submit, min_complete, flags);
+    if let Some(old_sigmask) = old_sigmask {
+        do_rt_sigprocmask(SigMaskHow::SIG_SETMASK, Some(old_sigmask))?;
+    }
+    let num_submitted = ret?;
+    Ok(num_submitted as isize)
+}
+
 pub fn do_fcntl(fd: FileDesc, cmd: u32, arg: u64) -> Result<isize> {
     let mut cmd = FcntlCmd::from_raw(cmd, arg)?;
     file_ops::do_fcntl(fd, &mut cmd)
 }
 
+pub fn do_flock(fd: FileDesc, operation: u32) -> Result<isize> {
+    file_ops::do_flock(fd, operation)?;
+    Ok(0)
+}
+
 pub fn do_ioctl(fd: FileDesc, cmd: u32, argp: *mut u8) -> Result<isize> {
     info!("ioctl: fd: {}, cmd: {}, argp: {:?}", fd, cmd, argp);
     let mut ioctl_cmd = unsafe {
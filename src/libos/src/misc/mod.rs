@@ -1,7 +1,9 @@
 use super::*;
 
+mod random;
 mod rlimit;
 mod uname;
 
+pub use self::random::{do_getrandom, get_random, EntropySource, GetRandomFlags};
 pub use self::rlimit::{do_prlimit, resource_t, rlimit_t, ResourceLimits, ResourceLimitsRef};
 pub use self::uname::{do_uname, utsname_t};
@@ -0,0 +1,118 @@
+use super::*;
+use std::arch::x86_64::_rdseed64_step;
+
+/// The source of the entropy of getrandom, /dev/random and /dev/urandom
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EntropySource {
+    /// Read the random bytes from RDRAND, which is fast
+    Rdrand,
+    /// Generate the random bytes by a CSPRNG that is seeded from RDSEED for
+    /// each request, which is slower but gets the entropy from the hardware
+    /// source directly
+    Rdseed,
+}
+
+impl EntropySource {
+    /// Parse the source from its name in the config
+    pub fn from_str(name: &str) -> Result<EntropySource> {
+        match name {
+            "rdrand" => Ok(EntropySource::Rdrand),
+            "rdseed" => Ok(EntropySource::Rdseed),
+            _ => return_errno!(EINVAL, "unknown entropy source"),
+        }
+    }
+}
+
+impl Default for EntropySource {
+    fn default() -> EntropySource {
+        EntropySource::Rdrand
+    }
+}
+
+bitflags! {
+    pub struct GetRandomFlags: u32 {
+        const GRND_NONBLOCK = 0x1;
+        const GRND_RANDOM = 0x2;
+    }
+}
+
+impl GetRandomFlags {
+    pub fn from_u32(bits: u32) -> Result<GetRandomFlags> {
+        GetRandomFlags::from_bits(bits).ok_or_else(|| errno!(EINVAL, "unknown getrandom flags"))
+    }
+}
+
+/// Fill the whole buffer with random bytes. Unlike getrandom on Linux, it
+/// never blocks or returns a short count, as the entropy source is always
+/// ready, so the flags make no difference. And getentropy of libc, which is
+/// implemented by getrandom, gets all the bytes or fails.
+pub fn do_getrandom(buf: &mut [u8], flags: GetRandomFlags) -> Result<usize> {
+    debug!("getrandom: len: {}, flags: {:?}", buf.len(), flags);
+    get_random(buf)?;
+    Ok(buf.len())
+}
+
+/// Fill the whole buffer with random bytes from the entropy source in the
+/// config, or fail with EIO if the source fails
+pub fn get_random(buf: &mut [u8]) -> Result<()> {
+    if buf.is_empty() {
+        return Ok(());
+    }
+    match config::LIBOS_CONFIG.random.entropy_source {
+        EntropySource::Rdrand => get_random_from_rdrand(buf),
+        EntropySource::Rdseed => get_random_from_rdseed(buf),
+    }
+}
+
+extern "C" {
+    fn sgx_read_rand(rand_buf: *mut u8, buf_size: usize) -> sgx_status_t;
+}
+
+fn get_random_from_rdrand(buf: &mut [u8]) -> Result<()> {
+    // sgx_read_rand is backed by RDRAND
+    let status = unsafe { sgx_read_rand(buf.as_mut_ptr(), buf.len()) };
+    if status != sgx_status_t::SGX_SUCCESS {
+        return_errno!(EIO, "failed to get random bytes from RDRAND");
+    }
+    Ok(())
+}
+
+fn get_random_from_rdseed(buf: &mut [u8]) -> Result<()> {
+    // The random bytes are the AES-CTR keystream, i.e., the encryption of
+    // zeros, whose key and initial counter are from RDSEED
+    let mut seed = [0u8; 32];
+    for chunk in seed.chunks_mut(8) {
+        chunk.copy_from_slice(&rdseed64()?.to_ne_bytes());
+    }
+    let mut key: sgx_aes_ctr_128bit_key_t = Default::default();
+    let mut ctr: sgx_aes_ctr_128bit_ctr_t = Default::default();
+    key.copy_from_slice(&seed[..16]);
+    ctr.copy_from_slice(&seed[16..]);
+
+    let zeros = vec![0u8; buf.len()];
+    let status = sgx_tcrypto::rsgx_aes_ctr_encrypt(&key, &zeros, &ctr, 128, buf);
+    if status.is_err() {
+        return_errno!(EIO, "failed to generate random bytes from the RDSEED seed");
+    }
+    Ok(())
+}
+
+/// RDSEED may fail transiently when the entropy is exhausted, so it is retried
+/// before giving up
+const MAX_RDSEED_RETRIES: usize = 1024;
+
+fn rdseed64() -> Result<u64> {
+    #[target_feature(enable = "rdseed")]
+    unsafe fn rdseed64_step(val: &mut u64) -> bool {
+        _rdseed64_step(val) == 1
+    }
+
+    let mut val = 0;
+    for _ in 0..MAX_RDSEED_RETRIES {
+        if unsafe { rdseed64_step(&mut val) } {
+            return Ok(val);
+        }
+        std::sync::atomic::spin_loop_hint();
+    }
+    return_errno!(EIO, "failed to get a seed from RDSEED");
+}
@@ -1,4 +1,5 @@
 use super::*;
+use fs::MAX_NUM_FDS;
 use process::pid_t;
 
 #[derive(Debug, Copy, Clone)]
@@ -15,6 +16,44 @@ impl ResourceLimits {
     pub fn get_mut(&mut self, resource: resource_t) -> &mut rlimit_t {
         &mut self.rlimits[resource as usize]
     }
+
+    /// Get the maximum size of the files that can be created, i.e., the soft
+    /// limit of RLIMIT_FSIZE
+    pub fn get_file_size_limit(&self) -> usize {
+        let limit = self.get(resource_t::RLIMIT_FSIZE).get_cur();
+        limit.min(usize::max_value() as u64) as usize
+    }
+
+    /// Get the maximum size of the memory that can be locked, i.e., the soft
+    /// limit of RLIMIT_MEMLOCK
+    pub fn get_memlock_limit(&self) -> usize {
+        let limit = self.get(resource_t::RLIMIT_MEMLOCK).get_cur();
+        limit.min(usize::max_value() as u64) as usize
+    }
+
+    /// Get the maximum number of the threads that can be live, i.e., the soft
+    /// limit of RLIMIT_NPROC
+    pub fn get_nproc_limit(&self) -> usize {
+        let limit = self.get(resource_t::RLIMIT_NPROC).get_cur();
+        limit.min(usize::max_value() as u64) as usize
+    }
+
+    /// Get the maximum number of the file descriptors, i.e., the soft limit
+    /// of RLIMIT_NOFILE
+    pub fn get_nofile_limit(&self) -> usize {
+        let limit = self.get(resource_t::RLIMIT_NOFILE).get_cur();
+        limit.min(usize::max_value() as u64) as usize
+    }
+
+    /// Check whether a file is allowed to have the size by RLIMIT_FSIZE. If
+    /// not, SIGXFSZ is raised on the current thread.
+    pub fn check_file_size(&self, size: usize) -> Result<()> {
+        if size > self.get_file_size_limit() {
+            process::raise_signal(process::SIGXFSZ);
+            return_errno!(EFBIG, "the file size exceeds RLIMIT_FSIZE");
+        }
+        Ok(())
+    }
 }
 
 impl Default for ResourceLimits {
@@ -23,10 +62,24 @@ impl Default for ResourceLimits {
         let mut rlimits = ResourceLimits {
             rlimits: [Default::default(); RLIMIT_COUNT],
         };
+        // The same as the default of Linux
+        *rlimits.get_mut(resource_t::RLIMIT_NOFILE) = rlimit_t {
+            cur: DEFAULT_NOFILE_LIMIT,
+            max: MAX_NUM_FDS as u64,
+        };
+        // No more threads can run than the TCSs of the enclave
+        let max_num_of_threads = config::LIBOS_CONFIG.process.max_num_of_threads as u64;
+        *rlimits.get_mut(resource_t::RLIMIT_NPROC) = rlimit_t {
+            cur: max_num_of_threads,
+            max: max_num_of_threads,
+        };
         rlimits
     }
 }
 
+/// The default soft limit of RLIMIT_NOFILE, while the hard one is MAX_NUM_FDS
+const DEFAULT_NOFILE_LIMIT: u64 = 1024;
+
 #[derive(Debug, Copy, Clone)]
 #[allow(non_camel_case_types)]
 pub struct rlimit_t {
@@ -34,6 +87,16 @@ pub struct rlimit_t {
     max: u64,
 }
 
+impl rlimit_t {
+    pub fn get_cur(&self) -> u64 {
+        self.cur
+    }
+
+    pub fn get_max(&self) -> u64 {
+        self.max
+    }
+}
+
 impl Default for rlimit_t {
     fn default() -> rlimit_t {
         rlimit_t {
@@ -93,6 +156,16 @@ pub fn do_prlimit(
     new_limit: Option<&rlimit_t>,
     old_limit: Option<&mut rlimit_t>,
 ) -> Result<()> {
+    if let Some(new_limit) = new_limit {
+        if new_limit.get_cur() > new_limit.get_max() {
+            return_errno!(EINVAL, "the soft limit is greater than the hard limit");
+        }
+        if let resource_t::RLIMIT_NOFILE = resource {
+            if new_limit.get_max() > MAX_NUM_FDS as u64 {
+                return_errno!(EPERM, "the limit exceeds the max number of fds");
+            }
+        }
+    }
     let process_ref = if pid == 0 {
         process::get_current()
     } else {
@@ -106,6 +179,12 @@ pub fn do_prlimit(
     }
     if let Some(new_limit) = new_limit {
         *rlimits.get_mut(resource) = *new_limit;
+        // The file table, which is shared by the threads like the limits, is
+        // not grown beyond the limit
+        if let resource_t::RLIMIT_NOFILE = resource {
+            let max_fds = rlimits.get_nofile_limit();
+            process.get_files().lock().unwrap().set_max_fds(max_fds);
+        }
     }
     Ok(())
 }
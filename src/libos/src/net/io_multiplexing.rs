@@ -1,12 +1,16 @@
 use super::*;
-use fs::{AsDevRandom, File, FileDesc, FileRef};
+use fs::{AsDevRandom, AsINodeFile, File, FileDesc, FileRef, PipeReader, PipeWriter, SizeWatch};
 use std::any::Any;
 use std::collections::btree_map::BTreeMap;
 use std::fmt;
 use std::sync::atomic::spin_loop_hint;
+use std::sync::Weak;
+use std::time::Duration;
 use std::vec::Vec;
+use time::{timespec_t, ClockID};
+use util::waiter::{Waiter, WaiterQueue};
 
-/// Forward to host `poll`
+/// Implemented on top of `do_poll`
 /// (sgx_libc doesn't have `select`)
 pub fn do_select(
     nfds: usize,
@@ -16,14 +20,7 @@ pub fn do_select(
     timeout: Option<libc::timeval>,
 ) -> Result<usize> {
     info!("select: nfds: {}", nfds);
-    // convert libos fd to Linux fd
-    let mut host_to_libos_fd = [0; libc::FD_SETSIZE];
-    let mut polls = Vec::<libc::pollfd>::new();
-
-    let current_ref = process::get_current();
-    let mut proc = current_ref.lock().unwrap();
-    let file_table_ref = proc.get_files().lock().unwrap();
-
+    let mut pollfds = Vec::<libc::pollfd>::new();
     for fd in 0..nfds {
         let (r, w, e) = (
             readfds.is_set(fd),
@@ -33,32 +30,6 @@ pub fn do_select(
         if !(r || w || e) {
             continue;
         }
-        if let Ok(socket) = file_table_ref.get(fd as FileDesc)?.as_unix_socket() {
-            warn!("select unix socket is unimplemented, spin for read");
-            readfds.clear();
-            writefds.clear();
-            exceptfds.clear();
-
-            // FIXME: spin poll until can read (hack for php)
-            while r && socket.poll()?.0 == false {
-                spin_loop_hint();
-            }
-
-            let (rr, ww, ee) = socket.poll()?;
-            if r && rr {
-                readfds.set(fd);
-            }
-            if w && ww {
-                writefds.set(fd);
-            }
-            if e && ee {
-                writefds.set(fd);
-            }
-            return Ok(1);
-        }
-        let host_fd = file_table_ref.get(fd as FileDesc)?.as_socket()?.fd();
-
-        host_to_libos_fd[host_fd as usize] = fd;
         let mut events = 0;
         if r {
             events |= libc::POLLIN;
@@ -67,11 +38,10 @@ pub fn do_select(
             events |= libc::POLLOUT;
         }
         if e {
-            events |= libc::POLLERR;
+            events |= libc::POLLPRI;
         }
-
-        polls.push(libc::pollfd {
-            fd: host_fd as c_int,
+        pollfds.push(libc::pollfd {
+            fd: fd as c_int,
             events,
             revents: 0,
         });
@@ -81,32 +51,33 @@ pub fn do_select(
         None => -1,
         Some(tv) => (tv.tv_sec * 1000 + tv.tv_usec / 1000) as i32,
     };
+    do_poll(&mut pollfds, timeout)?;
 
-    let ret = try_libc!(libc::ocall::poll(
-        polls.as_mut_ptr(),
-        polls.len() as u64,
-        timeout
-    ));
-
-    // convert fd back and write fdset
+    // Write back the fd sets. Like Linux, an error or a hangup makes an fd
+    // readable and writable, so that the following read or write reports it.
     readfds.clear();
     writefds.clear();
     exceptfds.clear();
-
-    for poll in polls.iter() {
-        let fd = host_to_libos_fd[poll.fd as usize];
-        if poll.revents & libc::POLLIN != 0 {
+    let mut num_events = 0;
+    for pollfd in pollfds.iter() {
+        let fd = pollfd.fd as usize;
+        let revents = pollfd.revents;
+        if pollfd.events & libc::POLLIN != 0
+            && revents & (libc::POLLIN | libc::POLLHUP | libc::POLLERR) != 0
+        {
             readfds.set(fd);
+            num_events += 1;
         }
-        if poll.revents & libc::POLLOUT != 0 {
+        if pollfd.events & libc::POLLOUT != 0 && revents & (libc::POLLOUT | libc::POLLERR) != 0 {
             writefds.set(fd);
+            num_events += 1;
         }
-        if poll.revents & libc::POLLERR != 0 {
+        if pollfd.events & libc::POLLPRI != 0 && revents & (libc::POLLPRI | libc::POLLERR) != 0 {
             exceptfds.set(fd);
+            num_events += 1;
         }
     }
-
-    Ok(ret as usize)
+    Ok(num_events)
 }
 
 pub fn do_poll(pollfds: &mut [libc::pollfd], timeout: c_int) -> Result<usize> {
@@ -116,22 +87,47 @@ pub fn do_poll(pollfds: &mut [libc::pollfd], timeout: c_int) -> Result<usize> {
         timeout
     );
 
-    let current_ref = process::get_current();
-    let mut proc = current_ref.lock().unwrap();
+    // The process is not kept locked while waiting, or its other threads,
+    // which may be the ones to make the files ready, would be blocked
+    let file_refs = {
+        let current_ref = process::get_current();
+        let proc = current_ref.lock().unwrap();
+        let file_table = proc.get_files().lock().unwrap();
+        pollfds
+            .iter()
+            .map(|pollfd| file_table.get(pollfd.fd as FileDesc))
+            .collect::<Result<Vec<FileRef>>>()?
+    };
 
-    // Untrusted pollfd's that will be modified by OCall
-    let mut u_pollfds: Vec<libc::pollfd> = pollfds.to_vec();
+    // Untrusted pollfd's of the host fds that will be modified by OCall
+    let mut u_pollfds: Vec<libc::pollfd> = Vec::new();
+    // The indexes of the host fds in pollfds
+    let mut u_pollfd_idxes: Vec<usize> = Vec::new();
+    // The files whose readiness is known to the LibOS
+    let mut local_files: Vec<(usize, FileRef)> = Vec::new();
+    // The UDP sockets whose looped back datagrams are known to the LibOS,
+    // which are polled on the host as well
+    let mut inbox_sockets: Vec<(usize, FileRef)> = Vec::new();
 
-    for (i, pollfd) in pollfds.iter_mut().enumerate() {
-        let file_ref = proc
-            .get_files()
-            .lock()
-            .unwrap()
-            .get(pollfd.fd as FileDesc)?;
-        if let Ok(socket) = file_ref.as_socket() {
+    for (i, (pollfd, file_ref)) in pollfds.iter_mut().zip(file_refs).enumerate() {
+        pollfd.revents = 0;
+        // The looped back TCP sockets are polled in the LibOS as the local
+        // files
+        if let Some(socket) = file_ref
+            .as_socket()
+            .ok()
+            .filter(|socket| !socket.is_loopback())
+        {
             // convert libos fd to host fd in the copy to keep pollfds unchanged
-            u_pollfds[i].fd = socket.fd();
-            u_pollfds[i].revents = 0;
+            u_pollfds.push(libc::pollfd {
+                fd: socket.fd(),
+                events: pollfd.events,
+                revents: 0,
+            });
+            u_pollfd_idxes.push(i);
+            if socket.udp_inbox().is_some() {
+                inbox_sockets.push((i, file_ref.clone()));
+            }
         } else if let Ok(socket) = file_ref.as_unix_socket() {
             // FIXME: spin poll until can read (hack for php)
             while (pollfd.events & libc::POLLIN) != 0 && socket.poll()?.0 == false {
@@ -151,31 +147,183 @@ pub fn do_poll(pollfds: &mut [libc::pollfd], timeout: c_int) -> Result<usize> {
             }
             warn!("poll unix socket is unimplemented, spin for read");
             return Ok(1);
-        } else if let Ok(dev_random) = file_ref.as_dev_random() {
-            return Ok(dev_random.poll(pollfd)?);
         } else {
-            return_errno!(EBADF, "not a supported file type");
+            // Fail early on the unsupported file types
+            poll_local_file(&file_ref, pollfd.events)?;
+            local_files.push((i, file_ref));
         }
     }
 
-    let num_events = try_libc!(libc::ocall::poll(
-        u_pollfds.as_mut_ptr(),
-        u_pollfds.len() as u64,
-        timeout
-    )) as usize;
-    assert!(num_events <= pollfds.len());
+    if local_files.is_empty() && inbox_sockets.is_empty() {
+        let num_events = try_libc!(libc::ocall::poll(
+            u_pollfds.as_mut_ptr(),
+            u_pollfds.len() as u64,
+            timeout
+        )) as usize;
+        assert!(num_events <= u_pollfds.len());
 
-    // Copy back revents from the untrusted pollfds
-    let mut num_nonzero_revents = 0;
-    for (i, pollfd) in pollfds.iter_mut().enumerate() {
-        if u_pollfds[i].revents == 0 {
-            continue;
+        // Copy back revents from the untrusted pollfds
+        for (u_pollfd, i) in u_pollfds.iter().zip(u_pollfd_idxes.iter()) {
+            pollfds[*i].revents = u_pollfd.revents;
+        }
+        return Ok(num_events);
+    }
+
+    // The local files and the inboxes of the UDP sockets wake the waiter when
+    // their readiness changes, while the host fds are polled on the host
+    // along with the waiter. The other local files are always ready.
+    let waiter = Waiter::new()?;
+    let waiter_queues: Vec<Arc<WaiterQueue>> = local_files
+        .iter()
+        .chain(inbox_sockets.iter())
+        .filter_map(|(_, file_ref)| local_file_waiters(file_ref))
+        .collect();
+    let deadline = if timeout > 0 {
+        let now = time::do_clock_gettime(ClockID::CLOCK_MONOTONIC)?.as_duration();
+        Some(now + Duration::from_millis(timeout as u64))
+    } else {
+        None
+    };
+    loop {
+        let max_wait = match deadline {
+            _ if timeout == 0 => Some(Duration::default()),
+            Some(deadline) => {
+                let now = time::do_clock_gettime(ClockID::CLOCK_MONOTONIC)?.as_duration();
+                Some(deadline.checked_sub(now).unwrap_or_default())
+            }
+            None => None,
+        };
+
+        waiter.reset();
+        for waiter_queue in waiter_queues.iter() {
+            waiter_queue.enqueue(&waiter);
         }
-        pollfd.revents = u_pollfds[i].revents;
-        num_nonzero_revents += 1;
+        let ret = poll_once(
+            pollfds,
+            &local_files,
+            &inbox_sockets,
+            &mut u_pollfds,
+            &u_pollfd_idxes,
+            &waiter,
+            max_wait,
+        );
+        for waiter_queue in waiter_queues.iter() {
+            waiter_queue.dequeue(&waiter);
+        }
+        let num_events = ret?;
+
+        if num_events > 0 || timeout == 0 {
+            return Ok(num_events);
+        }
+        if let Some(deadline) = deadline {
+            let now = time::do_clock_gettime(ClockID::CLOCK_MONOTONIC)?.as_duration();
+            if now >= deadline {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+/// Check the local files and poll the host fds, sleeping on the waiter for at
+/// most `max_wait`, or indefinitely if it is None, when none is ready
+fn poll_once(
+    pollfds: &mut [libc::pollfd],
+    local_files: &[(usize, FileRef)],
+    inbox_sockets: &[(usize, FileRef)],
+    u_pollfds: &mut Vec<libc::pollfd>,
+    u_pollfd_idxes: &[usize],
+    waiter: &Waiter,
+    max_wait: Option<Duration>,
+) -> Result<usize> {
+    let mut num_events = 0;
+    for (i, file_ref) in local_files.iter() {
+        let pollfd = &mut pollfds[*i];
+        // POLLERR and POLLHUP are always reported
+        pollfd.revents = poll_local_file(file_ref, pollfd.events)?
+            & (pollfd.events | libc::POLLERR | libc::POLLHUP);
+        if pollfd.revents != 0 {
+            num_events += 1;
+        }
+    }
+
+    let inbox_ready: Vec<usize> = inbox_sockets
+        .iter()
+        .filter(|(i, file_ref)| {
+            pollfds[*i].events & libc::POLLIN != 0
+                && file_ref
+                    .as_socket()
+                    .map_or(false, |socket| socket.has_udp_datagrams())
+        })
+        .map(|(i, _)| *i)
+        .collect();
+
+    let host_timeout = if num_events > 0 || !inbox_ready.is_empty() {
+        Some(Duration::default())
+    } else {
+        max_wait
+    };
+    let num_host_events = waiter.wait_with_host_fds(u_pollfds, host_timeout.as_ref())?;
+    assert!(num_host_events <= u_pollfds.len());
+
+    // Copy back revents from the untrusted pollfds, with POLLIN for the
+    // looped back datagrams
+    for (u_pollfd, i) in u_pollfds.iter().zip(u_pollfd_idxes.iter()) {
+        pollfds[*i].revents = u_pollfd.revents;
+    }
+    for i in inbox_ready {
+        pollfds[i].revents |= libc::POLLIN;
+    }
+    num_events += u_pollfd_idxes
+        .iter()
+        .filter(|i| pollfds[**i].revents != 0)
+        .count();
+    Ok(num_events)
+}
+
+/// Get the waiters that are woken when the readiness of a file that is not
+/// backed by a host fd changes, or None if there are no such waiters
+fn local_file_waiters(file_ref: &FileRef) -> Option<Arc<WaiterQueue>> {
+    if let Ok(socket) = file_ref.as_socket() {
+        return socket.loopback_waiters();
+    }
+    if let Some(pipe_reader) = file_ref.as_any().downcast_ref::<PipeReader>() {
+        return Some(pipe_reader.waiters().clone());
+    }
+    if let Some(pipe_writer) = file_ref.as_any().downcast_ref::<PipeWriter>() {
+        return Some(pipe_writer.waiters().clone());
+    }
+    None
+}
+
+/// Get the ready events of a file that is not backed by a host fd
+fn poll_local_file(file_ref: &FileRef, events: i16) -> Result<i16> {
+    if let Some(revents) = file_ref
+        .as_socket()
+        .ok()
+        .and_then(|socket| socket.poll_loopback())
+    {
+        return Ok(revents);
+    }
+    // Regular files and directories are always ready for read and write
+    if file_ref.as_inode_file().is_ok() {
+        return Ok(libc::POLLIN | libc::POLLOUT);
     }
-    assert!(num_nonzero_revents == num_events);
-    Ok(num_events as usize)
+    if let Ok(dev_random) = file_ref.as_dev_random() {
+        let mut pollfd = libc::pollfd {
+            fd: 0,
+            events,
+            revents: 0,
+        };
+        dev_random.poll(&mut pollfd)?;
+        return Ok(pollfd.revents);
+    }
+    if let Some(pipe_reader) = file_ref.as_any().downcast_ref::<PipeReader>() {
+        return Ok(pipe_reader.poll());
+    }
+    if let Some(pipe_writer) = file_ref.as_any().downcast_ref::<PipeWriter>() {
+        return Ok(pipe_writer.poll());
+    }
+    return_errno!(EBADF, "not a supported file type");
 }
 
 pub fn do_epoll_create1(flags: c_int) -> Result<FileDesc> {
@@ -190,7 +338,7 @@ pub fn do_epoll_create1(flags: c_int) -> Result<FileDesc> {
         proc.get_files()
             .lock()
             .unwrap()
-            .put(file_ref, close_on_spawn)
+            .put(file_ref, close_on_spawn)?
     };
     Ok(fd)
 }
@@ -207,17 +355,29 @@ pub fn do_epoll_ctl(
     let mut proc = current_ref.lock().unwrap();
     let mut file_table_ref = proc.get_files().lock().unwrap();
     let mut file_ref = file_table_ref.get(epfd)?;
-    let mut epoll = file_ref.as_epoll()?.inner.lock().unwrap();
+    let epoll_file = file_ref.as_epoll()?;
+    if fd == epfd {
+        return_errno!(EINVAL, "an epoll cannot be added to itself");
+    }
+    let mut epoll = epoll_file.inner.lock().unwrap();
+    // The closed fds, which may be reused, are not watched any more
+    epoll.remove_closed_fds();
 
+    // A nested epoll is added to the Linux epoll by its own Linux epoll. A
+    // regular file is only added with EPOLLSIZE, to watch its size changes,
+    // and is kept in the epoll until it is deleted.
     let fd_ref = file_table_ref.get(fd)?;
-    let sock_result = fd_ref.as_socket();
-    if sock_result.is_err() {
+    let host_fd = if let Ok(socket) = fd_ref.as_socket() {
+        socket.fd() as FileDesc
+    } else if let Ok(nested_epoll) = fd_ref.as_epoll() {
+        nested_epoll.epoll_fd as FileDesc
+    } else if fd_ref.as_inode_file().is_ok() && epoll.watches_size(op, SIZE_WATCH_KEY | fd, event) {
+        SIZE_WATCH_KEY | fd
+    } else {
         //FIXME: workaround for grpc, other fd types including pipe should be supported
         return Ok(());
-    }
-
-    let host_fd = sock_result.unwrap().fd() as FileDesc;
-    epoll.ctl(op, host_fd, event)?;
+    };
+    epoll.ctl(op, host_fd, event, &fd_ref)?;
 
     Ok(())
 }
@@ -234,15 +394,36 @@ pub fn do_epoll_wait(
         timeout
     );
 
-    let current_ref = process::get_current();
-    let mut proc = current_ref.lock().unwrap();
-    let mut file_ref = proc.get_files().lock().unwrap().get(epfd)?;
+    // The process is not kept locked while waiting, as in do_poll
+    let file_ref = fs::get_file(epfd)?;
     let mut epoll = file_ref.as_epoll()?.inner.lock().unwrap();
 
     let count = epoll.wait(events, timeout)?;
     Ok(count)
 }
 
+/// Like `do_epoll_wait`, but with a timeout of nanosecond precision. `None`
+/// means blocking indefinitely.
+pub fn do_epoll_pwait2(
+    epfd: FileDesc,
+    events: &mut [libc::epoll_event],
+    timeout: Option<&timespec_t>,
+) -> Result<usize> {
+    info!(
+        "epoll_pwait2: epfd: {}, len: {:?}, timeout: {:?}",
+        epfd,
+        events.len(),
+        timeout
+    );
+
+    // The process is not kept locked while waiting, as in do_poll
+    let file_ref = fs::get_file(epfd)?;
+    let mut epoll = file_ref.as_epoll()?.inner.lock().unwrap();
+
+    let count = epoll.wait_timespec(events, timeout)?;
+    Ok(count)
+}
+
 /// Safe methods for `libc::fd_set`
 trait FdSetExt {
     fn set(&mut self, fd: usize);
@@ -271,27 +452,214 @@ impl FdSetExt for libc::fd_set {
 }
 
 pub struct EpollFile {
+    // The Linux epoll, which can be read without locking the inner
+    epoll_fd: c_int,
     inner: SgxMutex<EpollFileInner>,
 }
 
 impl EpollFile {
     pub fn new() -> Result<Self> {
+        let inner = EpollFileInner::new()?;
         Ok(Self {
-            inner: SgxMutex::new(EpollFileInner::new()?),
+            epoll_fd: inner.epoll_fd,
+            inner: SgxMutex::new(inner),
         })
     }
+
+    /// Get the ready events of the epoll nested in another one, which is
+    /// readable if it has any fds to report. Also return how the LibOS knows
+    /// when it becomes ready, other than by its Linux epoll.
+    ///
+    /// While the epoll is being waited on, only its Linux epoll is polled,
+    /// and it is polled again after a short interval.
+    fn poll(&self) -> Result<(u32, LocalWakes)> {
+        let mut inner = match self.inner.try_lock() {
+            Ok(inner) => inner,
+            Err(_) => {
+                let revents = poll_host_fd(self.epoll_fd as FileDesc, EPOLLIN)?;
+                return Ok((revents, LocalWakes::by_polls()));
+            }
+        };
+        let (is_ready, local_wakes) = inner.poll()?;
+        Ok((if is_ready { EPOLLIN } else { 0 }, local_wakes))
+    }
 }
 
+const EPOLL_CTL_ADD: c_int = 1;
+const EPOLL_CTL_DEL: c_int = 2;
+const EPOLL_CTL_MOD: c_int = 3;
+
+const EPOLLIN: u32 = 0x001;
+const EPOLLERR: u32 = 0x008;
+const EPOLLHUP: u32 = 0x010;
+/// Report EPOLLIN and EPOLLSIZE when the size of a regular file changes,
+/// which is specific to Occlum. Without it, a regular file is not added.
+const EPOLLSIZE: u32 = 1 << 26;
+const EPOLLONESHOT: u32 = 1 << 30;
+const EPOLLET: u32 = 1 << 31;
+
+/// The keys of the regular files watched for their size changes in the
+/// epoll, which are the fds with the bit, apart from the host fds
+const SIZE_WATCH_KEY: FileDesc = 1 << 31;
+
+/// The interval to poll the files in the LibOS that have no waiter queues to
+/// wake the wait
+const LOCAL_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// The max number of the epolls that an epoll can be nested in, one inside
+/// another, which is the same as Linux
+const MAX_EPOLL_NESTS: usize = 4;
+
+lazy_static! {
+    /// The epolls that are nested in other ones, as the pairs of the outer
+    /// and the inner Linux epolls
+    static ref EPOLL_NESTS: SgxMutex<Vec<(c_int, c_int)>> = SgxMutex::new(Vec::new());
+}
+
+/// Epoll is implemented on top of a Linux epoll, to which all fds are added
+/// as edge-triggered, with the host fds as the data. So the Linux epoll only
+/// reports the readiness transitions, which is exactly what an edge-triggered
+/// fd needs. A level-triggered fd is kept in the ready list after it is
+/// reported, and is checked and reported again in the following waits, as
+/// long as the condition holds.
+///
+/// Some files are polled in the LibOS in every wait instead. The sockets
+/// looped back inside the enclave are never ready in the Linux epoll, so the
+/// wait sleeps on a waiter that is woken by their waiter queues, along with
+/// the Linux epoll. A nested epoll is added by its Linux epoll, which only
+/// wakes the wait up, for the level-triggered fds and the looped back sockets
+/// inside it are known by the LibOS only.
+///
+/// The regular files watched for their size changes are not in the Linux
+/// epoll. They are polled in the LibOS, which only checks the changes notified
+/// by the writes and truncates of the inodes, and are reported once for the
+/// changes since the last report, whether edge-triggered or not.
+///
+/// The fds whose files are closed are removed from the Linux epoll by the
+/// host, and from the epoll in the next wait or ctl.
 struct EpollFileInner {
     epoll_fd: c_int,
+    // The interests of the host fds
+    interests: HashMap<FileDesc, EpollInterest>,
+    // The level-triggered host fds that were reported in the last waits
+    ready_list: Vec<FileDesc>,
+    // The files of the host fds, which are not kept open by the epoll
+    files: HashMap<FileDesc, Weak<Box<dyn File>>>,
+    // The states of the host fds whose files are polled in the LibOS
+    local_states: HashMap<FileDesc, LocalState>,
+    // The size watches of the regular files
+    size_watches: HashMap<FileDesc, SizeWatchState>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct EpollInterest {
+    events: u32,
+    data: u64,
+}
+
+/// How a wait learns that the files polled in the LibOS become ready
+#[derive(Default)]
+struct LocalWakes {
+    // The waiter queues that are woken when the readiness of the files changes
+    queues: Vec<Arc<WaiterQueue>>,
+    // Whether any of the files has no waiter queue, and is polled again after
+    // LOCAL_POLL_INTERVAL
+    needs_polls: bool,
+}
+
+impl LocalWakes {
+    fn by_polls() -> LocalWakes {
+        LocalWakes {
+            queues: Vec::new(),
+            needs_polls: true,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.queues.is_empty() && !self.needs_polls
+    }
+
+    fn extend(&mut self, other: LocalWakes) {
+        self.queues.extend(other.queues);
+        self.needs_polls |= other.needs_polls;
+    }
+
+    /// Whether the queues are the same as the other ones
+    fn has_same_queues(&self, other: &LocalWakes) -> bool {
+        self.queues.len() == other.queues.len()
+            && self
+                .queues
+                .iter()
+                .zip(other.queues.iter())
+                .all(|(queue, other_queue)| Arc::ptr_eq(queue, other_queue))
+    }
+}
+
+impl EpollInterest {
+    fn is_level_triggered(&self) -> bool {
+        self.events & (EPOLLET | EPOLLONESHOT) == 0
+    }
+
+    /// The events to report, which always include errors and hangups
+    fn filter_events(&self, revents: u32) -> u32 {
+        revents & (self.events | EPOLLERR | EPOLLHUP)
+    }
+}
+
+/// The state of a file polled in the LibOS in the epoll
+#[derive(Debug, Default, Clone, Copy)]
+struct LocalState {
+    // The events that were ready in the last wait, to tell the edges
+    revents: u32,
+    // Whether the file of EPOLLONESHOT is reported, until it is modified
+    is_disarmed: bool,
+}
+
+/// The size watch of a regular file in the epoll
+#[derive(Debug)]
+struct SizeWatchState {
+    watch: SizeWatch,
+    // The sequence number of the size changes that was reported last time
+    reported_seq: u64,
+    // The one got in the last poll, which is reported if the file is
+    polled_seq: u64,
+}
+
+impl SizeWatchState {
+    fn new(file_ref: &FileRef) -> Result<SizeWatchState> {
+        let watch = SizeWatch::new(file_ref.as_inode_file()?.get_inode())?;
+        let seq = watch.size_seq();
+        Ok(SizeWatchState {
+            watch,
+            reported_seq: seq,
+            polled_seq: seq,
+        })
+    }
+
+    /// Get the events to report, which are ready if the size has changed
+    /// since the last report
+    fn poll(&mut self) -> u32 {
+        self.polled_seq = self.watch.size_seq();
+        if self.polled_seq != self.reported_seq {
+            EPOLLIN | EPOLLSIZE
+        } else {
+            0
+        }
+    }
 }
 
-// FIXME: What if a Linux fd is closed but still in an epoll?
 impl EpollFileInner {
     /// Create a new Linux epoll file descriptor
     pub fn new() -> Result<Self> {
         let ret = try_libc!(libc::ocall::epoll_create1(0));
-        Ok(EpollFileInner { epoll_fd: ret })
+        Ok(EpollFileInner {
+            epoll_fd: ret,
+            interests: HashMap::new(),
+            ready_list: Vec::new(),
+            files: HashMap::new(),
+            local_states: HashMap::new(),
+            size_watches: HashMap::new(),
+        })
     }
 
     pub fn ctl(
@@ -299,31 +667,493 @@ impl EpollFileInner {
         op: c_int,
         host_fd: FileDesc,
         event: *const libc::epoll_event,
+        file_ref: &FileRef,
     ) -> Result<()> {
-        let ret = try_libc!(libc::ocall::epoll_ctl(
-            self.epoll_fd,
-            op,
-            host_fd as c_int,
-            event as *mut _
-        ));
+        let interest = match op {
+            EPOLL_CTL_ADD | EPOLL_CTL_MOD => {
+                if event.is_null() {
+                    return_errno!(EFAULT, "event must not be null");
+                }
+                let event = unsafe { event.read() };
+                Some(EpollInterest {
+                    events: event.events,
+                    data: event.u64,
+                })
+            }
+            EPOLL_CTL_DEL => None,
+            _ => return_errno!(EINVAL, "invalid op"),
+        };
+        // A closed fd may be reused by a new file
+        self.remove_closed_fds();
+        match (op, self.interests.contains_key(&host_fd)) {
+            (EPOLL_CTL_ADD, true) => return_errno!(EEXIST, "fd is already in the epoll"),
+            (EPOLL_CTL_MOD, false) | (EPOLL_CTL_DEL, false) => {
+                return_errno!(ENOENT, "fd is not in the epoll")
+            }
+            _ => {}
+        }
+
+        let nested_epoll_fd = file_ref.as_epoll().ok().map(|epoll| epoll.epoll_fd);
+        if let (EPOLL_CTL_ADD, Some(nested_epoll_fd)) = (op, nested_epoll_fd) {
+            nest_epoll(self.epoll_fd, nested_epoll_fd)?;
+        }
+        let mut host_event = libc::epoll_event {
+            events: interest.map_or(0, |interest| interest.events | EPOLLET),
+            u64: host_fd as u64,
+        };
+        let host_result = (|| {
+            // The regular files watched for their size changes are not in the
+            // Linux epoll
+            if host_fd & SIZE_WATCH_KEY != 0 {
+                return Ok(());
+            }
+            try_libc!(libc::ocall::epoll_ctl(
+                self.epoll_fd,
+                op,
+                host_fd as c_int,
+                &mut host_event
+            ));
+            Ok(())
+        })();
+        if let Some(nested_epoll_fd) = nested_epoll_fd {
+            match op {
+                EPOLL_CTL_ADD if host_result.is_err() => {
+                    unnest_epoll(self.epoll_fd, nested_epoll_fd)
+                }
+                EPOLL_CTL_DEL if host_result.is_ok() => {
+                    unnest_epoll(self.epoll_fd, nested_epoll_fd)
+                }
+                _ => {}
+            }
+        }
+        host_result?;
+        if op == EPOLL_CTL_ADD && host_fd & SIZE_WATCH_KEY != 0 {
+            let size_watch = SizeWatchState::new(file_ref)?;
+            self.size_watches.insert(host_fd, size_watch);
+        }
+
+        // A modified fd is reported again by the Linux epoll if it is ready
+        self.ready_list.retain(|fd| *fd != host_fd);
+        self.local_states.remove(&host_fd);
+        match interest {
+            Some(interest) => {
+                self.interests.insert(host_fd, interest);
+                self.files.insert(host_fd, Arc::downgrade(file_ref));
+            }
+            None => {
+                self.interests.remove(&host_fd);
+                self.files.remove(&host_fd);
+                self.size_watches.remove(&host_fd);
+            }
+        };
         Ok(())
     }
 
+    /// Whether the regular file of the key is in the epoll to watch its size
+    /// changes, or is to be added for that
+    fn watches_size(&self, op: c_int, key: FileDesc, event: *const libc::epoll_event) -> bool {
+        if self.interests.contains_key(&key) {
+            return true;
+        }
+        op == EPOLL_CTL_ADD && !event.is_null() && unsafe { event.read() }.events & EPOLLSIZE != 0
+    }
+
+    /// Remove the fds whose files are closed, which are already removed from
+    /// the Linux epoll
+    fn remove_closed_fds(&mut self) {
+        let closed_fds: Vec<FileDesc> = self
+            .files
+            .iter()
+            .filter(|(_, file)| file.upgrade().is_none())
+            .map(|(host_fd, _)| *host_fd)
+            .collect();
+        for host_fd in closed_fds {
+            self.interests.remove(&host_fd);
+            self.files.remove(&host_fd);
+            self.local_states.remove(&host_fd);
+            self.size_watches.remove(&host_fd);
+        }
+        let interests = &self.interests;
+        self.ready_list
+            .retain(|host_fd| interests.contains_key(host_fd));
+    }
+
     /// Wait for an I/O event on the epoll.
     /// Returns the number of file descriptors ready for the requested I/O.
     pub fn wait(&mut self, events: &mut [libc::epoll_event], timeout: c_int) -> Result<usize> {
-        let ret = try_libc!(libc::ocall::epoll_wait(
-            self.epoll_fd,
-            events.as_mut_ptr(),
-            events.len() as c_int,
-            timeout,
-        ));
-        Ok(ret as usize)
+        let epoll_fd = self.epoll_fd;
+        let timeout = if timeout < 0 {
+            None
+        } else {
+            Some(Duration::from_millis(timeout as u64))
+        };
+        self.wait_with(events, timeout, |host_events, host_timeout| {
+            let ret = try_libc!(libc::ocall::epoll_wait(
+                epoll_fd,
+                host_events.as_mut_ptr(),
+                host_events.len() as c_int,
+                host_timeout.map_or(-1, |host_timeout| host_timeout.as_millis() as c_int),
+            ));
+            Ok(ret as usize)
+        })
+    }
+
+    /// Like `wait`, but the timeout is given as a timespec.
+    ///
+    /// The timeout is waited for on the host with epoll_pwait2, which Linux
+    /// supports since 5.11. On the older hosts, it is rounded up to
+    /// milliseconds for epoll_wait.
+    pub fn wait_timespec(
+        &mut self,
+        events: &mut [libc::epoll_event],
+        timeout: Option<&timespec_t>,
+    ) -> Result<usize> {
+        let epoll_fd = self.epoll_fd;
+        let timeout = timeout.map(|timeout| timeout.as_duration());
+        self.wait_with(events, timeout, |host_events, host_timeout| {
+            let host_timeout = host_timeout.map(timespec_t::from_duration);
+            let timeout_ptr = host_timeout
+                .as_ref()
+                .map_or(std::ptr::null(), |ts| ts as *const timespec_t);
+            let ret = try_libc!({
+                let mut retval: c_int = 0;
+                let status = occlum_ocall_epoll_pwait2(
+                    &mut retval,
+                    epoll_fd,
+                    host_events.as_mut_ptr(),
+                    host_events.len() * std::mem::size_of::<libc::epoll_event>(),
+                    host_events.len() as c_int,
+                    timeout_ptr,
+                );
+                assert!(status == sgx_status_t::SGX_SUCCESS);
+                retval
+            });
+            Ok(ret as usize)
+        })
+    }
+
+    /// Wait for the events with the given function, which waits on the Linux
+    /// epoll for the given time, or indefinitely if it is None.
+    ///
+    /// The wait sleeps on a waiter that is woken by the waiter queues of the
+    /// files polled in the LibOS, or by the Linux epoll, which is polled on
+    /// the host along with the waiter, so that the wait fails with EINTR when
+    /// a signal is sent to the thread. The files without waiter queues are
+    /// polled again after a short interval.
+    fn wait_with<F>(
+        &mut self,
+        events: &mut [libc::epoll_event],
+        timeout: Option<Duration>,
+        mut host_wait: F,
+    ) -> Result<usize>
+    where
+        F: FnMut(&mut [libc::epoll_event], Option<Duration>) -> Result<usize>,
+    {
+        let no_wait = Duration::from_secs(0);
+        let (num_events, mut local_wakes) =
+            self.wait_once(events, &mut host_wait, Some(no_wait))?;
+        if num_events > 0 || timeout == Some(no_wait) {
+            return Ok(num_events);
+        }
+
+        let deadline = match timeout {
+            Some(timeout) => {
+                let now = time::do_clock_gettime(ClockID::CLOCK_MONOTONIC)?.as_duration();
+                Some(now + timeout)
+            }
+            None => None,
+        };
+        let epoll_fd = self.epoll_fd;
+        let waiter = Waiter::new()?;
+        loop {
+            let mut max_wait = match deadline {
+                Some(deadline) => {
+                    let now = time::do_clock_gettime(ClockID::CLOCK_MONOTONIC)?.as_duration();
+                    if now >= deadline {
+                        return Ok(0);
+                    }
+                    Some(deadline - now)
+                }
+                None => None,
+            };
+            // The waiter sleeps for whole milliseconds, so the rest of the
+            // timeout below one is waited for on the Linux epoll alone
+            if local_wakes.is_empty()
+                && max_wait.map_or(false, |max_wait| max_wait < Duration::from_millis(1))
+            {
+                let (num_events, _) = self.wait_once(events, &mut host_wait, max_wait)?;
+                return Ok(num_events);
+            }
+            if local_wakes.needs_polls {
+                max_wait = Some(max_wait.map_or(LOCAL_POLL_INTERVAL, |max_wait| {
+                    min(max_wait, LOCAL_POLL_INTERVAL)
+                }));
+            }
+
+            // The waiter is put into the queues before the files are polled,
+            // so that no change after the poll is missed
+            waiter.reset();
+            for queue in local_wakes.queues.iter() {
+                queue.enqueue(&waiter);
+            }
+            let ret = self
+                .wait_once(events, &mut host_wait, Some(no_wait))
+                .and_then(|(num_events, new_wakes)| {
+                    // The files to wake the wait may have changed, e.g., by a
+                    // new loopback connection, which are polled again first
+                    if num_events == 0 && new_wakes.has_same_queues(&local_wakes) {
+                        let mut host_pollfds = vec![libc::pollfd {
+                            fd: epoll_fd,
+                            events: libc::POLLIN,
+                            revents: 0,
+                        }];
+                        waiter.wait_with_host_fds(&mut host_pollfds, max_wait.as_ref())?;
+                    }
+                    Ok((num_events, new_wakes))
+                });
+            for queue in local_wakes.queues.iter() {
+                queue.dequeue(&waiter);
+            }
+            let (num_events, new_wakes) = ret?;
+            if num_events > 0 {
+                return Ok(num_events);
+            }
+            local_wakes = new_wakes;
+        }
+    }
+
+    /// Report the ready fds, with the Linux epoll waited on for `timeout` if
+    /// none of them is known to be ready and there are no files to poll in
+    /// the LibOS. Return the number of the fds reported and how the LibOS
+    /// knows when the files polled in it become ready.
+    fn wait_once<F>(
+        &mut self,
+        events: &mut [libc::epoll_event],
+        host_wait: &mut F,
+        timeout: Option<Duration>,
+    ) -> Result<(usize, LocalWakes)>
+    where
+        F: FnMut(&mut [libc::epoll_event], Option<Duration>) -> Result<usize>,
+    {
+        self.remove_closed_fds();
+
+        // Check the level-triggered fds in the ready list
+        let mut still_ready = Vec::new();
+        for host_fd in std::mem::replace(&mut self.ready_list, Vec::new()) {
+            let interest = self.interests[&host_fd];
+            let revents = interest.filter_events(poll_host_fd(host_fd, interest.events)?);
+            if revents != 0 {
+                still_ready.push((host_fd, revents));
+            }
+        }
+        let (mut local_ready, local_wakes) = self.poll_local_files()?;
+
+        let host_timeout =
+            if !still_ready.is_empty() || !local_ready.is_empty() || !local_wakes.is_empty() {
+                Some(Duration::from_secs(0))
+            } else {
+                timeout
+            };
+        let mut host_events: Vec<libc::epoll_event> =
+            vec![libc::epoll_event { events: 0, u64: 0 }; events.len()];
+        let num_host_events = host_wait(&mut host_events, host_timeout)?;
+
+        // The fds reported by the Linux epoll come first, so that the edge-
+        // triggered ones are never lost for lack of room. The files polled in
+        // the LibOS are only woken up by the Linux epoll, and are polled again
+        // then.
+        let mut ready: Vec<(FileDesc, u32)> = Vec::new();
+        let mut wakes_local = false;
+        for host_event in host_events[..num_host_events].iter() {
+            let host_fd = host_event.u64 as FileDesc;
+            if self.local_states.contains_key(&host_fd) {
+                wakes_local = true;
+            } else {
+                ready.push((host_fd, host_event.events));
+            }
+        }
+        if local_ready.is_empty() && wakes_local {
+            local_ready = self.poll_local_files()?.0;
+        }
+        for (host_fd, revents) in still_ready.into_iter().chain(local_ready) {
+            match ready.iter_mut().find(|(fd, _)| *fd == host_fd) {
+                Some((_, ready_revents)) => *ready_revents |= revents,
+                None => ready.push((host_fd, revents)),
+            }
+        }
+
+        let mut num_events = 0;
+        for (host_fd, revents) in ready {
+            let interest = match self.interests.get(&host_fd) {
+                Some(interest) => *interest,
+                None => continue,
+            };
+            let local_state = self.local_states.get_mut(&host_fd);
+            if interest.is_level_triggered() && local_state.is_none() {
+                self.ready_list.push(host_fd);
+            }
+            if num_events == events.len() {
+                // Report the level-triggered fd in the next wait
+                continue;
+            }
+            events[num_events] = libc::epoll_event {
+                events: revents,
+                u64: interest.data,
+            };
+            num_events += 1;
+            if let Some(state) = local_state {
+                state.revents = revents;
+                state.is_disarmed = interest.events & EPOLLONESHOT != 0;
+            }
+            if let Some(size_watch) = self.size_watches.get_mut(&host_fd) {
+                size_watch.reported_seq = size_watch.polled_seq;
+            }
+        }
+        Ok((num_events, local_wakes))
+    }
+
+    /// Poll the files whose readiness is known to the LibOS, which are the
+    /// looped back sockets, the nested epolls and the regular files watched
+    /// for their size changes. Return the ones to report, and how the LibOS
+    /// knows when the others become ready.
+    fn poll_local_files(&mut self) -> Result<(Vec<(FileDesc, u32)>, LocalWakes)> {
+        let mut ready = Vec::new();
+        let mut local_wakes = LocalWakes::default();
+        for (host_fd, file) in self.files.iter() {
+            let file_ref = match file.upgrade() {
+                Some(file_ref) => file_ref,
+                None => continue,
+            };
+            let (revents, wakes) = if let Some(size_watch) = self.size_watches.get_mut(host_fd) {
+                let wakes = LocalWakes {
+                    queues: vec![size_watch.watch.waiters().clone()],
+                    needs_polls: false,
+                };
+                (size_watch.poll(), wakes)
+            } else if let Ok(epoll) = file_ref.as_epoll() {
+                epoll.poll()?
+            } else {
+                let socket = match file_ref.as_socket() {
+                    Ok(socket) => socket,
+                    Err(_) => continue,
+                };
+                match socket.poll_loopback() {
+                    Some(revents) => {
+                        let wakes = LocalWakes {
+                            queues: socket.loopback_waiters().into_iter().collect(),
+                            needs_polls: false,
+                        };
+                        (revents as u16 as u32, wakes)
+                    }
+                    None => continue,
+                }
+            };
+            let interest = self.interests[host_fd];
+            let state = self.local_states.entry(*host_fd).or_default();
+            if state.is_disarmed {
+                continue;
+            }
+            local_wakes.extend(wakes);
+
+            let revents = interest.filter_events(revents);
+            // Each report of the size changes is a new edge
+            let is_edge = revents & !state.revents != 0 || self.size_watches.contains_key(host_fd);
+            if revents != 0 && (interest.is_level_triggered() || is_edge) {
+                ready.push((*host_fd, revents));
+            } else {
+                // Forget the events that are no longer ready, so that they
+                // are reported once ready again
+                state.revents = revents;
+            }
+        }
+        Ok((ready, local_wakes))
+    }
+
+    /// Check whether there are any fds to report without waiting, for the
+    /// epoll nested in another one. Also return how the LibOS knows when the
+    /// files polled in it become ready.
+    fn poll(&mut self) -> Result<(bool, LocalWakes)> {
+        self.remove_closed_fds();
+        let (local_ready, local_wakes) = self.poll_local_files()?;
+        if !local_ready.is_empty() {
+            return Ok((true, local_wakes));
+        }
+        for host_fd in self.ready_list.iter() {
+            let interest = self.interests[host_fd];
+            if interest.filter_events(poll_host_fd(*host_fd, interest.events)?) != 0 {
+                return Ok((true, local_wakes));
+            }
+        }
+        let is_ready = poll_host_fd(self.epoll_fd as FileDesc, EPOLLIN)? != 0;
+        Ok((is_ready, local_wakes))
     }
 }
 
+/// Record that the inner epoll is nested in the outer one, unless it makes a
+/// loop, or the epolls are nested too deep
+fn nest_epoll(outer_fd: c_int, inner_fd: c_int) -> Result<()> {
+    let mut nests = EPOLL_NESTS.lock().unwrap();
+    if is_nested_in(&nests, outer_fd, inner_fd) {
+        return_errno!(ELOOP, "the epoll is already nested in the fd");
+    }
+    let depth = nested_depth(&nests, outer_fd, |(outer, inner)| (inner, outer))
+        + 1
+        + nested_depth(&nests, inner_fd, |nest| nest);
+    if depth > MAX_EPOLL_NESTS {
+        return_errno!(ELOOP, "the epolls are nested too deep");
+    }
+    nests.push((outer_fd, inner_fd));
+    Ok(())
+}
+
+fn unnest_epoll(outer_fd: c_int, inner_fd: c_int) {
+    let mut nests = EPOLL_NESTS.lock().unwrap();
+    if let Some(pos) = nests.iter().position(|nest| *nest == (outer_fd, inner_fd)) {
+        nests.remove(pos);
+    }
+}
+
+/// Whether the epoll is the same as or nested in the other one, directly or
+/// not
+fn is_nested_in(nests: &[(c_int, c_int)], epoll_fd: c_int, other_fd: c_int) -> bool {
+    epoll_fd == other_fd
+        || nests
+            .iter()
+            .any(|(outer, inner)| *outer == other_fd && is_nested_in(nests, epoll_fd, *inner))
+}
+
+/// The max number of the nests from the epoll, with each nest turned into a
+/// pair of the epoll and the next one by `to_next`
+fn nested_depth<F>(nests: &[(c_int, c_int)], epoll_fd: c_int, to_next: F) -> usize
+where
+    F: Fn((c_int, c_int)) -> (c_int, c_int) + Copy,
+{
+    nests
+        .iter()
+        .map(|nest| to_next(*nest))
+        .filter(|(epoll, _)| *epoll == epoll_fd)
+        .map(|(_, next)| 1 + nested_depth(nests, next, to_next))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Get the ready events of a host fd without blocking
+fn poll_host_fd(host_fd: FileDesc, events: u32) -> Result<u32> {
+    let mut pollfd = libc::pollfd {
+        fd: host_fd as c_int,
+        events: events as u16 as i16,
+        revents: 0,
+    };
+    try_libc!(libc::ocall::poll(&mut pollfd, 1, 0));
+    Ok(pollfd.revents as u16 as u32)
+}
+
 impl Drop for EpollFileInner {
     fn drop(&mut self) {
+        EPOLL_NESTS
+            .lock()
+            .unwrap()
+            .retain(|(outer, inner)| *outer != self.epoll_fd && *inner != self.epoll_fd);
         unsafe {
             libc::ocall::close(self.epoll_fd);
         }
@@ -338,9 +1168,8 @@ impl File for EpollFile {
 
 impl Debug for EpollFile {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let inner = self.inner.lock().unwrap();
         f.debug_struct("EpollFile")
-            .field("epoll_fd", &inner.epoll_fd)
+            .field("epoll_fd", &self.epoll_fd)
             .finish()
     }
 }
@@ -356,3 +1185,14 @@ impl AsEpoll for FileRef {
             .ok_or_else(|| errno!(EBADF, "not a epoll"))
     }
 }
+
+extern "C" {
+    fn occlum_ocall_epoll_pwait2(
+        ret: *mut c_int,
+        epfd: c_int,
+        events: *mut libc::epoll_event,
+        events_len: size_t,
+        maxevents: c_int,
+        timeout: *const timespec_t,
+    ) -> sgx_status_t;
+}
@@ -66,6 +66,50 @@ impl<'a> IovsMut<'a> {
     }
 }
 
+/// Split the slices into groups of at most `max_len` bytes in total, so that
+/// a huge vectored I/O can be done group by group with bounded buffers. A
+/// slice that crosses the end of a group is split between the groups.
+pub fn split_iovs<'a>(slices: &[&'a [u8]], max_len: usize) -> Vec<Vec<&'a [u8]>> {
+    let mut groups = vec![Vec::new()];
+    let mut group_len = 0;
+    for &slice in slices {
+        let mut rest = slice;
+        while !rest.is_empty() {
+            if group_len == max_len {
+                groups.push(Vec::new());
+                group_len = 0;
+            }
+            let len = min(rest.len(), max_len - group_len);
+            let (head, tail) = rest.split_at(len);
+            groups.last_mut().unwrap().push(head);
+            group_len += len;
+            rest = tail;
+        }
+    }
+    groups
+}
+
+/// The mutable version of `split_iovs`
+pub fn split_iovs_mut<'a>(slices: &'a mut [&mut [u8]], max_len: usize) -> Vec<Vec<&'a mut [u8]>> {
+    let mut groups = vec![Vec::new()];
+    let mut group_len = 0;
+    for slice in slices.iter_mut() {
+        let mut rest: &'a mut [u8] = &mut slice[..];
+        while !rest.is_empty() {
+            if group_len == max_len {
+                groups.push(Vec::new());
+                group_len = 0;
+            }
+            let len = min(rest.len(), max_len - group_len);
+            let (head, tail) = std::mem::replace(&mut rest, &mut []).split_at_mut(len);
+            groups.last_mut().unwrap().push(head);
+            group_len += len;
+            rest = tail;
+        }
+    }
+    groups
+}
+
 /// An extention trait that converts slice to libc::iovec
 pub trait SliceAsLibcIovec {
     fn as_libc_iovec(&self) -> libc::iovec;
@@ -1,17 +1,22 @@
 use super::*;
 use std;
 
-mod io_multiplexing;
+pub mod io_multiplexing;
 mod iovs;
 mod msg;
 mod msg_flags;
 mod socket_file;
 mod syscalls;
+mod unix_addr;
 mod unix_socket;
 
-pub use self::iovs::{Iovs, IovsMut, SliceAsLibcIovec};
+pub use self::iovs::{split_iovs, split_iovs_mut, Iovs, IovsMut, SliceAsLibcIovec};
 pub use self::msg::{msghdr, msghdr_mut, MsgHdr, MsgHdrMut};
 pub use self::msg_flags::MsgFlags;
-pub use self::socket_file::{AsSocket, SocketFile};
+pub use self::socket_file::{
+    raise_sigpipe_on_epipe, AsSocket, HowToShut, IpOption, SocketFile, UdpRecvd, IFNAMSIZ,
+    SO_BINDTODEVICE, SO_BUSY_POLL,
+};
 pub use self::syscalls::*;
+pub use self::unix_addr::UnixAddr;
 pub use self::unix_socket::{AsUnixSocket, UnixSocketFile};
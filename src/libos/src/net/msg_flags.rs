@@ -1,17 +1,29 @@
 use super::*;
 
-// TODO: use bitflag! to make this memory safe
-#[derive(Debug, Copy, Clone, Default)]
-pub struct MsgFlags {
-    bits: u32,
+bitflags! {
+    #[derive(Default)]
+    pub struct MsgFlags: u32 {
+        /// Send or receive the out-of-band data, i.e., the urgent data of TCP
+        const MSG_OOB = 0x1;
+        const MSG_PEEK = 0x2;
+        const MSG_DONTROUTE = 0x4;
+        const MSG_CTRUNC = 0x8;
+        const MSG_TRUNC = 0x20;
+        const MSG_DONTWAIT = 0x40;
+        const MSG_EOR = 0x80;
+        const MSG_WAITALL = 0x100;
+        const MSG_NOSIGNAL = 0x4000;
+        const MSG_MORE = 0x8000;
+        const MSG_CMSG_CLOEXEC = 0x4000_0000;
+    }
 }
 
 impl MsgFlags {
     pub fn from_u32(c_flags: u32) -> Result<MsgFlags> {
-        Ok(MsgFlags { bits: 0 })
+        Ok(MsgFlags::from_bits_truncate(c_flags))
     }
 
     pub fn to_u32(&self) -> u32 {
-        self.bits
+        self.bits()
     }
 }
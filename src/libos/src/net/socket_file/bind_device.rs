@@ -0,0 +1,62 @@
+use super::*;
+use std::ffi::{CStr, CString};
+
+/// The socket option to bind a socket to a network device by its name
+pub const SO_BINDTODEVICE: c_int = 25;
+
+/// The max length of the name of a network device, including the null
+/// terminator
+pub const IFNAMSIZ: usize = 16;
+
+impl SocketFile {
+    /// Bind the socket to the network device, so that only the packets of
+    /// the device are sent and received by the socket. An empty name removes
+    /// the binding.
+    ///
+    /// The device is looked up on the host, which fails an unknown one with
+    /// ENODEV. The binding itself is done by the host socket, whose error is
+    /// returned as is, e.g., EPERM if the host does not allow it without
+    /// CAP_NET_RAW.
+    pub fn set_bound_device(&self, name: &str) -> Result<()> {
+        if name.len() >= IFNAMSIZ {
+            return_errno!(EINVAL, "the name of the device is too long");
+        }
+        let cname = CString::new(name).map_err(|_| errno!(EINVAL, "invalid device name"))?;
+        if !name.is_empty() {
+            check_device_exists(&cname)?;
+        }
+
+        try_libc!(libc::ocall::setsockopt(
+            self.host_fd,
+            libc::SOL_SOCKET,
+            SO_BINDTODEVICE,
+            cname.as_ptr() as *const c_void,
+            name.len() as libc::socklen_t
+        ));
+        *self.bound_device.lock().unwrap() = if name.is_empty() {
+            None
+        } else {
+            Some(name.to_string())
+        };
+        Ok(())
+    }
+
+    /// Get the name of the network device that the socket is bound to
+    pub fn get_bound_device(&self) -> Option<String> {
+        self.bound_device.lock().unwrap().clone()
+    }
+}
+
+fn check_device_exists(name: &CStr) -> Result<()> {
+    try_libc!({
+        let mut retval: c_int = 0;
+        let status = occlum_ocall_if_nametoindex(&mut retval, name.as_ptr());
+        assert!(status == sgx_status_t::SGX_SUCCESS);
+        retval
+    });
+    Ok(())
+}
+
+extern "C" {
+    fn occlum_ocall_if_nametoindex(ret: *mut c_int, ifname: *const libc::c_char) -> sgx_status_t;
+}
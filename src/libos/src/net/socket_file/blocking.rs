@@ -0,0 +1,23 @@
+use super::*;
+
+impl SocketFile {
+    /// Do an operation on the host socket that may block, e.g., a receive.
+    ///
+    /// If the socket is closed while the operation is blocked, the operation
+    /// is interrupted and fails with EBADF (see `interrupt_blocked_ops`).
+    pub fn do_blocking_op<T>(&self, op: impl FnOnce() -> Result<T>) -> Result<T> {
+        if self.is_closed() {
+            return_errno!(EBADF, "the socket is closed");
+        }
+        let result = op();
+        if self.is_closed() {
+            return_errno!(EBADF, "the socket is closed");
+        }
+        result
+    }
+
+    /// Whether the last file descriptor of the socket is closed
+    pub fn is_closed(&self) -> bool {
+        self.fd_count.is_closed()
+    }
+}
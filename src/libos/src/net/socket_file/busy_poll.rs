@@ -0,0 +1,68 @@
+use super::*;
+use std::sync::atomic::{spin_loop_hint, Ordering};
+use std::time::Duration;
+use time::ClockID;
+
+/// The socket option to busy poll before a blocking receive, in microseconds
+pub const SO_BUSY_POLL: c_int = 46;
+
+/// The upper bound of the busy poll time, so that a socket cannot monopolize
+/// a vCPU for long by busy polling
+const MAX_BUSY_POLL_USECS: u32 = 100_000;
+
+impl SocketFile {
+    pub fn set_busy_poll(&self, usecs: c_int) -> Result<()> {
+        if usecs < 0 || usecs as u32 > MAX_BUSY_POLL_USECS {
+            return_errno!(EINVAL, "the busy poll time is out of range");
+        }
+        self.busy_poll_usecs.store(usecs as u32, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn get_busy_poll(&self) -> c_int {
+        self.busy_poll_usecs.load(Ordering::Relaxed) as c_int
+    }
+
+    /// Busy poll the socket until it is ready to receive, or the busy poll time
+    /// set by SO_BUSY_POLL elapses. Then the blocking receive is done as usual.
+    /// The busy polling fails with EINTR if a signal interrupts the thread.
+    ///
+    /// There is no busy polling if SO_BUSY_POLL is not set or the receive is
+    /// non-blocking. A receive of the out-of-band data never blocks, either.
+    /// Nor does a looped back socket, whose receive is woken inside the
+    /// enclave.
+    pub fn busy_poll_before_recv(&self, msg_flags: c_int) -> Result<()> {
+        let busy_poll_usecs = self.busy_poll_usecs.load(Ordering::Relaxed);
+        if busy_poll_usecs == 0 || (msg_flags & (libc::MSG_DONTWAIT | libc::MSG_OOB)) != 0 {
+            return Ok(());
+        }
+        if self.is_loopback() || self.udp_inbox().is_some() {
+            return Ok(());
+        }
+        if self.get_status_flags()?.contains(StatusFlags::O_NONBLOCK) {
+            return Ok(());
+        }
+
+        let deadline = time::do_clock_gettime(ClockID::CLOCK_MONOTONIC)?.as_duration()
+            + Duration::from_micros(busy_poll_usecs as u64);
+        loop {
+            let mut pollfd = libc::pollfd {
+                fd: self.host_fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            try_libc!(libc::ocall::poll(&mut pollfd, 1, 0));
+            // The errors and hang-ups are reported by the receive
+            if pollfd.revents != 0 {
+                return Ok(());
+            }
+            if time::do_clock_gettime(ClockID::CLOCK_MONOTONIC)?.as_duration() >= deadline {
+                return Ok(());
+            }
+            if process::is_sig_pending() {
+                return_errno!(EINTR, "the busy poll is interrupted by a signal");
+            }
+            spin_loop_hint();
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use super::*;
+
+/// The levels of the socket options of IPv4 and IPv6
+const SOL_IP: c_int = 0;
+const SOL_IPV6: c_int = 41;
+
+const IP_TOS: c_int = 1;
+const IP_TTL: c_int = 2;
+const IP_RECVTTL: c_int = 12;
+const IPV6_UNICAST_HOPS: c_int = 16;
+const IPV6_TCLASS: c_int = 67;
+
+/// The socket options of the IP headers, which are checked and kept by the
+/// LibOS, and applied to the host socket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IpOption {
+    /// The type of service of the packets sent, e.g., the DSCP value
+    Tos,
+    /// The time to live of the packets sent
+    Ttl,
+    /// Whether to receive the TTL of each datagram as an IP_TTL control
+    /// message. The host socket delivers the control messages to recvmsg.
+    RecvTtl,
+    /// The traffic class of the IPv6 packets sent
+    Ipv6TrafficClass,
+    /// The hop limit of the unicast IPv6 packets sent
+    Ipv6UnicastHops,
+}
+
+impl IpOption {
+    pub fn from_sockopt(level: c_int, optname: c_int) -> Option<IpOption> {
+        match (level, optname) {
+            (SOL_IP, IP_TOS) => Some(IpOption::Tos),
+            (SOL_IP, IP_TTL) => Some(IpOption::Ttl),
+            (SOL_IP, IP_RECVTTL) => Some(IpOption::RecvTtl),
+            (SOL_IPV6, IPV6_TCLASS) => Some(IpOption::Ipv6TrafficClass),
+            (SOL_IPV6, IPV6_UNICAST_HOPS) => Some(IpOption::Ipv6UnicastHops),
+            _ => None,
+        }
+    }
+
+    fn to_sockopt(&self) -> (c_int, c_int) {
+        match self {
+            IpOption::Tos => (SOL_IP, IP_TOS),
+            IpOption::Ttl => (SOL_IP, IP_TTL),
+            IpOption::RecvTtl => (SOL_IP, IP_RECVTTL),
+            IpOption::Ipv6TrafficClass => (SOL_IPV6, IPV6_TCLASS),
+            IpOption::Ipv6UnicastHops => (SOL_IPV6, IPV6_UNICAST_HOPS),
+        }
+    }
+
+    /// Whether the value may be given as a byte, instead of an int, as Linux
+    /// allows for the options of IPv4
+    pub fn accepts_byte(&self) -> bool {
+        self.to_sockopt().0 == SOL_IP
+    }
+
+    /// Check the range of the value, where -1 resets the TTL, the traffic
+    /// class or the hop limit to the default of the host
+    fn check_value(&self, val: c_int) -> Result<()> {
+        let is_valid = match self {
+            IpOption::Tos => val >= 0 && val <= 255,
+            IpOption::Ttl => val == -1 || (val >= 1 && val <= 255),
+            IpOption::RecvTtl => true,
+            IpOption::Ipv6TrafficClass | IpOption::Ipv6UnicastHops => val >= -1 && val <= 255,
+        };
+        if !is_valid {
+            return_errno!(EINVAL, "the value of the IP option is out of range");
+        }
+        Ok(())
+    }
+}
+
+impl SocketFile {
+    /// Set the IP option of the host socket, and keep the value that is in
+    /// effect, as the host may adjust it, e.g., the ECN bits of the TOS of TCP
+    /// are kept by the host.
+    pub fn set_ip_option(&self, opt: IpOption, val: c_int) -> Result<()> {
+        opt.check_value(val)?;
+        let (level, optname) = opt.to_sockopt();
+        try_libc!(libc::ocall::setsockopt(
+            self.host_fd,
+            level,
+            optname,
+            &val as *const c_int as *const c_void,
+            std::mem::size_of::<c_int>() as libc::socklen_t
+        ));
+        let val_in_effect = self.get_host_ip_option(opt)?;
+        self.ip_options.lock().unwrap().insert(opt, val_in_effect);
+        Ok(())
+    }
+
+    /// Get the value of the IP option kept, or the one of the host socket if
+    /// it is never set, e.g., the default TTL or the TOS inherited from the
+    /// listening socket
+    pub fn get_ip_option(&self, opt: IpOption) -> Result<c_int> {
+        if let Some(&val) = self.ip_options.lock().unwrap().get(&opt) {
+            return Ok(val);
+        }
+        self.get_host_ip_option(opt)
+    }
+
+    fn get_host_ip_option(&self, opt: IpOption) -> Result<c_int> {
+        let (level, optname) = opt.to_sockopt();
+        let mut val: c_int = 0;
+        let mut val_len = std::mem::size_of::<c_int>() as libc::socklen_t;
+        try_libc!(libc::ocall::getsockopt(
+            self.host_fd,
+            level,
+            optname,
+            &mut val as *mut c_int as *mut c_void,
+            &mut val_len
+        ));
+        if val_len as usize != std::mem::size_of::<c_int>() {
+            return_errno!(EINVAL, "unexpected length of the IP option from the host");
+        }
+        opt.check_value(val)?;
+        Ok(val)
+    }
+}
@@ -0,0 +1,643 @@
+//! The in-enclave loopback of TCP connections.
+//!
+//! A TCP connection to 127.0.0.1 or ::1 from a socket of the LibOS to a
+//! listening socket of the LibOS is set up by the host sockets as usual, so
+//! that the ports, the names of the sockets and the backlog of the listening
+//! socket are kept by the host. But the data of the connection never reaches
+//! the host sockets: it is transferred between two ring buffers inside the
+//! enclave, without OCalls.
+//!
+//! The client makes the pair of the ends of the connection before connecting,
+//! and keeps the end of the server as pending, by the names of the client and
+//! the server. The server takes the pending end once the host socket accepts
+//! the connection, whose peer name is the name of the client. The connections
+//! from the host, or to the addresses other than 127.0.0.1 and ::1, have no
+//! pending ends and go through the host sockets.
+//!
+//! A blocking send or receive sleeps on the waiter queue of the connection,
+//! which is woken by the sends, the receives and the shutdowns of both ends.
+//!
+//! The UDP sockets are looped back in `loopback_udp`.
+
+use super::*;
+use std::sync::atomic::Ordering;
+use util::ring_buf::{RingBuf, RingBufReader, RingBufWriter};
+use util::waiter::{self, WaiterQueue};
+
+/// The size of the buffer of each direction of a loopback connection, and of
+/// the datagrams queued to a looped back UDP socket
+pub(super) const LOOPBACK_BUF_SIZE: usize = 256 * 1024;
+
+/// The poll event of a socket whose peer is shut down for writing
+const POLLRDHUP: i16 = 0x2000;
+
+/// The option not in the libc of the enclave
+pub(super) const SO_REUSEPORT: c_int = 15;
+
+const SOCKADDR_IN_LEN: usize = 16;
+const SOCKADDR_IN6_LEN: usize = 28;
+/// The size of sockaddr_storage, which is enough for any name
+const SOCKADDR_STORAGE_LEN: usize = 128;
+
+lazy_static! {
+    /// The listening sockets of the LibOS, which may accept the loopback
+    /// connections
+    static ref LISTENERS: SgxMutex<Vec<Listener>> = SgxMutex::new(Vec::new());
+    /// The ends of the servers of the loopback connections that are not
+    /// accepted yet
+    static ref PENDING_CONNS: SgxMutex<HashMap<ConnKey, LoopbackStream>> =
+        SgxMutex::new(HashMap::new());
+}
+
+/// A connection is known by the names of the client and the server
+type ConnKey = (InetAddr, InetAddr);
+
+struct Listener {
+    host_fd: c_int,
+    addr: InetAddr,
+}
+
+/// The name of an IPv4 or IPv6 socket
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(super) struct InetAddr {
+    pub(super) is_ipv6: bool,
+    // An IPv4 address takes the first four bytes
+    pub(super) ip: [u8; 16],
+    pub(super) port: u16,
+}
+
+impl InetAddr {
+    /// Parse a sockaddr_in or sockaddr_in6, or return None for the other
+    /// families
+    pub(super) fn from_bytes(bytes: &[u8]) -> Option<InetAddr> {
+        if bytes.len() < 2 {
+            return None;
+        }
+        let mut ip = [0; 16];
+        let is_ipv6 = match u16::from_ne_bytes([bytes[0], bytes[1]]) as c_int {
+            libc::AF_INET if bytes.len() >= SOCKADDR_IN_LEN => {
+                ip[..4].copy_from_slice(&bytes[4..8]);
+                false
+            }
+            libc::AF_INET6 if bytes.len() >= SOCKADDR_IN6_LEN => {
+                ip.copy_from_slice(&bytes[8..24]);
+                true
+            }
+            _ => return None,
+        };
+        let port = u16::from_be_bytes([bytes[2], bytes[3]]);
+        Some(InetAddr { is_ipv6, ip, port })
+    }
+
+    pub(super) fn to_bytes(&self) -> Vec<u8> {
+        let (family, len) = if self.is_ipv6 {
+            (libc::AF_INET6, SOCKADDR_IN6_LEN)
+        } else {
+            (libc::AF_INET, SOCKADDR_IN_LEN)
+        };
+        let mut bytes = vec![0; len];
+        bytes[..2].copy_from_slice(&(family as u16).to_ne_bytes());
+        bytes[2..4].copy_from_slice(&self.port.to_be_bytes());
+        if self.is_ipv6 {
+            bytes[8..24].copy_from_slice(&self.ip);
+        } else {
+            bytes[4..8].copy_from_slice(&self.ip[..4]);
+        }
+        bytes
+    }
+
+    /// Whether the IP is 127.0.0.1 or ::1
+    pub(super) fn is_loopback(&self) -> bool {
+        let mut loopback_ip = [0; 16];
+        if self.is_ipv6 {
+            loopback_ip[15] = 1;
+        } else {
+            loopback_ip[..4].copy_from_slice(&[127, 0, 0, 1]);
+        }
+        self.ip == loopback_ip
+    }
+
+    /// Whether the IP is 0.0.0.0 or ::
+    pub(super) fn is_unspecified(&self) -> bool {
+        self.ip == [0; 16]
+    }
+
+    /// Whether a connection to the address is accepted by a socket listening
+    /// on the listen address
+    pub(super) fn is_accepted_by(&self, listen_addr: &InetAddr) -> bool {
+        self.is_ipv6 == listen_addr.is_ipv6
+            && self.port == listen_addr.port
+            && (listen_addr.is_unspecified() || self.ip == listen_addr.ip)
+    }
+}
+
+/// An end of a loopback connection
+pub struct LoopbackStream {
+    reader: SgxMutex<RingBufReader>,
+    writer: SgxMutex<RingBufWriter>,
+    // Whether the end is shut down for writing, which is the end of file to the
+    // peer
+    write_shut: Arc<AtomicBool>,
+    peer_write_shut: Arc<AtomicBool>,
+    // The waiters for the readiness of both ends, which are woken by the
+    // sends, the receives and the shutdowns
+    waiters: Arc<WaiterQueue>,
+}
+
+unsafe impl Send for LoopbackStream {}
+unsafe impl Sync for LoopbackStream {}
+
+impl LoopbackStream {
+    fn new_pair() -> (LoopbackStream, LoopbackStream) {
+        let buf1 = RingBuf::new(LOOPBACK_BUF_SIZE);
+        let buf2 = RingBuf::new(LOOPBACK_BUF_SIZE);
+        let write_shut1 = Arc::new(AtomicBool::new(false));
+        let write_shut2 = Arc::new(AtomicBool::new(false));
+        let waiters = Arc::new(WaiterQueue::new());
+        let stream1 = LoopbackStream {
+            reader: SgxMutex::new(buf1.reader),
+            writer: SgxMutex::new(buf2.writer),
+            write_shut: write_shut1.clone(),
+            peer_write_shut: write_shut2.clone(),
+            waiters: waiters.clone(),
+        };
+        let stream2 = LoopbackStream {
+            reader: SgxMutex::new(buf2.reader),
+            writer: SgxMutex::new(buf1.writer),
+            write_shut: write_shut2,
+            peer_write_shut: write_shut1,
+            waiters,
+        };
+        (stream1, stream2)
+    }
+
+    /// Read into the buffers, skipping the first `offset` bytes of them
+    fn read(&self, bufs: &mut [&mut [u8]], offset: usize, peeks: bool) -> Result<usize> {
+        let reader = self.reader.lock().unwrap();
+        if peeks {
+            // The data peeked is not consumed, so it is peeked all at once,
+            // which is never more than the data in the buffer
+            let total_len: usize = bufs.iter().map(|buf| buf.len()).sum();
+            let mut data = vec![0; min(total_len - offset, reader.bytes_to_read())];
+            let len = reader.peek(&mut data)?;
+            copy_to_bufs(bufs, offset, &data[..len]);
+            return Ok(len);
+        }
+
+        let mut buf_start = 0;
+        let mut read_len = 0;
+        for buf in bufs.iter_mut() {
+            let buf_end = buf_start + buf.len();
+            if buf_end > offset {
+                let buf = &mut buf[offset.saturating_sub(buf_start)..];
+                let len = reader.read(buf)?;
+                read_len += len;
+                if len < buf.len() {
+                    break;
+                }
+            }
+            buf_start = buf_end;
+        }
+        if read_len > 0 {
+            // The peer may be waiting for the room
+            self.waiters.dequeue_and_wake_all();
+        }
+        Ok(read_len)
+    }
+
+    /// Write the buffers, skipping the first `offset` bytes of them
+    fn write(&self, bufs: &[&[u8]], offset: usize) -> Result<usize> {
+        let writer = self.writer.lock().unwrap();
+        let mut buf_start = 0;
+        let mut written_len = 0;
+        for buf in bufs.iter() {
+            let buf_end = buf_start + buf.len();
+            if buf_end > offset {
+                let buf = &buf[offset.saturating_sub(buf_start)..];
+                let len = writer.write(buf)?;
+                written_len += len;
+                if len < buf.len() {
+                    break;
+                }
+            }
+            buf_start = buf_end;
+        }
+        if written_len > 0 {
+            self.waiters.dequeue_and_wake_all();
+        }
+        Ok(written_len)
+    }
+
+    fn can_read(&self) -> bool {
+        self.reader.lock().unwrap().can_read()
+    }
+
+    fn can_write(&self) -> bool {
+        self.writer.lock().unwrap().can_write()
+    }
+
+    /// Whether the peer is closed, after which a write fails with EPIPE
+    fn is_peer_closed(&self) -> bool {
+        self.writer.lock().unwrap().is_peer_closed()
+    }
+
+    fn shut_write(&self) {
+        self.write_shut.store(true, Ordering::SeqCst);
+        self.waiters.dequeue_and_wake_all();
+    }
+
+    fn is_write_shut(&self) -> bool {
+        self.write_shut.load(Ordering::SeqCst)
+    }
+
+    fn is_peer_write_shut(&self) -> bool {
+        self.peer_write_shut.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for LoopbackStream {
+    fn drop(&mut self) {
+        // So the peer reads the end of file, or fails to write with EPIPE,
+        // before it is woken
+        self.reader.lock().unwrap().close();
+        self.shut_write();
+    }
+}
+
+impl Debug for LoopbackStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("LoopbackStream")
+            .field("write_shut", &self.is_write_shut())
+            .field("peer_write_shut", &self.is_peer_write_shut())
+            .finish()
+    }
+}
+
+pub(super) fn copy_to_bufs(bufs: &mut [&mut [u8]], offset: usize, mut data: &[u8]) {
+    let mut buf_start = 0;
+    for buf in bufs.iter_mut() {
+        let buf_end = buf_start + buf.len();
+        if buf_end > offset {
+            let buf = &mut buf[offset.saturating_sub(buf_start)..];
+            let len = min(buf.len(), data.len());
+            buf[..len].copy_from_slice(&data[..len]);
+            data = &data[len..];
+        }
+        buf_start = buf_end;
+    }
+}
+
+fn check_loopback_flags(flags: MsgFlags) -> Result<()> {
+    let supported_flags = MsgFlags::MSG_PEEK
+        | MsgFlags::MSG_DONTROUTE
+        | MsgFlags::MSG_DONTWAIT
+        | MsgFlags::MSG_EOR
+        | MsgFlags::MSG_WAITALL
+        | MsgFlags::MSG_NOSIGNAL
+        | MsgFlags::MSG_MORE
+        | MsgFlags::MSG_CMSG_CLOEXEC;
+    if !supported_flags.contains(flags) {
+        return_errno!(EOPNOTSUPP, "the flags are not supported by the loopback");
+    }
+    Ok(())
+}
+
+impl SocketFile {
+    /// Listen on the host socket. A TCP socket that listens on the loopback
+    /// or the unspecified address accepts the loopback connections.
+    ///
+    /// The socket with SO_REUSEPORT does not, as the connections to its port
+    /// may be taken by a socket of the host.
+    pub fn listen(&self, backlog: c_int) -> Result<()> {
+        try_libc!(libc::ocall::listen(self.host_fd, backlog));
+
+        if get_host_sockopt(self.host_fd, libc::SO_TYPE)? != libc::SOCK_STREAM
+            || get_host_sockopt(self.host_fd, SO_REUSEPORT)? != 0
+        {
+            return Ok(());
+        }
+        let addr = match get_host_name(self.host_fd, libc::ocall::getsockname)? {
+            Some(addr) if addr.is_loopback() || addr.is_unspecified() => addr,
+            _ => return Ok(()),
+        };
+        let mut listeners = LISTENERS.lock().unwrap();
+        if !listeners
+            .iter()
+            .any(|listener| listener.host_fd == self.host_fd)
+        {
+            listeners.push(Listener {
+                host_fd: self.host_fd,
+                addr,
+            });
+        }
+        Ok(())
+    }
+
+    /// Connect the host socket. A connection to a listening socket of the
+    /// LibOS at 127.0.0.1 or ::1 is looped back, and so are the datagrams of
+    /// a UDP socket (see `loopback_udp`).
+    ///
+    /// If the socket is not bound, it is bound to the IP of the connection
+    /// first, as the host would do in the connect, so that the name of it is
+    /// known before the connect.
+    pub fn connect(&self, addr: &[u8]) -> Result<()> {
+        let loopback = self.prepare_loopback(addr)?;
+        let result = self.do_blocking_op(|| {
+            try_libc!(libc::ocall::connect(
+                self.host_fd,
+                addr.as_ptr() as *const libc::sockaddr,
+                addr.len() as libc::socklen_t
+            ));
+            Ok(())
+        });
+        if let Some((key, client_end)) = loopback {
+            match &result {
+                Err(e) if e.errno() != EINPROGRESS => {
+                    PENDING_CONNS.lock().unwrap().remove(&key);
+                }
+                _ => {
+                    *self.loopback.lock().unwrap() = Some(Arc::new(client_end));
+                }
+            }
+        }
+        result?;
+        self.connect_udp_loopback(addr)
+    }
+
+    fn prepare_loopback(&self, addr: &[u8]) -> Result<Option<(ConnKey, LoopbackStream)>> {
+        if self.is_loopback() {
+            return Ok(None);
+        }
+        let server = match InetAddr::from_bytes(addr) {
+            Some(addr) if addr.is_loopback() => addr,
+            _ => return Ok(None),
+        };
+        if !LISTENERS
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|listener| server.is_accepted_by(&listener.addr))
+        {
+            return Ok(None);
+        }
+        if get_host_sockopt(self.host_fd, libc::SO_TYPE)? != libc::SOCK_STREAM {
+            return Ok(None);
+        }
+
+        let mut client = match get_host_name(self.host_fd, libc::ocall::getsockname)? {
+            Some(addr) if addr.is_ipv6 == server.is_ipv6 => addr,
+            _ => return Ok(None),
+        };
+        if client.port == 0 {
+            let bind_addr = InetAddr { port: 0, ..server }.to_bytes();
+            try_libc!(libc::ocall::bind(
+                self.host_fd,
+                bind_addr.as_ptr() as *const libc::sockaddr,
+                bind_addr.len() as libc::socklen_t
+            ));
+            client = match get_host_name(self.host_fd, libc::ocall::getsockname)? {
+                Some(addr) => addr,
+                None => return Ok(None),
+            };
+        }
+        if client.is_unspecified() {
+            client.ip = server.ip;
+        } else if !client.is_loopback() {
+            return Ok(None);
+        }
+
+        let (client_end, server_end) = LoopbackStream::new_pair();
+        let key = (client, server);
+        // An end left by a connection that is never accepted is replaced
+        PENDING_CONNS.lock().unwrap().insert(key, server_end);
+        Ok(Some((key, client_end)))
+    }
+
+    /// Take the end of the loopback connection that the host socket accepts
+    /// from the listening socket, if the client is in the LibOS
+    pub fn accept_loopback(&self, listener: &SocketFile) {
+        if !LISTENERS
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|entry| entry.host_fd == listener.host_fd)
+        {
+            return;
+        }
+        let key = match (
+            get_host_name(self.host_fd, libc::ocall::getpeername),
+            get_host_name(self.host_fd, libc::ocall::getsockname),
+        ) {
+            (Ok(Some(client)), Ok(Some(server))) => (client, server),
+            _ => return,
+        };
+        if let Some(server_end) = PENDING_CONNS.lock().unwrap().remove(&key) {
+            *self.loopback.lock().unwrap() = Some(Arc::new(server_end));
+        }
+    }
+
+    /// Stop accepting the loopback connections when the listening socket is
+    /// closed. The pending ends of the connections to it are dropped, so the
+    /// clients read the end of file.
+    pub fn unregister_loopback_listener(&self) {
+        let mut listeners = LISTENERS.lock().unwrap();
+        let idx = match listeners
+            .iter()
+            .position(|listener| listener.host_fd == self.host_fd)
+        {
+            Some(idx) => idx,
+            None => return,
+        };
+        let listener = listeners.remove(idx);
+        PENDING_CONNS
+            .lock()
+            .unwrap()
+            .retain(|(_, server), _| !server.is_accepted_by(&listener.addr));
+    }
+
+    /// The end of the loopback connection, if the socket is looped back
+    pub fn loopback(&self) -> Option<Arc<LoopbackStream>> {
+        self.loopback.lock().unwrap().clone()
+    }
+
+    pub fn is_loopback(&self) -> bool {
+        self.loopback.lock().unwrap().is_some()
+    }
+
+    /// Receive from the loopback connection. A blocking receive waits until
+    /// some data is received, or all of the buffers are filled for
+    /// MSG_WAITALL, or the end of file.
+    pub fn recv_loopback(
+        &self,
+        stream: &LoopbackStream,
+        bufs: &mut [&mut [u8]],
+        flags: MsgFlags,
+    ) -> Result<usize> {
+        check_loopback_flags(flags)?;
+        let nonblocking = flags.contains(MsgFlags::MSG_DONTWAIT) || self.is_nonblocking();
+        let peeks = flags.contains(MsgFlags::MSG_PEEK);
+        let waits_all = flags.contains(MsgFlags::MSG_WAITALL) && !peeks;
+        let total_len: usize = bufs.iter().map(|buf| buf.len()).sum();
+
+        let mut recvd_len = 0;
+        let mut try_recv = || {
+            // The end of file is checked before the read, so that the data
+            // before it is never left behind
+            let is_eof = self.is_recv_shut() || stream.is_peer_write_shut();
+            if !self.is_recv_shut() {
+                recvd_len += stream.read(bufs, recvd_len, peeks)?;
+            }
+            if recvd_len == total_len || (recvd_len > 0 && !waits_all) || is_eof {
+                return Ok(Some(recvd_len));
+            }
+            if nonblocking {
+                if recvd_len == 0 {
+                    return_errno!(EAGAIN, "no data in the loopback connection");
+                }
+                return Ok(Some(recvd_len));
+            }
+            Ok(None)
+        };
+        match self.do_blocking_op(|| self.wait_loopback(stream, &mut try_recv)) {
+            Err(ref e) if e.errno() == EINTR && recvd_len > 0 => Ok(recvd_len),
+            ret => ret,
+        }
+    }
+
+    /// Send to the loopback connection. A blocking send waits until all of
+    /// the data is sent, or until it is interrupted by a signal, after which
+    /// the data sent so far is returned. SIGPIPE is raised by the callers.
+    pub fn send_loopback(
+        &self,
+        stream: &LoopbackStream,
+        bufs: &[&[u8]],
+        flags: MsgFlags,
+    ) -> Result<usize> {
+        check_loopback_flags(flags)?;
+        self.check_send_shut()?;
+        let nonblocking = flags.contains(MsgFlags::MSG_DONTWAIT) || self.is_nonblocking();
+        let total_len: usize = bufs.iter().map(|buf| buf.len()).sum();
+
+        let mut sent_len = 0;
+        let mut try_send = || {
+            match stream.write(bufs, sent_len) {
+                Ok(len) => sent_len += len,
+                Err(_) if sent_len > 0 => return Ok(Some(sent_len)),
+                Err(e) => return Err(e),
+            }
+            if sent_len == total_len {
+                return Ok(Some(sent_len));
+            }
+            if nonblocking {
+                if sent_len == 0 {
+                    return_errno!(EAGAIN, "the loopback connection is full");
+                }
+                return Ok(Some(sent_len));
+            }
+            Ok(None)
+        };
+        match self.do_blocking_op(|| self.wait_loopback(stream, &mut try_send)) {
+            Err(ref e) if e.errno() == EINTR && sent_len > 0 => Ok(sent_len),
+            ret => ret,
+        }
+    }
+
+    /// Shut down the loopback connection for writing, if any
+    pub fn shut_loopback_write(&self) {
+        if let Some(stream) = self.loopback() {
+            stream.shut_write();
+        }
+    }
+
+    /// Get the poll events of the loopback connection that are ready, or None
+    /// if the socket is not looped back
+    pub fn poll_loopback(&self) -> Option<i16> {
+        let stream = match self.loopback() {
+            Some(stream) => stream,
+            None => return self.poll_udp_loopback(),
+        };
+        let is_read_shut = self.is_recv_shut() || stream.is_peer_write_shut();
+        let is_write_shut = stream.is_write_shut() || stream.is_peer_closed();
+        let mut revents = 0;
+        if stream.can_read() || is_read_shut {
+            revents |= libc::POLLIN;
+        }
+        if is_read_shut {
+            revents |= POLLRDHUP;
+        }
+        if stream.can_write() || is_write_shut {
+            revents |= libc::POLLOUT;
+        }
+        if is_read_shut && is_write_shut {
+            revents |= libc::POLLHUP;
+        }
+        Some(revents)
+    }
+
+    /// Try the send or receive until it gives Some, sleeping on the waiter
+    /// queue of the connection between the tries. Fail with EBADF if the
+    /// socket is closed meanwhile, or with EINTR if a signal interrupts the
+    /// wait.
+    fn wait_loopback<F>(&self, stream: &LoopbackStream, try_op: &mut F) -> Result<usize>
+    where
+        F: FnMut() -> Result<Option<usize>>,
+    {
+        // Most sends and receives never wait, so no waiter is made for them
+        if let Some(len) = try_op()? {
+            return Ok(len);
+        }
+        waiter::wait_until(&[&*stream.waiters], None, || {
+            if self.is_closed() {
+                return_errno!(EBADF, "the socket is closed");
+            }
+            try_op()
+        })
+    }
+
+    /// Get the waiters that are woken when the readiness of the loopback of
+    /// the socket changes, or None if the socket is not looped back
+    pub fn loopback_waiters(&self) -> Option<Arc<WaiterQueue>> {
+        if let Some(stream) = self.loopback() {
+            return Some(stream.waiters.clone());
+        }
+        self.udp_inbox().map(|inbox| inbox.waiters().clone())
+    }
+
+    /// Wake the operations blocked on the loopback of the socket, so that
+    /// they see the socket is closed
+    pub(super) fn wake_loopback(&self) {
+        if let Some(waiters) = self.loopback_waiters() {
+            waiters.dequeue_and_wake_all();
+        }
+    }
+}
+
+pub(super) fn get_host_sockopt(host_fd: c_int, optname: c_int) -> Result<c_int> {
+    let mut optval: c_int = 0;
+    let mut optlen = std::mem::size_of::<c_int>() as libc::socklen_t;
+    try_libc!(libc::ocall::getsockopt(
+        host_fd,
+        libc::SOL_SOCKET,
+        optname,
+        &mut optval as *mut c_int as *mut c_void,
+        &mut optlen
+    ));
+    Ok(optval)
+}
+
+/// Get the name of the host socket or of its peer, by getsockname or
+/// getpeername. Return None if it is not an IPv4 or IPv6 socket.
+pub(super) fn get_host_name(
+    host_fd: c_int,
+    get_name: unsafe fn(c_int, *mut libc::sockaddr, *mut libc::socklen_t) -> c_int,
+) -> Result<Option<InetAddr>> {
+    let mut bytes = [0_u8; SOCKADDR_STORAGE_LEN];
+    let mut len = bytes.len() as libc::socklen_t;
+    try_libc!(get_name(
+        host_fd,
+        bytes.as_mut_ptr() as *mut libc::sockaddr,
+        &mut len
+    ));
+    let len = min(len as usize, bytes.len());
+    Ok(InetAddr::from_bytes(&bytes[..len]))
+}
@@ -0,0 +1,390 @@
+//! The in-enclave loopback of UDP datagrams.
+//!
+//! A UDP socket of the LibOS that is bound to 127.0.0.1, ::1 or the
+//! unspecified address is registered by its name, which is kept by the host
+//! socket as usual. A datagram from a socket of the LibOS to a registered
+//! socket at 127.0.0.1 or ::1 is queued to the inbox of the receiver inside
+//! the enclave, without OCalls. The other datagrams go through the host
+//! sockets, so a receive takes the datagrams from the inbox and from the host
+//! socket, whichever comes first.
+//!
+//! The sender is bound by the host before its first datagram is looped back,
+//! so that the receiver can reply to its name. As on the host, a datagram is
+//! dropped if the inbox of the receiver is full, or if the receiver is
+//! connected to another socket.
+
+use super::loopback::{
+    copy_to_bufs, get_host_name, get_host_sockopt, InetAddr, LOOPBACK_BUF_SIZE, SO_REUSEPORT,
+};
+use super::*;
+use util::waiter::{Waiter, WaiterQueue};
+
+/// The max size of the data of a UDP datagram
+const MAX_DATAGRAM_SIZE: usize = 65507;
+
+lazy_static! {
+    /// The UDP sockets of the LibOS that receive the looped back datagrams
+    static ref UDP_SOCKETS: SgxMutex<Vec<UdpSocketEntry>> = SgxMutex::new(Vec::new());
+}
+
+struct UdpSocketEntry {
+    host_fd: c_int,
+    inbox: Arc<DatagramInbox>,
+}
+
+/// A datagram looped back to a UDP socket
+#[derive(Debug)]
+pub struct Datagram {
+    src: InetAddr,
+    data: Vec<u8>,
+}
+
+impl Datagram {
+    /// Copy the data into the buffers, returning the length of the data
+    /// copied, or the real length with MSG_TRUNC in `flags`, and the flags
+    /// received, which have MSG_TRUNC if the data is truncated
+    pub fn copy_data_to(&self, bufs: &mut [&mut [u8]], flags: MsgFlags) -> (usize, MsgFlags) {
+        let total_len: usize = bufs.iter().map(|buf| buf.len()).sum();
+        let len = min(total_len, self.data.len());
+        copy_to_bufs(bufs, 0, &self.data[..len]);
+        if len == self.data.len() {
+            return (len, MsgFlags::default());
+        }
+        let len = if flags.contains(MsgFlags::MSG_TRUNC) {
+            self.data.len()
+        } else {
+            len
+        };
+        (len, MsgFlags::MSG_TRUNC)
+    }
+
+    /// Copy the name of the sender into the buffer, which is truncated if the
+    /// buffer is too small, returning the full length of the name
+    pub fn copy_name_to(&self, name: Option<&mut [u8]>) -> usize {
+        let src = self.src.to_bytes();
+        if let Some(name) = name {
+            let len = min(name.len(), src.len());
+            name[..len].copy_from_slice(&src[..len]);
+        }
+        src.len()
+    }
+}
+
+/// The datagrams looped back to a UDP socket that are not received yet
+#[derive(Debug)]
+pub struct DatagramInbox {
+    // The name of the socket, which is kept by the host socket
+    addr: InetAddr,
+    // The peer that the socket is connected to, if any, from which the
+    // datagrams are received only
+    peer: SgxMutex<Option<InetAddr>>,
+    datagrams: SgxMutex<Datagrams>,
+    // The waiters for the datagrams, which are woken by the sends
+    waiters: Arc<WaiterQueue>,
+}
+
+#[derive(Debug, Default)]
+struct Datagrams {
+    queue: VecDeque<Datagram>,
+    // The total length of the data of the datagrams
+    len: usize,
+}
+
+impl DatagramInbox {
+    fn new(addr: InetAddr) -> DatagramInbox {
+        DatagramInbox {
+            addr,
+            peer: SgxMutex::new(None),
+            datagrams: SgxMutex::new(Datagrams::default()),
+            waiters: Arc::new(WaiterQueue::new()),
+        }
+    }
+
+    /// Queue the datagram, unless the inbox is full, or the socket is
+    /// connected to another peer
+    fn push(&self, datagram: Datagram) {
+        if let Some(peer) = *self.peer.lock().unwrap() {
+            if peer != datagram.src {
+                return;
+            }
+        }
+        let mut datagrams = self.datagrams.lock().unwrap();
+        if datagrams.len + datagram.data.len() > LOOPBACK_BUF_SIZE {
+            return;
+        }
+        datagrams.len += datagram.data.len();
+        datagrams.queue.push_back(datagram);
+        drop(datagrams);
+        self.waiters.dequeue_and_wake_all();
+    }
+
+    /// Take the first datagram in the inbox, or copy it if the datagram is
+    /// only peeked
+    fn pop(&self, peeks: bool) -> Option<Datagram> {
+        let mut datagrams = self.datagrams.lock().unwrap();
+        if peeks {
+            return datagrams.queue.front().map(|datagram| Datagram {
+                src: datagram.src,
+                data: datagram.data.clone(),
+            });
+        }
+        let datagram = datagrams.queue.pop_front()?;
+        datagrams.len -= datagram.data.len();
+        Some(datagram)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.datagrams.lock().unwrap().queue.is_empty()
+    }
+
+    pub fn waiters(&self) -> &Arc<WaiterQueue> {
+        &self.waiters
+    }
+}
+
+/// What a receive of a UDP socket with an inbox gets
+pub enum UdpRecvd<T> {
+    // A datagram from the inbox
+    Looped(Datagram),
+    // What is received from the host socket
+    Host(T),
+}
+
+impl SocketFile {
+    /// The inbox of the looped back datagrams, if the socket is a registered
+    /// UDP socket
+    pub fn udp_inbox(&self) -> Option<Arc<DatagramInbox>> {
+        self.udp_inbox.lock().unwrap().clone()
+    }
+
+    /// Register the UDP socket to receive the looped back datagrams, if it
+    /// is bound to 127.0.0.1, ::1 or the unspecified address, and get its
+    /// inbox.
+    ///
+    /// The socket with SO_REUSEPORT is not registered, as the datagrams to
+    /// its port may be taken by a socket of the host.
+    pub fn register_udp_loopback(&self) -> Result<Option<Arc<DatagramInbox>>> {
+        if !self.is_udp {
+            return Ok(None);
+        }
+        let mut udp_inbox = self.udp_inbox.lock().unwrap();
+        if let Some(inbox) = udp_inbox.as_ref() {
+            return Ok(Some(inbox.clone()));
+        }
+        let addr = match get_host_name(self.host_fd, libc::ocall::getsockname)? {
+            Some(addr) if addr.port != 0 && (addr.is_loopback() || addr.is_unspecified()) => addr,
+            _ => return Ok(None),
+        };
+        if get_host_sockopt(self.host_fd, SO_REUSEPORT)? != 0 {
+            return Ok(None);
+        }
+        let inbox = Arc::new(DatagramInbox::new(addr));
+        UDP_SOCKETS.lock().unwrap().push(UdpSocketEntry {
+            host_fd: self.host_fd,
+            inbox: inbox.clone(),
+        });
+        *udp_inbox = Some(inbox.clone());
+        Ok(Some(inbox))
+    }
+
+    /// Stop receiving the looped back datagrams when the socket is closed
+    pub fn unregister_udp_loopback(&self) {
+        if self.udp_inbox.lock().unwrap().is_none() {
+            return;
+        }
+        UDP_SOCKETS
+            .lock()
+            .unwrap()
+            .retain(|entry| entry.host_fd != self.host_fd);
+    }
+
+    /// Register the UDP socket, which is bound by the host in the connect if
+    /// it is not, and receive from the peer only, or from all if the socket
+    /// is disconnected by AF_UNSPEC
+    pub(super) fn connect_udp_loopback(&self, addr: &[u8]) -> Result<()> {
+        if let Some(inbox) = self.register_udp_loopback()? {
+            *inbox.peer.lock().unwrap() = InetAddr::from_bytes(addr);
+        }
+        Ok(())
+    }
+
+    /// Send the datagram to a UDP socket of the LibOS at 127.0.0.1 or ::1
+    /// inside the enclave, to the name or to the connected peer if the name
+    /// is None. Return None if the datagram is not looped back, which is to
+    /// be sent by the host socket.
+    pub fn send_udp_loopback(&self, bufs: &[&[u8]], name: Option<&[u8]>) -> Result<Option<usize>> {
+        if !self.is_udp {
+            return Ok(None);
+        }
+        let dest = match name {
+            Some(name) => InetAddr::from_bytes(name),
+            None => self
+                .udp_inbox()
+                .and_then(|inbox| *inbox.peer.lock().unwrap()),
+        };
+        let dest = match dest {
+            Some(dest) if dest.is_loopback() => dest,
+            _ => return Ok(None),
+        };
+        let receiver = match UDP_SOCKETS
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|entry| dest.is_accepted_by(&entry.inbox.addr))
+        {
+            Some(entry) => entry.inbox.clone(),
+            None => return Ok(None),
+        };
+        self.check_send_shut()?;
+        let len: usize = bufs.iter().map(|buf| buf.len()).sum();
+        if len > MAX_DATAGRAM_SIZE {
+            return_errno!(EMSGSIZE, "the datagram is too long");
+        }
+
+        let sender = match self.register_udp_loopback()? {
+            Some(sender) => sender,
+            None => {
+                // The socket that is bound to another address or has
+                // SO_REUSEPORT is left to the host
+                match get_host_name(self.host_fd, libc::ocall::getsockname)? {
+                    Some(addr) if addr.port == 0 => {}
+                    _ => return Ok(None),
+                }
+                // Bound to the unspecified address, as the host would do in
+                // the send
+                let bind_addr = InetAddr {
+                    ip: [0; 16],
+                    port: 0,
+                    ..dest
+                }
+                .to_bytes();
+                try_libc!(libc::ocall::bind(
+                    self.host_fd,
+                    bind_addr.as_ptr() as *const libc::sockaddr,
+                    bind_addr.len() as libc::socklen_t
+                ));
+                match self.register_udp_loopback()? {
+                    Some(sender) => sender,
+                    None => return Ok(None),
+                }
+            }
+        };
+        let mut src = sender.addr;
+        if src.is_unspecified() {
+            src.ip = dest.ip;
+        }
+        let mut data = Vec::with_capacity(len);
+        for buf in bufs {
+            data.extend_from_slice(buf);
+        }
+        receiver.push(Datagram { src, data });
+        Ok(Some(len))
+    }
+
+    /// Receive a datagram from the inbox, or from the host socket by
+    /// `recv_host` with the flags given to it, whichever comes first. A
+    /// blocking receive waits for both of them.
+    pub fn recv_udp_loopback<T, F>(
+        &self,
+        inbox: &DatagramInbox,
+        flags: MsgFlags,
+        mut recv_host: F,
+    ) -> Result<UdpRecvd<T>>
+    where
+        F: FnMut(MsgFlags) -> Result<T>,
+    {
+        let nonblocking = flags.contains(MsgFlags::MSG_DONTWAIT) || self.is_nonblocking();
+        let peeks = flags.contains(MsgFlags::MSG_PEEK);
+        let host_flags = flags | MsgFlags::MSG_DONTWAIT;
+        let mut try_recv = || match inbox.pop(peeks) {
+            Some(datagram) => Ok(UdpRecvd::Looped(datagram)),
+            None => recv_host(host_flags).map(UdpRecvd::Host),
+        };
+        self.do_blocking_op(|| {
+            // Most receives never wait, so no waiter is made for them
+            match try_recv() {
+                Err(e) if e.errno() == EAGAIN && !nonblocking => {}
+                ret => return ret,
+            }
+            let waiter = Waiter::new()?;
+            loop {
+                waiter.reset();
+                inbox.waiters.enqueue(&waiter);
+                let ret = match try_recv() {
+                    Err(e) if e.errno() == EAGAIN && !self.is_closed() => {
+                        let mut host_pollfds = vec![libc::pollfd {
+                            fd: self.host_fd,
+                            events: libc::POLLIN,
+                            revents: 0,
+                        }];
+                        waiter
+                            .wait_with_host_fds(&mut host_pollfds, None)
+                            .map(|_| None)
+                    }
+                    ret => ret.map(Some),
+                };
+                inbox.waiters.dequeue(&waiter);
+                if let Some(recvd) = ret? {
+                    return Ok(recvd);
+                }
+            }
+        })
+    }
+
+    /// Whether there are looped back datagrams to receive
+    pub fn has_udp_datagrams(&self) -> bool {
+        self.udp_inbox().map_or(false, |inbox| !inbox.is_empty())
+    }
+
+    /// Get the poll events of the UDP socket with an inbox that are ready,
+    /// which are those of the host socket and POLLIN for the datagrams in the
+    /// inbox, or None if the socket has no inbox
+    pub(super) fn poll_udp_loopback(&self) -> Option<i16> {
+        let inbox = self.udp_inbox()?;
+        let mut pollfd = libc::pollfd {
+            fd: self.host_fd,
+            events: libc::POLLIN | libc::POLLOUT,
+            revents: 0,
+        };
+        let mut revents = match unsafe { libc::ocall::poll(&mut pollfd, 1, 0) } {
+            ret if ret < 0 => libc::POLLERR,
+            _ => pollfd.revents,
+        };
+        if !inbox.is_empty() {
+            revents |= libc::POLLIN;
+        }
+        Some(revents)
+    }
+}
+
+/// Receive from the host socket into the buffers, as a UDP socket with an
+/// inbox does in a read or readv. A datagram read into several buffers is
+/// received into a bounce buffer first, so that it is never split.
+pub(super) fn recv_host_into_bufs(
+    host_fd: c_int,
+    bufs: &mut [&mut [u8]],
+    flags: MsgFlags,
+) -> Result<usize> {
+    let total_len: usize = bufs.iter().map(|buf| buf.len()).sum();
+    let mut bounce_buf = Vec::new();
+    let buf = match &mut *bufs {
+        [buf] => &mut buf[..],
+        _ => {
+            bounce_buf.resize(min(total_len, MAX_DATAGRAM_SIZE), 0);
+            &mut bounce_buf[..]
+        }
+    };
+    let len = try_libc!(libc::ocall::recvfrom(
+        host_fd,
+        buf.as_mut_ptr() as *mut c_void,
+        buf.len(),
+        flags.to_u32() as c_int,
+        std::ptr::null_mut(),
+        std::ptr::null_mut()
+    )) as usize;
+    if !bounce_buf.is_empty() {
+        copy_to_bufs(bufs, 0, &bounce_buf[..min(len, bounce_buf.len())]);
+    }
+    Ok(len)
+}
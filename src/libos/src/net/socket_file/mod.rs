@@ -1,22 +1,73 @@
 use super::*;
 
+mod bind_device;
+mod blocking;
+mod busy_poll;
+mod ip_options;
+mod loopback;
+mod loopback_udp;
+mod pending_error;
 mod recv;
 mod send;
+mod shutdown;
 
-use fs::{AccessMode, CreationFlags, File, FileRef, IoctlCmd, StatusFlags};
+pub use self::bind_device::{IFNAMSIZ, SO_BINDTODEVICE};
+pub use self::busy_poll::SO_BUSY_POLL;
+pub use self::ip_options::IpOption;
+pub use self::loopback_udp::UdpRecvd;
+pub use self::send::raise_sigpipe_on_epipe;
+pub use self::shutdown::HowToShut;
+
+use self::loopback::LoopbackStream;
+use self::loopback_udp::{recv_host_into_bufs, DatagramInbox};
+
+use fs::{AccessMode, CreationFlags, FdCount, File, FileRef, IoctlCmd, StatusFlags};
 use std::any::Any;
 use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
 /// Native Linux socket
 #[derive(Debug)]
 pub struct SocketFile {
     host_fd: c_int,
+    // The name of the network device that the socket is bound to, which is set
+    // by SO_BINDTODEVICE
+    bound_device: SgxMutex<Option<String>>,
+    // The time in microseconds to busy poll before a blocking receive, which
+    // is set by SO_BUSY_POLL
+    busy_poll_usecs: AtomicU32,
+    // The values of the IP options that are set, e.g., IP_TOS and IP_TTL
+    ip_options: SgxMutex<HashMap<IpOption, c_int>>,
+    // The file descriptors of the socket, which tell the blocked operations
+    // whether the socket is closed
+    fd_count: FdCount,
+    // Whether the socket is shut down for receiving and sending
+    recv_shut: AtomicBool,
+    send_shut: AtomicBool,
+    // Whether the socket is nonblocking, which is kept so that the loopback
+    // connection never asks the host
+    nonblocking: AtomicBool,
+    // The end of the in-enclave loopback connection, if the socket is
+    // connected to another socket of the LibOS at 127.0.0.1 or ::1
+    loopback: SgxMutex<Option<Arc<LoopbackStream>>>,
+    // Whether the socket is a UDP socket, whose datagrams may be looped back
+    is_udp: bool,
+    // The inbox of the looped back datagrams, if the UDP socket is bound to
+    // 127.0.0.1, ::1 or the unspecified address
+    udp_inbox: SgxMutex<Option<Arc<DatagramInbox>>>,
 }
 
 impl SocketFile {
     pub fn new(domain: c_int, socket_type: c_int, protocol: c_int) -> Result<Self> {
         let ret = try_libc!(libc::ocall::socket(domain, socket_type, protocol));
-        Ok(SocketFile { host_fd: ret })
+        let is_udp = (domain == libc::AF_INET || domain == libc::AF_INET6)
+            && socket_type & SOCK_TYPE_MASK == libc::SOCK_DGRAM
+            && (protocol == 0 || protocol == libc::IPPROTO_UDP);
+        Ok(SocketFile::from_host_fd(
+            ret,
+            socket_type & libc::SOCK_NONBLOCK != 0,
+            is_udp,
+        ))
     }
 
     pub fn accept(
@@ -25,17 +76,70 @@ impl SocketFile {
         addr_len: *mut libc::socklen_t,
         flags: c_int,
     ) -> Result<Self> {
-        let ret = try_libc!(libc::ocall::accept4(self.host_fd, addr, addr_len, flags));
-        Ok(SocketFile { host_fd: ret })
+        let ret = self.do_blocking_op(|| {
+            Ok(try_libc!(libc::ocall::accept4(
+                self.host_fd,
+                addr,
+                addr_len,
+                flags
+            )))
+        })?;
+        let new_socket = SocketFile::from_host_fd(ret, flags & libc::SOCK_NONBLOCK != 0, false);
+        new_socket.accept_loopback(self);
+        Ok(new_socket)
+    }
+
+    fn from_host_fd(host_fd: c_int, nonblocking: bool, is_udp: bool) -> Self {
+        SocketFile {
+            host_fd,
+            bound_device: SgxMutex::new(None),
+            busy_poll_usecs: AtomicU32::new(0),
+            ip_options: SgxMutex::new(HashMap::new()),
+            fd_count: FdCount::new(),
+            recv_shut: AtomicBool::new(false),
+            send_shut: AtomicBool::new(false),
+            nonblocking: AtomicBool::new(nonblocking),
+            loopback: SgxMutex::new(None),
+            is_udp,
+            udp_inbox: SgxMutex::new(None),
+        }
     }
 
     pub fn fd(&self) -> c_int {
         self.host_fd
     }
+
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::Relaxed)
+    }
+
+    pub fn is_udp(&self) -> bool {
+        self.is_udp
+    }
+
+    /// Read a datagram into the buffers, from the inbox of the UDP socket or
+    /// from the host socket
+    fn readv_udp(&self, bufs: &mut [&mut [u8]]) -> Result<usize> {
+        let inbox = self.udp_inbox().unwrap();
+        let host_fd = self.host_fd;
+        let recvd = self.recv_udp_loopback(&inbox, MsgFlags::empty(), |flags| {
+            recv_host_into_bufs(host_fd, bufs, flags)
+        })?;
+        Ok(match recvd {
+            UdpRecvd::Looped(datagram) => datagram.copy_data_to(bufs, MsgFlags::empty()).0,
+            UdpRecvd::Host(len) => len,
+        })
+    }
 }
 
+/// The mask of the type of a socket, without SOCK_NONBLOCK and SOCK_CLOEXEC
+const SOCK_TYPE_MASK: c_int = 0xf;
+
 impl Drop for SocketFile {
     fn drop(&mut self) {
+        // Before the host fd is closed and reused
+        self.unregister_loopback_listener();
+        self.unregister_udp_loopback();
         let ret = unsafe { libc::ocall::close(self.host_fd) };
         assert!(ret == 0);
     }
@@ -45,21 +149,29 @@ impl Drop for SocketFile {
 // TODO: implement readfrom/sendto
 impl File for SocketFile {
     fn read(&self, buf: &mut [u8]) -> Result<usize> {
-        let ret = try_libc!(libc::ocall::read(
-            self.host_fd,
-            buf.as_mut_ptr() as *mut c_void,
-            buf.len()
-        ));
+        if self.is_recv_shut() {
+            return Ok(0);
+        }
+        if let Some(stream) = self.loopback() {
+            return self.recv_loopback(&stream, &mut [buf], MsgFlags::empty());
+        }
+        if self.udp_inbox().is_some() {
+            return self.readv_udp(&mut [buf]);
+        }
+        let ret = self.do_blocking_op(|| {
+            Ok(try_libc!(libc::ocall::read(
+                self.host_fd,
+                buf.as_mut_ptr() as *mut c_void,
+                buf.len()
+            )))
+        })?;
         Ok(ret as usize)
     }
 
+    /// Write to the socket, which raises SIGPIPE on EPIPE as a send without
+    /// MSG_NOSIGNAL does
     fn write(&self, buf: &[u8]) -> Result<usize> {
-        let ret = try_libc!(libc::ocall::write(
-            self.host_fd,
-            buf.as_ptr() as *const c_void,
-            buf.len()
-        ));
-        Ok(ret as usize)
+        raise_sigpipe_on_epipe(self.do_write(buf), MsgFlags::empty())
     }
 
     fn read_at(&self, _offset: usize, buf: &mut [u8]) -> Result<usize> {
@@ -71,6 +183,12 @@ impl File for SocketFile {
     }
 
     fn readv(&self, bufs: &mut [&mut [u8]]) -> Result<usize> {
+        if let Some(stream) = self.loopback() {
+            return self.recv_loopback(&stream, bufs, MsgFlags::empty());
+        }
+        if self.udp_inbox().is_some() {
+            return self.readv_udp(bufs);
+        }
         let mut total_len = 0;
         for buf in bufs {
             match self.read(buf) {
@@ -85,14 +203,21 @@ impl File for SocketFile {
     }
 
     fn writev(&self, bufs: &[&[u8]]) -> Result<usize> {
+        if let Some(stream) = self.loopback() {
+            let ret = self.send_loopback(&stream, bufs, MsgFlags::empty());
+            return raise_sigpipe_on_epipe(ret, MsgFlags::empty());
+        }
+        if let Some(len) = self.send_udp_loopback(bufs, None)? {
+            return Ok(len);
+        }
         let mut total_len = 0;
         for buf in bufs {
-            match self.write(buf) {
+            match self.do_write(buf) {
                 Ok(len) => {
                     total_len += len;
                 }
                 Err(_) if total_len != 0 => break,
-                Err(e) => return Err(e.into()),
+                Err(e) => return raise_sigpipe_on_epipe(Err(e), MsgFlags::empty()),
             }
         }
         Ok(total_len)
@@ -132,9 +257,32 @@ impl File for SocketFile {
             libc::F_SETFL,
             raw_status_flags as c_int
         ));
+        self.nonblocking.store(
+            new_status_flags.contains(StatusFlags::O_NONBLOCK),
+            Ordering::Relaxed,
+        );
         Ok(())
     }
 
+    fn busy_poll(&self) -> Result<()> {
+        self.busy_poll_before_recv(0)
+    }
+
+    fn fd_count(&self) -> Option<&FdCount> {
+        Some(&self.fd_count)
+    }
+
+    /// Shut down the host socket to wake up the blocked operations, which is
+    /// closed once the last reference is dropped. The operations blocked on
+    /// the loopback are woken by their waiter queues.
+    fn interrupt_blocked_ops(&self) {
+        // The socket may be not connected, which is fine
+        unsafe {
+            libc::ocall::shutdown(self.host_fd, libc::SHUT_RDWR);
+        }
+        self.wake_loopback();
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -0,0 +1,22 @@
+use super::*;
+
+impl SocketFile {
+    /// Take the pending error of the socket, which is cleared then, as
+    /// SO_ERROR does. Return 0 if there is no pending error.
+    ///
+    /// The errors that happen asynchronously, e.g., a failed connect in the
+    /// background or a reset by the peer, are recorded by the host socket,
+    /// which also reports EPOLLERR for them.
+    pub fn take_pending_error(&self) -> Result<c_int> {
+        let mut error: c_int = 0;
+        let mut error_len = std::mem::size_of::<c_int>() as libc::socklen_t;
+        try_libc!(libc::ocall::getsockopt(
+            self.host_fd,
+            libc::SOL_SOCKET,
+            libc::SO_ERROR,
+            &mut error as *mut c_int as *mut c_void,
+            &mut error_len
+        ));
+        Ok(error)
+    }
+}
@@ -20,11 +20,57 @@ impl SocketFile {
     }*/
 
     pub fn recvmsg<'a, 'b>(&self, msg: &'b mut MsgHdrMut<'a>, flags: MsgFlags) -> Result<usize> {
-        // Alloc untrusted iovecs to receive data via OCall
+        if let Some(stream) = self.loopback() {
+            let bytes_recvd =
+                self.recv_loopback(&stream, msg.get_iovs_mut().as_slices_mut(), flags)?;
+            // No names or control messages are received by a connected TCP
+            // socket
+            msg.set_name_len(0)?;
+            msg.set_control_len(0)?;
+            msg.set_flags(MsgFlags::default());
+            return Ok(bytes_recvd);
+        }
+        if let Some(inbox) = self.udp_inbox() {
+            return self.recvmsg_udp(&inbox, msg, flags);
+        }
+        self.do_blocking_op(|| self.recvmsg_host(msg, flags))
+    }
+
+    /// Receive a datagram from the inbox of the UDP socket or from the host
+    /// socket
+    fn recvmsg_udp(
+        &self,
+        inbox: &DatagramInbox,
+        msg: &mut MsgHdrMut,
+        flags: MsgFlags,
+    ) -> Result<usize> {
+        let datagram =
+            match self.recv_udp_loopback(inbox, flags, |flags| self.recvmsg_host(msg, flags))? {
+                UdpRecvd::Looped(datagram) => datagram,
+                UdpRecvd::Host(bytes_recvd) => return Ok(bytes_recvd),
+            };
+        let name_len = datagram.copy_name_to(msg.get_name_mut());
+        let name_len = min(name_len, msg.get_name_max_len());
+        msg.set_name_len(name_len)?;
+        msg.set_control_len(0)?;
+        let (bytes_recvd, flags_recvd) =
+            datagram.copy_data_to(msg.get_iovs_mut().as_slices_mut(), flags);
+        msg.set_flags(flags_recvd);
+        Ok(bytes_recvd)
+    }
+
+    /// Receive from the host socket, which may block
+    fn recvmsg_host(&self, msg: &mut MsgHdrMut, flags: MsgFlags) -> Result<usize> {
+        // Alloc untrusted iovecs to receive data via OCall. At most
+        // max_io_size bytes are received at once, so that the untrusted
+        // buffer is bounded, which is a short read for a stream socket, and
+        // is larger than any datagram.
         let msg_iov = msg.get_iovs();
-        let u_slice_alloc = UntrustedSliceAlloc::new(msg_iov.total_bytes())?;
-        let mut u_slices = msg_iov
-            .as_slices()
+        let max_io_size = config::LIBOS_CONFIG.fs.max_io_size;
+        let slices = split_iovs(msg_iov.as_slices(), max_io_size).swap_remove(0);
+        let total_bytes = slices.iter().map(|slice| slice.len()).sum();
+        let u_slice_alloc = UntrustedSliceAlloc::new(total_bytes)?;
+        let mut u_slices = slices
             .iter()
             .map(|slice| {
                 u_slice_alloc
@@ -66,6 +112,10 @@ impl SocketFile {
         mut name: Option<&mut [u8]>,
         mut control: Option<&mut [u8]>,
     ) -> Result<(usize, usize, usize, MsgFlags)> {
+        if self.is_recv_shut() {
+            return Ok((0, 0, 0, MsgFlags::default()));
+        }
+
         // Prepare the arguments for OCall
         // Host socket fd
         let host_fd = self.host_fd;
@@ -82,6 +132,9 @@ impl SocketFile {
         let msg_control = msg_control as *mut c_void;
         let mut msg_controllen_recvd = 0;
         // Flags
+        // With MSG_TRUNC, the real length of a datagram is returned, even if
+        // it is longer than the buffers
+        let returns_real_len = flags.contains(MsgFlags::MSG_TRUNC);
         let flags = flags.to_u32() as i32;
         let mut msg_flags_recvd = 0;
 
@@ -117,7 +170,7 @@ impl SocketFile {
 
             // Check bytes_recvd returned from outside the enclave
             let max_bytes_recvd = data.iter().map(|x| x.len()).sum();
-            assert!(retval <= max_bytes_recvd);
+            assert!(retval <= max_bytes_recvd || returns_real_len);
             retval
         };
         let msg_namelen_recvd = msg_namelen_recvd as usize;
@@ -1,3 +1,4 @@
+use super::loopback::get_host_sockopt;
 use super::*;
 use crate::untrusted::{SliceAsMutPtrAndLen, SliceAsPtrAndLen, UntrustedSliceAlloc};
 
@@ -19,11 +20,67 @@ impl SocketFile {
     */
 
     pub fn sendmsg<'a, 'b>(&self, msg: &'b MsgHdr<'a>, flags: MsgFlags) -> Result<usize> {
-        // Copy message's iovecs into untrusted iovecs
+        if let Some(stream) = self.loopback() {
+            // The name is ignored by a connected TCP socket, and no control
+            // messages are sent by TCP
+            return self.send_loopback(&stream, msg.get_iovs().as_slices(), flags);
+        }
+        // No control messages are looped back
+        if msg.get_control().is_none() {
+            let bufs = msg.get_iovs().as_slices();
+            if let Some(bytes_sent) = self.send_udp_loopback(bufs, msg.get_name())? {
+                return Ok(bytes_sent);
+            }
+        }
+
         let msg_iov = msg.get_iovs();
-        let u_slice_alloc = UntrustedSliceAlloc::new(msg_iov.total_bytes())?;
-        let u_slices = msg_iov
-            .as_slices()
+        let max_io_size = config::LIBOS_CONFIG.fs.max_io_size;
+        if msg_iov.total_bytes() <= max_io_size {
+            return self.sendmsg_host(
+                msg_iov.as_slices(),
+                flags,
+                msg.get_name(),
+                msg.get_control(),
+            );
+        }
+        // A huge message is sent in chunks, so that the untrusted buffer is
+        // bounded, which is only possible for a stream socket
+        if get_host_sockopt(self.host_fd, libc::SO_TYPE)? != libc::SOCK_STREAM {
+            return_errno!(EMSGSIZE, "the message is larger than max_io_size");
+        }
+        let mut total_len = 0;
+        for (idx, chunk) in split_iovs(msg_iov.as_slices(), max_io_size)
+            .iter()
+            .enumerate()
+        {
+            // The control messages are sent with the first chunk
+            let control = if idx == 0 { msg.get_control() } else { None };
+            let chunk_len: usize = chunk.iter().map(|slice| slice.len()).sum();
+            match self.sendmsg_host(chunk, flags, msg.get_name(), control) {
+                Ok(len) => {
+                    total_len += len;
+                    if len < chunk_len {
+                        break;
+                    }
+                }
+                Err(_) if total_len != 0 => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(total_len)
+    }
+
+    /// Copy the data into untrusted iovecs and send them to the host socket
+    fn sendmsg_host(
+        &self,
+        data: &[&[u8]],
+        flags: MsgFlags,
+        name: Option<&[u8]>,
+        control: Option<&[u8]>,
+    ) -> Result<usize> {
+        let total_bytes = data.iter().map(|slice| slice.len()).sum();
+        let u_slice_alloc = UntrustedSliceAlloc::new(total_bytes)?;
+        let u_slices = data
             .iter()
             .map(|src_slice| {
                 u_slice_alloc
@@ -33,7 +90,42 @@ impl SocketFile {
             .collect();
         let u_iovs = Iovs::new(u_slices);
 
-        self.do_sendmsg(u_iovs.as_slices(), flags, msg.get_name(), msg.get_control())
+        self.do_sendmsg(u_iovs.as_slices(), flags, name, control)
+    }
+
+    /// Send the data in an untrusted buffer, which is not copied again
+    pub fn send_untrusted(&self, u_buf: &[u8]) -> Result<usize> {
+        if let Some(stream) = self.loopback() {
+            return self.send_loopback(&stream, &[u_buf], MsgFlags::default());
+        }
+        if let Some(bytes_sent) = self.send_udp_loopback(&[u_buf], None)? {
+            return Ok(bytes_sent);
+        }
+        self.do_sendmsg(&[u_buf], MsgFlags::default(), None, None)
+    }
+
+    /// Write to the socket without raising SIGPIPE
+    pub(super) fn do_write(&self, buf: &[u8]) -> Result<usize> {
+        self.check_send_shut()?;
+        if let Some(stream) = self.loopback() {
+            return self.send_loopback(&stream, &[buf], MsgFlags::empty());
+        }
+        if let Some(len) = self.send_udp_loopback(&[buf], None)? {
+            return Ok(len);
+        }
+        // Sent with MSG_NOSIGNAL instead of written, so that the host never
+        // raises SIGPIPE (see do_sendmsg)
+        let ret = self.do_blocking_op(|| {
+            Ok(try_libc!(libc::ocall::sendto(
+                self.host_fd,
+                buf.as_ptr() as *const c_void,
+                buf.len(),
+                MsgFlags::MSG_NOSIGNAL.to_u32() as c_int,
+                std::ptr::null(),
+                0
+            )))
+        })?;
+        Ok(ret as usize)
     }
 
     fn do_sendmsg(
@@ -43,6 +135,8 @@ impl SocketFile {
         name: Option<&[u8]>,
         control: Option<&[u8]>,
     ) -> Result<usize> {
+        self.check_send_shut()?;
+
         // Prepare the arguments for OCall
         let mut retval: isize = 0;
         // Host socket fd
@@ -57,30 +151,47 @@ impl SocketFile {
         let (msg_control, msg_controllen) = control.as_ptr_and_len();
         let msg_control = msg_control as *const c_void;
         // Flags
-        let flags = flags.to_u32() as i32;
+        // The host never raises SIGPIPE for a broken stream, which would kill
+        // the whole enclave, but fails with EPIPE, on which the caller raises
+        // SIGPIPE in the LibOS (see raise_sigpipe_on_epipe)
+        let flags = (flags | MsgFlags::MSG_NOSIGNAL).to_u32() as i32;
 
-        let bytes_sent = try_libc!({
-            // Do OCall
-            let status = occlum_ocall_sendmsg(
-                &mut retval as *mut isize,
-                host_fd,
-                msg_name,
-                msg_namelen as u32,
-                msg_iov,
-                msg_iovlen,
-                msg_control,
-                msg_controllen,
-                flags,
-            );
-            assert!(status == sgx_status_t::SGX_SUCCESS);
+        let bytes_sent = self.do_blocking_op(|| {
+            Ok(try_libc!({
+                // Do OCall
+                let status = occlum_ocall_sendmsg(
+                    &mut retval as *mut isize,
+                    host_fd,
+                    msg_name,
+                    msg_namelen as u32,
+                    msg_iov,
+                    msg_iovlen,
+                    msg_control,
+                    msg_controllen,
+                    flags,
+                );
+                assert!(status == sgx_status_t::SGX_SUCCESS);
 
-            retval
-        });
+                retval
+            }))
+        })?;
         debug_assert!(bytes_sent >= 0);
         Ok(bytes_sent as usize)
     }
 }
 
+/// Raise SIGPIPE on the sender if the send fails with EPIPE, i.e., on a
+/// stream that is shut down for sending or broken, unless MSG_NOSIGNAL is
+/// given. A send cut short by the broken stream is not signaled.
+pub fn raise_sigpipe_on_epipe<T>(res: Result<T>, flags: MsgFlags) -> Result<T> {
+    if let Err(ref e) = res {
+        if e.errno() == EPIPE && !flags.contains(MsgFlags::MSG_NOSIGNAL) {
+            process::raise_signal(process::SIGPIPE);
+        }
+    }
+    res
+}
+
 extern "C" {
     fn occlum_ocall_sendmsg(
         ret: *mut ssize_t,
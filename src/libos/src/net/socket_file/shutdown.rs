@@ -0,0 +1,70 @@
+use super::*;
+use std::sync::atomic::Ordering;
+
+/// How a socket is shut down
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HowToShut {
+    Read,
+    Write,
+    Both,
+}
+
+impl HowToShut {
+    pub fn from_c_int(how: c_int) -> Result<HowToShut> {
+        match how {
+            libc::SHUT_RD => Ok(HowToShut::Read),
+            libc::SHUT_WR => Ok(HowToShut::Write),
+            libc::SHUT_RDWR => Ok(HowToShut::Both),
+            _ => return_errno!(EINVAL, "invalid how to shut down"),
+        }
+    }
+
+    fn shuts_read(&self) -> bool {
+        *self != HowToShut::Write
+    }
+
+    fn shuts_write(&self) -> bool {
+        *self != HowToShut::Read
+    }
+}
+
+impl SocketFile {
+    /// Shut down the socket for receiving, sending or both.
+    ///
+    /// The host socket is shut down, which sends a FIN to the peer for
+    /// SHUT_WR and wakes the threads blocked in receiving. The shut down
+    /// directions are also kept, so that a receive returns 0 and a send fails
+    /// with EPIPE afterwards without reaching the host socket, which would
+    /// raise SIGPIPE on the host otherwise.
+    pub fn shutdown(&self, how: HowToShut) -> Result<()> {
+        // The host fails with ENOTCONN if the socket is not connected
+        let how_c = match how {
+            HowToShut::Read => libc::SHUT_RD,
+            HowToShut::Write => libc::SHUT_WR,
+            HowToShut::Both => libc::SHUT_RDWR,
+        };
+        try_libc!(libc::ocall::shutdown(self.host_fd, how_c));
+        if how.shuts_read() {
+            self.recv_shut.store(true, Ordering::SeqCst);
+        }
+        if how.shuts_write() {
+            self.send_shut.store(true, Ordering::SeqCst);
+            self.shut_loopback_write();
+        }
+        Ok(())
+    }
+
+    /// Whether the socket is shut down for receiving, in which case a receive
+    /// returns 0, i.e., the end of file
+    pub fn is_recv_shut(&self) -> bool {
+        self.recv_shut.load(Ordering::SeqCst)
+    }
+
+    /// Fail with EPIPE if the socket is shut down for sending
+    pub fn check_send_shut(&self) -> Result<()> {
+        if self.send_shut.load(Ordering::SeqCst) {
+            return_errno!(EPIPE, "the socket is shut down for sending");
+        }
+        Ok(())
+    }
+}
@@ -2,7 +2,8 @@ use super::*;
 
 use super::io_multiplexing;
 use fs::{File, FileDesc, FileRef};
-use process::Process;
+use process::{do_rt_sigprocmask, Process, SigMaskHow, SigSet};
+use time::timespec_t;
 use util::mem_util::from_user;
 
 pub fn do_sendmsg(fd: c_int, msg_ptr: *const msghdr, flags_c: c_int) -> Result<isize> {
@@ -10,29 +11,28 @@ pub fn do_sendmsg(fd: c_int, msg_ptr: *const msghdr, flags_c: c_int) -> Result<i
         "sendmsg: fd: {}, msg: {:?}, flags: 0x{:x}",
         fd, msg_ptr, flags_c
     );
-    let current_ref = process::get_current();
-    let mut proc = current_ref.lock().unwrap();
-    let file_ref = proc.get_files().lock().unwrap().get(fd as FileDesc)?;
-
-    if let Ok(socket) = file_ref.as_socket() {
-        let msg_c = {
-            from_user::check_ptr(msg_ptr)?;
-            let msg_c = unsafe { &*msg_ptr };
-            msg_c.check_member_ptrs()?;
-            msg_c
-        };
-        let msg = unsafe { MsgHdr::from_c(&msg_c)? };
-
-        let flags = MsgFlags::from_u32(flags_c as u32)?;
-
-        socket
-            .sendmsg(&msg, flags)
-            .map(|bytes_sent| bytes_sent as isize)
-    } else if let Ok(socket) = file_ref.as_unix_socket() {
-        return_errno!(EBADF, "does not support unix socket")
-    } else {
-        return_errno!(EBADF, "not a socket")
+    // The socket may block, so the process is not kept locked
+    let file_ref = fs::get_file(fd as FileDesc)?;
+    if file_ref.as_socket().is_err() && file_ref.as_unix_socket().is_err() {
+        return_errno!(EBADF, "not a socket");
     }
+
+    let msg_c = {
+        from_user::check_ptr(msg_ptr)?;
+        let msg_c = unsafe { &*msg_ptr };
+        msg_c.check_member_ptrs()?;
+        msg_c
+    };
+    let msg = unsafe { MsgHdr::from_c(&msg_c)? };
+
+    let flags = MsgFlags::from_u32(flags_c as u32)?;
+
+    let bytes_sent = if let Ok(socket) = file_ref.as_socket() {
+        raise_sigpipe_on_epipe(socket.sendmsg(&msg, flags), flags)?
+    } else {
+        file_ref.as_unix_socket()?.sendmsg(&msg, flags)?
+    };
+    Ok(bytes_sent as isize)
 }
 
 pub fn do_recvmsg(fd: c_int, msg_mut_ptr: *mut msghdr_mut, flags_c: c_int) -> Result<isize> {
@@ -40,29 +40,29 @@ pub fn do_recvmsg(fd: c_int, msg_mut_ptr: *mut msghdr_mut, flags_c: c_int) -> Re
         "recvmsg: fd: {}, msg: {:?}, flags: 0x{:x}",
         fd, msg_mut_ptr, flags_c
     );
-    let current_ref = process::get_current();
-    let mut proc = current_ref.lock().unwrap();
-    let file_ref = proc.get_files().lock().unwrap().get(fd as FileDesc)?;
-
-    if let Ok(socket) = file_ref.as_socket() {
-        let msg_mut_c = {
-            from_user::check_mut_ptr(msg_mut_ptr)?;
-            let msg_mut_c = unsafe { &mut *msg_mut_ptr };
-            msg_mut_c.check_member_ptrs()?;
-            msg_mut_c
-        };
-        let mut msg_mut = unsafe { MsgHdrMut::from_c(msg_mut_c)? };
-
-        let flags = MsgFlags::from_u32(flags_c as u32)?;
-
-        socket
-            .recvmsg(&mut msg_mut, flags)
-            .map(|bytes_recvd| bytes_recvd as isize)
-    } else if let Ok(socket) = file_ref.as_unix_socket() {
-        return_errno!(EBADF, "does not support unix socket")
-    } else {
-        return_errno!(EBADF, "not a socket")
+    // The socket may block, so the process is not kept locked
+    let file_ref = fs::get_file(fd as FileDesc)?;
+    if file_ref.as_socket().is_err() && file_ref.as_unix_socket().is_err() {
+        return_errno!(EBADF, "not a socket");
     }
+
+    let msg_mut_c = {
+        from_user::check_mut_ptr(msg_mut_ptr)?;
+        let msg_mut_c = unsafe { &mut *msg_mut_ptr };
+        msg_mut_c.check_member_ptrs()?;
+        msg_mut_c
+    };
+    let mut msg_mut = unsafe { MsgHdrMut::from_c(msg_mut_c)? };
+
+    let flags = MsgFlags::from_u32(flags_c as u32)?;
+
+    let bytes_recvd = if let Ok(socket) = file_ref.as_socket() {
+        socket.busy_poll_before_recv(flags_c)?;
+        socket.recvmsg(&mut msg_mut, flags)?
+    } else {
+        file_ref.as_unix_socket()?.recvmsg(&mut msg_mut, flags)?
+    };
+    Ok(bytes_recvd as isize)
 }
 
 #[allow(non_camel_case_types)]
@@ -236,3 +236,47 @@ pub fn do_epoll_pwait(
     //TODO:add signal support
     do_epoll_wait(epfd, events, maxevents, 0)
 }
+
+pub fn do_epoll_pwait2(
+    epfd: c_int,
+    events: *mut libc::epoll_event,
+    maxevents: c_int,
+    timeout: *const timespec_t,
+    sigmask: *const u64,
+    sigsetsize: size_t,
+) -> Result<isize> {
+    let maxevents = {
+        if maxevents <= 0 {
+            return_errno!(EINVAL, "maxevents <= 0");
+        }
+        maxevents as usize
+    };
+    let events = {
+        from_user::check_mut_array(events, maxevents)?;
+        unsafe { std::slice::from_raw_parts_mut(events, maxevents) }
+    };
+    let timeout = if !timeout.is_null() {
+        from_user::check_ptr(timeout)?;
+        Some(timespec_t::from_raw_ptr(timeout)?)
+    } else {
+        None
+    };
+    // The signal mask is replaced while waiting, so the wait fails with EINTR
+    // by the signals that it unblocks
+    let old_sigmask = if !sigmask.is_null() {
+        if sigsetsize != std::mem::size_of::<u64>() {
+            return_errno!(EINVAL, "sigsetsize is invalid");
+        }
+        from_user::check_ptr(sigmask)?;
+        let sigmask = SigSet::from_u64(unsafe { *sigmask });
+        Some(do_rt_sigprocmask(SigMaskHow::SIG_SETMASK, Some(sigmask))?)
+    } else {
+        None
+    };
+    let ret = io_multiplexing::do_epoll_pwait2(epfd as FileDesc, events, timeout.as_ref());
+    if let Some(old_sigmask) = old_sigmask {
+        do_rt_sigprocmask(SigMaskHow::SIG_SETMASK, Some(old_sigmask))?;
+    }
+    let count = ret?;
+    Ok(count as isize)
+}
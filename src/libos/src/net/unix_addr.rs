@@ -0,0 +1,90 @@
+use super::*;
+use std::mem::size_of;
+use util::mem_util::from_user;
+
+const SA_FAMILY_LEN: usize = size_of::<libc::sa_family_t>();
+
+/// The address of a unix socket
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UnixAddr {
+    /// The address of a socket that is not bound, e.g., the sockets created by
+    /// socketpair
+    Unnamed,
+    /// A path in the file system
+    Pathname(String),
+    /// A name in the abstract namespace, which is the bytes after the leading
+    /// NUL of `sun_path`. The name may contain NULs.
+    Abstract(Vec<u8>),
+}
+
+impl UnixAddr {
+    /// Parse the address from the user's `sockaddr_un` of `addr_len` bytes
+    pub fn from_user(addr: *const libc::sockaddr, addr_len: libc::socklen_t) -> Result<UnixAddr> {
+        let addr_len = addr_len as usize;
+        if addr_len < SA_FAMILY_LEN || addr_len > size_of::<libc::sockaddr_un>() {
+            return_errno!(EINVAL, "invalid length of the unix socket address");
+        }
+        from_user::check_array(addr as *const u8, addr_len)?;
+        let addr = unsafe { &*(addr as *const libc::sockaddr_un) };
+        if addr.sun_family != libc::AF_UNIX as libc::sa_family_t {
+            return_errno!(EINVAL, "not a unix socket address");
+        }
+
+        let sun_path = unsafe {
+            std::slice::from_raw_parts(
+                addr.sun_path.as_ptr() as *const u8,
+                addr_len - SA_FAMILY_LEN,
+            )
+        };
+        Ok(match sun_path.first() {
+            None => UnixAddr::Unnamed,
+            Some(0) => UnixAddr::Abstract(sun_path[1..].to_vec()),
+            Some(_) => {
+                // The path may or may not be terminated by NUL within addr_len
+                let path_len = sun_path
+                    .iter()
+                    .position(|&b| b == 0)
+                    .unwrap_or(sun_path.len());
+                let path = std::str::from_utf8(&sun_path[..path_len])
+                    .map_err(|_| errno!(EINVAL, "the path is not valid UTF-8"))?;
+                UnixAddr::Pathname(path.to_string())
+            }
+        })
+    }
+
+    /// Copy the address to the user's buffer of `*addr_len` bytes, which may
+    /// be truncated. `*addr_len` is set to the full length of the address.
+    pub fn copy_to_user(
+        &self,
+        addr: *mut libc::sockaddr,
+        addr_len: *mut libc::socklen_t,
+    ) -> Result<()> {
+        from_user::check_mut_ptr(addr_len)?;
+        let addr_len = unsafe { &mut *addr_len };
+        if (*addr_len as i32) < 0 {
+            return_errno!(EINVAL, "the length of the address buffer is negative");
+        }
+
+        let mut full_addr = (libc::AF_UNIX as libc::sa_family_t).to_ne_bytes().to_vec();
+        match self {
+            UnixAddr::Unnamed => {}
+            UnixAddr::Pathname(path) => {
+                full_addr.extend_from_slice(path.as_bytes());
+                full_addr.push(0);
+            }
+            UnixAddr::Abstract(name) => {
+                full_addr.push(0);
+                full_addr.extend_from_slice(name);
+            }
+        }
+
+        let copy_len = full_addr.len().min(*addr_len as usize);
+        if copy_len > 0 {
+            from_user::check_mut_array(addr as *mut u8, copy_len)?;
+            let buf = unsafe { std::slice::from_raw_parts_mut(addr as *mut u8, copy_len) };
+            buf.copy_from_slice(&full_addr[..copy_len]);
+        }
+        *addr_len = full_addr.len() as libc::socklen_t;
+        Ok(())
+    }
+}
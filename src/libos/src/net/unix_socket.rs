@@ -1,5 +1,5 @@
 use super::*;
-use fs::{File, FileRef, IoctlCmd};
+use fs::{FdCount, File, FileRef, IoctlCmd};
 use rcore_fs::vfs::{FileType, Metadata, Timespec};
 use std::any::Any;
 use std::collections::btree_map::BTreeMap;
@@ -10,6 +10,9 @@ use util::ring_buf::{RingBuf, RingBufReader, RingBufWriter};
 
 pub struct UnixSocketFile {
     inner: Mutex<UnixSocket>,
+    // The file descriptors of the socket, which tell the blocked accepts
+    // whether the socket is closed
+    fd_count: FdCount,
 }
 
 impl File for UnixSocketFile {
@@ -85,25 +88,32 @@ impl File for UnixSocketFile {
         inner.ioctl(cmd)
     }
 
+    fn fd_count(&self) -> Option<&FdCount> {
+        Some(&self.fd_count)
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
 }
 
 static SOCKETPAIR_NUM: AtomicUsize = AtomicUsize::new(0);
-const SOCK_PATH_PREFIX: &str = "socketpair_";
+// The listening sockets that create socket pairs are bound to abstract names,
+// so no file is left in the file system
+const SOCK_NAME_PREFIX: &str = "occlum_socketpair_";
 
 impl UnixSocketFile {
     pub fn new(socket_type: c_int, protocol: c_int) -> Result<Self> {
         let inner = UnixSocket::new(socket_type, protocol)?;
         Ok(UnixSocketFile {
             inner: Mutex::new(inner),
+            fd_count: FdCount::new(),
         })
     }
 
-    pub fn bind(&self, path: impl AsRef<str>) -> Result<()> {
+    pub fn bind(&self, addr: &UnixAddr) -> Result<()> {
         let mut inner = self.inner.lock().unwrap();
-        inner.bind(path)
+        inner.bind(addr)
     }
 
     pub fn listen(&self) -> Result<()> {
@@ -111,17 +121,38 @@ impl UnixSocketFile {
         inner.listen()
     }
 
+    /// Accept a connection, which fails with EBADF if the socket is closed
+    /// while waiting for one
     pub fn accept(&self) -> Result<UnixSocketFile> {
-        let mut inner = self.inner.lock().unwrap();
-        let new_socket = inner.accept()?;
+        // FIXME: Block. Now spin loop.
+        let new_socket = loop {
+            if let Some(socket) = self.inner.lock().unwrap().try_accept()? {
+                break socket;
+            }
+            if self.fd_count.is_closed() {
+                return_errno!(EBADF, "the socket is closed");
+            }
+            spin_loop_hint();
+        };
         Ok(UnixSocketFile {
             inner: Mutex::new(new_socket),
+            fd_count: FdCount::new(),
         })
     }
 
-    pub fn connect(&self, path: impl AsRef<str>) -> Result<()> {
+    pub fn connect(&self, addr: &UnixAddr) -> Result<()> {
         let mut inner = self.inner.lock().unwrap();
-        inner.connect(path)
+        inner.connect(addr)
+    }
+
+    pub fn addr(&self) -> UnixAddr {
+        let inner = self.inner.lock().unwrap();
+        inner.addr.clone()
+    }
+
+    pub fn peer_addr(&self) -> Result<UnixAddr> {
+        let inner = self.inner.lock().unwrap();
+        inner.peer_addr()
     }
 
     pub fn poll(&self) -> Result<(bool, bool, bool)> {
@@ -129,29 +160,71 @@ impl UnixSocketFile {
         inner.poll()
     }
 
+    /// Send the data of the message, gathered from all of its iovecs, as
+    /// writev does
+    pub fn sendmsg(&self, msg: &MsgHdr, flags: MsgFlags) -> Result<usize> {
+        check_msg_flags(flags)?;
+        // The name is ignored by a connected stream socket
+        let control_len = msg.get_control().map_or(0, |control| control.len());
+        if control_len > 0 {
+            return_errno!(EOPNOTSUPP, "control messages are not supported");
+        }
+        self.writev(msg.get_iovs().as_slices())
+    }
+
+    /// Receive the data into all of the iovecs of the message, as readv does
+    pub fn recvmsg(&self, msg: &mut MsgHdrMut, flags: MsgFlags) -> Result<usize> {
+        check_msg_flags(flags)?;
+        let bytes_recvd = self.readv(msg.get_iovs_mut().as_slices_mut())?;
+        // No names or control messages are received by a connected stream
+        // socket, and the data of a stream is never truncated
+        msg.set_name_len(0)?;
+        msg.set_control_len(0)?;
+        msg.set_flags(MsgFlags::default());
+        Ok(bytes_recvd)
+    }
+
     pub fn socketpair(socket_type: i32, protocol: i32) -> Result<(Self, Self)> {
         let listen_socket = Self::new(socket_type, protocol)?;
-        let bound_path = listen_socket.bind_until_success();
+        let bound_addr = listen_socket.bind_until_success();
         listen_socket.listen()?;
 
         let client_socket = Self::new(socket_type, protocol)?;
-        client_socket.connect(&bound_path)?;
+        client_socket.connect(&bound_addr)?;
 
         let accepted_socket = listen_socket.accept()?;
+        // Both ends of a socket pair are unnamed
+        accepted_socket.inner.lock().unwrap().addr = UnixAddr::Unnamed;
+        client_socket.inner.lock().unwrap().peer_addr = Some(UnixAddr::Unnamed);
         Ok((client_socket, accepted_socket))
     }
 
-    fn bind_until_success(&self) -> String {
+    fn bind_until_success(&self) -> UnixAddr {
         loop {
-            let sock_path_suffix = SOCKETPAIR_NUM.fetch_add(1, Ordering::SeqCst);
-            let sock_path = format!("{}{}", SOCK_PATH_PREFIX, sock_path_suffix);
-            if self.bind(&sock_path).is_ok() {
-                return sock_path;
+            let sock_name_suffix = SOCKETPAIR_NUM.fetch_add(1, Ordering::SeqCst);
+            let sock_name = format!("{}{}", SOCK_NAME_PREFIX, sock_name_suffix);
+            let sock_addr = UnixAddr::Abstract(sock_name.into_bytes());
+            if self.bind(&sock_addr).is_ok() {
+                return sock_addr;
             }
         }
     }
 }
 
+// The reads and writes never block and no signals are sent, so MSG_DONTWAIT
+// and MSG_NOSIGNAL are always in effect
+fn check_msg_flags(flags: MsgFlags) -> Result<()> {
+    let supported_flags = MsgFlags::MSG_DONTWAIT
+        | MsgFlags::MSG_EOR
+        | MsgFlags::MSG_NOSIGNAL
+        | MsgFlags::MSG_MORE
+        | MsgFlags::MSG_CMSG_CLOEXEC;
+    if !supported_flags.contains(flags) {
+        return_errno!(EOPNOTSUPP, "the flags are not supported by unix socket");
+    }
+    Ok(())
+}
+
 impl Debug for UnixSocketFile {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "UnixSocketFile {{ ... }}")
@@ -173,6 +246,11 @@ impl AsUnixSocket for FileRef {
 pub struct UnixSocket {
     obj: Option<Arc<UnixSocketObject>>,
     status: Status,
+    // The local address, which is the address of the listening socket for an
+    // accepted socket
+    addr: UnixAddr,
+    // The address of the peer, which is set once connected
+    peer_addr: Option<UnixAddr>,
 }
 
 enum Status {
@@ -188,19 +266,25 @@ impl UnixSocket {
             Ok(UnixSocket {
                 obj: None,
                 status: Status::None,
+                addr: UnixAddr::Unnamed,
+                peer_addr: None,
             })
         } else {
             return_errno!(ENOSYS, "unimplemented unix socket type")
         }
     }
 
-    /// Server 2: Bind the socket to a file system path
-    pub fn bind(&mut self, path: impl AsRef<str>) -> Result<()> {
+    /// Server 2: Bind the socket to a file system path or an abstract name
+    pub fn bind(&mut self, addr: &UnixAddr) -> Result<()> {
         // TODO: check permission
         if self.obj.is_some() {
             return_errno!(EINVAL, "The socket is already bound to an address.");
         }
-        self.obj = Some(UnixSocketObject::create(path)?);
+        if let UnixAddr::Unnamed = addr {
+            return_errno!(EINVAL, "autobind is not supported");
+        }
+        self.obj = Some(UnixSocketObject::create(addr)?);
+        self.addr = addr.clone();
         Ok(())
     }
 
@@ -210,38 +294,40 @@ impl UnixSocket {
         Ok(())
     }
 
-    /// Server 4: Accept a connection on listening.
-    pub fn accept(&mut self) -> Result<UnixSocket> {
+    /// Server 4: Accept a connection on listening, if there is one
+    pub fn try_accept(&mut self) -> Result<Option<UnixSocket>> {
         match self.status {
             Status::Listening => {}
             _ => return_errno!(EINVAL, "unix socket is not listening"),
         };
-        // FIXME: Block. Now spin loop.
-        let socket = loop {
-            if let Some(socket) = self.obj.as_mut().unwrap().pop() {
-                break socket;
-            }
-            spin_loop_hint();
-        };
-        Ok(socket)
+        Ok(self.obj.as_mut().unwrap().pop())
     }
 
-    /// Client 2: Connect to a path
-    pub fn connect(&mut self, path: impl AsRef<str>) -> Result<()> {
+    /// Client 2: Connect to a path or an abstract name
+    pub fn connect(&mut self, addr: &UnixAddr) -> Result<()> {
         if let Status::Listening = self.status {
             return_errno!(EINVAL, "unix socket is listening?");
         }
-        let obj = UnixSocketObject::get(path)
+        let obj = UnixSocketObject::get(addr)
             .ok_or_else(|| errno!(EINVAL, "unix socket path not found"))?;
         let (channel1, channel2) = Channel::new_pair();
         self.status = Status::Connected(channel1);
+        self.peer_addr = Some(obj.addr.clone());
         obj.push(UnixSocket {
             obj: Some(obj.clone()),
             status: Status::Connected(channel2),
+            addr: obj.addr.clone(),
+            peer_addr: Some(self.addr.clone()),
         });
         Ok(())
     }
 
+    pub fn peer_addr(&self) -> Result<UnixAddr> {
+        self.peer_addr
+            .clone()
+            .ok_or_else(|| errno!(ENOTCONN, "unix socket is not connected"))
+    }
+
     pub fn read(&self, buf: &mut [u8]) -> Result<usize> {
         self.channel()?.reader.read(buf)
     }
@@ -284,15 +370,19 @@ impl UnixSocket {
 
 impl Drop for UnixSocket {
     fn drop(&mut self) {
-        if let Status::Listening = self.status {
-            let path = &self.obj.as_ref().unwrap().path;
-            UnixSocketObject::remove(path);
+        // The address is released by the socket bound to it, not the sockets
+        // accepted on it
+        if let Status::Connected(_) = self.status {
+            return;
+        }
+        if let Some(obj) = self.obj.as_ref() {
+            UnixSocketObject::remove(&obj.addr);
         }
     }
 }
 
 pub struct UnixSocketObject {
-    path: String,
+    addr: UnixAddr,
     accepted_sockets: Mutex<VecDeque<UnixSocket>>,
 }
 
@@ -305,25 +395,25 @@ impl UnixSocketObject {
         let mut queue = self.accepted_sockets.lock().unwrap();
         queue.pop_front()
     }
-    fn get(path: impl AsRef<str>) -> Option<Arc<Self>> {
-        let mut paths = UNIX_SOCKET_OBJS.lock().unwrap();
-        paths.get(path.as_ref()).map(|obj| obj.clone())
+    fn get(addr: &UnixAddr) -> Option<Arc<Self>> {
+        let mut addrs = UNIX_SOCKET_OBJS.lock().unwrap();
+        addrs.get(addr).map(|obj| obj.clone())
     }
-    fn create(path: impl AsRef<str>) -> Result<Arc<Self>> {
-        let mut paths = UNIX_SOCKET_OBJS.lock().unwrap();
-        if paths.contains_key(path.as_ref()) {
+    fn create(addr: &UnixAddr) -> Result<Arc<Self>> {
+        let mut addrs = UNIX_SOCKET_OBJS.lock().unwrap();
+        if addrs.contains_key(addr) {
             return_errno!(EADDRINUSE, "unix socket path already exists");
         }
         let obj = Arc::new(UnixSocketObject {
-            path: path.as_ref().to_string(),
+            addr: addr.clone(),
             accepted_sockets: Mutex::new(VecDeque::new()),
         });
-        paths.insert(path.as_ref().to_string(), obj.clone());
+        addrs.insert(addr.clone(), obj.clone());
         Ok(obj)
     }
-    fn remove(path: impl AsRef<str>) {
-        let mut paths = UNIX_SOCKET_OBJS.lock().unwrap();
-        paths.remove(path.as_ref());
+    fn remove(addr: &UnixAddr) {
+        let mut addrs = UNIX_SOCKET_OBJS.lock().unwrap();
+        addrs.remove(addr);
     }
 }
 
@@ -354,6 +444,6 @@ impl Channel {
 pub const DEFAULT_BUF_SIZE: usize = 1 * 1024 * 1024;
 
 lazy_static! {
-    static ref UNIX_SOCKET_OBJS: Mutex<BTreeMap<String, Arc<UnixSocketObject>>> =
+    static ref UNIX_SOCKET_OBJS: Mutex<BTreeMap<UnixAddr, Arc<UnixSocketObject>>> =
         Mutex::new(BTreeMap::new());
 }
@@ -0,0 +1,160 @@
+//! Linux-style capabilities.
+//!
+//! Each process has the effective, permitted and inheritable capability sets
+//! in its credentials, and a privileged operation is allowed only if the
+//! corresponding capability is in the effective set. Root has all of the
+//! capabilities by default. There are no file capabilities, ambient sets or
+//! bounding sets, i.e., the bounding set is always full.
+use super::*;
+
+bitflags! {
+    pub struct Capabilities: u64 {
+        const CHOWN             = 1 << 0;
+        const DAC_OVERRIDE      = 1 << 1;
+        const DAC_READ_SEARCH   = 1 << 2;
+        const FOWNER            = 1 << 3;
+        const FSETID            = 1 << 4;
+        const KILL              = 1 << 5;
+        const SETGID            = 1 << 6;
+        const SETUID            = 1 << 7;
+        const SETPCAP           = 1 << 8;
+        const LINUX_IMMUTABLE   = 1 << 9;
+        const NET_BIND_SERVICE  = 1 << 10;
+        const NET_BROADCAST     = 1 << 11;
+        const NET_ADMIN         = 1 << 12;
+        const NET_RAW           = 1 << 13;
+        const IPC_LOCK          = 1 << 14;
+        const IPC_OWNER         = 1 << 15;
+        const SYS_MODULE        = 1 << 16;
+        const SYS_RAWIO         = 1 << 17;
+        const SYS_CHROOT        = 1 << 18;
+        const SYS_PTRACE        = 1 << 19;
+        const SYS_PACCT         = 1 << 20;
+        const SYS_ADMIN         = 1 << 21;
+        const SYS_BOOT          = 1 << 22;
+        const SYS_NICE          = 1 << 23;
+        const SYS_RESOURCE      = 1 << 24;
+        const SYS_TIME          = 1 << 25;
+        const SYS_TTY_CONFIG    = 1 << 26;
+        const MKNOD             = 1 << 27;
+        const LEASE             = 1 << 28;
+        const AUDIT_WRITE       = 1 << 29;
+        const AUDIT_CONTROL     = 1 << 30;
+        const SETFCAP           = 1 << 31;
+        const MAC_OVERRIDE      = 1 << 32;
+        const MAC_ADMIN         = 1 << 33;
+        const SYSLOG            = 1 << 34;
+        const WAKE_ALARM        = 1 << 35;
+        const BLOCK_SUSPEND     = 1 << 36;
+        const AUDIT_READ        = 1 << 37;
+        const PERFMON           = 1 << 38;
+        const BPF               = 1 << 39;
+        const CHECKPOINT_RESTORE = 1 << 40;
+    }
+}
+
+/// The version of the 32-bit capabilities, with one data struct
+pub const LINUX_CAPABILITY_VERSION_1: u32 = 0x1998_0330;
+/// The deprecated version of the 64-bit capabilities, with two data structs
+pub const LINUX_CAPABILITY_VERSION_2: u32 = 0x2007_1026;
+/// The version of the 64-bit capabilities, with two data structs
+pub const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+#[repr(C)]
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy)]
+pub struct cap_user_header_t {
+    pub version: u32,
+    pub pid: i32,
+}
+
+/// The capability sets, 32 bits of each per struct with the lower bits first
+#[repr(C)]
+#[allow(non_camel_case_types)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct cap_user_data_t {
+    pub effective: u32,
+    pub permitted: u32,
+    pub inheritable: u32,
+}
+
+/// Get the number of the data structs of the version, or None if the version
+/// is not supported
+pub fn cap_user_data_len(version: u32) -> Option<usize> {
+    match version {
+        LINUX_CAPABILITY_VERSION_1 => Some(1),
+        LINUX_CAPABILITY_VERSION_2 | LINUX_CAPABILITY_VERSION_3 => Some(2),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CapabilitySets {
+    pub effective: Capabilities,
+    pub permitted: Capabilities,
+    pub inheritable: Capabilities,
+}
+
+impl CapabilitySets {
+    /// The capabilities of root, i.e., all except the inheritable ones
+    pub fn full() -> CapabilitySets {
+        CapabilitySets {
+            effective: Capabilities::all(),
+            permitted: Capabilities::all(),
+            inheritable: Capabilities::empty(),
+        }
+    }
+
+    /// Convert from the data structs, in which the missing and unknown bits
+    /// are taken as zeros
+    pub fn from_user(data: &[cap_user_data_t]) -> CapabilitySets {
+        let to_caps = |get_bits: fn(&cap_user_data_t) -> u32| {
+            let bits = data
+                .iter()
+                .take(2)
+                .enumerate()
+                .fold(0, |bits, (i, d)| bits | (get_bits(d) as u64) << (32 * i));
+            Capabilities::from_bits_truncate(bits)
+        };
+        CapabilitySets {
+            effective: to_caps(|d| d.effective),
+            permitted: to_caps(|d| d.permitted),
+            inheritable: to_caps(|d| d.inheritable),
+        }
+    }
+
+    pub fn to_user(&self, data: &mut [cap_user_data_t]) {
+        for (i, d) in data.iter_mut().take(2).enumerate() {
+            let shift = 32 * i;
+            d.effective = (self.effective.bits() >> shift) as u32;
+            d.permitted = (self.permitted.bits() >> shift) as u32;
+            d.inheritable = (self.inheritable.bits() >> shift) as u32;
+        }
+    }
+}
+
+/// Get the capability sets of a process, which is the current one if the pid
+/// is 0
+pub fn do_capget(pid: pid_t) -> Result<CapabilitySets> {
+    let process_ref = if pid == 0 {
+        get_current()
+    } else {
+        process_table::get(pid).map_err(|_| errno!(ESRCH, "no such process"))?
+    };
+    let process = process_ref.lock().unwrap();
+    let caps = process.get_credentials().lock().unwrap().capabilities();
+    Ok(caps)
+}
+
+/// Set the capability sets of the current process, as the ones of the other
+/// processes cannot be set since Linux 2.6.24
+pub fn do_capset(pid: pid_t, caps: &CapabilitySets) -> Result<()> {
+    info!("capset: pid: {}, caps: {:?}", pid, caps);
+    let current_ref = get_current();
+    let current = current_ref.lock().unwrap();
+    if pid != 0 && pid != current.get_tid() && pid != current.get_pid() {
+        return_errno!(EPERM, "the capabilities of other processes cannot be set");
+    }
+    let mut credentials = current.get_credentials().lock().unwrap();
+    credentials.set_capabilities(caps)
+}
@@ -0,0 +1,318 @@
+//! The user and group ids of a process, i.e., its credentials.
+//!
+//! The credentials are shared by the threads of a process, so that a change
+//! by one thread is seen by all of them, like what glibc and musl do for the
+//! set*id functions. A new process inherits a copy of them. The privilege is
+//! checked with the capabilities in the credentials, which are updated on the
+//! changes of the uids like Linux.
+use super::capabilities::{Capabilities, CapabilitySets};
+use super::*;
+
+#[allow(non_camel_case_types)]
+pub type uid_t = u32;
+#[allow(non_camel_case_types)]
+pub type gid_t = u32;
+
+pub type CredentialsRef = Arc<SgxMutex<Credentials>>;
+
+/// The max number of the supplementary groups, which is the same as Linux
+pub const NGROUPS_MAX: usize = 65536;
+
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    ruid: uid_t,
+    euid: uid_t,
+    suid: uid_t,
+    rgid: gid_t,
+    egid: gid_t,
+    sgid: gid_t,
+    groups: Vec<gid_t>,
+    caps: CapabilitySets,
+}
+
+impl Default for Credentials {
+    /// The credentials of root, with the full capabilities
+    fn default() -> Credentials {
+        Credentials {
+            ruid: 0,
+            euid: 0,
+            suid: 0,
+            rgid: 0,
+            egid: 0,
+            sgid: 0,
+            groups: Vec::new(),
+            caps: CapabilitySets::full(),
+        }
+    }
+}
+
+impl Credentials {
+    /// Get the real, effective and saved uids
+    pub fn uids(&self) -> (uid_t, uid_t, uid_t) {
+        (self.ruid, self.euid, self.suid)
+    }
+
+    /// Get the real, effective and saved gids
+    pub fn gids(&self) -> (gid_t, gid_t, gid_t) {
+        (self.rgid, self.egid, self.sgid)
+    }
+
+    pub fn ruid(&self) -> uid_t {
+        self.ruid
+    }
+
+    pub fn euid(&self) -> uid_t {
+        self.euid
+    }
+
+    pub fn rgid(&self) -> gid_t {
+        self.rgid
+    }
+
+    pub fn egid(&self) -> gid_t {
+        self.egid
+    }
+
+    pub fn groups(&self) -> &[gid_t] {
+        &self.groups
+    }
+
+    pub fn capabilities(&self) -> CapabilitySets {
+        self.caps
+    }
+
+    /// Whether the capability is in the effective set
+    pub fn has_capability(&self, cap: Capabilities) -> bool {
+        self.caps.effective.contains(cap)
+    }
+
+    /// Whether the gid is the effective gid or a supplementary group
+    pub fn is_in_group(&self, gid: gid_t) -> bool {
+        self.egid == gid || self.groups.contains(&gid)
+    }
+
+    /// Set the uids like setuid. A process with CAP_SETUID sets all of them,
+    /// while the others can only set the effective uid to the real or the
+    /// saved one.
+    pub fn set_uid(&mut self, uid: uid_t) -> Result<()> {
+        let old_uids = self.uids();
+        if self.has_capability(Capabilities::SETUID) {
+            self.ruid = uid;
+            self.suid = uid;
+        } else if uid != self.ruid && uid != self.suid {
+            return_errno!(EPERM, "the uid cannot be set without privilege");
+        }
+        self.euid = uid;
+        self.update_capabilities_for_uids(old_uids);
+        Ok(())
+    }
+
+    /// Set the gids like setgid, which is allowed like `set_uid` but with
+    /// CAP_SETGID
+    pub fn set_gid(&mut self, gid: gid_t) -> Result<()> {
+        if self.has_capability(Capabilities::SETGID) {
+            self.rgid = gid;
+            self.sgid = gid;
+        } else if gid != self.rgid && gid != self.sgid {
+            return_errno!(EPERM, "the gid cannot be set without privilege");
+        }
+        self.egid = gid;
+        Ok(())
+    }
+
+    /// Set the real, effective and saved uids, each of which is unchanged if
+    /// None. Without CAP_SETUID, they can only be set to the current ones.
+    pub fn set_resuid(
+        &mut self,
+        ruid: Option<uid_t>,
+        euid: Option<uid_t>,
+        suid: Option<uid_t>,
+    ) -> Result<()> {
+        let old_uids = self.uids();
+        let current_uids = [self.ruid, self.euid, self.suid];
+        if !self.has_capability(Capabilities::SETUID)
+            && [ruid, euid, suid]
+                .iter()
+                .any(|uid| uid.map_or(false, |uid| !current_uids.contains(&uid)))
+        {
+            return_errno!(EPERM, "the uids cannot be set without privilege");
+        }
+        self.ruid = ruid.unwrap_or(self.ruid);
+        self.euid = euid.unwrap_or(self.euid);
+        self.suid = suid.unwrap_or(self.suid);
+        self.update_capabilities_for_uids(old_uids);
+        Ok(())
+    }
+
+    /// Set the real, effective and saved gids, like `set_resuid` but with
+    /// CAP_SETGID
+    pub fn set_resgid(
+        &mut self,
+        rgid: Option<gid_t>,
+        egid: Option<gid_t>,
+        sgid: Option<gid_t>,
+    ) -> Result<()> {
+        let current_gids = [self.rgid, self.egid, self.sgid];
+        if !self.has_capability(Capabilities::SETGID)
+            && [rgid, egid, sgid]
+                .iter()
+                .any(|gid| gid.map_or(false, |gid| !current_gids.contains(&gid)))
+        {
+            return_errno!(EPERM, "the gids cannot be set without privilege");
+        }
+        self.rgid = rgid.unwrap_or(self.rgid);
+        self.egid = egid.unwrap_or(self.egid);
+        self.sgid = sgid.unwrap_or(self.sgid);
+        Ok(())
+    }
+
+    pub fn set_groups(&mut self, groups: &[gid_t]) -> Result<()> {
+        if !self.has_capability(Capabilities::SETGID) {
+            return_errno!(EPERM, "the groups cannot be set without privilege");
+        }
+        if groups.len() > NGROUPS_MAX {
+            return_errno!(EINVAL, "too many groups");
+        }
+        self.groups = groups.to_vec();
+        Ok(())
+    }
+
+    /// Set the capability sets like capset. The permitted set cannot be
+    /// raised, the effective set must be in the permitted one, and the
+    /// inheritable set can only be raised with the permitted capabilities
+    /// unless with CAP_SETPCAP.
+    pub fn set_capabilities(&mut self, caps: &CapabilitySets) -> Result<()> {
+        if !self.caps.permitted.contains(caps.permitted) {
+            return_errno!(EPERM, "the permitted capabilities cannot be raised");
+        }
+        if !caps.permitted.contains(caps.effective) {
+            return_errno!(EPERM, "the effective capabilities are not permitted");
+        }
+        if !self.has_capability(Capabilities::SETPCAP)
+            && !(self.caps.inheritable | self.caps.permitted).contains(caps.inheritable)
+        {
+            return_errno!(EPERM, "the inheritable capabilities cannot be raised");
+        }
+        self.caps = *caps;
+        Ok(())
+    }
+
+    /// Update the credentials for a new executable at spawn, i.e., set the
+    /// effective and saved uid to the owner of a set-user-ID executable, or
+    /// the gids to the group of a set-group-ID one, and then recompute the
+    /// capabilities.
+    ///
+    /// As there are no file capabilities, the new permitted set is full if the
+    /// real or effective uid is root, or empty otherwise, and the effective
+    /// set is the permitted one for the effective uid of root only, like
+    /// Linux. The inheritable set is unchanged.
+    pub fn update_for_exec(&mut self, uid: Option<uid_t>, gid: Option<gid_t>) {
+        if let Some(uid) = uid {
+            self.euid = uid;
+            self.suid = uid;
+        }
+        if let Some(gid) = gid {
+            self.egid = gid;
+            self.sgid = gid;
+        }
+
+        self.caps.permitted = if self.ruid == 0 || self.euid == 0 {
+            Capabilities::all()
+        } else {
+            Capabilities::empty()
+        };
+        self.caps.effective = if self.euid == 0 {
+            self.caps.permitted
+        } else {
+            Capabilities::empty()
+        };
+    }
+
+    /// Update the capabilities after the uids are changed, like Linux: all of
+    /// them are lost if none of the uids is root any more, and the effective
+    /// set is cleared or filled when the effective uid leaves or becomes root.
+    fn update_capabilities_for_uids(&mut self, old_uids: (uid_t, uid_t, uid_t)) {
+        let (old_ruid, old_euid, old_suid) = old_uids;
+        let was_root = old_ruid == 0 || old_euid == 0 || old_suid == 0;
+        if was_root && self.ruid != 0 && self.euid != 0 && self.suid != 0 {
+            self.caps.permitted = Capabilities::empty();
+            self.caps.effective = Capabilities::empty();
+        }
+        if old_euid == 0 && self.euid != 0 {
+            self.caps.effective = Capabilities::empty();
+        } else if old_euid != 0 && self.euid == 0 {
+            self.caps.effective = self.caps.permitted;
+        }
+    }
+}
+
+/// Convert the id given to set*id or chown, where -1 means unchanged
+pub fn id_or_unchanged(id: u32) -> Option<u32> {
+    if id == u32::max_value() {
+        None
+    } else {
+        Some(id)
+    }
+}
+
+pub fn do_getresuid() -> (uid_t, uid_t, uid_t) {
+    let current_ref = get_current();
+    let current = current_ref.lock().unwrap();
+    let uids = current.get_credentials().lock().unwrap().uids();
+    uids
+}
+
+pub fn do_getresgid() -> (gid_t, gid_t, gid_t) {
+    let current_ref = get_current();
+    let current = current_ref.lock().unwrap();
+    let gids = current.get_credentials().lock().unwrap().gids();
+    gids
+}
+
+pub fn do_getgroups() -> Vec<gid_t> {
+    let current_ref = get_current();
+    let current = current_ref.lock().unwrap();
+    let groups = current.get_credentials().lock().unwrap().groups().to_vec();
+    groups
+}
+
+pub fn do_setuid(uid: uid_t) -> Result<()> {
+    info!("setuid: uid: {}", uid);
+    update_credentials(|credentials| credentials.set_uid(uid))
+}
+
+pub fn do_setgid(gid: gid_t) -> Result<()> {
+    info!("setgid: gid: {}", gid);
+    update_credentials(|credentials| credentials.set_gid(gid))
+}
+
+pub fn do_setresuid(ruid: Option<uid_t>, euid: Option<uid_t>, suid: Option<uid_t>) -> Result<()> {
+    info!(
+        "setresuid: ruid: {:?}, euid: {:?}, suid: {:?}",
+        ruid, euid, suid
+    );
+    update_credentials(|credentials| credentials.set_resuid(ruid, euid, suid))
+}
+
+pub fn do_setresgid(rgid: Option<gid_t>, egid: Option<gid_t>, sgid: Option<gid_t>) -> Result<()> {
+    info!(
+        "setresgid: rgid: {:?}, egid: {:?}, sgid: {:?}",
+        rgid, egid, sgid
+    );
+    update_credentials(|credentials| credentials.set_resgid(rgid, egid, sgid))
+}
+
+pub fn do_setgroups(groups: &[gid_t]) -> Result<()> {
+    info!("setgroups: groups: {:?}", groups);
+    update_credentials(|credentials| credentials.set_groups(groups))
+}
+
+fn update_credentials<F>(update_fn: F) -> Result<()>
+where
+    F: FnOnce(&mut Credentials) -> Result<()>,
+{
+    let current_ref = get_current();
+    let current = current_ref.lock().unwrap();
+    let mut credentials = current.get_credentials().lock().unwrap();
+    update_fn(&mut credentials)
+}
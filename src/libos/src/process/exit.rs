@@ -12,12 +12,94 @@ pub enum ChildProcessFilter {
 
 unsafe impl Send for ChildProcessFilter {}
 
-pub fn do_exit(exit_status: i32) {
+impl ChildProcessFilter {
+    fn matches(&self, child: &Process) -> bool {
+        match self {
+            ChildProcessFilter::WithAnyPID => true,
+            ChildProcessFilter::WithPID(required_pid) => child.get_pid() == *required_pid,
+            ChildProcessFilter::WithPGID(required_pgid) => child.get_pgid() == *required_pgid,
+        }
+    }
+}
+
+/// How a process terminates
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TermStatus {
+    /// Exit with the status given to exit
+    Exited(u8),
+    /// Killed by the signal
+    Killed(u8),
+}
+
+impl TermStatus {
+    /// The status as returned by wait4, which is decoded by WIFEXITED,
+    /// WEXITSTATUS, WIFSIGNALED and WTERMSIG
+    pub fn as_wait_status(&self) -> i32 {
+        match *self {
+            TermStatus::Exited(status) => (status as i32) << 8,
+            TermStatus::Killed(signum) => signum as i32,
+        }
+    }
+
+    /// The exit status as seen by a shell, which is 128 plus the signal number
+    /// if the process is killed by a signal
+    pub fn as_exit_code(&self) -> i32 {
+        match *self {
+            TermStatus::Exited(status) => status as i32,
+            TermStatus::Killed(signum) => 128 + signum as i32,
+        }
+    }
+}
+
+impl Default for TermStatus {
+    fn default() -> TermStatus {
+        TermStatus::Exited(0)
+    }
+}
+
+/// The change of the state of a child process that is reported by wait
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChildStatus {
+    Terminated(TermStatus),
+    /// Stopped by the signal
+    Stopped(SigNum),
+    /// Continued by SIGCONT
+    Continued,
+}
+
+impl ChildStatus {
+    /// The status as returned by wait4, which is also decoded by WIFSTOPPED,
+    /// WSTOPSIG and WIFCONTINUED
+    pub fn as_wait_status(&self) -> i32 {
+        match *self {
+            ChildStatus::Terminated(term_status) => term_status.as_wait_status(),
+            ChildStatus::Stopped(signum) => ((signum.as_u32() as i32) << 8) | 0x7f,
+            ChildStatus::Continued => 0xffff,
+        }
+    }
+}
+
+bitflags! {
+    pub struct WaitOptions: u32 {
+        const WNOHANG = 0x1;
+        /// Also known as WUNTRACED in wait4
+        const WSTOPPED = 0x2;
+        const WEXITED = 0x4;
+        const WCONTINUED = 0x8;
+        const WNOWAIT = 0x0100_0000;
+    }
+}
+
+pub fn do_exit(term_status: TermStatus) {
     let current_ref = get_current();
+    account_cputime_of_exited_thread(&current_ref);
+    drop_file_table_of_exited_thread(&current_ref);
+
     let mut current = current_ref.lock().unwrap();
     // Update current
-    current.exit_status = exit_status;
+    current.term_status = term_status;
     current.status = Status::ZOMBIE;
+    process_table::dec_live_threads(current.live_uid);
 
     // Update children
     for child_ref in current.get_children_iter() {
@@ -31,7 +113,13 @@ pub fn do_exit(exit_status: i32) {
         unsafe {
             atomic_store(ctid, 0);
         }
-        futex_wake(ctid as *const i32, 1);
+        futex_wake(ctid as *const i32, 1, FUTEX_BITSET_MATCH_ANY);
+    }
+
+    // Only the exit of the main thread is seen by the parent, as wait4 and
+    // waitid wait for processes, not threads
+    if current.get_tid() != current.get_pid() {
+        return;
     }
 
     // Notify the parent process if necessary
@@ -41,94 +129,168 @@ pub fn do_exit(exit_status: i32) {
         drop(current);
         lock_two_in_order(&parent_ref, &current_ref)
     };
-    // Wake up the parent if it is waiting on this child
+    wake_parent(&mut parent, &current);
+}
+
+/// Notify the parent of the process that the job control state of the
+/// process is changed, i.e., stopped or continued
+pub fn notify_parent_of_state_change(process_ref: &ProcessRef) {
+    let parent_ref = process_ref.lock().unwrap().get_parent().clone();
+    let (mut parent, process) = lock_two_in_order(&parent_ref, process_ref);
+    wake_parent(&mut parent, &process);
+}
+
+/// Wake up the parent if it is waiting on the child, whose state is changed
+fn wake_parent(parent: &mut Process, child: &Process) {
     if parent.waiting_children.is_none() {
         return;
     }
     let mut wait_queue = parent.waiting_children.as_mut().unwrap();
     wait_queue.del_and_wake_one_waiter(|waiter_data| -> Option<pid_t> {
-        match waiter_data {
-            ChildProcessFilter::WithAnyPID => {}
-            ChildProcessFilter::WithPID(required_pid) => {
-                if current.get_pid() != *required_pid {
-                    return None;
-                }
-            }
-            ChildProcessFilter::WithPGID(required_pgid) => {
-                if current.get_pgid() != *required_pgid {
-                    return None;
-                }
-            }
+        if !waiter_data.matches(child) {
+            return None;
         }
-        Some(current.get_pid())
+        Some(child.get_pid())
     });
 }
 
-pub fn do_wait4(child_filter: &ChildProcessFilter, exit_status: &mut i32) -> Result<pid_t> {
+/// Wait for a child process that matches the filter to change its state.
+///
+/// Return the pid and the status of the child, or None if WNOHANG is given and
+/// no such child has changed its state yet. The state change is consumed
+/// unless WNOWAIT is given, so that it can be waited for again, i.e., an
+/// exited child is reaped.
+pub fn do_wait(
+    child_filter: &ChildProcessFilter,
+    options: WaitOptions,
+) -> Result<Option<(pid_t, ChildStatus)>> {
     let current_ref = get_current();
-    let waiter = {
-        let mut current = current_ref.lock().unwrap();
+    loop {
+        let waiter = {
+            let mut current = current_ref.lock().unwrap();
+            current.waiting_children = None;
 
-        let mut any_child_to_wait_for = false;
-        for child_ref in current.get_children_iter() {
-            let child = child_ref.lock().unwrap();
-
-            let may_wait_for = match child_filter {
-                ChildProcessFilter::WithAnyPID => true,
-                ChildProcessFilter::WithPID(required_pid) => child.get_pid() == *required_pid,
-                ChildProcessFilter::WithPGID(required_pgid) => child.get_pgid() == *required_pgid,
-            };
-            if !may_wait_for {
-                continue;
+            // Return immediately as a child that we wait for has already
+            // changed its state
+            if let Some((child_pid, child_status)) =
+                find_changed_child(&current, child_filter, options)?
+            {
+                if let ChildStatus::Terminated(_) = child_status {
+                    if !options.contains(WaitOptions::WNOWAIT) {
+                        reap_child(&mut current, child_pid);
+                    }
+                }
+                return Ok(Some((child_pid, child_status)));
             }
-
-            // Return immediately as a child that we wait for has already exited
-            if child.status == Status::ZOMBIE {
-                process_table::remove(child.pid);
-                return Ok(child.pid);
+            if options.contains(WaitOptions::WNOHANG) {
+                return Ok(None);
             }
 
-            any_child_to_wait_for = true;
-        }
-        if !any_child_to_wait_for {
-            return_errno!(ECHILD, "No such child");
-        }
+            let waiter = Waiter::new(child_filter);
+            let mut wait_queue = WaitQueue::new();
+            wait_queue.add_waiter(&waiter);
 
-        let waiter = Waiter::new(child_filter);
-        let mut wait_queue = WaitQueue::new();
-        wait_queue.add_waiter(&waiter);
+            current.waiting_children = Some(wait_queue);
 
-        current.waiting_children = Some(wait_queue);
+            waiter
+        };
 
-        waiter
-    };
-
-    let child_pid = waiter.sleep_until_woken_with_result();
+        // The child that wakes us up is found again in the next round, as it
+        // may have changed its state again, e.g., continued after stopped
+        sleep_interruptibly(|| waiter.sleep_until_woken_with_result());
+    }
+}
 
-    let mut current = current_ref.lock().unwrap();
-    let child_i = {
-        let mut child_i_opt = None;
-        for (child_i, child_ref) in current.get_children_iter().enumerate() {
-            let child = child_ref.lock().unwrap();
-            if child.get_pid() != child_pid {
-                continue;
-            }
+/// Find a child that matches the filter and has changed its state in the ways
+/// given by the options
+fn find_changed_child(
+    current: &Process,
+    child_filter: &ChildProcessFilter,
+    options: WaitOptions,
+) -> Result<Option<(pid_t, ChildStatus)>> {
+    let mut any_child_to_wait_for = false;
+    for child_ref in current.get_children_iter() {
+        let child = child_ref.lock().unwrap();
+        // Only the thread group leaders are waited for, not the threads
+        if child.get_tid() != child.get_pid() || !child_filter.matches(&child) {
+            continue;
+        }
+        any_child_to_wait_for = true;
 
-            if child.get_status() != Status::ZOMBIE {
-                panic!("THIS SHOULD NEVER HAPPEN!");
+        if child.status == Status::ZOMBIE {
+            if options.contains(WaitOptions::WEXITED) {
+                let child_status = ChildStatus::Terminated(child.get_term_status());
+                return Ok(Some((child.get_pid(), child_status)));
             }
-            child_i_opt = Some(child_i);
-            *exit_status = child.get_exit_status();
+            continue;
         }
-        child_i_opt.unwrap()
-    };
-    current.children.swap_remove(child_i);
-    current.waiting_children = None;
+        let change = child.get_job_control().take_unreported_change(options);
+        let child_status = match change {
+            Some(JobStateChange::Stopped(signum)) => ChildStatus::Stopped(signum),
+            Some(JobStateChange::Continued) => ChildStatus::Continued,
+            None => continue,
+        };
+        return Ok(Some((child.get_pid(), child_status)));
+    }
+    if !any_child_to_wait_for {
+        return_errno!(ECHILD, "No such child");
+    }
+    Ok(None)
+}
+
+/// Remove the exited child from the children and release it
+fn reap_child(current: &mut Process, child_pid: pid_t) {
+    current
+        .children
+        .retain(|child_weak| match child_weak.upgrade() {
+            Some(child_ref) => child_ref.lock().unwrap().get_tid() != child_pid,
+            None => false,
+        });
 
     // Release the last reference to the child process
     process_table::remove(child_pid);
+}
+
+/// Add the CPU time of the exiting thread to its thread group leader. This must
+/// be done by the exiting thread itself before its host thread is gone.
+fn account_cputime_of_exited_thread(current_ref: &ProcessRef) {
+    let cputime = match time::do_thread_getcpuclock() {
+        Ok(ts) => ts.as_duration(),
+        Err(_) => return,
+    };
+    let tgid = current_ref.lock().unwrap().get_pid();
+    if let Ok(leader_ref) = process_table::get(tgid) {
+        let mut leader = leader_ref.lock().unwrap();
+        leader.exited_threads_cputime += cputime;
+    }
+}
+
+lazy_static! {
+    // Serializes the drops of the file tables by the exiting threads, so that
+    // of the threads that share a file table and exit at the same time, the
+    // last one sees that it holds the last reference
+    static ref FILE_TABLE_DROP_LOCK: SgxMutex<()> = SgxMutex::new(());
+}
 
-    Ok(child_pid)
+/// Drop the reference of the exiting thread to the file table, which is
+/// shared by the threads of the process. When the last thread exits, the
+/// POSIX locks of the process are released and its files are closed.
+fn drop_file_table_of_exited_thread(current_ref: &ProcessRef) {
+    let (files, pid) = {
+        let mut current = current_ref.lock().unwrap();
+        let files = std::mem::replace(&mut current.file_table, Default::default());
+        (files, current.get_pid())
+    };
+    let files = {
+        let _guard = FILE_TABLE_DROP_LOCK.lock().unwrap();
+        if Arc::strong_count(&files) > 1 {
+            drop(files);
+            return;
+        }
+        files
+    };
+    fs::release_all_posix_locks(pid);
+    drop(files);
 }
 
 fn lock_two_in_order<'a>(
@@ -3,6 +3,9 @@ use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 use std::intrinsics::atomic_load;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use time::{timespec_t, ClockID};
+use util::waiter;
 
 /// `FutexOp`, `FutexFlags`, and `futex_op_and_flags_from_u32` are helper types and
 /// functions for handling the versatile commands and arguments of futex system
@@ -20,6 +23,7 @@ pub enum FutexOp {
     FUTEX_UNLOCK_PI = 7,
     FUTEX_TRYLOCK_PI = 8,
     FUTEX_WAIT_BITSET = 9,
+    FUTEX_WAKE_BITSET = 10,
 }
 const FUTEX_OP_MASK: u32 = 0x0000_000F;
 
@@ -36,6 +40,7 @@ impl FutexOp {
             7 => Ok(FutexOp::FUTEX_UNLOCK_PI),
             8 => Ok(FutexOp::FUTEX_TRYLOCK_PI),
             9 => Ok(FutexOp::FUTEX_WAIT_BITSET),
+            10 => Ok(FutexOp::FUTEX_WAKE_BITSET),
             _ => return_errno!(EINVAL, "Unknown futex op"),
         }
     }
@@ -67,10 +72,58 @@ pub fn futex_op_and_flags_from_u32(bits: u32) -> Result<(FutexOp, FutexFlags)> {
     Ok((op, flags))
 }
 
-/// Do futex wait
-pub fn futex_wait(futex_addr: *const i32, futex_val: i32) -> Result<()> {
+/// The bitset that matches any other bitset, with which FUTEX_WAIT and
+/// FUTEX_WAKE are done
+pub const FUTEX_BITSET_MATCH_ANY: u32 = 0xFFFF_FFFF;
+
+/// The time until which a futex wait waits
+#[derive(Clone, Copy, Debug)]
+pub struct FutexDeadline {
+    clockid: ClockID,
+    time: Duration,
+}
+
+impl FutexDeadline {
+    /// The deadline of a relative timeout, which is measured against
+    /// CLOCK_MONOTONIC, as FUTEX_WAIT does
+    pub fn from_timeout(timeout: &timespec_t) -> Result<FutexDeadline> {
+        let now = time::do_clock_gettime(ClockID::CLOCK_MONOTONIC)?.as_duration();
+        Ok(FutexDeadline {
+            clockid: ClockID::CLOCK_MONOTONIC,
+            time: now + timeout.as_duration(),
+        })
+    }
+
+    /// The deadline of an absolute time of the clock, as FUTEX_WAIT_BITSET
+    /// does
+    pub fn from_abs_time(abs_time: &timespec_t, clockid: ClockID) -> FutexDeadline {
+        FutexDeadline {
+            clockid,
+            time: abs_time.as_duration(),
+        }
+    }
+
+    /// The time left until the deadline, which is zero if passed
+    fn remaining(&self) -> Result<Duration> {
+        let now = time::do_clock_gettime(self.clockid)?.as_duration();
+        Ok(self.time.checked_sub(now).unwrap_or_default())
+    }
+}
+
+/// Do futex wait, until woken by a wake whose bitset intersects the bitset of
+/// the wait, or the deadline passes
+pub fn futex_wait(
+    futex_addr: *const i32,
+    futex_val: i32,
+    deadline: Option<FutexDeadline>,
+    bitset: u32,
+) -> Result<()> {
+    if bitset == 0 {
+        return_errno!(EINVAL, "the futex bitset must not be zero");
+    }
     // Get and lock the futex bucket
     let futex_key = FutexKey::new(futex_addr);
+    let futex_item = FutexItem::new(futex_key, bitset)?;
     let (_, futex_bucket_ref) = FUTEX_BUCKETS.get_bucket(futex_key);
     let mut futex_bucket = futex_bucket_ref.lock().unwrap();
 
@@ -110,41 +163,66 @@ pub fn futex_wait(futex_addr: *const i32, futex_val: i32) -> Result<()> {
     // it cannot find the transition of futex value from val to new_val and enqueue
     // to the bucket, which will cause the waiter to wait forever.
 
-    let futex_item = FutexItem::new(futex_key);
     futex_bucket.enqueue_item(futex_item.clone());
 
     // Must make sure that no locks are holded by this thread before wait
     drop(futex_bucket);
-    futex_item.wait()
+    if let Err(e) = sleep_interruptibly(|| futex_item.wait(deadline.as_ref())) {
+        // The wait times out, unless woken just now. The item is removed
+        // from the bucket, or by the next wake on the key if requeued.
+        if !futex_item.cancel() {
+            return Ok(());
+        }
+        let mut futex_bucket = futex_bucket_ref.lock().unwrap();
+        futex_bucket.remove_item(&futex_item);
+        return Err(e);
+    }
+    Ok(())
 }
 
-/// Do futex wake
-pub fn futex_wake(futex_addr: *const i32, max_count: usize) -> Result<usize> {
+/// Do futex wake, waking up to max_count waiters whose bitsets intersect the
+/// bitset
+pub fn futex_wake(futex_addr: *const i32, max_count: usize, bitset: u32) -> Result<usize> {
+    if bitset == 0 {
+        return_errno!(EINVAL, "the futex bitset must not be zero");
+    }
     // Get and lock the futex bucket
     let futex_key = FutexKey::new(futex_addr);
     let (_, futex_bucket_ref) = FUTEX_BUCKETS.get_bucket(futex_key);
     let mut futex_bucket = futex_bucket_ref.lock().unwrap();
 
     // Dequeue and wake up the items in the bucket
-    let count = futex_bucket.dequeue_and_wake_items(futex_key, max_count);
+    let count = futex_bucket.dequeue_and_wake_items(futex_key, max_count, bitset);
     Ok(count)
 }
 
-/// Do futex requeue
+/// Do futex requeue, waking up to max_nwakes waiters on futex_addr and moving
+/// up to max_nrequeues of the rest to futex_new_addr, where they wait without
+/// being woken. If expected_val is given, as FUTEX_CMP_REQUEUE does, the value
+/// at futex_addr is checked first. Return the number of the waiters that are
+/// woken or requeued.
 pub fn futex_requeue(
     futex_addr: *const i32,
     max_nwakes: usize,
     max_nrequeues: usize,
     futex_new_addr: *const i32,
+    expected_val: Option<i32>,
 ) -> Result<usize> {
-    if futex_new_addr == futex_addr {
-        return futex_wake(futex_addr, max_nwakes);
-    }
     let futex_key = FutexKey::new(futex_addr);
     let futex_new_key = FutexKey::new(futex_new_addr);
     let (bucket_idx, futex_bucket_ref) = FUTEX_BUCKETS.get_bucket(futex_key);
     let (new_bucket_idx, futex_new_bucket_ref) = FUTEX_BUCKETS.get_bucket(futex_new_key);
-    let nwakes = {
+    // The futex value is checked with the bucket locked, the same as
+    // futex_wait, so that no waiter is enqueued after the value changes
+    let check_val = || -> Result<()> {
+        if let Some(expected_val) = expected_val {
+            if futex_key.load_val() != expected_val {
+                return_errno!(EAGAIN, "futex value does not match");
+            }
+        }
+        Ok(())
+    };
+    let count = {
         if bucket_idx != new_bucket_idx {
             let (mut futex_bucket, mut futex_new_bucket) = {
                 if bucket_idx < new_bucket_idx {
@@ -158,23 +236,32 @@ pub fn futex_requeue(
                     (futex_bucket, futex_new_bucket)
                 }
             };
-            let nwakes = futex_bucket.dequeue_and_wake_items(futex_key, max_nwakes);
-            futex_bucket.requeue_items_to_another_bucket(
+            check_val()?;
+            let nwakes =
+                futex_bucket.dequeue_and_wake_items(futex_key, max_nwakes, FUTEX_BITSET_MATCH_ANY);
+            let nrequeues = futex_bucket.requeue_items_to_another_bucket(
                 futex_key,
                 &mut futex_new_bucket,
                 futex_new_key,
                 max_nrequeues,
             );
-            nwakes
+            nwakes + nrequeues
         } else {
             // bucket_idx == new_bucket_idx
             let mut futex_bucket = futex_bucket_ref.lock().unwrap();
-            let nwakes = futex_bucket.dequeue_and_wake_items(futex_key, max_nwakes);
-            futex_bucket.update_item_keys(futex_key, futex_new_key, max_nrequeues);
-            nwakes
+            check_val()?;
+            let nwakes =
+                futex_bucket.dequeue_and_wake_items(futex_key, max_nwakes, FUTEX_BITSET_MATCH_ANY);
+            // Requeuing to the same address changes nothing
+            let nrequeues = if futex_new_addr != futex_addr {
+                futex_bucket.update_item_keys(futex_key, futex_new_key, max_nrequeues)
+            } else {
+                0
+            };
+            nwakes + nrequeues
         }
     };
-    Ok(nwakes)
+    Ok(count)
 }
 
 // Make sure futex bucket count is the power of 2
@@ -205,23 +292,43 @@ impl FutexKey {
 #[derive(Clone)]
 struct FutexItem {
     key: FutexKey,
+    bitset: u32,
     waiter: WaiterRef,
 }
 
 impl FutexItem {
-    pub fn new(key: FutexKey) -> FutexItem {
-        FutexItem {
+    pub fn new(key: FutexKey, bitset: u32) -> Result<FutexItem> {
+        Ok(FutexItem {
             key: key,
-            waiter: Arc::new(Waiter::new()),
-        }
+            bitset: bitset,
+            waiter: Arc::new(Waiter::new()?),
+        })
     }
 
-    pub fn wake(&self) {
+    /// Return whether the item is woken by this call, i.e., neither woken
+    /// nor cancelled before
+    pub fn wake(&self) -> bool {
         self.waiter.wake()
     }
 
-    pub fn wait(&self) -> Result<()> {
-        self.waiter.wait()
+    /// Return whether the item is cancelled by this call, i.e., neither woken
+    /// nor cancelled before
+    pub fn cancel(&self) -> bool {
+        self.waiter.cancel()
+    }
+
+    /// Return whether the item is cancelled by a timeout, as the items that
+    /// are woken are no longer in the buckets
+    pub fn is_cancelled(&self) -> bool {
+        self.waiter.is_woken()
+    }
+
+    pub fn wait(&self, deadline: Option<&FutexDeadline>) -> Result<()> {
+        self.waiter.wait(deadline)
+    }
+
+    fn is_same(&self, other: &FutexItem) -> bool {
+        Arc::ptr_eq(&self.waiter, &other.waiter)
     }
 }
 
@@ -242,14 +349,26 @@ impl FutexBucket {
         self.queue.push_back(item);
     }
 
-    pub fn dequeue_and_wake_items(&mut self, key: FutexKey, max_count: usize) -> usize {
+    pub fn remove_item(&mut self, item: &FutexItem) {
+        self.queue.retain(|queued_item| !queued_item.is_same(item));
+    }
+
+    pub fn dequeue_and_wake_items(
+        &mut self,
+        key: FutexKey,
+        max_count: usize,
+        bitset: u32,
+    ) -> usize {
         let mut count = 0;
         let mut idx = 0;
         while count < max_count && idx < self.queue.len() {
-            if key == self.queue[idx].key {
+            let item = &self.queue[idx];
+            if key == item.key && (item.bitset & bitset) != 0 {
                 if let Some(item) = self.queue.swap_remove_back(idx) {
-                    item.wake();
-                    count += 1;
+                    // The items cancelled by the timeouts are not counted
+                    if item.wake() {
+                        count += 1;
+                    }
                 }
             } else {
                 idx += 1;
@@ -258,7 +377,17 @@ impl FutexBucket {
         count
     }
 
-    pub fn update_item_keys(&mut self, key: FutexKey, new_key: FutexKey, max_count: usize) -> () {
+    /// Change the keys of up to max_count items from key to new_key. Return
+    /// the number of the changed items.
+    pub fn update_item_keys(
+        &mut self,
+        key: FutexKey,
+        new_key: FutexKey,
+        max_count: usize,
+    ) -> usize {
+        // The items cancelled by the timeouts are dropped, not requeued
+        self.queue
+            .retain(|item| item.key != key || !item.is_cancelled());
         let mut count = 0;
         for item in self.queue.iter_mut() {
             if count == max_count {
@@ -269,20 +398,27 @@ impl FutexBucket {
                 count += 1;
             }
         }
+        count
     }
 
+    /// Move up to max_nrequeues items with the key to another bucket, changing
+    /// their keys to new_key. Return the number of the moved items.
     pub fn requeue_items_to_another_bucket(
         &mut self,
         key: FutexKey,
         another: &mut Self,
         new_key: FutexKey,
         max_nrequeues: usize,
-    ) -> () {
+    ) -> usize {
         let mut count = 0;
         let mut idx = 0;
         while count < max_nrequeues && idx < self.queue.len() {
             if key == self.queue[idx].key {
                 if let Some(mut item) = self.queue.swap_remove_back(idx) {
+                    // The items cancelled by the timeouts are dropped
+                    if item.is_cancelled() {
+                        continue;
+                    }
                     item.key = new_key;
                     another.enqueue_item(item);
                     count += 1;
@@ -291,6 +427,7 @@ impl FutexBucket {
                 idx += 1;
             }
         }
+        count
     }
 }
 
@@ -322,72 +459,60 @@ impl FutexBucketVec {
     }
 }
 
+/// The waiter of a futex item, which is woken or cancelled only once
 #[derive(Debug)]
 struct Waiter {
-    thread: *const c_void,
+    inner: waiter::Waiter,
     is_woken: AtomicBool,
 }
 
 type WaiterRef = Arc<Waiter>;
 
 impl Waiter {
-    pub fn new() -> Waiter {
-        Waiter {
-            thread: unsafe { sgx_thread_get_self() },
+    pub fn new() -> Result<Waiter> {
+        Ok(Waiter {
+            inner: waiter::Waiter::new()?,
             is_woken: AtomicBool::new(false),
-        }
+        })
     }
 
-    pub fn wait(&self) -> Result<()> {
-        let current = unsafe { sgx_thread_get_self() };
-        if current != self.thread {
-            return Ok(());
-        }
-        while self.is_woken.load(Ordering::SeqCst) == false {
-            wait_event(self.thread);
+    pub fn wait(&self, deadline: Option<&FutexDeadline>) -> Result<()> {
+        while !self.is_woken() {
+            let remaining = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.remaining()?;
+                    if remaining == Duration::default() {
+                        return_errno!(ETIMEDOUT, "the futex wait times out");
+                    }
+                    Some(remaining)
+                }
+                None => None,
+            };
+            // The deadline may be of CLOCK_REALTIME, which can be changed
+            // during the wait, so it is checked again when the wait times out
+            match self.inner.wait(remaining.as_ref()) {
+                Err(e) if e.errno() == ETIMEDOUT => continue,
+                ret => ret?,
+            }
         }
         Ok(())
     }
 
-    pub fn wake(&self) {
-        if self.is_woken.fetch_or(true, Ordering::SeqCst) == false {
-            set_event(self.thread);
-        }
+    pub fn is_woken(&self) -> bool {
+        self.is_woken.load(Ordering::SeqCst)
     }
-}
-
-unsafe impl Send for Waiter {}
-unsafe impl Sync for Waiter {}
 
-fn wait_event(thread: *const c_void) {
-    let mut ret: c_int = 0;
-    let mut sgx_ret: c_int = 0;
-    unsafe {
-        sgx_ret = sgx_thread_wait_untrusted_event_ocall(&mut ret as *mut c_int, thread);
-    }
-    if ret != 0 || sgx_ret != 0 {
-        panic!("ERROR: sgx_thread_wait_untrusted_event_ocall failed");
+    pub fn wake(&self) -> bool {
+        if self.is_woken.fetch_or(true, Ordering::SeqCst) == false {
+            self.inner.wake();
+            return true;
+        }
+        false
     }
-}
 
-fn set_event(thread: *const c_void) {
-    let mut ret: c_int = 0;
-    let mut sgx_ret: c_int = 0;
-    unsafe {
-        sgx_ret = sgx_thread_set_untrusted_event_ocall(&mut ret as *mut c_int, thread);
+    /// Mark the waiter as woken without waking it, so that it is no longer
+    /// woken by others
+    pub fn cancel(&self) -> bool {
+        self.is_woken.fetch_or(true, Ordering::SeqCst) == false
     }
-    if ret != 0 || sgx_ret != 0 {
-        panic!("ERROR: sgx_thread_set_untrusted_event_ocall failed");
-    }
-}
-
-extern "C" {
-    fn sgx_thread_get_self() -> *const c_void;
-
-    /* Go outside and wait on my untrusted event */
-    fn sgx_thread_wait_untrusted_event_ocall(ret: *mut c_int, self_thread: *const c_void) -> c_int;
-
-    /* Wake a thread waiting on its untrusted event */
-    fn sgx_thread_set_untrusted_event_ocall(ret: *mut c_int, waiter_thread: *const c_void)
-        -> c_int;
 }
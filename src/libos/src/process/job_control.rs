@@ -0,0 +1,143 @@
+//! Job control, i.e., stopping, continuing and killing a process by signals.
+//!
+//! A thread that runs in the enclave cannot be interrupted. So a process is
+//! stopped or killed cooperatively: each thread of the process checks the job
+//! control state at the entry and the exit of every syscall, where it is
+//! parked while the process is stopped, or exits if the process is killed. A
+//! thread that never makes a syscall, or blocks in one forever, is not
+//! stopped or killed.
+use super::*;
+
+pub type JobControlRef = Arc<JobControl>;
+
+/// The job control state of a process, which is shared by its threads
+#[derive(Debug)]
+pub struct JobControl {
+    inner: SgxMutex<JobControlInner>,
+}
+
+#[derive(Debug)]
+struct JobControlInner {
+    stopped: bool,
+    // The signal by which the process is killed, if any
+    killed: Option<SigNum>,
+    // The change of the state that has not been reported to the parent by
+    // wait yet
+    unreported_change: Option<JobStateChange>,
+    // The threads that are parked while the process is stopped
+    parked_threads: WaitQueue<(), ()>,
+}
+
+/// A change of the job control state that is reported to the parent
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JobStateChange {
+    Stopped(SigNum),
+    Continued,
+}
+
+impl JobControl {
+    pub fn new() -> JobControlRef {
+        Arc::new(JobControl {
+            inner: SgxMutex::new(JobControlInner {
+                stopped: false,
+                killed: None,
+                unreported_change: None,
+                parked_threads: WaitQueue::new(),
+            }),
+        })
+    }
+
+    /// Stop the process by the signal, if not stopped yet. The threads are
+    /// parked at their next syscalls. Return whether the state is changed.
+    pub fn stop(&self, signum: SigNum) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.killed.is_some() || inner.stopped {
+            return false;
+        }
+        inner.stopped = true;
+        inner.unreported_change = Some(JobStateChange::Stopped(signum));
+        true
+    }
+
+    /// Continue the process, if stopped. The stop that has not been reported
+    /// is discarded, as the process is no longer stopped. Return whether the
+    /// state is changed.
+    pub fn cont(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.stopped {
+            return false;
+        }
+        inner.stopped = false;
+        inner.unreported_change = Some(JobStateChange::Continued);
+        wake_all_parked_threads(&mut inner);
+        true
+    }
+
+    /// Kill the process by the signal, even if stopped. The threads exit at
+    /// their next syscalls. The first signal that kills the process is kept.
+    pub fn kill(&self, signum: SigNum) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.killed.get_or_insert(signum);
+        inner.stopped = false;
+        inner.unreported_change = None;
+        wake_all_parked_threads(&mut inner);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.inner.lock().unwrap().stopped
+    }
+
+    /// Take the change of the state that has not been reported to the parent,
+    /// if it is one to wait for by the options of wait. The change is kept if
+    /// WNOWAIT is given.
+    pub fn take_unreported_change(&self, options: WaitOptions) -> Option<JobStateChange> {
+        let mut inner = self.inner.lock().unwrap();
+        let change = inner.unreported_change?;
+        let to_report = match change {
+            JobStateChange::Stopped(_) => options.contains(WaitOptions::WSTOPPED),
+            JobStateChange::Continued => options.contains(WaitOptions::WCONTINUED),
+        };
+        if !to_report {
+            return None;
+        }
+        if !options.contains(WaitOptions::WNOWAIT) {
+            inner.unreported_change = None;
+        }
+        Some(change)
+    }
+
+    /// Park the current thread while the process is stopped. Return the
+    /// signal by which the process is killed, if any.
+    fn park_while_stopped(&self) -> Option<SigNum> {
+        loop {
+            let waiter = {
+                let mut inner = self.inner.lock().unwrap();
+                if !inner.stopped {
+                    return inner.killed;
+                }
+                let waiter = Waiter::new(&());
+                inner.parked_threads.add_waiter(&waiter);
+                waiter
+            };
+            waiter.sleep_until_woken_with_result();
+        }
+    }
+}
+
+fn wake_all_parked_threads(inner: &mut JobControlInner) {
+    while inner.parked_threads.del_and_wake_one_waiter(|_| Some(())) > 0 {}
+}
+
+/// Park the current thread while its process is stopped. Return the
+/// termination status if the process is killed, with which the current thread
+/// must exit. This is done at the entry and the exit of every syscall.
+pub fn handle_job_control() -> Option<TermStatus> {
+    let job_control = {
+        let current_ref = get_current();
+        let current = current_ref.lock().unwrap();
+        current.get_job_control().clone()
+    };
+    job_control
+        .park_while_stopped()
+        .map(|signum| TermStatus::Killed(signum.as_u32() as u8))
+}
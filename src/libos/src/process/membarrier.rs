@@ -0,0 +1,100 @@
+use super::*;
+use std::sync::atomic::{fence, Ordering};
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MembarrierCmd {
+    MEMBARRIER_CMD_QUERY = 0,
+    MEMBARRIER_CMD_GLOBAL = 1,
+    MEMBARRIER_CMD_GLOBAL_EXPEDITED = 2,
+    MEMBARRIER_CMD_REGISTER_GLOBAL_EXPEDITED = 4,
+    MEMBARRIER_CMD_PRIVATE_EXPEDITED = 8,
+    MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED = 16,
+    MEMBARRIER_CMD_PRIVATE_EXPEDITED_SYNC_CORE = 32,
+    MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED_SYNC_CORE = 64,
+}
+
+impl MembarrierCmd {
+    pub fn from_i32(bits: i32) -> Result<MembarrierCmd> {
+        match bits {
+            0 => Ok(MembarrierCmd::MEMBARRIER_CMD_QUERY),
+            1 => Ok(MembarrierCmd::MEMBARRIER_CMD_GLOBAL),
+            2 => Ok(MembarrierCmd::MEMBARRIER_CMD_GLOBAL_EXPEDITED),
+            4 => Ok(MembarrierCmd::MEMBARRIER_CMD_REGISTER_GLOBAL_EXPEDITED),
+            8 => Ok(MembarrierCmd::MEMBARRIER_CMD_PRIVATE_EXPEDITED),
+            16 => Ok(MembarrierCmd::MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED),
+            32 => Ok(MembarrierCmd::MEMBARRIER_CMD_PRIVATE_EXPEDITED_SYNC_CORE),
+            64 => Ok(MembarrierCmd::MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED_SYNC_CORE),
+            _ => return_errno!(EINVAL, "Unknown membarrier command"),
+        }
+    }
+}
+
+/// The commands that are supported by Occlum, as reported by MEMBARRIER_CMD_QUERY
+const SUPPORTED_CMDS: i32 = MembarrierCmd::MEMBARRIER_CMD_GLOBAL as i32
+    | MembarrierCmd::MEMBARRIER_CMD_PRIVATE_EXPEDITED as i32
+    | MembarrierCmd::MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED as i32;
+
+/// Issue memory barriers on a set of threads.
+///
+/// All LibOS threads are backed by host threads of the same host process. So
+/// the rendezvous required by the expedited commands is delegated to the
+/// host's membarrier, which interrupts every CPU that is currently running a
+/// thread of the host process (and thus every running thread of the LibOS
+/// process). The interrupted threads exit the enclave (AEX), which serializes
+/// their memory accesses. Threads that are not running at the moment have
+/// already gone through a full barrier when they were scheduled out.
+pub fn do_membarrier(cmd: MembarrierCmd, flags: i32) -> Result<i32> {
+    info!("membarrier: cmd: {:?}, flags: {}", cmd, flags);
+    if flags != 0 {
+        return_errno!(EINVAL, "flags must be zero");
+    }
+
+    let current_vm_ref = {
+        let current_ref = get_current();
+        let current = current_ref.lock().unwrap();
+        current.get_vm().clone()
+    };
+
+    match cmd {
+        MembarrierCmd::MEMBARRIER_CMD_QUERY => Ok(SUPPORTED_CMDS),
+        MembarrierCmd::MEMBARRIER_CMD_GLOBAL => {
+            do_host_membarrier(cmd)?;
+            Ok(0)
+        }
+        MembarrierCmd::MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED => {
+            let mut current_vm = current_vm_ref.lock().unwrap();
+            if !current_vm.is_membarrier_registered() {
+                do_host_membarrier(cmd)?;
+                current_vm.set_membarrier_registered();
+            }
+            Ok(0)
+        }
+        MembarrierCmd::MEMBARRIER_CMD_PRIVATE_EXPEDITED => {
+            if !current_vm_ref.lock().unwrap().is_membarrier_registered() {
+                return_errno!(EPERM, "expedited membarrier is not registered");
+            }
+            do_host_membarrier(cmd)?;
+            Ok(0)
+        }
+        _ => return_errno!(EINVAL, "the membarrier command is not supported"),
+    }
+}
+
+fn do_host_membarrier(cmd: MembarrierCmd) -> Result<()> {
+    // The memory accesses of the calling thread must be ordered before and
+    // after the barriers issued on the other threads
+    fence(Ordering::SeqCst);
+    try_libc!({
+        let mut retval = 0;
+        let sgx_status = occlum_ocall_membarrier(&mut retval, cmd as i32, 0);
+        assert!(sgx_status == sgx_status_t::SGX_SUCCESS);
+        retval
+    });
+    fence(Ordering::SeqCst);
+    Ok(())
+}
+
+extern "C" {
+    fn occlum_ocall_membarrier(ret: *mut i32, cmd: i32, flags: i32) -> sgx_status_t;
+}
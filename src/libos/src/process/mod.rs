@@ -1,13 +1,41 @@
 pub use self::arch_prctl::{do_arch_prctl, ArchPrctlCode};
-pub use self::exit::{do_exit, do_wait4, ChildProcessFilter};
+pub use self::capabilities::{
+    cap_user_data_len, cap_user_data_t, cap_user_header_t, do_capget, do_capset, Capabilities,
+    CapabilitySets, LINUX_CAPABILITY_VERSION_3,
+};
+pub use self::credentials::{
+    do_getgroups, do_getresgid, do_getresuid, do_setgid, do_setgroups, do_setresgid, do_setresuid,
+    do_setuid, gid_t, id_or_unchanged, uid_t, Credentials, CredentialsRef, NGROUPS_MAX,
+};
+pub use self::exit::{
+    do_exit, do_wait, notify_parent_of_state_change, ChildProcessFilter, ChildStatus, TermStatus,
+    WaitOptions,
+};
 pub use self::futex::{
-    futex_op_and_flags_from_u32, futex_requeue, futex_wait, futex_wake, FutexFlags, FutexOp,
+    futex_op_and_flags_from_u32, futex_requeue, futex_wait, futex_wake, FutexDeadline, FutexFlags,
+    FutexOp, FUTEX_BITSET_MATCH_ANY,
 };
+pub use self::job_control::{handle_job_control, JobControl, JobControlRef, JobStateChange};
+pub use self::membarrier::{do_membarrier, MembarrierCmd};
 pub use self::process::{Status, IDLE_PROCESS};
-pub use self::process_table::get;
-pub use self::sched::{do_sched_getaffinity, do_sched_setaffinity, do_sched_yield, CpuSet};
-pub use self::spawn::{do_spawn, do_spawn_without_exec, ElfFile, FileAction, ProgramHeaderExt};
-pub use self::task::{current_pid, get_current, run_task};
+pub use self::process_table::{get, get_all};
+pub use self::sched::{
+    do_getcpu, do_sched_getaffinity, do_sched_setaffinity, do_sched_yield, CpuSet,
+};
+pub use self::signal::{
+    deliver_pending_signals, do_kill, do_rt_sigaction, do_rt_sigprocmask, do_tgkill, do_tkill,
+    is_sig_pending, raise_signal, return_from_sig_handler, sigaction_t, wait_interruptibly,
+    SigActions, SigActionsRef, SigFrame, SigInterruptRef, SigMaskHow, SigNum, SigSet,
+    SyscallRestart, SIGIO, SIGPIPE, SIGXFSZ,
+};
+pub use self::spawn::{
+    do_spawn, do_spawn_without_exec, ElfFile, FileAction, ProgramHeaderExt, SpawnAttr,
+};
+pub use self::syscall_filter::{
+    do_set_syscall_filter, filter_syscall, kill_by_syscall_filter, syscall_filter_rule_t,
+    SyscallFilterAction, SyscallFiltersRef,
+};
+pub use self::task::{current_pid, current_vm, get_current, run_task};
 pub use self::thread::{do_clone, do_set_tid_address, CloneFlags, ThreadGroup};
 pub use self::wait::{WaitQueue, Waiter};
 
@@ -22,11 +50,32 @@ pub struct Process {
     pgid: pid_t,
     tgid: pid_t,
     host_tid: pid_t,
-    exit_status: i32,
+    term_status: TermStatus,
     // TODO: move cwd, root_inode into a FileSystem structure
-    // TODO: should cwd be a String or INode?
-    cwd: String,
+    cwd: Cwd,
+    mnt_ns: Arc<MountNamespace>,
+    // The file mode creation mask
+    umask: u32,
+    // The signals blocked by the thread
+    sig_mask: SigSet,
+    // The signals sent to the thread that have not been delivered yet
+    sig_pending: SigSet,
+    // The states to restore after the handlers of the signals return
+    sig_frames: Vec<SigFrame>,
+    // The state with which the signals interrupt the waits of the thread
+    sig_interrupt: SigInterruptRef,
+    // The actions of the signals, which are shared by the threads of the
+    // process
+    sig_actions: SigActionsRef,
+    // The job control state, which is shared by the threads of the process
+    job_control: JobControlRef,
+    // The user and group ids, which are shared by the threads of the process
+    credentials: CredentialsRef,
+    // The real uid by which the thread is counted as live
+    live_uid: uid_t,
     elf_path: String,
+    // The detailed error of the last failed syscall
+    last_error: Option<String>,
     clear_child_tid: Option<*mut pid_t>,
     parent: Option<ProcessRef>,
     children: Vec<ProcessWeakRef>,
@@ -35,6 +84,12 @@ pub struct Process {
     vm: ProcessVMRef,
     file_table: FileTableRef,
     rlimits: ResourceLimitsRef,
+    syscall_filters: SyscallFiltersRef,
+    // The CPU time consumed by the exited threads of the thread group. Only
+    // valid for the thread group leader.
+    exited_threads_cputime: Duration,
+    // The time when the process is created, by CLOCK_MONOTONIC
+    start_time: Duration,
 }
 
 pub type ProcessRef = Arc<SgxMutex<Process>>;
@@ -61,6 +116,25 @@ pub fn do_getpgid() -> pid_t {
     current.get_pgid()
 }
 
+/// Run the blocking function with the current thread marked as sleeping, i.e.,
+/// in the INTERRUPTIBLE status, as seen in /proc/[pid]/stat
+pub fn sleep_interruptibly<T>(blocking_fn: impl FnOnce() -> T) -> T {
+    let current_ref = get_current();
+    current_ref.lock().unwrap().status = Status::INTERRUPTIBLE;
+    let ret = blocking_fn();
+    current_ref.lock().unwrap().status = Status::RUNNING;
+    ret
+}
+
+/// Set the file mode creation mask, returning the previous one
+pub fn do_umask(mask: u32) -> u32 {
+    let current_ref = get_current();
+    let mut current = current_ref.lock().unwrap();
+    let old_mask = current.get_umask();
+    current.set_umask(mask);
+    old_mask
+}
+
 pub fn do_getppid() -> pid_t {
     let parent_ref = {
         let current_ref = get_current();
@@ -72,19 +146,26 @@ pub fn do_getppid() -> pid_t {
 }
 
 mod arch_prctl;
+mod capabilities;
+mod credentials;
 mod exit;
 mod futex;
+mod job_control;
+mod membarrier;
 mod process;
 mod process_table;
 mod sched;
+mod signal;
 mod spawn;
+mod syscall_filter;
 mod task;
 mod thread;
 mod wait;
 
 use self::task::Task;
 use super::*;
-use fs::{File, FileRef, FileTable};
+use fs::{Cwd, File, FileRef, FileTable, MountNamespace};
 use misc::ResourceLimitsRef;
+use std::time::Duration;
 use time::GLOBAL_PROFILER;
 use vm::ProcessVM;
@@ -1,6 +1,7 @@
 use super::task::Task;
 use super::*;
-use fs::{File, FileRef, FileTable};
+use fs::{Cwd, File, FileRef, FileTable, MountNamespace, INIT_MOUNT_NS};
+use rcore_fs::vfs::INode;
 use vm::ProcessVM;
 
 lazy_static! {
@@ -13,9 +14,20 @@ lazy_static! {
             pgid: 1,
             tgid: 0,
             host_tid: 0,
-            exit_status: 0,
-            cwd: "/".to_owned(),
+            term_status: Default::default(),
+            cwd: Cwd::root(),
+            mnt_ns: INIT_MOUNT_NS.clone(),
+            umask: DEFAULT_UMASK,
+            sig_mask: Default::default(),
+            sig_pending: Default::default(),
+            sig_frames: Vec::new(),
+            sig_interrupt: Default::default(),
+            sig_actions: Default::default(),
+            job_control: JobControl::new(),
+            credentials: Default::default(),
+            live_uid: 0,
             elf_path: "/".to_owned(),
+            last_error: None,
             clear_child_tid: None,
             parent: None,
             children: Vec::new(),
@@ -23,13 +35,18 @@ lazy_static! {
             vm: Default::default(),
             file_table: Default::default(),
             rlimits: Default::default(),
+            syscall_filters: Default::default(),
+            exited_threads_cputime: Default::default(),
+            start_time: Default::default(),
         }))
     };
 }
 
+const DEFAULT_UMASK: u32 = 0o022;
+
 impl Process {
     pub fn new(
-        cwd: &str,
+        cwd: &Cwd,
         elf_path: &str,
         task: Task,
         vm_ref: ProcessVMRef,
@@ -37,6 +54,7 @@ impl Process {
         rlimits_ref: ResourceLimitsRef,
     ) -> Result<(pid_t, ProcessRef)> {
         let new_pid = process_table::alloc_pid();
+        let start_time = time::do_clock_gettime(time::ClockID::CLOCK_MONOTONIC)?.as_duration();
         let new_process_ref = Arc::new(SgxMutex::new(Process {
             task: task,
             status: Default::default(),
@@ -44,16 +62,30 @@ impl Process {
             pgid: 1, // TODO: implement pgid
             tgid: new_pid,
             host_tid: 0,
-            cwd: cwd.to_owned(),
+            cwd: cwd.clone(),
+            mnt_ns: INIT_MOUNT_NS.clone(),
+            umask: DEFAULT_UMASK,
+            sig_mask: Default::default(),
+            sig_pending: Default::default(),
+            sig_frames: Vec::new(),
+            sig_interrupt: Default::default(),
+            sig_actions: Default::default(),
+            job_control: JobControl::new(),
+            credentials: Default::default(),
+            live_uid: 0,
             elf_path: elf_path.to_owned(),
+            last_error: None,
             clear_child_tid: None,
-            exit_status: 0,
+            term_status: Default::default(),
             parent: None,
             children: Vec::new(),
             waiting_children: None,
             vm: vm_ref,
             file_table: file_table_ref,
             rlimits: rlimits_ref,
+            syscall_filters: Default::default(),
+            exited_threads_cputime: Default::default(),
+            start_time,
         }));
         Ok((new_pid, new_process_ref))
     }
@@ -84,15 +116,59 @@ impl Process {
     pub fn get_status(&self) -> Status {
         self.status
     }
-    pub fn get_exit_status(&self) -> i32 {
-        self.exit_status
+    pub fn get_term_status(&self) -> TermStatus {
+        self.term_status
     }
     pub fn get_cwd(&self) -> &str {
-        &self.cwd
+        self.cwd.path()
+    }
+    /// Get the current path of the cwd, which is derived from the inode of the
+    /// cwd, so that it is still right after the cwd or its ancestors are moved
+    pub fn get_cwd_path(&self) -> String {
+        self.cwd.current_path(&self.mnt_ns.root_inode())
+    }
+    pub fn get_cwd_inode(&self) -> &Arc<dyn INode> {
+        self.cwd.inode()
+    }
+    pub fn get_cwd_mut(&mut self) -> &mut Cwd {
+        &mut self.cwd
+    }
+    pub fn get_mnt_ns(&self) -> &Arc<MountNamespace> {
+        &self.mnt_ns
+    }
+    pub fn set_mnt_ns(&mut self, mnt_ns: Arc<MountNamespace>) {
+        self.mnt_ns = mnt_ns;
+    }
+    pub fn get_umask(&self) -> u32 {
+        self.umask
+    }
+    pub fn set_umask(&mut self, umask: u32) {
+        self.umask = umask & 0o777;
+    }
+    pub fn get_sig_mask(&self) -> SigSet {
+        self.sig_mask
+    }
+    pub fn set_sig_mask(&mut self, sig_mask: SigSet) {
+        self.sig_mask = sig_mask;
+    }
+    pub fn get_sig_actions(&self) -> &SigActionsRef {
+        &self.sig_actions
+    }
+    pub fn get_job_control(&self) -> &JobControlRef {
+        &self.job_control
+    }
+    pub fn get_credentials(&self) -> &CredentialsRef {
+        &self.credentials
     }
     pub fn get_elf_path(&self) -> &str {
         &self.elf_path
     }
+    pub fn get_last_error(&self) -> Option<&str> {
+        self.last_error.as_ref().map(|e| e.as_str())
+    }
+    pub fn set_last_error(&mut self, last_error: String) {
+        self.last_error = Some(last_error);
+    }
     pub fn get_vm(&self) -> &ProcessVMRef {
         &self.vm
     }
@@ -107,21 +183,19 @@ impl Process {
             .iter()
             .filter_map(|child_weak| child_weak.upgrade())
     }
-    pub fn change_cwd(&mut self, path: &str) {
-        if path.len() > 0 && path.as_bytes()[0] == b'/' {
-            // absolute
-            self.cwd = path.to_owned();
-        } else {
-            // relative
-            if !self.cwd.ends_with("/") {
-                self.cwd += "/";
-            }
-            self.cwd += path;
-        }
+    pub fn change_cwd(&mut self, path: &str, inode: Arc<dyn INode>) {
+        let abs_path = self.convert_to_abs_path(path);
+        self.cwd = Cwd::new(&abs_path, inode);
     }
     pub fn get_rlimits(&self) -> &ResourceLimitsRef {
         &self.rlimits
     }
+    pub fn get_exited_threads_cputime(&self) -> Duration {
+        self.exited_threads_cputime
+    }
+    pub fn get_start_time(&self) -> Duration {
+        self.start_time
+    }
 }
 
 impl Drop for Process {
@@ -23,6 +23,15 @@ pub fn get(pid: pid_t) -> Result<ProcessRef> {
         .ok_or_else(|| errno!(ENOENT, "process not found"))
 }
 
+pub fn get_all() -> Vec<ProcessRef> {
+    PROCESS_TABLE
+        .lock()
+        .unwrap()
+        .values()
+        .map(|pr| pr.clone())
+        .collect()
+}
+
 static NEXT_PID: AtomicU32 = AtomicU32::new(1);
 
 pub fn alloc_pid() -> u32 {
@@ -36,3 +45,51 @@ pub fn free_pid(pid: u32) {
     }
     // TODO:
 }
+
+lazy_static! {
+    static ref LIVE_THREADS: SgxMutex<LiveThreads> = SgxMutex::new(LiveThreads::default());
+}
+
+/// The threads of all the processes. The ones that have not exited are
+/// counted per real user, which is limited by RLIMIT_NPROC. Unlike Linux, the
+/// limit applies to root too, as a thread is also counted in total until it
+/// leaves the enclave, which is limited by the max number of threads, i.e.,
+/// the TCSs that the threads take.
+#[derive(Debug, Default)]
+struct LiveThreads {
+    num_in_enclave: usize,
+    num_per_user: HashMap<uid_t, usize>,
+}
+
+/// Count a new thread of the user as live, or fail with EAGAIN if the user
+/// already has as many live threads as the limit, or no more threads can
+/// enter the enclave
+pub fn inc_live_threads(uid: uid_t, limit: usize) -> Result<()> {
+    let mut live_threads = LIVE_THREADS.lock().unwrap();
+    if live_threads.num_in_enclave >= config::LIBOS_CONFIG.process.max_num_of_threads {
+        return_errno!(EAGAIN, "the number of threads reaches max_num_of_threads");
+    }
+    let num_of_user = live_threads.num_per_user.entry(uid).or_insert(0);
+    if *num_of_user >= limit {
+        return_errno!(EAGAIN, "the number of threads reaches RLIMIT_NPROC");
+    }
+    *num_of_user += 1;
+    live_threads.num_in_enclave += 1;
+    Ok(())
+}
+
+/// Count an exited thread of the user as no longer live
+pub fn dec_live_threads(uid: uid_t) {
+    let mut live_threads = LIVE_THREADS.lock().unwrap();
+    let num_of_user = live_threads.num_per_user.get_mut(&uid).unwrap();
+    *num_of_user -= 1;
+    if *num_of_user == 0 {
+        live_threads.num_per_user.remove(&uid);
+    }
+}
+
+/// Count a thread that has left the enclave, or has failed to enter it, as
+/// no longer taking a TCS
+pub fn dec_threads_in_enclave() {
+    LIVE_THREADS.lock().unwrap().num_in_enclave -= 1;
+}
@@ -101,6 +101,22 @@ pub fn do_sched_setaffinity(pid: pid_t, cpu_set: &CpuSet) -> Result<()> {
     Ok(())
 }
 
+/// Get the CPU and the NUMA node on which the calling thread is running.
+///
+/// Since a LibOS thread always runs on the same host thread, the result is
+/// consistent with the CPU affinity of the thread.
+pub fn do_getcpu() -> Result<(u32, u32)> {
+    let mut cpu = 0;
+    let mut node = 0;
+    try_libc!({
+        let mut retval = 0;
+        let sgx_status = occlum_ocall_getcpu(&mut retval, &mut cpu, &mut node);
+        assert!(sgx_status == sgx_status_t::SGX_SUCCESS);
+        retval
+    });
+    Ok((cpu, node))
+}
+
 pub fn do_sched_yield() {
     unsafe {
         let status = occlum_ocall_sched_yield();
@@ -122,4 +138,5 @@ extern "C" {
         mask: *const c_uchar,
     ) -> sgx_status_t;
     fn occlum_ocall_sched_yield() -> sgx_status_t;
+    fn occlum_ocall_getcpu(ret: *mut i32, cpu: *mut u32, node: *mut u32) -> sgx_status_t;
 }
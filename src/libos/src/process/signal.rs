@@ -0,0 +1,617 @@
+//! Signals.
+//!
+//! A thread that runs in the enclave cannot be interrupted. So a signal is
+//! delivered cooperatively: it is added to the pending signals of the target
+//! thread, which delivers its pending signals that are not blocked by its
+//! signal mask at the exit of every syscall, by running the handler of the
+//! signal before returning to the user, or doing the default action of the
+//! signal. A thread never delivers a signal while it blocks in a syscall.
+//!
+//! Instead, a signal that is neither blocked nor ignored interrupts the
+//! blocking waits of the thread, which fail with EINTR, so that the signal is
+//! delivered at the exit of the syscall. The interrupted syscall is restarted
+//! after the handler returns if the handler is set with SA_RESTART and the
+//! syscall is restartable, or at once if no handler runs.
+//!
+//! The default action of terminating the process kills all the threads of the
+//! process, and those of stopping or continuing the process are done on the
+//! whole process as well (see job_control).
+//!
+//! A handler is called by the syscall entry with the signal number and a
+//! siginfo_t, but without any ucontext_t, and there is no sigreturn: the state
+//! of the thread before the handler, i.e., its signal mask and the user stack
+//! to return to, is kept by the LibOS in a frame, which is restored when the
+//! handler returns. A handler that jumps out by siglongjmp leaves its frame
+//! behind, which is dropped at the delivery of a later signal once the thread
+//! is seen to be back on the stack above the frame.
+use super::*;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use util::mpx_util::{self, MpxReg};
+use util::waiter::Waiter;
+
+/// The largest signal number, including the real-time signals
+pub const SIGRTMAX: u32 = 64;
+
+/// A valid signal number
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SigNum(u32);
+
+impl SigNum {
+    /// Return None for the null signal, i.e., zero, which checks the target
+    /// without sending any signal
+    pub fn from_u32(num: u32) -> Result<Option<SigNum>> {
+        match num {
+            0 => Ok(None),
+            1..=SIGRTMAX => Ok(Some(SigNum(num))),
+            _ => return_errno!(EINVAL, "invalid signal number"),
+        }
+    }
+
+    pub fn as_u32(&self) -> u32 {
+        self.0
+    }
+}
+
+pub const SIGKILL: SigNum = SigNum(9);
+pub const SIGPIPE: SigNum = SigNum(13);
+pub const SIGCHLD: SigNum = SigNum(17);
+pub const SIGCONT: SigNum = SigNum(18);
+pub const SIGSTOP: SigNum = SigNum(19);
+pub const SIGTSTP: SigNum = SigNum(20);
+pub const SIGTTIN: SigNum = SigNum(21);
+pub const SIGTTOU: SigNum = SigNum(22);
+pub const SIGURG: SigNum = SigNum(23);
+pub const SIGXFSZ: SigNum = SigNum(25);
+pub const SIGWINCH: SigNum = SigNum(28);
+pub const SIGIO: SigNum = SigNum(29);
+pub const SIGSYS: SigNum = SigNum(31);
+
+/// A set of signals, in the same layout as the kernel's sigset_t
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SigSet(u64);
+
+impl SigSet {
+    pub fn from_u64(bits: u64) -> SigSet {
+        SigSet(bits)
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+
+    pub fn union(&self, other: SigSet) -> SigSet {
+        SigSet(self.0 | other.0)
+    }
+
+    pub fn difference(&self, other: SigSet) -> SigSet {
+        SigSet(self.0 & !other.0)
+    }
+
+    pub fn contains(&self, signum: SigNum) -> bool {
+        self.0 & SigSet::bit_of(signum) != 0
+    }
+
+    pub fn add(&mut self, signum: SigNum) {
+        self.0 |= SigSet::bit_of(signum);
+    }
+
+    pub fn remove(&mut self, signum: SigNum) {
+        self.0 &= !SigSet::bit_of(signum);
+    }
+
+    /// The signal of the lowest number in the set, if any
+    fn first(&self) -> Option<SigNum> {
+        if self.0 == 0 {
+            return None;
+        }
+        Some(SigNum(self.0.trailing_zeros() + 1))
+    }
+
+    fn bit_of(signum: SigNum) -> u64 {
+        1 << (signum.as_u32() - 1)
+    }
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SigMaskHow {
+    SIG_BLOCK = 0,
+    SIG_UNBLOCK = 1,
+    SIG_SETMASK = 2,
+}
+
+impl SigMaskHow {
+    pub fn from_u32(how: u32) -> Result<SigMaskHow> {
+        match how {
+            0 => Ok(SigMaskHow::SIG_BLOCK),
+            1 => Ok(SigMaskHow::SIG_UNBLOCK),
+            2 => Ok(SigMaskHow::SIG_SETMASK),
+            _ => return_errno!(EINVAL, "invalid how"),
+        }
+    }
+}
+
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+
+const SA_RESTART: u64 = 0x1000_0000;
+const SA_NODEFER: u64 = 0x4000_0000;
+const SA_RESETHAND: u64 = 0x8000_0000;
+
+/// The action of a signal, in the same layout as the kernel's sigaction
+#[repr(C)]
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct sigaction_t {
+    pub handler: usize,
+    pub flags: u64,
+    pub restorer: usize,
+    pub mask: u64,
+}
+
+/// The state of a thread before the handler of a signal runs, which is
+/// restored after the handler returns
+#[derive(Clone, Copy, Debug)]
+pub struct SigFrame {
+    sig_mask: SigSet,
+    // The user stack at the entry of the syscall after which the handler runs
+    user_rsp: usize,
+    // The syscall to restart after the handler returns, if any
+    restart: Option<SyscallRestart>,
+}
+
+/// A syscall that is interrupted by a signal and will be restarted
+#[derive(Clone, Copy, Debug)]
+pub struct SyscallRestart {
+    pub num: u32,
+    pub args: [isize; 6],
+    // The deadline of the timed wait of the syscall, which a restarted wait
+    // keeps instead of waiting for the whole timeout again
+    pub deadline: Option<FutexDeadline>,
+}
+
+pub type SigInterruptRef = Arc<SigInterrupt>;
+
+/// The state with which the signals sent to a thread interrupt its blocking
+/// waits, which is read by the waits without locking the thread
+#[derive(Debug, Default)]
+pub struct SigInterrupt {
+    // Whether any pending signal of the thread is neither blocked nor ignored
+    is_pending: AtomicBool,
+    // The waiter on which the thread is sleeping, if any
+    waiter: SgxMutex<Option<Waiter>>,
+}
+
+impl SigInterrupt {
+    fn set_pending(&self, is_pending: bool) {
+        self.is_pending.store(is_pending, Ordering::SeqCst);
+        if is_pending {
+            if let Some(waiter) = self.waiter.lock().unwrap().as_ref() {
+                waiter.interrupt();
+            }
+        }
+    }
+}
+
+thread_local! {
+    // The interrupt state of the current thread, which is set with the current
+    // process (see task.rs)
+    static CURRENT_SIG_INTERRUPT: RefCell<Option<SigInterruptRef>> = RefCell::new(None);
+    // The signals raised by the current thread on itself, which are made
+    // pending at the delivery
+    static RAISED_SIGNALS: Cell<SigSet> = Cell::new(SigSet::default());
+}
+
+/// Raise a signal on the current thread for an error of the current syscall,
+/// e.g., SIGPIPE for EPIPE, which is delivered at the exit of the syscall.
+///
+/// The current thread is not locked here, so the signal can be raised with
+/// the thread locked.
+pub fn raise_signal(signum: SigNum) {
+    RAISED_SIGNALS.with(|raised| {
+        let mut signals = raised.get();
+        signals.add(signum);
+        raised.set(signals);
+    });
+}
+
+pub(super) fn set_current_sig_interrupt(sig_interrupt: Option<SigInterruptRef>) {
+    CURRENT_SIG_INTERRUPT.with(|current| *current.borrow_mut() = sig_interrupt);
+}
+
+/// Whether the current thread has a pending signal that interrupts its
+/// blocking waits, i.e., one that is neither blocked nor ignored
+pub fn is_sig_pending() -> bool {
+    CURRENT_SIG_INTERRUPT.with(|current| match current.borrow().as_ref() {
+        Some(sig_interrupt) => sig_interrupt.is_pending.load(Ordering::SeqCst),
+        None => false,
+    })
+}
+
+/// Run the blocking wait of the current thread on the waiter, whose sleeps are
+/// made to return by the signals that interrupt the thread, as checked by
+/// is_sig_pending
+pub fn wait_interruptibly<T>(waiter: &Waiter, wait_fn: impl FnOnce() -> T) -> T {
+    let sig_interrupt = match CURRENT_SIG_INTERRUPT.with(|current| current.borrow().clone()) {
+        Some(sig_interrupt) => sig_interrupt,
+        // The threads of the LibOS itself are never interrupted
+        None => return wait_fn(),
+    };
+    *sig_interrupt.waiter.lock().unwrap() = Some(waiter.clone());
+    let ret = wait_fn();
+    *sig_interrupt.waiter.lock().unwrap() = None;
+    ret
+}
+
+// Update whether the signals of the thread interrupt its waits, which must be
+// done whenever its pending signals or its signal mask changes
+fn update_sig_interrupt(thread: &Process) {
+    let unblocked = thread.sig_pending.difference(thread.sig_mask);
+    let is_pending = unblocked != SigSet::default() && {
+        let sig_actions = thread.sig_actions.lock().unwrap();
+        (1..=SIGRTMAX)
+            .map(SigNum)
+            .filter(|signum| unblocked.contains(*signum))
+            .any(|signum| match sig_actions.get(signum).handler {
+                SIG_IGN => false,
+                SIG_DFL => DefaultAction::of(signum) == DefaultAction::Terminate,
+                _ => true,
+            })
+    };
+    thread.sig_interrupt.set_pending(is_pending);
+}
+
+pub type SigActionsRef = Arc<SgxMutex<SigActions>>;
+
+/// The actions of the signals, which are shared by the threads of a process
+#[derive(Clone, Debug)]
+pub struct SigActions {
+    actions: Vec<sigaction_t>,
+}
+
+impl Default for SigActions {
+    fn default() -> SigActions {
+        SigActions {
+            actions: vec![Default::default(); SIGRTMAX as usize],
+        }
+    }
+}
+
+impl SigActions {
+    pub fn get(&self, signum: SigNum) -> sigaction_t {
+        self.actions[signum.as_u32() as usize - 1]
+    }
+
+    pub fn set(&mut self, signum: SigNum, action: sigaction_t) {
+        self.actions[signum.as_u32() as usize - 1] = action;
+    }
+
+    /// The actions of a new program, in which the handled signals are reset
+    /// to the default actions, while the ignored ones stay ignored
+    pub fn reset_for_exec(&self) -> SigActions {
+        let actions = self
+            .actions
+            .iter()
+            .map(|action| match action.handler {
+                SIG_IGN => *action,
+                _ => Default::default(),
+            })
+            .collect();
+        SigActions { actions }
+    }
+}
+
+/// The default action of a signal, i.e., the one done if it is not handled
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DefaultAction {
+    Terminate,
+    Ignore,
+    Stop,
+    Continue,
+}
+
+impl DefaultAction {
+    fn of(signum: SigNum) -> DefaultAction {
+        match signum {
+            SIGCHLD | SIGURG | SIGWINCH => DefaultAction::Ignore,
+            SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => DefaultAction::Stop,
+            SIGCONT => DefaultAction::Continue,
+            // The real-time signals terminate the process by default, too
+            _ => DefaultAction::Terminate,
+        }
+    }
+}
+
+/// Change the action of a signal for the process, returning the old one.
+///
+/// The actions of SIGKILL and SIGSTOP cannot be changed. A handler must be a
+/// cfi_label in the code of the program, as it is called by the LibOS.
+pub fn do_rt_sigaction(signum: SigNum, action: Option<sigaction_t>) -> Result<sigaction_t> {
+    info!("rt_sigaction: signum: {:?}, action: {:?}", signum, action);
+    if action.is_some() && (signum == SIGKILL || signum == SIGSTOP) {
+        return_errno!(EINVAL, "the action of SIGKILL or SIGSTOP cannot be changed");
+    }
+    let current_ref = get_current();
+    if let Some(action) = action {
+        if action.handler != SIG_DFL && action.handler != SIG_IGN {
+            check_sig_handler(&current_ref, action.handler)?;
+        }
+    }
+    let sig_actions_ref = current_ref.lock().unwrap().get_sig_actions().clone();
+    let mut sig_actions = sig_actions_ref.lock().unwrap();
+    let old_action = sig_actions.get(signum);
+    if let Some(mut action) = action {
+        // The handler cannot block SIGKILL or SIGSTOP, either
+        action.mask = SigSet(action.mask).difference(unblockable()).as_u64();
+        sig_actions.set(signum, action);
+    }
+    Ok(old_action)
+}
+
+/// Change the signal mask of the current thread, returning the old one.
+///
+/// The signal mask is per thread, i.e., the signal masks of the other threads
+/// of the process are not changed. SIGKILL and SIGSTOP can never be blocked,
+/// so they are silently removed from the new mask.
+pub fn do_rt_sigprocmask(how: SigMaskHow, set: Option<SigSet>) -> Result<SigSet> {
+    info!("rt_sigprocmask: how: {:?}, set: {:?}", how, set);
+    let current_ref = get_current();
+    let mut current = current_ref.lock().unwrap();
+    let old_mask = current.get_sig_mask();
+    let set = match set {
+        Some(set) => set,
+        None => return Ok(old_mask),
+    };
+    let new_mask = match how {
+        SigMaskHow::SIG_BLOCK => old_mask.union(set),
+        SigMaskHow::SIG_UNBLOCK => old_mask.difference(set),
+        SigMaskHow::SIG_SETMASK => set,
+    };
+    // The pending signals that are unblocked are delivered at the exit of the
+    // syscall
+    current.set_sig_mask(new_mask.difference(unblockable()));
+    update_sig_interrupt(&current);
+    Ok(old_mask)
+}
+
+// The handler is checked as the syscall entry checks it before calling it,
+// i.e., its first 8 bytes must be within the bounds in %bnd2
+fn check_sig_handler(current_ref: &ProcessRef, handler: usize) -> Result<()> {
+    let vm_ref = current_ref.lock().unwrap().get_vm().clone();
+    let is_in_code = vm_ref
+        .lock()
+        .unwrap()
+        .get_elf_ranges()
+        .iter()
+        .any(|range| range.contains(handler) && handler + 8 <= range.end());
+    if !is_in_code {
+        return_errno!(EINVAL, "the handler is not in the code of the program");
+    }
+    let label = unsafe { std::ptr::read_unaligned(handler as *const usize) };
+    let (lower, upper) = mpx_util::mpx_bounds(MpxReg::BND2);
+    if label < lower || label > upper {
+        return_errno!(EINVAL, "the handler is not a cfi_label");
+    }
+    Ok(())
+}
+
+fn unblockable() -> SigSet {
+    SigSet(SigSet::bit_of(SIGKILL) | SigSet::bit_of(SIGSTOP))
+}
+
+/// Send a signal to the process `pid`
+pub fn do_kill(pid: pid_t, signum: Option<SigNum>) -> Result<()> {
+    info!("kill: pid: {}, signum: {:?}", pid, signum);
+    let process_ref = process_table::get(pid).map_err(|_| errno!(ESRCH, "no such process"))?;
+    // A zombie can still be sent signals, which have no effect
+    if process_ref.lock().unwrap().get_status() == Status::ZOMBIE {
+        return Ok(());
+    }
+    match signum {
+        Some(signum) => {
+            let thread_ref = choose_thread_of_process(&process_ref, signum);
+            send_signal_to_thread(&thread_ref, Some(signum))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Choose the thread of the process to which a signal to the process is sent,
+/// which is the first one that does not block the signal, or the main thread
+/// if all of them block it
+fn choose_thread_of_process(process_ref: &ProcessRef, signum: SigNum) -> ProcessRef {
+    let tgid = process_ref.lock().unwrap().get_pid();
+    process_table::get_all()
+        .into_iter()
+        .find(|thread_ref| {
+            let thread = thread_ref.lock().unwrap();
+            thread.get_pid() == tgid
+                && thread.get_status() != Status::ZOMBIE
+                && !thread.get_sig_mask().contains(signum)
+        })
+        .unwrap_or_else(|| process_ref.clone())
+}
+
+/// Send a signal to the thread `tid`, which must be in the thread group `tgid`
+pub fn do_tgkill(tgid: pid_t, tid: pid_t, signum: Option<SigNum>) -> Result<()> {
+    info!("tgkill: tgid: {}, tid: {}, signum: {:?}", tgid, tid, signum);
+    let thread_ref = get_live_thread(tid)?;
+    if thread_ref.lock().unwrap().get_pid() != tgid {
+        return_errno!(ESRCH, "the thread is not in the thread group");
+    }
+    send_signal_to_thread(&thread_ref, signum)
+}
+
+/// Send a signal to the thread `tid`
+pub fn do_tkill(tid: pid_t, signum: Option<SigNum>) -> Result<()> {
+    info!("tkill: tid: {}, signum: {:?}", tid, signum);
+    let thread_ref = get_live_thread(tid)?;
+    send_signal_to_thread(&thread_ref, signum)
+}
+
+fn get_live_thread(tid: pid_t) -> Result<ProcessRef> {
+    let thread_ref = process_table::get(tid).map_err(|_| errno!(ESRCH, "no such thread"))?;
+    if thread_ref.lock().unwrap().get_status() == Status::ZOMBIE {
+        return_errno!(ESRCH, "the thread has exited");
+    }
+    Ok(thread_ref)
+}
+
+fn send_signal_to_thread(thread_ref: &ProcessRef, signum: Option<SigNum>) -> Result<()> {
+    let signum = match signum {
+        Some(signum) => signum,
+        // The null signal only checks that the thread exists
+        None => return Ok(()),
+    };
+    let mut thread = thread_ref.lock().unwrap();
+    match signum {
+        SIGKILL | SIGSTOP => {
+            drop(thread);
+            send_job_control_signal(thread_ref, signum);
+            return Ok(());
+        }
+        SIGCONT => {
+            // A stopped process is continued even if SIGCONT is blocked or
+            // handled, as a stopped thread cannot deliver any signal
+            drop(thread);
+            send_job_control_signal(thread_ref, signum);
+            thread = thread_ref.lock().unwrap();
+        }
+        SIGTSTP | SIGTTIN | SIGTTOU => {
+            // A stopped thread cannot deliver any signal, so the process is
+            // stopped at once if the signal is not blocked or handled
+            let action = thread.get_sig_actions().lock().unwrap().get(signum);
+            if action.handler == SIG_DFL && !thread.get_sig_mask().contains(signum) {
+                drop(thread);
+                send_job_control_signal(thread_ref, signum);
+                return Ok(());
+            }
+        }
+        _ => {}
+    }
+    thread.sig_pending.add(signum);
+    update_sig_interrupt(&thread);
+    Ok(())
+}
+
+/// Deliver the pending signals of the current thread that are not blocked,
+/// which is done at the exit of every syscall.
+///
+/// The handler of the first handled signal, if any, will run before returning
+/// to the user, with the signal of the handler and the mask of its action
+/// blocked until the handler returns (see return_from_sig_handler).
+/// The other signals are done their default actions. Return the termination
+/// status if the process is killed by a signal, with which the current thread
+/// must exit.
+///
+/// The syscall interrupted by the signals, if any, is taken by the handler to
+/// be restarted after it returns if the handler is set with SA_RESTART, or
+/// dropped otherwise. It is left as is if no handler runs.
+pub fn deliver_pending_signals(restart: &mut Option<SyscallRestart>) -> Option<TermStatus> {
+    let current_ref = get_current();
+    let raised = RAISED_SIGNALS.with(|raised| raised.replace(SigSet::default()));
+    if raised != SigSet::default() {
+        let mut current = current_ref.lock().unwrap();
+        current.sig_pending = current.sig_pending.union(raised);
+    }
+    loop {
+        let (signum, job_control) = {
+            let mut current = current_ref.lock().unwrap();
+            let signum = match current.sig_pending.difference(current.sig_mask).first() {
+                Some(signum) => signum,
+                None => {
+                    update_sig_interrupt(&current);
+                    return None;
+                }
+            };
+            current.sig_pending.remove(signum);
+            let action = {
+                let mut sig_actions = current.sig_actions.lock().unwrap();
+                let action = sig_actions.get(signum);
+                if action.handler != SIG_IGN && action.flags & SA_RESETHAND != 0 {
+                    sig_actions.set(signum, Default::default());
+                }
+                action
+            };
+            match action.handler {
+                SIG_IGN => continue,
+                SIG_DFL => (signum, current.get_job_control().clone()),
+                handler => {
+                    let old_mask = current.sig_mask;
+                    let mut new_mask = old_mask.union(SigSet(action.mask));
+                    if action.flags & SA_NODEFER == 0 {
+                        new_mask.add(signum);
+                    }
+                    // The frames above the current user stack are left by the
+                    // handlers that never return
+                    let user_rsp = current.get_task().get_syscall_user_rsp();
+                    current.sig_frames.retain(|frame| frame.user_rsp > user_rsp);
+                    current.sig_frames.push(SigFrame {
+                        sig_mask: old_mask,
+                        user_rsp,
+                        restart: restart.take().filter(|_| action.flags & SA_RESTART != 0),
+                    });
+                    current.sig_mask = new_mask.difference(unblockable());
+                    current
+                        .get_task_mut()
+                        .set_sig_handler(handler, signum.as_u32() as usize);
+                    update_sig_interrupt(&current);
+                    return None;
+                }
+            }
+        };
+        match DefaultAction::of(signum) {
+            DefaultAction::Terminate => {
+                // The whole process is killed, not only the current thread
+                job_control.kill(signum);
+                return Some(TermStatus::Killed(signum.as_u32() as u8));
+            }
+            // The current thread is parked at its next syscall
+            DefaultAction::Stop => send_job_control_signal(&current_ref, signum),
+            // The process has been continued when the signal is sent
+            DefaultAction::Ignore | DefaultAction::Continue => {}
+        }
+    }
+}
+
+/// Restore the state of the current thread after the handler of a signal
+/// returns, returning the user stack to return to and the syscall to restart,
+/// if any
+pub fn return_from_sig_handler() -> (usize, Option<SyscallRestart>) {
+    let current_ref = get_current();
+    let mut current = current_ref.lock().unwrap();
+    match current.sig_frames.pop() {
+        Some(frame) => {
+            current.sig_mask = frame.sig_mask;
+            update_sig_interrupt(&current);
+            // The next handler, if any, returns to the same user stack
+            current.get_task_mut().set_syscall_user_rsp(frame.user_rsp);
+            (frame.user_rsp, frame.restart)
+        }
+        None => (current.get_task().get_syscall_user_rsp(), None),
+    }
+}
+
+/// Do the default action of a signal of job control, which acts on the whole
+/// process that the thread is in
+fn send_job_control_signal(thread_ref: &ProcessRef, signum: SigNum) {
+    let (job_control, tgid) = {
+        let thread = thread_ref.lock().unwrap();
+        (thread.get_job_control().clone(), thread.get_pid())
+    };
+    let is_changed = match signum {
+        SIGKILL => {
+            // The parent is notified when the process exits
+            job_control.kill(signum);
+            false
+        }
+        SIGCONT => job_control.cont(),
+        _ => job_control.stop(signum),
+    };
+    if is_changed {
+        if let Ok(leader_ref) = process_table::get(tgid) {
+            notify_parent_of_state_change(&leader_ref);
+        }
+    }
+}
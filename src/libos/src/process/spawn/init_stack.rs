@@ -213,7 +213,8 @@ pub enum AuxKey {
     AT_SYSINFO = 32,
 
     /* Occlum-specific entries */
-    AT_OCCLUM_ENTRY = 48, /* the entry point of Occlum, i.e., syscall */
+    AT_OCCLUM_ENTRY = 48,     /* the entry point of Occlum, i.e., syscall */
+    AT_OCCLUM_VDSO_TIME = 49, /* the time page for the vDSO-style fast path */
 }
 
 #[derive(Clone, Default, Debug)]
@@ -5,10 +5,12 @@ use std::path::Path;
 use std::sgxfs::SgxFile;
 
 use super::fs::{
-    CreationFlags, File, FileDesc, FileTable, INodeExt, StdinFile, StdoutFile, ROOT_INODE,
+    CreationFlags, Cwd, File, FileDesc, FileTable, INodeExt, StatMode, StdinFile, StdoutFile,
+    ROOT_INODE,
 };
 use super::misc::ResourceLimitsRef;
 use super::vm::{ProcessVM, ProcessVMBuilder};
+use rcore_fs::vfs::FileType;
 
 pub use self::elf_file::{ElfFile, ProgramHeaderExt};
 use self::init_stack::{AuxKey, AuxTable};
@@ -22,10 +24,12 @@ pub fn do_spawn(
     argv: &[CString],
     envp: &[CString],
     file_actions: &[FileAction],
+    spawn_attr: &SpawnAttr,
     parent_ref: &ProcessRef,
 ) -> Result<pid_t> {
-    let (new_tid, new_process_ref) = new_process(elf_path, argv, envp, file_actions, parent_ref)?;
-    task::enqueue_and_exec_task(new_tid, new_process_ref);
+    let (new_tid, new_process_ref) =
+        new_process(elf_path, argv, envp, file_actions, spawn_attr, parent_ref)?;
+    task::enqueue_and_exec_task(new_tid, new_process_ref)?;
     Ok(new_tid)
 }
 
@@ -34,9 +38,11 @@ pub fn do_spawn_without_exec(
     argv: &[CString],
     envp: &[CString],
     file_actions: &[FileAction],
+    spawn_attr: &SpawnAttr,
     parent_ref: &ProcessRef,
 ) -> Result<pid_t> {
-    let (new_tid, new_process_ref) = new_process(elf_path, argv, envp, file_actions, parent_ref)?;
+    let (new_tid, new_process_ref) =
+        new_process(elf_path, argv, envp, file_actions, spawn_attr, parent_ref)?;
     task::enqueue_task(new_tid, new_process_ref);
     Ok(new_tid)
 }
@@ -46,8 +52,12 @@ fn new_process(
     argv: &[CString],
     envp: &[CString],
     file_actions: &[FileAction],
+    spawn_attr: &SpawnAttr,
     parent_ref: &ProcessRef,
 ) -> Result<(pid_t, ProcessRef)> {
+    // The cwd is resolved in the parent before anything is done for the child
+    let cwd = init_cwd(parent_ref, spawn_attr)?;
+    let credentials = init_credentials(elf_path, parent_ref)?;
     let elf_buf = load_elf_to_vec(elf_path, parent_ref)
         .cause_err(|e| errno!(e.errno(), "cannot load the executable"))?;
     let ldso_path = "/lib/ld-musl-x86_64.so.1";
@@ -60,9 +70,9 @@ fn new_process(
         ElfFile::new(&ldso_elf_buf).cause_err(|e| errno!(e.errno(), "invalid ld.so"))?;
 
     let (new_pid, new_process_ref) = {
-        let cwd = parent_ref.lock().unwrap().get_cwd().to_owned();
         let vm = init_vm::do_init(&exec_elf_file, &ldso_elf_file)?;
-        let auxtbl = init_auxtbl(&vm, &exec_elf_file)?;
+        let auxtbl = init_auxtbl(&vm, &exec_elf_file, &credentials)?;
+        time::init_vdso_time_page(vm.get_vdso_range())?;
 
         // Notify debugger to load the symbols from elf file
         let ldso_elf_base = vm.get_elf_ranges()[1].start() as u64;
@@ -110,9 +120,49 @@ fn new_process(
             let files = init_files(parent_ref, file_actions)?;
             Arc::new(SgxMutex::new(files))
         };
-        let rlimits_ref = Default::default();
+        // The resource limits are inherited from the parent, but not shared
+        let rlimits_ref = {
+            let rlimits = *parent_ref.lock().unwrap().get_rlimits().lock().unwrap();
+            Arc::new(SgxMutex::new(rlimits))
+        };
         Process::new(&cwd, elf_path, task, vm_ref, files_ref, rlimits_ref)?
     };
+    // The syscall filters, the mount namespace, the signal mask and the
+    // ignored signals are inherited from the parent
+    let (syscall_filters, mnt_ns, sig_mask, sig_actions) = {
+        let parent = parent_ref.lock().unwrap();
+        let sig_actions = parent.sig_actions.lock().unwrap().reset_for_exec();
+        (
+            parent.syscall_filters.clone(),
+            parent.mnt_ns.clone(),
+            parent.sig_mask,
+            sig_actions,
+        )
+    };
+    let live_uid = credentials.ruid();
+    {
+        let mut new_process = new_process_ref.lock().unwrap();
+        new_process.syscall_filters = syscall_filters;
+        new_process.mnt_ns = mnt_ns;
+        new_process.sig_mask = sig_mask;
+        new_process.sig_actions = Arc::new(SgxMutex::new(sig_actions));
+        new_process.credentials = Arc::new(SgxMutex::new(credentials));
+    }
+    // The umask is inherited from the parent, unless it is set at spawn
+    let umask = spawn_attr
+        .umask
+        .unwrap_or_else(|| parent_ref.lock().unwrap().get_umask());
+    new_process_ref.lock().unwrap().set_umask(umask);
+    // The new process is counted as a live thread, as the new threads are
+    let nproc_limit = parent_ref
+        .lock()
+        .unwrap()
+        .get_rlimits()
+        .lock()
+        .unwrap()
+        .get_nproc_limit();
+    process_table::inc_live_threads(live_uid, nproc_limit)?;
+    new_process_ref.lock().unwrap().live_uid = live_uid;
     parent_adopts_new_child(&parent_ref, &new_process_ref);
     process_table::put(new_pid, new_process_ref.clone());
     let new_tid = new_pid;
@@ -133,12 +183,65 @@ pub enum FileAction {
     Close(FileDesc),
 }
 
+/// The attributes of the new process that are set at spawn, before the new
+/// process starts running.
+#[derive(Debug, Default)]
+pub struct SpawnAttr {
+    /// The initial working directory. Inherited from the parent if None.
+    pub cwd: Option<String>,
+    /// The initial file mode creation mask. Inherited from the parent if None.
+    pub umask: Option<u32>,
+}
+
+fn init_cwd(parent_ref: &ProcessRef, spawn_attr: &SpawnAttr) -> Result<Cwd> {
+    let parent = parent_ref.lock().unwrap();
+    let cwd = match spawn_attr.cwd {
+        Some(ref cwd) => cwd,
+        None => {
+            let cwd_path = parent.get_cwd_path();
+            return Ok(Cwd::new(&cwd_path, parent.get_cwd_inode().clone()));
+        }
+    };
+    let inode = parent
+        .lookup_inode_follow(cwd)
+        .cause_err(|e| errno!(e.errno(), "cannot find the cwd"))?;
+    if inode.metadata()?.type_ != FileType::Dir {
+        return_errno!(ENOTDIR, "the cwd is not a directory");
+    }
+    Ok(Cwd::new(&parent.convert_to_abs_path(cwd), inode))
+}
+
+/// The credentials are copied from the parent, and then updated for the
+/// executable, e.g., if it is set-user-ID or set-group-ID
+fn init_credentials(elf_path: &str, parent_ref: &ProcessRef) -> Result<Credentials> {
+    let parent = parent_ref.lock().unwrap();
+    let mut credentials = parent.get_credentials().lock().unwrap().clone();
+    let metadata = parent
+        .lookup_inode_follow(elf_path)
+        .cause_err(|e| errno!(e.errno(), "cannot find the ELF"))?
+        .metadata()?;
+    let mode = StatMode::from_bits_truncate(metadata.mode as u32);
+    let uid = if mode.contains(StatMode::SET_UID) {
+        Some(metadata.uid as uid_t)
+    } else {
+        None
+    };
+    // Like Linux, the set-group-ID bit without the group-execute bit is ignored
+    let gid = if mode.contains(StatMode::SET_GID | StatMode::GROUP_EXEC) {
+        Some(metadata.gid as gid_t)
+    } else {
+        None
+    };
+    credentials.update_for_exec(uid, gid);
+    Ok(credentials)
+}
+
 fn load_elf_to_vec(elf_path: &str, parent_ref: &ProcessRef) -> Result<Vec<u8>> {
     #[rustfmt::skip]
     parent_ref
         .lock()
         .unwrap()
-        .lookup_inode(elf_path)
+        .lookup_inode_follow(elf_path)
             .map_err(|e| errno!(e.errno(), "cannot find the ELF"))?
         .read_as_vec()
             .map_err(|e| errno!(e.errno(), "failed to read the executable ELF"))
@@ -149,8 +252,16 @@ fn init_files(parent_ref: &ProcessRef, file_actions: &[FileAction]) -> Result<Fi
     let parent = parent_ref.lock().unwrap();
     let should_inherit_file_table = parent.get_pid() > 0;
     if should_inherit_file_table {
+        // Fast path: without file actions, the close-on-spawn files are skipped
+        // when cloning, instead of being cloned and closed (see close_on_spawn)
+        if file_actions.is_empty() {
+            return Ok(parent.get_files().lock().unwrap().clone_for_spawn());
+        }
+
         // Fork: clone file table
         let mut cloned_file_table = parent.get_files().lock().unwrap().clone();
+        // The parent is not kept locked while finishing the opens, see do_open
+        drop(parent);
         // Perform file actions to modify the cloned file table
         for file_action in file_actions {
             match file_action {
@@ -160,15 +271,20 @@ fn init_files(parent_ref: &ProcessRef, file_actions: &[FileAction]) -> Result<Fi
                     oflag,
                     fd,
                 } => {
-                    let file = parent.open_file(path.as_str(), oflag, mode)?;
+                    let pending_open =
+                        parent_ref
+                            .lock()
+                            .unwrap()
+                            .prepare_open(path.as_str(), oflag, mode)?;
+                    let file = pending_open.finish()?;
                     let file_ref: Arc<Box<dyn File>> = Arc::new(file);
                     let creation_flags = CreationFlags::from_bits_truncate(oflag);
-                    cloned_file_table.put_at(fd, file_ref, creation_flags.must_close_on_spawn());
+                    cloned_file_table.put_at(fd, file_ref, creation_flags.must_close_on_spawn())?;
                 }
                 &FileAction::Dup2(old_fd, new_fd) => {
                     let file = cloned_file_table.get(old_fd)?;
                     if old_fd != new_fd {
-                        cloned_file_table.put_at(new_fd, file, false);
+                        cloned_file_table.put_at(new_fd, file, false)?;
                     }
                 }
                 &FileAction::Close(fd) => {
@@ -181,28 +297,38 @@ fn init_files(parent_ref: &ProcessRef, file_actions: &[FileAction]) -> Result<Fi
         cloned_file_table.close_on_spawn();
         return Ok(cloned_file_table);
     }
-    drop(parent);
-
     // But, for init process, we initialize file table for it
     let mut file_table = FileTable::new();
+    let max_fds = parent.get_rlimits().lock().unwrap().get_nofile_limit();
+    file_table.set_max_fds(max_fds);
+    drop(parent);
+
     let stdin: Arc<Box<dyn File>> = Arc::new(Box::new(StdinFile::new()));
     let stdout: Arc<Box<dyn File>> = Arc::new(Box::new(StdoutFile::new()));
     // TODO: implement and use a real stderr
     let stderr = stdout.clone();
-    file_table.put(stdin, false);
-    file_table.put(stdout, false);
-    file_table.put(stderr, false);
+    file_table.put(stdin, false)?;
+    file_table.put(stdout, false)?;
+    file_table.put(stderr, false)?;
     Ok(file_table)
 }
 
-fn init_auxtbl(process_vm: &ProcessVM, exec_elf_file: &ElfFile) -> Result<AuxTable> {
+fn init_auxtbl(
+    process_vm: &ProcessVM,
+    exec_elf_file: &ElfFile,
+    credentials: &Credentials,
+) -> Result<AuxTable> {
+    let (ruid, euid, _) = credentials.uids();
+    let (rgid, egid, _) = credentials.gids();
+    // The secure mode is set for the set-user-ID and set-group-ID executables
+    let secure = ruid != euid || rgid != egid;
     let mut auxtbl = AuxTable::new();
     auxtbl.set(AuxKey::AT_PAGESZ, 4096)?;
-    auxtbl.set(AuxKey::AT_UID, 0)?;
-    auxtbl.set(AuxKey::AT_GID, 0)?;
-    auxtbl.set(AuxKey::AT_EUID, 0)?;
-    auxtbl.set(AuxKey::AT_EGID, 0)?;
-    auxtbl.set(AuxKey::AT_SECURE, 0)?;
+    auxtbl.set(AuxKey::AT_UID, ruid as u64)?;
+    auxtbl.set(AuxKey::AT_GID, rgid as u64)?;
+    auxtbl.set(AuxKey::AT_EUID, euid as u64)?;
+    auxtbl.set(AuxKey::AT_EGID, egid as u64)?;
+    auxtbl.set(AuxKey::AT_SECURE, secure as u64)?;
     auxtbl.set(AuxKey::AT_SYSINFO, 0)?;
 
     let exec_elf_base = process_vm.get_elf_ranges()[0].start() as u64;
@@ -220,6 +346,9 @@ fn init_auxtbl(process_vm: &ProcessVM, exec_elf_file: &ElfFile) -> Result<AuxTab
 
     let syscall_addr = __occlum_syscall as *const () as u64;
     auxtbl.set(AuxKey::AT_OCCLUM_ENTRY, syscall_addr)?;
+
+    let vdso_time_addr = process_vm.get_vdso_range().start() as u64;
+    auxtbl.set(AuxKey::AT_OCCLUM_VDSO_TIME, vdso_time_addr)?;
     // TODO: init AT_EXECFN
     // auxtbl.set_val(AuxKey::AT_EXECFN, "program_name")?;
 
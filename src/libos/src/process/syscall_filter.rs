@@ -0,0 +1,221 @@
+/// Seccomp-style syscall filters.
+///
+/// A process installs a filter, which is a set of rules that map syscall
+/// numbers and argument ranges to actions, with the Occlum-specific
+/// set_syscall_filter syscall. Each syscall is checked against the filters of
+/// the current process before it is handled. Like seccomp, the filters are
+/// inherited by new threads and spawned processes, and can never be removed,
+/// so a filter can only make the process more restricted.
+use super::signal::SIGSYS;
+use super::*;
+
+/// Allow the syscall
+pub const SYSCALL_FILTER_RET_ALLOW: u32 = 0x7fff_0000;
+/// Fail the syscall with the errno in the lower 16 bits
+pub const SYSCALL_FILTER_RET_ERRNO: u32 = 0x0005_0000;
+/// Terminate the process
+pub const SYSCALL_FILTER_RET_KILL: u32 = 0x8000_0000;
+
+const SYSCALL_FILTER_RET_ACTION_MASK: u32 = 0xffff_0000;
+const SYSCALL_FILTER_RET_DATA_MASK: u32 = 0x0000_ffff;
+
+/// The maximum number of rules in a filter
+const MAX_RULES_PER_FILTER: usize = 4096;
+/// The maximum number of filters that can be installed on a process
+const MAX_FILTERS: usize = 32;
+/// The largest argument index, as a syscall has six arguments at most
+const MAX_ARG_INDEX: i32 = 5;
+
+/// The termination status of a process that is killed by a filter, i.e., the
+/// process is terminated by SIGSYS
+pub const SYSCALL_FILTER_KILL_STATUS: TermStatus = TermStatus::Killed(31);
+
+/// A filter rule, as passed by the user
+#[repr(C)]
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy)]
+pub struct syscall_filter_rule_t {
+    /// The syscall number
+    nr: u32,
+    /// The index of the argument to check, or -1 to match any arguments
+    arg_index: i32,
+    /// The inclusive range of the argument
+    arg_min: u64,
+    arg_max: u64,
+    /// The action, which is one of SYSCALL_FILTER_RET_*
+    action: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyscallFilterAction {
+    Allow,
+    Errno(Errno),
+    Kill,
+}
+
+impl SyscallFilterAction {
+    pub fn from_u32(raw: u32) -> Result<SyscallFilterAction> {
+        let data = raw & SYSCALL_FILTER_RET_DATA_MASK;
+        let action = match raw & SYSCALL_FILTER_RET_ACTION_MASK {
+            SYSCALL_FILTER_RET_ALLOW => SyscallFilterAction::Allow,
+            SYSCALL_FILTER_RET_ERRNO => {
+                if data < Errno::EPERM as u32 || data > Errno::EHWPOISON as u32 {
+                    return_errno!(EINVAL, "invalid errno for the filter action");
+                }
+                SyscallFilterAction::Errno(Errno::from(data))
+            }
+            SYSCALL_FILTER_RET_KILL => SyscallFilterAction::Kill,
+            _ => return_errno!(EINVAL, "invalid filter action"),
+        };
+        Ok(action)
+    }
+
+    /// The action with a higher precedence takes effect when filters disagree
+    fn precedence(&self) -> u32 {
+        match self {
+            SyscallFilterAction::Allow => 0,
+            SyscallFilterAction::Errno(_) => 1,
+            SyscallFilterAction::Kill => 2,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SyscallFilter {
+    rules: Vec<SyscallFilterRule>,
+    default_action: SyscallFilterAction,
+}
+
+#[derive(Debug)]
+struct SyscallFilterRule {
+    nr: u32,
+    // The index and the inclusive range of the argument to check
+    arg: Option<(usize, u64, u64)>,
+    action: SyscallFilterAction,
+}
+
+impl SyscallFilter {
+    pub fn new(
+        raw_rules: &[syscall_filter_rule_t],
+        default_action: SyscallFilterAction,
+    ) -> Result<SyscallFilter> {
+        if raw_rules.len() > MAX_RULES_PER_FILTER {
+            return_errno!(EINVAL, "too many rules in the filter");
+        }
+        let rules = raw_rules
+            .iter()
+            .map(|raw_rule| {
+                let arg = match raw_rule.arg_index {
+                    -1 => None,
+                    0..=MAX_ARG_INDEX => {
+                        if raw_rule.arg_min > raw_rule.arg_max {
+                            return_errno!(EINVAL, "invalid argument range");
+                        }
+                        Some((
+                            raw_rule.arg_index as usize,
+                            raw_rule.arg_min,
+                            raw_rule.arg_max,
+                        ))
+                    }
+                    _ => return_errno!(EINVAL, "invalid argument index"),
+                };
+                Ok(SyscallFilterRule {
+                    nr: raw_rule.nr,
+                    arg,
+                    action: SyscallFilterAction::from_u32(raw_rule.action)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(SyscallFilter {
+            rules,
+            default_action,
+        })
+    }
+
+    /// Get the action of the first matching rule, or the default action if no
+    /// rule matches
+    fn evaluate(&self, num: u32, args: &[isize; 6]) -> SyscallFilterAction {
+        self.rules
+            .iter()
+            .find(|rule| {
+                rule.nr == num
+                    && rule.arg.map_or(true, |(idx, min, max)| {
+                        let arg = args[idx] as u64;
+                        min <= arg && arg <= max
+                    })
+            })
+            .map_or(self.default_action, |rule| rule.action)
+    }
+}
+
+/// Install a filter on the current process
+pub fn do_set_syscall_filter(rules: &[syscall_filter_rule_t], default_action: u32) -> Result<()> {
+    info!(
+        "set_syscall_filter: rules: {:?}, default_action: {:#x}",
+        rules, default_action
+    );
+    let default_action = SyscallFilterAction::from_u32(default_action)?;
+    let filter = SyscallFilter::new(rules, default_action)?;
+
+    let current_ref = get_current();
+    let mut current = current_ref.lock().unwrap();
+    if current.syscall_filters.len() >= MAX_FILTERS {
+        return_errno!(ENOMEM, "too many filters");
+    }
+    // The filters are copied on write, as they are shared with the cache and
+    // the new threads
+    let mut filters = (*current.syscall_filters).clone();
+    filters.push(Arc::new(filter));
+    current.syscall_filters = Arc::new(filters);
+    CACHED_FILTERS.with(|cached| cached.replace(Some(current.syscall_filters.clone())));
+    Ok(())
+}
+
+/// Check the syscall against the filters of the current process.
+///
+/// All the filters are evaluated. The action with the highest precedence
+/// takes effect. Among the actions of the same precedence, the one of the most
+/// recently installed filter takes effect.
+pub fn filter_syscall(num: u32, args: &[isize; 6]) -> SyscallFilterAction {
+    CACHED_FILTERS.with(|cached| {
+        let mut cached = cached.borrow_mut();
+        let filters =
+            cached.get_or_insert_with(|| get_current().lock().unwrap().syscall_filters.clone());
+        filters
+            .iter()
+            .rev()
+            .fold(SyscallFilterAction::Allow, |action, filter| {
+                let new_action = filter.evaluate(num, args);
+                if new_action.precedence() > action.precedence() {
+                    new_action
+                } else {
+                    action
+                }
+            })
+    })
+}
+
+/// Kill the process of the current thread, whose syscall is denied by the
+/// KILL action of a filter, returning the termination status with which the
+/// current thread must exit. The other threads of the process exit at their
+/// next syscalls.
+pub fn kill_by_syscall_filter() -> TermStatus {
+    let job_control = get_current().lock().unwrap().get_job_control().clone();
+    job_control.kill(SIGSYS);
+    SYSCALL_FILTER_KILL_STATUS
+}
+
+/// Forget the cached filters when the thread that runs on the enclave thread
+/// exits, as the enclave thread is reused by other threads
+pub(super) fn reset_cached_filters() {
+    CACHED_FILTERS.with(|cached| cached.replace(None));
+}
+
+pub type SyscallFiltersRef = Arc<Vec<Arc<SyscallFilter>>>;
+
+thread_local! {
+    // The filters of the current thread, which are cached so that the syscalls
+    // are checked without locking the current thread. The filters of a thread
+    // are only changed by the thread itself.
+    static CACHED_FILTERS: RefCell<Option<SyscallFiltersRef>> = RefCell::new(None);
+}
@@ -16,6 +16,13 @@ pub struct Task {
     user_fs: usize,
     user_entry_addr: usize,
     saved_state: usize, // struct jmpbuf*
+    // The handler of the signal to run before returning to the user, which is
+    // set at the exit of a syscall and cleared once run
+    sig_handler: usize,
+    sig_num: usize,
+    // The user stack pointer at the entry of the last syscall, where the
+    // syscall returns to after the handlers of the signals
+    syscall_user_rsp: usize,
 }
 
 impl Task {
@@ -52,6 +59,19 @@ impl Task {
     pub fn get_user_fs(&self) -> usize {
         self.user_fs
     }
+
+    pub fn set_sig_handler(&mut self, sig_handler: usize, sig_num: usize) {
+        self.sig_handler = sig_handler;
+        self.sig_num = sig_num;
+    }
+
+    pub fn get_syscall_user_rsp(&self) -> usize {
+        self.syscall_user_rsp
+    }
+
+    pub fn set_syscall_user_rsp(&mut self, user_rsp: usize) {
+        self.syscall_user_rsp = user_rsp;
+    }
 }
 
 lazy_static! {
@@ -68,13 +88,36 @@ pub fn enqueue_task(new_tid: pid_t, new_process: ProcessRef) {
     assert!(existing_task.is_none());
 }
 
-pub fn enqueue_and_exec_task(new_tid: pid_t, new_process: ProcessRef) {
+/// Start the new task on a new host thread, or fail with EAGAIN if the host
+/// thread cannot be created. The task that fails to start is no longer
+/// counted as live, and is removed from its parent and the process table.
+pub fn enqueue_and_exec_task(new_tid: pid_t, new_process: ProcessRef) -> Result<()> {
     enqueue_task(new_tid, new_process);
 
     let mut ret = 0;
     let ocall_status = unsafe { occlum_ocall_exec_thread_async(&mut ret, new_tid) };
     if ocall_status != sgx_status_t::SGX_SUCCESS || ret != 0 {
-        panic!("Failed to start the process");
+        let new_process = dequeue_task(new_tid)?;
+        drop_unstarted_task(&new_process);
+        return_errno!(EAGAIN, "failed to start the thread");
+    }
+    Ok(())
+}
+
+fn drop_unstarted_task(process_ref: &ProcessRef) {
+    let (tid, live_uid, parent_ref) = {
+        let process = process_ref.lock().unwrap();
+        (process.get_tid(), process.live_uid, process.parent.clone())
+    };
+    process_table::remove(tid);
+    process_table::dec_live_threads(live_uid);
+    process_table::dec_threads_in_enclave();
+    if let Some(parent_ref) = parent_ref {
+        parent_ref.lock().unwrap().children.retain(|child| {
+            child
+                .upgrade()
+                .map_or(true, |child| !Arc::ptr_eq(&child, process_ref))
+        });
     }
 }
 
@@ -120,7 +163,7 @@ pub fn run_task(libos_tid: pid_t, host_tid: pid_t) -> Result<i32> {
     let (exit_status, parent_pid) = {
         let mut process = new_process.lock().unwrap();
         let parent = process.get_parent().lock().unwrap();
-        (process.get_exit_status(), parent.get_tid())
+        (process.get_term_status().as_exit_code(), parent.get_tid())
     };
 
     // If process's parent is the IDLE_PROCESS (pid = 0), so it has to release itself
@@ -128,6 +171,8 @@ pub fn run_task(libos_tid: pid_t, host_tid: pid_t) -> Result<i32> {
         process_table::remove(pid);
     }
 
+    // The TCS is released soon when the thread leaves the enclave
+    process_table::dec_threads_in_enclave();
     reset_current();
     Ok(exit_status)
 }
@@ -138,12 +183,19 @@ thread_local! {
     };
     // for log getting pid without locking process
     static _PID: Cell<pid_t> = Cell::new(0);
+    // for checking user buffers without locking process
+    static _CURRENT_VM: RefCell<Option<ProcessVMRef>> = RefCell::new(None);
 }
 
 pub fn current_pid() -> pid_t {
     _PID.with(|p| p.get())
 }
 
+/// The VM of the current thread, which is None outside of a user thread
+pub fn current_vm() -> Option<ProcessVMRef> {
+    _CURRENT_VM.with(|vm| vm.borrow().clone())
+}
+
 pub fn get_current() -> ProcessRef {
     let current_ptr = _CURRENT_PROCESS_PTR.with(|cell| cell.get());
 
@@ -155,8 +207,17 @@ pub fn get_current() -> ProcessRef {
 }
 
 fn set_current(process: &ProcessRef) {
-    let pid = process.lock().unwrap().get_pid();
+    let (pid, sig_interrupt, vm) = {
+        let process = process.lock().unwrap();
+        (
+            process.get_pid(),
+            process.sig_interrupt.clone(),
+            process.get_vm().clone(),
+        )
+    };
     _PID.with(|p| p.set(pid));
+    _CURRENT_VM.with(|current_vm| *current_vm.borrow_mut() = Some(vm));
+    signal::set_current_sig_interrupt(Some(sig_interrupt));
 
     let process_ref_clone = process.clone();
     let process_ptr = Arc::into_raw(process_ref_clone);
@@ -168,6 +229,9 @@ fn set_current(process: &ProcessRef) {
 
 fn reset_current() {
     _PID.with(|p| p.set(0));
+    _CURRENT_VM.with(|current_vm| *current_vm.borrow_mut() = None);
+    signal::set_current_sig_interrupt(None);
+    syscall_filter::reset_cached_filters();
     let mut process_ptr = _CURRENT_PROCESS_PTR.with(|cp| cp.replace(0 as *const SgxMutex<Process>));
 
     // Prevent memory leakage
@@ -82,11 +82,23 @@ pub fn do_clone(
         Process::new(cwd, elf_path, task, vm_ref, files_ref, rlimits_ref)?
     };
 
-    if let Some(ctid) = ctid {
+    {
         let mut new_thread = new_thread_ref.lock().unwrap();
-        new_thread.clear_child_tid = Some(ctid);
+        new_thread.clear_child_tid = ctid;
+        new_thread.umask = current.umask;
+        new_thread.sig_mask = current.sig_mask;
+        new_thread.sig_actions = current.sig_actions.clone();
+        new_thread.job_control = current.job_control.clone();
+        new_thread.credentials = current.credentials.clone();
+        new_thread.mnt_ns = current.mnt_ns.clone();
+        new_thread.syscall_filters = current.syscall_filters.clone();
     }
 
+    let live_uid = current.credentials.lock().unwrap().ruid();
+    let nproc_limit = current.get_rlimits().lock().unwrap().get_nproc_limit();
+    process_table::inc_live_threads(live_uid, nproc_limit)?;
+    new_thread_ref.lock().unwrap().live_uid = live_uid;
+
     // TODO: always get parent lock first to avoid deadlock
     {
         let parent_ref = current.parent.as_ref().unwrap();
@@ -106,7 +118,7 @@ pub fn do_clone(
         }
     }
 
-    task::enqueue_and_exec_task(new_thread_pid, new_thread_ref);
+    task::enqueue_and_exec_task(new_thread_pid, new_thread_ref)?;
     Ok(new_thread_pid)
 }
 
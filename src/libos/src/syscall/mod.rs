@@ -8,10 +8,19 @@
 //! 4. Do some memory checks then call `mod::do_*` (at each module)
 pub use self::syscall_num::SyscallNum;
 
-use fs::{File, FileDesc, FileRef, Stat};
-use misc::{resource_t, rlimit_t, utsname_t};
-use net::{msghdr, msghdr_mut, AsSocket, AsUnixSocket, SocketFile, UnixSocketFile};
-use process::{pid_t, ChildProcessFilter, CloneFlags, CpuSet, FileAction, FutexFlags, FutexOp};
+use fs::{File, FileDesc, FileRef, Stat, Statfs, Statx};
+use misc::{resource_t, rlimit_t, utsname_t, GetRandomFlags};
+use net::{
+    msghdr, msghdr_mut, AsSocket, AsUnixSocket, HowToShut, IpOption, MsgFlags, SocketFile,
+    UdpRecvd, UnixAddr, UnixSocketFile, IFNAMSIZ, SO_BINDTODEVICE, SO_BUSY_POLL,
+};
+use process::{
+    cap_user_data_t, cap_user_header_t, id_or_unchanged, pid_t, syscall_filter_rule_t,
+    Capabilities, CapabilitySets, ChildProcessFilter, ChildStatus, CloneFlags, CpuSet, Credentials,
+    FileAction, FutexDeadline, FutexFlags, FutexOp, MembarrierCmd, SigMaskHow, SigNum, SigSet,
+    SpawnAttr, SyscallFilterAction, SyscallRestart, TermStatus, WaitOptions,
+    FUTEX_BITSET_MATCH_ANY,
+};
 use std::any::Any;
 use std::convert::TryFrom;
 use std::ffi::{CStr, CString};
@@ -19,7 +28,7 @@ use std::io::{Read, Seek, SeekFrom, Write};
 use std::ptr;
 use time::{clockid_t, timespec_t, timeval_t, GLOBAL_PROFILER};
 use util::mem_util::from_user::*;
-use vm::{MMapFlags, VMPerms};
+use vm::{MMapFlags, VMPerms, PAGE_SIZE};
 use {fs, process, std, vm};
 
 use super::*;
@@ -31,7 +40,6 @@ mod syscall_num;
 //use std::libc_io as io;
 
 #[no_mangle]
-#[deny(unreachable_patterns)]
 pub extern "C" fn dispatch_syscall(
     num: u32,
     arg0: isize,
@@ -49,17 +57,124 @@ pub extern "C" fn dispatch_syscall(
         pid, syscall_num, arg0, arg1, arg2, arg3, arg4, arg5
     );
 
-    #[cfg(feature = "syscall_timing")]
-    GLOBAL_PROFILER
-        .lock()
-        .unwrap()
-        .syscall_enter(syscall_num)
-        .expect("unexpected error from profiler to enter syscall");
+    // The syscall filters are applied before the syscall is handled
+    match process::filter_syscall(num, &[arg0, arg1, arg2, arg3, arg4, arg5]) {
+        SyscallFilterAction::Allow => {}
+        SyscallFilterAction::Errno(errno) => {
+            info!(
+                "tid: {} => {:?} denied by the syscall filter",
+                pid, syscall_num
+            );
+            return -(errno as isize);
+        }
+        SyscallFilterAction::Kill => {
+            warn!(
+                "tid: {} => {:?} killed by the syscall filter",
+                pid, syscall_num
+            );
+            // The whole process is killed, not only the current thread
+            do_exit(process::kill_by_syscall_filter());
+        }
+    }
+
+    let args = [arg0, arg1, arg2, arg3, arg4, arg5];
+    loop {
+        // A thread is parked at syscalls while its process is stopped
+        if let Some(term_status) = process::handle_job_control() {
+            do_exit(term_status);
+        }
+
+        #[cfg(feature = "syscall_timing")]
+        GLOBAL_PROFILER
+            .lock()
+            .unwrap()
+            .syscall_enter(syscall_num)
+            .expect("unexpected error from profiler to enter syscall");
+
+        let ret = do_syscall(syscall_num, num, arg0, arg1, arg2, arg3, arg4, arg5);
+
+        #[cfg(feature = "syscall_timing")]
+        GLOBAL_PROFILER
+            .lock()
+            .unwrap()
+            .syscall_exit(syscall_num, ret.is_err())
+            .expect("unexpected error from profiler to exit syscall");
+
+        info!("tid: {} => {:?} ", process::do_gettid(), ret);
+
+        // The process may be stopped or killed while the syscall is blocked
+        if let Some(term_status) = process::handle_job_control() {
+            do_exit(term_status);
+        }
+
+        // The directory changes made by the syscall are notified to the dnotify
+        // watches, which may send signals to the current process as well
+        fs::send_dnotify_signals();
+
+        // The signals sent to the thread are delivered before returning to the
+        // user, which may kill the process. A syscall interrupted by them is
+        // restarted at once if no handler runs, or after the handler returns if
+        // it is set with SA_RESTART.
+        let restart_deadline = RESTART_DEADLINE.with(|deadline| deadline.take());
+        let mut restart = match ret {
+            Err(ref e) if e.errno() == EINTR && is_restartable(syscall_num, &args) => {
+                Some(SyscallRestart {
+                    num,
+                    args,
+                    deadline: restart_deadline,
+                })
+            }
+            _ => None,
+        };
+        if let Some(term_status) = process::deliver_pending_signals(&mut restart) {
+            do_exit(term_status);
+        }
+        if let Some(restart) = restart {
+            RESTART_DEADLINE.with(|deadline| deadline.set(restart.deadline));
+            continue;
+        }
+
+        return match ret {
+            Ok(retval) => retval as isize,
+            Err(e) => {
+                warn!("{}", e.backtrace());
+                if config::LIBOS_CONFIG.process.record_last_error {
+                    record_last_error(syscall_num, &args, &e);
+                }
+
+                let retval = -(e.errno() as isize);
+                debug_assert!(retval != 0);
+                retval
+            }
+        };
+    }
+}
 
+#[deny(unreachable_patterns)]
+fn do_syscall(
+    syscall_num: SyscallNum,
+    num: u32,
+    arg0: isize,
+    arg1: isize,
+    arg2: isize,
+    arg3: isize,
+    arg4: isize,
+    arg5: isize,
+) -> Result<isize> {
     use self::syscall_num::SyscallNum::*;
-    let ret = match syscall_num {
+    match syscall_num {
         // file
         SysOpen => fs::do_open(arg0 as *const i8, arg1 as u32, arg2 as u32),
+        SysNameToHandleAt => fs::do_name_to_handle_at(
+            arg0 as i32,
+            arg1 as *const i8,
+            arg2 as *mut fs::file_handle_t,
+            arg3 as *mut i32,
+            arg4 as u32,
+        ),
+        SysOpenByHandleAt => {
+            fs::do_open_by_handle_at(arg0 as i32, arg1 as *const fs::file_handle_t, arg2 as u32)
+        }
         SysClose => fs::do_close(arg0 as FileDesc),
         SysRead => fs::do_read(arg0 as FileDesc, arg1 as *mut u8, arg2 as usize),
         SysWrite => fs::do_write(arg0 as FileDesc, arg1 as *const u8, arg2 as usize),
@@ -77,9 +192,35 @@ pub extern "C" fn dispatch_syscall(
         ),
         SysReadv => fs::do_readv(arg0 as FileDesc, arg1 as *mut fs::iovec_t, arg2 as i32),
         SysWritev => fs::do_writev(arg0 as FileDesc, arg1 as *mut fs::iovec_t, arg2 as i32),
+        SysPreadv2 => fs::do_preadv2(
+            arg0 as FileDesc,
+            arg1 as *mut fs::iovec_t,
+            arg2 as i32,
+            arg3 as off_t,
+            arg5 as u32,
+        ),
+        SysPreadv => fs::do_preadv(
+            arg0 as FileDesc,
+            arg1 as *mut fs::iovec_t,
+            arg2 as i32,
+            arg3 as off_t,
+        ),
+        SysPwritev => fs::do_pwritev(
+            arg0 as FileDesc,
+            arg1 as *const fs::iovec_t,
+            arg2 as i32,
+            arg3 as off_t,
+        ),
         SysStat => fs::do_stat(arg0 as *const i8, arg1 as *mut Stat),
         SysFstat => fs::do_fstat(arg0 as FileDesc, arg1 as *mut Stat),
         SysLstat => fs::do_lstat(arg0 as *const i8, arg1 as *mut Stat),
+        SysStatx => fs::do_statx(
+            arg0 as i32,
+            arg1 as *const i8,
+            arg2 as u32,
+            arg3 as u32,
+            arg4 as *mut Statx,
+        ),
         SysAccess => fs::do_access(arg0 as *const i8, arg1 as u32),
         SysFaccessat => fs::do_faccessat(arg0 as i32, arg1 as *const i8, arg2 as u32, arg3 as u32),
         SysLseek => fs::do_lseek(arg0 as FileDesc, arg1 as off_t, arg2 as i32),
@@ -87,12 +228,33 @@ pub extern "C" fn dispatch_syscall(
         SysFdatasync => fs::do_fdatasync(arg0 as FileDesc),
         SysTruncate => fs::do_truncate(arg0 as *const i8, arg1 as usize),
         SysFtruncate => fs::do_ftruncate(arg0 as FileDesc, arg1 as usize),
+        SysFallocate => {
+            fs::do_fallocate(arg0 as FileDesc, arg1 as u32, arg2 as off_t, arg3 as off_t)
+        }
         SysGetdents64 => fs::do_getdents64(arg0 as FileDesc, arg1 as *mut u8, arg2 as usize),
         SysSync => fs::do_sync(),
-        SysGetcwd => do_getcwd(arg0 as *mut u8, arg1 as usize),
+        SysStatfs => fs::do_statfs(arg0 as *const i8, arg1 as *mut Statfs),
+        SysFstatfs => fs::do_fstatfs(arg0 as FileDesc, arg1 as *mut Statfs),
+        SysMount => fs::do_mount(
+            arg0 as *const i8,
+            arg1 as *const i8,
+            arg2 as *const i8,
+            arg3 as u32,
+            arg4 as *const c_void,
+        ),
+        SysUnshare => fs::do_unshare(arg0 as u32),
+        SysSetns => fs::do_setns(arg0 as FileDesc, arg1 as u32),
+        SysGetcwd => fs::do_getcwd(arg0 as *mut u8, arg1 as usize),
         SysChdir => fs::do_chdir(arg0 as *mut i8),
+        SysFchdir => fs::do_fchdir(arg0 as FileDesc),
         SysRename => fs::do_rename(arg0 as *const i8, arg1 as *const i8),
         SysMkdir => fs::do_mkdir(arg0 as *const i8, arg1 as usize),
+        SysMknod => fs::do_mknod(arg0 as *const i8, arg1 as u32, arg2 as usize),
+        SysChmod => fs::do_chmod(arg0 as *const i8, arg1 as u16),
+        SysFchmod => fs::do_fchmod(arg0 as FileDesc, arg1 as u16),
+        SysChown => fs::do_chown(arg0 as *const i8, arg1 as u32, arg2 as u32),
+        SysFchown => fs::do_fchown(arg0 as FileDesc, arg1 as u32, arg2 as u32),
+        SysLchown => fs::do_lchown(arg0 as *const i8, arg1 as u32, arg2 as u32),
         SysRmdir => fs::do_rmdir(arg0 as *const i8),
         SysLink => fs::do_link(arg0 as *const i8, arg1 as *const i8),
         SysUnlink => fs::do_unlink(arg0 as *const i8),
@@ -103,7 +265,17 @@ pub extern "C" fn dispatch_syscall(
             arg2 as *mut off_t,
             arg3 as usize,
         ),
+        SysIoUringSetup => fs::do_io_uring_setup(arg0 as u32, arg1 as *mut fs::io_uring_params_t),
+        SysIoUringEnter => fs::do_io_uring_enter(
+            arg0 as FileDesc,
+            arg1 as u32,
+            arg2 as u32,
+            arg3 as u32,
+            arg4 as *const u64,
+            arg5 as usize,
+        ),
         SysFcntl => fs::do_fcntl(arg0 as FileDesc, arg1 as u32, arg2 as u64),
+        SysFlock => fs::do_flock(arg0 as FileDesc, arg1 as u32),
         SysIoctl => fs::do_ioctl(arg0 as FileDesc, arg1 as u32, arg2 as *mut u8),
 
         // Io multiplexing
@@ -140,30 +312,85 @@ pub extern "C" fn dispatch_syscall(
             arg3 as c_int,
             arg4 as *const usize, //Todo:add sigset_t
         ),
+        SysEpollPwait2 => net::do_epoll_pwait2(
+            arg0 as c_int,
+            arg1 as *mut libc::epoll_event,
+            arg2 as c_int,
+            arg3 as *const timespec_t,
+            arg4 as *const u64,
+            arg5 as size_t,
+        ),
 
         // process
-        SysExit => do_exit(arg0 as i32),
+        SysExit => do_exit(TermStatus::Exited(arg0 as u8)),
         SysSpawn => do_spawn(
             arg0 as *mut u32,
             arg1 as *mut i8,
             arg2 as *const *const i8,
             arg3 as *const *const i8,
             arg4 as *const FdOp,
+            ptr::null(),
+        ),
+        SysSpawnWithAttr => do_spawn(
+            arg0 as *mut u32,
+            arg1 as *mut i8,
+            arg2 as *const *const i8,
+            arg3 as *const *const i8,
+            arg4 as *const FdOp,
+            arg5 as *const spawn_attr_t,
+        ),
+        SysWait4 => do_wait4(arg0 as i32, arg1 as *mut i32, arg2 as u32),
+        SysWaitid => do_waitid(
+            arg0 as u32,
+            arg1 as u32,
+            arg2 as *mut siginfo_t,
+            arg3 as u32,
+        ),
+        SysSetSyscallFilter => do_set_syscall_filter(
+            arg0 as *const syscall_filter_rule_t,
+            arg1 as usize,
+            arg2 as u32,
         ),
-        SysWait4 => do_wait4(arg0 as i32, arg1 as *mut i32),
 
         SysGetpid => do_getpid(),
         SysGettid => do_gettid(),
         SysGetppid => do_getppid(),
         SysGetpgid => do_getpgid(),
+        SysUmask => do_umask(arg0 as u32),
 
         SysGetuid => do_getuid(),
         SysGetgid => do_getgid(),
         SysGeteuid => do_geteuid(),
         SysGetegid => do_getegid(),
+        SysGetresuid => do_getresuid(arg0 as *mut u32, arg1 as *mut u32, arg2 as *mut u32),
+        SysGetresgid => do_getresgid(arg0 as *mut u32, arg1 as *mut u32, arg2 as *mut u32),
+        SysGetgroups => do_getgroups(arg0 as i32, arg1 as *mut u32),
+        SysSetuid => do_setuid(arg0 as u32),
+        SysSetgid => do_setgid(arg0 as u32),
+        SysSetresuid => do_setresuid(arg0 as u32, arg1 as u32, arg2 as u32),
+        SysSetresgid => do_setresgid(arg0 as u32, arg1 as u32, arg2 as u32),
+        SysSetgroups => do_setgroups(arg0 as usize, arg1 as *const u32),
+        SysCapget => do_capget(arg0 as *mut cap_user_header_t, arg1 as *mut cap_user_data_t),
+        SysCapset => do_capset(
+            arg0 as *mut cap_user_header_t,
+            arg1 as *const cap_user_data_t,
+        ),
 
-        SysRtSigaction => do_rt_sigaction(),
-        SysRtSigprocmask => do_rt_sigprocmask(),
+        SysRtSigaction => do_rt_sigaction(
+            arg0 as u32,
+            arg1 as *const process::sigaction_t,
+            arg2 as *mut process::sigaction_t,
+            arg3 as usize,
+        ),
+        SysRtSigprocmask => do_rt_sigprocmask(
+            arg0 as u32,
+            arg1 as *const u64,
+            arg2 as *mut u64,
+            arg3 as usize,
+        ),
+        SysKill => do_kill(arg0 as i32, arg1 as u32),
+        SysTkill => do_tkill(arg0 as i32, arg1 as u32),
+        SysTgkill => do_tgkill(arg0 as i32, arg1 as i32, arg2 as u32),
 
         SysClone => do_clone(
             arg0 as u32,
@@ -176,9 +403,9 @@ pub extern "C" fn dispatch_syscall(
             arg0 as *const i32,
             arg1 as u32,
             arg2 as i32,
-            arg3 as i32,
+            arg3 as usize,
             arg4 as *const i32,
-            // Todo: accept other optional arguments
+            arg5 as u32,
         ),
         SysArchPrctl => do_arch_prctl(arg0 as u32, arg1 as *mut usize),
         SysSetTidAddress => do_set_tid_address(arg0 as *mut pid_t),
@@ -191,6 +418,8 @@ pub extern "C" fn dispatch_syscall(
         SysSchedSetaffinity => {
             do_sched_setaffinity(arg0 as pid_t, arg1 as size_t, arg2 as *const c_uchar)
         }
+        SysGetcpu => do_getcpu(arg0 as *mut u32, arg1 as *mut u32),
+        SysMembarrier => do_membarrier(arg0 as i32, arg1 as i32),
 
         // memory
         SysMmap => do_mmap(
@@ -210,6 +439,12 @@ pub extern "C" fn dispatch_syscall(
             arg4 as usize,
         ),
         SysMprotect => do_mprotect(arg0 as usize, arg1 as usize, arg2 as u32),
+        SysMincore => do_mincore(arg0 as usize, arg1 as usize, arg2 as *mut u8),
+        SysMadvise => do_madvise(arg0 as usize, arg1 as usize, arg2 as i32),
+        SysMlock => do_mlock(arg0 as usize, arg1 as usize),
+        SysMunlock => do_munlock(arg0 as usize, arg1 as usize),
+        SysMlockall => do_mlockall(arg0 as i32),
+        SysMunlockall => do_munlockall(),
         SysBrk => do_brk(arg0 as usize),
 
         SysPipe => fs::do_pipe2(arg0 as *mut i32, 0),
@@ -220,10 +455,12 @@ pub extern "C" fn dispatch_syscall(
 
         SysGettimeofday => do_gettimeofday(arg0 as *mut timeval_t),
         SysClockGettime => do_clock_gettime(arg0 as clockid_t, arg1 as *mut timespec_t),
+        SysClockGetres => do_clock_getres(arg0 as clockid_t, arg1 as *mut timespec_t),
 
         SysNanosleep => do_nanosleep(arg0 as *const timespec_t, arg1 as *mut timespec_t),
 
         SysUname => do_uname(arg0 as *mut utsname_t),
+        SysGetrandom => do_getrandom(arg0 as *mut u8, arg1 as usize, arg2 as u32),
 
         SysPrlimit64 => do_prlimit(
             arg0 as pid_t,
@@ -310,29 +547,47 @@ pub extern "C" fn dispatch_syscall(
         SysRecvmsg => net::do_recvmsg(arg0 as c_int, arg1 as *mut msghdr_mut, arg2 as c_int),
 
         _ => do_unknown(num, arg0, arg1, arg2, arg3, arg4, arg5),
-    };
-
-    #[cfg(feature = "syscall_timing")]
-    GLOBAL_PROFILER
-        .lock()
-        .unwrap()
-        .syscall_exit(syscall_num, ret.is_err())
-        .expect("unexpected error from profiler to exit syscall");
-
-    info!("tid: {} => {:?} ", process::do_gettid(), ret);
+    }
+}
 
-    match ret {
-        Ok(retval) => retval as isize,
-        Err(e) => {
-            warn!("{}", e.backtrace());
+thread_local! {
+    // The deadline of the timed wait of the syscall that is interrupted by a
+    // signal, which is kept when the syscall is restarted
+    static RESTART_DEADLINE: Cell<Option<FutexDeadline>> = Cell::new(None);
+}
 
-            let retval = -(e.errno() as isize);
-            debug_assert!(retval != 0);
-            retval
-        }
+/// Whether the syscall interrupted by a signal is restarted after the handler
+/// of the signal if the handler is set with SA_RESTART, as in Linux. The waits
+/// for the readiness of many files and the sleeps always fail with EINTR.
+fn is_restartable(syscall_num: SyscallNum, args: &[isize; 6]) -> bool {
+    use self::syscall_num::SyscallNum::*;
+    match syscall_num {
+        SysRead | SysWrite | SysReadv | SysWritev | SysPread64 | SysPwrite64 | SysPreadv
+        | SysPwritev | SysSendto | SysRecvfrom | SysSendmsg | SysRecvmsg | SysAccept
+        | SysAccept4 | SysOpen | SysOpenat | SysFlock | SysFcntl | SysIoctl | SysWait4 => true,
+        SysFutex => match process::futex_op_and_flags_from_u32(args[1] as u32) {
+            Ok((FutexOp::FUTEX_WAIT, _)) | Ok((FutexOp::FUTEX_WAIT_BITSET, _)) => true,
+            _ => false,
+        },
+        _ => false,
     }
 }
 
+// Record the error of the syscall for /proc/self/occlum_last_error. The
+// arguments are recorded as a whole since the offending one is unknown.
+fn record_last_error(syscall_num: SyscallNum, args: &[isize], error: &Error) {
+    let args_str: Vec<String> = args.iter().map(|arg| format!("{:#x}", arg)).collect();
+    let last_error = format!(
+        "syscall: {:?}\nargs: {}\nerror: {}\n",
+        syscall_num,
+        args_str.join(", "),
+        error.backtrace()
+    );
+    let current_ref = process::get_current();
+    let mut current = current_ref.lock().unwrap();
+    current.set_last_error(last_error);
+}
+
 /*
  * This Rust-version of fdop correspond to the C-version one in Occlum.
  * See <path_to_musl_libc>/src/process/fdop.h.
@@ -386,25 +641,66 @@ fn clone_file_actions_safely(fdop_ptr: *const FdOp) -> Result<Vec<FileAction>> {
     Ok(file_actions)
 }
 
+/*
+ * The attributes of spawn, which are an extension of Occlum to posix_spawn.
+ * The attributes are given by SysSpawnWithAttr, so that SysSpawn is kept
+ * compatible. The pointer to them can be null.
+ */
+const SPAWN_SETCWD: u32 = 1;
+const SPAWN_SETUMASK: u32 = 2;
+
+#[repr(C)]
+#[derive(Debug)]
+#[allow(non_camel_case_types)]
+pub struct spawn_attr_t {
+    flags: u32,
+    umask: u32,
+    cwd: *const i8,
+}
+
+fn clone_spawn_attr_safely(attr_ptr: *const spawn_attr_t) -> Result<SpawnAttr> {
+    let mut spawn_attr = SpawnAttr::default();
+    if attr_ptr == ptr::null() {
+        return Ok(spawn_attr);
+    }
+    check_ptr(attr_ptr)?;
+    let attr = unsafe { &*attr_ptr };
+    if attr.flags & !(SPAWN_SETCWD | SPAWN_SETUMASK) != 0 {
+        return_errno!(EINVAL, "Unknown spawn attribute flags");
+    }
+    if attr.flags & SPAWN_SETCWD != 0 {
+        let cwd = clone_cstring_safely(attr.cwd)?
+            .to_string_lossy()
+            .into_owned();
+        spawn_attr.cwd = Some(cwd);
+    }
+    if attr.flags & SPAWN_SETUMASK != 0 {
+        spawn_attr.umask = Some(attr.umask);
+    }
+    Ok(spawn_attr)
+}
+
 fn do_spawn(
     child_pid_ptr: *mut u32,
     path: *const i8,
     argv: *const *const i8,
     envp: *const *const i8,
     fdop_list: *const FdOp,
+    attr_ptr: *const spawn_attr_t,
 ) -> Result<isize> {
     check_mut_ptr(child_pid_ptr)?;
     let path = clone_cstring_safely(path)?.to_string_lossy().into_owned();
     let argv = clone_cstrings_safely(argv)?;
     let envp = clone_cstrings_safely(envp)?;
     let file_actions = clone_file_actions_safely(fdop_list)?;
+    let spawn_attr = clone_spawn_attr_safely(attr_ptr)?;
     let parent = process::get_current();
     info!(
-        "spawn: path: {:?}, argv: {:?}, envp: {:?}, fdop: {:?}",
-        path, argv, envp, file_actions
+        "spawn: path: {:?}, argv: {:?}, envp: {:?}, fdop: {:?}, attr: {:?}",
+        path, argv, envp, file_actions, spawn_attr
     );
 
-    let child_pid = process::do_spawn(&path, &argv, &envp, &file_actions, &parent)?;
+    let child_pid = process::do_spawn(&path, &argv, &envp, &file_actions, &spawn_attr, &parent)?;
 
     unsafe { *child_pid_ptr = child_pid };
     Ok(0)
@@ -449,12 +745,16 @@ pub fn do_clone(
     Ok(child_pid as isize)
 }
 
+/// The timeout argument of futex is either a pointer to the timeout, or an
+/// integer, e.g., the max number of the waiters to requeue. The bitset argument
+/// is the expected futex value in FUTEX_CMP_REQUEUE.
 pub fn do_futex(
     futex_addr: *const i32,
     futex_op: u32,
     futex_val: i32,
-    timeout: i32,
+    timeout: usize,
     futex_new_addr: *const i32,
+    bitset: u32,
 ) -> Result<isize> {
     check_ptr(futex_addr)?;
     let (futex_op, futex_flags) = process::futex_op_and_flags_from_u32(futex_op)?;
@@ -466,18 +766,74 @@ pub fn do_futex(
         Ok(val as usize)
     };
 
+    let get_timeout = || -> Result<Option<timespec_t>> {
+        let timeout_ptr = timeout as *const timespec_t;
+        if timeout_ptr.is_null() {
+            return Ok(None);
+        }
+        check_ptr(timeout_ptr)?;
+        Ok(Some(timespec_t::from_raw_ptr(timeout_ptr)?))
+    };
+
     match futex_op {
-        FutexOp::FUTEX_WAIT => process::futex_wait(futex_addr, futex_val).map(|_| 0),
+        FutexOp::FUTEX_WAIT => {
+            // The timeout of FUTEX_WAIT is relative, so a restarted wait keeps
+            // the deadline of the interrupted one
+            let deadline = match RESTART_DEADLINE.with(|deadline| deadline.take()) {
+                Some(deadline) => Some(deadline),
+                None => match get_timeout()? {
+                    Some(timeout) => Some(FutexDeadline::from_timeout(&timeout)?),
+                    None => None,
+                },
+            };
+            let ret = process::futex_wait(futex_addr, futex_val, deadline, FUTEX_BITSET_MATCH_ANY);
+            if let Err(ref e) = ret {
+                if e.errno() == EINTR {
+                    RESTART_DEADLINE.with(|restart_deadline| restart_deadline.set(deadline));
+                }
+            }
+            ret.map(|_| 0)
+        }
+        FutexOp::FUTEX_WAIT_BITSET => {
+            // The timeout of FUTEX_WAIT_BITSET is absolute
+            let clockid = if futex_flags.contains(FutexFlags::FUTEX_CLOCK_REALTIME) {
+                time::ClockID::CLOCK_REALTIME
+            } else {
+                time::ClockID::CLOCK_MONOTONIC
+            };
+            let deadline =
+                get_timeout()?.map(|abs_time| FutexDeadline::from_abs_time(&abs_time, clockid));
+            process::futex_wait(futex_addr, futex_val, deadline, bitset).map(|_| 0)
+        }
         FutexOp::FUTEX_WAKE => {
             let max_count = get_futex_val(futex_val)?;
-            process::futex_wake(futex_addr, max_count).map(|count| count as isize)
+            process::futex_wake(futex_addr, max_count, FUTEX_BITSET_MATCH_ANY)
+                .map(|count| count as isize)
+        }
+        FutexOp::FUTEX_WAKE_BITSET => {
+            let max_count = get_futex_val(futex_val)?;
+            process::futex_wake(futex_addr, max_count, bitset).map(|count| count as isize)
         }
         FutexOp::FUTEX_REQUEUE => {
             check_ptr(futex_new_addr)?;
             let max_nwakes = get_futex_val(futex_val)?;
-            let max_nrequeues = get_futex_val(timeout)?;
-            process::futex_requeue(futex_addr, max_nwakes, max_nrequeues, futex_new_addr)
-                .map(|nwakes| nwakes as isize)
+            let max_nrequeues = get_futex_val(timeout as i32)?;
+            process::futex_requeue(futex_addr, max_nwakes, max_nrequeues, futex_new_addr, None)
+                .map(|count| count as isize)
+        }
+        FutexOp::FUTEX_CMP_REQUEUE => {
+            check_ptr(futex_new_addr)?;
+            let max_nwakes = get_futex_val(futex_val)?;
+            let max_nrequeues = get_futex_val(timeout as i32)?;
+            let expected_val = bitset as i32;
+            process::futex_requeue(
+                futex_addr,
+                max_nwakes,
+                max_nrequeues,
+                futex_new_addr,
+                Some(expected_val),
+            )
+            .map(|count| count as isize)
         }
         _ => return_errno!(ENOSYS, "the futex operation is not supported"),
     }
@@ -514,7 +870,46 @@ fn do_mremap(
 }
 
 fn do_mprotect(addr: usize, len: usize, prot: u32) -> Result<isize> {
-    // TODO: implement it
+    let perms = VMPerms::from_u32(prot)?;
+    vm::do_mprotect(addr, len, perms)?;
+    Ok(0)
+}
+
+fn do_mincore(addr: usize, size: usize, vec: *mut u8) -> Result<isize> {
+    let vec = {
+        let num_pages = size
+            .checked_add(PAGE_SIZE - 1)
+            .ok_or_else(|| errno!(ENOMEM, "size is too large"))?
+            / PAGE_SIZE;
+        check_mut_array(vec, num_pages)?;
+        unsafe { std::slice::from_raw_parts_mut(vec, num_pages) }
+    };
+    vm::do_mincore(addr, size, vec)?;
+    Ok(0)
+}
+
+fn do_madvise(addr: usize, size: usize, advice: i32) -> Result<isize> {
+    vm::do_madvise(addr, size, advice)?;
+    Ok(0)
+}
+
+fn do_mlock(addr: usize, size: usize) -> Result<isize> {
+    vm::do_mlock(addr, size)?;
+    Ok(0)
+}
+
+fn do_munlock(addr: usize, size: usize) -> Result<isize> {
+    vm::do_munlock(addr, size)?;
+    Ok(0)
+}
+
+fn do_mlockall(flags: i32) -> Result<isize> {
+    vm::do_mlockall(flags)?;
+    Ok(0)
+}
+
+fn do_munlockall() -> Result<isize> {
+    vm::do_munlockall()?;
     Ok(0)
 }
 
@@ -523,7 +918,7 @@ fn do_brk(new_brk_addr: usize) -> Result<isize> {
     Ok(ret_brk_addr as isize)
 }
 
-fn do_wait4(pid: i32, _exit_status: *mut i32) -> Result<isize> {
+fn do_wait4(pid: i32, _exit_status: *mut i32, options: u32) -> Result<isize> {
     if !_exit_status.is_null() {
         check_mut_ptr(_exit_status)?;
     }
@@ -540,18 +935,95 @@ fn do_wait4(pid: i32, _exit_status: *mut i32) -> Result<isize> {
             panic!("THIS SHOULD NEVER HAPPEN!");
         }
     };
-    let mut exit_status = 0;
-    match process::do_wait4(&child_process_filter, &mut exit_status) {
-        Ok(pid) => {
+    let options = WaitOptions::from_bits(options)
+        .filter(|options| {
+            (*options - (WaitOptions::WNOHANG | WaitOptions::WSTOPPED | WaitOptions::WCONTINUED))
+                .is_empty()
+        })
+        .ok_or_else(|| errno!(EINVAL, "invalid options"))?;
+    match process::do_wait(&child_process_filter, options | WaitOptions::WEXITED)? {
+        Some((pid, child_status)) => {
             if !_exit_status.is_null() {
                 unsafe {
-                    *_exit_status = exit_status;
+                    *_exit_status = child_status.as_wait_status();
                 }
             }
             Ok(pid as isize)
         }
-        Err(e) => Err(e),
+        None => Ok(0),
+    }
+}
+
+const P_ALL: u32 = 0;
+const P_PID: u32 = 1;
+const P_PGID: u32 = 2;
+
+const SIGCHLD: i32 = 17;
+const CLD_EXITED: i32 = 1;
+const CLD_KILLED: i32 = 2;
+const CLD_STOPPED: i32 = 5;
+const CLD_CONTINUED: i32 = 6;
+const SIGCONT: i32 = 18;
+
+/// The siginfo of SIGCHLD, which is the only one that is used for now
+#[repr(C)]
+#[derive(Debug, Default)]
+#[allow(non_camel_case_types)]
+pub struct siginfo_t {
+    si_signo: i32,
+    si_errno: i32,
+    si_code: i32,
+    _pad0: i32,
+    si_pid: pid_t,
+    si_uid: u32,
+    si_status: i32,
+    _pad1: i32,
+    si_utime: i64,
+    si_stime: i64,
+    _pad2: [u64; 10],
+}
+
+fn do_waitid(idtype: u32, id: u32, infop: *mut siginfo_t, options: u32) -> Result<isize> {
+    if !infop.is_null() {
+        check_mut_ptr(infop)?;
+    }
+
+    let child_process_filter = match idtype {
+        P_ALL => process::ChildProcessFilter::WithAnyPID,
+        P_PID => process::ChildProcessFilter::WithPID(id as pid_t),
+        // Since Linux 5.4, an id of zero means the process group of the caller
+        P_PGID if id == 0 => process::ChildProcessFilter::WithPGID(process::do_getpgid()),
+        P_PGID => process::ChildProcessFilter::WithPGID(id as pid_t),
+        _ => return_errno!(EINVAL, "invalid idtype"),
+    };
+    let options =
+        WaitOptions::from_bits(options).ok_or_else(|| errno!(EINVAL, "invalid options"))?;
+    if !options.intersects(WaitOptions::WEXITED | WaitOptions::WSTOPPED | WaitOptions::WCONTINUED) {
+        return_errno!(EINVAL, "no state changes to wait for");
+    }
+
+    // The siginfo is zeroed if no child has changed its state under WNOHANG
+    let mut siginfo = siginfo_t::default();
+    if let Some((pid, child_status)) = process::do_wait(&child_process_filter, options)? {
+        let (si_code, si_status) = match child_status {
+            ChildStatus::Terminated(TermStatus::Exited(status)) => (CLD_EXITED, status as i32),
+            ChildStatus::Terminated(TermStatus::Killed(signum)) => (CLD_KILLED, signum as i32),
+            ChildStatus::Stopped(signum) => (CLD_STOPPED, signum.as_u32() as i32),
+            ChildStatus::Continued => (CLD_CONTINUED, SIGCONT),
+        };
+        siginfo.si_signo = SIGCHLD;
+        siginfo.si_code = si_code;
+        siginfo.si_pid = pid;
+        // TODO: use the real uid of the child when uids are supported
+        siginfo.si_uid = 0;
+        siginfo.si_status = si_status;
+    }
+    if !infop.is_null() {
+        unsafe {
+            *infop = siginfo;
+        }
     }
+    Ok(0)
 }
 
 fn do_getpid() -> Result<isize> {
@@ -574,21 +1046,167 @@ fn do_getpgid() -> Result<isize> {
     Ok(pgid as isize)
 }
 
-// TODO: implement uid, gid, euid, egid
+fn do_umask(mask: u32) -> Result<isize> {
+    let old_mask = process::do_umask(mask);
+    Ok(old_mask as isize)
+}
 
 fn do_getuid() -> Result<isize> {
-    Ok(0)
+    let (ruid, _, _) = process::do_getresuid();
+    Ok(ruid as isize)
 }
 
 fn do_getgid() -> Result<isize> {
-    Ok(0)
+    let (rgid, _, _) = process::do_getresgid();
+    Ok(rgid as isize)
 }
 
 fn do_geteuid() -> Result<isize> {
-    Ok(0)
+    let (_, euid, _) = process::do_getresuid();
+    Ok(euid as isize)
 }
 
 fn do_getegid() -> Result<isize> {
+    let (_, egid, _) = process::do_getresgid();
+    Ok(egid as isize)
+}
+
+fn do_getresuid(ruid: *mut u32, euid: *mut u32, suid: *mut u32) -> Result<isize> {
+    check_mut_ptr(ruid)?;
+    check_mut_ptr(euid)?;
+    check_mut_ptr(suid)?;
+    let uids = process::do_getresuid();
+    unsafe {
+        *ruid = uids.0;
+        *euid = uids.1;
+        *suid = uids.2;
+    }
+    Ok(0)
+}
+
+fn do_getresgid(rgid: *mut u32, egid: *mut u32, sgid: *mut u32) -> Result<isize> {
+    check_mut_ptr(rgid)?;
+    check_mut_ptr(egid)?;
+    check_mut_ptr(sgid)?;
+    let gids = process::do_getresgid();
+    unsafe {
+        *rgid = gids.0;
+        *egid = gids.1;
+        *sgid = gids.2;
+    }
+    Ok(0)
+}
+
+fn do_getgroups(size: i32, list: *mut u32) -> Result<isize> {
+    if size < 0 {
+        return_errno!(EINVAL, "size must not be negative");
+    }
+    let groups = process::do_getgroups();
+    // A size of zero only queries the number of the groups
+    if size == 0 {
+        return Ok(groups.len() as isize);
+    }
+    if (size as usize) < groups.len() {
+        return_errno!(EINVAL, "size is less than the number of the groups");
+    }
+    let list = {
+        check_mut_array(list, groups.len())?;
+        unsafe { std::slice::from_raw_parts_mut(list, groups.len()) }
+    };
+    list.copy_from_slice(&groups);
+    Ok(groups.len() as isize)
+}
+
+fn do_setuid(uid: u32) -> Result<isize> {
+    process::do_setuid(uid)?;
+    Ok(0)
+}
+
+fn do_setgid(gid: u32) -> Result<isize> {
+    process::do_setgid(gid)?;
+    Ok(0)
+}
+
+fn do_setresuid(ruid: u32, euid: u32, suid: u32) -> Result<isize> {
+    process::do_setresuid(
+        id_or_unchanged(ruid),
+        id_or_unchanged(euid),
+        id_or_unchanged(suid),
+    )?;
+    Ok(0)
+}
+
+fn do_setresgid(rgid: u32, egid: u32, sgid: u32) -> Result<isize> {
+    process::do_setresgid(
+        id_or_unchanged(rgid),
+        id_or_unchanged(egid),
+        id_or_unchanged(sgid),
+    )?;
+    Ok(0)
+}
+
+fn do_setgroups(size: usize, list: *const u32) -> Result<isize> {
+    if size > process::NGROUPS_MAX {
+        return_errno!(EINVAL, "too many groups");
+    }
+    let groups: &[u32] = if size == 0 {
+        &[]
+    } else {
+        check_array(list, size)?;
+        unsafe { std::slice::from_raw_parts(list, size) }
+    };
+    process::do_setgroups(groups)?;
+    Ok(0)
+}
+
+fn do_capget(header: *mut cap_user_header_t, data: *mut cap_user_data_t) -> Result<isize> {
+    check_mut_ptr(header)?;
+    let header = unsafe { &mut *header };
+    let data_len = match process::cap_user_data_len(header.version) {
+        Some(data_len) => data_len,
+        None => {
+            // Tell the preferred version, which is probed with a null data
+            header.version = process::LINUX_CAPABILITY_VERSION_3;
+            if data.is_null() {
+                return Ok(0);
+            }
+            return_errno!(EINVAL, "unsupported version");
+        }
+    };
+    if header.pid < 0 {
+        return_errno!(EINVAL, "invalid pid");
+    }
+    if data.is_null() {
+        return Ok(0);
+    }
+    let data = {
+        check_mut_array(data, data_len)?;
+        unsafe { std::slice::from_raw_parts_mut(data, data_len) }
+    };
+    let caps = process::do_capget(header.pid as pid_t)?;
+    caps.to_user(data);
+    Ok(0)
+}
+
+fn do_capset(header: *mut cap_user_header_t, data: *const cap_user_data_t) -> Result<isize> {
+    check_mut_ptr(header)?;
+    let header = unsafe { &mut *header };
+    let data_len = match process::cap_user_data_len(header.version) {
+        Some(data_len) => data_len,
+        None => {
+            header.version = process::LINUX_CAPABILITY_VERSION_3;
+            return_errno!(EINVAL, "unsupported version");
+        }
+    };
+    if header.pid < 0 {
+        return_errno!(EPERM, "invalid pid");
+    }
+    let data = {
+        check_array(data, data_len)?;
+        unsafe { std::slice::from_raw_parts(data, data_len) }
+    };
+    let caps = CapabilitySets::from_user(data);
+    process::do_capset(header.pid as pid_t, &caps)?;
     Ok(0)
 }
 
@@ -612,6 +1230,21 @@ fn do_clock_gettime(clockid: clockid_t, ts_u: *mut timespec_t) -> Result<isize>
     Ok(0)
 }
 
+fn do_clock_getres(clockid: clockid_t, res_u: *mut timespec_t) -> Result<isize> {
+    if !res_u.is_null() {
+        check_mut_ptr(res_u)?;
+    }
+    let clockid = time::ClockID::from_raw(clockid)?;
+    if res_u.is_null() {
+        return Ok(0);
+    }
+    let res = time::do_clock_getres(clockid)?;
+    unsafe {
+        *res_u = res;
+    }
+    Ok(0)
+}
+
 // TODO: handle remainder
 fn do_nanosleep(req_u: *const timespec_t, rem_u: *mut timespec_t) -> Result<isize> {
     check_ptr(req_u)?;
@@ -620,24 +1253,39 @@ fn do_nanosleep(req_u: *const timespec_t, rem_u: *mut timespec_t) -> Result<isiz
     }
 
     let req = timespec_t::from_raw_ptr(req_u)?;
-    time::do_nanosleep(&req)?;
+    process::sleep_interruptibly(|| time::do_nanosleep(&req))?;
     Ok(0)
 }
 
 // FIXME: use this
 const MAP_FAILED: *const c_void = ((-1) as i64) as *const c_void;
 
-fn do_exit(status: i32) -> ! {
-    info!("exit: {}", status);
+fn do_exit(term_status: TermStatus) -> ! {
+    info!("exit: {:?}", term_status);
     extern "C" {
         fn do_exit_task() -> !;
     }
-    process::do_exit(status);
+    process::do_exit(term_status);
     unsafe {
         do_exit_task();
     }
 }
 
+fn do_set_syscall_filter(
+    rules: *const syscall_filter_rule_t,
+    num_rules: usize,
+    default_action: u32,
+) -> Result<isize> {
+    let rules = if num_rules > 0 {
+        check_array(rules, num_rules)?;
+        unsafe { std::slice::from_raw_parts(rules, num_rules) }
+    } else {
+        &[]
+    };
+    process::do_set_syscall_filter(rules, default_action)?;
+    Ok(0)
+}
+
 fn do_unknown(
     num: u32,
     arg0: isize,
@@ -654,22 +1302,6 @@ fn do_unknown(
     return_errno!(ENOSYS, "Unknown syscall")
 }
 
-fn do_getcwd(buf: *mut u8, size: usize) -> Result<isize> {
-    let safe_buf = {
-        check_mut_array(buf, size)?;
-        unsafe { std::slice::from_raw_parts_mut(buf, size) }
-    };
-    let proc_ref = process::get_current();
-    let mut proc = proc_ref.lock().unwrap();
-    let cwd = proc.get_cwd();
-    if cwd.len() + 1 > safe_buf.len() {
-        return_errno!(ERANGE, "buf is not long enough");
-    }
-    safe_buf[..cwd.len()].copy_from_slice(cwd.as_bytes());
-    safe_buf[cwd.len()] = 0;
-    Ok(buf as isize)
-}
-
 fn do_arch_prctl(code: u32, addr: *mut usize) -> Result<isize> {
     let code = process::ArchPrctlCode::from_u32(code)?;
     check_mut_ptr(addr)?;
@@ -724,6 +1356,31 @@ fn do_sched_setaffinity(pid: pid_t, cpusize: size_t, buf: *const c_uchar) -> Res
     Ok(0)
 }
 
+// The tcache argument is unused since Linux 2.6.24
+fn do_getcpu(cpu_ptr: *mut u32, node_ptr: *mut u32) -> Result<isize> {
+    if !cpu_ptr.is_null() {
+        check_mut_ptr(cpu_ptr)?;
+    }
+    if !node_ptr.is_null() {
+        check_mut_ptr(node_ptr)?;
+    }
+    let (cpu, node) = process::do_getcpu()?;
+    unsafe {
+        if !cpu_ptr.is_null() {
+            *cpu_ptr = cpu;
+        }
+        if !node_ptr.is_null() {
+            *node_ptr = node;
+        }
+    }
+    Ok(0)
+}
+
+fn do_membarrier(cmd: i32, flags: i32) -> Result<isize> {
+    let cmd = MembarrierCmd::from_i32(cmd)?;
+    process::do_membarrier(cmd, flags).map(|retval| retval as isize)
+}
+
 fn do_socket(domain: c_int, socket_type: c_int, protocol: c_int) -> Result<isize> {
     info!(
         "socket: domain: {}, socket_type: 0x{:x}, protocol: {}",
@@ -744,28 +1401,34 @@ fn do_socket(domain: c_int, socket_type: c_int, protocol: c_int) -> Result<isize
     let current_ref = process::get_current();
     let mut proc = current_ref.lock().unwrap();
 
-    let fd = proc.get_files().lock().unwrap().put(file_ref, false);
+    let fd = proc.get_files().lock().unwrap().put(file_ref, false)?;
     Ok(fd as isize)
 }
 
+/// Connect a socket. A nonblocking host socket connects in the background: it
+/// fails with EINPROGRESS at first and EALREADY before the connection is done,
+/// and then becomes writable, or reports the failure by EPOLLERR and SO_ERROR.
+/// The status flags of the host socket are set by socket and fcntl, so the
+/// connect is never waited for here.
+///
+/// A connection to a socket of the LibOS at 127.0.0.1 or ::1 is set up by the
+/// host sockets, but its data is looped back inside the enclave (see
+/// `SocketFile::connect`).
 fn do_connect(fd: c_int, addr: *const libc::sockaddr, addr_len: libc::socklen_t) -> Result<isize> {
     info!(
         "connect: fd: {}, addr: {:?}, addr_len: {}",
         fd, addr, addr_len
     );
-    let current_ref = process::get_current();
-    let mut proc = current_ref.lock().unwrap();
-    let file_ref = proc.get_files().lock().unwrap().get(fd as FileDesc)?;
+    // The socket may block, so the process is not kept locked
+    let file_ref = fs::get_file(fd as FileDesc)?;
     if let Ok(socket) = file_ref.as_socket() {
-        let ret = try_libc!(libc::ocall::connect(socket.fd(), addr, addr_len));
-        Ok(ret as isize)
+        check_array(addr as *const u8, addr_len as usize)?;
+        let addr = unsafe { std::slice::from_raw_parts(addr as *const u8, addr_len as usize) };
+        socket.connect(addr)?;
+        Ok(0)
     } else if let Ok(unix_socket) = file_ref.as_unix_socket() {
-        let addr = addr as *const libc::sockaddr_un;
-        check_ptr(addr)?; // TODO: check addr_len
-        let path = clone_cstring_safely(unsafe { (&*addr).sun_path.as_ptr() })?
-            .to_string_lossy()
-            .into_owned();
-        unix_socket.connect(path)?;
+        let addr = UnixAddr::from_user(addr, addr_len)?;
+        unix_socket.connect(&addr)?;
         Ok(0)
     } else {
         return_errno!(EBADF, "not a socket")
@@ -782,29 +1445,25 @@ fn do_accept4(
         "accept4: fd: {}, addr: {:?}, addr_len: {:?}, flags: {:#x}",
         fd, addr, addr_len, flags
     );
-    let current_ref = process::get_current();
-    let mut proc = current_ref.lock().unwrap();
-    let file_ref = proc.get_files().lock().unwrap().get(fd as FileDesc)?;
-    if let Ok(socket) = file_ref.as_socket() {
-        let socket = file_ref.as_socket()?;
-
+    // The socket may block, so the process is not kept locked
+    let file_ref = fs::get_file(fd as FileDesc)?;
+    let new_file_ref: Arc<Box<dyn File>> = if let Ok(socket) = file_ref.as_socket() {
         let new_socket = socket.accept(addr, addr_len, flags)?;
-        let new_file_ref: Arc<Box<dyn File>> = Arc::new(Box::new(new_socket));
-        let new_fd = proc.get_files().lock().unwrap().put(new_file_ref, false);
-
-        Ok(new_fd as isize)
+        Arc::new(Box::new(new_socket))
     } else if let Ok(unix_socket) = file_ref.as_unix_socket() {
-        let addr = addr as *mut libc::sockaddr_un;
-        check_mut_ptr(addr)?; // TODO: check addr_len
-
         let new_socket = unix_socket.accept()?;
-        let new_file_ref: Arc<Box<dyn File>> = Arc::new(Box::new(new_socket));
-        let new_fd = proc.get_files().lock().unwrap().put(new_file_ref, false);
-
-        Ok(new_fd as isize)
+        if !addr.is_null() {
+            new_socket.peer_addr()?.copy_to_user(addr, addr_len)?;
+        }
+        Arc::new(Box::new(new_socket))
     } else {
         return_errno!(EBADF, "not a socket")
-    }
+    };
+
+    let current_ref = process::get_current();
+    let proc = current_ref.lock().unwrap();
+    let new_fd = proc.get_files().lock().unwrap().put(new_file_ref, false)?;
+    Ok(new_fd as isize)
 }
 
 fn do_shutdown(fd: c_int, how: c_int) -> Result<isize> {
@@ -813,8 +1472,8 @@ fn do_shutdown(fd: c_int, how: c_int) -> Result<isize> {
     let mut proc = current_ref.lock().unwrap();
     let file_ref = proc.get_files().lock().unwrap().get(fd as FileDesc)?;
     if let Ok(socket) = file_ref.as_socket() {
-        let ret = try_libc!(libc::ocall::shutdown(socket.fd(), how));
-        Ok(ret as isize)
+        socket.shutdown(HowToShut::from_c_int(how)?)?;
+        Ok(0)
     } else {
         return_errno!(EBADF, "not a socket")
     }
@@ -827,29 +1486,48 @@ fn do_bind(fd: c_int, addr: *const libc::sockaddr, addr_len: libc::socklen_t) ->
     let file_ref = proc.get_files().lock().unwrap().get(fd as FileDesc)?;
     if let Ok(socket) = file_ref.as_socket() {
         check_ptr(addr)?; // TODO: check addr_len
+        let credentials = proc.get_credentials().lock().unwrap().clone();
+        check_bind_port(addr, &credentials)?;
         let ret = try_libc!(libc::ocall::bind(socket.fd(), addr, addr_len));
+        socket.register_udp_loopback()?;
         Ok(ret as isize)
     } else if let Ok(unix_socket) = file_ref.as_unix_socket() {
-        let addr = addr as *const libc::sockaddr_un;
-        check_ptr(addr)?; // TODO: check addr_len
-        let path = clone_cstring_safely(unsafe { (&*addr).sun_path.as_ptr() })?
-            .to_string_lossy()
-            .into_owned();
-        unix_socket.bind(path)?;
+        let addr = UnixAddr::from_user(addr, addr_len)?;
+        unix_socket.bind(&addr)?;
         Ok(0)
     } else {
         return_errno!(EBADF, "not a socket")
     }
 }
 
+/// The ports below it can only be bound with CAP_NET_BIND_SERVICE
+const PROT_SOCK: u16 = 1024;
+
+/// Check that the port of an IPv4 or IPv6 address can be bound. The address
+/// is as long as sockaddr at least, so the port of sockaddr_in or sockaddr_in6
+/// is always there.
+fn check_bind_port(addr: *const libc::sockaddr, credentials: &Credentials) -> Result<()> {
+    let family = unsafe { (*addr).sa_family } as c_int;
+    if family != libc::AF_INET && family != libc::AF_INET6 {
+        return Ok(());
+    }
+    let bytes = unsafe { std::slice::from_raw_parts(addr as *const u8, 4) };
+    let port = u16::from_be_bytes([bytes[2], bytes[3]]);
+    if port != 0 && port < PROT_SOCK && !credentials.has_capability(Capabilities::NET_BIND_SERVICE)
+    {
+        return_errno!(EACCES, "the port requires CAP_NET_BIND_SERVICE");
+    }
+    Ok(())
+}
+
 fn do_listen(fd: c_int, backlog: c_int) -> Result<isize> {
     info!("listen: fd: {}, backlog: {}", fd, backlog);
     let current_ref = process::get_current();
     let mut proc = current_ref.lock().unwrap();
     let file_ref = proc.get_files().lock().unwrap().get(fd as FileDesc)?;
     if let Ok(socket) = file_ref.as_socket() {
-        let ret = try_libc!(libc::ocall::listen(socket.fd(), backlog));
-        Ok(ret as isize)
+        socket.listen(backlog)?;
+        Ok(0)
     } else if let Ok(unix_socket) = file_ref.as_unix_socket() {
         unix_socket.listen()?;
         Ok(0)
@@ -873,6 +1551,26 @@ fn do_setsockopt(
     let mut proc = current_ref.lock().unwrap();
     let file_ref = proc.get_files().lock().unwrap().get(fd as FileDesc)?;
     if let Ok(socket) = file_ref.as_socket() {
+        // Busy polling is done by the LibOS, not the host
+        if level == libc::SOL_SOCKET && optname == SO_BUSY_POLL {
+            if optlen < std::mem::size_of::<c_int>() as libc::socklen_t {
+                return_errno!(EINVAL, "optlen is too small");
+            }
+            let optval = optval as *const c_int;
+            check_ptr(optval)?;
+            socket.set_busy_poll(unsafe { *optval })?;
+            return Ok(0);
+        }
+        if level == libc::SOL_SOCKET && optname == SO_BINDTODEVICE {
+            let name = device_name_from_user(optval, optlen)?;
+            socket.set_bound_device(&name)?;
+            return Ok(0);
+        }
+        if let Some(opt) = IpOption::from_sockopt(level, optname) {
+            let val = int_sockopt_from_user(optval, optlen, opt.accepts_byte())?;
+            socket.set_ip_option(opt, val)?;
+            return Ok(0);
+        }
         let ret = try_libc!(libc::ocall::setsockopt(
             socket.fd(),
             level,
@@ -903,8 +1601,32 @@ fn do_getsockopt(
     let current_ref = process::get_current();
     let mut proc = current_ref.lock().unwrap();
     let file_ref = proc.get_files().lock().unwrap().get(fd as FileDesc)?;
+
+    // The errors of unix sockets are all returned by the operations that
+    // fail, so none is pending
+    if level == libc::SOL_SOCKET && optname == libc::SO_ERROR && file_ref.as_unix_socket().is_ok() {
+        copy_int_sockopt_to_user(0, optval, optlen)?;
+        return Ok(0);
+    }
     let socket = file_ref.as_socket()?;
 
+    if level == libc::SOL_SOCKET && optname == SO_BUSY_POLL {
+        copy_int_sockopt_to_user(socket.get_busy_poll(), optval, optlen)?;
+        return Ok(0);
+    }
+    if level == libc::SOL_SOCKET && optname == libc::SO_ERROR {
+        copy_int_sockopt_to_user(socket.take_pending_error()?, optval, optlen)?;
+        return Ok(0);
+    }
+    if level == libc::SOL_SOCKET && optname == SO_BINDTODEVICE {
+        copy_device_name_to_user(socket.get_bound_device(), optval, optlen)?;
+        return Ok(0);
+    }
+    if let Some(opt) = IpOption::from_sockopt(level, optname) {
+        copy_int_sockopt_to_user(socket.get_ip_option(opt)?, optval, optlen)?;
+        return Ok(0);
+    }
+
     let ret = try_libc!(libc::ocall::getsockopt(
         socket.fd(),
         level,
@@ -915,6 +1637,91 @@ fn do_getsockopt(
     Ok(ret as isize)
 }
 
+/// Get the value of a socket option of type int from the user, which may be
+/// given as a byte instead if the option accepts it
+fn int_sockopt_from_user(
+    optval: *const c_void,
+    optlen: libc::socklen_t,
+    accepts_byte: bool,
+) -> Result<c_int> {
+    if optlen as usize >= std::mem::size_of::<c_int>() {
+        let optval = optval as *const c_int;
+        check_ptr(optval)?;
+        return Ok(unsafe { *optval });
+    }
+    if accepts_byte && optlen >= 1 {
+        let optval = optval as *const u8;
+        check_ptr(optval)?;
+        return Ok(unsafe { *optval } as c_int);
+    }
+    return_errno!(EINVAL, "optlen is too small")
+}
+
+/// Copy the value of a socket option of type int to the user
+fn copy_int_sockopt_to_user(
+    val: c_int,
+    optval: *mut c_void,
+    optlen: *mut libc::socklen_t,
+) -> Result<()> {
+    check_mut_ptr(optlen)?;
+    let optlen = unsafe { &mut *optlen };
+    if *optlen < std::mem::size_of::<c_int>() as libc::socklen_t {
+        return_errno!(EINVAL, "optlen is too small");
+    }
+    let optval = optval as *mut c_int;
+    check_mut_ptr(optval)?;
+    unsafe {
+        *optval = val;
+    }
+    *optlen = std::mem::size_of::<c_int>() as libc::socklen_t;
+    Ok(())
+}
+
+/// Get the name of a network device from the user, which ends at the null
+/// terminator or the end of the buffer, whichever comes first. A name of
+/// IFNAMSIZ bytes or more is read as is, and is then rejected with EINVAL.
+fn device_name_from_user(optval: *const c_void, optlen: libc::socklen_t) -> Result<String> {
+    let len = min(optlen as usize, IFNAMSIZ);
+    if len == 0 {
+        return Ok(String::new());
+    }
+    let optval = optval as *const u8;
+    check_array(optval, len)?;
+    let bytes = unsafe { std::slice::from_raw_parts(optval, len) };
+    let name_len = bytes.iter().position(|&b| b == 0).unwrap_or(len);
+    let name =
+        std::str::from_utf8(&bytes[..name_len]).map_err(|_| errno!(ENODEV, "no such device"))?;
+    Ok(name.to_string())
+}
+
+/// Copy the name of a network device to the user with the null terminator.
+/// The length is zero if there is no device, whatever the size of the buffer.
+fn copy_device_name_to_user(
+    name: Option<String>,
+    optval: *mut c_void,
+    optlen: *mut libc::socklen_t,
+) -> Result<()> {
+    check_mut_ptr(optlen)?;
+    let optlen = unsafe { &mut *optlen };
+    let name = match name {
+        Some(name) => name,
+        None => {
+            *optlen = 0;
+            return Ok(());
+        }
+    };
+    if (*optlen as usize) < IFNAMSIZ {
+        return_errno!(EINVAL, "optlen is too small");
+    }
+    let optval = optval as *mut u8;
+    check_mut_array(optval, name.len() + 1)?;
+    let buf = unsafe { std::slice::from_raw_parts_mut(optval, name.len() + 1) };
+    buf[..name.len()].copy_from_slice(name.as_bytes());
+    buf[name.len()] = 0;
+    *optlen = (name.len() + 1) as libc::socklen_t;
+    Ok(())
+}
+
 fn do_getpeername(
     fd: c_int,
     addr: *mut libc::sockaddr,
@@ -931,11 +1738,8 @@ fn do_getpeername(
         let ret = try_libc!(libc::ocall::getpeername(socket.fd(), addr, addr_len));
         Ok(ret as isize)
     } else if let Ok(unix_socket) = file_ref.as_unix_socket() {
-        warn!("getpeername for unix socket is unimplemented");
-        return_errno!(
-            ENOTCONN,
-            "hack for php: Transport endpoint is not connected"
-        )
+        unix_socket.peer_addr()?.copy_to_user(addr, addr_len)?;
+        Ok(0)
     } else {
         return_errno!(EBADF, "not a socket")
     }
@@ -957,7 +1761,7 @@ fn do_getsockname(
         let ret = try_libc!(libc::ocall::getsockname(socket.fd(), addr, addr_len));
         Ok(ret as isize)
     } else if let Ok(unix_socket) = file_ref.as_unix_socket() {
-        warn!("getsockname for unix socket is unimplemented");
+        unix_socket.addr().copy_to_user(addr, addr_len)?;
         Ok(0)
     } else {
         return_errno!(EBADF, "not a socket")
@@ -976,19 +1780,56 @@ fn do_sendto(
         "sendto: fd: {}, base: {:?}, len: {}, addr: {:?}, addr_len: {}",
         fd, base, len, addr, addr_len
     );
-    let current_ref = process::get_current();
-    let mut proc = current_ref.lock().unwrap();
-    let file_ref = proc.get_files().lock().unwrap().get(fd as FileDesc)?;
+    // The socket may block, so the process is not kept locked
+    let file_ref = fs::get_file(fd as FileDesc)?;
     let socket = file_ref.as_socket()?;
+    let msg_flags = MsgFlags::from_u32(flags as u32)?;
+    let ret = sendto_socket(socket, base, len, flags, addr, addr_len);
+    net::raise_sigpipe_on_epipe(ret, msg_flags)
+}
 
-    let ret = try_libc!(libc::ocall::sendto(
-        socket.fd(),
-        base,
-        len,
-        flags,
-        addr,
-        addr_len
-    ));
+fn sendto_socket(
+    socket: &SocketFile,
+    base: *const c_void,
+    len: size_t,
+    flags: c_int,
+    addr: *const libc::sockaddr,
+    addr_len: libc::socklen_t,
+) -> Result<isize> {
+    socket.check_send_shut()?;
+    if let Some(stream) = socket.loopback() {
+        check_array(base as *const u8, len)?;
+        let buf = unsafe { std::slice::from_raw_parts(base as *const u8, len) };
+        let flags = MsgFlags::from_u32(flags as u32)?;
+        // The address is ignored by a connected TCP socket
+        let ret = socket.send_loopback(&stream, &[buf], flags)?;
+        return Ok(ret as isize);
+    }
+    if socket.is_udp() {
+        check_array(base as *const u8, len)?;
+        let buf = unsafe { std::slice::from_raw_parts(base as *const u8, len) };
+        let name = if addr.is_null() {
+            None
+        } else {
+            check_array(addr as *const u8, addr_len as usize)?;
+            Some(unsafe { std::slice::from_raw_parts(addr as *const u8, addr_len as usize) })
+        };
+        if let Some(ret) = socket.send_udp_loopback(&[buf], name)? {
+            return Ok(ret as isize);
+        }
+    }
+    // The host never raises SIGPIPE, as for sendmsg
+    let host_flags = flags | MsgFlags::MSG_NOSIGNAL.to_u32() as c_int;
+    let ret = socket.do_blocking_op(|| {
+        Ok(try_libc!(libc::ocall::sendto(
+            socket.fd(),
+            base,
+            len,
+            host_flags,
+            addr,
+            addr_len
+        )))
+    })?;
     Ok(ret as isize)
 }
 
@@ -1004,19 +1845,69 @@ fn do_recvfrom(
         "recvfrom: fd: {}, base: {:?}, len: {}, flags: {}, addr: {:?}, addr_len: {:?}",
         fd, base, len, flags, addr, addr_len
     );
-    let current_ref = process::get_current();
-    let mut proc = current_ref.lock().unwrap();
-    let file_ref = proc.get_files().lock().unwrap().get(fd as FileDesc)?;
+    // The socket may block, so the process is not kept locked
+    let file_ref = fs::get_file(fd as FileDesc)?;
     let socket = file_ref.as_socket()?;
 
-    let ret = try_libc!(libc::ocall::recvfrom(
-        socket.fd(),
-        base,
-        len,
-        flags,
-        addr,
-        addr_len
-    ));
+    if socket.is_recv_shut() {
+        return Ok(0);
+    }
+    if let Some(stream) = socket.loopback() {
+        check_mut_array(base as *mut u8, len)?;
+        let buf = unsafe { std::slice::from_raw_parts_mut(base as *mut u8, len) };
+        let flags = MsgFlags::from_u32(flags as u32)?;
+        let ret = socket.recv_loopback(&stream, &mut [buf], flags)?;
+        // No address is received by a connected TCP socket
+        if !addr.is_null() {
+            check_mut_ptr(addr_len)?;
+            unsafe {
+                *addr_len = 0;
+            }
+        }
+        return Ok(ret as isize);
+    }
+    if let Some(inbox) = socket.udp_inbox() {
+        check_mut_array(base as *mut u8, len)?;
+        let buf = unsafe { std::slice::from_raw_parts_mut(base as *mut u8, len) };
+        let flags = MsgFlags::from_u32(flags as u32)?;
+        let recvd = socket.recv_udp_loopback(&inbox, flags, |flags| {
+            Ok(try_libc!(libc::ocall::recvfrom(
+                socket.fd(),
+                base,
+                len,
+                flags.to_u32() as c_int,
+                addr,
+                addr_len
+            )))
+        })?;
+        let datagram = match recvd {
+            UdpRecvd::Looped(datagram) => datagram,
+            UdpRecvd::Host(ret) => return Ok(ret as isize),
+        };
+        if !addr.is_null() {
+            check_mut_ptr(addr_len)?;
+            let name_len = unsafe { *addr_len } as usize;
+            check_mut_array(addr as *mut u8, name_len)?;
+            let name = unsafe { std::slice::from_raw_parts_mut(addr as *mut u8, name_len) };
+            let name_len = datagram.copy_name_to(Some(name));
+            unsafe {
+                *addr_len = name_len as libc::socklen_t;
+            }
+        }
+        let (ret, _) = datagram.copy_data_to(&mut [buf], flags);
+        return Ok(ret as isize);
+    }
+    socket.busy_poll_before_recv(flags)?;
+    let ret = socket.do_blocking_op(|| {
+        Ok(try_libc!(libc::ocall::recvfrom(
+            socket.fd(),
+            base,
+            len,
+            flags,
+            addr,
+            addr_len
+        )))
+    })?;
     Ok(ret as isize)
 }
 
@@ -1040,16 +1931,17 @@ fn do_socketpair(
             UnixSocketFile::socketpair(socket_type as i32, protocol as i32)?;
         let current_ref = process::get_current();
         let mut proc = current_ref.lock().unwrap();
-        sock_pair[0] = proc
-            .get_files()
-            .lock()
-            .unwrap()
-            .put(Arc::new(Box::new(client_socket)), false);
-        sock_pair[1] = proc
-            .get_files()
-            .lock()
-            .unwrap()
-            .put(Arc::new(Box::new(server_socket)), false);
+        let mut file_table = proc.get_files().lock().unwrap();
+        let client_fd = file_table.put(Arc::new(Box::new(client_socket)), false)?;
+        let server_fd = match file_table.put(Arc::new(Box::new(server_socket)), false) {
+            Ok(server_fd) => server_fd,
+            Err(e) => {
+                file_table.del(client_fd);
+                return Err(e);
+            }
+        };
+        sock_pair[0] = client_fd;
+        sock_pair[1] = server_fd;
 
         info!("socketpair: ({}, {})", sock_pair[0], sock_pair[1]);
         Ok(0)
@@ -1066,6 +1958,15 @@ fn do_uname(name: *mut utsname_t) -> Result<isize> {
     misc::do_uname(name).map(|_| 0)
 }
 
+fn do_getrandom(buf: *mut u8, len: usize, flags: u32) -> Result<isize> {
+    let buf = {
+        check_mut_array(buf, len)?;
+        unsafe { std::slice::from_raw_parts_mut(buf, len) }
+    };
+    let flags = GetRandomFlags::from_u32(flags)?;
+    misc::do_getrandom(buf, flags).map(|len| len as isize)
+}
+
 fn do_prlimit(
     pid: pid_t,
     resource: u32,
@@ -1092,12 +1993,125 @@ fn do_prlimit(
     misc::do_prlimit(pid, resource, new_limit, old_limit).map(|_| 0)
 }
 
-// TODO: implement signals
+/// The return value of the syscall and the user stack to return to, which are
+/// given to the syscall entry in %rax and %rdx
+#[repr(C)]
+pub struct SigReturn {
+    syscall_ret: isize,
+    user_rsp: usize,
+}
+
+/// Called by the syscall entry after the handler of a signal that is delivered
+/// at the exit of a syscall returns, before returning to the user
+#[no_mangle]
+pub extern "C" fn return_from_signal_handler(syscall_ret: isize) -> SigReturn {
+    let (user_rsp, restart) = process::return_from_sig_handler();
+    let syscall_ret = match restart {
+        // The syscall is done again from the start, so its arguments are
+        // checked again, and the next signals are delivered at its exit
+        Some(restart) => {
+            RESTART_DEADLINE.with(|deadline| deadline.set(restart.deadline));
+            let [arg0, arg1, arg2, arg3, arg4, arg5] = restart.args;
+            dispatch_syscall(restart.num, arg0, arg1, arg2, arg3, arg4, arg5)
+        }
+        None => {
+            if let Some(term_status) = process::deliver_pending_signals(&mut None) {
+                do_exit(term_status);
+            }
+            syscall_ret
+        }
+    };
+    SigReturn {
+        syscall_ret,
+        user_rsp,
+    }
+}
 
-fn do_rt_sigaction() -> Result<isize> {
+fn do_rt_sigaction(
+    signum: u32,
+    act: *const process::sigaction_t,
+    oldact: *mut process::sigaction_t,
+    sigsetsize: usize,
+) -> Result<isize> {
+    if sigsetsize != std::mem::size_of::<u64>() {
+        return_errno!(EINVAL, "invalid sigsetsize");
+    }
+    let signum =
+        SigNum::from_u32(signum)?.ok_or_else(|| errno!(EINVAL, "invalid signal number"))?;
+    let act = if !act.is_null() {
+        check_ptr(act)?;
+        Some(unsafe { *act })
+    } else {
+        None
+    };
+    if !oldact.is_null() {
+        check_mut_ptr(oldact)?;
+    }
+    let old_act = process::do_rt_sigaction(signum, act)?;
+    if !oldact.is_null() {
+        unsafe {
+            *oldact = old_act;
+        }
+    }
     Ok(0)
 }
 
-fn do_rt_sigprocmask() -> Result<isize> {
+fn do_rt_sigprocmask(
+    how: u32,
+    set: *const u64,
+    oldset: *mut u64,
+    sigsetsize: usize,
+) -> Result<isize> {
+    if sigsetsize != std::mem::size_of::<u64>() {
+        return_errno!(EINVAL, "invalid sigsetsize");
+    }
+    let set = if !set.is_null() {
+        check_ptr(set)?;
+        Some(SigSet::from_u64(unsafe { *set }))
+    } else {
+        None
+    };
+    if !oldset.is_null() {
+        check_mut_ptr(oldset)?;
+    }
+    // The how is checked only if the mask is to be changed, as in Linux
+    let how = match set {
+        Some(_) => SigMaskHow::from_u32(how)?,
+        None => SigMaskHow::SIG_BLOCK,
+    };
+    let old_mask = process::do_rt_sigprocmask(how, set)?;
+    if !oldset.is_null() {
+        unsafe {
+            *oldset = old_mask.as_u64();
+        }
+    }
+    Ok(0)
+}
+
+fn do_kill(pid: i32, signum: u32) -> Result<isize> {
+    if pid <= 0 {
+        // TODO: send the signal to a process group or all processes
+        return_errno!(ENOSYS, "only sending signals to a process is supported");
+    }
+    let signum = SigNum::from_u32(signum)?;
+    process::do_kill(pid as pid_t, signum)?;
+    Ok(0)
+}
+
+fn do_tkill(tid: i32, signum: u32) -> Result<isize> {
+    if tid <= 0 {
+        return_errno!(EINVAL, "invalid tid");
+    }
+    let signum = SigNum::from_u32(signum)?;
+    process::do_tkill(tid as pid_t, signum)?;
+    Ok(0)
+}
+
+fn do_tgkill(tgid: i32, tid: i32, signum: u32) -> Result<isize> {
+    if tgid <= 0 || tid <= 0 {
+        return_errno!(EINVAL, "invalid tgid or tid");
+    }
+    let signum = SigNum::from_u32(signum)?;
+    process::do_tgkill(tgid as pid_t, tid as pid_t, signum)?;
     Ok(0)
 }
@@ -328,18 +328,31 @@ pub enum SyscallNum {
     SysUserfaultfd = 323,
     SysMembarrier = 324,
     SysMlock2 = 325,
+    SysCopyFileRange = 326,
+    SysPreadv2 = 327,
+    SysPwritev2 = 328,
+
+    SysStatx = 332,
 
     SysSpawn = 360,
+    SysSetSyscallFilter = 361,
+    SysSpawnWithAttr = 362,
+
+    SysIoUringSetup = 425,
+    SysIoUringEnter = 426,
+
+    SysEpollPwait2 = 441,
 }
 
 impl TryFrom<u32> for SyscallNum {
     type Error = error::Error;
 
     fn try_from(value: u32) -> Result<Self> {
-        if value > 325 && value != 360 {
-            return_errno!(EINVAL, "invalid syscall number");
-        } else {
-            Ok(unsafe { core::mem::transmute(value as u16) })
+        match value {
+            0..=328 | 332 | 360..=362 | 425 | 426 | 441 => {
+                Ok(unsafe { core::mem::transmute(value as u16) })
+            }
+            _ => return_errno!(EINVAL, "invalid syscall number"),
         }
     }
 }
@@ -8,7 +8,9 @@ use std::{fmt, u64};
 use syscall::SyscallNum;
 
 mod profiler;
+mod vdso;
 
+pub use self::vdso::init_page as init_vdso_time_page;
 pub use profiler::GLOBAL_PROFILER;
 
 #[allow(non_camel_case_types)]
@@ -49,6 +51,13 @@ pub fn do_gettimeofday() -> timeval_t {
         occlum_ocall_gettimeofday(&mut tv as *mut timeval_t);
     }
     tv.validate().expect("ocall returned invalid timeval_t");
+    vdso::update(
+        ClockID::CLOCK_REALTIME,
+        &timespec_t {
+            sec: tv.sec,
+            nsec: tv.usec * 1_000,
+        },
+    );
     tv
 }
 
@@ -61,6 +70,13 @@ pub struct timespec_t {
 }
 
 impl timespec_t {
+    pub fn from_duration(duration: Duration) -> timespec_t {
+        timespec_t {
+            sec: duration.as_secs() as time_t,
+            nsec: duration.subsec_nanos() as i64,
+        }
+    }
+
     pub fn from_raw_ptr(ptr: *const timespec_t) -> Result<timespec_t> {
         let ts = unsafe { *ptr };
         ts.validate()?;
@@ -113,7 +129,18 @@ impl ClockID {
     }
 }
 
+/// Get the time of a clock.
+///
+/// The CPU-time clocks are accounted per LibOS thread and per LibOS process.
+/// All other clocks, including CLOCK_MONOTONIC_RAW and CLOCK_BOOTTIME, are
+/// provided by the host, which keeps them monotonic across calls.
 pub fn do_clock_gettime(clockid: ClockID) -> Result<timespec_t> {
+    match clockid {
+        ClockID::CLOCK_THREAD_CPUTIME_ID => return do_thread_getcpuclock(),
+        ClockID::CLOCK_PROCESS_CPUTIME_ID => return do_process_getcpuclock(),
+        _ => {}
+    }
+
     extern "C" {
         fn occlum_ocall_clock_gettime(clockid: clockid_t, tp: *mut timespec_t) -> sgx_status_t;
     }
@@ -123,9 +150,38 @@ pub fn do_clock_gettime(clockid: ClockID) -> Result<timespec_t> {
         occlum_ocall_clock_gettime(clockid as clockid_t, &mut tv as *mut timespec_t);
     }
     tv.validate().expect("ocall returned invalid timespec");
+    vdso::update(clockid, &tv);
     Ok(tv)
 }
 
+/// Get the resolution of a clock.
+///
+/// The values returned by do_clock_gettime come from the host, so does the
+/// resolution.
+pub fn do_clock_getres(clockid: ClockID) -> Result<timespec_t> {
+    extern "C" {
+        fn occlum_ocall_clock_getres(
+            ret: *mut c_int,
+            clockid: clockid_t,
+            res: *mut timespec_t,
+        ) -> sgx_status_t;
+    }
+
+    let mut res: timespec_t = Default::default();
+    try_libc!({
+        let mut retval: i32 = 0;
+        let status = occlum_ocall_clock_getres(
+            &mut retval,
+            clockid as clockid_t,
+            &mut res as *mut timespec_t,
+        );
+        assert!(status == sgx_status_t::SGX_SUCCESS);
+        retval
+    });
+    res.validate()?;
+    Ok(res)
+}
+
 pub fn do_nanosleep(req: &timespec_t) -> Result<()> {
     extern "C" {
         fn occlum_ocall_nanosleep(req: *const timespec_t) -> sgx_status_t;
@@ -152,6 +208,68 @@ pub fn do_thread_getcpuclock() -> Result<timespec_t> {
     Ok(tv)
 }
 
+/// Get the CPU time of a host thread that runs a LibOS thread
+fn do_host_thread_getcpuclock(host_tid: pid_t) -> Result<timespec_t> {
+    extern "C" {
+        fn occlum_ocall_host_thread_getcpuclock(
+            ret: *mut c_int,
+            host_tid: c_int,
+            tp: *mut timespec_t,
+        ) -> sgx_status_t;
+    }
+
+    let mut tv: timespec_t = Default::default();
+    try_libc!({
+        let mut retval: i32 = 0;
+        let status = occlum_ocall_host_thread_getcpuclock(
+            &mut retval,
+            host_tid as c_int,
+            &mut tv as *mut timespec_t,
+        );
+        assert!(status == sgx_status_t::SGX_SUCCESS);
+        retval
+    });
+    tv.validate()?;
+    Ok(tv)
+}
+
+/// Get the CPU time of the current process, i.e., the sum of the CPU time
+/// consumed by all threads in the thread group, including the exited ones.
+pub fn do_process_getcpuclock() -> Result<timespec_t> {
+    let tgid = process::do_getpid();
+    let total = get_process_cputime(tgid)?;
+    Ok(timespec_t::from_duration(total))
+}
+
+/// Get the CPU time of the process given by the thread group ID, the same as
+/// CLOCK_PROCESS_CPUTIME_ID of the process
+pub fn get_process_cputime(tgid: pid_t) -> Result<Duration> {
+    let mut total = {
+        let leader_ref = process::get(tgid)?;
+        let leader = leader_ref.lock().unwrap();
+        leader.get_exited_threads_cputime()
+    };
+    for thread_ref in process::get_all() {
+        let host_tid = {
+            let thread = thread_ref.lock().unwrap();
+            if thread.get_pid() != tgid || thread.get_status() == process::Status::ZOMBIE {
+                continue;
+            }
+            thread.get_host_tid()
+        };
+        // The thread has not started running yet
+        if host_tid == 0 {
+            continue;
+        }
+        // The thread may exit in the meantime, whose CPU time is then
+        // accounted to the thread group leader
+        if let Ok(ts) = do_host_thread_getcpuclock(host_tid) {
+            total += ts.as_duration();
+        }
+    }
+    Ok(total)
+}
+
 // For SEFS
 pub struct OcclumTimeProvider;
 
@@ -0,0 +1,196 @@
+//! A vDSO-style fast path for clock_gettime and gettimeofday.
+//!
+//! Every process has a time page, which is made read-only for the user by the
+//! host page tables, and whose address is passed to the user program by the
+//! auxiliary vector entry `AT_OCCLUM_VDSO_TIME`. The page contains the TSC scaling parameters and a
+//! (TSC, time) base for CLOCK_REALTIME and CLOCK_MONOTONIC. With these, the
+//! user program can calculate the current time with RDTSC, without calling
+//! into the LibOS, let alone making an OCall. See `occlum_vdso_time.h` for the
+//! user-side reader.
+//!
+//! The parameters are calibrated and refreshed on the slow path, i.e., every
+//! time the LibOS gets the time from the host. The fast path is disabled if
+//! RDTSC is not supported natively inside the enclave.
+use super::*;
+use exception::is_rdtsc_emulated;
+use std::ptr;
+use std::sync::atomic::{fence, Ordering};
+use vm::{VMPerms, VMRange};
+
+/// The layout of the time page. This must be in sync with occlum_vdso_time.h.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+#[allow(non_camel_case_types)]
+struct vdso_time_data_t {
+    seq: u32,
+    valid: u32,
+    shift: u32,
+    padding: u32,
+    mult: u64,
+    // Indexed by the clock ID: CLOCK_REALTIME and CLOCK_MONOTONIC
+    bases: [vdso_time_base_t; 2],
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+#[allow(non_camel_case_types)]
+struct vdso_time_base_t {
+    tsc: u64,
+    sec: u64,
+    nsec: u64,
+}
+
+/// The time is calculated as `base_ns + ((tsc - base_tsc) * mult) >> SHIFT`
+const SHIFT: u32 = 32;
+/// The minimal interval between the first sample and the current sample for
+/// the TSC frequency to be calibrated accurately enough
+const MIN_CALIBRATION_NS: u64 = 10_000_000;
+/// The minimal interval between the refreshes of the time page of a process,
+/// each of which makes the page writable for a while by two OCalls
+const MIN_REFRESH_NS: u64 = 100_000_000;
+
+#[derive(Debug, Default)]
+struct VdsoTimeKeeper {
+    // The first sample of CLOCK_MONOTONIC, used for calibration
+    first: Option<vdso_time_base_t>,
+    data: vdso_time_data_t,
+}
+
+lazy_static! {
+    static ref VDSO_TIME_KEEPER: SgxMutex<VdsoTimeKeeper> = Default::default();
+}
+
+fn rdtsc() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+fn to_ns(sec: u64, nsec: u64) -> u64 {
+    sec * 1_000_000_000 + nsec
+}
+
+/// Record a sample of the time got from the host.
+pub fn update(clockid: ClockID, ts: &timespec_t) {
+    let idx = match clockid {
+        ClockID::CLOCK_REALTIME => 0,
+        ClockID::CLOCK_MONOTONIC => 1,
+        _ => return,
+    };
+    // RDTSC is not sampled once it is known to be emulated, which costs an
+    // exception each time. So only the first sample is taken in vain then.
+    if is_rdtsc_emulated() {
+        return;
+    }
+    let sample = vdso_time_base_t {
+        tsc: rdtsc(),
+        sec: ts.sec as u64,
+        nsec: ts.nsec as u64,
+    };
+    if is_rdtsc_emulated() {
+        return;
+    }
+
+    let data = {
+        let mut keeper = VDSO_TIME_KEEPER.lock().unwrap();
+        keeper.data.bases[idx] = sample;
+        if idx == 1 {
+            match keeper.first {
+                None => keeper.first = Some(sample),
+                Some(first) => {
+                    // The samples taken concurrently may be recorded out of
+                    // order, which fails the calibration this time
+                    let elapsed_ns = to_ns(sample.sec, sample.nsec)
+                        .checked_sub(to_ns(first.sec, first.nsec))
+                        .unwrap_or(0);
+                    let elapsed_tsc = sample.tsc.checked_sub(first.tsc).unwrap_or(0);
+                    if elapsed_ns >= MIN_CALIBRATION_NS && elapsed_tsc > 0 {
+                        keeper.data.mult =
+                            (((elapsed_ns as u128) << SHIFT) / elapsed_tsc as u128) as u64;
+                        keeper.data.shift = SHIFT;
+                    }
+                }
+            }
+        }
+        // Both clocks must have a base before the fast path can be used
+        let has_bases = keeper.data.bases.iter().all(|base| base.tsc != 0);
+        keeper.data.valid = (keeper.data.mult != 0 && has_bases) as u32;
+        keeper.data
+    };
+
+    // The time page can only be updated in the context of a user process.
+    // Since the time may be got by the LibOS while the current process or its
+    // VM is locked (e.g., by SEFS to update timestamps), refreshing the page is
+    // best effort: it is skipped if the locks are not available.
+    if process::current_pid() == 0 {
+        return;
+    }
+    let current_ref = process::get_current();
+    let current = match current_ref.try_lock() {
+        Ok(current) => current,
+        Err(_) => return,
+    };
+    let vm = match current.get_vm().try_lock() {
+        Ok(vm) => vm,
+        Err(_) => return,
+    };
+    let page = vm.get_vdso_range();
+    if !needs_refresh(page.start(), &data, idx) {
+        return;
+    }
+    if let Err(e) = refresh_page(page, &data) {
+        warn!("failed to refresh the vDSO time page: {:?}", e);
+    }
+}
+
+/// Initialize the time page of a new process, which is then read-only for the
+/// user
+pub fn init_page(page: &VMRange) -> Result<()> {
+    let data = VDSO_TIME_KEEPER.lock().unwrap().data;
+    unsafe {
+        write_page(page.start(), &data);
+    }
+    vm::set_host_page_protection(page, VMPerms::READ)
+}
+
+// The page is refreshed if the fast path is enabled or disabled, or the base
+// of the clock in the page is old. As the time is extrapolated from the base
+// by the TSC, an old base is still accurate as long as the TSC is stable.
+fn needs_refresh(page: usize, data: &vdso_time_data_t, idx: usize) -> bool {
+    let page = page as *const vdso_time_data_t;
+    let (valid, base) = unsafe {
+        (
+            ptr::read_volatile(&(*page).valid),
+            ptr::read_volatile(&(*page).bases[idx]),
+        )
+    };
+    let new_base = &data.bases[idx];
+    valid != data.valid
+        || to_ns(new_base.sec, new_base.nsec).saturating_sub(to_ns(base.sec, base.nsec))
+            >= MIN_REFRESH_NS
+}
+
+fn refresh_page(page: &VMRange, data: &vdso_time_data_t) -> Result<()> {
+    vm::set_host_page_protection(page, VMPerms::READ | VMPerms::WRITE)?;
+    unsafe {
+        write_page(page.start(), data);
+    }
+    vm::set_host_page_protection(page, VMPerms::READ)
+}
+
+/// Write the time page like a seqlock writer. Concurrent writers of the same
+/// page are serialized by the lock of the process's VM.
+unsafe fn write_page(page: usize, data: &vdso_time_data_t) {
+    let page = page as *mut vdso_time_data_t;
+    let seq_ptr = &mut (*page).seq as *mut u32;
+
+    let seq = ptr::read_volatile(seq_ptr);
+    ptr::write_volatile(seq_ptr, seq.wrapping_add(1));
+    fence(Ordering::Release);
+
+    ptr::write_volatile(&mut (*page).valid, data.valid);
+    ptr::write_volatile(&mut (*page).shift, data.shift);
+    ptr::write_volatile(&mut (*page).mult, data.mult);
+    ptr::write_volatile(&mut (*page).bases, data.bases);
+
+    fence(Ordering::Release);
+    ptr::write_volatile(seq_ptr, seq.wrapping_add(2));
+}
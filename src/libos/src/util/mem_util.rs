@@ -12,7 +12,7 @@ pub mod from_user {
         if user_ptr.is_null() {
             return_errno!(EINVAL, "Address 0 is invalid");
         }
-        Ok(())
+        check_access(user_ptr as usize, std::mem::size_of::<T>(), false)
     }
 
     /// Check the mutable user pointer is within the writable memory of the user process
@@ -20,19 +20,32 @@ pub mod from_user {
         if user_ptr.is_null() {
             return_errno!(EINVAL, "Address 0 is invalid");
         }
-        Ok(())
+        check_access(user_ptr as usize, std::mem::size_of::<T>(), true)
     }
 
     /// Check the readonly array is within the readable memory of the user process
     pub fn check_array<T>(user_buf: *const T, count: usize) -> Result<()> {
-        check_ptr(user_buf);
-        Ok(())
+        let len = count
+            .checked_mul(std::mem::size_of::<T>())
+            .ok_or_else(|| errno!(EFAULT, "the array is too large"))?;
+        check_access(user_buf as usize, len, false)
     }
 
     /// Check the mutable array is within the writable memory of the user process
     pub fn check_mut_array<T>(user_buf: *mut T, count: usize) -> Result<()> {
-        check_mut_ptr(user_buf);
-        Ok(())
+        let len = count
+            .checked_mul(std::mem::size_of::<T>())
+            .ok_or_else(|| errno!(EFAULT, "the array is too large"))?;
+        check_access(user_buf as usize, len, true)
+    }
+
+    // The memory is checked against the protections tracked by the VM of the
+    // current process, since they are not enforced by the hardware
+    fn check_access(addr: usize, len: usize, write: bool) -> Result<()> {
+        match process::current_vm() {
+            Some(vm) => vm.lock().unwrap().check_user_access(addr, len, write),
+            None => Ok(()),
+        }
     }
 
     /// Clone a C-string from the user process safely
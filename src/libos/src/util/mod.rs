@@ -4,3 +4,4 @@ pub mod log;
 pub mod mem_util;
 pub mod mpx_util;
 pub mod ring_buf;
+pub mod waiter;
@@ -27,6 +27,12 @@ void __mpx_bndcu1(unsigned long x);
 void __mpx_bndcu2(unsigned long x);
 void __mpx_bndcu3(unsigned long x);
 
+/* Store the lower bound and the upper bound (in 1's complement) of bnd<i> */
+void __mpx_bndmov0(unsigned long bounds[2]);
+void __mpx_bndmov1(unsigned long bounds[2]);
+void __mpx_bndmov2(unsigned long bounds[2]);
+void __mpx_bndmov3(unsigned long bounds[2]);
+
 #ifdef __cplusplus
 }
 #endif
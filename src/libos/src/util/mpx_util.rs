@@ -47,6 +47,19 @@ pub fn mpx_bndcu(bndreg: MpxReg, addr: usize) {
     }
 }
 
+/// Get the lower bound and the upper bound of the bound register
+pub fn mpx_bounds(bndreg: MpxReg) -> (usize, usize) {
+    let mut bounds = [0_usize; 2];
+    match bndreg {
+        MpxReg::BND0 => unsafe { __mpx_bndmov0(bounds.as_mut_ptr()) },
+        MpxReg::BND1 => unsafe { __mpx_bndmov1(bounds.as_mut_ptr()) },
+        MpxReg::BND2 => unsafe { __mpx_bndmov2(bounds.as_mut_ptr()) },
+        MpxReg::BND3 => unsafe { __mpx_bndmov3(bounds.as_mut_ptr()) },
+    }
+    // The upper bound is kept in 1's complement
+    (bounds[0], !bounds[1])
+}
+
 extern "C" {
     // See mpx_util.h
     fn __mpx_enable() -> i32;
@@ -62,4 +75,8 @@ extern "C" {
     fn __mpx_bndcu1(x: usize);
     fn __mpx_bndcu2(x: usize);
     fn __mpx_bndcu3(x: usize);
+    fn __mpx_bndmov0(bounds: *mut usize);
+    fn __mpx_bndmov1(bounds: *mut usize);
+    fn __mpx_bndmov2(bounds: *mut usize);
+    fn __mpx_bndmov3(bounds: *mut usize);
 }
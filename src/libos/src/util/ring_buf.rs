@@ -43,7 +43,7 @@ struct RingBufInner {
     capacity: usize,
     head: AtomicUsize,  // write to head
     tail: AtomicUsize,  // read from tail
-    closed: AtomicBool, // if reader has been dropped
+    closed: AtomicBool, // if reader or writer has been dropped
 }
 
 const RING_BUF_ALIGN: usize = 16;
@@ -93,6 +93,16 @@ impl RingBufInner {
         self.closed.store(true, Ordering::SeqCst);
     }
 
+    fn len(&self) -> usize {
+        let tail = self.get_tail();
+        let head = self.get_head();
+        if tail <= head {
+            head - tail
+        } else {
+            self.capacity - tail + head
+        }
+    }
+
     unsafe fn read_at(&self, pos: usize, dst_buf: &mut [u8]) {
         let dst_ptr = dst_buf.as_mut_ptr();
         let dst_len = dst_buf.len();
@@ -123,6 +133,16 @@ impl Drop for RingBufInner {
 
 impl RingBufReader {
     pub fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        self.do_read(buf, true)
+    }
+
+    /// Read the data as `read` does, but leave it in the buffer to be read
+    /// again
+    pub fn peek(&self, buf: &mut [u8]) -> Result<usize> {
+        self.do_read(buf, false)
+    }
+
+    fn do_read(&self, buf: &mut [u8], consumes: bool) -> Result<usize> {
         let mut tail = self.inner.get_tail();
         let mut buf_remain = buf.len();
         let mut buf_pos = 0;
@@ -148,7 +168,9 @@ impl RingBufReader {
             }
 
             tail = (tail + read_nbytes) & self.inner.get_mask();
-            self.inner.set_tail(tail);
+            if consumes {
+                self.inner.set_tail(tail);
+            }
 
             buf_pos += read_nbytes;
             buf_remain -= read_nbytes;
@@ -161,13 +183,15 @@ impl RingBufReader {
     }
 
     pub fn bytes_to_read(&self) -> usize {
-        let tail = self.inner.get_tail();
-        let head = self.inner.get_head();
-        if tail <= head {
-            head - tail
-        } else {
-            self.inner.capacity - tail + head
-        }
+        self.inner.len()
+    }
+
+    pub fn is_peer_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    pub fn close(&self) {
+        self.inner.close();
     }
 }
 
@@ -192,7 +216,9 @@ impl RingBufWriter {
 
             let write_nbytes = {
                 let may_write_nbytes = if tail <= head {
-                    self.inner.capacity - head
+                    // Leave a byte unused if the tail is at the start, or the
+                    // full buffer would be taken as empty
+                    self.inner.capacity - head - if tail == 0 { 1 } else { 0 }
                 } else {
                     tail - head - 1
                 };
@@ -218,13 +244,26 @@ impl RingBufWriter {
     }
 
     pub fn can_write(&self) -> bool {
-        let tail = self.inner.get_tail();
-        let head = self.inner.get_head();
-        let may_write_nbytes = if tail <= head {
-            self.inner.capacity - head
-        } else {
-            tail - head - 1
-        };
-        may_write_nbytes != 0
+        self.bytes_to_write() != 0
+    }
+
+    pub fn bytes_to_write(&self) -> usize {
+        // A byte is always left unused to tell a full buffer from an empty one
+        self.inner.capacity - 1 - self.inner.len()
+    }
+
+    pub fn is_peer_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    pub fn close(&self) {
+        self.inner.close();
+    }
+}
+
+impl Drop for RingBufWriter {
+    fn drop(&mut self) {
+        // So the reader knows when the writer is finished
+        self.inner.close();
     }
 }
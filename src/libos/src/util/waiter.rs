@@ -0,0 +1,338 @@
+//! Waiters and waiter queues.
+//!
+//! A thread that waits for a condition puts a `Waiter` into the `WaiterQueue`s
+//! of the objects whose changes may satisfy the condition, checks the
+//! condition, and then sleeps on the waiter until it is woken by one of the
+//! queues or the timeout expires. The waiter sleeps on a host eventfd of the
+//! thread, which, unlike the untrusted event of the SGX SDK, can be waited on
+//! with a timeout.
+//!
+//! The waits are interrupted by the signals sent to the thread, with which
+//! they fail with EINTR (see process::signal).
+
+use super::*;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+use time::{timespec_t, ClockID};
+
+#[derive(Debug, Clone)]
+pub struct Waiter {
+    inner: Arc<WaiterInner>,
+}
+
+#[derive(Debug)]
+struct WaiterInner {
+    is_woken: AtomicBool,
+    host_eventfd: Arc<HostEventFd>,
+}
+
+impl Waiter {
+    pub fn new() -> Result<Waiter> {
+        Ok(Waiter {
+            inner: Arc::new(WaiterInner {
+                is_woken: AtomicBool::new(false),
+                host_eventfd: current_host_eventfd()?,
+            }),
+        })
+    }
+
+    pub fn is_woken(&self) -> bool {
+        self.inner.is_woken.load(Ordering::SeqCst)
+    }
+
+    pub fn reset(&self) {
+        self.inner.is_woken.store(false, Ordering::SeqCst);
+    }
+
+    /// Sleep until the waiter is woken, or fail with ETIMEDOUT when the
+    /// timeout expires, or with EINTR when a signal interrupts the thread. The
+    /// wait is indefinite if the timeout is None.
+    pub fn wait(&self, timeout: Option<&Duration>) -> Result<()> {
+        let deadline = match timeout {
+            Some(timeout) => Some(monotonic_now()? + *timeout),
+            None => None,
+        };
+        process::wait_interruptibly(self, || {
+            while !self.is_woken() {
+                if process::is_sig_pending() {
+                    return_errno!(EINTR, "the wait is interrupted by a signal");
+                }
+                let remaining = match deadline {
+                    Some(deadline) => {
+                        let now = monotonic_now()?;
+                        if now >= deadline {
+                            return_errno!(ETIMEDOUT, "the wait times out");
+                        }
+                        Some(deadline - now)
+                    }
+                    None => None,
+                };
+                self.inner.host_eventfd.poll(remaining.as_ref())?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Sleep until the waiter is woken, any of the host fds is ready, or the
+    /// timeout expires, returning the number of the ready host fds as the
+    /// host poll does, or fail with EINTR when a signal interrupts the thread
+    /// before any host fd is ready. The wait is indefinite if the timeout is
+    /// None.
+    pub fn wait_with_host_fds(
+        &self,
+        host_pollfds: &mut Vec<libc::pollfd>,
+        timeout: Option<&Duration>,
+    ) -> Result<usize> {
+        process::wait_interruptibly(self, || self.do_wait_with_host_fds(host_pollfds, timeout))
+    }
+
+    fn do_wait_with_host_fds(
+        &self,
+        host_pollfds: &mut Vec<libc::pollfd>,
+        timeout: Option<&Duration>,
+    ) -> Result<usize> {
+        let host_timeout = match timeout {
+            _ if process::is_sig_pending() => 0,
+            _ if self.is_woken() => 0,
+            // Round up, or a short timeout would not be waited for at all
+            Some(timeout) => ((timeout.as_nanos() + 999_999) / 1_000_000) as c_int,
+            None => -1,
+        };
+        host_pollfds.push(libc::pollfd {
+            fd: self.inner.host_eventfd.fd,
+            events: libc::POLLIN,
+            revents: 0,
+        });
+        let ret = unsafe {
+            libc::ocall::poll(
+                host_pollfds.as_mut_ptr(),
+                host_pollfds.len() as u64,
+                host_timeout,
+            )
+        };
+        let errno = unsafe { libc::errno() };
+        let eventfd_pollfd = host_pollfds.pop().unwrap();
+        if ret < 0 {
+            return_errno!(Errno::from(errno as u32), "failed to poll the host fds");
+        }
+        let mut num_events = ret as usize;
+        if eventfd_pollfd.revents != 0 {
+            self.inner.host_eventfd.reset();
+            num_events -= 1;
+        }
+        if num_events == 0 && !self.is_woken() && process::is_sig_pending() {
+            return_errno!(EINTR, "the wait is interrupted by a signal");
+        }
+        Ok(num_events)
+    }
+
+    /// Wake the waiter, returning whether it was not woken yet
+    pub fn wake(&self) -> bool {
+        if self.inner.is_woken.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        self.inner.host_eventfd.write();
+        true
+    }
+
+    /// Make the sleep of the waiter return without waking it, after which the
+    /// waiter checks the signals of the thread
+    pub fn interrupt(&self) {
+        self.inner.host_eventfd.write();
+    }
+}
+
+#[derive(Debug)]
+pub struct WaiterQueue {
+    // The number of the waiters, which lets the wakes skip the lock when there
+    // is no waiter
+    count: AtomicUsize,
+    waiters: SgxMutex<VecDeque<Waiter>>,
+}
+
+impl WaiterQueue {
+    pub fn new() -> WaiterQueue {
+        WaiterQueue {
+            count: AtomicUsize::new(0),
+            waiters: SgxMutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count.load(Ordering::SeqCst) == 0
+    }
+
+    pub fn enqueue(&self, waiter: &Waiter) {
+        let mut waiters = self.waiters.lock().unwrap();
+        waiters.push_back(waiter.clone());
+        self.count.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn dequeue(&self, waiter: &Waiter) {
+        let mut waiters = self.waiters.lock().unwrap();
+        if let Some(i) = waiters
+            .iter()
+            .position(|queued| Arc::ptr_eq(&queued.inner, &waiter.inner))
+        {
+            waiters.remove(i);
+            self.count.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Wake all the waiters in the queue, which are removed from it, returning
+    /// the number of the waiters woken
+    pub fn dequeue_and_wake_all(&self) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+        let waiters: Vec<Waiter> = {
+            let mut waiters = self.waiters.lock().unwrap();
+            self.count.fetch_sub(waiters.len(), Ordering::SeqCst);
+            waiters.drain(..).collect()
+        };
+        waiters.iter().filter(|waiter| waiter.wake()).count()
+    }
+}
+
+/// Wait until `cond` gives Some, which is checked again each time the thread
+/// is woken by any of the queues, or fail with ETIMEDOUT when the timeout
+/// expires, or with EINTR when a signal interrupts the thread. The wait is
+/// indefinite if the timeout is None.
+///
+/// The waiter is put into the queues before the condition is checked, so a
+/// change that is made after the check and followed by a wake of the queues
+/// is never missed.
+pub fn wait_until<F, R>(
+    queues: &[&WaiterQueue],
+    timeout: Option<&Duration>,
+    mut cond: F,
+) -> Result<R>
+where
+    F: FnMut() -> Result<Option<R>>,
+{
+    let waiter = Waiter::new()?;
+    let deadline = match timeout {
+        Some(timeout) => Some(monotonic_now()? + *timeout),
+        None => None,
+    };
+    loop {
+        waiter.reset();
+        for queue in queues {
+            queue.enqueue(&waiter);
+        }
+        let ret = cond().and_then(|ret| match ret {
+            Some(ret) => Ok(Some(ret)),
+            None => {
+                let remaining = match deadline {
+                    Some(deadline) => {
+                        Some(deadline.checked_sub(monotonic_now()?).unwrap_or_default())
+                    }
+                    None => None,
+                };
+                waiter.wait(remaining.as_ref()).map(|_| None)
+            }
+        });
+        for queue in queues {
+            queue.dequeue(&waiter);
+        }
+        if let Some(ret) = ret? {
+            return Ok(ret);
+        }
+    }
+}
+
+fn monotonic_now() -> Result<Duration> {
+    Ok(time::do_clock_gettime(ClockID::CLOCK_MONOTONIC)?.as_duration())
+}
+
+/// The eventfd on the host, on which a thread sleeps until it is written
+#[derive(Debug)]
+struct HostEventFd {
+    fd: c_int,
+}
+
+impl HostEventFd {
+    fn new() -> Result<HostEventFd> {
+        let fd = try_libc!({
+            let mut retval: c_int = 0;
+            let status = occlum_ocall_eventfd(&mut retval, 0, EFD_CLOEXEC | EFD_NONBLOCK);
+            assert!(status == sgx_status_t::SGX_SUCCESS);
+            retval
+        });
+        Ok(HostEventFd { fd })
+    }
+
+    /// Sleep until the eventfd is written or the timeout expires. A write that
+    /// is made before the sleep makes it return at once.
+    fn poll(&self, timeout: Option<&Duration>) -> Result<()> {
+        let timeout = timeout.map(|timeout| timespec_t::from_duration(*timeout));
+        let timeout_ptr = timeout
+            .as_ref()
+            .map_or(std::ptr::null(), |ts| ts as *const timespec_t);
+        try_libc!({
+            let mut retval: c_int = 0;
+            let status = occlum_ocall_eventfd_poll(&mut retval, self.fd, timeout_ptr);
+            assert!(status == sgx_status_t::SGX_SUCCESS);
+            retval
+        });
+        Ok(())
+    }
+
+    /// Consume the writes to the eventfd, which has been polled on the host
+    fn reset(&self) {
+        let mut val: u64 = 0;
+        unsafe {
+            libc::ocall::read(
+                self.fd,
+                &mut val as *mut u64 as *mut c_void,
+                std::mem::size_of::<u64>(),
+            );
+        }
+    }
+
+    fn write(&self) {
+        let val: u64 = 1;
+        unsafe {
+            libc::ocall::write(
+                self.fd,
+                &val as *const u64 as *const c_void,
+                std::mem::size_of::<u64>(),
+            );
+        }
+    }
+}
+
+impl Drop for HostEventFd {
+    fn drop(&mut self) {
+        unsafe {
+            libc::ocall::close(self.fd);
+        }
+    }
+}
+
+thread_local! {
+    // The eventfd that the thread sleeps on, which is created on the first wait
+    static HOST_EVENTFD: RefCell<Option<Arc<HostEventFd>>> = RefCell::new(None);
+}
+
+fn current_host_eventfd() -> Result<Arc<HostEventFd>> {
+    HOST_EVENTFD.with(|host_eventfd| {
+        let mut host_eventfd = host_eventfd.borrow_mut();
+        if host_eventfd.is_none() {
+            *host_eventfd = Some(Arc::new(HostEventFd::new()?));
+        }
+        Ok(host_eventfd.as_ref().unwrap().clone())
+    })
+}
+
+const EFD_CLOEXEC: c_int = 0o2000000;
+const EFD_NONBLOCK: c_int = 0o4000;
+
+extern "C" {
+    fn occlum_ocall_eventfd(ret: *mut c_int, initval: c_uint, flags: c_int) -> sgx_status_t;
+    fn occlum_ocall_eventfd_poll(
+        ret: *mut c_int,
+        eventfd: c_int,
+        timeout: *const timespec_t,
+    ) -> sgx_status_t;
+}
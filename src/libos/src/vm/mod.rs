@@ -1,5 +1,5 @@
 use super::*;
-use fs::{File, FileDesc, FileRef};
+use fs::{File, FileDesc, FileRef, IoUring};
 use process::{get_current, Process, ProcessRef};
 use std::fmt;
 
@@ -7,12 +7,15 @@ mod process_vm;
 mod user_space_vm;
 mod vm_layout;
 mod vm_manager;
+mod vm_perms_map;
 mod vm_range;
 
 use self::vm_layout::VMLayout;
 use self::vm_manager::{VMManager, VMMapOptionsBuilder};
 
-pub use self::process_vm::{MMapFlags, ProcessVM, ProcessVMBuilder, VMPerms};
+pub use self::process_vm::{
+    set_host_page_protection, MMapFlags, ProcessVM, ProcessVMBuilder, VMPerms,
+};
 pub use self::vm_range::VMRange;
 
 pub fn do_mmap(
@@ -35,13 +38,21 @@ pub fn do_mmap(
         );
     }
 
+    let memlock_limit = get_memlock_limit();
     let mut current_vm_ref = {
         let current_ref = get_current();
         let current_process = current_ref.lock().unwrap();
         current_process.get_vm().clone()
     };
     let mut current_vm = current_vm_ref.lock().unwrap();
-    current_vm.mmap(addr, size, perms, flags, fd, offset)
+    let mmap_addr = current_vm.mmap(addr, size, perms, flags, fd, offset)?;
+    if flags.contains(MMapFlags::MAP_LOCKED) || current_vm.is_lock_future() {
+        if current_vm.mlock(mmap_addr, size, memlock_limit).is_err() {
+            current_vm.munmap(mmap_addr, size)?;
+            return_errno!(EAGAIN, "the mmap region cannot be locked");
+        }
+    }
+    Ok(mmap_addr)
 }
 
 pub fn do_munmap(addr: usize, size: usize) -> Result<()> {
@@ -55,13 +66,164 @@ pub fn do_munmap(addr: usize, size: usize) -> Result<()> {
     current_vm.munmap(addr, size)
 }
 
+pub fn do_mincore(addr: usize, size: usize, vec: &mut [u8]) -> Result<()> {
+    info!("mincore: addr: {:#x}, size: {:#x}", addr, size);
+    if addr % PAGE_SIZE != 0 {
+        return_errno!(EINVAL, "addr must be page-aligned");
+    }
+    if size == 0 {
+        return Ok(());
+    }
+    let mut current_vm_ref = {
+        let current_ref = get_current();
+        let current_process = current_ref.lock().unwrap();
+        current_process.get_vm().clone()
+    };
+    let mut current_vm = current_vm_ref.lock().unwrap();
+    current_vm.mincore(addr, size, vec)
+}
+
+pub const MADV_NORMAL: i32 = 0;
+pub const MADV_RANDOM: i32 = 1;
+pub const MADV_SEQUENTIAL: i32 = 2;
+pub const MADV_WILLNEED: i32 = 3;
+pub const MADV_DONTNEED: i32 = 4;
+
+pub fn do_madvise(addr: usize, size: usize, advice: i32) -> Result<()> {
+    info!(
+        "madvise: addr: {:#x}, size: {:#x}, advice: {}",
+        addr, size, advice
+    );
+    if addr % PAGE_SIZE != 0 {
+        return_errno!(EINVAL, "addr must be page-aligned");
+    }
+    match advice {
+        // The hints about the access patterns make no difference in enclaves
+        MADV_NORMAL | MADV_RANDOM | MADV_SEQUENTIAL | MADV_WILLNEED => return Ok(()),
+        MADV_DONTNEED => {}
+        _ => return_errno!(EINVAL, "unsupported advice"),
+    }
+    if size == 0 {
+        return Ok(());
+    }
+    let mut current_vm_ref = {
+        let current_ref = get_current();
+        let current_process = current_ref.lock().unwrap();
+        current_process.get_vm().clone()
+    };
+    let mut current_vm = current_vm_ref.lock().unwrap();
+    current_vm.madvise_dontneed(addr, size)
+}
+
 pub fn do_brk(addr: usize) -> Result<usize> {
     info!("brk: addr: {:#x}", addr);
     let current_ref = get_current();
     let current_process = current_ref.lock().unwrap();
     let current_vm_ref = current_process.get_vm();
     let mut current_vm = current_vm_ref.lock().unwrap();
-    current_vm.brk(addr)
+    let old_brk = current_vm.get_brk();
+    let new_brk = current_vm.brk(addr)?;
+    if new_brk > old_brk && current_vm.is_lock_future() {
+        let memlock_limit = current_process
+            .get_rlimits()
+            .lock()
+            .unwrap()
+            .get_memlock_limit();
+        if current_vm
+            .mlock(old_brk, new_brk - old_brk, memlock_limit)
+            .is_err()
+        {
+            current_vm.brk(old_brk)?;
+            return_errno!(ENOMEM, "the heap cannot be locked");
+        }
+    }
+    Ok(new_brk)
+}
+
+pub fn do_mprotect(addr: usize, size: usize, perms: VMPerms) -> Result<()> {
+    info!(
+        "mprotect: addr: {:#x}, size: {:#x}, perms: {:?}",
+        addr, size, perms
+    );
+    let mut current_vm_ref = {
+        let current_ref = get_current();
+        let current_process = current_ref.lock().unwrap();
+        current_process.get_vm().clone()
+    };
+    let mut current_vm = current_vm_ref.lock().unwrap();
+    current_vm.mprotect(addr, size, perms)
+}
+
+pub fn do_mlock(addr: usize, size: usize) -> Result<()> {
+    info!("mlock: addr: {:#x}, size: {:#x}", addr, size);
+    let memlock_limit = get_memlock_limit();
+    let mut current_vm_ref = {
+        let current_ref = get_current();
+        let current_process = current_ref.lock().unwrap();
+        current_process.get_vm().clone()
+    };
+    let mut current_vm = current_vm_ref.lock().unwrap();
+    current_vm.mlock(addr, size, memlock_limit)
+}
+
+pub fn do_munlock(addr: usize, size: usize) -> Result<()> {
+    info!("munlock: addr: {:#x}, size: {:#x}", addr, size);
+    let mut current_vm_ref = {
+        let current_ref = get_current();
+        let current_process = current_ref.lock().unwrap();
+        current_process.get_vm().clone()
+    };
+    let mut current_vm = current_vm_ref.lock().unwrap();
+    current_vm.munlock(addr, size)
+}
+
+pub const MCL_CURRENT: i32 = 1;
+pub const MCL_FUTURE: i32 = 2;
+pub const MCL_ONFAULT: i32 = 4;
+
+pub fn do_mlockall(flags: i32) -> Result<()> {
+    info!("mlockall: flags: {:#x}", flags);
+    if flags & !(MCL_CURRENT | MCL_FUTURE | MCL_ONFAULT) != 0
+        || flags & (MCL_CURRENT | MCL_FUTURE) == 0
+    {
+        return_errno!(EINVAL, "invalid flags");
+    }
+    let memlock_limit = get_memlock_limit();
+    let mut current_vm_ref = {
+        let current_ref = get_current();
+        let current_process = current_ref.lock().unwrap();
+        current_process.get_vm().clone()
+    };
+    let mut current_vm = current_vm_ref.lock().unwrap();
+    current_vm.mlockall(
+        flags & MCL_CURRENT != 0,
+        flags & MCL_FUTURE != 0,
+        memlock_limit,
+    )
+}
+
+pub fn do_munlockall() -> Result<()> {
+    info!("munlockall");
+    let mut current_vm_ref = {
+        let current_ref = get_current();
+        let current_process = current_ref.lock().unwrap();
+        current_process.get_vm().clone()
+    };
+    let mut current_vm = current_vm_ref.lock().unwrap();
+    current_vm.munlockall();
+    Ok(())
+}
+
+// Get the RLIMIT_MEMLOCK of the current process
+fn get_memlock_limit() -> usize {
+    let current_ref = get_current();
+    let current_process = current_ref.lock().unwrap();
+    let memlock_limit = current_process
+        .get_rlimits()
+        .lock()
+        .unwrap()
+        .get_memlock_limit();
+    memlock_limit
 }
 
 pub const PAGE_SIZE: usize = 4096;
@@ -4,6 +4,8 @@ use super::config;
 use super::process::{ElfFile, ProgramHeaderExt};
 use super::user_space_vm::{UserSpaceVMManager, UserSpaceVMRange, USER_SPACE_VM_MANAGER};
 use super::vm_manager::{VMInitializer, VMManager, VMMapAddr, VMMapOptions, VMMapOptionsBuilder};
+use super::vm_perms_map::VMPermsMap;
+use std::collections::BTreeSet;
 
 #[derive(Debug)]
 pub struct ProcessVMBuilder<'a, 'b> {
@@ -73,6 +75,7 @@ impl<'a, 'b> ProcessVMBuilder<'a, 'b> {
             VMLayout::new(heap_size, PAGE_SIZE)?,
             VMLayout::new(stack_size, PAGE_SIZE)?,
             VMLayout::new(mmap_size, PAGE_SIZE)?,
+            VMLayout::new(PAGE_SIZE, PAGE_SIZE)?,
         ];
         let process_layout = elf_layouts.iter().chain(other_layouts.iter()).fold(
             VMLayout::new_empty(),
@@ -137,20 +140,32 @@ impl<'a, 'b> ProcessVMBuilder<'a, 'b> {
         // Note: we do not need to fill zeros of the mmap region.
         // VMManager will fill zeros (if necessary) on mmap.
 
+        // Init the vDSO time page in the process
+        let vdso_layout = &other_layouts[3];
+        let vdso_min_start = mmap_range.end();
+        let vdso_range = VMRange::new_with_layout(vdso_layout, vdso_min_start);
+        unsafe { fill_zeros(vdso_range.start(), vdso_range.size()) };
+
         debug_assert!(elf_ranges
             .iter()
             .all(|elf_range| process_range.range().is_superset_of(elf_range)));
         debug_assert!(process_range.range().is_superset_of(&heap_range));
         debug_assert!(process_range.range().is_superset_of(&stack_range));
         debug_assert!(process_range.range().is_superset_of(&mmap_range));
+        debug_assert!(process_range.range().is_superset_of(&vdso_range));
 
         Ok(ProcessVM {
             process_range,
             elf_ranges,
             heap_range,
             stack_range,
+            vdso_range,
             brk,
             mmap_manager,
+            membarrier_registered: false,
+            locked_pages: BTreeSet::new(),
+            lock_future: false,
+            mmap_perms: VMPermsMap::new(),
         })
     }
 
@@ -204,8 +219,29 @@ pub struct ProcessVM {
     elf_ranges: Vec<VMRange>,
     heap_range: VMRange,
     stack_range: VMRange,
+    vdso_range: VMRange,
     brk: usize,
     mmap_manager: VMManager,
+    // Like mm_struct in Linux, the registration state of membarrier is shared
+    // by all threads that share the same address space
+    membarrier_registered: bool,
+    // The pages locked by mlock or mlockall
+    locked_pages: BTreeSet<usize>,
+    // Whether the pages mapped in the future are locked, i.e., MCL_FUTURE
+    lock_future: bool,
+    // The protections of the pages in the mmap area
+    mmap_perms: VMPermsMap,
+}
+
+impl Drop for ProcessVM {
+    fn drop(&mut self) {
+        // The page is reused by the LibOS after the process exits
+        if !self.vdso_range.empty() {
+            if let Err(e) = set_host_page_protection(&self.vdso_range, VMPerms::all()) {
+                warn!("failed to make the vDSO time page writable: {:?}", e);
+            }
+        }
+    }
 }
 
 impl Default for ProcessVM {
@@ -215,8 +251,13 @@ impl Default for ProcessVM {
             elf_ranges: Default::default(),
             heap_range: Default::default(),
             stack_range: Default::default(),
+            vdso_range: Default::default(),
             brk: Default::default(),
             mmap_manager: Default::default(),
+            membarrier_registered: false,
+            locked_pages: BTreeSet::new(),
+            lock_future: false,
+            mmap_perms: VMPermsMap::new(),
         }
     }
 }
@@ -238,6 +279,10 @@ impl ProcessVM {
         &self.stack_range
     }
 
+    pub fn get_vdso_range(&self) -> &VMRange {
+        &self.vdso_range
+    }
+
     pub fn get_base_addr(&self) -> usize {
         self.get_process_range().start()
     }
@@ -268,6 +313,8 @@ impl ProcessVM {
 
         if self.brk < new_brk {
             unsafe { fill_zeros(self.brk, new_brk - self.brk) };
+        } else {
+            self.unlock_pages(align_up(new_brk, PAGE_SIZE), align_up(self.brk, PAGE_SIZE));
         }
 
         self.brk = new_brk;
@@ -283,8 +330,16 @@ impl ProcessVM {
         fd: FileDesc,
         offset: usize,
     ) -> Result<usize> {
+        if config::LIBOS_CONFIG.vm.enforce_wx && perms.can_write() && perms.can_execute() {
+            return_errno!(EACCES, "writable and executable mappings are not allowed");
+        }
         let addr_option = {
-            if flags.contains(MMapFlags::MAP_FIXED) {
+            if flags.contains(MMapFlags::MAP_FIXED_NOREPLACE) {
+                if !self.process_range.range().contains(addr) {
+                    return_errno!(EINVAL, "Beyond valid memory range");
+                }
+                VMMapAddr::FixedNoReplace(addr)
+            } else if flags.contains(MMapFlags::MAP_FIXED) {
                 if !self.process_range.range().contains(addr) {
                     return_errno!(EINVAL, "Beyond valid memory range");
                 }
@@ -304,6 +359,10 @@ impl ProcessVM {
                 let current_ref = get_current();
                 let current_process = current_ref.lock().unwrap();
                 let file_ref = current_process.get_files().lock().unwrap().get(fd)?;
+                // The rings of an io_uring are already in the user space
+                if let Some(io_uring) = file_ref.as_any().downcast_ref::<IoUring>() {
+                    return io_uring.get_mmap_addr(offset, size);
+                }
                 VMInitializer::LoadFromFile {
                     file: file_ref,
                     offset: offset,
@@ -316,16 +375,297 @@ impl ProcessVM {
             .initializer(initializer)
             .build()?;
         let mmap_addr = self.mmap_manager.mmap(&mmap_options)?;
+        // The pages replaced by a fixed mmap are no longer locked
+        self.unlock_pages(mmap_addr, mmap_addr + *mmap_options.size());
+        let mmap_range =
+            unsafe { VMRange::from_unchecked(mmap_addr, mmap_addr + *mmap_options.size()) };
+        self.mmap_perms.set(&mmap_range, perms);
         Ok(mmap_addr)
     }
 
     pub fn munmap(&mut self, addr: usize, size: usize) -> Result<()> {
-        self.mmap_manager.munmap(addr, size)
+        self.mmap_manager.munmap(addr, size)?;
+        let end = addr.saturating_add(align_up(size, PAGE_SIZE));
+        let range = unsafe { VMRange::from_unchecked(addr, end) };
+        for part in self.mmap_manager.unpinned_parts(&range) {
+            self.unlock_pages(part.start(), part.end());
+            self.mmap_perms.remove(&part);
+        }
+        Ok(())
+    }
+
+    /// Map the zeroed memory that is owned by the LibOS in the mmap area, so
+    /// that the user can access it, but cannot unmap or replace it
+    pub fn mmap_pinned(&mut self, size: usize, perms: VMPerms) -> Result<usize> {
+        let mmap_options = VMMapOptionsBuilder::default()
+            .size(size)
+            .initializer(VMInitializer::FillZeros())
+            .build()?;
+        let mmap_addr = self.mmap_manager.mmap_pinned(&mmap_options)?;
+        let mmap_range =
+            unsafe { VMRange::from_unchecked(mmap_addr, mmap_addr + *mmap_options.size()) };
+        self.mmap_perms.set(&mmap_range, perms);
+        Ok(mmap_addr)
+    }
+
+    /// Unmap the memory mapped by mmap_pinned
+    pub fn munmap_pinned(&mut self, addr: usize, size: usize) -> Result<()> {
+        self.mmap_manager.munmap_pinned(addr)?;
+        let range = unsafe { VMRange::from_unchecked(addr, addr + align_up(size, PAGE_SIZE)) };
+        self.unlock_pages(range.start(), range.end());
+        self.mmap_perms.remove(&range);
+        Ok(())
+    }
+
+    /// Change the protection of the pages in the range.
+    ///
+    /// The permissions of the enclave pages cannot be changed in SGX 1, so
+    /// the protections are only tracked. Even the PROT_NONE pages are not made
+    /// inaccessible by the host page tables, as an access to them, e.g., by
+    /// the LibOS copying a user buffer, would crash the whole enclave. Instead,
+    /// the LibOS checks the user buffers by `check_user_access`. If W^X is
+    /// enforced by the config, a page cannot be both writable and executable,
+    /// and a page that is writable cannot be made executable directly.
+    pub fn mprotect(&mut self, addr: usize, size: usize, perms: VMPerms) -> Result<()> {
+        let range = {
+            let end = addr
+                .checked_add(align_up(size, PAGE_SIZE))
+                .ok_or_else(|| errno!(ENOMEM, "the range is too large"))?;
+            VMRange::new(addr, end)?
+        };
+        if range.empty() {
+            return Ok(());
+        }
+        if !self.is_mapped(&range) {
+            return_errno!(ENOMEM, "the range is not fully mapped");
+        }
+
+        let enforce_wx = config::LIBOS_CONFIG.vm.enforce_wx;
+        if enforce_wx && perms.can_write() && perms.can_execute() {
+            return_errno!(EACCES, "writable and executable pages are not allowed");
+        }
+        if !self.mmap_manager.range().is_superset_of(&range) {
+            // The protections of the other regions are not tracked
+            return Ok(());
+        }
+        if enforce_wx
+            && perms.can_execute()
+            && self
+                .mmap_perms
+                .iter_in(&range)
+                .any(|(_, old_perms)| old_perms.can_write())
+        {
+            return_errno!(EACCES, "writable pages cannot be made executable");
+        }
+        self.mmap_perms.set(&range, perms);
+        Ok(())
+    }
+
+    /// Check whether the LibOS may access the user memory of `len` bytes at
+    /// `addr` for the user, as the tracked protections allow, or fail with
+    /// EFAULT. As on x86, the pages of any protection but PROT_NONE are
+    /// readable. The vDSO time page is never writable.
+    pub fn check_user_access(&self, addr: usize, len: usize, write: bool) -> Result<()> {
+        if len == 0 {
+            return Ok(());
+        }
+        let range = {
+            let end = addr
+                .checked_add(len)
+                .ok_or_else(|| errno!(EFAULT, "the user buffer is out of range"))?;
+            unsafe {
+                VMRange::from_unchecked(align_down(addr, PAGE_SIZE), align_up(end, PAGE_SIZE))
+            }
+        };
+        let is_accessible = |perms: VMPerms| {
+            if write {
+                perms.can_write()
+            } else {
+                !perms.is_empty()
+            }
+        };
+        if self
+            .mmap_perms
+            .iter_in(&range)
+            .any(|(_, perms)| !is_accessible(perms))
+        {
+            return_errno!(EFAULT, "the user buffer is not accessible");
+        }
+        let in_vdso = self
+            .vdso_range
+            .intersect(&range)
+            .map_or(false, |part| !part.empty());
+        if write && in_vdso {
+            return_errno!(EFAULT, "the vDSO time page is read-only");
+        }
+        Ok(())
+    }
+
+    pub fn madvise_dontneed(&mut self, addr: usize, size: usize) -> Result<()> {
+        let range = VMRange::new(addr, addr + align_up(size, PAGE_SIZE))?;
+        if self
+            .locked_pages
+            .range(range.start()..range.end())
+            .next()
+            .is_some()
+        {
+            return_errno!(EINVAL, "locked pages cannot be dropped");
+        }
+        if self.mmap_manager.range().is_superset_of(&range) {
+            return self.mmap_manager.madvise_dontneed(&range);
+        }
+        // The pages of the other regions are kept, which is allowed as
+        // MADV_DONTNEED is a hint
+        if !self.is_in_fixed_regions(&range) {
+            return_errno!(ENOMEM, "the range is not fully mapped");
+        }
+        Ok(())
+    }
+
+    pub fn mincore(&mut self, addr: usize, size: usize, vec: &mut [u8]) -> Result<()> {
+        let range = VMRange::new(addr, addr + align_up(size, PAGE_SIZE))?;
+        if self.mmap_manager.range().is_superset_of(&range) {
+            return self.mmap_manager.mincore(&range, vec);
+        }
+        // The pages of the other regions are always resident
+        if !self.is_in_fixed_regions(&range) {
+            return_errno!(ENOMEM, "the range is not fully mapped");
+        }
+        for resident in vec.iter_mut() {
+            *resident = 1;
+        }
+        Ok(())
+    }
+
+    /// Lock the pages in the range, as long as the total size of the locked
+    /// pages does not exceed the limit
+    pub fn mlock(&mut self, addr: usize, size: usize, limit: usize) -> Result<()> {
+        let range = {
+            let start = align_down(addr, PAGE_SIZE);
+            let end = addr
+                .checked_add(size)
+                .ok_or_else(|| errno!(ENOMEM, "the range is too large"))?;
+            VMRange::new(start, align_up(end, PAGE_SIZE))?
+        };
+        if !self.is_mapped(&range) {
+            return_errno!(ENOMEM, "the range is not fully mapped");
+        }
+        self.lock_pages(range.start(), range.end(), limit)
+    }
+
+    pub fn munlock(&mut self, addr: usize, size: usize) -> Result<()> {
+        let range = {
+            let start = align_down(addr, PAGE_SIZE);
+            let end = addr
+                .checked_add(size)
+                .ok_or_else(|| errno!(ENOMEM, "the range is too large"))?;
+            VMRange::new(start, align_up(end, PAGE_SIZE))?
+        };
+        if !self.is_mapped(&range) {
+            return_errno!(ENOMEM, "the range is not fully mapped");
+        }
+        self.unlock_pages(range.start(), range.end());
+        Ok(())
+    }
+
+    /// Lock all the pages currently mapped if lock_current is true, and all
+    /// the pages to be mapped if lock_future is true
+    pub fn mlockall(&mut self, lock_current: bool, lock_future: bool, limit: usize) -> Result<()> {
+        if lock_current {
+            let mapped_ranges: Vec<VMRange> = self
+                .fixed_regions()
+                .into_iter()
+                .chain(self.mmap_manager.mmap_regions().cloned())
+                .collect();
+            let num_new_pages: usize = mapped_ranges
+                .iter()
+                .map(|range| self.num_unlocked_pages(range.start(), range.end()))
+                .sum();
+            if (self.locked_pages.len() + num_new_pages) * PAGE_SIZE > limit {
+                return_errno!(ENOMEM, "RLIMIT_MEMLOCK is exceeded");
+            }
+            for range in mapped_ranges {
+                self.lock_pages(range.start(), range.end(), limit)?;
+            }
+        }
+        if lock_future {
+            self.lock_future = true;
+        }
+        Ok(())
+    }
+
+    pub fn munlockall(&mut self) {
+        self.locked_pages.clear();
+        self.lock_future = false;
+    }
+
+    pub fn is_lock_future(&self) -> bool {
+        self.lock_future
+    }
+
+    fn lock_pages(&mut self, start: usize, end: usize, limit: usize) -> Result<()> {
+        let num_new_pages = self.num_unlocked_pages(start, end);
+        if (self.locked_pages.len() + num_new_pages) * PAGE_SIZE > limit {
+            return_errno!(ENOMEM, "RLIMIT_MEMLOCK is exceeded");
+        }
+        for page in (start..end).step_by(PAGE_SIZE) {
+            self.locked_pages.insert(page);
+        }
+        Ok(())
+    }
+
+    fn unlock_pages(&mut self, start: usize, end: usize) {
+        let pages: Vec<usize> = self.locked_pages.range(start..end).cloned().collect();
+        for page in pages {
+            self.locked_pages.remove(&page);
+        }
+    }
+
+    fn num_unlocked_pages(&self, start: usize, end: usize) -> usize {
+        let num_pages = (end - start) / PAGE_SIZE;
+        num_pages - self.locked_pages.range(start..end).count()
+    }
+
+    fn is_mapped(&self, range: &VMRange) -> bool {
+        if self.mmap_manager.range().is_superset_of(range) {
+            self.mmap_manager.is_mapped(range)
+        } else {
+            self.is_in_fixed_regions(range)
+        }
+    }
+
+    // The ELF, heap, stack and vDSO regions, which are mapped as a whole when
+    // the process is created
+    fn fixed_regions(&self) -> Vec<VMRange> {
+        let used_heap_range = unsafe {
+            VMRange::from_unchecked(self.heap_range.start(), align_up(self.brk, PAGE_SIZE))
+        };
+        self.elf_ranges
+            .iter()
+            .cloned()
+            .chain(vec![used_heap_range, self.stack_range, self.vdso_range])
+            .collect()
+    }
+
+    // Check whether the range is in one of the ELF, heap, stack and vDSO
+    // regions, which are mapped as a whole when the process is created
+    fn is_in_fixed_regions(&self, range: &VMRange) -> bool {
+        self.fixed_regions()
+            .iter()
+            .any(|region| region.is_superset_of(range))
     }
 
     pub fn find_mmap_region(&self, addr: usize) -> Result<&VMRange> {
         self.mmap_manager.find_mmap_region(addr)
     }
+
+    pub fn is_membarrier_registered(&self) -> bool {
+        self.membarrier_registered
+    }
+
+    pub fn set_membarrier_registered(&mut self) {
+        self.membarrier_registered = true;
+    }
 }
 
 bitflags! {
@@ -384,6 +724,33 @@ impl VMPerms {
     }
 }
 
+/// Set the protection of the pages in the host page tables, which restrict
+/// the access to the enclave pages further than their permissions in the EPCM,
+/// i.e., readable, writable and executable
+pub fn set_host_page_protection(range: &VMRange, perms: VMPerms) -> Result<()> {
+    try_libc!({
+        let mut retval: c_int = 0;
+        let status = occlum_ocall_mprotect(
+            &mut retval,
+            range.start() as *const c_void,
+            range.size(),
+            perms.bits() as c_int,
+        );
+        assert!(status == sgx_status_t::SGX_SUCCESS);
+        retval
+    });
+    Ok(())
+}
+
+extern "C" {
+    fn occlum_ocall_mprotect(
+        ret: *mut c_int,
+        addr: *const c_void,
+        len: size_t,
+        prot: c_int,
+    ) -> sgx_status_t;
+}
+
 unsafe fn fill_zeros(addr: usize, size: usize) {
     let ptr = addr as *mut u8;
     let buf = std::slice::from_raw_parts_mut(ptr, size);
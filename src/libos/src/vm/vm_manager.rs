@@ -43,6 +43,8 @@ pub enum VMMapAddr {
     Any,          // Free to choose any address
     Hint(usize),  // Prefer the given address
     Fixed(usize), // Must be the given address
+    // Must be the given address, and fail if the address is occupied
+    FixedNoReplace(usize),
 }
 
 impl Default for VMMapAddr {
@@ -94,6 +96,12 @@ impl VMMapOptionsBuilder {
                     }
                     VMMapAddr::Fixed(addr)
                 }
+                VMMapAddr::FixedNoReplace(addr) => {
+                    if addr % align != 0 {
+                        return_errno!(EINVAL, "unaligned addr for fixed mmap");
+                    }
+                    VMMapAddr::FixedNoReplace(addr)
+                }
             }
         };
         let initializer = match self.initializer.as_ref() {
@@ -127,6 +135,14 @@ impl VMMapOptions {
 pub struct VMManager {
     range: VMRange,
     sub_ranges: Vec<VMRange>,
+    // The zero-filled pages that are not written since mapped or dropped by
+    // MADV_DONTNEED, which are not faulted in as far as the user can tell
+    untouched_ranges: Vec<VMRange>,
+    // The subranges whose contents are loaded from files
+    file_backed_ranges: Vec<VMRange>,
+    // The subranges owned by the LibOS, e.g., the rings of io_uring, which
+    // are not unmapped or replaced by the mmaps and munmaps of the user
+    pinned_ranges: Vec<VMRange>,
 }
 
 impl VMManager {
@@ -139,7 +155,13 @@ impl VMManager {
             let end_sentry = VMRange::new(end, end)?;
             vec![start_sentry, end_sentry]
         };
-        Ok(VMManager { range, sub_ranges })
+        Ok(VMManager {
+            range,
+            sub_ranges,
+            untouched_ranges: Vec::new(),
+            file_backed_ranges: Vec::new(),
+            pinned_ranges: Vec::new(),
+        })
     }
 
     pub fn range(&self) -> &VMRange {
@@ -151,9 +173,25 @@ impl VMManager {
         let addr = *options.addr();
         let size = *options.size();
 
-        if let VMMapAddr::Fixed(addr) = addr {
-            self.munmap(addr, size)?;
-        }
+        let addr = match addr {
+            VMMapAddr::Fixed(addr) => {
+                self.munmap(addr, size)?;
+                VMMapAddr::Fixed(addr)
+            }
+            VMMapAddr::FixedNoReplace(addr) => {
+                let mmap_range = VMRange::new(addr, addr + size)?;
+                let is_occupied = self.sub_ranges.iter().any(|subrange| {
+                    subrange
+                        .intersect(&mmap_range)
+                        .map_or(false, |intersection| !intersection.empty())
+                });
+                if is_occupied {
+                    return_errno!(EEXIST, "the addr for fixed mmap is occupied");
+                }
+                VMMapAddr::Fixed(addr)
+            }
+            addr => addr,
+        };
 
         // Allocate a new subrange for this mmap request
         let (insert_idx, free_subrange) = self.find_free_subrange(size, addr)?;
@@ -168,10 +206,48 @@ impl VMManager {
 
         // After initializing, we can safely add the new subrange
         self.sub_ranges.insert(insert_idx, new_subrange);
+        match options.initializer {
+            VMInitializer::FillZeros() => self.untouched_ranges.push(new_subrange),
+            VMInitializer::LoadFromFile { .. } => self.file_backed_ranges.push(new_subrange),
+            VMInitializer::DoNothing() => {}
+        }
 
         Ok(new_subrange_addr)
     }
 
+    /// Map a subrange that is owned by the LibOS, which is only unmapped by
+    /// munmap_pinned
+    pub fn mmap_pinned(&mut self, options: &VMMapOptions) -> Result<usize> {
+        let addr = self.mmap(options)?;
+        let range = unsafe { VMRange::from_unchecked(addr, addr + *options.size()) };
+        self.pinned_ranges.push(range);
+        Ok(addr)
+    }
+
+    pub fn munmap_pinned(&mut self, addr: usize) -> Result<()> {
+        let idx = self
+            .pinned_ranges
+            .iter()
+            .position(|range| range.start() == addr)
+            .ok_or_else(|| errno!(EINVAL, "not a pinned range"))?;
+        let range = self.pinned_ranges.remove(idx);
+        self.unmap_range(&range);
+        Ok(())
+    }
+
+    /// Get the parts of the range that are not pinned
+    pub fn unpinned_parts(&self, range: &VMRange) -> Vec<VMRange> {
+        self.pinned_ranges
+            .iter()
+            .fold(vec![*range], |parts, pinned_range| {
+                parts
+                    .iter()
+                    .flat_map(|part| part.subtract(pinned_range))
+                    .collect()
+            })
+    }
+
+    /// Unmap the pages in the range, except the pinned ones
     pub fn munmap(&mut self, addr: usize, size: usize) -> Result<()> {
         let size = {
             if size == 0 {
@@ -194,12 +270,19 @@ impl VMManager {
             effective_munmap_range
         };
 
+        for part in self.unpinned_parts(&munmap_range) {
+            self.unmap_range(&part);
+        }
+        Ok(())
+    }
+
+    fn unmap_range(&mut self, munmap_range: &VMRange) {
         let new_sub_ranges = self
             .sub_ranges
             .iter()
             .flat_map(|subrange| {
                 if subrange.size() > 0 {
-                    subrange.subtract(&munmap_range)
+                    subrange.subtract(munmap_range)
                 } else {
                     // Keep the two sentry subranges intact
                     vec![*subrange]
@@ -207,9 +290,89 @@ impl VMManager {
             })
             .collect();
         self.sub_ranges = new_sub_ranges;
+        subtract_from_ranges(&mut self.untouched_ranges, munmap_range);
+        subtract_from_ranges(&mut self.file_backed_ranges, munmap_range);
+    }
+
+    /// Drop the anonymous pages in the range, which are zero-filled just like
+    /// newly faulted-in pages.
+    ///
+    /// As the memory of an enclave is committed when mapped, the pages are
+    /// not really freed. But they are reported as non-resident by mincore
+    /// until written again. The pages of file-backed mappings are kept, which
+    /// is allowed as MADV_DONTNEED is a hint.
+    pub fn madvise_dontneed(&mut self, range: &VMRange) -> Result<()> {
+        if !self.is_mapped(range) {
+            return_errno!(ENOMEM, "the range is not fully mapped");
+        }
+        let mut anonymous_parts = vec![*range];
+        for file_backed_range in self.file_backed_ranges.iter() {
+            subtract_from_ranges(&mut anonymous_parts, file_backed_range);
+        }
+        for part in anonymous_parts {
+            unsafe {
+                for b in part.as_slice_mut() {
+                    *b = 0;
+                }
+            }
+            subtract_from_ranges(&mut self.untouched_ranges, &part);
+            self.untouched_ranges.push(part);
+        }
+        Ok(())
+    }
+
+    /// Report whether each page in the range is resident, with one byte per
+    /// page in vec.
+    pub fn mincore(&mut self, range: &VMRange, vec: &mut [u8]) -> Result<()> {
+        if !self.is_mapped(range) {
+            return_errno!(ENOMEM, "the range is not fully mapped");
+        }
+        for (page, resident) in (range.start()..range.end())
+            .step_by(PAGE_SIZE)
+            .zip(vec.iter_mut())
+        {
+            let is_untouched = self
+                .untouched_ranges
+                .iter()
+                .any(|untouched_range| untouched_range.contains(page));
+            let is_resident = if is_untouched {
+                // An untouched page is faulted in once it is written
+                let page_buf = unsafe { std::slice::from_raw_parts(page as *const u8, PAGE_SIZE) };
+                let is_written = page_buf.iter().any(|b| *b != 0);
+                if is_written {
+                    let page_range = unsafe { VMRange::from_unchecked(page, page + PAGE_SIZE) };
+                    subtract_from_ranges(&mut self.untouched_ranges, &page_range);
+                }
+                is_written
+            } else {
+                true
+            };
+            *resident = is_resident as u8;
+        }
         Ok(())
     }
 
+    /// Check whether the range is fully covered by the mmap regions
+    pub fn is_mapped(&self, range: &VMRange) -> bool {
+        let mut addr = range.start();
+        for subrange in self.sub_ranges.iter() {
+            if addr >= range.end() {
+                break;
+            }
+            if subrange.contains(addr) {
+                addr = subrange.end();
+            }
+        }
+        addr >= range.end()
+    }
+
+    /// Get the mmap regions, excluding the two sentries
+    pub fn mmap_regions(&self) -> impl Iterator<Item = &VMRange> {
+        self.sub_ranges
+            .iter()
+            .filter(|subrange| subrange.size() > 0)
+    }
+
     pub fn find_mmap_region(&self, addr: usize) -> Result<&VMRange> {
         self.sub_ranges
             .iter()
@@ -308,3 +471,12 @@ impl VMManager {
         new_subrange
     }
 }
+
+// Remove the range from the ranges, which are split if needed
+fn subtract_from_ranges(ranges: &mut Vec<VMRange>, range: &VMRange) {
+    *ranges = ranges
+        .iter()
+        .flat_map(|subrange| subrange.subtract(range))
+        .filter(|subrange| !subrange.empty())
+        .collect();
+}
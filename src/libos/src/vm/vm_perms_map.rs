@@ -0,0 +1,80 @@
+use super::*;
+
+/// The memory protections of a set of ranges.
+///
+/// The ranges are sorted and disjoint. Setting the protection of a part of a
+/// range splits the range, and the adjacent ranges of the same protection are
+/// merged.
+#[derive(Debug, Default)]
+pub struct VMPermsMap {
+    ranges: Vec<(VMRange, VMPerms)>,
+}
+
+impl VMPermsMap {
+    pub fn new() -> VMPermsMap {
+        VMPermsMap { ranges: Vec::new() }
+    }
+
+    pub fn set(&mut self, range: &VMRange, perms: VMPerms) {
+        if range.empty() {
+            return;
+        }
+        self.remove(range);
+        let insert_idx = self
+            .ranges
+            .iter()
+            .position(|(existing_range, _)| existing_range.start() >= range.end())
+            .unwrap_or(self.ranges.len());
+        self.ranges.insert(insert_idx, (*range, perms));
+
+        // Merge with the next range, then the previous one
+        if insert_idx + 1 < self.ranges.len() {
+            self.try_merge(insert_idx);
+        }
+        if insert_idx > 0 {
+            self.try_merge(insert_idx - 1);
+        }
+    }
+
+    pub fn remove(&mut self, range: &VMRange) {
+        let ranges = std::mem::replace(&mut self.ranges, Vec::new());
+        self.ranges = ranges
+            .into_iter()
+            .flat_map(|(existing_range, perms)| {
+                existing_range
+                    .subtract(range)
+                    .into_iter()
+                    .filter(|remaining_range| !remaining_range.empty())
+                    .map(move |remaining_range| (remaining_range, perms))
+            })
+            .collect();
+    }
+
+    /// Iterate the protections of the parts of the ranges that are in the
+    /// given range
+    pub fn iter_in<'a>(
+        &'a self,
+        range: &'a VMRange,
+    ) -> impl Iterator<Item = (VMRange, VMPerms)> + 'a {
+        self.ranges
+            .iter()
+            .filter_map(move |(existing_range, perms)| {
+                existing_range
+                    .intersect(range)
+                    .filter(|intersection| !intersection.empty())
+                    .map(|intersection| (intersection, *perms))
+            })
+    }
+
+    // Merge the range at idx with the next one if they are adjacent and of the
+    // same protection
+    fn try_merge(&mut self, idx: usize) {
+        let (range, perms) = self.ranges[idx];
+        let (next_range, next_perms) = self.ranges[idx + 1];
+        if range.end() == next_range.start() && perms == next_perms {
+            self.ranges[idx].0 =
+                unsafe { VMRange::from_unchecked(range.start(), next_range.end()) };
+            self.ranges.remove(idx + 1);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <poll.h>
+#include <stdint.h>
+#include <unistd.h>
+#include <sys/eventfd.h>
+#include "ocalls.h"
+
+int occlum_ocall_eventfd(unsigned int initval, int flags) {
+    return eventfd(initval, flags);
+}
+
+/*
+ * Wait until the eventfd is readable or the timeout expires, and then reset
+ * the counter of the eventfd. The wait is indefinite if the timeout is NULL.
+ *
+ * Return 1 if the eventfd was readable or the wait was interrupted, or 0 if
+ * the wait timed out.
+ */
+int occlum_ocall_eventfd_poll(int eventfd, const struct timespec *timeout) {
+    struct pollfd pollfd = {
+        .fd = eventfd,
+        .events = POLLIN,
+        .revents = 0,
+    };
+    int ret = ppoll(&pollfd, 1, timeout, NULL);
+    if (ret < 0 && errno == EINTR) {
+        // A wake-up that is spurious, after which the waiter checks again
+        return 1;
+    }
+    if (ret <= 0) {
+        return ret;
+    }
+
+    uint64_t counter;
+    if (read(eventfd, &counter, sizeof(counter)) < 0 && errno != EAGAIN) {
+        return -1;
+    }
+    return 1;
+}
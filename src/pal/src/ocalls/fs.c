@@ -1,6 +1,59 @@
+#include <dirent.h>
+#include <fcntl.h>
+#include <string.h>
 #include <unistd.h>
+#include <sys/vfs.h>
 #include "ocalls.h"
 
 void occlum_ocall_sync(void) {
     sync();
 }
+
+int occlum_ocall_fsync_host_file(const char *path) {
+    int fd = open(path, O_RDONLY);
+    if (fd < 0) {
+        return -1;
+    }
+    int ret = fsync(fd);
+    int saved_errno = errno;
+    close(fd);
+    errno = saved_errno;
+    return ret;
+}
+
+/*
+ * Read the names of the entries in a directory (except "." and "..") into the
+ * buffer, each of which is terminated by NUL.
+ *
+ * Return the total length of the names. If it is greater than the length of
+ * the buffer, only the names that fit into the buffer are written.
+ */
+ssize_t occlum_ocall_read_dir(const char *path, char *buf, size_t buf_len) {
+    DIR *dir = opendir(path);
+    if (dir == NULL) {
+        return -1;
+    }
+
+    size_t total_len = 0;
+    struct dirent *entry;
+    while ((entry = readdir(dir)) != NULL) {
+        if (strcmp(entry->d_name, ".") == 0 || strcmp(entry->d_name, "..") == 0) {
+            continue;
+        }
+        size_t name_len = strlen(entry->d_name) + 1;
+        if (total_len + name_len <= buf_len) {
+            memcpy(buf + total_len, entry->d_name, name_len);
+        }
+        total_len += name_len;
+    }
+    closedir(dir);
+    return total_len;
+}
+
+int occlum_ocall_statfs(const char *path, void *buf, size_t buf_len) {
+    if (buf_len != sizeof(struct statfs)) {
+        errno = EINVAL;
+        return -1;
+    }
+    return statfs(path, (struct statfs *)buf);
+}
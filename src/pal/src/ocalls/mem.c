@@ -1,4 +1,5 @@
 #include <stdlib.h>
+#include <sys/mman.h>
 #include "ocalls.h"
 
 void* occlum_ocall_posix_memalign(size_t alignment, size_t size) {
@@ -25,3 +26,11 @@ void* occlum_ocall_posix_memalign(size_t alignment, size_t size) {
 void occlum_ocall_free(void* ptr) {
     free(ptr);
 }
+
+/*
+ * Change the protection of the enclave pages in the page tables, which can
+ * only restrict the permissions of the pages in the EPCM further.
+ */
+int occlum_ocall_mprotect(void* addr, size_t len, int prot) {
+    return mprotect(addr, len, prot);
+}
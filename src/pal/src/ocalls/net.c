@@ -1,8 +1,17 @@
 #include <sys/types.h>
 #include <sys/socket.h>
+#include <sys/epoll.h>
+#include <net/if.h>
+#include <errno.h>
+#include <limits.h>
 #include <stddef.h>
+#include <time.h>
 #include "ocalls.h"
 
+#ifndef __NR_epoll_pwait2
+#define __NR_epoll_pwait2 441
+#endif
+
 ssize_t occlum_ocall_sendmsg(int sockfd,
                              const void *msg_name,
                              socklen_t msg_namelen,
@@ -53,3 +62,33 @@ ssize_t occlum_ocall_recvmsg(int sockfd,
     *msg_flags_recv = msg.msg_flags;
     return ret;
 }
+
+int occlum_ocall_epoll_pwait2(int epfd,
+                              void *events,
+                              size_t events_len,
+                              int maxevents,
+                              const struct timespec *timeout)
+{
+    int ret = syscall(__NR_epoll_pwait2, epfd, events, maxevents, timeout, NULL, 0);
+    if (ret >= 0 || errno != ENOSYS) {
+        return ret;
+    }
+
+    // Fall back to epoll_wait on older kernels, rounding up the timeout
+    int timeout_ms = -1;
+    if (timeout != NULL) {
+        long long ms = (long long)timeout->tv_sec * 1000 +
+                       (timeout->tv_nsec + 999999) / 1000000;
+        timeout_ms = ms > INT_MAX ? INT_MAX : (int)ms;
+    }
+    return epoll_wait(epfd, (struct epoll_event *)events, maxevents, timeout_ms);
+}
+
+int occlum_ocall_if_nametoindex(const char *ifname)
+{
+    unsigned int index = if_nametoindex(ifname);
+    if (index == 0) {
+        return -1;
+    }
+    return (int)index;
+}
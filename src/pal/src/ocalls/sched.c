@@ -10,6 +10,14 @@ int occlum_ocall_sched_setaffinity(int host_tid, size_t cpusize, const unsigned
     return syscall(__NR_sched_setaffinity, host_tid, cpusize, buf);
 }
 
+int occlum_ocall_getcpu(unsigned int* cpu, unsigned int* node) {
+    return syscall(__NR_getcpu, cpu, node, NULL);
+}
+
+int occlum_ocall_membarrier(int cmd, int flags) {
+    return syscall(__NR_membarrier, cmd, flags);
+}
+
 /* In the Linux implementation, sched_yield() always succeeds */
 void occlum_ocall_sched_yield(void) {
     sched_yield();
@@ -1,5 +1,6 @@
 #include <stdlib.h>
 #include <pthread.h>
+#include <unistd.h>
 #include "ocalls.h"
 
 typedef struct {
@@ -13,7 +14,12 @@ void* exec_libos_thread(void* _thread_data) {
     int host_tid = gettid();
     int libos_tid = thread_data->libos_tid;
     int libos_exit_status = -1;
-    sgx_status_t status = occlum_ecall_exec_thread(eid, &libos_exit_status, libos_tid, host_tid);
+    sgx_status_t status;
+    // The TCS of a LibOS thread that has just exited may not be released yet
+    while ((status = occlum_ecall_exec_thread(eid, &libos_exit_status, libos_tid, host_tid))
+            == SGX_ERROR_OUT_OF_TCS) {
+        usleep(1000);
+    }
     if (status != SGX_SUCCESS) {
         const char* sgx_err = pal_get_sgx_error_msg(status);
         PAL_ERROR("Failed to enter the enclave to execute a LibOS thread: %s", sgx_err);
@@ -33,7 +39,7 @@ int occlum_ocall_exec_thread_async(int libos_tid) {
     thread_data->enclave_id = pal_get_enclave_id();
     thread_data->libos_tid = libos_tid;
 
-    if ((ret = pthread_create(&thread, NULL, exec_libos_thread, thread_data)) < 0) {
+    if ((ret = pthread_create(&thread, NULL, exec_libos_thread, thread_data)) != 0) {
         free(thread_data);
         return -1;
     }
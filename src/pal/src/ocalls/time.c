@@ -10,6 +10,10 @@ void occlum_ocall_clock_gettime(int clockid, struct timespec *tp) {
     clock_gettime(clockid, tp);
 }
 
+int occlum_ocall_clock_getres(int clockid, struct timespec *res) {
+    return clock_getres(clockid, res);
+}
+
 void occlum_ocall_nanosleep(const struct timespec* req) {
     nanosleep(req, NULL);
 }
@@ -24,3 +28,15 @@ int occlum_ocall_thread_getcpuclock(struct timespec *tp) {
 
     return clock_gettime(thread_clock_id, tp);
 }
+
+/* See MAKE_THREAD_CPUCLOCK in Linux's include/linux/posix-timers.h */
+#define CPUCLOCK_PERTHREAD_MASK     4
+#define CPUCLOCK_SCHED              2
+#define MAKE_THREAD_CPUCLOCK(tid, clock) \
+    ((~(clockid_t)(tid) << 3) | (clockid_t)(clock))
+
+int occlum_ocall_host_thread_getcpuclock(int host_tid, struct timespec *tp) {
+    clockid_t clock_id = MAKE_THREAD_CPUCLOCK(host_tid,
+                         CPUCLOCK_PERTHREAD_MASK | CPUCLOCK_SCHED);
+    return clock_gettime(clock_id, tp);
+}
@@ -0,0 +1,112 @@
+#include <sys/stat.h>
+#include <sys/types.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <unistd.h>
+#include "test.h"
+
+#define TEST_DIR            "/root"
+#define FILE_PATH           TEST_DIR "/test_access_file.txt"
+#define READONLY_FILE_PATH  TEST_DIR "/test_access_readonly_file.txt"
+#define DIR_PATH            TEST_DIR "/test_access_dir"
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int create_file(const char *path, mode_t mode) {
+    int fd = open(path, O_WRONLY | O_CREAT | O_TRUNC, mode);
+    if (fd < 0) {
+        THROW_ERROR("failed to create the file %s", path);
+    }
+    close(fd);
+    return 0;
+}
+
+// ============================================================================
+// Test cases for access and faccessat
+// ============================================================================
+
+static int test_access_file() {
+    if (create_file(FILE_PATH, 0644) < 0) {
+        return -1;
+    }
+    if (access(FILE_PATH, F_OK) < 0 || access(FILE_PATH, R_OK | W_OK) < 0) {
+        THROW_ERROR("failed to access the file");
+    }
+    if (access(FILE_PATH, X_OK) == 0 || errno != EACCES) {
+        THROW_ERROR("the file should not be executable");
+    }
+    unlink(FILE_PATH);
+    return 0;
+}
+
+static int test_access_readonly_file() {
+    // The file cannot be created by open, which opens it for writing
+    if (mknod(READONLY_FILE_PATH, S_IFREG | 0444, 0) < 0) {
+        THROW_ERROR("failed to create the readonly file");
+    }
+    if (access(READONLY_FILE_PATH, R_OK) < 0) {
+        THROW_ERROR("the readonly file should be readable");
+    }
+    if (access(READONLY_FILE_PATH, W_OK) == 0 || errno != EACCES) {
+        THROW_ERROR("the readonly file should not be writable");
+    }
+    if (faccessat(AT_FDCWD, READONLY_FILE_PATH, W_OK, AT_EACCESS) == 0 || errno != EACCES) {
+        THROW_ERROR("the readonly file should not be writable with AT_EACCESS");
+    }
+    unlink(READONLY_FILE_PATH);
+    return 0;
+}
+
+static int test_access_file_with_trailing_slash() {
+    if (create_file(FILE_PATH, 0644) < 0) {
+        return -1;
+    }
+    if (access(FILE_PATH "/", F_OK) == 0 || errno != ENOTDIR) {
+        THROW_ERROR("access to a file with a trailing slash should fail with ENOTDIR");
+    }
+    if (faccessat(AT_FDCWD, FILE_PATH "/", R_OK, AT_SYMLINK_NOFOLLOW) == 0 || errno != ENOTDIR) {
+        THROW_ERROR("faccessat to a file with a trailing slash should fail with ENOTDIR");
+    }
+    unlink(FILE_PATH);
+    return 0;
+}
+
+static int test_access_dir_with_trailing_slash() {
+    if (mkdir(DIR_PATH, 0755) < 0) {
+        THROW_ERROR("failed to create the dir");
+    }
+    if (access(DIR_PATH "/", F_OK) < 0) {
+        THROW_ERROR("failed to access the dir with a trailing slash");
+    }
+    // X_OK means the permission to search the dir
+    if (faccessat(AT_FDCWD, DIR_PATH "/", R_OK | X_OK, 0) < 0) {
+        THROW_ERROR("failed to search the dir with a trailing slash");
+    }
+    rmdir(DIR_PATH);
+    return 0;
+}
+
+static int test_access_nonexistent_file() {
+    if (access(TEST_DIR "/nonexistent_file", F_OK) == 0 || errno != ENOENT) {
+        THROW_ERROR("access to a nonexistent file should fail with ENOENT");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_access_file),
+    TEST_CASE(test_access_readonly_file),
+    TEST_CASE(test_access_file_with_trailing_slash),
+    TEST_CASE(test_access_dir_with_trailing_slash),
+    TEST_CASE(test_access_nonexistent_file),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
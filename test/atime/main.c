@@ -0,0 +1,199 @@
+#define _GNU_SOURCE
+#include <sys/mman.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <sys/types.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define MNT_DIR             "/root"
+#define FILE_PATH           MNT_DIR "/test_atime_file.txt"
+#define FILE_CONTENT        "Hello atime"
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int set_atime_policy(unsigned long atime_flag) {
+    if (mount(NULL, MNT_DIR, NULL, MS_REMOUNT | atime_flag, NULL) < 0) {
+        THROW_ERROR("failed to remount with the atime policy");
+    }
+    return 0;
+}
+
+// Create the file, and wait for a while so that a later access time differs
+static int create_file() {
+    int fd = open(FILE_PATH, O_WRONLY | O_CREAT | O_TRUNC, 0644);
+    if (fd < 0) {
+        THROW_ERROR("failed to create the file");
+    }
+    if (write(fd, FILE_CONTENT, sizeof(FILE_CONTENT)) != sizeof(FILE_CONTENT)) {
+        close(fd);
+        THROW_ERROR("failed to write the file");
+    }
+    close(fd);
+    usleep(1100 * 1000);
+    return 0;
+}
+
+static int get_atime(struct timespec *atime) {
+    struct stat stat_buf;
+    if (stat(FILE_PATH, &stat_buf) < 0) {
+        THROW_ERROR("failed to stat the file");
+    }
+    *atime = stat_buf.st_atim;
+    return 0;
+}
+
+static int timespec_equals(const struct timespec *a, const struct timespec *b) {
+    return a->tv_sec == b->tv_sec && a->tv_nsec == b->tv_nsec;
+}
+
+static int read_file(int flags) {
+    char buf[sizeof(FILE_CONTENT)];
+    int fd = open(FILE_PATH, O_RDONLY | flags);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the file");
+    }
+    if (read(fd, buf, sizeof(buf)) != sizeof(buf)) {
+        close(fd);
+        THROW_ERROR("failed to read the file");
+    }
+    close(fd);
+    return 0;
+}
+
+// Read the file, and tell whether the access time advances
+static int read_and_check_atime(int flags, int should_advance) {
+    struct timespec old_atime, new_atime;
+    if (get_atime(&old_atime) < 0 || read_file(flags) < 0 || get_atime(&new_atime) < 0) {
+        return -1;
+    }
+    if (should_advance && timespec_equals(&old_atime, &new_atime)) {
+        THROW_ERROR("the access time should advance");
+    }
+    if (!should_advance && !timespec_equals(&old_atime, &new_atime)) {
+        THROW_ERROR("the access time should not advance");
+    }
+    return 0;
+}
+
+static int cleanup() {
+    unlink(FILE_PATH);
+    return set_atime_policy(MS_NOATIME);
+}
+
+// ============================================================================
+// Test cases for the atime policies
+// ============================================================================
+
+static int test_noatime() {
+    if (set_atime_policy(MS_NOATIME) < 0 || create_file() < 0) {
+        return -1;
+    }
+    if (read_and_check_atime(0, 0) < 0) {
+        return -1;
+    }
+    return cleanup();
+}
+
+static int test_strictatime() {
+    if (set_atime_policy(MS_STRICTATIME) < 0 || create_file() < 0) {
+        return -1;
+    }
+    if (read_and_check_atime(0, 1) < 0) {
+        return -1;
+    }
+    usleep(1100 * 1000);
+    if (read_and_check_atime(0, 1) < 0) {
+        return -1;
+    }
+    return cleanup();
+}
+
+static int test_relatime() {
+    if (set_atime_policy(MS_RELATIME) < 0 || create_file() < 0) {
+        return -1;
+    }
+    // The first read since the modification updates the access time
+    if (read_and_check_atime(0, 1) < 0) {
+        return -1;
+    }
+    // But the reads after it do not
+    usleep(1100 * 1000);
+    if (read_and_check_atime(0, 0) < 0) {
+        return -1;
+    }
+    return cleanup();
+}
+
+static int test_open_with_noatime() {
+    if (set_atime_policy(MS_STRICTATIME) < 0 || create_file() < 0) {
+        return -1;
+    }
+    if (read_and_check_atime(O_NOATIME, 0) < 0) {
+        return -1;
+    }
+    return cleanup();
+}
+
+static int test_mmap_with_relatime() {
+    if (set_atime_policy(MS_RELATIME) < 0 || create_file() < 0) {
+        return -1;
+    }
+    struct timespec old_atime, new_atime;
+    if (get_atime(&old_atime) < 0) {
+        return -1;
+    }
+
+    int fd = open(FILE_PATH, O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the file");
+    }
+    char *buf = mmap(NULL, sizeof(FILE_CONTENT), PROT_READ, MAP_PRIVATE, fd, 0);
+    close(fd);
+    if (buf == MAP_FAILED) {
+        THROW_ERROR("failed to mmap the file");
+    }
+    int content_matches = memcmp(buf, FILE_CONTENT, sizeof(FILE_CONTENT)) == 0;
+    munmap(buf, sizeof(FILE_CONTENT));
+    if (!content_matches) {
+        THROW_ERROR("the content of the mmapped file is wrong");
+    }
+
+    if (get_atime(&new_atime) < 0) {
+        return -1;
+    }
+    if (timespec_equals(&old_atime, &new_atime)) {
+        THROW_ERROR("the access time should advance after mmap");
+    }
+    return cleanup();
+}
+
+static int test_remount_with_invalid_flags() {
+    if (mount(NULL, MNT_DIR, NULL, MS_REMOUNT | MS_NOEXEC, NULL) == 0 ||
+            errno != EINVAL) {
+        THROW_ERROR("remount with unsupported flags should fail with EINVAL");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_noatime),
+    TEST_CASE(test_strictatime),
+    TEST_CASE(test_relatime),
+    TEST_CASE(test_open_with_noatime),
+    TEST_CASE(test_mmap_with_relatime),
+    TEST_CASE(test_remount_with_invalid_flags),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,132 @@
+#define _GNU_SOURCE
+#include <sys/socket.h>
+#include <sys/uio.h>
+#include <netinet/in.h>
+#include <netinet/tcp.h>
+#include <arpa/inet.h>
+#include <pthread.h>
+#include <time.h>
+#include <unistd.h>
+#include <stdio.h>
+#include <string.h>
+
+#ifndef SO_BUSY_POLL
+#define SO_BUSY_POLL        46
+#endif
+#ifndef RWF_HIPRI
+#define RWF_HIPRI           0x00000001
+#endif
+
+#define NREPEATS            10000
+#define BUSY_POLL_USECS     50
+#define SERVER_PORT         8809
+
+static unsigned long elapsed_ns(const struct timespec *start, const struct timespec *end) {
+    return (end->tv_sec - start->tv_sec) * 1000000000UL + (end->tv_nsec - start->tv_nsec);
+}
+
+static int set_nodelay_and_busy_poll(int fd, int busy_poll_usecs) {
+    int one = 1;
+    if (setsockopt(fd, IPPROTO_TCP, TCP_NODELAY, &one, sizeof(one)) < 0) {
+        printf("ERROR: failed to set TCP_NODELAY\n");
+        return -1;
+    }
+    if (setsockopt(fd, SOL_SOCKET, SO_BUSY_POLL, &busy_poll_usecs,
+                   sizeof(busy_poll_usecs)) < 0) {
+        printf("ERROR: failed to set SO_BUSY_POLL\n");
+        return -1;
+    }
+    return 0;
+}
+
+// Echo every byte back, busy polling if SO_BUSY_POLL is set
+static void *echo_thread_func(void *arg) {
+    int fd = *(int *)arg;
+    char c;
+    while (recv(fd, &c, 1, 0) == 1) {
+        if (send(fd, &c, 1, 0) != 1) {
+            break;
+        }
+    }
+    return NULL;
+}
+
+static int connect_pair(int *client_fd, int *server_fd) {
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_port = htons(SERVER_PORT);
+    addr.sin_addr.s_addr = inet_addr("127.0.0.1");
+
+    int listen_fd = socket(AF_INET, SOCK_STREAM, 0);
+    int reuse = 1;
+    setsockopt(listen_fd, SOL_SOCKET, SO_REUSEADDR, &reuse, sizeof(reuse));
+    if (listen_fd < 0 || bind(listen_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0 ||
+            listen(listen_fd, 1) < 0) {
+        printf("ERROR: failed to listen\n");
+        return -1;
+    }
+    *client_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (*client_fd < 0 || connect(*client_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        printf("ERROR: failed to connect\n");
+        return -1;
+    }
+    *server_fd = accept(listen_fd, NULL, NULL);
+    if (*server_fd < 0) {
+        printf("ERROR: failed to accept\n");
+        return -1;
+    }
+    close(listen_fd);
+    return 0;
+}
+
+// Measure the round-trip latency. The client receives the echo by recv, or by
+// preadv2 with RWF_HIPRI.
+static int bench_round_trip(const char *name, int busy_poll_usecs, int use_preadv2) {
+    int client_fd, server_fd;
+    if (connect_pair(&client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    if (set_nodelay_and_busy_poll(client_fd, busy_poll_usecs) < 0 ||
+            set_nodelay_and_busy_poll(server_fd, busy_poll_usecs) < 0) {
+        return -1;
+    }
+    pthread_t echo_thread;
+    if (pthread_create(&echo_thread, NULL, echo_thread_func, &server_fd) != 0) {
+        printf("ERROR: failed to create the echo thread\n");
+        return -1;
+    }
+
+    struct timespec start, end;
+    char c = 'x';
+    struct iovec iov = { .iov_base = &c, .iov_len = 1 };
+    clock_gettime(CLOCK_MONOTONIC, &start);
+    for (unsigned long i = 0; i < NREPEATS; i++) {
+        if (send(client_fd, &c, 1, 0) != 1) {
+            printf("ERROR: failed to send\n");
+            return -1;
+        }
+        ssize_t len = use_preadv2 ? preadv2(client_fd, &iov, 1, -1, RWF_HIPRI)
+                                  : recv(client_fd, &c, 1, 0);
+        if (len != 1) {
+            printf("ERROR: failed to receive the echo\n");
+            return -1;
+        }
+    }
+    clock_gettime(CLOCK_MONOTONIC, &end);
+    printf("Round-trip latency (%s) = %lu ns\n", name, elapsed_ns(&start, &end) / NREPEATS);
+
+    close(client_fd);
+    pthread_join(echo_thread, NULL);
+    close(server_fd);
+    return 0;
+}
+
+int main(int argc, const char *argv[]) {
+    if (bench_round_trip("blocking", 0, 0) < 0 ||
+            bench_round_trip("busy poll", BUSY_POLL_USECS, 0) < 0 ||
+            bench_round_trip("busy poll, preadv2 with RWF_HIPRI", BUSY_POLL_USECS, 1) < 0) {
+        return -1;
+    }
+    return 0;
+}
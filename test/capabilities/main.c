@@ -0,0 +1,287 @@
+#define _GNU_SOURCE
+#include <sys/mount.h>
+#include <sys/socket.h>
+#include <sys/stat.h>
+#include <sys/syscall.h>
+#include <sys/types.h>
+#include <sys/wait.h>
+#include <linux/capability.h>
+#include <netinet/in.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <spawn.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define FILE_PATH           "/root/test_capabilities.txt"
+#define MNT_DIR             "/root/test_capabilities_dir"
+#define PRIVILEGED_PORT     80
+#define USER_ID             1000
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+typedef struct {
+    unsigned long long effective;
+    unsigned long long permitted;
+    unsigned long long inheritable;
+} caps_t;
+
+#define CAP(cap)            (1ULL << (cap))
+
+static int get_caps(caps_t *caps) {
+    struct __user_cap_header_struct header = { _LINUX_CAPABILITY_VERSION_3, 0 };
+    struct __user_cap_data_struct data[2];
+    if (syscall(SYS_capget, &header, data) < 0) {
+        THROW_ERROR("failed to get the capabilities");
+    }
+    caps->effective = data[0].effective | (unsigned long long)data[1].effective << 32;
+    caps->permitted = data[0].permitted | (unsigned long long)data[1].permitted << 32;
+    caps->inheritable = data[0].inheritable | (unsigned long long)data[1].inheritable << 32;
+    return 0;
+}
+
+static int set_caps(const caps_t *caps) {
+    struct __user_cap_header_struct header = { _LINUX_CAPABILITY_VERSION_3, 0 };
+    struct __user_cap_data_struct data[2];
+    for (int i = 0; i < 2; i++) {
+        data[i].effective = caps->effective >> (32 * i);
+        data[i].permitted = caps->permitted >> (32 * i);
+        data[i].inheritable = caps->inheritable >> (32 * i);
+    }
+    return syscall(SYS_capset, &header, data);
+}
+
+// Drop the capability from the effective set, which can be raised again
+static int drop_effective_cap(int cap, caps_t *old_caps) {
+    caps_t caps;
+    if (get_caps(old_caps) < 0) {
+        return -1;
+    }
+    caps = *old_caps;
+    caps.effective &= ~CAP(cap);
+    if (set_caps(&caps) < 0) {
+        THROW_ERROR("failed to drop the capability");
+    }
+    return 0;
+}
+
+static int bind_port(int port) {
+    int sock = socket(AF_INET, SOCK_STREAM, 0);
+    if (sock < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_port = htons(port);
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    int ret = bind(sock, (struct sockaddr *)&addr, sizeof(addr));
+    int saved_errno = errno;
+    close(sock);
+    errno = saved_errno;
+    return ret;
+}
+
+// ============================================================================
+// Test cases
+// ============================================================================
+
+static int test_capget() {
+    // The version is probed with a null data
+    struct __user_cap_header_struct header = { 0, 0 };
+    if (syscall(SYS_capget, &header, NULL) < 0 ||
+            header.version != _LINUX_CAPABILITY_VERSION_3) {
+        THROW_ERROR("failed to probe the version");
+    }
+
+    caps_t caps;
+    if (get_caps(&caps) < 0) {
+        return -1;
+    }
+    unsigned long long expected_caps = CAP(CAP_CHOWN) | CAP(CAP_DAC_OVERRIDE) |
+                                       CAP(CAP_NET_BIND_SERVICE) | CAP(CAP_SYS_ADMIN);
+    if ((caps.effective & expected_caps) != expected_caps ||
+            (caps.permitted & expected_caps) != expected_caps) {
+        THROW_ERROR("root should have the capabilities");
+    }
+    return 0;
+}
+
+static int test_drop_net_bind_service() {
+    caps_t old_caps;
+    if (drop_effective_cap(CAP_NET_BIND_SERVICE, &old_caps) < 0) {
+        return -1;
+    }
+    if (bind_port(PRIVILEGED_PORT) == 0 || errno != EACCES) {
+        THROW_ERROR("the privileged port should not be bound");
+    }
+    // The unprivileged ports can still be bound
+    if (bind_port(0) < 0) {
+        THROW_ERROR("failed to bind an unprivileged port");
+    }
+    if (set_caps(&old_caps) < 0) {
+        THROW_ERROR("failed to raise the capability again");
+    }
+    return 0;
+}
+
+static int test_drop_chown() {
+    int fd = open(FILE_PATH, O_WRONLY | O_CREAT | O_TRUNC, 0644);
+    if (fd < 0) {
+        THROW_ERROR("failed to create the file");
+    }
+    close(fd);
+
+    caps_t old_caps;
+    if (drop_effective_cap(CAP_CHOWN, &old_caps) < 0) {
+        return -1;
+    }
+    if (chown(FILE_PATH, USER_ID, USER_ID) == 0 || errno != EPERM) {
+        THROW_ERROR("chown should fail without CAP_CHOWN");
+    }
+    if (set_caps(&old_caps) < 0) {
+        THROW_ERROR("failed to raise the capability again");
+    }
+    if (chown(FILE_PATH, USER_ID, USER_ID) < 0) {
+        THROW_ERROR("failed to chown with CAP_CHOWN");
+    }
+    unlink(FILE_PATH);
+    return 0;
+}
+
+static int test_drop_dac_override() {
+    int fd = open(FILE_PATH, O_WRONLY | O_CREAT | O_TRUNC, 0600);
+    if (fd < 0) {
+        THROW_ERROR("failed to create the file");
+    }
+    close(fd);
+    if (chown(FILE_PATH, USER_ID, USER_ID) < 0) {
+        THROW_ERROR("failed to chown the file");
+    }
+
+    // The file of another user can be opened with CAP_DAC_OVERRIDE only
+    fd = open(FILE_PATH, O_RDWR);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the file with CAP_DAC_OVERRIDE");
+    }
+    close(fd);
+    caps_t old_caps;
+    if (drop_effective_cap(CAP_DAC_OVERRIDE, &old_caps) < 0) {
+        return -1;
+    }
+    if (open(FILE_PATH, O_RDWR) >= 0 || errno != EACCES) {
+        THROW_ERROR("the file should not be opened without CAP_DAC_OVERRIDE");
+    }
+    if (set_caps(&old_caps) < 0) {
+        THROW_ERROR("failed to raise the capability again");
+    }
+    unlink(FILE_PATH);
+    return 0;
+}
+
+static int test_drop_sys_admin() {
+    if (mkdir(MNT_DIR, 0755) < 0) {
+        THROW_ERROR("failed to create the mount point");
+    }
+    caps_t old_caps;
+    if (drop_effective_cap(CAP_SYS_ADMIN, &old_caps) < 0) {
+        return -1;
+    }
+    if (mount("none", MNT_DIR, "ramfs", 0, NULL) == 0 || errno != EPERM) {
+        THROW_ERROR("mount should fail without CAP_SYS_ADMIN");
+    }
+    if (set_caps(&old_caps) < 0) {
+        THROW_ERROR("failed to raise the capability again");
+    }
+    rmdir(MNT_DIR);
+    return 0;
+}
+
+static int test_capset_invalid() {
+    caps_t caps;
+    if (get_caps(&caps) < 0) {
+        return -1;
+    }
+    caps_t invalid_caps = caps;
+    invalid_caps.permitted &= ~CAP(CAP_CHOWN);
+    if (set_caps(&invalid_caps) == 0 || errno != EPERM) {
+        THROW_ERROR("the effective set should not exceed the permitted one");
+    }
+
+    struct __user_cap_header_struct header = { 0, 0 };
+    struct __user_cap_data_struct data[2];
+    memset(data, 0, sizeof(data));
+    if (syscall(SYS_capset, &header, data) == 0 || errno != EINVAL) {
+        THROW_ERROR("capset should fail with an invalid version");
+    }
+    return 0;
+}
+
+static int test_permitted_cap_dropped_for_good() {
+    caps_t caps;
+    if (get_caps(&caps) < 0) {
+        return -1;
+    }
+    caps_t dropped_caps = caps;
+    dropped_caps.effective &= ~CAP(CAP_CHOWN);
+    dropped_caps.permitted &= ~CAP(CAP_CHOWN);
+    if (set_caps(&dropped_caps) < 0) {
+        THROW_ERROR("failed to drop the capability");
+    }
+    if (set_caps(&caps) == 0 || errno != EPERM) {
+        THROW_ERROR("the permitted set should not be raised");
+    }
+    return 0;
+}
+
+// Run after the permitted CAP_CHOWN is dropped, which is regained by the new
+// process of root
+static int test_caps_of_new_process() {
+    int child_pid, status;
+    const char *child_argv[] = { "capabilities", "child", NULL };
+    if (posix_spawn(&child_pid, "/bin/capabilities", NULL, NULL, (char *const *)child_argv,
+                    NULL) != 0) {
+        THROW_ERROR("failed to spawn the child");
+    }
+    if (waitpid(child_pid, &status, 0) < 0 || !WIFEXITED(status) ||
+            WEXITSTATUS(status) != 0) {
+        THROW_ERROR("the child should have the full capabilities");
+    }
+    return 0;
+}
+
+static int child_main() {
+    caps_t caps;
+    if (get_caps(&caps) < 0) {
+        return -1;
+    }
+    if (!(caps.effective & CAP(CAP_CHOWN)) || !(caps.permitted & CAP(CAP_CHOWN))) {
+        return -1;
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_capget),
+    TEST_CASE(test_drop_net_bind_service),
+    TEST_CASE(test_drop_chown),
+    TEST_CASE(test_drop_dac_override),
+    TEST_CASE(test_drop_sys_admin),
+    TEST_CASE(test_capset_invalid),
+    TEST_CASE(test_permitted_cap_dropped_for_good),
+    TEST_CASE(test_caps_of_new_process),
+};
+
+int main(int argc, const char *argv[]) {
+    if (argc > 1 && strcmp(argv[1], "child") == 0) {
+        return child_main();
+    }
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
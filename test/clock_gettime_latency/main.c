@@ -0,0 +1,42 @@
+#include <sys/syscall.h>
+#include <sys/time.h>
+#include <time.h>
+#include <unistd.h>
+#include <stdio.h>
+#include "../../src/libos/include/occlum_vdso_time.h"
+
+#define NREPEATS 1000000
+
+static unsigned long elapsed_ns(const struct timespec *start, const struct timespec *end) {
+    return (end->tv_sec - start->tv_sec) * 1000000000UL + (end->tv_nsec - start->tv_nsec);
+}
+
+int main(int argc, const char* argv[]) {
+    struct timespec ts, start, end;
+
+    // Give the LibOS some time to calibrate the TSC frequency
+    syscall(__NR_clock_gettime, CLOCK_REALTIME, &ts);
+    syscall(__NR_clock_gettime, CLOCK_MONOTONIC, &ts);
+    usleep(20 * 1000);
+
+    syscall(__NR_clock_gettime, CLOCK_MONOTONIC, &start);
+    for (unsigned long i = 0; i < NREPEATS; i++) {
+        syscall(__NR_clock_gettime, CLOCK_MONOTONIC, &ts);
+    }
+    syscall(__NR_clock_gettime, CLOCK_MONOTONIC, &end);
+    printf("Latency of clock_gettime (syscall) = %lu ns\n",
+           elapsed_ns(&start, &end) / NREPEATS);
+
+    syscall(__NR_clock_gettime, CLOCK_MONOTONIC, &start);
+    for (unsigned long i = 0; i < NREPEATS; i++) {
+        occlum_vdso_clock_gettime(CLOCK_MONOTONIC, &ts);
+    }
+    syscall(__NR_clock_gettime, CLOCK_MONOTONIC, &end);
+    printf("Latency of clock_gettime (fast path) = %lu ns\n",
+           elapsed_ns(&start, &end) / NREPEATS);
+
+    if (!occlum_vdso_time_data()->valid) {
+        printf("WARNING: the fast path is not available on this platform\n");
+    }
+    return 0;
+}
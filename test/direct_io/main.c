@@ -0,0 +1,140 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define FILE_PATH       "/root/test_direct_io.txt"
+#define BLOCK_SIZE      512
+#define BUF_SIZE        (4 * BLOCK_SIZE)
+
+static char direct_buf[BUF_SIZE] __attribute__((aligned(BLOCK_SIZE)));
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int open_direct(int flags) {
+    int fd = open(FILE_PATH, flags | O_DIRECT | O_CREAT, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the file with O_DIRECT");
+    }
+    return fd;
+}
+
+// ============================================================================
+// Test cases for direct I/O
+// ============================================================================
+
+static int test_direct_write_and_buffered_read() {
+    int fd = open_direct(O_WRONLY | O_TRUNC);
+    if (fd < 0) {
+        return -1;
+    }
+    memset(direct_buf, 'a', BUF_SIZE);
+    if (write(fd, direct_buf, BUF_SIZE) != BUF_SIZE) {
+        THROW_ERROR("failed to write with O_DIRECT");
+    }
+    memset(direct_buf, 'b', BLOCK_SIZE);
+    if (pwrite(fd, direct_buf, BLOCK_SIZE, BLOCK_SIZE) != BLOCK_SIZE) {
+        THROW_ERROR("failed to pwrite with O_DIRECT");
+    }
+
+    // The data written by direct I/O is visible to buffered I/O
+    int buffered_fd = open(FILE_PATH, O_RDONLY);
+    if (buffered_fd < 0) {
+        THROW_ERROR("failed to open the file");
+    }
+    char buf[BUF_SIZE];
+    if (read(buffered_fd, buf, BUF_SIZE) != BUF_SIZE) {
+        THROW_ERROR("failed to read the file");
+    }
+    for (int i = 0; i < BUF_SIZE; i++) {
+        char expected = (i >= BLOCK_SIZE && i < 2 * BLOCK_SIZE) ? 'b' : 'a';
+        if (buf[i] != expected) {
+            THROW_ERROR("the data read is not the data written by direct I/O");
+        }
+    }
+    close(buffered_fd);
+    close(fd);
+    return 0;
+}
+
+static int test_buffered_write_and_direct_read() {
+    int buffered_fd = open(FILE_PATH, O_WRONLY);
+    if (buffered_fd < 0) {
+        THROW_ERROR("failed to open the file");
+    }
+    if (pwrite(buffered_fd, "hello", 5, 0) != 5) {
+        THROW_ERROR("failed to write the file");
+    }
+
+    int fd = open_direct(O_RDONLY);
+    if (fd < 0) {
+        return -1;
+    }
+    if (read(fd, direct_buf, BLOCK_SIZE) != BLOCK_SIZE || memcmp(direct_buf, "hello", 5) != 0) {
+        THROW_ERROR("the data read by direct I/O is not the data written");
+    }
+    close(fd);
+    close(buffered_fd);
+    return 0;
+}
+
+static int test_unaligned_direct_io() {
+    int fd = open_direct(O_RDWR);
+    if (fd < 0) {
+        return -1;
+    }
+    if (pread(fd, direct_buf + 1, BLOCK_SIZE, 0) >= 0 || errno != EINVAL) {
+        THROW_ERROR("direct I/O with an unaligned buffer should fail with EINVAL");
+    }
+    if (pread(fd, direct_buf, BLOCK_SIZE - 1, 0) >= 0 || errno != EINVAL) {
+        THROW_ERROR("direct I/O with an unaligned length should fail with EINVAL");
+    }
+    if (pwrite(fd, direct_buf, BLOCK_SIZE, 1) >= 0 || errno != EINVAL) {
+        THROW_ERROR("direct I/O with an unaligned offset should fail with EINVAL");
+    }
+    close(fd);
+    return 0;
+}
+
+static int test_set_direct_by_fcntl() {
+    int fd = open(FILE_PATH, O_RDWR);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the file");
+    }
+    // Buffered I/O needs no alignment
+    if (pread(fd, direct_buf + 1, 1, 1) != 1) {
+        THROW_ERROR("failed to read the file");
+    }
+    int flags = fcntl(fd, F_GETFL);
+    if (fcntl(fd, F_SETFL, flags | O_DIRECT) < 0) {
+        THROW_ERROR("failed to set O_DIRECT");
+    }
+    if ((fcntl(fd, F_GETFL) & O_DIRECT) == 0) {
+        THROW_ERROR("O_DIRECT should be set");
+    }
+    if (pread(fd, direct_buf + 1, 1, 1) >= 0 || errno != EINVAL) {
+        THROW_ERROR("unaligned I/O should fail after O_DIRECT is set");
+    }
+    close(fd);
+    unlink(FILE_PATH);
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_direct_write_and_buffered_read),
+    TEST_CASE(test_buffered_write_and_direct_read),
+    TEST_CASE(test_unaligned_direct_io),
+    TEST_CASE(test_set_direct_by_fcntl),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
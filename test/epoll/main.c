@@ -0,0 +1,405 @@
+#include <sys/epoll.h>
+#include <sys/socket.h>
+#include <arpa/inet.h>
+#include <netinet/in.h>
+#include <sys/syscall.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <pthread.h>
+#include <signal.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define WAIT_TIMEOUT_MS     100
+
+#ifndef SYS_epoll_pwait2
+#define SYS_epoll_pwait2    441
+#endif
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+// Create a pair of connected TCP sockets on the loopback
+static int create_tcp_pair(int *client_fd, int *server_fd) {
+    struct sockaddr_in addr;
+    socklen_t addr_len = sizeof(addr);
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    addr.sin_port = 0;
+
+    int listen_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (listen_fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    if (bind(listen_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0 ||
+            listen(listen_fd, 1) < 0 ||
+            getsockname(listen_fd, (struct sockaddr *)&addr, &addr_len) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to listen on the loopback");
+    }
+
+    *client_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (*client_fd < 0 || connect(*client_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to connect to the server");
+    }
+    *server_fd = accept(listen_fd, NULL, NULL);
+    close(listen_fd);
+    if (*server_fd < 0) {
+        close(*client_fd);
+        THROW_ERROR("failed to accept the connection");
+    }
+    return 0;
+}
+
+static int epoll_add(int ep_fd, int fd, uint32_t events) {
+    struct epoll_event event = { .events = events, .data.fd = fd };
+    if (epoll_ctl(ep_fd, EPOLL_CTL_ADD, fd, &event) < 0) {
+        THROW_ERROR("failed to add an fd to the epoll");
+    }
+    return 0;
+}
+
+// Wait for the events of the fd, returning the number of events
+static int wait_events(int ep_fd, int fd, uint32_t *events) {
+    struct epoll_event event;
+    memset(&event, 0, sizeof(event));
+    int ret = epoll_wait(ep_fd, &event, 1, WAIT_TIMEOUT_MS);
+    if (ret < 0) {
+        THROW_ERROR("failed to wait on the epoll");
+    }
+    if (ret > 0 && event.data.fd != fd) {
+        THROW_ERROR("incorrect data of the event");
+    }
+    *events = event.events;
+    return ret;
+}
+
+// ============================================================================
+// Test cases for epoll
+// ============================================================================
+
+static int __test_trigger_mode(int edge_triggered) {
+    int client_fd, server_fd, ret = -1;
+    if (create_tcp_pair(&client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    int ep_fd = epoll_create1(0);
+    if (ep_fd < 0) {
+        THROW_ERROR("failed to create an epoll");
+    }
+    uint32_t mode = edge_triggered ? EPOLLET : 0;
+    if (epoll_add(ep_fd, server_fd, EPOLLIN | mode) < 0) {
+        goto out;
+    }
+
+    uint32_t events;
+    char buf[16] = "0123456789abcdef";
+    if (write(client_fd, buf, sizeof(buf)) != sizeof(buf)) {
+        printf("\t\tERROR: failed to write the socket\n");
+        goto out;
+    }
+    if (wait_events(ep_fd, server_fd, &events) != 1 || !(events & EPOLLIN)) {
+        printf("\t\tERROR: the data should be reported\n");
+        goto out;
+    }
+    // Read only a part of the data
+    if (read(server_fd, buf, sizeof(buf) / 2) != sizeof(buf) / 2) {
+        printf("\t\tERROR: failed to read the socket\n");
+        goto out;
+    }
+    int expected = edge_triggered ? 0 : 1;
+    if (wait_events(ep_fd, server_fd, &events) != expected) {
+        printf("\t\tERROR: the remaining data should %sbe reported again\n",
+               edge_triggered ? "not " : "");
+        goto out;
+    }
+    // New data makes an edge
+    if (write(client_fd, buf, 1) != 1) {
+        printf("\t\tERROR: failed to write the socket\n");
+        goto out;
+    }
+    if (wait_events(ep_fd, server_fd, &events) != 1 || !(events & EPOLLIN)) {
+        printf("\t\tERROR: the new data should be reported\n");
+        goto out;
+    }
+    ret = 0;
+out:
+    close(ep_fd);
+    close(client_fd);
+    close(server_fd);
+    return ret;
+}
+
+static int test_edge_triggered() {
+    return __test_trigger_mode(1);
+}
+
+static int test_level_triggered() {
+    return __test_trigger_mode(0);
+}
+
+static int test_rdhup() {
+    int client_fd, server_fd, ret = -1;
+    if (create_tcp_pair(&client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    int ep_fd = epoll_create1(0);
+    if (ep_fd < 0) {
+        THROW_ERROR("failed to create an epoll");
+    }
+    if (epoll_add(ep_fd, server_fd, EPOLLIN | EPOLLRDHUP | EPOLLET) < 0) {
+        goto out;
+    }
+    uint32_t events;
+    if (wait_events(ep_fd, server_fd, &events) != 0) {
+        printf("\t\tERROR: no event should be reported before the half-close\n");
+        goto out;
+    }
+    if (shutdown(client_fd, SHUT_WR) < 0) {
+        printf("\t\tERROR: failed to shut down the socket\n");
+        goto out;
+    }
+    if (wait_events(ep_fd, server_fd, &events) != 1 || !(events & EPOLLRDHUP)) {
+        printf("\t\tERROR: EPOLLRDHUP should be reported on the half-close\n");
+        goto out;
+    }
+    ret = 0;
+out:
+    close(ep_fd);
+    close(client_fd);
+    close(server_fd);
+    return ret;
+}
+
+static int test_ctl_errors() {
+    int client_fd, server_fd, ret = -1;
+    if (create_tcp_pair(&client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    int ep_fd = epoll_create1(0);
+    if (ep_fd < 0) {
+        THROW_ERROR("failed to create an epoll");
+    }
+    struct epoll_event event = { .events = EPOLLIN, .data.fd = server_fd };
+    if (epoll_ctl(ep_fd, EPOLL_CTL_MOD, server_fd, &event) == 0 || errno != ENOENT) {
+        printf("\t\tERROR: modifying an fd not in the epoll should fail with ENOENT\n");
+        goto out;
+    }
+    if (epoll_add(ep_fd, server_fd, EPOLLIN) < 0) {
+        goto out;
+    }
+    if (epoll_ctl(ep_fd, EPOLL_CTL_ADD, server_fd, &event) == 0 || errno != EEXIST) {
+        printf("\t\tERROR: adding an fd twice should fail with EEXIST\n");
+        goto out;
+    }
+    ret = 0;
+out:
+    close(ep_fd);
+    close(client_fd);
+    close(server_fd);
+    return ret;
+}
+
+static int test_ctl_closed_fd() {
+    int client_fd, server_fd, ret = -1;
+    if (create_tcp_pair(&client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    int ep_fd = epoll_create1(0);
+    if (ep_fd < 0) {
+        THROW_ERROR("failed to create an epoll");
+    }
+    if (epoll_add(ep_fd, server_fd, EPOLLIN) < 0) {
+        goto out;
+    }
+    // The new socket reuses the fd of the closed one
+    int closed_fd = server_fd;
+    close(server_fd);
+    server_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (server_fd != closed_fd) {
+        printf("\t\tERROR: the fd of the closed socket should be reused\n");
+        goto out;
+    }
+    if (epoll_add(ep_fd, server_fd, EPOLLIN) < 0) {
+        printf("\t\tERROR: the closed fd should be removed from the epoll\n");
+        goto out;
+    }
+    ret = 0;
+out:
+    close(ep_fd);
+    close(client_fd);
+    close(server_fd);
+    return ret;
+}
+
+static int test_nested_epoll() {
+    int client_fd, server_fd, ret = -1;
+    if (create_tcp_pair(&client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    int inner_fd = epoll_create1(0);
+    int outer_fd = epoll_create1(0);
+    if (inner_fd < 0 || outer_fd < 0) {
+        THROW_ERROR("failed to create the epolls");
+    }
+    if (epoll_add(inner_fd, server_fd, EPOLLIN) < 0 ||
+            epoll_add(outer_fd, inner_fd, EPOLLIN) < 0) {
+        goto out;
+    }
+
+    uint32_t events;
+    if (wait_events(outer_fd, inner_fd, &events) != 0) {
+        printf("\t\tERROR: the inner epoll should not be ready without data\n");
+        goto out;
+    }
+    char buf[16] = "0123456789abcdef";
+    if (write(client_fd, buf, sizeof(buf)) != sizeof(buf)) {
+        printf("\t\tERROR: failed to write the socket\n");
+        goto out;
+    }
+    if (wait_events(outer_fd, inner_fd, &events) != 1 || !(events & EPOLLIN)) {
+        printf("\t\tERROR: the inner epoll should be reported by the outer one\n");
+        goto out;
+    }
+    if (wait_events(inner_fd, server_fd, &events) != 1 || !(events & EPOLLIN)) {
+        printf("\t\tERROR: the data should be reported by the inner epoll\n");
+        goto out;
+    }
+    if (read(server_fd, buf, sizeof(buf)) != sizeof(buf)) {
+        printf("\t\tERROR: failed to read the socket\n");
+        goto out;
+    }
+    if (wait_events(outer_fd, inner_fd, &events) != 0) {
+        printf("\t\tERROR: the inner epoll should not be ready after the read\n");
+        goto out;
+    }
+    ret = 0;
+out:
+    close(outer_fd);
+    close(inner_fd);
+    close(client_fd);
+    close(server_fd);
+    return ret;
+}
+
+static int test_nested_epoll_loop() {
+    int ret = -1;
+    int outer_fd = epoll_create1(0);
+    int inner_fd = epoll_create1(0);
+    if (inner_fd < 0 || outer_fd < 0) {
+        THROW_ERROR("failed to create the epolls");
+    }
+    struct epoll_event event = { .events = EPOLLIN, .data.fd = outer_fd };
+    if (epoll_ctl(outer_fd, EPOLL_CTL_ADD, outer_fd, &event) == 0 || errno != EINVAL) {
+        printf("\t\tERROR: adding an epoll to itself should fail with EINVAL\n");
+        goto out;
+    }
+    if (epoll_add(outer_fd, inner_fd, EPOLLIN) < 0) {
+        goto out;
+    }
+    if (epoll_ctl(inner_fd, EPOLL_CTL_ADD, outer_fd, &event) == 0 || errno != ELOOP) {
+        printf("\t\tERROR: adding an epoll to the one inside it should fail with ELOOP\n");
+        goto out;
+    }
+    ret = 0;
+out:
+    close(outer_fd);
+    close(inner_fd);
+    return ret;
+}
+
+static volatile int signal_count = 0;
+
+static void handle_signal(int signum) {
+    signal_count++;
+}
+
+static void *signal_after_sleep(void *arg) {
+    pid_t tid = *(pid_t *)arg;
+    usleep(100 * 1000);
+    syscall(SYS_tkill, tid, SIGUSR1);
+    return NULL;
+}
+
+static int test_pwait2_sigmask() {
+    int client_fd, server_fd, ret = -1;
+    if (create_tcp_pair(&client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    int ep_fd = epoll_create1(0);
+    if (ep_fd < 0) {
+        THROW_ERROR("failed to create an epoll");
+    }
+    if (epoll_add(ep_fd, server_fd, EPOLLIN) < 0) {
+        goto out;
+    }
+    struct sigaction sa = { 0 };
+    sa.sa_handler = handle_signal;
+    sigset_t blocked, wait_mask, mask;
+    sigemptyset(&blocked);
+    sigaddset(&blocked, SIGUSR1);
+    sigemptyset(&wait_mask);
+    signal_count = 0;
+    if (sigaction(SIGUSR1, &sa, NULL) < 0 ||
+            sigprocmask(SIG_BLOCK, &blocked, NULL) < 0) {
+        printf("\t\tERROR: failed to set up SIGUSR1\n");
+        goto out;
+    }
+
+    // The wait without a timeout is interrupted by the signal that only its
+    // mask unblocks
+    pid_t tid = syscall(SYS_gettid);
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, signal_after_sleep, &tid) != 0) {
+        printf("\t\tERROR: failed to create a thread\n");
+        goto out_mask;
+    }
+    struct epoll_event event;
+    long res = syscall(SYS_epoll_pwait2, ep_fd, &event, 1, NULL, &wait_mask, 8);
+    int err = errno;
+    pthread_join(thread, NULL);
+    if (res >= 0 || err != EINTR) {
+        printf("\t\tERROR: the wait should fail with EINTR by the signal\n");
+        goto out_mask;
+    }
+    if (sigprocmask(SIG_BLOCK, NULL, &mask) < 0 || !sigismember(&mask, SIGUSR1)) {
+        printf("\t\tERROR: the signal mask should be restored after the wait\n");
+        goto out_mask;
+    }
+    ret = 0;
+out_mask:
+    sigprocmask(SIG_UNBLOCK, &blocked, NULL);
+    signal(SIGUSR1, SIG_DFL);
+    if (ret == 0 && signal_count != 1) {
+        printf("\t\tERROR: the handler of the signal should be run once\n");
+        ret = -1;
+    }
+out:
+    close(ep_fd);
+    close(client_fd);
+    close(server_fd);
+    return ret;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_edge_triggered),
+    TEST_CASE(test_level_triggered),
+    TEST_CASE(test_rdhup),
+    TEST_CASE(test_ctl_errors),
+    TEST_CASE(test_ctl_closed_fd),
+    TEST_CASE(test_nested_epoll),
+    TEST_CASE(test_nested_epoll_loop),
+    TEST_CASE(test_pwait2_sigmask),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
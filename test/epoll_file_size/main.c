@@ -0,0 +1,268 @@
+#include <sys/epoll.h>
+#include <sys/stat.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <pthread.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+// Report EPOLLIN and EPOLLSIZE when the size of a regular file changes, which
+// is specific to Occlum
+#define EPOLLSIZE           (1u << 26)
+
+#define FILE_PATH           "/root/test_epoll_file_size.txt"
+#define WAIT_TIMEOUT_MS     100
+#define APPEND_DELAY_US     (50 * 1000)
+#define NAPPENDS            10
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int open_file_with_epoll(int *fd, int *ep_fd, uint32_t events) {
+    *fd = open(FILE_PATH, O_RDWR | O_CREAT | O_TRUNC, 00666);
+    if (*fd < 0) {
+        THROW_ERROR("failed to open the file");
+    }
+    *ep_fd = epoll_create1(0);
+    if (*ep_fd < 0) {
+        close(*fd);
+        THROW_ERROR("failed to create an epoll");
+    }
+    struct epoll_event event = { .events = events | EPOLLSIZE, .data.fd = *fd };
+    if (epoll_ctl(*ep_fd, EPOLL_CTL_ADD, *fd, &event) < 0) {
+        close(*ep_fd);
+        close(*fd);
+        THROW_ERROR("failed to add the file to the epoll");
+    }
+    return 0;
+}
+
+static void close_file_with_epoll(int fd, int ep_fd) {
+    close(ep_fd);
+    close(fd);
+    unlink(FILE_PATH);
+}
+
+// Wait for the events of the fd, returning the number of events
+static int wait_events(int ep_fd, int fd, int timeout) {
+    struct epoll_event event;
+    memset(&event, 0, sizeof(event));
+    int ret = epoll_wait(ep_fd, &event, 1, timeout);
+    if (ret < 0) {
+        THROW_ERROR("failed to wait on the epoll");
+    }
+    if (ret > 0 && (event.data.fd != fd || !(event.events & EPOLLIN) ||
+                    !(event.events & EPOLLSIZE))) {
+        THROW_ERROR("incorrect event");
+    }
+    return ret;
+}
+
+static off_t get_file_size(int fd) {
+    struct stat stat_buf;
+    if (fstat(fd, &stat_buf) < 0) {
+        return -1;
+    }
+    return stat_buf.st_size;
+}
+
+static void *appender(void *arg) {
+    int fd = open(FILE_PATH, O_WRONLY | O_APPEND);
+    if (fd < 0) {
+        return NULL;
+    }
+    for (int i = 0; i < NAPPENDS; i++) {
+        usleep(APPEND_DELAY_US);
+        if (write(fd, "0123456789", 10) != 10) {
+            break;
+        }
+    }
+    close(fd);
+    return NULL;
+}
+
+// ============================================================================
+// Test cases for the size changes of regular files in epoll
+// ============================================================================
+
+static int __test_wake_on_append(uint32_t mode) {
+    int fd, ep_fd, ret = -1;
+    pthread_t thread;
+    if (open_file_with_epoll(&fd, &ep_fd, EPOLLIN | mode) < 0) {
+        return -1;
+    }
+    if (wait_events(ep_fd, fd, 0) != 0) {
+        printf("\t\tERROR: no event should be reported before any change\n");
+        goto out;
+    }
+    if (pthread_create(&thread, NULL, appender, NULL) != 0) {
+        printf("\t\tERROR: failed to create the appender thread\n");
+        goto out;
+    }
+
+    // Tail the file like `tail -f`, with each wake up for the new data
+    off_t size = 0;
+    ret = 0;
+    while (size < NAPPENDS * 10) {
+        if (wait_events(ep_fd, fd, -1) != 1) {
+            ret = -1;
+            printf("\t\tERROR: the waiter should be woken up by the append\n");
+            break;
+        }
+        off_t new_size = get_file_size(fd);
+        if (new_size <= size) {
+            ret = -1;
+            printf("\t\tERROR: the file should have grown on the wake up\n");
+            break;
+        }
+        size = new_size;
+    }
+    pthread_join(thread, NULL);
+    // The changes are reported once, whether edge-triggered or not
+    if (ret == 0 && wait_events(ep_fd, fd, 0) != 0) {
+        ret = -1;
+        printf("\t\tERROR: no change should be reported again\n");
+    }
+out:
+    close_file_with_epoll(fd, ep_fd);
+    return ret;
+}
+
+static int test_wake_on_append() {
+    return __test_wake_on_append(0);
+}
+
+static int test_wake_on_append_edge_triggered() {
+    return __test_wake_on_append(EPOLLET);
+}
+
+static int test_wake_on_truncate() {
+    int fd, ep_fd, ret = -1;
+    if (open_file_with_epoll(&fd, &ep_fd, EPOLLIN | EPOLLET) < 0) {
+        return -1;
+    }
+    if (write(fd, "0123456789", 10) != 10) {
+        printf("\t\tERROR: failed to write the file\n");
+        goto out;
+    }
+    if (wait_events(ep_fd, fd, WAIT_TIMEOUT_MS) != 1) {
+        printf("\t\tERROR: the write should be reported\n");
+        goto out;
+    }
+    if (ftruncate(fd, 5) < 0) {
+        printf("\t\tERROR: failed to truncate the file\n");
+        goto out;
+    }
+    if (wait_events(ep_fd, fd, WAIT_TIMEOUT_MS) != 1) {
+        printf("\t\tERROR: the truncate should be reported\n");
+        goto out;
+    }
+    if (truncate(FILE_PATH, 100) < 0) {
+        printf("\t\tERROR: failed to extend the file\n");
+        goto out;
+    }
+    if (wait_events(ep_fd, fd, WAIT_TIMEOUT_MS) != 1) {
+        printf("\t\tERROR: the extension should be reported\n");
+        goto out;
+    }
+    ret = 0;
+out:
+    close_file_with_epoll(fd, ep_fd);
+    return ret;
+}
+
+static int test_no_wake_without_size_change() {
+    int fd, ep_fd, ret = -1;
+    if (open_file_with_epoll(&fd, &ep_fd, EPOLLIN) < 0) {
+        return -1;
+    }
+    if (write(fd, "0123456789", 10) != 10 || wait_events(ep_fd, fd, WAIT_TIMEOUT_MS) != 1) {
+        printf("\t\tERROR: the write should be reported\n");
+        goto out;
+    }
+    // Overwriting the data does not change the size
+    if (pwrite(fd, "abcde", 5, 0) != 5) {
+        printf("\t\tERROR: failed to overwrite the file\n");
+        goto out;
+    }
+    if (wait_events(ep_fd, fd, WAIT_TIMEOUT_MS) != 0) {
+        printf("\t\tERROR: the overwrite should not be reported\n");
+        goto out;
+    }
+    ret = 0;
+out:
+    close_file_with_epoll(fd, ep_fd);
+    return ret;
+}
+
+static int test_oneshot() {
+    int fd, ep_fd, ret = -1;
+    if (open_file_with_epoll(&fd, &ep_fd, EPOLLIN | EPOLLONESHOT) < 0) {
+        return -1;
+    }
+    if (write(fd, "0123456789", 10) != 10 || wait_events(ep_fd, fd, WAIT_TIMEOUT_MS) != 1) {
+        printf("\t\tERROR: the write should be reported\n");
+        goto out;
+    }
+    if (write(fd, "0123456789", 10) != 10 || wait_events(ep_fd, fd, WAIT_TIMEOUT_MS) != 0) {
+        printf("\t\tERROR: the disarmed file should not be reported\n");
+        goto out;
+    }
+    // The file is rearmed, with the changes since it is modified
+    struct epoll_event event = { .events = EPOLLIN | EPOLLSIZE | EPOLLONESHOT, .data.fd = fd };
+    if (epoll_ctl(ep_fd, EPOLL_CTL_MOD, fd, &event) < 0) {
+        printf("\t\tERROR: failed to modify the file in the epoll\n");
+        goto out;
+    }
+    if (wait_events(ep_fd, fd, WAIT_TIMEOUT_MS) != 1) {
+        printf("\t\tERROR: the write before the rearm should be reported\n");
+        goto out;
+    }
+    ret = 0;
+out:
+    close_file_with_epoll(fd, ep_fd);
+    return ret;
+}
+
+static int test_ctl_del() {
+    int fd, ep_fd, ret = -1;
+    if (open_file_with_epoll(&fd, &ep_fd, EPOLLIN) < 0) {
+        return -1;
+    }
+    struct epoll_event event = { .events = EPOLLIN | EPOLLSIZE, .data.fd = fd };
+    if (epoll_ctl(ep_fd, EPOLL_CTL_ADD, fd, &event) == 0 || errno != EEXIST) {
+        printf("\t\tERROR: adding the file twice should fail with EEXIST\n");
+        goto out;
+    }
+    if (epoll_ctl(ep_fd, EPOLL_CTL_DEL, fd, NULL) < 0) {
+        printf("\t\tERROR: failed to delete the file from the epoll\n");
+        goto out;
+    }
+    if (write(fd, "0123456789", 10) != 10 || wait_events(ep_fd, fd, WAIT_TIMEOUT_MS) != 0) {
+        printf("\t\tERROR: the deleted file should not be reported\n");
+        goto out;
+    }
+    ret = 0;
+out:
+    close_file_with_epoll(fd, ep_fd);
+    return ret;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_wake_on_append),
+    TEST_CASE(test_wake_on_append_edge_triggered),
+    TEST_CASE(test_wake_on_truncate),
+    TEST_CASE(test_no_wake_without_size_change),
+    TEST_CASE(test_oneshot),
+    TEST_CASE(test_ctl_del),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,181 @@
+#include <sys/stat.h>
+#include <sys/types.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <limits.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define TEST_DIR            "/root"
+#define DIR_PATH            TEST_DIR "/test_fchdir_dir"
+#define RENAMED_DIR_PATH    TEST_DIR "/test_fchdir_renamed_dir"
+#define FILE_NAME           "test_fchdir_file.txt"
+#define SUBDIR_NAME         "test_fchdir_subdir"
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int check_cwd(const char *expected_cwd) {
+    char cwd[PATH_MAX];
+    if (getcwd(cwd, sizeof(cwd)) == NULL) {
+        THROW_ERROR("failed to getcwd");
+    }
+    if (strcmp(cwd, expected_cwd) != 0) {
+        THROW_ERROR("incorrect cwd %s, expected %s", cwd, expected_cwd);
+    }
+    return 0;
+}
+
+static int open_dir(const char *path) {
+    if (mkdir(path, 0755) < 0) {
+        THROW_ERROR("failed to create the dir %s", path);
+    }
+    int dir_fd = open(path, O_RDONLY | O_DIRECTORY);
+    if (dir_fd < 0) {
+        THROW_ERROR("failed to open the dir %s", path);
+    }
+    return dir_fd;
+}
+
+// ============================================================================
+// Test cases for fchdir
+// ============================================================================
+
+static int test_fchdir() {
+    int dir_fd = open_dir(DIR_PATH);
+    if (dir_fd < 0) {
+        return -1;
+    }
+    if (fchdir(dir_fd) < 0) {
+        THROW_ERROR("failed to fchdir");
+    }
+    close(dir_fd);
+    if (check_cwd(DIR_PATH) < 0) {
+        return -1;
+    }
+
+    chdir("/");
+    if (rmdir(DIR_PATH) < 0) {
+        THROW_ERROR("failed to remove the dir");
+    }
+    return 0;
+}
+
+static int test_fchdir_after_rename() {
+    int dir_fd = open_dir(DIR_PATH);
+    if (dir_fd < 0) {
+        return -1;
+    }
+    if (rename(DIR_PATH, RENAMED_DIR_PATH) < 0) {
+        THROW_ERROR("failed to rename the dir");
+    }
+    if (fchdir(dir_fd) < 0) {
+        THROW_ERROR("failed to fchdir to the renamed dir");
+    }
+    close(dir_fd);
+    if (check_cwd(RENAMED_DIR_PATH) < 0) {
+        return -1;
+    }
+
+    // The file is created in the renamed dir
+    int fd = open(FILE_NAME, O_WRONLY | O_CREAT | O_TRUNC, 0644);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file in the cwd");
+    }
+    close(fd);
+    struct stat stat_buf;
+    if (stat(RENAMED_DIR_PATH "/" FILE_NAME, &stat_buf) < 0) {
+        THROW_ERROR("the file is not created in the renamed dir");
+    }
+
+    chdir("/");
+    if (unlink(RENAMED_DIR_PATH "/" FILE_NAME) < 0 || rmdir(RENAMED_DIR_PATH) < 0) {
+        THROW_ERROR("failed to remove the renamed dir");
+    }
+    return 0;
+}
+
+static int test_relative_path_after_renaming_cwd_parent() {
+    int dir_fd = open_dir(DIR_PATH);
+    if (dir_fd < 0) {
+        return -1;
+    }
+    close(dir_fd);
+    if (mkdir(DIR_PATH "/" SUBDIR_NAME, 0755) < 0) {
+        THROW_ERROR("failed to create the subdir");
+    }
+    if (chdir(DIR_PATH "/" SUBDIR_NAME) < 0) {
+        THROW_ERROR("failed to chdir to the subdir");
+    }
+    if (rename(DIR_PATH, RENAMED_DIR_PATH) < 0) {
+        THROW_ERROR("failed to rename the parent of the cwd");
+    }
+    if (check_cwd(RENAMED_DIR_PATH "/" SUBDIR_NAME) < 0) {
+        return -1;
+    }
+
+    // The relative paths are resolved in the moved cwd
+    int fd = open(FILE_NAME, O_WRONLY | O_CREAT | O_TRUNC, 0644);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file in the cwd");
+    }
+    close(fd);
+    struct stat stat_buf;
+    if (stat(FILE_NAME, &stat_buf) < 0) {
+        THROW_ERROR("failed to stat the file in the cwd");
+    }
+    if (unlink(FILE_NAME) < 0) {
+        THROW_ERROR("failed to unlink the file in the cwd");
+    }
+    if (stat(RENAMED_DIR_PATH "/" SUBDIR_NAME "/" FILE_NAME, &stat_buf) == 0 ||
+            errno != ENOENT) {
+        THROW_ERROR("the file is not unlinked from the moved cwd");
+    }
+
+    chdir("/");
+    if (rmdir(RENAMED_DIR_PATH "/" SUBDIR_NAME) < 0 || rmdir(RENAMED_DIR_PATH) < 0) {
+        THROW_ERROR("failed to remove the renamed dir");
+    }
+    return 0;
+}
+
+static int test_fchdir_with_invalid_fd() {
+    if (fchdir(-1) == 0 || errno != EBADF) {
+        THROW_ERROR("fchdir with an invalid fd should fail with EBADF");
+    }
+    return 0;
+}
+
+static int test_fchdir_to_file() {
+    int fd = open(TEST_DIR "/" FILE_NAME, O_WRONLY | O_CREAT | O_TRUNC, 0644);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+    int ret = fchdir(fd);
+    int saved_errno = errno;
+    close(fd);
+    unlink(TEST_DIR "/" FILE_NAME);
+    if (ret == 0 || saved_errno != ENOTDIR) {
+        THROW_ERROR("fchdir to a file should fail with ENOTDIR");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_fchdir),
+    TEST_CASE(test_fchdir_after_rename),
+    TEST_CASE(test_relative_path_after_renaming_cwd_parent),
+    TEST_CASE(test_fchdir_with_invalid_fd),
+    TEST_CASE(test_fchdir_to_file),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
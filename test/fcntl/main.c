@@ -1,6 +1,9 @@
+#define _GNU_SOURCE
+#include <sys/stat.h>
 #include <unistd.h>
 #include <errno.h>
 #include <fcntl.h>
+#include <signal.h>
 #include <stdio.h>
 #include "test.h"
 
@@ -104,6 +107,106 @@ static int test_getlk_and_setlk() {
     return test_fcntl_framework(__fcntl_getlk_and_setlk);
 }
 
+static int __fcntl_notify_on_file(int fd, int open_flags) {
+    if (fcntl(fd, F_NOTIFY, DN_CREATE) == 0 || errno != EINVAL) {
+        THROW_ERROR("F_NOTIFY on a non-directory should fail with EINVAL");
+    }
+    return 0;
+}
+
+static int test_fcntl_notify() {
+    if (test_fcntl_framework(__fcntl_notify_on_file) < 0) {
+        return -1;
+    }
+
+    int fd = open("/root", O_RDONLY | O_DIRECTORY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the directory");
+    }
+    // Clearing the notification always succeeds
+    if (fcntl(fd, F_NOTIFY, 0) < 0) {
+        THROW_ERROR("failed to clear the notification");
+    }
+    close(fd);
+    return 0;
+}
+
+#define NOTIFY_DIR_PATH     "/root/test_fcntl_notify_dir"
+#define NOTIFY_FILE_PATH    NOTIFY_DIR_PATH "/file"
+
+static volatile sig_atomic_t num_sigio = 0;
+
+static void sigio_handler(int signum) {
+    num_sigio++;
+}
+
+static int create_and_unlink_file() {
+    int fd = open(NOTIFY_FILE_PATH, O_WRONLY | O_CREAT, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create the file");
+    }
+    close(fd);
+    if (unlink(NOTIFY_FILE_PATH) < 0) {
+        THROW_ERROR("failed to unlink the file");
+    }
+    return 0;
+}
+
+static int __test_fcntl_notify_signal(int dir_fd) {
+    // A watch is removed after the first event without DN_MULTISHOT
+    if (fcntl(dir_fd, F_NOTIFY, DN_CREATE | DN_DELETE) < 0) {
+        THROW_ERROR("failed to arm the notification");
+    }
+    if (create_and_unlink_file() < 0) {
+        return -1;
+    }
+    if (num_sigio != 1) {
+        THROW_ERROR("SIGIO is received %d times instead of once", num_sigio);
+    }
+
+    if (fcntl(dir_fd, F_NOTIFY, DN_CREATE | DN_DELETE | DN_MULTISHOT) < 0) {
+        THROW_ERROR("failed to arm the notification");
+    }
+    if (create_and_unlink_file() < 0) {
+        return -1;
+    }
+    if (num_sigio != 3) {
+        THROW_ERROR("SIGIO is received %d times instead of 3", num_sigio);
+    }
+
+    // The events that are not watched are not notified
+    if (fcntl(dir_fd, F_NOTIFY, 0) < 0 || fcntl(dir_fd, F_NOTIFY, DN_RENAME) < 0) {
+        THROW_ERROR("failed to arm the notification");
+    }
+    if (create_and_unlink_file() < 0) {
+        return -1;
+    }
+    if (num_sigio != 3) {
+        THROW_ERROR("the events that are not watched should not be notified");
+    }
+    if (fcntl(dir_fd, F_NOTIFY, 0) < 0) {
+        THROW_ERROR("failed to clear the notification");
+    }
+    return 0;
+}
+
+static int test_fcntl_notify_signal() {
+    if (mkdir(NOTIFY_DIR_PATH, 00777) < 0) {
+        THROW_ERROR("failed to create the directory");
+    }
+    int dir_fd = open(NOTIFY_DIR_PATH, O_RDONLY | O_DIRECTORY);
+    if (dir_fd < 0) {
+        THROW_ERROR("failed to open the directory");
+    }
+    num_sigio = 0;
+    signal(SIGIO, sigio_handler);
+    int ret = __test_fcntl_notify_signal(dir_fd);
+    signal(SIGIO, SIG_DFL);
+    close(dir_fd);
+    rmdir(NOTIFY_DIR_PATH);
+    return ret;
+}
+
 // ============================================================================
 // Test suite
 // ============================================================================
@@ -112,6 +215,8 @@ static test_case_t test_cases[] = {
     TEST_CASE(test_fcntl_getfl),
     TEST_CASE(test_fcntl_setfl),
     TEST_CASE(test_getlk_and_setlk),
+    TEST_CASE(test_fcntl_notify),
+    TEST_CASE(test_fcntl_notify_signal),
 };
 
 int main() {
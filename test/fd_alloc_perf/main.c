@@ -0,0 +1,99 @@
+#include <sys/resource.h>
+#include <fcntl.h>
+#include <time.h>
+#include <unistd.h>
+#include <stdio.h>
+
+// The fds are allocated up to the high limit, at which finding the lowest free
+// fd used to cost linear time
+#define NOFILE_LIMIT        65536
+#define NUM_STEPS           8
+#define NUM_REALLOCS        10000
+
+static unsigned long elapsed_ns(const struct timespec *start, const struct timespec *end) {
+    return (end->tv_sec - start->tv_sec) * 1000000000UL + (end->tv_nsec - start->tv_nsec);
+}
+
+// Allocate the fds up to end_fd, returning the time of each allocation
+static long bench_alloc(int end_fd) {
+    struct timespec start, end;
+    long num_fds = 0;
+    clock_gettime(CLOCK_MONOTONIC, &start);
+    for (;;) {
+        int fd = dup(STDOUT_FILENO);
+        if (fd < 0) {
+            printf("ERROR: failed to dup\n");
+            return -1;
+        }
+        num_fds++;
+        if (fd == end_fd - 1) {
+            break;
+        }
+    }
+    clock_gettime(CLOCK_MONOTONIC, &end);
+    return elapsed_ns(&start, &end) / num_fds;
+}
+
+// Free the lowest fd and allocate it again, or by F_DUPFD with the min fd
+// near it, returning the time of each reallocation
+static long bench_realloc(int lowest_fd, int use_dupfd) {
+    struct timespec start, end;
+    clock_gettime(CLOCK_MONOTONIC, &start);
+    for (int i = 0; i < NUM_REALLOCS; i++) {
+        close(lowest_fd);
+        int fd = use_dupfd ?
+                 fcntl(STDOUT_FILENO, F_DUPFD, lowest_fd > 0 ? lowest_fd - 1 : 0) :
+                 dup(STDOUT_FILENO);
+        if (fd != lowest_fd) {
+            printf("ERROR: the fd %d is allocated instead of %d\n", fd, lowest_fd);
+            return -1;
+        }
+    }
+    clock_gettime(CLOCK_MONOTONIC, &end);
+    return elapsed_ns(&start, &end) / NUM_REALLOCS;
+}
+
+int main(int argc, const char *argv[]) {
+    struct rlimit rlim;
+    if (getrlimit(RLIMIT_NOFILE, &rlim) < 0) {
+        printf("ERROR: failed to get RLIMIT_NOFILE\n");
+        return -1;
+    }
+    rlim_t old_limit = rlim.rlim_cur;
+    rlim.rlim_cur = NOFILE_LIMIT < rlim.rlim_max ? NOFILE_LIMIT : rlim.rlim_max;
+    if (setrlimit(RLIMIT_NOFILE, &rlim) < 0) {
+        printf("ERROR: failed to set RLIMIT_NOFILE\n");
+        return -1;
+    }
+    int limit = rlim.rlim_cur;
+    int first_fd = dup(STDOUT_FILENO);
+    if (first_fd < 0) {
+        printf("ERROR: failed to dup\n");
+        return -1;
+    }
+    close(first_fd);
+
+    int ret = 0;
+    int fds_per_step = (limit - first_fd) / NUM_STEPS;
+    int end_fd = first_fd;
+    for (int step = 0; step < NUM_STEPS; step++) {
+        int step_start_fd = end_fd;
+        end_fd = step == NUM_STEPS - 1 ? limit : end_fd + fds_per_step;
+        long alloc_ns = bench_alloc(end_fd);
+        long dup_ns = bench_realloc(step_start_fd, 0);
+        long dupfd_ns = bench_realloc(end_fd - 1, 1);
+        if (alloc_ns < 0 || dup_ns < 0 || dupfd_ns < 0) {
+            ret = -1;
+            break;
+        }
+        printf("With %d fds: alloc = %ld ns, realloc by dup = %ld ns, by F_DUPFD = %ld ns\n",
+               end_fd, alloc_ns, dup_ns, dupfd_ns);
+    }
+
+    for (int fd = first_fd; fd < limit; fd++) {
+        close(fd);
+    }
+    rlim.rlim_cur = old_limit;
+    setrlimit(RLIMIT_NOFILE, &rlim);
+    return ret;
+}
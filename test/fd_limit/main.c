@@ -0,0 +1,225 @@
+#include <sys/resource.h>
+#include <sys/socket.h>
+#include <sys/types.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <unistd.h>
+#include "test.h"
+
+#define NOFILE_LIMIT        64
+#define HIGH_NOFILE_LIMIT   4096
+
+static struct rlimit old_nofile_rlim;
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int set_nofile_limit(rlim_t limit) {
+    struct rlimit rlim = {
+        .rlim_cur = limit,
+        .rlim_max = old_nofile_rlim.rlim_max,
+    };
+    if (setrlimit(RLIMIT_NOFILE, &rlim) < 0) {
+        THROW_ERROR("failed to set RLIMIT_NOFILE");
+    }
+    return 0;
+}
+
+// Fill the file table up to the limit by dups, returning the number of the fds
+// dup'ed, which are closed by close_fds
+static int fill_fds(rlim_t limit) {
+    int num_fds = 0;
+    for (;;) {
+        int fd = dup(STDOUT_FILENO);
+        if (fd < 0) {
+            if (errno != EMFILE) {
+                THROW_ERROR("the dup should fail with EMFILE");
+            }
+            return num_fds;
+        }
+        if (fd >= limit) {
+            close(fd);
+            THROW_ERROR("the fd %d exceeds the limit", fd);
+        }
+        num_fds++;
+    }
+}
+
+static void close_fds(int min_fd, rlim_t limit) {
+    for (int fd = min_fd; fd < limit; fd++) {
+        close(fd);
+    }
+}
+
+#define CHECK_EMFILE(name, call)                                        \
+    do {                                                                \
+        errno = 0;                                                      \
+        if ((call) >= 0 || errno != EMFILE) {                           \
+            THROW_ERROR("%s should fail with EMFILE", name);            \
+        }                                                               \
+    } while (0)
+
+// ============================================================================
+// Test cases for the limit of the file descriptors
+// ============================================================================
+
+static int __test_out_of_fds(int first_fd) {
+    int fds[2];
+    CHECK_EMFILE("dup", dup(STDOUT_FILENO));
+    CHECK_EMFILE("open", open("/dev/null", O_RDONLY));
+    CHECK_EMFILE("fcntl(F_DUPFD)", fcntl(STDOUT_FILENO, F_DUPFD, 0));
+    CHECK_EMFILE("socket", socket(AF_INET, SOCK_STREAM, 0));
+
+    // With one free fd, no fd is taken by a pipe or a socket pair that fails
+    close(first_fd);
+    CHECK_EMFILE("pipe", pipe(fds));
+    CHECK_EMFILE("socketpair", socketpair(AF_UNIX, SOCK_STREAM, 0, fds));
+    if (dup(STDOUT_FILENO) != first_fd) {
+        THROW_ERROR("the free fd should be left to dup");
+    }
+    return 0;
+}
+
+static int test_out_of_fds() {
+    int first_fd = dup(STDOUT_FILENO);
+    if (first_fd < 0) {
+        THROW_ERROR("failed to dup");
+    }
+    close(first_fd);
+    if (set_nofile_limit(NOFILE_LIMIT) < 0) {
+        return -1;
+    }
+    int ret = fill_fds(NOFILE_LIMIT);
+    if (ret >= 0 && ret != NOFILE_LIMIT - first_fd) {
+        printf("\t\tERROR: %d fds are dup'ed instead of %d\n", ret, NOFILE_LIMIT - first_fd);
+        ret = -1;
+    }
+    if (ret >= 0) {
+        ret = __test_out_of_fds(first_fd);
+    }
+    close_fds(first_fd, NOFILE_LIMIT);
+    set_nofile_limit(old_nofile_rlim.rlim_cur);
+    return ret;
+}
+
+static int test_fd_beyond_limit() {
+    if (set_nofile_limit(NOFILE_LIMIT) < 0) {
+        return -1;
+    }
+    int ret = -1;
+    // F_DUPFD fails with EINVAL, and dup2 fails with EBADF
+    errno = 0;
+    if (fcntl(STDOUT_FILENO, F_DUPFD, NOFILE_LIMIT) >= 0 || errno != EINVAL) {
+        printf("\t\tERROR: F_DUPFD beyond the limit should fail with EINVAL\n");
+        goto out;
+    }
+    errno = 0;
+    if (dup2(STDOUT_FILENO, NOFILE_LIMIT) >= 0 || errno != EBADF) {
+        printf("\t\tERROR: dup2 beyond the limit should fail with EBADF\n");
+        goto out;
+    }
+    // The last fd below the limit can be used
+    if (dup2(STDOUT_FILENO, NOFILE_LIMIT - 1) != NOFILE_LIMIT - 1) {
+        printf("\t\tERROR: dup2 to the last fd should succeed\n");
+        goto out;
+    }
+    close(NOFILE_LIMIT - 1);
+    ret = 0;
+out:
+    set_nofile_limit(old_nofile_rlim.rlim_cur);
+    return ret;
+}
+
+static int test_lowest_free_fd() {
+    int fds[3];
+    for (int i = 0; i < 3; i++) {
+        fds[i] = dup(STDOUT_FILENO);
+        if (fds[i] < 0) {
+            THROW_ERROR("failed to dup");
+        }
+    }
+    int ret = -1;
+    // The fd closed in the middle is the lowest free one
+    close(fds[1]);
+    int fd = dup(STDOUT_FILENO);
+    if (fd != fds[1]) {
+        printf("\t\tERROR: the fd %d is dup'ed instead of %d\n", fd, fds[1]);
+        goto out;
+    }
+    // F_DUPFD finds the lowest free one that is not below the min fd
+    fd = fcntl(STDOUT_FILENO, F_DUPFD, fds[0]);
+    if (fd != fds[2] + 1) {
+        printf("\t\tERROR: the fd %d is dup'ed instead of %d\n", fd, fds[2] + 1);
+        goto out;
+    }
+    close(fd);
+    ret = 0;
+out:
+    for (int i = 0; i < 3; i++) {
+        close(fds[i]);
+    }
+    return ret;
+}
+
+static int test_high_limit() {
+    // The limit can not be raised beyond the hard limit, e.g., on Linux
+    rlim_t limit = HIGH_NOFILE_LIMIT;
+    if (limit > old_nofile_rlim.rlim_max) {
+        limit = old_nofile_rlim.rlim_max;
+    }
+    if (set_nofile_limit(limit) < 0) {
+        return -1;
+    }
+    int ret = 0;
+    // The fds near the limit are allocated on demand
+    int fd = fcntl(STDOUT_FILENO, F_DUPFD, limit - 1);
+    if (fd != limit - 1) {
+        printf("\t\tERROR: failed to dup the last fd below the limit\n");
+        ret = -1;
+    }
+    close(fd);
+    set_nofile_limit(old_nofile_rlim.rlim_cur);
+    return ret;
+}
+
+static int test_huge_fd() {
+    // The fds far beyond the limit are rejected without growing the table
+    errno = 0;
+    if (dup2(STDOUT_FILENO, 2000000000) >= 0 || errno != EBADF) {
+        THROW_ERROR("dup2 to a huge fd should fail with EBADF");
+    }
+    errno = 0;
+    if (fcntl(STDOUT_FILENO, F_DUPFD, 2000000000) >= 0 || errno != EINVAL) {
+        THROW_ERROR("F_DUPFD with a huge min fd should fail with EINVAL");
+    }
+    // The hard limit cannot be raised beyond the max number of fds
+    struct rlimit rlim = {
+        .rlim_cur = old_nofile_rlim.rlim_cur,
+        .rlim_max = RLIM_INFINITY,
+    };
+    errno = 0;
+    if (setrlimit(RLIMIT_NOFILE, &rlim) == 0 || errno != EPERM) {
+        THROW_ERROR("raising the hard limit to infinity should fail with EPERM");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_out_of_fds),
+    TEST_CASE(test_fd_beyond_limit),
+    TEST_CASE(test_lowest_free_fd),
+    TEST_CASE(test_high_limit),
+    TEST_CASE(test_huge_fd),
+};
+
+int main() {
+    if (getrlimit(RLIMIT_NOFILE, &old_nofile_rlim) < 0) {
+        THROW_ERROR("failed to get RLIMIT_NOFILE");
+    }
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,195 @@
+#include <sys/syscall.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <stdint.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define MAX_HANDLE_SZ       128
+
+struct file_handle_t {
+    uint32_t handle_bytes;
+    int32_t handle_type;
+    unsigned char f_handle[MAX_HANDLE_SZ];
+};
+
+#define MOUNT_PATH          "/root"
+#define FILE_PATH           "/root/test_file_handle.txt"
+// The symlink is made on host by the Makefile, as there is no symlink syscall:
+// file_handle_link -> file_handle_target
+#define TARGET_PATH         "/host/file_handle_target"
+#define SYMLINK_PATH        "/host/file_handle_link"
+
+static const char FILE_CONTENT[] = "The content to be read by handle";
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int name_to_handle_at(int dirfd, const char *path, struct file_handle_t *handle,
+                             int *mount_id, int flags) {
+    return syscall(__NR_name_to_handle_at, dirfd, path, handle, mount_id, flags);
+}
+
+static int open_by_handle_at(int mount_fd, struct file_handle_t *handle, int flags) {
+    return syscall(__NR_open_by_handle_at, mount_fd, handle, flags);
+}
+
+static int create_file(void) {
+    int fd = open(FILE_PATH, O_WRONLY | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+    if (write(fd, FILE_CONTENT, sizeof(FILE_CONTENT)) != sizeof(FILE_CONTENT)) {
+        THROW_ERROR("failed to write the file");
+    }
+    close(fd);
+    return 0;
+}
+
+static int get_handle(struct file_handle_t *handle) {
+    int mount_id;
+    handle->handle_bytes = MAX_HANDLE_SZ;
+    if (name_to_handle_at(AT_FDCWD, FILE_PATH, handle, &mount_id, 0) < 0) {
+        THROW_ERROR("failed to get the handle of the file");
+    }
+    return 0;
+}
+
+static int open_handle(struct file_handle_t *handle) {
+    int mount_fd = open(MOUNT_PATH, O_RDONLY);
+    if (mount_fd < 0) {
+        THROW_ERROR("failed to open the mount point");
+    }
+    int fd = open_by_handle_at(mount_fd, handle, O_RDONLY);
+    close(mount_fd);
+    return fd;
+}
+
+// ============================================================================
+// Test cases for file handles
+// ============================================================================
+
+static int test_name_to_handle_with_small_buffer() {
+    if (create_file() < 0) {
+        return -1;
+    }
+    struct file_handle_t handle;
+    int mount_id;
+    handle.handle_bytes = 0;
+    if (name_to_handle_at(AT_FDCWD, FILE_PATH, &handle, &mount_id, 0) == 0 ||
+            errno != EOVERFLOW) {
+        THROW_ERROR("a small buffer for the handle should fail with EOVERFLOW");
+    }
+    if (handle.handle_bytes == 0 || handle.handle_bytes > MAX_HANDLE_SZ) {
+        THROW_ERROR("the required size of the handle should be reported");
+    }
+    return 0;
+}
+
+static int test_name_to_handle_with_empty_path() {
+    struct file_handle_t handle, handle_by_fd;
+    int mount_id, mount_id_by_fd;
+    handle.handle_bytes = MAX_HANDLE_SZ;
+    if (name_to_handle_at(AT_FDCWD, FILE_PATH, &handle, &mount_id, 0) < 0) {
+        THROW_ERROR("failed to get the handle by path");
+    }
+
+    int fd = open(FILE_PATH, O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the file");
+    }
+    handle_by_fd.handle_bytes = MAX_HANDLE_SZ;
+    if (name_to_handle_at(fd, "", &handle_by_fd, &mount_id_by_fd, AT_EMPTY_PATH) < 0) {
+        THROW_ERROR("failed to get the handle by fd");
+    }
+    close(fd);
+
+    if (mount_id != mount_id_by_fd || handle.handle_bytes != handle_by_fd.handle_bytes ||
+            memcmp(handle.f_handle, handle_by_fd.f_handle, handle.handle_bytes) != 0) {
+        THROW_ERROR("the handles by path and by fd should be the same");
+    }
+    return 0;
+}
+
+static int test_name_to_handle_of_symlink() {
+    struct file_handle_t handle, link_handle, target_handle;
+    int mount_id;
+    int fd = open(TARGET_PATH, O_WRONLY | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create the target of the symlink");
+    }
+    close(fd);
+    handle.handle_bytes = MAX_HANDLE_SZ;
+    if (name_to_handle_at(AT_FDCWD, TARGET_PATH, &handle, &mount_id, 0) < 0) {
+        THROW_ERROR("failed to get the handle of the target");
+    }
+    link_handle.handle_bytes = MAX_HANDLE_SZ;
+    if (name_to_handle_at(AT_FDCWD, SYMLINK_PATH, &link_handle, &mount_id, 0) < 0) {
+        THROW_ERROR("failed to get the handle of the symlink");
+    }
+    target_handle.handle_bytes = MAX_HANDLE_SZ;
+    if (name_to_handle_at(AT_FDCWD, SYMLINK_PATH, &target_handle, &mount_id,
+                          AT_SYMLINK_FOLLOW) < 0) {
+        THROW_ERROR("failed to get the handle of the symlink target");
+    }
+
+    if (memcmp(link_handle.f_handle, handle.f_handle, handle.handle_bytes) == 0) {
+        THROW_ERROR("the symlink should not be followed without AT_SYMLINK_FOLLOW");
+    }
+    if (target_handle.handle_bytes != handle.handle_bytes ||
+            memcmp(target_handle.f_handle, handle.f_handle, handle.handle_bytes) != 0) {
+        THROW_ERROR("the symlink should be followed with AT_SYMLINK_FOLLOW");
+    }
+    return 0;
+}
+
+static int test_open_by_handle() {
+    struct file_handle_t handle;
+    if (get_handle(&handle) < 0) {
+        return -1;
+    }
+    int fd = open_handle(&handle);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the file by handle");
+    }
+    char buf[sizeof(FILE_CONTENT)] = {0};
+    if (read(fd, buf, sizeof(buf)) != sizeof(buf) || strcmp(buf, FILE_CONTENT) != 0) {
+        THROW_ERROR("failed to read the file opened by handle");
+    }
+    close(fd);
+    return 0;
+}
+
+static int test_open_by_stale_handle() {
+    struct file_handle_t handle;
+    if (get_handle(&handle) < 0) {
+        return -1;
+    }
+    // Delete and recreate the file
+    if (unlink(FILE_PATH) < 0 || create_file() < 0) {
+        THROW_ERROR("failed to recreate the file");
+    }
+    if (open_handle(&handle) >= 0 || errno != ESTALE) {
+        THROW_ERROR("opening a stale handle should fail with ESTALE");
+    }
+    unlink(FILE_PATH);
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_name_to_handle_with_small_buffer),
+    TEST_CASE(test_name_to_handle_with_empty_path),
+    TEST_CASE(test_name_to_handle_of_symlink),
+    TEST_CASE(test_open_by_handle),
+    TEST_CASE(test_open_by_stale_handle),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
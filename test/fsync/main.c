@@ -0,0 +1,114 @@
+#include <sys/types.h>
+#include <sys/stat.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define FILE_PATH       "/root/test_fsync.txt"
+#define BUF_SIZE        (64 * 1024)
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static char write_buf[BUF_SIZE];
+static char read_buf[BUF_SIZE];
+
+static void fill_buf(char *buf, size_t len, char c) {
+    for (size_t i = 0; i < len; i++) {
+        buf[i] = c + (i % 26);
+    }
+}
+
+static int write_and_sync(const char *path, off_t offset, const char *buf, size_t len,
+                          int (*sync_fn)(int)) {
+    int fd = open(path, O_WRONLY | O_CREAT, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to open a file to write");
+    }
+    if (pwrite(fd, buf, len, offset) != len) {
+        THROW_ERROR("failed to write the file");
+    }
+    if (sync_fn(fd) < 0) {
+        THROW_ERROR("failed to sync the file");
+    }
+    close(fd);
+    return 0;
+}
+
+static int check_file_content(const char *path, const char *expected, size_t len) {
+    int fd = open(path, O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to reopen the file");
+    }
+    memset(read_buf, 0, sizeof(read_buf));
+    if (read(fd, read_buf, len) != len) {
+        THROW_ERROR("failed to read the file");
+    }
+    close(fd);
+    if (memcmp(read_buf, expected, len) != 0) {
+        THROW_ERROR("the content of the file is not consistent after sync");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for fsync and fdatasync
+// ============================================================================
+
+static int test_fsync() {
+    unlink(FILE_PATH);
+    fill_buf(write_buf, BUF_SIZE, 'a');
+    if (write_and_sync(FILE_PATH, 0, write_buf, BUF_SIZE, fsync) < 0) {
+        return -1;
+    }
+    return check_file_content(FILE_PATH, write_buf, BUF_SIZE);
+}
+
+static int test_fsync_after_overwrite() {
+    // Overwrite the middle of the file, which updates both the data blocks
+    // and the metadata that references them
+    size_t offset = BUF_SIZE / 4;
+    size_t len = BUF_SIZE / 2;
+    fill_buf(write_buf + offset, len, 'A');
+    if (write_and_sync(FILE_PATH, offset, write_buf + offset, len, fsync) < 0) {
+        return -1;
+    }
+    return check_file_content(FILE_PATH, write_buf, BUF_SIZE);
+}
+
+static int test_fdatasync() {
+    fill_buf(write_buf, BUF_SIZE, 'k');
+    if (write_and_sync(FILE_PATH, 0, write_buf, BUF_SIZE, fdatasync) < 0) {
+        return -1;
+    }
+    if (check_file_content(FILE_PATH, write_buf, BUF_SIZE) < 0) {
+        return -1;
+    }
+    unlink(FILE_PATH);
+    return 0;
+}
+
+static int test_fsync_with_invalid_fd() {
+    if (fsync(-1) == 0 || errno != EBADF) {
+        THROW_ERROR("fsync with an invalid fd should fail with EBADF");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_fsync),
+    TEST_CASE(test_fsync_after_overwrite),
+    TEST_CASE(test_fdatasync),
+    TEST_CASE(test_fsync_with_invalid_fd),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,305 @@
+#include <linux/futex.h>
+#include <sys/syscall.h>
+#include <sys/time.h>
+#include <errno.h>
+#include <limits.h>
+#include <pthread.h>
+#include <stdint.h>
+#include <time.h>
+#include <unistd.h>
+#include "test.h"
+
+// The time to wait for the waiters to be blocked
+#define BLOCK_WAIT_US       (100 * 1000)
+// The timeout of the timed waits
+#define TIMEOUT_NS          (50 * 1000 * 1000)
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static long futex(int *uaddr, int op, int val, const struct timespec *timeout,
+                  int *uaddr2, int val3) {
+    return syscall(SYS_futex, uaddr, op, val, timeout, uaddr2, val3);
+}
+
+static void add_ns(struct timespec *ts, long ns) {
+    ts->tv_nsec += ns;
+    ts->tv_sec += ts->tv_nsec / 1000000000;
+    ts->tv_nsec %= 1000000000;
+}
+
+static long elapsed_ns(const struct timespec *start, const struct timespec *end) {
+    return (end->tv_sec - start->tv_sec) * 1000000000L + (end->tv_nsec - start->tv_nsec);
+}
+
+struct waiter {
+    int *futex_word;
+    int bitset;
+    volatile int is_woken;
+    int ret;
+};
+
+// The timeout of the timed waits that are expected to be woken before it
+#define LONG_TIMEOUT_S      10
+
+static void *wait_with_long_timeout(void *arg) {
+    struct waiter *waiter = (struct waiter *)arg;
+    struct timespec timeout = { .tv_sec = LONG_TIMEOUT_S, .tv_nsec = 0 };
+    waiter->ret = futex(waiter->futex_word, FUTEX_WAIT, 0, &timeout, NULL, 0);
+    waiter->is_woken = 1;
+    return NULL;
+}
+
+static void *wait_bitset(void *arg) {
+    struct waiter *waiter = (struct waiter *)arg;
+    waiter->ret = futex(waiter->futex_word, FUTEX_WAIT_BITSET, 0, NULL, NULL,
+                        waiter->bitset);
+    waiter->is_woken = 1;
+    return NULL;
+}
+
+// A mutex on a futex word, which is 0 if unlocked, or 2 if locked and there
+// may be waiters. The lock always marks the mutex as contended, as the waiters
+// requeued onto the mutex are not seen by the word.
+static void mutex_lock(int *mutex) {
+    while (__atomic_exchange_n(mutex, 2, __ATOMIC_SEQ_CST) != 0) {
+        futex(mutex, FUTEX_WAIT, 2, NULL, NULL, 0);
+    }
+}
+
+static void mutex_unlock(int *mutex) {
+    if (__atomic_exchange_n(mutex, 0, __ATOMIC_SEQ_CST) == 2) {
+        futex(mutex, FUTEX_WAKE, 1, NULL, NULL, 0);
+    }
+}
+
+#define NUM_COND_WAITERS    4
+
+struct condvar {
+    int cond;
+    int mutex;
+    // The number of the waiters that have returned from the wait on cond
+    volatile int nreturned;
+    // The number of the waiters that hold the mutex now, and at most
+    volatile int nholders;
+    volatile int max_nholders;
+};
+
+static void *wait_cond(void *arg) {
+    struct condvar *cv = (struct condvar *)arg;
+    futex(&cv->cond, FUTEX_WAIT, 0, NULL, NULL, 0);
+    __atomic_add_fetch(&cv->nreturned, 1, __ATOMIC_SEQ_CST);
+
+    mutex_lock(&cv->mutex);
+    int nholders = __atomic_add_fetch(&cv->nholders, 1, __ATOMIC_SEQ_CST);
+    if (nholders > cv->max_nholders) {
+        cv->max_nholders = nholders;
+    }
+    usleep(BLOCK_WAIT_US / 10);
+    __atomic_sub_fetch(&cv->nholders, 1, __ATOMIC_SEQ_CST);
+    mutex_unlock(&cv->mutex);
+    return NULL;
+}
+
+// ============================================================================
+// Test cases for futex
+// ============================================================================
+
+static int test_wake_bitset_targets_one_waiter() {
+    int futex_word = 0;
+    struct waiter waiters[2] = {
+        { .futex_word = &futex_word, .bitset = 0x1, .is_woken = 0, .ret = -1 },
+        { .futex_word = &futex_word, .bitset = 0x2, .is_woken = 0, .ret = -1 },
+    };
+    pthread_t threads[2];
+    for (int i = 0; i < 2; i++) {
+        if (pthread_create(&threads[i], NULL, wait_bitset, &waiters[i]) != 0) {
+            THROW_ERROR("failed to create a thread");
+        }
+    }
+    usleep(BLOCK_WAIT_US);
+
+    // Only the waiter whose bitset intersects the bitset of the wake is woken
+    if (futex(&futex_word, FUTEX_WAKE_BITSET, INT_MAX, NULL, NULL, 0x2) != 1) {
+        THROW_ERROR("the bitset wake should wake exactly one waiter");
+    }
+    pthread_join(threads[1], NULL);
+    usleep(BLOCK_WAIT_US);
+    if (waiters[0].is_woken) {
+        THROW_ERROR("the waiter on another bit should stay asleep");
+    }
+
+    if (futex(&futex_word, FUTEX_WAKE_BITSET, INT_MAX, NULL, NULL, 0x1) != 1) {
+        THROW_ERROR("the bitset wake should wake the other waiter");
+    }
+    pthread_join(threads[0], NULL);
+    if (waiters[0].ret != 0 || waiters[1].ret != 0) {
+        THROW_ERROR("the woken waits should return 0");
+    }
+    return 0;
+}
+
+static int test_wake_matches_any_bitset() {
+    int futex_word = 0;
+    struct waiter waiter = {
+        .futex_word = &futex_word, .bitset = 0x4, .is_woken = 0, .ret = -1
+    };
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, wait_bitset, &waiter) != 0) {
+        THROW_ERROR("failed to create a thread");
+    }
+    usleep(BLOCK_WAIT_US);
+    if (futex(&futex_word, FUTEX_WAKE, 1, NULL, NULL, 0) != 1) {
+        THROW_ERROR("FUTEX_WAKE should wake the bitset waiter");
+    }
+    pthread_join(thread, NULL);
+    return 0;
+}
+
+static int test_zero_bitset() {
+    int futex_word = 0;
+    if (futex(&futex_word, FUTEX_WAIT_BITSET, 0, NULL, NULL, 0) >= 0 || errno != EINVAL) {
+        THROW_ERROR("FUTEX_WAIT_BITSET with a zero bitset should fail with EINVAL");
+    }
+    if (futex(&futex_word, FUTEX_WAKE_BITSET, 1, NULL, NULL, 0) >= 0 || errno != EINVAL) {
+        THROW_ERROR("FUTEX_WAKE_BITSET with a zero bitset should fail with EINVAL");
+    }
+    return 0;
+}
+
+static int __test_wait_bitset_abs_timeout(clockid_t clockid, int op) {
+    int futex_word = 0;
+    struct timespec start, deadline, end;
+
+    clock_gettime(clockid, &start);
+    deadline = start;
+    add_ns(&deadline, TIMEOUT_NS);
+    if (futex(&futex_word, op, 0, &deadline, NULL, FUTEX_BITSET_MATCH_ANY) >= 0 ||
+            errno != ETIMEDOUT) {
+        THROW_ERROR("the wait should time out");
+    }
+    clock_gettime(clockid, &end);
+    if (elapsed_ns(&deadline, &end) < 0) {
+        THROW_ERROR("the wait should not time out before the deadline");
+    }
+    return 0;
+}
+
+static int test_wait_bitset_monotonic_timeout() {
+    return __test_wait_bitset_abs_timeout(CLOCK_MONOTONIC, FUTEX_WAIT_BITSET);
+}
+
+static int test_wait_bitset_realtime_timeout() {
+    return __test_wait_bitset_abs_timeout(CLOCK_REALTIME,
+                                          FUTEX_WAIT_BITSET | FUTEX_CLOCK_REALTIME);
+}
+
+static int test_wait_relative_timeout() {
+    int futex_word = 0;
+    struct timespec start, end;
+    struct timespec timeout = { .tv_sec = 0, .tv_nsec = TIMEOUT_NS };
+
+    clock_gettime(CLOCK_MONOTONIC, &start);
+    if (futex(&futex_word, FUTEX_WAIT, 0, &timeout, NULL, 0) >= 0 || errno != ETIMEDOUT) {
+        THROW_ERROR("the wait should time out");
+    }
+    clock_gettime(CLOCK_MONOTONIC, &end);
+    if (elapsed_ns(&start, &end) < TIMEOUT_NS) {
+        THROW_ERROR("the wait should not time out before the timeout");
+    }
+    return 0;
+}
+
+static int test_wake_timed_wait() {
+    int futex_word = 0;
+    struct waiter waiter = { .futex_word = &futex_word, .is_woken = 0, .ret = -1 };
+    struct timespec start, end;
+    pthread_t thread;
+
+    if (pthread_create(&thread, NULL, wait_with_long_timeout, &waiter) != 0) {
+        THROW_ERROR("failed to create the waiter thread");
+    }
+    usleep(BLOCK_WAIT_US);
+    clock_gettime(CLOCK_MONOTONIC, &start);
+    if (futex(&futex_word, FUTEX_WAKE, 1, NULL, NULL, 0) != 1) {
+        THROW_ERROR("the timed waiter should be woken");
+    }
+    pthread_join(thread, NULL);
+    clock_gettime(CLOCK_MONOTONIC, &end);
+    if (waiter.ret != 0) {
+        THROW_ERROR("the timed wait should return when woken");
+    }
+    if (elapsed_ns(&start, &end) >= LONG_TIMEOUT_S * 1000000000L / 2) {
+        THROW_ERROR("the timed wait should return soon after woken");
+    }
+    return 0;
+}
+
+static int test_cmp_requeue_condvar_broadcast() {
+    struct condvar cv = { .cond = 0, .mutex = 0, .nreturned = 0, .nholders = 0,
+                          .max_nholders = 0 };
+    pthread_t threads[NUM_COND_WAITERS];
+    for (int i = 0; i < NUM_COND_WAITERS; i++) {
+        if (pthread_create(&threads[i], NULL, wait_cond, &cv) != 0) {
+            THROW_ERROR("failed to create a thread");
+        }
+    }
+    usleep(BLOCK_WAIT_US);
+
+    // Broadcast as a condvar does: with the mutex locked, wake one waiter and
+    // requeue the others onto the mutex, so that they are woken one by one
+    // by the unlocks instead of all at once
+    cv.mutex = 2;
+    cv.cond = 1;
+    long ret = futex(&cv.cond, FUTEX_CMP_REQUEUE, 1, (struct timespec *)INT_MAX,
+                     &cv.mutex, 1);
+    if (ret != NUM_COND_WAITERS) {
+        THROW_ERROR("all waiters should be either woken or requeued");
+    }
+    usleep(BLOCK_WAIT_US);
+    if (cv.nreturned != 1) {
+        THROW_ERROR("the requeued waiters should not be woken");
+    }
+
+    mutex_unlock(&cv.mutex);
+    for (int i = 0; i < NUM_COND_WAITERS; i++) {
+        pthread_join(threads[i], NULL);
+    }
+    if (cv.nreturned != NUM_COND_WAITERS) {
+        THROW_ERROR("the requeued waiters should be woken by the unlocks");
+    }
+    if (cv.max_nholders != 1) {
+        THROW_ERROR("only one waiter should hold the mutex at a time");
+    }
+    return 0;
+}
+
+static int test_cmp_requeue_val_mismatch() {
+    int futex_word = 0, new_futex_word = 0;
+    if (futex(&futex_word, FUTEX_CMP_REQUEUE, 1, (struct timespec *)1, &new_futex_word,
+              1) >= 0 || errno != EAGAIN) {
+        THROW_ERROR("FUTEX_CMP_REQUEUE should fail with EAGAIN if the value mismatches");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_wake_bitset_targets_one_waiter),
+    TEST_CASE(test_wake_matches_any_bitset),
+    TEST_CASE(test_zero_bitset),
+    TEST_CASE(test_wait_bitset_monotonic_timeout),
+    TEST_CASE(test_wait_bitset_realtime_timeout),
+    TEST_CASE(test_wait_relative_timeout),
+    TEST_CASE(test_wake_timed_wait),
+    TEST_CASE(test_cmp_requeue_condvar_broadcast),
+    TEST_CASE(test_cmp_requeue_val_mismatch),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,118 @@
+#include <sys/syscall.h>
+#include <errno.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+// The max length of getentropy
+#define MAX_ENTROPY_LEN     256
+// A length that is much larger than the one of a single read of RDRAND
+#define LARGE_LEN           (1024 * 1024)
+
+#ifndef GRND_NONBLOCK
+#define GRND_NONBLOCK       0x0001
+#endif
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static long getrandom_syscall(void *buf, size_t len, unsigned int flags) {
+    return syscall(SYS_getrandom, buf, len, flags);
+}
+
+// Whether the tail of the buffer is still filled with the byte, which is
+// very unlikely if the whole buffer is filled with random bytes
+static int is_tail_untouched(const unsigned char *buf, size_t len, unsigned char byte) {
+    const size_t tail_len = 64;
+    for (size_t i = len - tail_len; i < len; i++) {
+        if (buf[i] != byte) {
+            return 0;
+        }
+    }
+    return 1;
+}
+
+// ============================================================================
+// Test cases for getentropy and getrandom
+// ============================================================================
+
+static int test_getentropy_fills_buffer() {
+    unsigned char buf[MAX_ENTROPY_LEN], another_buf[MAX_ENTROPY_LEN];
+    memset(buf, 0, sizeof(buf));
+    memset(another_buf, 0, sizeof(another_buf));
+
+    if (getentropy(buf, sizeof(buf)) != 0) {
+        THROW_ERROR("getentropy failed");
+    }
+    if (is_tail_untouched(buf, sizeof(buf), 0)) {
+        THROW_ERROR("getentropy should fill the whole buffer");
+    }
+    if (getentropy(another_buf, sizeof(another_buf)) != 0) {
+        THROW_ERROR("getentropy failed");
+    }
+    if (memcmp(buf, another_buf, sizeof(buf)) == 0) {
+        THROW_ERROR("getentropy should return different bytes each time");
+    }
+    return 0;
+}
+
+static int test_getentropy_too_long() {
+    unsigned char buf[MAX_ENTROPY_LEN + 1];
+    if (getentropy(buf, sizeof(buf)) == 0) {
+        THROW_ERROR("getentropy should fail if the length exceeds 256");
+    }
+    return 0;
+}
+
+static int __test_getrandom_full_count(unsigned int flags) {
+    unsigned char *buf = malloc(LARGE_LEN);
+    if (buf == NULL) {
+        THROW_ERROR("failed to allocate the buffer");
+    }
+    memset(buf, 0xff, LARGE_LEN);
+
+    long ret = getrandom_syscall(buf, LARGE_LEN, flags);
+    int untouched = is_tail_untouched(buf, LARGE_LEN, 0xff);
+    free(buf);
+    if (ret != LARGE_LEN) {
+        THROW_ERROR("getrandom should never return a short count");
+    }
+    if (untouched) {
+        THROW_ERROR("getrandom should fill the whole buffer");
+    }
+    return 0;
+}
+
+static int test_getrandom_full_count() {
+    return __test_getrandom_full_count(0);
+}
+
+static int test_getrandom_nonblock_full_count() {
+    return __test_getrandom_full_count(GRND_NONBLOCK);
+}
+
+static int test_getrandom_invalid_flags() {
+    unsigned char buf[16];
+    if (getrandom_syscall(buf, sizeof(buf), 0x100) >= 0 || errno != EINVAL) {
+        THROW_ERROR("getrandom with unknown flags should fail with EINVAL");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_getentropy_fills_buffer),
+    TEST_CASE(test_getentropy_too_long),
+    TEST_CASE(test_getrandom_full_count),
+    TEST_CASE(test_getrandom_nonblock_full_count),
+    TEST_CASE(test_getrandom_invalid_flags),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,218 @@
+#define _GNU_SOURCE
+#include <sys/stat.h>
+#include <sys/syscall.h>
+#include <dirent.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+// ============================================================================
+// Occlum-specific definitions, which must be in sync with the LibOS
+// ============================================================================
+
+#define O_INTEGRITY_ONLY        (1 << 24)
+
+#define STATX_ATTR_ENCRYPTED    0x00000800
+#define STATX_ATTR_VERITY       0x00100000
+
+struct statx_timestamp_t {
+    int64_t tv_sec;
+    uint32_t tv_nsec;
+    int32_t reserved;
+};
+
+struct statx_t {
+    uint32_t stx_mask;
+    uint32_t stx_blksize;
+    uint64_t stx_attributes;
+    uint32_t stx_nlink;
+    uint32_t stx_uid;
+    uint32_t stx_gid;
+    uint16_t stx_mode;
+    uint16_t spare0;
+    uint64_t stx_ino;
+    uint64_t stx_size;
+    uint64_t stx_blocks;
+    uint64_t stx_attributes_mask;
+    struct statx_timestamp_t stx_atime;
+    struct statx_timestamp_t stx_btime;
+    struct statx_timestamp_t stx_ctime;
+    struct statx_timestamp_t stx_mtime;
+    uint32_t stx_rdev_major;
+    uint32_t stx_rdev_minor;
+    uint32_t stx_dev_major;
+    uint32_t stx_dev_minor;
+    uint64_t spare2[14];
+};
+
+#ifndef __NR_statx
+#define __NR_statx              332
+#endif
+
+#define INTEGRITY_FILE_PATH     "/root/test_integrity_only.txt"
+#define ENCRYPTED_FILE_PATH     "/root/test_encrypted.txt"
+
+// The host directories that may back the SEFS mounted at /root
+static const char *HOST_SEFS_DIRS[] = {
+    "/host/.occlum/run/mount/root",
+    "/host/run/mount/root",
+};
+
+static const char FILE_CONTENT[] = "INTEGRITY_ONLY_MARKER_0x5a5a5a5a";
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int create_file(const char *path, int extra_flags) {
+    int fd = open(path, O_WRONLY | O_CREAT | O_TRUNC | extra_flags, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+    if (write(fd, FILE_CONTENT, sizeof(FILE_CONTENT)) != sizeof(FILE_CONTENT)) {
+        THROW_ERROR("failed to write the file");
+    }
+    if (fsync(fd) < 0) {
+        THROW_ERROR("failed to fsync the file");
+    }
+    close(fd);
+    return 0;
+}
+
+static int get_attributes(const char *path, uint64_t *attributes) {
+    struct statx_t stx;
+    if (syscall(__NR_statx, AT_FDCWD, path, 0, 0, &stx) < 0) {
+        THROW_ERROR("failed to call statx");
+    }
+    *attributes = stx.stx_attributes;
+    return 0;
+}
+
+// Find the content on the host and flip one byte of it
+static int tamper_host_file(const char *host_dir) {
+    DIR *dir = opendir(host_dir);
+    if (dir == NULL) {
+        return -1;
+    }
+
+    int tampered = -1;
+    struct dirent *entry;
+    while (tampered < 0 && (entry = readdir(dir)) != NULL) {
+        char path[512];
+        char buf[4096];
+        snprintf(path, sizeof(path), "%s/%s", host_dir, entry->d_name);
+
+        int fd = open(path, O_RDWR);
+        if (fd < 0) {
+            continue;
+        }
+        ssize_t len = read(fd, buf, sizeof(buf));
+        void *pos = len > 0 ? memmem(buf, len, FILE_CONTENT, strlen(FILE_CONTENT)) : NULL;
+        if (pos != NULL) {
+            off_t offset = (char *)pos - buf;
+            char byte = buf[offset] ^ 0xff;
+            if (pwrite(fd, &byte, 1, offset) == 1 && fsync(fd) == 0) {
+                tampered = 0;
+            }
+        }
+        close(fd);
+    }
+    closedir(dir);
+    return tampered;
+}
+
+// ============================================================================
+// Test cases for integrity-only files
+// ============================================================================
+
+static int test_create_integrity_only_file() {
+    if (create_file(INTEGRITY_FILE_PATH, O_INTEGRITY_ONLY) < 0) {
+        THROW_ERROR("failed to create an integrity-only file");
+    }
+    if (create_file(ENCRYPTED_FILE_PATH, 0) < 0) {
+        THROW_ERROR("failed to create an encrypted file");
+    }
+
+    char buf[sizeof(FILE_CONTENT)] = {0};
+    int fd = open(INTEGRITY_FILE_PATH, O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the integrity-only file");
+    }
+    if (read(fd, buf, sizeof(buf)) != sizeof(buf) || strcmp(buf, FILE_CONTENT) != 0) {
+        THROW_ERROR("failed to read the integrity-only file");
+    }
+    close(fd);
+    return 0;
+}
+
+static int test_statx_attributes() {
+    uint64_t attributes;
+    if (get_attributes(INTEGRITY_FILE_PATH, &attributes) < 0) {
+        THROW_ERROR("failed to get the attributes of the integrity-only file");
+    }
+    if (!(attributes & STATX_ATTR_VERITY) || (attributes & STATX_ATTR_ENCRYPTED)) {
+        THROW_ERROR("the integrity-only file should be reported as verity");
+    }
+
+    if (get_attributes(ENCRYPTED_FILE_PATH, &attributes) < 0) {
+        THROW_ERROR("failed to get the attributes of the encrypted file");
+    }
+    if (!(attributes & STATX_ATTR_ENCRYPTED) || (attributes & STATX_ATTR_VERITY)) {
+        THROW_ERROR("the encrypted file should be reported as encrypted");
+    }
+    return 0;
+}
+
+static int test_read_tampered_file() {
+    int tampered = -1;
+    for (int i = 0; i < ARRAY_SIZE(HOST_SEFS_DIRS) && tampered < 0; i++) {
+        tampered = tamper_host_file(HOST_SEFS_DIRS[i]);
+    }
+    if (tampered < 0) {
+        THROW_ERROR("failed to find the integrity-only file on the host");
+    }
+
+    char buf[sizeof(FILE_CONTENT)] = {0};
+    int fd = open(INTEGRITY_FILE_PATH, O_RDONLY);
+    if (fd < 0) {
+        if (errno != EIO) {
+            THROW_ERROR("opening a tampered file should fail with EIO");
+        }
+    } else {
+        if (read(fd, buf, sizeof(buf)) >= 0 || errno != EIO) {
+            THROW_ERROR("reading a tampered file should fail with EIO");
+        }
+        close(fd);
+    }
+
+    unlink(INTEGRITY_FILE_PATH);
+    unlink(ENCRYPTED_FILE_PATH);
+    return 0;
+}
+
+static int test_integrity_only_on_non_sefs() {
+    int fd = open("/tmp/test_integrity_only.txt", O_WRONLY | O_CREAT | O_INTEGRITY_ONLY, 00666);
+    if (fd >= 0 || errno != EINVAL) {
+        THROW_ERROR("O_INTEGRITY_ONLY on a non-SEFS mount should fail with EINVAL");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_create_integrity_only_file),
+    TEST_CASE(test_statx_attributes),
+    TEST_CASE(test_read_tampered_file),
+    TEST_CASE(test_integrity_only_on_non_sefs),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,377 @@
+#include <sys/mman.h>
+#include <sys/syscall.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <poll.h>
+#include <stdint.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define SYS_io_uring_setup      425
+#define SYS_io_uring_enter      426
+
+#define IORING_OFF_SQ_RING      0ULL
+#define IORING_OFF_CQ_RING      0x8000000ULL
+#define IORING_OFF_SQES         0x10000000ULL
+
+#define IORING_ENTER_GETEVENTS  (1U << 0)
+
+#define IORING_OP_NOP           0
+#define IORING_OP_FSYNC         3
+#define IORING_OP_POLL_ADD      6
+#define IORING_OP_READ          22
+#define IORING_OP_WRITE         23
+
+#define QUEUE_DEPTH             8
+#define FILE_PATH               "/root/test_io_uring.txt"
+
+struct io_sqring_offsets {
+    uint32_t head;
+    uint32_t tail;
+    uint32_t ring_mask;
+    uint32_t ring_entries;
+    uint32_t flags;
+    uint32_t dropped;
+    uint32_t array;
+    uint32_t resv1;
+    uint64_t resv2;
+};
+
+struct io_cqring_offsets {
+    uint32_t head;
+    uint32_t tail;
+    uint32_t ring_mask;
+    uint32_t ring_entries;
+    uint32_t overflow;
+    uint32_t cqes;
+    uint32_t flags;
+    uint32_t resv1;
+    uint64_t resv2;
+};
+
+struct io_uring_params {
+    uint32_t sq_entries;
+    uint32_t cq_entries;
+    uint32_t flags;
+    uint32_t sq_thread_cpu;
+    uint32_t sq_thread_idle;
+    uint32_t features;
+    uint32_t wq_fd;
+    uint32_t resv[3];
+    struct io_sqring_offsets sq_off;
+    struct io_cqring_offsets cq_off;
+};
+
+struct io_uring_sqe {
+    uint8_t opcode;
+    uint8_t flags;
+    uint16_t ioprio;
+    int32_t fd;
+    uint64_t off;
+    uint64_t addr;
+    uint32_t len;
+    uint32_t op_flags;
+    uint64_t user_data;
+    uint16_t buf_index;
+    uint16_t personality;
+    int32_t splice_fd_in;
+    uint64_t pad[2];
+};
+
+struct io_uring_cqe {
+    uint64_t user_data;
+    int32_t res;
+    uint32_t flags;
+};
+
+struct ring {
+    int fd;
+    char *sq_ptr;
+    size_t sq_size;
+    unsigned int sq_entries;
+    unsigned int cq_entries;
+    unsigned int *sq_head;
+    unsigned int *sq_tail;
+    unsigned int *sq_mask;
+    unsigned int *sq_array;
+    unsigned int *cq_head;
+    unsigned int *cq_tail;
+    unsigned int *cq_mask;
+    struct io_uring_cqe *cqes;
+    struct io_uring_sqe *sqes;
+};
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int io_uring_setup(unsigned int entries, struct io_uring_params *params) {
+    return syscall(SYS_io_uring_setup, entries, params);
+}
+
+static int io_uring_enter(int fd, unsigned int to_submit, unsigned int min_complete,
+                          unsigned int flags) {
+    return syscall(SYS_io_uring_enter, fd, to_submit, min_complete, flags, NULL, 0);
+}
+
+static int ring_init(struct ring *ring) {
+    struct io_uring_params params;
+    memset(&params, 0, sizeof(params));
+    ring->fd = io_uring_setup(QUEUE_DEPTH, &params);
+    if (ring->fd < 0) {
+        THROW_ERROR("failed to set up io_uring");
+    }
+    ring->sq_entries = params.sq_entries;
+    ring->cq_entries = params.cq_entries;
+
+    size_t sq_size = params.sq_off.array + params.sq_entries * sizeof(unsigned int);
+    char *sq_ptr = mmap(NULL, sq_size, PROT_READ | PROT_WRITE, MAP_SHARED | MAP_POPULATE,
+                        ring->fd, IORING_OFF_SQ_RING);
+    if (sq_ptr == MAP_FAILED) {
+        THROW_ERROR("failed to mmap the SQ ring");
+    }
+    size_t cq_size = params.cq_off.cqes + params.cq_entries * sizeof(struct io_uring_cqe);
+    char *cq_ptr = mmap(NULL, cq_size, PROT_READ | PROT_WRITE, MAP_SHARED | MAP_POPULATE,
+                        ring->fd, IORING_OFF_CQ_RING);
+    if (cq_ptr == MAP_FAILED) {
+        THROW_ERROR("failed to mmap the CQ ring");
+    }
+    size_t sqes_size = params.sq_entries * sizeof(struct io_uring_sqe);
+    ring->sqes = mmap(NULL, sqes_size, PROT_READ | PROT_WRITE, MAP_SHARED | MAP_POPULATE,
+                      ring->fd, IORING_OFF_SQES);
+    if (ring->sqes == MAP_FAILED) {
+        THROW_ERROR("failed to mmap the SQE array");
+    }
+
+    ring->sq_ptr = sq_ptr;
+    ring->sq_size = sq_size;
+    ring->sq_head = (unsigned int *)(sq_ptr + params.sq_off.head);
+    ring->sq_tail = (unsigned int *)(sq_ptr + params.sq_off.tail);
+    ring->sq_mask = (unsigned int *)(sq_ptr + params.sq_off.ring_mask);
+    ring->sq_array = (unsigned int *)(sq_ptr + params.sq_off.array);
+    ring->cq_head = (unsigned int *)(cq_ptr + params.cq_off.head);
+    ring->cq_tail = (unsigned int *)(cq_ptr + params.cq_off.tail);
+    ring->cq_mask = (unsigned int *)(cq_ptr + params.cq_off.ring_mask);
+    ring->cqes = (struct io_uring_cqe *)(cq_ptr + params.cq_off.cqes);
+    return 0;
+}
+
+static void ring_exit(struct ring *ring) {
+    close(ring->fd);
+}
+
+// Queue an SQE without submitting it
+static void ring_queue(struct ring *ring, uint8_t opcode, int fd, void *addr,
+                       uint32_t len, uint64_t off, uint32_t op_flags, uint64_t user_data) {
+    unsigned int tail = *ring->sq_tail;
+    unsigned int idx = tail & *ring->sq_mask;
+    struct io_uring_sqe *sqe = &ring->sqes[idx];
+    memset(sqe, 0, sizeof(*sqe));
+    sqe->opcode = opcode;
+    sqe->fd = fd;
+    sqe->addr = (uint64_t)addr;
+    sqe->len = len;
+    sqe->off = off;
+    sqe->op_flags = op_flags;
+    sqe->user_data = user_data;
+    ring->sq_array[idx] = idx;
+    __atomic_store_n(ring->sq_tail, tail + 1, __ATOMIC_RELEASE);
+}
+
+// Pop a CQE, or return -1 if the CQ is empty
+static int ring_pop(struct ring *ring, struct io_uring_cqe *cqe) {
+    unsigned int head = *ring->cq_head;
+    if (head == __atomic_load_n(ring->cq_tail, __ATOMIC_ACQUIRE)) {
+        return -1;
+    }
+    *cqe = ring->cqes[head & *ring->cq_mask];
+    __atomic_store_n(ring->cq_head, head + 1, __ATOMIC_RELEASE);
+    return 0;
+}
+
+static int check_cqe(struct ring *ring, uint64_t user_data, int32_t res) {
+    struct io_uring_cqe cqe;
+    if (ring_pop(ring, &cqe) < 0) {
+        THROW_ERROR("no CQE for user_data %lu", user_data);
+    }
+    if (cqe.user_data != user_data || cqe.res != res) {
+        THROW_ERROR("incorrect CQE (user_data = %lu, res = %d), expected (%lu, %d)",
+                    cqe.user_data, cqe.res, user_data, res);
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for io_uring
+// ============================================================================
+
+static int test_setup_and_nop() {
+    struct ring ring;
+    if (ring_init(&ring) < 0) {
+        return -1;
+    }
+    if (ring.sq_entries != QUEUE_DEPTH || ring.cq_entries != 2 * QUEUE_DEPTH) {
+        THROW_ERROR("incorrect number of entries");
+    }
+    ring_queue(&ring, IORING_OP_NOP, -1, NULL, 0, 0, 0, 42);
+    if (io_uring_enter(ring.fd, 1, 1, IORING_ENTER_GETEVENTS) != 1) {
+        THROW_ERROR("failed to submit a NOP");
+    }
+    if (check_cqe(&ring, 42, 0) < 0) {
+        return -1;
+    }
+    ring_exit(&ring);
+    return 0;
+}
+
+static int test_invalid_setup() {
+    struct io_uring_params params;
+    memset(&params, 0, sizeof(params));
+    if (io_uring_setup(0, &params) >= 0 || errno != EINVAL) {
+        THROW_ERROR("setup with zero entries should fail with EINVAL");
+    }
+    params.resv[0] = 1;
+    if (io_uring_setup(QUEUE_DEPTH, &params) >= 0 || errno != EINVAL) {
+        THROW_ERROR("setup with non-zero reserved fields should fail with EINVAL");
+    }
+    return 0;
+}
+
+static int test_batched_read_write() {
+    struct ring ring;
+    if (ring_init(&ring) < 0) {
+        return -1;
+    }
+    int fd = open(FILE_PATH, O_RDWR | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+
+    // Submit many writes and an fsync with a single io_uring_enter
+    char write_bufs[4][16];
+    for (int i = 0; i < 4; i++) {
+        memset(write_bufs[i], 'a' + i, sizeof(write_bufs[i]));
+        ring_queue(&ring, IORING_OP_WRITE, fd, write_bufs[i], sizeof(write_bufs[i]),
+                   i * sizeof(write_bufs[i]), 0, i);
+    }
+    ring_queue(&ring, IORING_OP_FSYNC, fd, NULL, 0, 0, 0, 4);
+    if (io_uring_enter(ring.fd, 5, 5, IORING_ENTER_GETEVENTS) != 5) {
+        THROW_ERROR("failed to submit the writes");
+    }
+    for (int i = 0; i < 4; i++) {
+        if (check_cqe(&ring, i, sizeof(write_bufs[i])) < 0) {
+            return -1;
+        }
+    }
+    if (check_cqe(&ring, 4, 0) < 0) {
+        return -1;
+    }
+
+    // Read the data back in a batch
+    char read_bufs[4][16];
+    memset(read_bufs, 0, sizeof(read_bufs));
+    for (int i = 0; i < 4; i++) {
+        ring_queue(&ring, IORING_OP_READ, fd, read_bufs[i], sizeof(read_bufs[i]),
+                   i * sizeof(read_bufs[i]), 0, 10 + i);
+    }
+    if (io_uring_enter(ring.fd, 4, 4, IORING_ENTER_GETEVENTS) != 4) {
+        THROW_ERROR("failed to submit the reads");
+    }
+    for (int i = 0; i < 4; i++) {
+        if (check_cqe(&ring, 10 + i, sizeof(read_bufs[i])) < 0) {
+            return -1;
+        }
+    }
+    if (memcmp(read_bufs, write_bufs, sizeof(write_bufs)) != 0) {
+        THROW_ERROR("the data read is different from the data written");
+    }
+
+    close(fd);
+    unlink(FILE_PATH);
+    ring_exit(&ring);
+    return 0;
+}
+
+static int test_error_result() {
+    struct ring ring;
+    if (ring_init(&ring) < 0) {
+        return -1;
+    }
+    char buf[16];
+    ring_queue(&ring, IORING_OP_READ, 1000, buf, sizeof(buf), 0, 0, 1);
+    ring_queue(&ring, 0xff, -1, NULL, 0, 0, 0, 2);
+    if (io_uring_enter(ring.fd, 2, 2, IORING_ENTER_GETEVENTS) != 2) {
+        THROW_ERROR("failed to submit the SQEs");
+    }
+    if (check_cqe(&ring, 1, -EBADF) < 0 || check_cqe(&ring, 2, -EINVAL) < 0) {
+        return -1;
+    }
+    ring_exit(&ring);
+    return 0;
+}
+
+static int test_poll_add() {
+    struct ring ring;
+    if (ring_init(&ring) < 0) {
+        return -1;
+    }
+    int fd = open("/dev/urandom", O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open /dev/urandom");
+    }
+    ring_queue(&ring, IORING_OP_POLL_ADD, fd, NULL, 0, 0, POLLIN, 7);
+    if (io_uring_enter(ring.fd, 1, 1, IORING_ENTER_GETEVENTS) != 1) {
+        THROW_ERROR("failed to submit the poll");
+    }
+    struct io_uring_cqe cqe;
+    if (ring_pop(&ring, &cqe) < 0 || cqe.user_data != 7 || !(cqe.res & POLLIN)) {
+        THROW_ERROR("the poll should complete with POLLIN");
+    }
+    close(fd);
+    ring_exit(&ring);
+    return 0;
+}
+
+// The memory mapped after the rings are unmapped by the user is not touched
+// when the io_uring is closed
+static int test_munmap_before_close() {
+    struct ring ring;
+    if (ring_init(&ring) < 0) {
+        return -1;
+    }
+    if (munmap(ring.sq_ptr, ring.sq_size) < 0) {
+        THROW_ERROR("failed to munmap the SQ ring");
+    }
+    char *buf = mmap(NULL, ring.sq_size, PROT_READ | PROT_WRITE,
+                     MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    if (buf == MAP_FAILED) {
+        THROW_ERROR("failed to mmap");
+    }
+    memset(buf, 0xab, ring.sq_size);
+    ring_exit(&ring);
+    for (size_t i = 0; i < ring.sq_size; i++) {
+        if (buf[i] != (char)0xab) {
+            THROW_ERROR("the memory is changed by closing the io_uring");
+        }
+    }
+    munmap(buf, ring.sq_size);
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_setup_and_nop),
+    TEST_CASE(test_invalid_setup),
+    TEST_CASE(test_batched_read_write),
+    TEST_CASE(test_error_result),
+    TEST_CASE(test_poll_add),
+    TEST_CASE(test_munmap_before_close),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
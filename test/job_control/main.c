@@ -0,0 +1,197 @@
+#include <sys/types.h>
+#include <sys/wait.h>
+#include <errno.h>
+#include <signal.h>
+#include <spawn.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+// The interval that the child makes progress, i.e., writes a byte
+#define PROGRESS_INTERVAL_US    (10 * 1000)
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+// The child writes a byte to its stdout periodically, until killed
+static int run_child(void) {
+    while (1) {
+        if (write(STDOUT_FILENO, "x", 1) != 1) {
+            return -1;
+        }
+        usleep(PROGRESS_INTERVAL_US);
+    }
+}
+
+// Spawn a child whose stdout is redirected to the pipe, from which its
+// progress is read
+static int spawn_child(int pipe_fds[2]) {
+    if (pipe(pipe_fds) < 0) {
+        THROW_ERROR("failed to create a pipe");
+    }
+    posix_spawn_file_actions_t file_actions;
+    posix_spawn_file_actions_init(&file_actions);
+    posix_spawn_file_actions_adddup2(&file_actions, pipe_fds[1], STDOUT_FILENO);
+    posix_spawn_file_actions_addclose(&file_actions, pipe_fds[0]);
+
+    int child_pid;
+    const char *child_argv[3] = { "job_control", "child", NULL };
+    int ret = posix_spawn(&child_pid, "/bin/job_control", &file_actions, NULL,
+                          (char *const *)child_argv, NULL);
+    posix_spawn_file_actions_destroy(&file_actions);
+    close(pipe_fds[1]);
+    if (ret != 0) {
+        close(pipe_fds[0]);
+        THROW_ERROR("failed to spawn a child process");
+    }
+    return child_pid;
+}
+
+// Count the bytes written by the child during the time
+static int count_progress(int pipe_rd_fd, int time_us) {
+    int total_len = 0;
+    for (int elapsed_us = 0; elapsed_us < time_us; elapsed_us += PROGRESS_INTERVAL_US) {
+        char buf[64];
+        ssize_t len;
+        while ((len = read(pipe_rd_fd, buf, sizeof(buf))) > 0) {
+            total_len += len;
+        }
+        usleep(PROGRESS_INTERVAL_US);
+    }
+    return total_len;
+}
+
+static int kill_and_reap_child(int child_pid) {
+    if (kill(child_pid, SIGKILL) < 0) {
+        THROW_ERROR("failed to send SIGKILL to the child");
+    }
+    int status;
+    if (waitpid(child_pid, &status, 0) != child_pid) {
+        THROW_ERROR("failed to wait for the killed child");
+    }
+    if (!WIFSIGNALED(status) || WTERMSIG(status) != SIGKILL) {
+        THROW_ERROR("the child should be killed by SIGKILL");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for job control
+// ============================================================================
+
+static int test_stop_and_continue() {
+    int pipe_fds[2];
+    int child_pid = spawn_child(pipe_fds);
+    if (child_pid < 0) {
+        return -1;
+    }
+    int pipe_rd_fd = pipe_fds[0];
+    if (count_progress(pipe_rd_fd, 200 * 1000) == 0) {
+        THROW_ERROR("the child makes no progress before stopped");
+    }
+
+    if (kill(child_pid, SIGSTOP) < 0) {
+        THROW_ERROR("failed to send SIGSTOP to the child");
+    }
+    siginfo_t info;
+    memset(&info, 0, sizeof(info));
+    if (waitid(P_PID, child_pid, &info, WSTOPPED) < 0) {
+        THROW_ERROR("failed to wait for the child to stop");
+    }
+    if (info.si_pid != child_pid || info.si_code != CLD_STOPPED ||
+            info.si_status != SIGSTOP) {
+        THROW_ERROR("the siginfo of the stopped child is wrong");
+    }
+    // The write that is in progress when stopped may still complete
+    count_progress(pipe_rd_fd, 50 * 1000);
+    if (count_progress(pipe_rd_fd, 200 * 1000) != 0) {
+        THROW_ERROR("the child makes progress while stopped");
+    }
+
+    if (kill(child_pid, SIGCONT) < 0) {
+        THROW_ERROR("failed to send SIGCONT to the child");
+    }
+    memset(&info, 0, sizeof(info));
+    if (waitid(P_PID, child_pid, &info, WCONTINUED) < 0) {
+        THROW_ERROR("failed to wait for the child to continue");
+    }
+    if (info.si_pid != child_pid || info.si_code != CLD_CONTINUED ||
+            info.si_status != SIGCONT) {
+        THROW_ERROR("the siginfo of the continued child is wrong");
+    }
+    if (count_progress(pipe_rd_fd, 200 * 1000) == 0) {
+        THROW_ERROR("the child makes no progress after continued");
+    }
+
+    int ret = kill_and_reap_child(child_pid);
+    close(pipe_rd_fd);
+    return ret;
+}
+
+static int test_kill_stopped_child() {
+    int pipe_fds[2];
+    int child_pid = spawn_child(pipe_fds);
+    if (child_pid < 0) {
+        return -1;
+    }
+    if (kill(child_pid, SIGTSTP) < 0) {
+        THROW_ERROR("failed to send SIGTSTP to the child");
+    }
+    int status;
+    if (waitpid(child_pid, &status, WUNTRACED) != child_pid) {
+        THROW_ERROR("failed to wait for the child to stop");
+    }
+    if (!WIFSTOPPED(status) || WSTOPSIG(status) != SIGTSTP) {
+        THROW_ERROR("the child should be stopped by SIGTSTP");
+    }
+
+    int ret = kill_and_reap_child(child_pid);
+    close(pipe_fds[0]);
+    return ret;
+}
+
+static int test_continue_not_stopped_child() {
+    int pipe_fds[2];
+    int child_pid = spawn_child(pipe_fds);
+    if (child_pid < 0) {
+        return -1;
+    }
+    // SIGCONT to a child that is not stopped is not reported
+    if (kill(child_pid, SIGCONT) < 0) {
+        THROW_ERROR("failed to send SIGCONT to the child");
+    }
+    int status;
+    if (waitpid(child_pid, &status, WCONTINUED | WNOHANG) != 0) {
+        THROW_ERROR("the child that is not stopped should not be reported as continued");
+    }
+
+    int ret = kill_and_reap_child(child_pid);
+    close(pipe_fds[0]);
+    return ret;
+}
+
+static int test_kill_nonexistent_process() {
+    if (kill(0x7fff0000, SIGSTOP) == 0 || errno != ESRCH) {
+        THROW_ERROR("kill to a nonexistent process should fail with ESRCH");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_stop_and_continue),
+    TEST_CASE(test_kill_stopped_child),
+    TEST_CASE(test_continue_not_stopped_child),
+    TEST_CASE(test_kill_nonexistent_process),
+};
+
+int main(int argc, const char *argv[]) {
+    if (argc > 1 && strcmp(argv[1], "child") == 0) {
+        return run_child();
+    }
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,18 @@
+#!/usr/bin/env python
+# Run the command, and fail if the peak RSS of it is more than the limit
+#
+# Usage: check_peak_rss.py <max_peak_rss_kb> <command> [args...]
+import resource
+import subprocess
+import sys
+
+max_peak_rss_kb = int(sys.argv[1])
+ret = subprocess.call(sys.argv[2:])
+if ret != 0:
+    sys.exit(ret)
+
+# The peak RSS of the largest process among the descendants, in KB on Linux
+peak_rss_kb = resource.getrusage(resource.RUSAGE_CHILDREN).ru_maxrss
+if peak_rss_kb > max_peak_rss_kb:
+    print("The peak RSS is %dKB, which is more than %dKB" % (peak_rss_kb, max_peak_rss_kb))
+    sys.exit(1)
@@ -0,0 +1,333 @@
+#include <sys/resource.h>
+#include <sys/types.h>
+#include <sys/uio.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <pthread.h>
+#include <signal.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+// The max_io_size of the Occlum instance of the test is 1MB (see Makefile),
+// so a buffer of BUF_SIZE is read or written in multiple chunks. A buffer of
+// 1GB cannot be allocated in the user space of the test, so the vectored I/O
+// of 1GB is done with NUM_IOVS vectors of the same buffer, and Makefile checks
+// that the peak memory of the run is not as large.
+#define FILE_PATH       "/host/test_large_rw.txt"
+#define BUF_SIZE        (8 * 1024 * 1024)
+#define NUM_IOVS        128
+#define LARGE_IO_SIZE   ((ssize_t)BUF_SIZE * NUM_IOVS)
+#define OFFSET          12345
+#define FSIZE_LIMIT     (2 * 1024 * 1024 + 512 * 1024)
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+// Fill the buffer with a pattern that differs from chunk to chunk
+static void fill_buf(char *buf, size_t len) {
+    for (size_t i = 0; i < len; i++) {
+        buf[i] = (char)(i * 7 + i / 4096);
+    }
+}
+
+static int check_buf(const char *buf, size_t len) {
+    for (size_t i = 0; i < len; i++) {
+        if (buf[i] != (char)(i * 7 + i / 4096)) {
+            THROW_ERROR("the data at %lu is wrong", i);
+        }
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for large reads and writes
+// ============================================================================
+
+static int test_read_write() {
+    char *buf = malloc(BUF_SIZE);
+    if (buf == NULL) {
+        THROW_ERROR("failed to allocate the buffer");
+    }
+    int ret = 0;
+    int fd = open(FILE_PATH, O_RDWR | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        free(buf);
+        THROW_ERROR("failed to open the file");
+    }
+
+    fill_buf(buf, BUF_SIZE);
+    if (write(fd, buf, BUF_SIZE) != BUF_SIZE) {
+        ret = -1;
+        printf("\t\tERROR: failed to write the whole buffer at once\n");
+        goto out;
+    }
+    if (lseek(fd, 0, SEEK_CUR) != BUF_SIZE) {
+        ret = -1;
+        printf("\t\tERROR: the file offset is wrong after write\n");
+        goto out;
+    }
+    memset(buf, 0, BUF_SIZE);
+    if (lseek(fd, 0, SEEK_SET) != 0 || read(fd, buf, BUF_SIZE) != BUF_SIZE) {
+        ret = -1;
+        printf("\t\tERROR: failed to read the whole buffer at once\n");
+        goto out;
+    }
+    ret = check_buf(buf, BUF_SIZE);
+    // A read at EOF is a short one, even if it is larger than a chunk
+    if (ret == 0 && (lseek(fd, -100, SEEK_END) < 0 || read(fd, buf, BUF_SIZE) != 100)) {
+        ret = -1;
+        printf("\t\tERROR: the read near EOF should return the rest of the file\n");
+    }
+out:
+    close(fd);
+    unlink(FILE_PATH);
+    free(buf);
+    return ret;
+}
+
+static int test_pread_pwrite() {
+    char *buf = malloc(BUF_SIZE);
+    if (buf == NULL) {
+        THROW_ERROR("failed to allocate the buffer");
+    }
+    int ret = 0;
+    int fd = open(FILE_PATH, O_RDWR | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        free(buf);
+        THROW_ERROR("failed to open the file");
+    }
+
+    fill_buf(buf, BUF_SIZE);
+    if (pwrite(fd, buf, BUF_SIZE, OFFSET) != BUF_SIZE) {
+        ret = -1;
+        printf("\t\tERROR: failed to pwrite the whole buffer at once\n");
+        goto out;
+    }
+    memset(buf, 0, BUF_SIZE);
+    if (pread(fd, buf, BUF_SIZE, OFFSET) != BUF_SIZE) {
+        ret = -1;
+        printf("\t\tERROR: failed to pread the whole buffer at once\n");
+        goto out;
+    }
+    ret = check_buf(buf, BUF_SIZE);
+    if (ret == 0 && lseek(fd, 0, SEEK_CUR) != 0) {
+        ret = -1;
+        printf("\t\tERROR: the file offset should not be changed\n");
+    }
+out:
+    close(fd);
+    unlink(FILE_PATH);
+    free(buf);
+    return ret;
+}
+
+static void init_iovs(struct iovec *iov, char *buf) {
+    for (int i = 0; i < NUM_IOVS; i++) {
+        iov[i].iov_base = buf;
+        iov[i].iov_len = BUF_SIZE;
+    }
+}
+
+static int test_readv_writev_1g() {
+    char *buf = malloc(BUF_SIZE);
+    if (buf == NULL) {
+        THROW_ERROR("failed to allocate the buffer");
+    }
+    struct iovec iov[NUM_IOVS];
+    init_iovs(iov, buf);
+    int ret = 0;
+    int fd = open(FILE_PATH, O_RDWR | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        free(buf);
+        THROW_ERROR("failed to open the file");
+    }
+
+    fill_buf(buf, BUF_SIZE);
+    if (writev(fd, iov, NUM_IOVS) != LARGE_IO_SIZE) {
+        ret = -1;
+        printf("\t\tERROR: failed to writev 1GB at once\n");
+        goto out;
+    }
+    memset(buf, 0, BUF_SIZE);
+    if (lseek(fd, 0, SEEK_SET) != 0 || readv(fd, iov, NUM_IOVS) != LARGE_IO_SIZE) {
+        ret = -1;
+        printf("\t\tERROR: failed to readv 1GB at once\n");
+        goto out;
+    }
+    ret = check_buf(buf, BUF_SIZE);
+out:
+    close(fd);
+    unlink(FILE_PATH);
+    free(buf);
+    return ret;
+}
+
+static int test_preadv_pwritev_1g() {
+    char *buf = malloc(BUF_SIZE);
+    if (buf == NULL) {
+        THROW_ERROR("failed to allocate the buffer");
+    }
+    struct iovec iov[NUM_IOVS];
+    init_iovs(iov, buf);
+    int ret = 0;
+    int fd = open(FILE_PATH, O_RDWR | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        free(buf);
+        THROW_ERROR("failed to open the file");
+    }
+
+    fill_buf(buf, BUF_SIZE);
+    if (pwritev(fd, iov, NUM_IOVS, OFFSET) != LARGE_IO_SIZE) {
+        ret = -1;
+        printf("\t\tERROR: failed to pwritev 1GB at once\n");
+        goto out;
+    }
+    memset(buf, 0, BUF_SIZE);
+    if (preadv(fd, iov, NUM_IOVS, OFFSET) != LARGE_IO_SIZE) {
+        ret = -1;
+        printf("\t\tERROR: failed to preadv 1GB at once\n");
+        goto out;
+    }
+    ret = check_buf(buf, BUF_SIZE);
+    if (ret == 0 && lseek(fd, 0, SEEK_CUR) != 0) {
+        ret = -1;
+        printf("\t\tERROR: the file offset should not be changed\n");
+    }
+out:
+    close(fd);
+    unlink(FILE_PATH);
+    free(buf);
+    return ret;
+}
+
+static void *pipe_writer(void *arg) {
+    int fd = *(int *)arg;
+    char *buf = malloc(BUF_SIZE);
+    if (buf != NULL) {
+        fill_buf(buf, BUF_SIZE);
+        // A blocking write may be a short one if interrupted, so write the rest
+        size_t done = 0;
+        while (done < BUF_SIZE) {
+            ssize_t len = write(fd, buf + done, BUF_SIZE - done);
+            if (len <= 0) {
+                break;
+            }
+            done += len;
+        }
+        free(buf);
+    }
+    close(fd);
+    return NULL;
+}
+
+static int test_pipe() {
+    char *buf = malloc(BUF_SIZE);
+    if (buf == NULL) {
+        THROW_ERROR("failed to allocate the buffer");
+    }
+    int pipe_fds[2];
+    pthread_t thread;
+    if (pipe(pipe_fds) < 0) {
+        THROW_ERROR("failed to create a pipe");
+    }
+    if (pthread_create(&thread, NULL, pipe_writer, &pipe_fds[1]) != 0) {
+        THROW_ERROR("failed to create the writer thread");
+    }
+
+    size_t done = 0;
+    while (done < BUF_SIZE) {
+        ssize_t len = read(pipe_fds[0], buf + done, BUF_SIZE - done);
+        if (len <= 0) {
+            break;
+        }
+        done += len;
+    }
+    pthread_join(thread, NULL);
+    close(pipe_fds[0]);
+
+    int ret = 0;
+    if (done != BUF_SIZE) {
+        ret = -1;
+        printf("\t\tERROR: only %lu bytes are read from the pipe\n", done);
+    } else {
+        ret = check_buf(buf, BUF_SIZE);
+    }
+    free(buf);
+    return ret;
+}
+
+static int test_write_partially_failed() {
+    char *buf = malloc(BUF_SIZE);
+    if (buf == NULL) {
+        THROW_ERROR("failed to allocate the buffer");
+    }
+    struct rlimit old_rlim, rlim;
+    if (getrlimit(RLIMIT_FSIZE, &old_rlim) < 0) {
+        THROW_ERROR("failed to get RLIMIT_FSIZE");
+    }
+    // On Linux, SIGXFSZ terminates the process by default
+    signal(SIGXFSZ, SIG_IGN);
+    rlim.rlim_cur = FSIZE_LIMIT;
+    rlim.rlim_max = old_rlim.rlim_max;
+    if (setrlimit(RLIMIT_FSIZE, &rlim) < 0) {
+        THROW_ERROR("failed to set RLIMIT_FSIZE");
+    }
+
+    int ret = 0;
+    int fd = open(FILE_PATH, O_RDWR | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        ret = -1;
+        printf("\t\tERROR: failed to open the file\n");
+    } else {
+        // The bytes written before the limit is hit are returned
+        fill_buf(buf, BUF_SIZE);
+        ssize_t len = write(fd, buf, BUF_SIZE);
+        if (len != FSIZE_LIMIT) {
+            ret = -1;
+            printf("\t\tERROR: the write returns %ld instead of the bytes written\n", len);
+        } else if (write(fd, buf, BUF_SIZE) == 0 || errno != EFBIG) {
+            ret = -1;
+            printf("\t\tERROR: the write beyond the limit should fail with EFBIG\n");
+        }
+    }
+
+    if (setrlimit(RLIMIT_FSIZE, &old_rlim) < 0) {
+        ret = -1;
+        printf("\t\tERROR: failed to restore RLIMIT_FSIZE\n");
+    }
+    if (ret == 0) {
+        memset(buf, 0, BUF_SIZE);
+        if (pread(fd, buf, BUF_SIZE, 0) != FSIZE_LIMIT) {
+            ret = -1;
+            printf("\t\tERROR: the file should have the bytes written only\n");
+        } else {
+            ret = check_buf(buf, FSIZE_LIMIT);
+        }
+    }
+    if (fd >= 0) {
+        close(fd);
+    }
+    unlink(FILE_PATH);
+    free(buf);
+    return ret;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_read_write),
+    TEST_CASE(test_pread_pwrite),
+    TEST_CASE(test_readv_writev_1g),
+    TEST_CASE(test_preadv_pwritev_1g),
+    TEST_CASE(test_pipe),
+    TEST_CASE(test_write_partially_failed),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
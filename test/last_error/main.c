@@ -0,0 +1,125 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <pthread.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+// The recording of the last error is enabled in Occlum.json for tests
+#define LAST_ERROR_PATH     "/proc/self/occlum_last_error"
+#define NONEXISTENT_PATH    "/root/test_last_error_nonexistent.txt"
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int read_last_error(int fd, char *buf, size_t buf_size) {
+    memset(buf, 0, buf_size);
+    ssize_t len = pread(fd, buf, buf_size - 1, 0);
+    if (len < 0) {
+        THROW_ERROR("failed to read the last error");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for the last error
+// ============================================================================
+
+static int test_last_error() {
+    char buf[1024];
+    int fd = open(LAST_ERROR_PATH, O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the last error");
+    }
+
+    if (open(NONEXISTENT_PATH, O_RDONLY) >= 0 || errno != ENOENT) {
+        THROW_ERROR("open on a nonexistent file should fail with ENOENT");
+    }
+    if (read_last_error(fd, buf, sizeof(buf)) < 0) {
+        return -1;
+    }
+    if (strstr(buf, "SysOpen") == NULL || strstr(buf, "ENOENT") == NULL) {
+        THROW_ERROR("the last error should be recorded: %s", buf);
+    }
+
+    // The successful syscalls do not clear the last error
+    if (getpid() < 0 || read_last_error(fd, buf, sizeof(buf)) < 0) {
+        return -1;
+    }
+    if (strstr(buf, "SysOpen") == NULL) {
+        THROW_ERROR("the last error should be kept after a successful syscall");
+    }
+
+    // The next failed syscall replaces the last error
+    if (close(-1) == 0 || errno != EBADF) {
+        THROW_ERROR("close on an invalid fd should fail with EBADF");
+    }
+    if (read_last_error(fd, buf, sizeof(buf)) < 0) {
+        return -1;
+    }
+    if (strstr(buf, "SysClose") == NULL || strstr(buf, "EBADF") == NULL) {
+        THROW_ERROR("the last error should be replaced: %s", buf);
+    }
+    close(fd);
+    return 0;
+}
+
+static void *thread_func(void *arg) {
+    char buf[1024];
+    int fd = *(int *)arg;
+    // The last error of the main thread should not be visible
+    if (read_last_error(fd, buf, sizeof(buf)) < 0 || strstr(buf, "SysOpen") != NULL) {
+        return (void *)-1;
+    }
+    if (close(-1) == 0 || read_last_error(fd, buf, sizeof(buf)) < 0 ||
+            strstr(buf, "SysClose") == NULL) {
+        return (void *)-1;
+    }
+    return NULL;
+}
+
+static int test_last_error_per_thread() {
+    int fd = open(LAST_ERROR_PATH, O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the last error");
+    }
+    if (open(NONEXISTENT_PATH, O_RDONLY) >= 0) {
+        THROW_ERROR("open on a nonexistent file should fail");
+    }
+
+    pthread_t thread;
+    void *thread_ret;
+    if (pthread_create(&thread, NULL, thread_func, &fd) != 0) {
+        THROW_ERROR("failed to create a thread");
+    }
+    if (pthread_join(thread, &thread_ret) != 0) {
+        THROW_ERROR("failed to join the thread");
+    }
+    if (thread_ret != NULL) {
+        THROW_ERROR("the last error should be recorded for each thread");
+    }
+    close(fd);
+    return 0;
+}
+
+static int test_open_last_error_for_write() {
+    if (open(LAST_ERROR_PATH, O_WRONLY) >= 0 || errno != EACCES) {
+        THROW_ERROR("open the last error for write should fail with EACCES");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_last_error),
+    TEST_CASE(test_last_error_per_thread),
+    TEST_CASE(test_open_last_error_for_write),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,284 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <pthread.h>
+#include <signal.h>
+#include <unistd.h>
+#include "test.h"
+
+#define FILE_PATH       "/root/test_lease.txt"
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static volatile int sigio_count = 0;
+
+static void handle_sigio(int signum) {
+    sigio_count++;
+}
+
+// The lease holder is sent SIGIO when its lease starts to be broken, which
+// would terminate the process by default
+static int set_sigio_handler(void) {
+    struct sigaction sa = { 0 };
+    sa.sa_handler = handle_sigio;
+    sa.sa_flags = SA_RESTART;
+    if (sigaction(SIGIO, &sa, NULL) < 0) {
+        THROW_ERROR("failed to set the SIGIO handler");
+    }
+    return 0;
+}
+
+static int create_file(void) {
+    int fd = open(FILE_PATH, O_WRONLY | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+    close(fd);
+    return 0;
+}
+
+static int open_with_lease(int flags, int lease_type) {
+    int fd = open(FILE_PATH, flags);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the file");
+    }
+    if (fcntl(fd, F_SETLEASE, lease_type) < 0) {
+        close(fd);
+        THROW_ERROR("failed to set the lease");
+    }
+    if (fcntl(fd, F_GETLEASE) != lease_type) {
+        close(fd);
+        THROW_ERROR("failed to get the lease");
+    }
+    return fd;
+}
+
+static void *open_for_write(void *arg) {
+    int *ret = (int *)arg;
+    int fd = open(FILE_PATH, O_WRONLY);
+    if (fd >= 0) {
+        close(fd);
+    }
+    __atomic_store_n(ret, fd >= 0 ? 1 : -1, __ATOMIC_SEQ_CST);
+    return NULL;
+}
+
+static void *truncate_file(void *arg) {
+    int *ret = (int *)arg;
+    int status = truncate(FILE_PATH, 0);
+    __atomic_store_n(ret, status == 0 ? 1 : -1, __ATOMIC_SEQ_CST);
+    return NULL;
+}
+
+// ============================================================================
+// Test cases for file leases
+// ============================================================================
+
+static int test_read_lease() {
+    if (create_file() < 0) {
+        return -1;
+    }
+    int fd = open_with_lease(O_RDONLY, F_RDLCK);
+    if (fd < 0) {
+        return -1;
+    }
+
+    // Opening for read does not break a read lease
+    int other_fd = open(FILE_PATH, O_RDONLY | O_NONBLOCK);
+    if (other_fd < 0) {
+        THROW_ERROR("opening for read should not break a read lease");
+    }
+    close(other_fd);
+    if (fcntl(fd, F_GETLEASE) != F_RDLCK) {
+        THROW_ERROR("the read lease should not be broken");
+    }
+
+    // Opening for write starts to break the lease
+    if (open(FILE_PATH, O_RDWR | O_NONBLOCK) >= 0 || errno != EWOULDBLOCK) {
+        THROW_ERROR("opening a leased file should fail with EWOULDBLOCK");
+    }
+    if (fcntl(fd, F_GETLEASE) != F_UNLCK) {
+        THROW_ERROR("the lease should be being broken");
+    }
+
+    if (fcntl(fd, F_SETLEASE, F_UNLCK) < 0) {
+        THROW_ERROR("failed to release the lease");
+    }
+    other_fd = open(FILE_PATH, O_RDWR | O_NONBLOCK);
+    if (other_fd < 0) {
+        THROW_ERROR("failed to open the file after the lease is released");
+    }
+    close(other_fd);
+    close(fd);
+    return 0;
+}
+
+static int test_write_lease_downgrade() {
+    int fd = open_with_lease(O_RDONLY, F_WRLCK);
+    if (fd < 0) {
+        return -1;
+    }
+
+    if (open(FILE_PATH, O_RDONLY | O_NONBLOCK) >= 0 || errno != EWOULDBLOCK) {
+        THROW_ERROR("opening a file with write lease should fail with EWOULDBLOCK");
+    }
+    if (fcntl(fd, F_GETLEASE) != F_RDLCK) {
+        THROW_ERROR("the write lease should be being downgraded");
+    }
+
+    if (fcntl(fd, F_SETLEASE, F_RDLCK) < 0) {
+        THROW_ERROR("failed to downgrade the lease");
+    }
+    int other_fd = open(FILE_PATH, O_RDONLY | O_NONBLOCK);
+    if (other_fd < 0) {
+        THROW_ERROR("failed to open the file after the lease is downgraded");
+    }
+    close(other_fd);
+    close(fd);
+    return 0;
+}
+
+static int test_conflicting_open_blocks() {
+    int fd = open_with_lease(O_RDONLY, F_RDLCK);
+    if (fd < 0) {
+        return -1;
+    }
+
+    int opened = 0;
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, open_for_write, &opened) != 0) {
+        THROW_ERROR("failed to create a thread");
+    }
+    // Wait for the lease break, which should block the open
+    while (fcntl(fd, F_GETLEASE) != F_UNLCK) {
+        usleep(1000);
+    }
+    usleep(100 * 1000);
+    if (__atomic_load_n(&opened, __ATOMIC_SEQ_CST) != 0) {
+        THROW_ERROR("the conflicting open should be blocked");
+    }
+
+    if (fcntl(fd, F_SETLEASE, F_UNLCK) < 0) {
+        THROW_ERROR("failed to release the lease");
+    }
+    pthread_join(thread, NULL);
+    if (opened != 1) {
+        THROW_ERROR("the conflicting open should succeed after the lease is released");
+    }
+    close(fd);
+    return 0;
+}
+
+static int test_conflicting_truncate_blocks() {
+    int fd = open_with_lease(O_RDONLY, F_RDLCK);
+    if (fd < 0) {
+        return -1;
+    }
+
+    int truncated = 0;
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, truncate_file, &truncated) != 0) {
+        THROW_ERROR("failed to create a thread");
+    }
+    // The lease holder is not blocked while the truncate waits for the break
+    while (fcntl(fd, F_GETLEASE) != F_UNLCK) {
+        usleep(1000);
+    }
+    usleep(100 * 1000);
+    if (__atomic_load_n(&truncated, __ATOMIC_SEQ_CST) != 0) {
+        THROW_ERROR("the truncate should be blocked");
+    }
+
+    if (fcntl(fd, F_SETLEASE, F_UNLCK) < 0) {
+        THROW_ERROR("failed to release the lease");
+    }
+    pthread_join(thread, NULL);
+    if (truncated != 1) {
+        THROW_ERROR("the truncate should succeed after the lease is released");
+    }
+    close(fd);
+    return 0;
+}
+
+static int test_lease_break_signal() {
+    int fd = open_with_lease(O_RDONLY, F_RDLCK);
+    if (fd < 0) {
+        return -1;
+    }
+
+    int old_count = sigio_count;
+    if (open(FILE_PATH, O_RDWR | O_NONBLOCK) >= 0 || errno != EWOULDBLOCK) {
+        THROW_ERROR("opening a leased file should fail with EWOULDBLOCK");
+    }
+    for (int i = 0; i < 100 && sigio_count == old_count; i++) {
+        usleep(1000);
+    }
+    if (sigio_count != old_count + 1) {
+        THROW_ERROR("the lease holder should be sent SIGIO once");
+    }
+
+    // The lease being broken is not signaled again
+    if (open(FILE_PATH, O_RDWR | O_NONBLOCK) >= 0 || errno != EWOULDBLOCK) {
+        THROW_ERROR("opening a leased file should fail with EWOULDBLOCK");
+    }
+    usleep(10 * 1000);
+    if (sigio_count != old_count + 1) {
+        THROW_ERROR("the lease being broken should not be signaled again");
+    }
+
+    if (fcntl(fd, F_SETLEASE, F_UNLCK) < 0) {
+        THROW_ERROR("failed to release the lease");
+    }
+    close(fd);
+    return 0;
+}
+
+static int test_write_lease_with_other_opens() {
+    int fd = open(FILE_PATH, O_RDONLY);
+    int other_fd = open(FILE_PATH, O_RDONLY);
+    if (fd < 0 || other_fd < 0) {
+        THROW_ERROR("failed to open the file");
+    }
+    if (fcntl(fd, F_SETLEASE, F_WRLCK) == 0 || errno != EAGAIN) {
+        THROW_ERROR("write lease on a file opened by others should fail with EAGAIN");
+    }
+    close(other_fd);
+    close(fd);
+    return 0;
+}
+
+static int test_read_lease_with_writer() {
+    int fd = open(FILE_PATH, O_RDWR);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the file");
+    }
+    if (fcntl(fd, F_SETLEASE, F_RDLCK) == 0 || errno != EAGAIN) {
+        THROW_ERROR("read lease on a file opened for write should fail with EAGAIN");
+    }
+    close(fd);
+    unlink(FILE_PATH);
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_read_lease),
+    TEST_CASE(test_write_lease_downgrade),
+    TEST_CASE(test_conflicting_open_blocks),
+    TEST_CASE(test_conflicting_truncate_blocks),
+    TEST_CASE(test_lease_break_signal),
+    TEST_CASE(test_write_lease_with_other_opens),
+    TEST_CASE(test_read_lease_with_writer),
+};
+
+int main() {
+    if (set_sigio_handler() < 0) {
+        return -1;
+    }
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,161 @@
+#include <sys/mman.h>
+#include <sys/resource.h>
+#include <errno.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define PAGE_SIZE       4096
+#define NUM_PAGES       4
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static char *map_pages(size_t num_pages, int flags) {
+    char *buf = mmap(NULL, num_pages * PAGE_SIZE, PROT_READ | PROT_WRITE,
+                     MAP_PRIVATE | MAP_ANONYMOUS | flags, -1, 0);
+    if (buf == MAP_FAILED) {
+        return NULL;
+    }
+    memset(buf, 0xab, num_pages * PAGE_SIZE);
+    return buf;
+}
+
+// Try to reclaim a page, returning 1 if the page survives with its content
+static int page_survives_reclaim(char *page) {
+    int reclaimed = madvise(page, PAGE_SIZE, MADV_DONTNEED) == 0;
+    return !reclaimed && (unsigned char)page[0] == 0xab &&
+           (unsigned char)page[PAGE_SIZE - 1] == 0xab;
+}
+
+// ============================================================================
+// Test cases for mlock
+// ============================================================================
+
+static int test_mlock() {
+    char *buf = map_pages(NUM_PAGES, 0);
+    if (buf == NULL) {
+        THROW_ERROR("mmap failed");
+    }
+    if (mlock(buf, NUM_PAGES * PAGE_SIZE) < 0) {
+        THROW_ERROR("mlock failed");
+    }
+    for (int i = 0; i < NUM_PAGES; i++) {
+        if (!page_survives_reclaim(buf + i * PAGE_SIZE)) {
+            THROW_ERROR("a locked page should not be reclaimed");
+        }
+    }
+
+    // Unlock only the pages in the middle
+    if (munlock(buf + PAGE_SIZE, 2 * PAGE_SIZE) < 0) {
+        THROW_ERROR("munlock failed");
+    }
+    if (!page_survives_reclaim(buf) || !page_survives_reclaim(buf + 3 * PAGE_SIZE)) {
+        THROW_ERROR("the pages out of the munlock range should be kept locked");
+    }
+    if (madvise(buf + PAGE_SIZE, 2 * PAGE_SIZE, MADV_DONTNEED) < 0 || buf[PAGE_SIZE] != 0) {
+        THROW_ERROR("an unlocked page should be reclaimed");
+    }
+
+    munmap(buf, NUM_PAGES * PAGE_SIZE);
+    if (mlock(buf, PAGE_SIZE) == 0 || errno != ENOMEM) {
+        THROW_ERROR("mlock on an unmapped range should fail with ENOMEM");
+    }
+    return 0;
+}
+
+static int test_mlock_with_memlock_limit() {
+    struct rlimit old_rlim, rlim;
+    if (getrlimit(RLIMIT_MEMLOCK, &old_rlim) < 0) {
+        THROW_ERROR("failed to get RLIMIT_MEMLOCK");
+    }
+    char *buf = map_pages(NUM_PAGES, 0);
+    if (buf == NULL) {
+        THROW_ERROR("mmap failed");
+    }
+    rlim.rlim_cur = 2 * PAGE_SIZE;
+    rlim.rlim_max = old_rlim.rlim_max;
+    if (setrlimit(RLIMIT_MEMLOCK, &rlim) < 0) {
+        THROW_ERROR("failed to set RLIMIT_MEMLOCK");
+    }
+
+    int ret = 0;
+    if (mlock(buf, 2 * PAGE_SIZE) < 0) {
+        ret = -1;
+        printf("\t\tERROR: mlock within the limit should succeed\n");
+    } else if (mlock(buf + 2 * PAGE_SIZE, PAGE_SIZE) == 0 || errno != ENOMEM) {
+        ret = -1;
+        printf("\t\tERROR: mlock beyond the limit should fail with ENOMEM\n");
+    } else if (mlock(buf, 2 * PAGE_SIZE) < 0) {
+        ret = -1;
+        printf("\t\tERROR: locking the locked pages again should not count\n");
+    }
+
+    munlock(buf, NUM_PAGES * PAGE_SIZE);
+    munmap(buf, NUM_PAGES * PAGE_SIZE);
+    if (setrlimit(RLIMIT_MEMLOCK, &old_rlim) < 0) {
+        THROW_ERROR("failed to restore RLIMIT_MEMLOCK");
+    }
+    return ret;
+}
+
+static int test_mlockall() {
+    char *current_buf = map_pages(1, 0);
+    if (current_buf == NULL) {
+        THROW_ERROR("mmap failed");
+    }
+    if (mlockall(0) == 0 || errno != EINVAL) {
+        THROW_ERROR("mlockall with no flags should fail with EINVAL");
+    }
+    if (mlockall(MCL_CURRENT | MCL_FUTURE) < 0) {
+        THROW_ERROR("mlockall failed");
+    }
+    char *future_buf = map_pages(1, 0);
+    if (future_buf == NULL) {
+        THROW_ERROR("mmap failed");
+    }
+    if (!page_survives_reclaim(current_buf)) {
+        THROW_ERROR("the existing pages should be locked by MCL_CURRENT");
+    }
+    if (!page_survives_reclaim(future_buf)) {
+        THROW_ERROR("the new pages should be locked by MCL_FUTURE");
+    }
+
+    if (munlockall() < 0) {
+        THROW_ERROR("munlockall failed");
+    }
+    if (page_survives_reclaim(current_buf) || page_survives_reclaim(future_buf)) {
+        THROW_ERROR("the pages should be unlocked by munlockall");
+    }
+    munmap(current_buf, PAGE_SIZE);
+    munmap(future_buf, PAGE_SIZE);
+    return 0;
+}
+
+static int test_mmap_locked() {
+    char *buf = map_pages(1, MAP_LOCKED);
+    if (buf == NULL) {
+        THROW_ERROR("mmap failed");
+    }
+    if (!page_survives_reclaim(buf)) {
+        THROW_ERROR("the pages mapped with MAP_LOCKED should be locked");
+    }
+    munmap(buf, PAGE_SIZE);
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_mlock),
+    TEST_CASE(test_mlock_with_memlock_limit),
+    TEST_CASE(test_mlockall),
+    TEST_CASE(test_mmap_locked),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -7,6 +7,7 @@
 #include <assert.h>
 #include <string.h>
 #include <fcntl.h>
+#include <errno.h>
 #include "test.h"
 
 // ============================================================================
@@ -22,6 +23,10 @@
 
 #define MAX_MMAP_USED_MEMORY    (4 * MB)
 
+#ifndef MAP_FIXED_NOREPLACE
+#define MAP_FIXED_NOREPLACE     0x100000
+#endif
+
 // ============================================================================
 // Helper functions
 // ============================================================================
@@ -458,6 +463,70 @@ int test_fixed_mmap_with_non_page_aligned_addr() {
     return 0;
 }
 
+int test_fixed_noreplace_mmap_that_does_not_override_any_mmaping() {
+    size_t hint = HINT_BEGIN + (HINT_END - HINT_BEGIN) / 3;
+    hint = ALIGN_DOWN(hint, PAGE_SIZE);
+    size_t len = 2 * PAGE_SIZE;
+    int prot = PROT_READ | PROT_WRITE;
+    int flags = MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED_NOREPLACE;
+    void* addr = mmap((void*)hint, len, prot, flags, -1, 0);
+    if (addr != (void*)hint) {
+        THROW_ERROR("mmap with fixed address failed");
+    }
+
+    int ret = munmap(addr, len);
+    if (ret < 0) {
+        THROW_ERROR("munmap failed");
+    }
+    return 0;
+}
+
+int test_fixed_noreplace_mmap_that_overlaps_existing_mmaping() {
+    size_t parent_len = 4 * PAGE_SIZE;
+    int prot = PROT_READ | PROT_WRITE;
+    int flags = MAP_PRIVATE | MAP_ANONYMOUS;
+    void* parent_buf = mmap(NULL, parent_len, prot, flags, -1, 0);
+    if (parent_buf == MAP_FAILED) {
+        THROW_ERROR("mmap for parent failed");
+    }
+    int parent_val = 0xab;
+    memset(parent_buf, parent_val, parent_len);
+
+    // Both a range within the parent_buf and a range that overlaps its end
+    void* child_bufs[2] = {
+        (char*)parent_buf + PAGE_SIZE,
+        (char*)parent_buf + parent_len - PAGE_SIZE,
+    };
+    for (int i = 0; i < 2; i++) {
+        void* addr = mmap(child_bufs[i], 2 * PAGE_SIZE, prot,
+                          flags | MAP_FIXED_NOREPLACE, -1, 0);
+        if (addr != MAP_FAILED || errno != EEXIST) {
+            THROW_ERROR("fixed noreplace mmap over an existing mmap should fail with EEXIST");
+        }
+    }
+    if (check_bytes_in_buf(parent_buf, parent_len, parent_val) < 0) {
+        THROW_ERROR("the content of parent mmap memory is broken");
+    }
+
+    int ret = munmap(parent_buf, parent_len);
+    if (ret < 0) {
+        THROW_ERROR("munmap failed");
+    }
+    return 0;
+}
+
+int test_fixed_noreplace_mmap_with_non_page_aligned_addr() {
+    size_t hint = HINT_BEGIN + 123; // Not aligned!
+    size_t len = 1 * PAGE_SIZE;
+    int prot = PROT_READ | PROT_WRITE;
+    int flags = MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED_NOREPLACE;
+    void* addr = mmap((void*)hint, len, prot, flags, -1, 0);
+    if (addr != MAP_FAILED || errno != EINVAL) {
+        THROW_ERROR("fixed noreplace mmap with non-page aligned hint should fail with EINVAL");
+    }
+    return 0;
+}
+
 // ============================================================================
 // Test cases for munmap
 // ============================================================================
@@ -615,6 +684,138 @@ int test_munmap_with_non_page_aligned_len() {
     return 0;
 }
 
+// ============================================================================
+// Test cases for mincore
+// ============================================================================
+
+int test_mincore() {
+    size_t len = 4 * PAGE_SIZE;
+    int prot = PROT_READ | PROT_WRITE;
+    int flags = MAP_PRIVATE | MAP_ANONYMOUS;
+    char* buf = mmap(NULL, len, prot, flags, -1, 0);
+    if (buf == MAP_FAILED) {
+        THROW_ERROR("mmap failed");
+    }
+    memset(buf, 0xab, len);
+
+    unsigned char vec[4];
+    if (mincore(buf, len, vec) < 0) {
+        THROW_ERROR("mincore failed");
+    }
+    for (int i = 0; i < 4; i++) {
+        if ((vec[i] & 1) == 0) {
+            THROW_ERROR("the pages written should be resident");
+        }
+    }
+
+    // The dropped pages are non-resident until written again
+    if (madvise(buf + PAGE_SIZE, 2 * PAGE_SIZE, MADV_DONTNEED) < 0) {
+        THROW_ERROR("madvise failed");
+    }
+    if (check_bytes_in_buf(buf + PAGE_SIZE, 2 * PAGE_SIZE, 0) < 0) {
+        THROW_ERROR("the dropped pages should be zero-filled");
+    }
+    buf[2 * PAGE_SIZE] = 1;
+    if (mincore(buf, len, vec) < 0) {
+        THROW_ERROR("mincore failed");
+    }
+    if ((vec[0] & 1) != 1 || (vec[1] & 1) != 0 || (vec[2] & 1) != 1 || (vec[3] & 1) != 1) {
+        THROW_ERROR("incorrect residency after madvise");
+    }
+
+    int ret = munmap(buf, len);
+    if (ret < 0) {
+        THROW_ERROR("munmap failed");
+    }
+    return 0;
+}
+
+int test_mincore_of_untouched_pages() {
+    size_t len = 2 * PAGE_SIZE;
+    int prot = PROT_READ | PROT_WRITE;
+    int flags = MAP_PRIVATE | MAP_ANONYMOUS;
+    char* buf = mmap(NULL, len, prot, flags, -1, 0);
+    if (buf == MAP_FAILED) {
+        THROW_ERROR("mmap failed");
+    }
+
+    // The pages never written are not faulted in yet
+    unsigned char vec[2];
+    if (mincore(buf, len, vec) < 0) {
+        THROW_ERROR("mincore failed");
+    }
+    if ((vec[0] & 1) != 0 || (vec[1] & 1) != 0) {
+        THROW_ERROR("the untouched pages should be non-resident");
+    }
+    buf[PAGE_SIZE] = 1;
+    if (mincore(buf, len, vec) < 0) {
+        THROW_ERROR("mincore failed");
+    }
+    if ((vec[0] & 1) != 0 || (vec[1] & 1) != 1) {
+        THROW_ERROR("only the page written should be resident");
+    }
+
+    int ret = munmap(buf, len);
+    if (ret < 0) {
+        THROW_ERROR("munmap failed");
+    }
+    return 0;
+}
+
+int test_madvise_dontneed_on_file_mmap() {
+    const char* file_path = "/root/mmap_file.data";
+    int fd = open(file_path, O_CREAT | O_TRUNC | O_RDWR, 0644);
+    if (fd < 0) {
+        THROW_ERROR("file creation failed");
+    }
+    size_t len = 2 * PAGE_SIZE;
+    int byte_val = 0xab;
+    fill_file_with_repeated_bytes(fd, len, byte_val);
+
+    char* buf = mmap(NULL, len, PROT_READ | PROT_WRITE, MAP_PRIVATE, fd, 0);
+    close(fd);
+    if (buf == MAP_FAILED) {
+        THROW_ERROR("mmap failed");
+    }
+    // The contents of a file-backed mapping are not zero-filled
+    if (madvise(buf, len, MADV_DONTNEED) < 0) {
+        THROW_ERROR("madvise failed");
+    }
+    if (check_bytes_in_buf(buf, len, byte_val) < 0) {
+        THROW_ERROR("the file-backed pages should keep the contents of the file");
+    }
+
+    int ret = munmap(buf, len);
+    if (ret < 0) {
+        THROW_ERROR("munmap failed");
+    }
+    unlink(file_path);
+    return 0;
+}
+
+int test_mincore_with_invalid_range() {
+    size_t len = 2 * PAGE_SIZE;
+    int prot = PROT_READ | PROT_WRITE;
+    int flags = MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED;
+    char* buf = mmap((void*)HINT_BEGIN, len, prot, flags, -1, 0);
+    if (buf != (void*)HINT_BEGIN) {
+        THROW_ERROR("mmap failed");
+    }
+
+    unsigned char vec[3];
+    if (mincore(buf + 123, PAGE_SIZE, vec) == 0 || errno != EINVAL) {
+        THROW_ERROR("mincore with an unaligned addr should fail with EINVAL");
+    }
+    if (mincore(buf, 3 * PAGE_SIZE, vec) == 0 || errno != ENOMEM) {
+        THROW_ERROR("mincore beyond the mmap region should fail with ENOMEM");
+    }
+    munmap(buf, len);
+    if (mincore(buf, PAGE_SIZE, vec) == 0 || errno != ENOMEM) {
+        THROW_ERROR("mincore on an unmapped range should fail with ENOMEM");
+    }
+    return 0;
+}
+
 // ============================================================================
 // Test suite main
 // ============================================================================
@@ -633,6 +834,9 @@ static test_case_t test_cases[] = {
     TEST_CASE(test_fixed_mmap_that_does_not_override_any_mmaping),
     TEST_CASE(test_fixed_mmap_that_overrides_existing_mmaping),
     TEST_CASE(test_fixed_mmap_with_non_page_aligned_addr),
+    TEST_CASE(test_fixed_noreplace_mmap_that_does_not_override_any_mmaping),
+    TEST_CASE(test_fixed_noreplace_mmap_that_overlaps_existing_mmaping),
+    TEST_CASE(test_fixed_noreplace_mmap_with_non_page_aligned_addr),
     TEST_CASE(test_munmap_whose_range_is_a_subset_of_a_mmap_region),
     TEST_CASE(test_munmap_whose_range_is_a_superset_of_a_mmap_region),
     TEST_CASE(test_munmap_whose_range_intersects_with_a_mmap_region),
@@ -640,7 +844,11 @@ static test_case_t test_cases[] = {
     TEST_CASE(test_munmap_whose_range_intersects_with_multiple_mmap_regions),
     TEST_CASE(test_munmap_with_null_addr),
     TEST_CASE(test_munmap_with_zero_len),
-    TEST_CASE(test_munmap_with_non_page_aligned_len)
+    TEST_CASE(test_munmap_with_non_page_aligned_len),
+    TEST_CASE(test_mincore),
+    TEST_CASE(test_mincore_of_untouched_pages),
+    TEST_CASE(test_madvise_dontneed_on_file_mmap),
+    TEST_CASE(test_mincore_with_invalid_range)
 };
 
 int main() {
@@ -0,0 +1,163 @@
+#define _GNU_SOURCE
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <sys/types.h>
+#include <sys/wait.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <sched.h>
+#include <spawn.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define MNT_DIR             "/root/test_mount_ns_dir"
+#define MNT_FILE_PATH       MNT_DIR "/test_mount_ns_file.txt"
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int create_mnt_dir() {
+    if (mkdir(MNT_DIR, 0755) < 0) {
+        THROW_ERROR("failed to create the mount point");
+    }
+    return 0;
+}
+
+// Mount a RamFS and create a file on it
+static int mount_ramfs_with_file() {
+    if (mount("none", MNT_DIR, "ramfs", 0, NULL) < 0) {
+        THROW_ERROR("failed to mount a ramfs");
+    }
+    int fd = open(MNT_FILE_PATH, O_WRONLY | O_CREAT | O_TRUNC, 0644);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file on the ramfs");
+    }
+    close(fd);
+    return 0;
+}
+
+static int file_exists(const char *path) {
+    struct stat stat_buf;
+    return stat(path, &stat_buf) == 0;
+}
+
+// The child unshares its mount namespace and mounts a RamFS
+static int run_child() {
+    if (unshare(CLONE_NEWNS) < 0) {
+        THROW_ERROR("failed to unshare the mount namespace");
+    }
+    if (mount_ramfs_with_file() < 0) {
+        return -1;
+    }
+    if (!file_exists(MNT_FILE_PATH)) {
+        THROW_ERROR("the file on the ramfs is not seen by the child");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for mount namespaces
+// ============================================================================
+
+static int test_unshare_in_child() {
+    if (create_mnt_dir() < 0) {
+        return -1;
+    }
+
+    int child_pid;
+    const char *child_argv[3] = { "mount_ns", "child", NULL };
+    if (posix_spawn(&child_pid, "/bin/mount_ns", NULL, NULL, (char *const *)child_argv,
+                    NULL) < 0) {
+        THROW_ERROR("failed to spawn a child process");
+    }
+    int status;
+    if (wait4(child_pid, &status, 0, NULL) < 0) {
+        THROW_ERROR("failed to wait4 the child process");
+    }
+    if (!WIFEXITED(status) || WEXITSTATUS(status) != 0) {
+        THROW_ERROR("the child process failed");
+    }
+
+    // The mount in the child is not seen by the parent
+    if (file_exists(MNT_FILE_PATH)) {
+        THROW_ERROR("the mount in the child should not be seen by the parent");
+    }
+    rmdir(MNT_DIR);
+    return 0;
+}
+
+static int test_setns() {
+    if (create_mnt_dir() < 0) {
+        return -1;
+    }
+    int ns_fd = open("/proc/self/ns/mnt", O_RDONLY);
+    if (ns_fd < 0) {
+        THROW_ERROR("failed to open the mount namespace");
+    }
+
+    if (unshare(CLONE_NEWNS) < 0) {
+        close(ns_fd);
+        THROW_ERROR("failed to unshare the mount namespace");
+    }
+    if (mount_ramfs_with_file() < 0) {
+        close(ns_fd);
+        return -1;
+    }
+    if (!file_exists(MNT_FILE_PATH)) {
+        close(ns_fd);
+        THROW_ERROR("the file on the ramfs is not seen in the new namespace");
+    }
+
+    // Join the original namespace again, where there is no mount
+    if (setns(ns_fd, CLONE_NEWNS) < 0) {
+        close(ns_fd);
+        THROW_ERROR("failed to join the original mount namespace");
+    }
+    close(ns_fd);
+    if (file_exists(MNT_FILE_PATH)) {
+        THROW_ERROR("the mount should not be seen in the original namespace");
+    }
+    rmdir(MNT_DIR);
+    return 0;
+}
+
+static int test_unshare_with_unsupported_flags() {
+    if (unshare(CLONE_NEWPID) == 0 || errno != EINVAL) {
+        THROW_ERROR("unshare with CLONE_NEWPID should fail with EINVAL");
+    }
+    return 0;
+}
+
+static int test_setns_with_invalid_fd() {
+    int fd = open("/dev/null", O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open /dev/null");
+    }
+    int ret = setns(fd, CLONE_NEWNS);
+    int saved_errno = errno;
+    close(fd);
+    if (ret == 0 || saved_errno != EINVAL) {
+        THROW_ERROR("setns with a non-namespace fd should fail with EINVAL");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_unshare_in_child),
+    TEST_CASE(test_setns),
+    TEST_CASE(test_unshare_with_unsupported_flags),
+    TEST_CASE(test_setns_with_invalid_fd),
+};
+
+int main(int argc, const char *argv[]) {
+    if (argc > 1 && strcmp(argv[1], "child") == 0) {
+        return run_child() < 0 ? -1 : 0;
+    }
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,197 @@
+#include <sys/mman.h>
+#include <errno.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define PAGE_SIZE       4096
+#define NUM_PAGES       4
+
+// Note: W^X is enforced by the "enforce_wx" option in the Occlum.json of the
+// instance of the test
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static char *map_rw_pages(size_t num_pages) {
+    char *buf = mmap(NULL, num_pages * PAGE_SIZE, PROT_READ | PROT_WRITE,
+                     MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    if (buf == MAP_FAILED) {
+        return NULL;
+    }
+    return buf;
+}
+
+// ============================================================================
+// Test cases for mprotect
+// ============================================================================
+
+static int test_mmap_wx() {
+    char *buf = mmap(NULL, PAGE_SIZE, PROT_READ | PROT_WRITE | PROT_EXEC,
+                     MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    if (buf != MAP_FAILED || errno != EACCES) {
+        THROW_ERROR("mmap with PROT_WRITE | PROT_EXEC should fail with EACCES");
+    }
+    return 0;
+}
+
+static int test_mprotect_wx() {
+    char *buf = map_rw_pages(1);
+    if (buf == NULL) {
+        THROW_ERROR("mmap failed");
+    }
+    if (mprotect(buf, PAGE_SIZE, PROT_READ | PROT_WRITE | PROT_EXEC) == 0 || errno != EACCES) {
+        THROW_ERROR("mprotect with PROT_WRITE | PROT_EXEC should fail with EACCES");
+    }
+    // A writable page cannot be made executable directly
+    if (mprotect(buf, PAGE_SIZE, PROT_READ | PROT_EXEC) == 0 || errno != EACCES) {
+        THROW_ERROR("mprotect of a writable page to PROT_EXEC should fail with EACCES");
+    }
+    if (mprotect(buf, PAGE_SIZE, PROT_READ) < 0) {
+        THROW_ERROR("mprotect to PROT_READ failed");
+    }
+    if (mprotect(buf, PAGE_SIZE, PROT_READ | PROT_EXEC) < 0) {
+        THROW_ERROR("mprotect of a read-only page to PROT_EXEC failed");
+    }
+    munmap(buf, PAGE_SIZE);
+    return 0;
+}
+
+static int test_mprotect_partial_range() {
+    char *buf = map_rw_pages(NUM_PAGES);
+    if (buf == NULL) {
+        THROW_ERROR("mmap failed");
+    }
+    // Split the mapping into three ranges of RW, R and RW
+    if (mprotect(buf + PAGE_SIZE, 2 * PAGE_SIZE, PROT_READ) < 0) {
+        THROW_ERROR("mprotect of the middle pages failed");
+    }
+    if (mprotect(buf + PAGE_SIZE, 2 * PAGE_SIZE, PROT_READ | PROT_EXEC) < 0) {
+        THROW_ERROR("mprotect of the read-only middle pages to PROT_EXEC failed");
+    }
+    if (mprotect(buf, PAGE_SIZE, PROT_READ | PROT_EXEC) == 0 || errno != EACCES) {
+        THROW_ERROR("the first page should be kept writable");
+    }
+    if (mprotect(buf + 2 * PAGE_SIZE, 2 * PAGE_SIZE, PROT_READ | PROT_EXEC) == 0
+            || errno != EACCES) {
+        THROW_ERROR("the last page should be kept writable");
+    }
+
+    // Merge the ranges back into one
+    if (mprotect(buf, NUM_PAGES * PAGE_SIZE, PROT_READ) < 0) {
+        THROW_ERROR("mprotect of the whole range failed");
+    }
+    if (mprotect(buf, NUM_PAGES * PAGE_SIZE, PROT_READ | PROT_EXEC) < 0) {
+        THROW_ERROR("mprotect of the whole read-only range to PROT_EXEC failed");
+    }
+    munmap(buf, NUM_PAGES * PAGE_SIZE);
+    return 0;
+}
+
+static int test_mprotect_none() {
+    char *buf = map_rw_pages(NUM_PAGES);
+    if (buf == NULL) {
+        THROW_ERROR("mmap failed");
+    }
+    memset(buf, 'a', NUM_PAGES * PAGE_SIZE);
+    if (mprotect(buf, NUM_PAGES * PAGE_SIZE, PROT_NONE) < 0) {
+        THROW_ERROR("mprotect to PROT_NONE failed");
+    }
+    if (mprotect(buf, NUM_PAGES * PAGE_SIZE, PROT_READ | PROT_EXEC) < 0) {
+        THROW_ERROR("mprotect of an inaccessible range to PROT_EXEC failed");
+    }
+    // The pages are accessible again, with the content kept
+    for (size_t i = 0; i < NUM_PAGES * PAGE_SIZE; i++) {
+        if (buf[i] != 'a') {
+            THROW_ERROR("the content of the pages should be kept");
+        }
+    }
+    munmap(buf, NUM_PAGES * PAGE_SIZE);
+    return 0;
+}
+
+static int test_mmap_fixed_over_none() {
+    char *buf = mmap(NULL, NUM_PAGES * PAGE_SIZE, PROT_NONE, MAP_PRIVATE | MAP_ANONYMOUS,
+                     -1, 0);
+    if (buf == MAP_FAILED) {
+        THROW_ERROR("mmap with PROT_NONE failed");
+    }
+    // The inaccessible pages are replaced by the zeroed pages
+    char *fixed_buf = mmap(buf, PAGE_SIZE, PROT_READ | PROT_WRITE,
+                           MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED, -1, 0);
+    if (fixed_buf != buf) {
+        THROW_ERROR("mmap with MAP_FIXED over the PROT_NONE pages failed");
+    }
+    for (size_t i = 0; i < PAGE_SIZE; i++) {
+        if (fixed_buf[i] != 0) {
+            THROW_ERROR("the pages mapped by MAP_FIXED should be zeroed");
+        }
+    }
+    if (munmap(buf, NUM_PAGES * PAGE_SIZE) < 0) {
+        THROW_ERROR("munmap of the PROT_NONE pages failed");
+    }
+    return 0;
+}
+
+static int test_mprotect_with_invalid_range() {
+    char *buf = map_rw_pages(1);
+    if (buf == NULL) {
+        THROW_ERROR("mmap failed");
+    }
+    if (mprotect(buf + 1, PAGE_SIZE, PROT_READ) == 0 || errno != EINVAL) {
+        THROW_ERROR("mprotect with an unaligned address should fail with EINVAL");
+    }
+    munmap(buf, PAGE_SIZE);
+    if (mprotect(buf, PAGE_SIZE, PROT_READ) == 0 || errno != ENOMEM) {
+        THROW_ERROR("mprotect on an unmapped range should fail with ENOMEM");
+    }
+    return 0;
+}
+
+static int test_access_by_syscalls() {
+    int pipe_fds[2];
+    if (pipe(pipe_fds) < 0) {
+        THROW_ERROR("failed to create a pipe");
+    }
+    char *buf = map_rw_pages(2);
+    if (buf == NULL) {
+        THROW_ERROR("mmap failed");
+    }
+    if (mprotect(buf, PAGE_SIZE, PROT_NONE) < 0 ||
+            mprotect(buf + PAGE_SIZE, PAGE_SIZE, PROT_READ) < 0) {
+        THROW_ERROR("mprotect failed");
+    }
+    // The buffers that cross into the pages are checked as a whole
+    if (write(pipe_fds[1], buf + PAGE_SIZE - 1, 2) >= 0 || errno != EFAULT) {
+        THROW_ERROR("a write from the PROT_NONE pages should fail with EFAULT");
+    }
+    if (write(pipe_fds[1], buf + PAGE_SIZE, 2) != 2) {
+        THROW_ERROR("a write from the PROT_READ pages should succeed");
+    }
+    if (read(pipe_fds[0], buf + PAGE_SIZE, 2) >= 0 || errno != EFAULT) {
+        THROW_ERROR("a read into the PROT_READ pages should fail with EFAULT");
+    }
+    munmap(buf, 2 * PAGE_SIZE);
+    close(pipe_fds[0]);
+    close(pipe_fds[1]);
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_mmap_wx),
+    TEST_CASE(test_mprotect_wx),
+    TEST_CASE(test_mprotect_partial_range),
+    TEST_CASE(test_mprotect_none),
+    TEST_CASE(test_mmap_fixed_over_none),
+    TEST_CASE(test_mprotect_with_invalid_range),
+    TEST_CASE(test_access_by_syscalls),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
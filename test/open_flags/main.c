@@ -0,0 +1,216 @@
+#define _GNU_SOURCE
+#include <sys/types.h>
+#include <sys/stat.h>
+#include <sys/syscall.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <unistd.h>
+#include <stdint.h>
+#include "test.h"
+
+// The symlinks are made on host by the Makefile, as there is no symlink
+// syscall: open_flags_link -> open_flags_target and open_flags_dir_link -> .
+#define TARGET_PATH     "/host/open_flags_target"
+#define LINK_PATH       "/host/open_flags_link"
+#define DIR_LINK_PATH   "/host/open_flags_dir_link"
+#define DIR_PATH        "/root/test_open_flags_dir"
+
+struct statx_timestamp_t {
+    int64_t tv_sec;
+    uint32_t tv_nsec;
+    int32_t reserved;
+};
+
+struct statx_t {
+    uint32_t stx_mask;
+    uint32_t stx_blksize;
+    uint64_t stx_attributes;
+    uint32_t stx_nlink;
+    uint32_t stx_uid;
+    uint32_t stx_gid;
+    uint16_t stx_mode;
+    uint16_t spare0;
+    uint64_t stx_ino;
+    uint64_t stx_size;
+    uint64_t stx_blocks;
+    uint64_t stx_attributes_mask;
+    struct statx_timestamp_t stx_atime;
+    struct statx_timestamp_t stx_btime;
+    struct statx_timestamp_t stx_ctime;
+    struct statx_timestamp_t stx_mtime;
+    uint32_t stx_rdev_major;
+    uint32_t stx_rdev_minor;
+    uint32_t stx_dev_major;
+    uint32_t stx_dev_minor;
+    uint64_t spare2[14];
+};
+
+#ifndef __NR_statx
+#define __NR_statx      332
+#endif
+#define STATX_BASIC_STATS 0x7ff
+
+// ============================================================================
+// Helper function
+// ============================================================================
+
+static int create_file(const char *file_path) {
+    int fd = open(file_path, O_WRONLY | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+    close(fd);
+    return 0;
+}
+
+// ============================================================================
+// Test cases for open flags
+// ============================================================================
+
+static int test_open_symlink_follow() {
+    struct stat target_stat, stat_buf;
+    int fd;
+
+    if (create_file(TARGET_PATH) < 0) {
+        return -1;
+    }
+    if (stat(TARGET_PATH, &target_stat) < 0) {
+        THROW_ERROR("failed to stat the target");
+    }
+    fd = open(LINK_PATH, O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the symlink");
+    }
+    if (fstat(fd, &stat_buf) < 0) {
+        close(fd);
+        THROW_ERROR("failed to fstat the opened file");
+    }
+    close(fd);
+    if (!S_ISREG(stat_buf.st_mode) || stat_buf.st_ino != target_stat.st_ino) {
+        THROW_ERROR("the symlink is not followed");
+    }
+    return 0;
+}
+
+static int test_open_symlink_with_nofollow() {
+    if (create_file(TARGET_PATH) < 0) {
+        return -1;
+    }
+    if (open(LINK_PATH, O_RDONLY | O_NOFOLLOW) >= 0 || errno != ELOOP) {
+        THROW_ERROR("open a symlink with O_NOFOLLOW should fail with ELOOP");
+    }
+    if (open(LINK_PATH, O_WRONLY | O_CREAT | O_NOFOLLOW, 00666) >= 0 || errno != ELOOP) {
+        THROW_ERROR("create a symlink with O_NOFOLLOW should fail with ELOOP");
+    }
+    return 0;
+}
+
+static int test_open_symlink_in_middle_with_nofollow() {
+    int fd;
+
+    if (create_file(TARGET_PATH) < 0) {
+        return -1;
+    }
+    // Only the final component is not followed
+    fd = open(DIR_LINK_PATH "/open_flags_target", O_RDONLY | O_NOFOLLOW);
+    if (fd < 0) {
+        THROW_ERROR("failed to open a file via a symlink in the middle with O_NOFOLLOW");
+    }
+    close(fd);
+    return 0;
+}
+
+static int test_open_symlink_with_path_and_nofollow() {
+    struct stat stat_buf;
+    int fd;
+
+    fd = open(LINK_PATH, O_PATH | O_NOFOLLOW);
+    if (fd < 0) {
+        THROW_ERROR("failed to open a symlink with O_PATH | O_NOFOLLOW");
+    }
+    if (fstat(fd, &stat_buf) < 0) {
+        close(fd);
+        THROW_ERROR("failed to fstat the symlink");
+    }
+    close(fd);
+    if (!S_ISLNK(stat_buf.st_mode)) {
+        THROW_ERROR("the fd does not refer to the symlink");
+    }
+    return 0;
+}
+
+static int test_statx_symlink() {
+    struct statx_t stx;
+
+    if (create_file(TARGET_PATH) < 0) {
+        return -1;
+    }
+    if (syscall(__NR_statx, AT_FDCWD, LINK_PATH, 0, STATX_BASIC_STATS, &stx) < 0) {
+        THROW_ERROR("failed to statx the symlink");
+    }
+    if (!S_ISREG(stx.stx_mode)) {
+        THROW_ERROR("the symlink should be followed without AT_SYMLINK_NOFOLLOW");
+    }
+    if (syscall(__NR_statx, AT_FDCWD, LINK_PATH, AT_SYMLINK_NOFOLLOW, STATX_BASIC_STATS,
+                &stx) < 0) {
+        THROW_ERROR("failed to statx the symlink with AT_SYMLINK_NOFOLLOW");
+    }
+    if (!S_ISLNK(stx.stx_mode)) {
+        THROW_ERROR("the symlink should not be followed with AT_SYMLINK_NOFOLLOW");
+    }
+    return 0;
+}
+
+static int test_open_dir_with_directory() {
+    int fd;
+
+    if (mkdir(DIR_PATH, 0755) < 0) {
+        THROW_ERROR("failed to create a dir");
+    }
+    fd = open(DIR_PATH, O_RDONLY | O_DIRECTORY);
+    rmdir(DIR_PATH);
+    if (fd < 0) {
+        THROW_ERROR("failed to open a dir with O_DIRECTORY");
+    }
+    close(fd);
+
+    // The symlink to a directory is followed
+    fd = open(DIR_LINK_PATH, O_RDONLY | O_DIRECTORY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open a symlink to a dir with O_DIRECTORY");
+    }
+    close(fd);
+    return 0;
+}
+
+static int test_open_file_with_directory() {
+    if (create_file(TARGET_PATH) < 0) {
+        return -1;
+    }
+    if (open(TARGET_PATH, O_RDONLY | O_DIRECTORY) >= 0 || errno != ENOTDIR) {
+        THROW_ERROR("open a file with O_DIRECTORY should fail with ENOTDIR");
+    }
+    if (open(LINK_PATH, O_RDONLY | O_DIRECTORY) >= 0 || errno != ENOTDIR) {
+        THROW_ERROR("open a symlink to a file with O_DIRECTORY should fail with ENOTDIR");
+    }
+    unlink(TARGET_PATH);
+    return 0;
+}
+
+// ============================================================================
+// Test suite main
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_open_symlink_follow),
+    TEST_CASE(test_open_symlink_with_nofollow),
+    TEST_CASE(test_open_symlink_in_middle_with_nofollow),
+    TEST_CASE(test_open_symlink_with_path_and_nofollow),
+    TEST_CASE(test_statx_symlink),
+    TEST_CASE(test_open_dir_with_directory),
+    TEST_CASE(test_open_file_with_directory),
+};
+
+int main(int argc, const char *argv[]) {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -1,6 +1,11 @@
 #include <sys/syscall.h>
 #include <sys/wait.h>
+#include <errno.h>
 #include <fcntl.h>
+#include <limits.h>
+#include <poll.h>
+#include <pthread.h>
+#include <signal.h>
 #include <unistd.h>
 #include <stdlib.h>
 #include <stdio.h>
@@ -17,6 +22,65 @@ static void free_pipe(int *pipe) {
     close(pipe[1]);
 }
 
+// Fill the pipe with non-blocking writes until it is full
+static int fill_pipe(int pipe_wr_fd) {
+    static char buf[64 * 1024];
+    while (1) {
+        ssize_t len = write(pipe_wr_fd, buf, sizeof(buf));
+        if (len < 0) {
+            if (errno == EAGAIN) {
+                return 0;
+            }
+            THROW_ERROR("failed to write to the pipe");
+        }
+    }
+}
+
+static int drain_pipe(int pipe_rd_fd, size_t len) {
+    char buf[PIPE_BUF];
+    while (len > 0) {
+        size_t read_len = len < sizeof(buf) ? len : sizeof(buf);
+        if (read(pipe_rd_fd, buf, read_len) != read_len) {
+            THROW_ERROR("failed to read from the pipe");
+        }
+        len -= read_len;
+    }
+    return 0;
+}
+
+static short poll_pipe_writer(int pipe_wr_fd) {
+    struct pollfd pollfd = { .fd = pipe_wr_fd, .events = POLLOUT, .revents = 0 };
+    if (poll(&pollfd, 1, 0) < 0) {
+        return -1;
+    }
+    return pollfd.revents;
+}
+
+static volatile int signal_count;
+
+static void handle_signal(int signum) {
+    signal_count++;
+}
+
+// The handler is set without SA_RESTART, so that the interrupted syscalls
+// are not restarted
+static int set_signal_handler(int signum) {
+    struct sigaction sa = { 0 };
+    sa.sa_handler = handle_signal;
+    if (sigaction(signum, &sa, NULL) < 0) {
+        THROW_ERROR("failed to set the signal handler");
+    }
+    signal_count = 0;
+    return 0;
+}
+
+static void *signal_after_sleep(void *arg) {
+    pid_t tid = *(pid_t *)arg;
+    usleep(100 * 1000);
+    syscall(SYS_tkill, tid, SIGUSR1);
+    return NULL;
+}
+
 // ============================================================================
 // Test cases
 // ============================================================================
@@ -113,6 +177,276 @@ int test_read_write() {
     return 0;
 }
 
+int test_pollout_threshold() {
+    int pipe_fds[2];
+    if (pipe2(pipe_fds, O_NONBLOCK) < 0) {
+        THROW_ERROR("failed to create a pipe");
+    }
+    int pipe_rd_fd = pipe_fds[0];
+    int pipe_wr_fd = pipe_fds[1];
+
+    if (poll_pipe_writer(pipe_wr_fd) != POLLOUT) {
+        free_pipe(pipe_fds);
+        THROW_ERROR("an empty pipe should be writable");
+    }
+    if (fill_pipe(pipe_wr_fd) < 0) {
+        free_pipe(pipe_fds);
+        return -1;
+    }
+    if (poll_pipe_writer(pipe_wr_fd) != 0) {
+        free_pipe(pipe_fds);
+        THROW_ERROR("a full pipe should not be writable");
+    }
+
+    // A pipe is writable only if there is room for PIPE_BUF bytes
+    if (drain_pipe(pipe_rd_fd, PIPE_BUF - 1) < 0) {
+        free_pipe(pipe_fds);
+        return -1;
+    }
+    if (poll_pipe_writer(pipe_wr_fd) != 0) {
+        free_pipe(pipe_fds);
+        THROW_ERROR("a pipe with less than PIPE_BUF bytes of room should not be writable");
+    }
+    char buf[PIPE_BUF] = {0};
+    if (write(pipe_wr_fd, buf, sizeof(buf)) >= 0 || errno != EAGAIN) {
+        free_pipe(pipe_fds);
+        THROW_ERROR("a write of PIPE_BUF bytes should not be done partially");
+    }
+    if (drain_pipe(pipe_rd_fd, 1) < 0) {
+        free_pipe(pipe_fds);
+        return -1;
+    }
+    if (poll_pipe_writer(pipe_wr_fd) != POLLOUT) {
+        free_pipe(pipe_fds);
+        THROW_ERROR("a pipe with PIPE_BUF bytes of room should be writable");
+    }
+    if (write(pipe_wr_fd, buf, sizeof(buf)) != sizeof(buf)) {
+        free_pipe(pipe_fds);
+        THROW_ERROR("a write of PIPE_BUF bytes should be done all at once");
+    }
+
+    free_pipe(pipe_fds);
+    return 0;
+}
+
+int test_write_after_readers_closed() {
+    int pipe_fds[2];
+    if (pipe(pipe_fds) < 0) {
+        THROW_ERROR("failed to create a pipe");
+    }
+    close(pipe_fds[0]);
+
+    if (set_signal_handler(SIGPIPE) < 0) {
+        close(pipe_fds[1]);
+        return -1;
+    }
+    char buf[] = "Hello";
+    if (write(pipe_fds[1], buf, sizeof(buf)) >= 0 || errno != EPIPE) {
+        close(pipe_fds[1]);
+        THROW_ERROR("a write to a pipe without readers should fail with EPIPE");
+    }
+    signal(SIGPIPE, SIG_DFL);
+    if (signal_count != 1) {
+        close(pipe_fds[1]);
+        THROW_ERROR("a write to a pipe without readers should raise SIGPIPE");
+    }
+    if (!(poll_pipe_writer(pipe_fds[1]) & POLLERR)) {
+        close(pipe_fds[1]);
+        THROW_ERROR("a pipe without readers should be polled with POLLERR");
+    }
+
+    close(pipe_fds[1]);
+    return 0;
+}
+
+int test_poll_after_writers_closed() {
+    int pipe_fds[2];
+    if (pipe(pipe_fds) < 0) {
+        THROW_ERROR("failed to create a pipe");
+    }
+    char buf[] = "Hello";
+    if (write(pipe_fds[1], buf, sizeof(buf)) != sizeof(buf)) {
+        free_pipe(pipe_fds);
+        THROW_ERROR("failed to write to the pipe");
+    }
+    close(pipe_fds[1]);
+
+    // The data left is still readable, along with the hang-up
+    struct pollfd pollfd = { .fd = pipe_fds[0], .events = POLLIN, .revents = 0 };
+    if (poll(&pollfd, 1, -1) != 1 || pollfd.revents != (POLLIN | POLLHUP)) {
+        close(pipe_fds[0]);
+        THROW_ERROR("a pipe with data but no writers should be polled with POLLIN | POLLHUP");
+    }
+    if (read(pipe_fds[0], buf, sizeof(buf)) != sizeof(buf)) {
+        close(pipe_fds[0]);
+        THROW_ERROR("failed to read from the pipe");
+    }
+    // A finished pipe does not block the poll without a timeout
+    pollfd.revents = 0;
+    if (poll(&pollfd, 1, -1) != 1 || pollfd.revents != POLLHUP) {
+        close(pipe_fds[0]);
+        THROW_ERROR("an empty pipe without writers should be polled with POLLHUP");
+    }
+
+    close(pipe_fds[0]);
+    return 0;
+}
+
+static void *write_after_sleep(void *arg) {
+    int pipe_wr_fd = *(int *)arg;
+    usleep(100 * 1000);
+    char buf[] = "Hello";
+    write(pipe_wr_fd, buf, sizeof(buf));
+    return NULL;
+}
+
+int test_poll_woken_by_write() {
+    int pipe_fds[2];
+    if (pipe(pipe_fds) < 0) {
+        THROW_ERROR("failed to create a pipe");
+    }
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, write_after_sleep, &pipe_fds[1]) != 0) {
+        free_pipe(pipe_fds);
+        THROW_ERROR("failed to create a thread");
+    }
+
+    // The poll blocks until the write of the other thread, which is not blocked
+    // by the poll
+    struct pollfd pollfd = { .fd = pipe_fds[0], .events = POLLIN, .revents = 0 };
+    int ret = poll(&pollfd, 1, 10 * 1000);
+    pthread_join(thread, NULL);
+    if (ret != 1 || pollfd.revents != POLLIN) {
+        free_pipe(pipe_fds);
+        THROW_ERROR("the poll should be woken by the write");
+    }
+
+    free_pipe(pipe_fds);
+    return 0;
+}
+
+static void *drain_after_sleep(void *arg) {
+    int pipe_rd_fd = *(int *)arg;
+    usleep(100 * 1000);
+    drain_pipe(pipe_rd_fd, PIPE_BUF);
+    return NULL;
+}
+
+int test_blocking_write_woken_by_read() {
+    int pipe_fds[2];
+    if (pipe2(pipe_fds, O_NONBLOCK) < 0) {
+        THROW_ERROR("failed to create a pipe");
+    }
+    if (fill_pipe(pipe_fds[1]) < 0 || fcntl(pipe_fds[1], F_SETFL, 0) < 0) {
+        free_pipe(pipe_fds);
+        THROW_ERROR("failed to fill the pipe");
+    }
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, drain_after_sleep, &pipe_fds[0]) != 0) {
+        free_pipe(pipe_fds);
+        THROW_ERROR("failed to create a thread");
+    }
+
+    // The write blocks until the read of the other thread, which is not
+    // blocked by the write
+    char buf[PIPE_BUF] = { 0 };
+    ssize_t len = write(pipe_fds[1], buf, sizeof(buf));
+    pthread_join(thread, NULL);
+    free_pipe(pipe_fds);
+    if (len != sizeof(buf)) {
+        THROW_ERROR("the blocking write should be woken by the read");
+    }
+    return 0;
+}
+
+struct blocked_write {
+    int fd;
+    ssize_t ret;
+    int err;
+};
+
+static void *write_blocked(void *arg) {
+    struct blocked_write *op = (struct blocked_write *)arg;
+    char buf[PIPE_BUF] = { 0 };
+    op->ret = write(op->fd, buf, sizeof(buf));
+    op->err = errno;
+    return NULL;
+}
+
+int test_blocking_write_interrupted_by_close() {
+    int pipe_fds[2];
+    if (pipe2(pipe_fds, O_NONBLOCK) < 0) {
+        THROW_ERROR("failed to create a pipe");
+    }
+    if (fill_pipe(pipe_fds[1]) < 0 || fcntl(pipe_fds[1], F_SETFL, 0) < 0) {
+        free_pipe(pipe_fds);
+        THROW_ERROR("failed to fill the pipe");
+    }
+    struct blocked_write op = { .fd = pipe_fds[1], .ret = 0, .err = 0 };
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, write_blocked, &op) != 0) {
+        free_pipe(pipe_fds);
+        THROW_ERROR("failed to create a thread");
+    }
+
+    // Closing the last fd of the write end interrupts the blocked write
+    usleep(100 * 1000);
+    close(pipe_fds[1]);
+    pthread_join(thread, NULL);
+    close(pipe_fds[0]);
+    if (op.ret >= 0 || op.err != EBADF) {
+        THROW_ERROR("the blocked write should fail with EBADF");
+    }
+    return 0;
+}
+
+int test_blocking_write_interrupted_by_signal() {
+    int pipe_fds[2];
+    if (pipe2(pipe_fds, O_NONBLOCK) < 0) {
+        THROW_ERROR("failed to create a pipe");
+    }
+    if (fill_pipe(pipe_fds[1]) < 0 || fcntl(pipe_fds[1], F_SETFL, 0) < 0) {
+        free_pipe(pipe_fds);
+        THROW_ERROR("failed to fill the pipe");
+    }
+    if (set_signal_handler(SIGUSR1) < 0) {
+        free_pipe(pipe_fds);
+        return -1;
+    }
+    pid_t tid = syscall(SYS_gettid);
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, signal_after_sleep, &tid) != 0) {
+        free_pipe(pipe_fds);
+        THROW_ERROR("failed to create a thread");
+    }
+
+    // The blocked write fails with EINTR as nothing is written
+    char buf[PIPE_BUF] = { 0 };
+    ssize_t len = write(pipe_fds[1], buf, sizeof(buf));
+    int err = errno;
+    pthread_join(thread, NULL);
+    if (len >= 0 || err != EINTR || signal_count != 1) {
+        free_pipe(pipe_fds);
+        THROW_ERROR("the blocked write should fail with EINTR");
+    }
+
+    // A write interrupted after some bytes are written is a short write
+    static char large_buf[4 * PIPE_BUF];
+    if (drain_pipe(pipe_fds[0], PIPE_BUF) < 0 ||
+            pthread_create(&thread, NULL, signal_after_sleep, &tid) != 0) {
+        free_pipe(pipe_fds);
+        THROW_ERROR("failed to drain the pipe or create a thread");
+    }
+    len = write(pipe_fds[1], large_buf, sizeof(large_buf));
+    pthread_join(thread, NULL);
+    signal(SIGUSR1, SIG_DFL);
+    free_pipe(pipe_fds);
+    if (len != PIPE_BUF || signal_count != 2) {
+        THROW_ERROR("the interrupted write should return the bytes written");
+    }
+    return 0;
+}
+
 // ============================================================================
 // Test suite
 // ============================================================================
@@ -121,6 +455,13 @@ static test_case_t test_cases[] = {
     TEST_CASE(test_fcntl_set_flags),
     TEST_CASE(test_create_with_flags),
     TEST_CASE(test_read_write),
+    TEST_CASE(test_pollout_threshold),
+    TEST_CASE(test_write_after_readers_closed),
+    TEST_CASE(test_poll_after_writers_closed),
+    TEST_CASE(test_poll_woken_by_write),
+    TEST_CASE(test_blocking_write_woken_by_read),
+    TEST_CASE(test_blocking_write_interrupted_by_close),
+    TEST_CASE(test_blocking_write_interrupted_by_signal),
 };
 
 int main(int argc, const char* argv[]) {
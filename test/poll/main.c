@@ -0,0 +1,156 @@
+#include <sys/select.h>
+#include <sys/stat.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <poll.h>
+#include <unistd.h>
+#include "test.h"
+
+#define FILE_PATH       "/root/test_poll.txt"
+#define DIR_PATH        "/root"
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int create_file(void) {
+    int fd = open(FILE_PATH, O_RDWR | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+    return fd;
+}
+
+// ============================================================================
+// Test cases for poll and select
+// ============================================================================
+
+static int test_poll_regular_file() {
+    int fd = create_file();
+    if (fd < 0) {
+        return -1;
+    }
+    struct pollfd pollfds[2] = {
+        { .fd = fd, .events = POLLIN | POLLOUT },
+        { .fd = fd, .events = POLLIN },
+    };
+    // A regular file is always ready, so poll should not block
+    if (poll(pollfds, 2, -1) != 2) {
+        THROW_ERROR("poll on a regular file should return immediately");
+    }
+    if (pollfds[0].revents != (POLLIN | POLLOUT) || pollfds[1].revents != POLLIN) {
+        THROW_ERROR("incorrect revents of a regular file");
+    }
+    close(fd);
+    unlink(FILE_PATH);
+    return 0;
+}
+
+static int test_poll_dir() {
+    int fd = open(DIR_PATH, O_RDONLY | O_DIRECTORY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open a directory");
+    }
+    struct pollfd pollfd = { .fd = fd, .events = POLLIN };
+    if (poll(&pollfd, 1, -1) != 1 || pollfd.revents != POLLIN) {
+        THROW_ERROR("a directory should be always readable");
+    }
+    close(fd);
+    return 0;
+}
+
+static int test_poll_pipe() {
+    int pipefds[2];
+    if (pipe(pipefds) < 0) {
+        THROW_ERROR("failed to create a pipe");
+    }
+    struct pollfd pollfds[2] = {
+        { .fd = pipefds[0], .events = POLLIN },
+        { .fd = pipefds[1], .events = POLLOUT },
+    };
+    if (poll(pollfds, 2, 0) != 1 || pollfds[0].revents != 0 || pollfds[1].revents != POLLOUT) {
+        THROW_ERROR("only the write end of an empty pipe should be ready");
+    }
+    if (poll(pollfds, 1, 10) != 0) {
+        THROW_ERROR("poll on an empty pipe should time out");
+    }
+    char c = 'a';
+    if (write(pipefds[1], &c, 1) != 1) {
+        THROW_ERROR("failed to write the pipe");
+    }
+    if (poll(pollfds, 1, -1) != 1 || pollfds[0].revents != POLLIN) {
+        THROW_ERROR("the read end should be ready after a write");
+    }
+    close(pipefds[0]);
+    close(pipefds[1]);
+    return 0;
+}
+
+static int test_select_regular_file() {
+    int fd = create_file();
+    if (fd < 0) {
+        return -1;
+    }
+    fd_set readfds, writefds, exceptfds;
+    FD_ZERO(&readfds);
+    FD_ZERO(&writefds);
+    FD_ZERO(&exceptfds);
+    FD_SET(fd, &readfds);
+    FD_SET(fd, &writefds);
+    FD_SET(fd, &exceptfds);
+    if (select(fd + 1, &readfds, &writefds, &exceptfds, NULL) != 2) {
+        THROW_ERROR("select on a regular file should return immediately");
+    }
+    if (!FD_ISSET(fd, &readfds) || !FD_ISSET(fd, &writefds)) {
+        THROW_ERROR("a regular file should be readable and writable");
+    }
+    if (FD_ISSET(fd, &exceptfds)) {
+        THROW_ERROR("a regular file should have no exceptional condition");
+    }
+    close(fd);
+    unlink(FILE_PATH);
+    return 0;
+}
+
+static int test_select_pipe() {
+    int pipefds[2];
+    if (pipe(pipefds) < 0) {
+        THROW_ERROR("failed to create a pipe");
+    }
+    fd_set readfds;
+    FD_ZERO(&readfds);
+    FD_SET(pipefds[0], &readfds);
+    struct timeval timeout = { .tv_sec = 0, .tv_usec = 10 * 1000 };
+    if (select(pipefds[0] + 1, &readfds, NULL, NULL, &timeout) != 0
+            || FD_ISSET(pipefds[0], &readfds)) {
+        THROW_ERROR("select on an empty pipe should time out");
+    }
+    char c = 'a';
+    if (write(pipefds[1], &c, 1) != 1) {
+        THROW_ERROR("failed to write the pipe");
+    }
+    FD_SET(pipefds[0], &readfds);
+    if (select(pipefds[0] + 1, &readfds, NULL, NULL, NULL) != 1
+            || !FD_ISSET(pipefds[0], &readfds)) {
+        THROW_ERROR("the read end should be ready after a write");
+    }
+    close(pipefds[0]);
+    close(pipefds[1]);
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_poll_regular_file),
+    TEST_CASE(test_poll_dir),
+    TEST_CASE(test_poll_pipe),
+    TEST_CASE(test_select_regular_file),
+    TEST_CASE(test_select_pipe),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,389 @@
+#define _GNU_SOURCE
+#include <sys/file.h>
+#include <sys/stat.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <pthread.h>
+#include <signal.h>
+#include <spawn.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#ifndef F_OFD_SETLK
+#define F_OFD_GETLK         36
+#define F_OFD_SETLK         37
+#define F_OFD_SETLKW        38
+#endif
+
+#define PROC_LOCKS_PATH     "/proc/locks"
+#define FILE_PATH           "/root/test_proc_locks.txt"
+
+static int fd;
+static ino_t ino;
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int read_proc_locks(char *buf, size_t buf_size) {
+    int proc_fd = open(PROC_LOCKS_PATH, O_RDONLY);
+    if (proc_fd < 0) {
+        THROW_ERROR("failed to open %s", PROC_LOCKS_PATH);
+    }
+    memset(buf, 0, buf_size);
+    ssize_t len = read(proc_fd, buf, buf_size - 1);
+    close(proc_fd);
+    if (len < 0) {
+        THROW_ERROR("failed to read %s", PROC_LOCKS_PATH);
+    }
+    return 0;
+}
+
+// Check whether there is a line that contains the entry and ends with the
+// inode and the range of the lock
+static int has_lock_entry(const char *entry, const char *range) {
+    char buf[4096];
+    char suffix[64];
+    if (read_proc_locks(buf, sizeof(buf)) < 0) {
+        return -1;
+    }
+    snprintf(suffix, sizeof(suffix), ":%lu %s", (unsigned long)ino, range);
+    for (char *line = strtok(buf, "\n"); line != NULL; line = strtok(NULL, "\n")) {
+        size_t line_len = strlen(line);
+        size_t suffix_len = strlen(suffix);
+        if (strstr(line, entry) != NULL && line_len >= suffix_len &&
+                strcmp(line + line_len - suffix_len, suffix) == 0) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+static int set_lock(int cmd, int target_fd, short type, off_t start, off_t len) {
+    struct flock lock = {
+        .l_type = type,
+        .l_whence = SEEK_SET,
+        .l_start = start,
+        .l_len = len,
+        .l_pid = 0,
+    };
+    return fcntl(target_fd, cmd, &lock);
+}
+
+static volatile int signal_count = 0;
+
+static void handle_signal(int signum) {
+    signal_count++;
+}
+
+static void *signal_after_sleep(void *arg) {
+    pid_t tid = *(pid_t *)arg;
+    usleep(100 * 1000);
+    syscall(SYS_tkill, tid, SIGUSR1);
+    return NULL;
+}
+
+// Wait for the conflicting lock with the given function, which should be
+// interrupted by a signal sent from another thread
+static int wait_lock_interrupted(int (*lock_fn)(int), int target_fd) {
+    struct sigaction sa = { 0 };
+    sa.sa_handler = handle_signal;
+    if (sigaction(SIGUSR1, &sa, NULL) < 0) {
+        THROW_ERROR("failed to set the signal handler");
+    }
+    signal_count = 0;
+    pid_t tid = syscall(SYS_gettid);
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, signal_after_sleep, &tid) != 0) {
+        THROW_ERROR("failed to create a thread");
+    }
+    int ret = lock_fn(target_fd);
+    int err = errno;
+    pthread_join(thread, NULL);
+    signal(SIGUSR1, SIG_DFL);
+    if (ret == 0 || err != EINTR) {
+        THROW_ERROR("the blocking lock should fail with EINTR by the signal");
+    }
+    if (signal_count != 1) {
+        THROW_ERROR("the handler of the signal should be run once");
+    }
+    return 0;
+}
+
+static int flock_exclusive(int target_fd) {
+    return flock(target_fd, LOCK_EX);
+}
+
+static int ofd_lock_exclusive(int target_fd) {
+    return set_lock(F_OFD_SETLKW, target_fd, F_WRLCK, 0, 0);
+}
+
+// ============================================================================
+// Test cases for /proc/locks
+// ============================================================================
+
+static int test_posix_lock() {
+    char entry[64];
+    snprintf(entry, sizeof(entry), "POSIX  ADVISORY  WRITE %d ", getpid());
+    if (set_lock(F_SETLK, fd, F_WRLCK, 0, 0) < 0) {
+        THROW_ERROR("failed to set a POSIX write lock");
+    }
+    if (has_lock_entry(entry, "0 EOF") != 1) {
+        THROW_ERROR("the POSIX write lock should be shown in %s", PROC_LOCKS_PATH);
+    }
+
+    // Unlocking a part of the file splits the lock
+    if (set_lock(F_SETLK, fd, F_UNLCK, 0, 100) < 0) {
+        THROW_ERROR("failed to unlock a part of the POSIX lock");
+    }
+    if (has_lock_entry(entry, "100 EOF") != 1) {
+        THROW_ERROR("the rest of the POSIX lock should be shown in %s", PROC_LOCKS_PATH);
+    }
+
+    if (set_lock(F_SETLK, fd, F_UNLCK, 0, 0) < 0) {
+        THROW_ERROR("failed to unlock the POSIX lock");
+    }
+    if (has_lock_entry(entry, "100 EOF") != 0) {
+        THROW_ERROR("the POSIX lock should be removed from %s", PROC_LOCKS_PATH);
+    }
+    return 0;
+}
+
+static int test_ofd_lock_conflicts_with_posix_lock() {
+    int other_fd = open(FILE_PATH, O_RDWR);
+    if (other_fd < 0) {
+        THROW_ERROR("failed to open the file again");
+    }
+    if (set_lock(F_SETLK, fd, F_WRLCK, 0, 10) < 0) {
+        THROW_ERROR("failed to set a POSIX write lock");
+    }
+    if (set_lock(F_OFD_SETLK, other_fd, F_RDLCK, 5, 10) == 0 || errno != EAGAIN) {
+        THROW_ERROR("the conflicting OFD lock should fail with EAGAIN");
+    }
+
+    struct flock lock = {
+        .l_type = F_RDLCK,
+        .l_whence = SEEK_SET,
+        .l_start = 5,
+        .l_len = 10,
+        .l_pid = 0,
+    };
+    if (fcntl(other_fd, F_OFD_GETLK, &lock) < 0) {
+        THROW_ERROR("failed to test the OFD lock");
+    }
+    if (lock.l_type != F_WRLCK || lock.l_start != 0 || lock.l_len != 10 ||
+            lock.l_pid != getpid()) {
+        THROW_ERROR("the conflicting POSIX lock should be returned");
+    }
+
+    if (set_lock(F_OFD_SETLK, other_fd, F_RDLCK, 10, 10) < 0) {
+        THROW_ERROR("failed to set a non-conflicting OFD lock");
+    }
+    if (has_lock_entry("OFDLCK ADVISORY  READ  -1 ", "10 19") != 1) {
+        THROW_ERROR("the OFD lock should be shown in %s", PROC_LOCKS_PATH);
+    }
+
+    // Closing the file releases the OFD locks on it
+    close(other_fd);
+    if (has_lock_entry("OFDLCK ADVISORY  READ  -1 ", "10 19") != 0) {
+        THROW_ERROR("the OFD lock should be released on close");
+    }
+    if (set_lock(F_SETLK, fd, F_UNLCK, 0, 0) < 0) {
+        THROW_ERROR("failed to unlock the POSIX lock");
+    }
+    return 0;
+}
+
+static int test_flock() {
+    if (flock(fd, LOCK_SH) < 0) {
+        THROW_ERROR("failed to flock with LOCK_SH");
+    }
+    if (has_lock_entry("FLOCK  ADVISORY  READ  ", "0 EOF") != 1) {
+        THROW_ERROR("the shared flock lock should be shown in %s", PROC_LOCKS_PATH);
+    }
+
+    int other_fd = open(FILE_PATH, O_RDONLY);
+    if (other_fd < 0) {
+        THROW_ERROR("failed to open the file again");
+    }
+    if (flock(other_fd, LOCK_EX | LOCK_NB) == 0 || errno != EWOULDBLOCK) {
+        THROW_ERROR("the conflicting flock should fail with EWOULDBLOCK");
+    }
+    // The flock locks do not interact with the record locks
+    if (set_lock(F_SETLK, fd, F_WRLCK, 0, 0) < 0) {
+        THROW_ERROR("failed to set a POSIX lock along with the flock lock");
+    }
+    set_lock(F_SETLK, fd, F_UNLCK, 0, 0);
+    close(other_fd);
+
+    if (flock(fd, LOCK_UN) < 0) {
+        THROW_ERROR("failed to flock with LOCK_UN");
+    }
+    if (has_lock_entry("FLOCK  ADVISORY  READ  ", "0 EOF") != 0) {
+        THROW_ERROR("the flock lock should be removed from %s", PROC_LOCKS_PATH);
+    }
+    if (flock(fd, LOCK_SH | LOCK_EX) == 0 || errno != EINVAL) {
+        THROW_ERROR("flock with an invalid operation should fail with EINVAL");
+    }
+    return 0;
+}
+
+static int test_dup2_releases_posix_lock() {
+    char entry[64];
+    snprintf(entry, sizeof(entry), "POSIX  ADVISORY  WRITE %d ", getpid());
+    int dup_fd = dup(fd);
+    int other_fd = open(FILE_PATH, O_RDONLY);
+    if (dup_fd < 0 || other_fd < 0) {
+        THROW_ERROR("failed to dup or open the file again");
+    }
+    if (set_lock(F_SETLK, fd, F_WRLCK, 0, 0) < 0) {
+        THROW_ERROR("failed to set a POSIX write lock");
+    }
+    // Closing any fd of the file releases the POSIX locks, including the
+    // close of the fd replaced by dup2
+    if (dup2(other_fd, dup_fd) < 0) {
+        THROW_ERROR("failed to dup2");
+    }
+    if (has_lock_entry(entry, "0 EOF") != 0) {
+        THROW_ERROR("the POSIX lock should be released by dup2 over the fd");
+    }
+    close(dup_fd);
+    close(other_fd);
+    return 0;
+}
+
+static int test_posix_lock_deadlock() {
+    if (set_lock(F_SETLK, fd, F_WRLCK, 0, 10) < 0) {
+        THROW_ERROR("failed to set a POSIX write lock");
+    }
+    int child_pid, status;
+    const char *child_argv[] = { "proc_locks", "deadlock_child", NULL };
+    if (posix_spawn(&child_pid, "/bin/proc_locks", NULL, NULL, (char *const *)child_argv,
+                    NULL) != 0) {
+        THROW_ERROR("failed to spawn the child");
+    }
+
+    // Wait until the child locks [10, 20) and then waits for [0, 10)
+    char entry[64];
+    snprintf(entry, sizeof(entry), "POSIX  ADVISORY  WRITE %d ", child_pid);
+    for (int i = 0; has_lock_entry(entry, "10 19") != 1; i++) {
+        if (i == 500) {
+            THROW_ERROR("the child should lock the file");
+        }
+        usleep(10 * 1000);
+    }
+    usleep(100 * 1000);
+
+    if (set_lock(F_SETLKW, fd, F_WRLCK, 10, 10) == 0 || errno != EDEADLK) {
+        THROW_ERROR("waiting for the lock of the waiting child should fail with EDEADLK");
+    }
+    // The child acquires the lock once it is released
+    if (set_lock(F_SETLK, fd, F_UNLCK, 0, 10) < 0) {
+        THROW_ERROR("failed to unlock the POSIX lock");
+    }
+    if (waitpid(child_pid, &status, 0) < 0 || !WIFEXITED(status) ||
+            WEXITSTATUS(status) != 0) {
+        THROW_ERROR("the child should acquire the lock");
+    }
+    return 0;
+}
+
+static int deadlock_child_main() {
+    int child_fd = open(FILE_PATH, O_RDWR);
+    if (child_fd < 0) {
+        return -1;
+    }
+    if (set_lock(F_SETLK, child_fd, F_WRLCK, 10, 10) < 0) {
+        return -1;
+    }
+    if (set_lock(F_SETLKW, child_fd, F_WRLCK, 0, 10) < 0) {
+        return -1;
+    }
+    return 0;
+}
+
+static int test_blocking_lock_interrupted_by_signal() {
+    int other_fd = open(FILE_PATH, O_RDWR);
+    if (other_fd < 0) {
+        THROW_ERROR("failed to open the file again");
+    }
+    if (flock(fd, LOCK_EX) < 0 || set_lock(F_OFD_SETLK, fd, F_WRLCK, 0, 0) < 0) {
+        THROW_ERROR("failed to lock the file");
+    }
+    if (wait_lock_interrupted(flock_exclusive, other_fd) < 0 ||
+            wait_lock_interrupted(ofd_lock_exclusive, other_fd) < 0) {
+        return -1;
+    }
+    // The interrupted waits leave no lock behind
+    flock(fd, LOCK_UN);
+    set_lock(F_OFD_SETLK, fd, F_UNLCK, 0, 0);
+    if (flock(other_fd, LOCK_EX | LOCK_NB) < 0 ||
+            set_lock(F_OFD_SETLK, other_fd, F_WRLCK, 0, 0) < 0) {
+        THROW_ERROR("the locks should be acquired after they are released");
+    }
+    close(other_fd);
+    return 0;
+}
+
+static int test_lease() {
+    int rd_fd = open(FILE_PATH, O_RDONLY);
+    if (rd_fd < 0) {
+        THROW_ERROR("failed to open the file for reading");
+    }
+    // A read lease requires that the file is not opened for writing
+    close(fd);
+    if (fcntl(rd_fd, F_SETLEASE, F_RDLCK) < 0) {
+        THROW_ERROR("failed to set a read lease");
+    }
+    char entry[64];
+    snprintf(entry, sizeof(entry), "LEASE  ACTIVE    READ  %d ", getpid());
+    if (has_lock_entry(entry, "0 EOF") != 1) {
+        THROW_ERROR("the lease should be shown in %s", PROC_LOCKS_PATH);
+    }
+    fcntl(rd_fd, F_SETLEASE, F_UNLCK);
+    close(rd_fd);
+
+    fd = open(FILE_PATH, O_RDWR);
+    if (fd < 0) {
+        THROW_ERROR("failed to reopen the file");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_posix_lock),
+    TEST_CASE(test_ofd_lock_conflicts_with_posix_lock),
+    TEST_CASE(test_flock),
+    TEST_CASE(test_dup2_releases_posix_lock),
+    TEST_CASE(test_posix_lock_deadlock),
+    TEST_CASE(test_blocking_lock_interrupted_by_signal),
+    TEST_CASE(test_lease),
+};
+
+int main(int argc, const char *argv[]) {
+    if (argc > 1 && strcmp(argv[1], "deadlock_child") == 0) {
+        return deadlock_child_main();
+    }
+    fd = open(FILE_PATH, O_RDWR | O_CREAT | O_TRUNC, 0666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+    struct stat stat_buf;
+    if (fstat(fd, &stat_buf) < 0) {
+        THROW_ERROR("failed to fstat the file");
+    }
+    ino = stat_buf.st_ino;
+
+    int ret = test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+    close(fd);
+    unlink(FILE_PATH);
+    return ret;
+}
@@ -0,0 +1,258 @@
+#include <sys/types.h>
+#include <sys/wait.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <libgen.h>
+#include <pthread.h>
+#include <spawn.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+// The time to wait for a child to sleep or exit
+#define CHILD_WAIT_US       (100 * 1000)
+
+// The indexes of the fields in /proc/[pid]/stat, starting from 1 as proc(5)
+#define STAT_FIELD_PID          1
+#define STAT_FIELD_STATE        3
+#define STAT_FIELD_PPID         4
+#define STAT_FIELD_NUM_THREADS  20
+
+static const char *prog_name;
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int read_proc_file(int pid, const char *entry, char *buf, size_t buf_size) {
+    char path[64];
+    if (pid == 0) {
+        snprintf(path, sizeof(path), "/proc/self/%s", entry);
+    } else {
+        snprintf(path, sizeof(path), "/proc/%d/%s", pid, entry);
+    }
+    int fd = open(path, O_RDONLY);
+    if (fd < 0) {
+        return -1;
+    }
+    ssize_t len = read(fd, buf, buf_size - 1);
+    close(fd);
+    if (len <= 0) {
+        return -1;
+    }
+    buf[len] = '\0';
+    return 0;
+}
+
+// Get a field of /proc/[pid]/stat as a string. The fields after the name are
+// found after the last ')', as the name may contain spaces and parentheses.
+static int get_stat_field(int pid, int field, char *value, size_t value_size) {
+    char stat[1024];
+    if (read_proc_file(pid, "stat", stat, sizeof(stat)) < 0) {
+        THROW_ERROR("failed to read the stat file");
+    }
+    if (field == STAT_FIELD_PID) {
+        snprintf(value, value_size, "%d", atoi(stat));
+        return 0;
+    }
+    char *fields = strrchr(stat, ')');
+    if (fields == NULL) {
+        THROW_ERROR("no name in the stat file");
+    }
+    char *saveptr = NULL;
+    char *token = strtok_r(fields + 1, " \n", &saveptr);
+    for (int i = STAT_FIELD_STATE; token != NULL && i < field; i++) {
+        token = strtok_r(NULL, " \n", &saveptr);
+    }
+    if (token == NULL) {
+        THROW_ERROR("too few fields in the stat file");
+    }
+    snprintf(value, value_size, "%s", token);
+    return 0;
+}
+
+static long get_stat_field_long(int pid, int field) {
+    char value[32];
+    if (get_stat_field(pid, field, value, sizeof(value)) < 0) {
+        return -1;
+    }
+    return atol(value);
+}
+
+static char get_stat_state(int pid) {
+    char value[32];
+    if (get_stat_field(pid, STAT_FIELD_STATE, value, sizeof(value)) < 0) {
+        return '\0';
+    }
+    return value[0];
+}
+
+// Spawn a child that sleeps for `sleep_ms` milliseconds and then exits
+static int spawn_child(int sleep_ms) {
+    char sleep_ms_str[16];
+    snprintf(sleep_ms_str, sizeof(sleep_ms_str), "%d", sleep_ms);
+
+    int child_pid;
+    const char *child_argv[4] = { "proc_stat", "child", sleep_ms_str, NULL };
+    if (posix_spawn(&child_pid, "/bin/proc_stat", NULL, NULL, (char *const *)child_argv,
+                    NULL) < 0) {
+        THROW_ERROR("failed to spawn a child process");
+    }
+    return child_pid;
+}
+
+static void *block_on_pipe(void *arg) {
+    int fd = *(int *)arg;
+    char byte;
+    read(fd, &byte, 1);
+    return NULL;
+}
+
+// ============================================================================
+// Test cases for /proc/[pid]/stat and /proc/[pid]/status
+// ============================================================================
+
+static int test_stat_pid() {
+    char stat[1024];
+    if (read_proc_file(0, "stat", stat, sizeof(stat)) < 0) {
+        THROW_ERROR("failed to read /proc/self/stat");
+    }
+    int pid, ppid;
+    char comm[32], state;
+    if (sscanf(stat, "%d (%31[^)]) %c %d", &pid, comm, &state, &ppid) != 4) {
+        THROW_ERROR("failed to parse /proc/self/stat");
+    }
+    if (pid != getpid()) {
+        THROW_ERROR("the pid field is not the pid");
+    }
+    if (strcmp(comm, prog_name) != 0) {
+        THROW_ERROR("the name field is not the name of the program");
+    }
+    if (state != 'R') {
+        THROW_ERROR("the process that reads the file should be running");
+    }
+    if (ppid != getppid()) {
+        THROW_ERROR("the ppid field is not the pid of the parent");
+    }
+    return 0;
+}
+
+static int test_stat_by_pid() {
+    if (get_stat_field_long(getpid(), STAT_FIELD_PID) != getpid()) {
+        THROW_ERROR("the pid field of /proc/[pid]/stat is not the pid");
+    }
+    return 0;
+}
+
+static int test_stat_num_threads() {
+    if (get_stat_field_long(0, STAT_FIELD_NUM_THREADS) != 1) {
+        THROW_ERROR("there should be only one thread");
+    }
+
+    int pipe_fds[2];
+    if (pipe(pipe_fds) < 0) {
+        THROW_ERROR("failed to create a pipe");
+    }
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, block_on_pipe, &pipe_fds[0]) != 0) {
+        THROW_ERROR("failed to create a thread");
+    }
+    long num_threads = get_stat_field_long(0, STAT_FIELD_NUM_THREADS);
+    write(pipe_fds[1], "x", 1);
+    pthread_join(thread, NULL);
+    close(pipe_fds[0]);
+    close(pipe_fds[1]);
+    if (num_threads != 2) {
+        THROW_ERROR("the new thread should be counted");
+    }
+    return 0;
+}
+
+static int test_status_fields() {
+    char status[4096];
+    if (read_proc_file(0, "status", status, sizeof(status)) < 0) {
+        THROW_ERROR("failed to read /proc/self/status");
+    }
+    char expected[64];
+    snprintf(expected, sizeof(expected), "\nPid:\t%d\n", getpid());
+    if (strstr(status, expected) == NULL) {
+        THROW_ERROR("the Pid field is not the pid");
+    }
+    snprintf(expected, sizeof(expected), "\nPPid:\t%d\n", getppid());
+    if (strstr(status, expected) == NULL) {
+        THROW_ERROR("the PPid field is not the pid of the parent");
+    }
+    if (strstr(status, "\nState:\tR (running)\n") == NULL) {
+        THROW_ERROR("the process that reads the file should be running");
+    }
+    if (strstr(status, "\nThreads:\t1\n") == NULL) {
+        THROW_ERROR("there should be only one thread");
+    }
+    const char *fields[] = { "Name:", "VmSize:", "SigQ:", "SigBlk:" };
+    for (int i = 0; i < ARRAY_SIZE(fields); i++) {
+        if (strstr(status, fields[i]) == NULL) {
+            THROW_ERROR("a field is missing in /proc/self/status");
+        }
+    }
+    return 0;
+}
+
+static int test_stat_state_of_child() {
+    int child_pid = spawn_child(CHILD_WAIT_US * 3 / 1000);
+    if (child_pid < 0) {
+        return -1;
+    }
+
+    usleep(CHILD_WAIT_US);
+    char state = get_stat_state(child_pid);
+    if (state != 'S') {
+        THROW_ERROR("the child that sleeps should be sleeping");
+    }
+    if (get_stat_field_long(child_pid, STAT_FIELD_PPID) != getpid()) {
+        THROW_ERROR("the ppid field of the child is not the pid of the parent");
+    }
+
+    usleep(CHILD_WAIT_US * 3);
+    state = get_stat_state(child_pid);
+    if (state != 'Z') {
+        THROW_ERROR("the child that exits but is not waited for should be a zombie");
+    }
+
+    int status;
+    if (waitpid(child_pid, &status, 0) != child_pid) {
+        THROW_ERROR("failed to wait for the child");
+    }
+    return 0;
+}
+
+static int test_stat_of_nonexistent_process() {
+    int fd = open("/proc/999999/stat", O_RDONLY);
+    if (fd >= 0 || errno != ENOENT) {
+        THROW_ERROR("the stat of a nonexistent process should not be found");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_stat_pid),
+    TEST_CASE(test_stat_by_pid),
+    TEST_CASE(test_stat_num_threads),
+    TEST_CASE(test_status_fields),
+    TEST_CASE(test_stat_state_of_child),
+    TEST_CASE(test_stat_of_nonexistent_process),
+};
+
+int main(int argc, const char *argv[]) {
+    if (argc > 2 && strcmp(argv[1], "child") == 0) {
+        usleep(atoi(argv[2]) * 1000);
+        return 0;
+    }
+    prog_name = basename(strdup(argv[0]));
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,182 @@
+#include <sys/types.h>
+#include <sys/uio.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <pthread.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define FILE_PATH       "/root/test_pwritev.txt"
+#define NTHREADS        2
+#define NIOVS           4
+#define IOV_SIZE        1024
+#define NREPEATS        1000
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int open_file(void) {
+    int fd = open(FILE_PATH, O_RDWR | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the file");
+    }
+    return fd;
+}
+
+// Make an iovec of the buffer split into NIOVS equal parts
+static void split_buf(struct iovec *iov, char *buf, size_t len) {
+    for (int i = 0; i < NIOVS; i++) {
+        iov[i].iov_base = buf + i * (len / NIOVS);
+        iov[i].iov_len = len / NIOVS;
+    }
+}
+
+// ============================================================================
+// Test cases for preadv and pwritev
+// ============================================================================
+
+static int test_file_offset_unchanged() {
+    char write_buf[64], read_buf[64];
+    struct iovec iov[NIOVS];
+    memset(write_buf, 'a', sizeof(write_buf));
+
+    int fd = open_file();
+    if (fd < 0) {
+        return -1;
+    }
+    if (write(fd, "0123", 4) != 4) {
+        THROW_ERROR("failed to write the file");
+    }
+    split_buf(iov, write_buf, sizeof(write_buf));
+    if (pwritev(fd, iov, NIOVS, 16) != sizeof(write_buf)) {
+        THROW_ERROR("failed to pwritev the file");
+    }
+    split_buf(iov, read_buf, sizeof(read_buf));
+    if (preadv(fd, iov, NIOVS, 16) != sizeof(read_buf) ||
+            memcmp(read_buf, write_buf, sizeof(read_buf)) != 0) {
+        THROW_ERROR("failed to preadv what is written");
+    }
+    if (lseek(fd, 0, SEEK_CUR) != 4) {
+        THROW_ERROR("the file offset should not be changed");
+    }
+    close(fd);
+    return 0;
+}
+
+static int test_preadv_near_eof() {
+    char buf[64];
+    struct iovec iov[NIOVS];
+    int fd = open_file();
+    if (fd < 0) {
+        return -1;
+    }
+    if (write(fd, "0123456789", 10) != 10) {
+        THROW_ERROR("failed to write the file");
+    }
+    split_buf(iov, buf, sizeof(buf));
+    if (preadv(fd, iov, NIOVS, 4) != 6 || memcmp(buf, "456789", 6) != 0) {
+        THROW_ERROR("preadv near EOF should read the rest of the file");
+    }
+    if (preadv(fd, iov, NIOVS, 20) != 0) {
+        THROW_ERROR("preadv beyond EOF should read nothing");
+    }
+    close(fd);
+    return 0;
+}
+
+static int test_pipe() {
+    char buf[16];
+    struct iovec iov = { .iov_base = buf, .iov_len = sizeof(buf) };
+    int pipe_fds[2];
+    if (pipe(pipe_fds) < 0) {
+        THROW_ERROR("failed to create a pipe");
+    }
+    if (pwritev(pipe_fds[1], &iov, 1, 0) >= 0 || errno != ESPIPE) {
+        THROW_ERROR("pwritev on a pipe should fail with ESPIPE");
+    }
+    if (preadv(pipe_fds[0], &iov, 1, 0) >= 0 || errno != ESPIPE) {
+        THROW_ERROR("preadv on a pipe should fail with ESPIPE");
+    }
+    close(pipe_fds[0]);
+    close(pipe_fds[1]);
+    return 0;
+}
+
+struct writer_arg {
+    int fd;
+    char c;
+    off_t offset;
+};
+
+static void *writer_thread_func(void *_arg) {
+    struct writer_arg *arg = _arg;
+    char buf[NIOVS * IOV_SIZE];
+    struct iovec iov[NIOVS];
+    memset(buf, arg->c, sizeof(buf));
+    split_buf(iov, buf, sizeof(buf));
+    for (int i = 0; i < NREPEATS; i++) {
+        if (pwritev(arg->fd, iov, NIOVS, arg->offset) != sizeof(buf)) {
+            return (void *)-1;
+        }
+    }
+    return NULL;
+}
+
+static int test_concurrent_pwritev() {
+    int fd = open_file();
+    if (fd < 0) {
+        return -1;
+    }
+    pthread_t threads[NTHREADS];
+    struct writer_arg args[NTHREADS];
+    for (int i = 0; i < NTHREADS; i++) {
+        args[i] = (struct writer_arg) {
+            .fd = fd, .c = 'a' + i, .offset = i * NIOVS * IOV_SIZE
+        };
+        if (pthread_create(&threads[i], NULL, writer_thread_func, &args[i]) != 0) {
+            THROW_ERROR("failed to create a thread");
+        }
+    }
+    for (int i = 0; i < NTHREADS; i++) {
+        void *ret;
+        pthread_join(threads[i], &ret);
+        if (ret != NULL) {
+            THROW_ERROR("failed to pwritev in a thread");
+        }
+    }
+
+    // Each thread owns its range, which has only its data
+    char buf[NIOVS * IOV_SIZE];
+    for (int i = 0; i < NTHREADS; i++) {
+        if (pread(fd, buf, sizeof(buf), args[i].offset) != sizeof(buf)) {
+            THROW_ERROR("failed to read the file");
+        }
+        for (size_t j = 0; j < sizeof(buf); j++) {
+            if (buf[j] != args[i].c) {
+                THROW_ERROR("the data of the threads are interleaved");
+            }
+        }
+    }
+    if (lseek(fd, 0, SEEK_CUR) != 0) {
+        THROW_ERROR("the file offset should not be changed");
+    }
+    close(fd);
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_file_offset_unchanged),
+    TEST_CASE(test_preadv_near_eof),
+    TEST_CASE(test_pipe),
+    TEST_CASE(test_concurrent_pwritev),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
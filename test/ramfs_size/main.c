@@ -0,0 +1,311 @@
+#define _GNU_SOURCE
+#include <sys/resource.h>
+#include <sys/stat.h>
+#include <sys/vfs.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <signal.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+// The size limit of the RamFS at /tmp, which must be in sync with
+// test/Occlum.json
+#define RAMFS_SIZE      (4 * 1024 * 1024)
+
+#define FILE_PATH       "/tmp/test_ramfs_size.txt"
+#define OTHER_FILE_PATH "/tmp/test_ramfs_size_other.txt"
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int get_statfs(struct statfs *buf) {
+    if (statfs("/tmp", buf) < 0) {
+        THROW_ERROR("failed to statfs");
+    }
+    return 0;
+}
+
+static long get_avail_bytes(void) {
+    struct statfs buf;
+    if (get_statfs(&buf) < 0) {
+        return -1;
+    }
+    return buf.f_bavail * buf.f_bsize;
+}
+
+// Write the file until the RamFS is full and return the size written
+static long fill_file(int fd) {
+    static char buf[64 * 1024];
+    memset(buf, 'a', sizeof(buf));
+
+    long total = 0;
+    while (1) {
+        ssize_t len = write(fd, buf, sizeof(buf));
+        if (len < 0) {
+            if (errno != ENOSPC) {
+                THROW_ERROR("writing a full RamFS should fail with ENOSPC");
+            }
+            return total;
+        }
+        total += len;
+        if (total > RAMFS_SIZE) {
+            THROW_ERROR("the size limit is not enforced");
+        }
+    }
+}
+
+// ============================================================================
+// Test cases for the size limit of RamFS
+// ============================================================================
+
+static int test_statfs_reports_limit() {
+    struct statfs buf;
+    if (get_statfs(&buf) < 0) {
+        return -1;
+    }
+    if (buf.f_blocks * buf.f_bsize != RAMFS_SIZE) {
+        THROW_ERROR("f_blocks should be the size limit");
+    }
+    if (buf.f_bavail != buf.f_bfree || buf.f_bavail > buf.f_blocks) {
+        THROW_ERROR("f_bavail should be the remaining capacity");
+    }
+    return 0;
+}
+
+static int test_fill_to_limit() {
+    long avail = get_avail_bytes();
+    int fd = open(FILE_PATH, O_WRONLY | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+    long written = fill_file(fd);
+    close(fd);
+    if (written != avail) {
+        THROW_ERROR("the size written should be the remaining capacity");
+    }
+    if (get_avail_bytes() != 0) {
+        THROW_ERROR("no space should be available after filling");
+    }
+    if (open(OTHER_FILE_PATH, O_WRONLY | O_CREAT, 00666) >= 0 || errno != ENOSPC) {
+        THROW_ERROR("creating a file in a full RamFS should fail with ENOSPC");
+    }
+
+    // Deletion frees the space
+    if (unlink(FILE_PATH) < 0) {
+        THROW_ERROR("failed to unlink the file");
+    }
+    if (get_avail_bytes() != avail) {
+        THROW_ERROR("the space should be freed by unlink");
+    }
+    fd = open(OTHER_FILE_PATH, O_WRONLY | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file after freeing space");
+    }
+    if (write(fd, "hello", 5) != 5) {
+        THROW_ERROR("failed to write after freeing space");
+    }
+    close(fd);
+    unlink(OTHER_FILE_PATH);
+    return 0;
+}
+
+static int test_truncate_frees_space() {
+    long avail = get_avail_bytes();
+    int fd = open(FILE_PATH, O_WRONLY | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+    fill_file(fd);
+    if (ftruncate(fd, 0) < 0) {
+        THROW_ERROR("failed to truncate the file");
+    }
+    if (get_avail_bytes() != avail) {
+        THROW_ERROR("the space should be freed by truncate");
+    }
+    close(fd);
+    unlink(FILE_PATH);
+    return 0;
+}
+
+static int test_sparse_file() {
+    struct statfs buf;
+    if (get_statfs(&buf) < 0) {
+        return -1;
+    }
+    long avail = buf.f_bavail * buf.f_bsize;
+
+    int fd = open(FILE_PATH, O_WRONLY | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+    // Extending a file by truncate takes no space
+    if (ftruncate(fd, RAMFS_SIZE / 2) < 0) {
+        THROW_ERROR("failed to extend the file");
+    }
+    if (get_avail_bytes() != avail) {
+        THROW_ERROR("the hole of a sparse file should take no space");
+    }
+    // Only the block that is written is accounted
+    if (pwrite(fd, "a", 1, RAMFS_SIZE / 4) != 1) {
+        THROW_ERROR("failed to write the sparse file");
+    }
+    if (get_avail_bytes() != avail - buf.f_bsize) {
+        THROW_ERROR("only the written block should be accounted");
+    }
+    // Writing beyond the end fills the gap from it, which is accounted
+    if (pwrite(fd, "a", 1, RAMFS_SIZE / 2 + 3 * buf.f_bsize) != 1) {
+        THROW_ERROR("failed to write beyond the end");
+    }
+    if (get_avail_bytes() != avail - 5 * buf.f_bsize) {
+        THROW_ERROR("the gap before the written block should be accounted");
+    }
+    // A write whose gap exceeds the limit fails, taking no space
+    if (pwrite(fd, "a", 1, 2 * RAMFS_SIZE) >= 0 || errno != ENOSPC) {
+        THROW_ERROR("writing beyond the limit should fail with ENOSPC");
+    }
+    if (get_avail_bytes() != avail - 5 * buf.f_bsize) {
+        THROW_ERROR("the failed write should take no space");
+    }
+    close(fd);
+    unlink(FILE_PATH);
+    if (get_avail_bytes() != avail) {
+        THROW_ERROR("the space should be freed by unlink");
+    }
+    return 0;
+}
+
+static int test_fallocate_to_limit() {
+    long avail = get_avail_bytes();
+    int fd = open(FILE_PATH, O_RDWR | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+    if (fallocate(fd, 0, 0, avail) < 0) {
+        THROW_ERROR("failed to fallocate the remaining capacity");
+    }
+    struct stat stat_buf;
+    if (fstat(fd, &stat_buf) < 0 || stat_buf.st_size != avail) {
+        THROW_ERROR("the file should be extended by fallocate");
+    }
+    if (get_avail_bytes() != 0) {
+        THROW_ERROR("no space should be available after fallocate");
+    }
+    // The allocated blocks can be written without more space
+    if (pwrite(fd, "a", 1, avail / 2) != 1) {
+        THROW_ERROR("failed to write the allocated blocks");
+    }
+    if (fallocate(fd, FALLOC_FL_KEEP_SIZE, avail, 4096) == 0 || errno != ENOSPC) {
+        THROW_ERROR("fallocate beyond the limit should fail with ENOSPC");
+    }
+
+    // Punching a hole frees the blocks in it
+    if (fallocate(fd, FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE, 0, avail / 2) < 0) {
+        THROW_ERROR("failed to punch a hole");
+    }
+    if (get_avail_bytes() != avail / 2 / 4096 * 4096) {
+        THROW_ERROR("the blocks in the hole should be freed");
+    }
+    char c = 'a';
+    if (pread(fd, &c, 1, 0) != 1 || c != 0) {
+        THROW_ERROR("the hole should read as zeros");
+    }
+    close(fd);
+    unlink(FILE_PATH);
+    if (get_avail_bytes() != avail) {
+        THROW_ERROR("the space should be freed by unlink");
+    }
+    return 0;
+}
+
+static int test_fallocate_huge_range() {
+    long avail = get_avail_bytes();
+    int fd = open(FILE_PATH, O_RDWR | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+    // The range far beyond the limit is rejected without taking any space
+    int ret = fallocate(fd, FALLOC_FL_KEEP_SIZE, 0, 1L << 40);
+    int err = errno;
+    if (ret == 0 || err != ENOSPC) {
+        close(fd);
+        unlink(FILE_PATH);
+        THROW_ERROR("fallocate of a huge range should fail with ENOSPC");
+    }
+    if (get_avail_bytes() != avail) {
+        close(fd);
+        unlink(FILE_PATH);
+        THROW_ERROR("the failed fallocate should take no space");
+    }
+
+    // Punching a hole in the middle of the allocated range frees the blocks
+    // in the hole only
+    ret = -1;
+    if (fallocate(fd, FALLOC_FL_KEEP_SIZE, 0, 16 * 4096) < 0) {
+        printf("\t\tERROR: failed to fallocate\n");
+        goto out;
+    }
+    if (fallocate(fd, FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE, 4 * 4096, 8 * 4096) < 0) {
+        printf("\t\tERROR: failed to punch a hole\n");
+        goto out;
+    }
+    if (get_avail_bytes() != avail - 8 * 4096) {
+        printf("\t\tERROR: only the blocks in the hole should be freed\n");
+        goto out;
+    }
+    ret = 0;
+out:
+    close(fd);
+    unlink(FILE_PATH);
+    return ret;
+}
+
+static int test_fallocate_with_fsize_limit() {
+    struct rlimit old_limit, new_limit;
+    if (getrlimit(RLIMIT_FSIZE, &old_limit) < 0) {
+        THROW_ERROR("failed to get RLIMIT_FSIZE");
+    }
+    new_limit = old_limit;
+    new_limit.rlim_cur = 64 * 1024;
+    if (setrlimit(RLIMIT_FSIZE, &new_limit) < 0) {
+        THROW_ERROR("failed to set RLIMIT_FSIZE");
+    }
+    signal(SIGXFSZ, SIG_IGN);
+
+    long avail = get_avail_bytes();
+    int fd = open(FILE_PATH, O_RDWR | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+    int ret = fallocate(fd, 0, 0, 128 * 1024);
+    int err = errno;
+    close(fd);
+    unlink(FILE_PATH);
+    setrlimit(RLIMIT_FSIZE, &old_limit);
+    if (ret == 0 || err != EFBIG) {
+        THROW_ERROR("fallocate beyond RLIMIT_FSIZE should fail with EFBIG");
+    }
+    if (get_avail_bytes() != avail) {
+        THROW_ERROR("the failed fallocate should take no space");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_statfs_reports_limit),
+    TEST_CASE(test_fill_to_limit),
+    TEST_CASE(test_truncate_frees_space),
+    TEST_CASE(test_sparse_file),
+    TEST_CASE(test_fallocate_to_limit),
+    TEST_CASE(test_fallocate_huge_range),
+    TEST_CASE(test_fallocate_with_fsize_limit),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,221 @@
+#define _GNU_SOURCE
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <sys/statfs.h>
+#include <sys/statvfs.h>
+#include <sys/types.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+// The RamFS at /tmp is remounted read-only for the test, and then writable
+// again
+#define MNT_DIR             "/tmp"
+#define TEST_DIR            MNT_DIR "/test_read_only_mount"
+#define FILE_PATH           TEST_DIR "/file.txt"
+#define SUBDIR_PATH         TEST_DIR "/dir"
+#define NEW_PATH            TEST_DIR "/new.txt"
+#define FILE_CONTENT        "the content never changes"
+// The paths to the file through a writable mount, i.e., the hostfs at /host.
+// The symlink is made on host by the Makefile, as there is no symlink syscall.
+#define DOTDOT_PATH         "/host/.." FILE_PATH
+#define LINK_PATH           "/host/read_only_mount_link"
+
+// The file opened for read before the remount
+static int read_fd = -1;
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int create_test_files() {
+    if (mkdir(TEST_DIR, 0755) < 0 || mkdir(SUBDIR_PATH, 0755) < 0) {
+        THROW_ERROR("failed to create the test directories");
+    }
+    int fd = open(FILE_PATH, O_WRONLY | O_CREAT | O_TRUNC, 0644);
+    if (fd < 0) {
+        THROW_ERROR("failed to create the test file");
+    }
+    if (write(fd, FILE_CONTENT, strlen(FILE_CONTENT)) != strlen(FILE_CONTENT)) {
+        close(fd);
+        THROW_ERROR("failed to write the test file");
+    }
+    close(fd);
+    read_fd = open(FILE_PATH, O_RDONLY);
+    if (read_fd < 0) {
+        THROW_ERROR("failed to open the test file");
+    }
+    return 0;
+}
+
+static void remove_test_files() {
+    close(read_fd);
+    unlink(FILE_PATH);
+    unlink(NEW_PATH);
+    rmdir(SUBDIR_PATH);
+    rmdir(TEST_DIR);
+}
+
+static int remount(unsigned long flags) {
+    if (mount(NULL, MNT_DIR, NULL, MS_REMOUNT | flags, NULL) < 0) {
+        THROW_ERROR("failed to remount %s", MNT_DIR);
+    }
+    return 0;
+}
+
+#define CHECK_EROFS(name, call)                                         \
+    do {                                                                \
+        errno = 0;                                                      \
+        if ((call) >= 0 || errno != EROFS) {                            \
+            THROW_ERROR("%s should fail with EROFS", name);             \
+        }                                                               \
+    } while (0)
+
+static int check_file_content(void) {
+    char buf[64] = { 0 };
+    if (pread(read_fd, buf, sizeof(buf), 0) != strlen(FILE_CONTENT) ||
+            strcmp(buf, FILE_CONTENT) != 0) {
+        THROW_ERROR("the content of the file is changed");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for the read-only mount
+// ============================================================================
+
+static int test_statfs() {
+    struct statfs statfs_buf;
+    struct statvfs statvfs_buf;
+    if (statfs(FILE_PATH, &statfs_buf) < 0 || statvfs(FILE_PATH, &statvfs_buf) < 0) {
+        THROW_ERROR("failed to statfs");
+    }
+    if (!(statfs_buf.f_flags & ST_RDONLY) || !(statvfs_buf.f_flag & ST_RDONLY)) {
+        THROW_ERROR("the file system should be reported as read-only");
+    }
+    return 0;
+}
+
+static int test_open() {
+    CHECK_EROFS("open for write", open(FILE_PATH, O_WRONLY));
+    CHECK_EROFS("open for read and write", open(FILE_PATH, O_RDWR));
+    CHECK_EROFS("open to create", open(NEW_PATH, O_RDONLY | O_CREAT, 0644));
+    CHECK_EROFS("open to create a temp file", open(TEST_DIR, O_TMPFILE | O_RDWR, 0644));
+    // The files can still be opened for read
+    int fd = open(FILE_PATH, O_RDONLY | O_CREAT, 0644);
+    if (fd < 0) {
+        THROW_ERROR("the file should be opened for read");
+    }
+    close(fd);
+    return 0;
+}
+
+static int test_truncate() {
+    CHECK_EROFS("truncate", truncate(FILE_PATH, 0));
+    return check_file_content();
+}
+
+// Unlike Linux, which fails the remount with EBUSY if any file on the mount is
+// open for write, the writes to such a file fail with EROFS after the remount
+static int test_write_to_file_opened_before() {
+    if (remount(0) < 0) {
+        return -1;
+    }
+    int fd = open(FILE_PATH, O_WRONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the file for write");
+    }
+    int ret = remount(MS_RDONLY);
+    if (ret == 0) {
+        CHECK_EROFS("write", write(fd, "x", 1));
+        CHECK_EROFS("pwrite", pwrite(fd, "x", 1, 0));
+        CHECK_EROFS("ftruncate", ftruncate(fd, 0));
+        CHECK_EROFS("fallocate", fallocate(fd, 0, 0, 4096));
+        ret = check_file_content();
+    }
+    close(fd);
+    return ret;
+}
+
+static int test_create_and_remove() {
+    CHECK_EROFS("mkdir", mkdir(TEST_DIR "/new_dir", 0755));
+    CHECK_EROFS("mknod", mknod(NEW_PATH, S_IFREG | 0644, 0));
+    CHECK_EROFS("link", link(FILE_PATH, NEW_PATH));
+    CHECK_EROFS("rename", rename(FILE_PATH, NEW_PATH));
+    CHECK_EROFS("unlink", unlink(FILE_PATH));
+    CHECK_EROFS("rmdir", rmdir(SUBDIR_PATH));
+    if (access(FILE_PATH, F_OK) < 0 || access(SUBDIR_PATH, F_OK) < 0 ||
+            access(NEW_PATH, F_OK) == 0) {
+        THROW_ERROR("the files should not be changed");
+    }
+    return 0;
+}
+
+static int test_chmod_and_chown() {
+    CHECK_EROFS("chmod", chmod(FILE_PATH, 0600));
+    CHECK_EROFS("fchmod", fchmod(read_fd, 0600));
+    CHECK_EROFS("chown", chown(FILE_PATH, 1, 1));
+    CHECK_EROFS("lchown", lchown(FILE_PATH, 1, 1));
+    CHECK_EROFS("fchown", fchown(read_fd, 1, 1));
+    struct stat stat_buf;
+    if (stat(FILE_PATH, &stat_buf) < 0 || (stat_buf.st_mode & 0777) != 0644 ||
+            stat_buf.st_uid != 0) {
+        THROW_ERROR("the mode and the owner should not be changed");
+    }
+    return 0;
+}
+
+static int test_write_through_other_mount() {
+    // The mount is told by the file that the path leads to, not by the path
+    CHECK_EROFS("open through ..", open(DOTDOT_PATH, O_WRONLY));
+    CHECK_EROFS("truncate through ..", truncate(DOTDOT_PATH, 0));
+    CHECK_EROFS("open through a symlink", open(LINK_PATH, O_WRONLY));
+    CHECK_EROFS("truncate through a symlink", truncate(LINK_PATH, 0));
+    CHECK_EROFS("chmod through a symlink", chmod(LINK_PATH, 0600));
+    CHECK_EROFS("mkdir through ..", mkdir("/host/.." TEST_DIR "/new_dir", 0755));
+    return check_file_content();
+}
+
+static int test_remount_writable() {
+    if (remount(0) < 0) {
+        return -1;
+    }
+    struct statfs statfs_buf;
+    if (statfs(FILE_PATH, &statfs_buf) < 0 || (statfs_buf.f_flags & ST_RDONLY)) {
+        THROW_ERROR("the file system should be writable after remount");
+    }
+    int fd = open(FILE_PATH, O_WRONLY);
+    if (fd < 0 || write(fd, "x", 1) != 1) {
+        THROW_ERROR("the file should be writable after remount");
+    }
+    close(fd);
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_statfs),
+    TEST_CASE(test_open),
+    TEST_CASE(test_truncate),
+    TEST_CASE(test_write_to_file_opened_before),
+    TEST_CASE(test_create_and_remove),
+    TEST_CASE(test_chmod_and_chown),
+    TEST_CASE(test_write_through_other_mount),
+    TEST_CASE(test_remount_writable),
+};
+
+int main() {
+    if (create_test_files() < 0 || remount(MS_RDONLY) < 0) {
+        remove_test_files();
+        return -1;
+    }
+    int ret = test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+    remount(0);
+    remove_test_files();
+    return ret;
+}
@@ -3,11 +3,137 @@
 #include <dirent.h>
 #include <errno.h>
 #include <fcntl.h>
+#include <pthread.h>
 #include <string.h>
 #include <stdio.h>
 #include <unistd.h>
 #include "test.h"
 
+#define TEST_DIR            "/root/test_readdir"
+#define NUM_FILES           32
+#define NUM_SCANS           16
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int create_file(const char *name) {
+    char path[128];
+    snprintf(path, sizeof(path), "%s/%s", TEST_DIR, name);
+    int fd = open(path, O_WRONLY | O_CREAT, 00666);
+    if (fd < 0) {
+        return -1;
+    }
+    close(fd);
+    return 0;
+}
+
+static int remove_file(const char *name) {
+    char path[128];
+    snprintf(path, sizeof(path), "%s/%s", TEST_DIR, name);
+    return unlink(path);
+}
+
+static int create_test_dir(void) {
+    if (mkdir(TEST_DIR, 00775) < 0 && errno != EEXIST) {
+        THROW_ERROR("failed to create the test dir");
+    }
+    for (int i = 0; i < NUM_FILES; i++) {
+        char name[32];
+        snprintf(name, sizeof(name), "file_%d", i);
+        if (create_file(name) < 0) {
+            THROW_ERROR("failed to create a file");
+        }
+    }
+    return 0;
+}
+
+static void remove_test_dir(void) {
+    for (int i = 0; i < NUM_FILES; i++) {
+        char name[32];
+        snprintf(name, sizeof(name), "file_%d", i);
+        remove_file(name);
+    }
+    rmdir(TEST_DIR);
+}
+
+// Scan the test dir, and check every file created by create_test_dir is
+// reported exactly once
+static int scan_test_dir(void) {
+    int seen[NUM_FILES] = {0};
+    DIR *dirp = opendir(TEST_DIR);
+    if (dirp == NULL) {
+        THROW_ERROR("failed to open directory");
+    }
+    struct dirent *dp;
+    while ((dp = readdir(dirp)) != NULL) {
+        int i;
+        if (sscanf(dp->d_name, "file_%d", &i) == 1 && i >= 0 && i < NUM_FILES) {
+            seen[i]++;
+        }
+    }
+    closedir(dirp);
+    for (int i = 0; i < NUM_FILES; i++) {
+        if (seen[i] != 1) {
+            THROW_ERROR("file_%d is reported %d times", i, seen[i]);
+        }
+    }
+    return 0;
+}
+
+// Check that the inode number of every entry of the directory is the same
+// as what lstat returns for the entry, returning the number of entries
+static int check_dir_entry_inos(const char *dir_path) {
+    DIR *dirp = opendir(dir_path);
+    if (dirp == NULL) {
+        THROW_ERROR("failed to open directory %s", dir_path);
+    }
+    int num_entries = 0;
+    struct dirent *dp;
+    while ((dp = readdir(dirp)) != NULL) {
+        if (strcmp(dp->d_name, ".") == 0 || strcmp(dp->d_name, "..") == 0) {
+            continue;
+        }
+        char path[256];
+        snprintf(path, sizeof(path), "%s/%s", dir_path, dp->d_name);
+        struct stat stat_buf;
+        if (lstat(path, &stat_buf) < 0) {
+            closedir(dirp);
+            THROW_ERROR("failed to lstat %s", path);
+        }
+        if (dp->d_ino == 0 || dp->d_ino != stat_buf.st_ino) {
+            closedir(dirp);
+            THROW_ERROR("the d_ino of %s is not the st_ino", path);
+        }
+        num_entries++;
+    }
+    closedir(dirp);
+    return num_entries;
+}
+
+static volatile int creator_should_stop = 0;
+
+// Create and remove files in the test dir until told to stop
+static void *creator_thread_func(void *arg) {
+    int n = 0;
+    while (!creator_should_stop) {
+        char name[32];
+        snprintf(name, sizeof(name), "new_%d", n % NUM_FILES);
+        if (n / NUM_FILES % 2 == 0) {
+            create_file(name);
+        } else {
+            remove_file(name);
+        }
+        n++;
+    }
+    for (int i = 0; i < NUM_FILES; i++) {
+        char name[32];
+        snprintf(name, sizeof(name), "new_%d", i);
+        remove_file(name);
+    }
+    return NULL;
+}
+
 // ============================================================================
 // The test case of readdir
 // ============================================================================
@@ -74,6 +200,116 @@ static int test_getdents_with_too_small_buffer() {
     return 0;
 }
 
+static int test_readdir_with_concurrent_updates() {
+    if (create_test_dir() < 0) {
+        return -1;
+    }
+    pthread_t creator_thread;
+    creator_should_stop = 0;
+    if (pthread_create(&creator_thread, NULL, creator_thread_func, NULL) != 0) {
+        THROW_ERROR("failed to create the creator thread");
+    }
+    int ret = 0;
+    for (int i = 0; i < NUM_SCANS && ret == 0; i++) {
+        ret = scan_test_dir();
+    }
+    creator_should_stop = 1;
+    pthread_join(creator_thread, NULL);
+    return ret;
+}
+
+static int test_seekdir() {
+    DIR *dirp = opendir(TEST_DIR);
+    if (dirp == NULL) {
+        THROW_ERROR("failed to open directory");
+    }
+    struct dirent *dp;
+    for (int i = 0; i < NUM_FILES / 2; i++) {
+        if (readdir(dirp) == NULL) {
+            THROW_ERROR("failed to read the directory");
+        }
+    }
+    long pos = telldir(dirp);
+    if ((dp = readdir(dirp)) == NULL) {
+        THROW_ERROR("failed to read the directory");
+    }
+    char name[256];
+    strcpy(name, dp->d_name);
+    // Updates to the directory do not change the cookie
+    if (create_file("new_file") < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+    seekdir(dirp, pos);
+    if ((dp = readdir(dirp)) == NULL || strcmp(dp->d_name, name) != 0) {
+        THROW_ERROR("seekdir should reposition to the cookie");
+    }
+    remove_file("new_file");
+
+    rewinddir(dirp);
+    int num_entries = 0;
+    while (readdir(dirp) != NULL) {
+        num_entries++;
+    }
+    if (num_entries < NUM_FILES) {
+        THROW_ERROR("rewinddir should restart the scan");
+    }
+    closedir(dirp);
+    remove_test_dir();
+    return 0;
+}
+
+static int test_readdir_ino() {
+    if (check_dir_entry_inos("/") < 0) {
+        return -1;
+    }
+    // The entries of /root are on a different file system from /
+    if (check_dir_entry_inos("/root") < 0) {
+        return -1;
+    }
+    return 0;
+}
+
+static int test_readdir_ino_of_proc_self_fd() {
+    int fd = open("/", O_RDONLY | O_DIRECTORY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open a file");
+    }
+    int dup_fd = dup(fd);
+    if (dup_fd < 0) {
+        THROW_ERROR("failed to dup the fd");
+    }
+    int ret = check_dir_entry_inos("/proc/self/fd");
+    close(dup_fd);
+    close(fd);
+    if (ret < 0) {
+        return -1;
+    }
+    // At least the fds of stdio and the two fds above
+    if (ret < 5) {
+        THROW_ERROR("the entries of /proc/self/fd are missing");
+    }
+
+    // The fds of the same file have different inode numbers in procfs
+    char path[64];
+    struct stat fd_stat, dup_fd_stat;
+    fd = open("/", O_RDONLY | O_DIRECTORY);
+    dup_fd = dup(fd);
+    snprintf(path, sizeof(path), "/proc/self/fd/%d", fd);
+    if (lstat(path, &fd_stat) < 0) {
+        THROW_ERROR("failed to lstat %s", path);
+    }
+    snprintf(path, sizeof(path), "/proc/self/fd/%d", dup_fd);
+    if (lstat(path, &dup_fd_stat) < 0) {
+        THROW_ERROR("failed to lstat %s", path);
+    }
+    close(dup_fd);
+    close(fd);
+    if (fd_stat.st_ino == dup_fd_stat.st_ino) {
+        THROW_ERROR("the fds should have different inode numbers");
+    }
+    return 0;
+}
+
 // ============================================================================
 // Test suite main
 // ============================================================================
@@ -82,6 +318,10 @@ static test_case_t test_cases[] = {
     TEST_CASE(test_readdir),
     TEST_CASE(test_getdents_with_big_enough_buffer),
     TEST_CASE(test_getdents_with_too_small_buffer),
+    TEST_CASE(test_readdir_with_concurrent_updates),
+    TEST_CASE(test_seekdir),
+    TEST_CASE(test_readdir_ino),
+    TEST_CASE(test_readdir_ino_of_proc_self_fd),
 };
 
 int main() {
@@ -0,0 +1,117 @@
+#include <sys/stat.h>
+#include <sys/syscall.h>
+#include <sys/types.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <time.h>
+#include <unistd.h>
+#include <stdio.h>
+
+// The directory is on HostFS, in which listing a directory used to cost
+// quadratic time. It is left empty after the benchmark, as rmdir is not
+// supported by HostFS.
+#define TEST_DIR            "/host/test_readdir_perf"
+#define NUM_STEPS           5
+#define FILES_PER_STEP      10000
+#define BUF_SIZE            4096
+
+static char buf[BUF_SIZE];
+
+static unsigned long elapsed_ns(const struct timespec *start, const struct timespec *end) {
+    return (end->tv_sec - start->tv_sec) * 1000000000UL + (end->tv_nsec - start->tv_nsec);
+}
+
+static int create_files(int start, int end) {
+    char path[128];
+    for (int i = start; i < end; i++) {
+        snprintf(path, sizeof(path), TEST_DIR "/file_%d", i);
+        int fd = open(path, O_WRONLY | O_CREAT, 0644);
+        if (fd < 0) {
+            printf("ERROR: failed to create %s\n", path);
+            return -1;
+        }
+        close(fd);
+    }
+    return 0;
+}
+
+static void remove_files(int num_files) {
+    char path[128];
+    for (int i = 0; i < num_files; i++) {
+        snprintf(path, sizeof(path), TEST_DIR "/file_%d", i);
+        unlink(path);
+    }
+}
+
+// Read all the entries from the current position, returning the number of them
+static long read_entries(int fd) {
+    long num_entries = 0;
+    for (;;) {
+        long len = syscall(SYS_getdents64, fd, buf, sizeof(buf));
+        if (len < 0) {
+            printf("ERROR: failed to call getdents64\n");
+            return -1;
+        }
+        if (len == 0) {
+            return num_entries;
+        }
+        for (long pos = 0; pos < len;) {
+            unsigned short reclen = *(unsigned short *)(buf + pos + 16);
+            pos += reclen;
+            num_entries++;
+        }
+    }
+}
+
+static int bench_scan(int num_files) {
+    int fd = open(TEST_DIR, O_RDONLY | O_DIRECTORY);
+    if (fd < 0) {
+        printf("ERROR: failed to open the dir\n");
+        return -1;
+    }
+
+    struct timespec start, end;
+    clock_gettime(CLOCK_MONOTONIC, &start);
+    long num_entries = read_entries(fd);
+    clock_gettime(CLOCK_MONOTONIC, &end);
+    // The files, "." and ".."
+    if (num_entries != num_files + 2) {
+        printf("ERROR: %ld entries are read, but %d are expected\n", num_entries, num_files + 2);
+        close(fd);
+        return -1;
+    }
+    unsigned long total_ns = elapsed_ns(&start, &end);
+    printf("Scan of %d entries: total = %lu us, per entry = %lu ns\n",
+           num_files, total_ns / 1000, total_ns / num_entries);
+
+    // A seek to the start makes the entries read again
+    if (lseek(fd, 0, SEEK_SET) != 0 || read_entries(fd) != num_entries) {
+        printf("ERROR: failed to read the entries again after seeking to the start\n");
+        close(fd);
+        return -1;
+    }
+    close(fd);
+    return 0;
+}
+
+int main(int argc, const char *argv[]) {
+    if (mkdir(TEST_DIR, 0755) < 0 && errno != EEXIST) {
+        printf("ERROR: failed to create the dir\n");
+        return -1;
+    }
+    int ret = 0;
+    int num_files = 0;
+    for (int step = 0; step < NUM_STEPS; step++) {
+        if (create_files(num_files, num_files + FILES_PER_STEP) < 0) {
+            ret = -1;
+            break;
+        }
+        num_files += FILES_PER_STEP;
+        if (bench_scan(num_files) < 0) {
+            ret = -1;
+            break;
+        }
+    }
+    remove_files(num_files);
+    return ret;
+}
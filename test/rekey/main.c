@@ -0,0 +1,142 @@
+#include <sys/ioctl.h>
+#include <sys/stat.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <stdint.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+// ============================================================================
+// SEFS rekey ioctls, which must be in sync with src/libos/src/fs/sefs/rekey.rs
+// ============================================================================
+
+struct sefs_rekey_arg {
+    uint8_t new_key[16];
+};
+
+struct sefs_rekey_progress {
+    uint32_t in_progress;
+    uint32_t padding;
+    uint64_t total_files;
+    uint64_t done_files;
+};
+
+#define SEFS_IOC_REKEY                  _IOW('f', 0x80, struct sefs_rekey_arg)
+#define SEFS_IOC_GET_REKEY_PROGRESS     _IOR('f', 0x81, struct sefs_rekey_progress)
+
+#define SEFS_DIR        "/root"
+#define FILE_PATH       "/root/test_rekey.txt"
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static const char FILE_CONTENT[] = "The content must survive the rekey";
+
+static int rekey(const char *dir, uint8_t key_byte) {
+    struct sefs_rekey_arg arg;
+    memset(arg.new_key, key_byte, sizeof(arg.new_key));
+
+    int fd = open(dir, O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the directory");
+    }
+    int ret = ioctl(fd, SEFS_IOC_REKEY, &arg);
+    close(fd);
+    return ret;
+}
+
+static int check_file_content(const char *path) {
+    char buf[sizeof(FILE_CONTENT)] = {0};
+    int fd = open(path, O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the file");
+    }
+    if (read(fd, buf, sizeof(buf)) != sizeof(buf)) {
+        THROW_ERROR("failed to read the file");
+    }
+    close(fd);
+    if (strcmp(buf, FILE_CONTENT) != 0) {
+        THROW_ERROR("the content of the file is changed by the rekey");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for SEFS rekey
+// ============================================================================
+
+static int test_rekey() {
+    int fd = open(FILE_PATH, O_WRONLY | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+    if (write(fd, FILE_CONTENT, sizeof(FILE_CONTENT)) != sizeof(FILE_CONTENT)) {
+        THROW_ERROR("failed to write the file");
+    }
+    close(fd);
+
+    if (rekey(SEFS_DIR, 0x5a) < 0) {
+        THROW_ERROR("failed to rekey SEFS");
+    }
+    return check_file_content(FILE_PATH);
+}
+
+static int test_rekey_with_opened_file() {
+    // The file opened before the rekey must still be usable after the rekey
+    int fd = open(FILE_PATH, O_RDWR);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the file");
+    }
+    if (rekey(SEFS_DIR, 0xa5) < 0) {
+        THROW_ERROR("failed to rekey SEFS");
+    }
+    if (pwrite(fd, FILE_CONTENT, sizeof(FILE_CONTENT), 0) != sizeof(FILE_CONTENT)) {
+        THROW_ERROR("failed to write the file after the rekey");
+    }
+    close(fd);
+    return check_file_content(FILE_PATH);
+}
+
+static int test_get_rekey_progress() {
+    struct sefs_rekey_progress progress;
+    int fd = open(SEFS_DIR, O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the directory");
+    }
+    if (ioctl(fd, SEFS_IOC_GET_REKEY_PROGRESS, &progress) < 0) {
+        THROW_ERROR("failed to get the progress of the rekey");
+    }
+    close(fd);
+    if (progress.in_progress) {
+        THROW_ERROR("no rekey should be in progress");
+    }
+    if (progress.total_files == 0 || progress.done_files != progress.total_files) {
+        THROW_ERROR("the last rekey should have re-encrypted all files");
+    }
+    unlink(FILE_PATH);
+    return 0;
+}
+
+static int test_rekey_on_non_sefs() {
+    if (rekey("/tmp", 0x5a) == 0 || errno != ENOTTY) {
+        THROW_ERROR("rekey on a non-SEFS mount should fail with ENOTTY");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_rekey),
+    TEST_CASE(test_rekey_with_opened_file),
+    TEST_CASE(test_get_rekey_progress),
+    TEST_CASE(test_rekey_on_non_sefs),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
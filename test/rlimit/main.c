@@ -1,15 +1,333 @@
 #include <sys/resource.h>
-#include <stdio.h>
+#include <sys/uio.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <pthread.h>
+#include <signal.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
 
-int main(int argc, const char* argv[]) {
+#define FILE_PATH       "/root/test_rlimit_fsize.txt"
+#define FSIZE_LIMIT     4096
+// The limit of the live threads, including the main thread
+#define NPROC_LIMIT     4
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static struct rlimit old_fsize_rlim;
+
+static int set_fsize_limit(void) {
+    if (getrlimit(RLIMIT_FSIZE, &old_fsize_rlim) < 0) {
+        THROW_ERROR("failed to get RLIMIT_FSIZE");
+    }
+    // On Linux, SIGXFSZ terminates the process by default
+    signal(SIGXFSZ, SIG_IGN);
+    struct rlimit rlim = {
+        .rlim_cur = FSIZE_LIMIT,
+        .rlim_max = old_fsize_rlim.rlim_max,
+    };
+    if (setrlimit(RLIMIT_FSIZE, &rlim) < 0) {
+        THROW_ERROR("failed to set RLIMIT_FSIZE");
+    }
+    return 0;
+}
+
+static int restore_fsize_limit(void) {
+    if (setrlimit(RLIMIT_FSIZE, &old_fsize_rlim) < 0) {
+        THROW_ERROR("failed to restore RLIMIT_FSIZE");
+    }
+    return 0;
+}
+
+static int open_file(int flags, off_t size) {
+    int fd = open(FILE_PATH, flags | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+    if (ftruncate(fd, size) < 0) {
+        close(fd);
+        THROW_ERROR("failed to set the size of the file");
+    }
+    return fd;
+}
+
+// The test body is run with the limit set, which is restored on both success
+// and failure
+#define RUN_WITH_FSIZE_LIMIT(body) do { \
+    if (set_fsize_limit() < 0) { \
+        return -1; \
+    } \
+    int __ret = body(); \
+    if (restore_fsize_limit() < 0) { \
+        return -1; \
+    } \
+    unlink(FILE_PATH); \
+    return __ret; \
+} while (0)
+
+// ============================================================================
+// Test cases for RLIMIT_FSIZE
+// ============================================================================
+
+static int test_rlimit_as() {
     struct rlimit rlim;
     if (getrlimit(RLIMIT_AS, &rlim) < 0) {
-        printf("ERROR: getrlimit failed\n");
-        return -1;
+        THROW_ERROR("getrlimit failed");
     }
     if (setrlimit(RLIMIT_AS, &rlim) < 0) {
-        printf("ERROR: getrlimit failed\n");
+        THROW_ERROR("setrlimit failed");
+    }
+    return 0;
+}
+
+static int __test_write_at_fsize_limit() {
+    char buf[16] = {0};
+    int fd = open_file(O_WRONLY, FSIZE_LIMIT - sizeof(buf));
+    if (fd < 0) {
         return -1;
     }
+    lseek(fd, 0, SEEK_END);
+    // A write up to the limit succeeds
+    if (write(fd, buf, sizeof(buf)) != sizeof(buf)) {
+        THROW_ERROR("a write up to the limit should succeed");
+    }
+    // A write beyond the limit fails
+    if (write(fd, buf, 1) >= 0 || errno != EFBIG) {
+        THROW_ERROR("a write beyond the limit should fail with EFBIG");
+    }
+    close(fd);
     return 0;
 }
+
+static int test_write_at_fsize_limit() {
+    RUN_WITH_FSIZE_LIMIT(__test_write_at_fsize_limit);
+}
+
+static int __test_write_across_fsize_limit() {
+    char buf[16] = {0};
+    int fd = open_file(O_WRONLY, FSIZE_LIMIT - sizeof(buf) / 2);
+    if (fd < 0) {
+        return -1;
+    }
+    // A write across the limit is cut short at the limit
+    if (pwrite(fd, buf, sizeof(buf), FSIZE_LIMIT - sizeof(buf) / 2) != sizeof(buf) / 2) {
+        THROW_ERROR("a write across the limit should write up to the limit");
+    }
+    if (pwrite(fd, buf, sizeof(buf), FSIZE_LIMIT) >= 0 || errno != EFBIG) {
+        THROW_ERROR("a write beyond the limit should fail with EFBIG");
+    }
+
+    struct iovec iov[2] = {
+        { .iov_base = buf, .iov_len = sizeof(buf) / 4 },
+        { .iov_base = buf, .iov_len = sizeof(buf) },
+    };
+    lseek(fd, FSIZE_LIMIT - sizeof(buf) / 2, SEEK_SET);
+    if (writev(fd, iov, 2) != sizeof(buf) / 2) {
+        THROW_ERROR("a writev across the limit should write up to the limit");
+    }
+    if (lseek(fd, 0, SEEK_END) != FSIZE_LIMIT) {
+        THROW_ERROR("the file should not be extended beyond the limit");
+    }
+    close(fd);
+    return 0;
+}
+
+static int test_write_across_fsize_limit() {
+    RUN_WITH_FSIZE_LIMIT(__test_write_across_fsize_limit);
+}
+
+static int __test_append_at_fsize_limit() {
+    char buf[16] = {0};
+    int fd = open_file(O_WRONLY | O_APPEND, FSIZE_LIMIT - sizeof(buf) / 2);
+    if (fd < 0) {
+        return -1;
+    }
+    // The limit is checked at the EOF, not the current offset
+    lseek(fd, 0, SEEK_SET);
+    if (write(fd, buf, sizeof(buf)) != sizeof(buf) / 2) {
+        THROW_ERROR("an append across the limit should write up to the limit");
+    }
+    if (write(fd, buf, sizeof(buf)) >= 0 || errno != EFBIG) {
+        THROW_ERROR("an append beyond the limit should fail with EFBIG");
+    }
+    close(fd);
+    return 0;
+}
+
+static int test_append_at_fsize_limit() {
+    RUN_WITH_FSIZE_LIMIT(__test_append_at_fsize_limit);
+}
+
+static volatile int sigxfsz_count;
+
+static void handle_sigxfsz(int signum) {
+    sigxfsz_count++;
+}
+
+static int __test_sigxfsz_beyond_fsize_limit() {
+    struct sigaction sa = { 0 };
+    sa.sa_handler = handle_sigxfsz;
+    if (sigaction(SIGXFSZ, &sa, NULL) < 0) {
+        THROW_ERROR("failed to set the SIGXFSZ handler");
+    }
+    sigxfsz_count = 0;
+
+    char buf[16] = {0};
+    int fd = open_file(O_WRONLY, FSIZE_LIMIT - sizeof(buf) / 2);
+    if (fd < 0) {
+        return -1;
+    }
+    // A write cut short at the limit is not signaled
+    if (pwrite(fd, buf, sizeof(buf), FSIZE_LIMIT - sizeof(buf) / 2) != sizeof(buf) / 2) {
+        THROW_ERROR("a write across the limit should write up to the limit");
+    }
+    if (sigxfsz_count != 0) {
+        THROW_ERROR("a write cut short should not raise SIGXFSZ");
+    }
+    if (pwrite(fd, buf, sizeof(buf), FSIZE_LIMIT) >= 0 || errno != EFBIG) {
+        THROW_ERROR("a write beyond the limit should fail with EFBIG");
+    }
+    if (sigxfsz_count != 1) {
+        THROW_ERROR("a write beyond the limit should raise SIGXFSZ");
+    }
+    if (ftruncate(fd, FSIZE_LIMIT + 1) >= 0 || errno != EFBIG) {
+        THROW_ERROR("a truncate beyond the limit should fail with EFBIG");
+    }
+    if (sigxfsz_count != 2) {
+        THROW_ERROR("a truncate beyond the limit should raise SIGXFSZ");
+    }
+    if (fallocate(fd, 0, FSIZE_LIMIT, sizeof(buf)) >= 0 || errno != EFBIG) {
+        THROW_ERROR("a fallocate beyond the limit should fail with EFBIG");
+    }
+    if (sigxfsz_count != 3) {
+        THROW_ERROR("a fallocate beyond the limit should raise SIGXFSZ");
+    }
+    close(fd);
+    signal(SIGXFSZ, SIG_IGN);
+    return 0;
+}
+
+static int test_sigxfsz_beyond_fsize_limit() {
+    RUN_WITH_FSIZE_LIMIT(__test_sigxfsz_beyond_fsize_limit);
+}
+
+static volatile int num_running_threads;
+static volatile int threads_should_exit;
+
+static void *wait_until_told_to_exit(void *arg) {
+    __sync_fetch_and_add(&num_running_threads, 1);
+    while (!threads_should_exit) {
+        usleep(1000);
+    }
+    return NULL;
+}
+
+static int __test_rlimit_nproc() {
+    pthread_t threads[NPROC_LIMIT];
+    int num_threads = 0;
+    int ret = 0;
+    num_running_threads = 0;
+    threads_should_exit = 0;
+    for (; num_threads < NPROC_LIMIT; num_threads++) {
+        ret = pthread_create(&threads[num_threads], NULL, wait_until_told_to_exit, NULL);
+        if (ret != 0) {
+            break;
+        }
+    }
+    while (num_running_threads < num_threads) {
+        usleep(1000);
+    }
+    // The existing threads keep running after a thread fails to be created
+    int num_running = num_running_threads;
+
+    threads_should_exit = 1;
+    for (int i = 0; i < num_threads; i++) {
+        pthread_join(threads[i], NULL);
+    }
+    if (ret != EAGAIN) {
+        THROW_ERROR("the thread beyond the limit should fail to be created with EAGAIN");
+    }
+    // The main thread is counted, as may be the threads of other processes
+    if (num_threads >= NPROC_LIMIT) {
+        THROW_ERROR("the threads should be created up to the limit");
+    }
+    if (num_running != num_threads) {
+        THROW_ERROR("the created threads should keep running");
+    }
+
+    // The exited threads are no longer counted
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, wait_until_told_to_exit, NULL) != 0) {
+        THROW_ERROR("a thread should be created after the other threads exit");
+    }
+    pthread_join(thread, NULL);
+    return 0;
+}
+
+static int test_rlimit_nproc() {
+    struct rlimit old_rlim;
+    if (getrlimit(RLIMIT_NPROC, &old_rlim) < 0) {
+        THROW_ERROR("failed to get RLIMIT_NPROC");
+    }
+    struct rlimit rlim = {
+        .rlim_cur = NPROC_LIMIT,
+        .rlim_max = old_rlim.rlim_max,
+    };
+    if (setrlimit(RLIMIT_NPROC, &rlim) < 0) {
+        THROW_ERROR("failed to set RLIMIT_NPROC");
+    }
+    int ret = __test_rlimit_nproc();
+    if (setrlimit(RLIMIT_NPROC, &old_rlim) < 0) {
+        THROW_ERROR("failed to restore RLIMIT_NPROC");
+    }
+    return ret;
+}
+
+static int test_rlimit_nproc_default() {
+    // The threads are bounded by default, e.g., by the TCSs of the enclave
+    struct rlimit rlim;
+    if (getrlimit(RLIMIT_NPROC, &rlim) < 0) {
+        THROW_ERROR("failed to get RLIMIT_NPROC");
+    }
+    if (rlim.rlim_cur == RLIM_INFINITY || rlim.rlim_max == RLIM_INFINITY) {
+        THROW_ERROR("RLIMIT_NPROC should be bounded by default");
+    }
+    return 0;
+}
+
+static int test_rlimit_soft_above_hard() {
+    struct rlimit rlim;
+    if (getrlimit(RLIMIT_NPROC, &rlim) < 0) {
+        THROW_ERROR("failed to get RLIMIT_NPROC");
+    }
+    struct rlimit invalid_rlim = {
+        .rlim_cur = NPROC_LIMIT + 1,
+        .rlim_max = NPROC_LIMIT,
+    };
+    if (setrlimit(RLIMIT_NPROC, &invalid_rlim) == 0 || errno != EINVAL) {
+        THROW_ERROR("the soft limit above the hard limit should fail with EINVAL");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_rlimit_as),
+    TEST_CASE(test_write_at_fsize_limit),
+    TEST_CASE(test_write_across_fsize_limit),
+    TEST_CASE(test_append_at_fsize_limit),
+    TEST_CASE(test_sigxfsz_beyond_fsize_limit),
+    TEST_CASE(test_rlimit_nproc),
+    TEST_CASE(test_rlimit_nproc_default),
+    TEST_CASE(test_rlimit_soft_above_hard),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -154,6 +154,52 @@ static int test_sched_setaffinity_with_null_buffer() {
     return 0;
 }
 
+// ============================================================================
+// Test cases for getcpu
+// ============================================================================
+
+static int test_getcpu() {
+    int nproc = sysconf(_SC_NPROCESSORS_ONLN);
+    unsigned int cpu = -1, node = -1;
+    if (syscall(__NR_getcpu, &cpu, &node, NULL) < 0) {
+        THROW_ERROR("failed to call __NR_getcpu");
+    }
+    if (cpu >= nproc) {
+        THROW_ERROR("cpu id is out of range");
+    }
+    if (syscall(__NR_getcpu, NULL, NULL, NULL) < 0) {
+        THROW_ERROR("failed to call __NR_getcpu with NULL arguments");
+    }
+    return 0;
+}
+
+static int test_getcpu_after_setaffinity() {
+    int nproc = sysconf(_SC_NPROCESSORS_ONLN);
+    cpu_set_t mask_old;
+    if (sched_getaffinity(0, sizeof(cpu_set_t), &mask_old) < 0) {
+        THROW_ERROR("failed to call sched_getaffinity");
+    }
+    for (int i = 0; i < nproc; ++i) {
+        cpu_set_t mask;
+        CPU_ZERO(&mask);
+        CPU_SET(i, &mask);
+        if (sched_setaffinity(0, sizeof(cpu_set_t), &mask) < 0) {
+            THROW_ERROR("failed to call sched_setaffinity");
+        }
+        unsigned int cpu;
+        if (syscall(__NR_getcpu, &cpu, NULL, NULL) < 0) {
+            THROW_ERROR("failed to call __NR_getcpu");
+        }
+        if (cpu != i) {
+            THROW_ERROR("getcpu returns %u, but the thread is pinned to cpu %d", cpu, i);
+        }
+    }
+    if (sched_setaffinity(0, sizeof(cpu_set_t), &mask_old) < 0) {
+        THROW_ERROR("recover cpuset error");
+    }
+    return 0;
+}
+
 // ============================================================================
 // Test cases for sched_yield
 // ============================================================================
@@ -180,6 +226,8 @@ static test_case_t test_cases[] = {
     TEST_CASE(test_sched_setaffinity_with_zero_cpusetsize),
     TEST_CASE(test_sched_getaffinity_with_null_buffer),
     TEST_CASE(test_sched_setaffinity_with_null_buffer),
+    TEST_CASE(test_getcpu),
+    TEST_CASE(test_getcpu_after_setaffinity),
     TEST_CASE(test_sched_yield),
 };
 
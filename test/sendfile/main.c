@@ -0,0 +1,198 @@
+#include <sys/sendfile.h>
+#include <sys/socket.h>
+#include <netinet/in.h>
+#include <arpa/inet.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define FILE_PATH           "/root/test_sendfile.txt"
+#define FILE_SIZE           (64 * 1024)
+#define LARGE_FILE_SIZE     (4 * 1024 * 1024)
+#define SERVER_PORT         8810
+
+static char file_data[FILE_SIZE];
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int create_file(size_t size) {
+    int fd = open(FILE_PATH, O_RDWR | O_CREAT | O_TRUNC, 0666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+    for (size_t written = 0; written < size; written += FILE_SIZE) {
+        if (write(fd, file_data, FILE_SIZE) != FILE_SIZE) {
+            THROW_ERROR("failed to write the file");
+        }
+    }
+    if (lseek(fd, 0, SEEK_SET) != 0) {
+        THROW_ERROR("failed to rewind the file");
+    }
+    return fd;
+}
+
+static int read_all(int fd, char *buf, size_t len) {
+    size_t total_len = 0;
+    while (total_len < len) {
+        ssize_t ret = read(fd, buf + total_len, len - total_len);
+        if (ret <= 0) {
+            THROW_ERROR("failed to read the data");
+        }
+        total_len += ret;
+    }
+    return 0;
+}
+
+static int connect_sockets(int *client_fd, int *server_fd) {
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_port = htons(SERVER_PORT);
+    addr.sin_addr.s_addr = inet_addr("127.0.0.1");
+
+    int listen_fd = socket(AF_INET, SOCK_STREAM, 0);
+    int reuse = 1;
+    setsockopt(listen_fd, SOL_SOCKET, SO_REUSEADDR, &reuse, sizeof(reuse));
+    if (listen_fd < 0 || bind(listen_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0 ||
+            listen(listen_fd, 1) < 0) {
+        THROW_ERROR("failed to listen");
+    }
+    *client_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (*client_fd < 0 || connect(*client_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        THROW_ERROR("failed to connect");
+    }
+    *server_fd = accept(listen_fd, NULL, NULL);
+    if (*server_fd < 0) {
+        THROW_ERROR("failed to accept");
+    }
+    close(listen_fd);
+    return 0;
+}
+
+// ============================================================================
+// Test cases for sendfile
+// ============================================================================
+
+static int test_sendfile_to_pipe() {
+    int fd = create_file(FILE_SIZE);
+    int pipe_fds[2];
+    if (fd < 0 || pipe(pipe_fds) < 0) {
+        THROW_ERROR("failed to create the file or the pipe");
+    }
+    char buf[FILE_SIZE];
+
+    // With the offset, which is advanced while the file position is not
+    off_t offset = 100;
+    if (sendfile(pipe_fds[1], fd, &offset, 1000) != 1000 || offset != 1100) {
+        THROW_ERROR("sendfile with an offset failed");
+    }
+    if (read_all(pipe_fds[0], buf, 1000) < 0 || memcmp(buf, file_data + 100, 1000) != 0) {
+        THROW_ERROR("incorrect data sent with an offset");
+    }
+    if (lseek(fd, 0, SEEK_CUR) != 0) {
+        THROW_ERROR("the file position should not be changed");
+    }
+
+    // Without the offset, the file position is used and advanced
+    if (sendfile(pipe_fds[1], fd, NULL, 2000) != 2000 || lseek(fd, 0, SEEK_CUR) != 2000) {
+        THROW_ERROR("sendfile without an offset failed");
+    }
+    if (read_all(pipe_fds[0], buf, 2000) < 0 || memcmp(buf, file_data, 2000) != 0) {
+        THROW_ERROR("incorrect data sent without an offset");
+    }
+
+    // At EOF, nothing is sent
+    offset = FILE_SIZE;
+    if (sendfile(pipe_fds[1], fd, &offset, 1000) != 0 || offset != FILE_SIZE) {
+        THROW_ERROR("sendfile at EOF should return 0");
+    }
+
+    close(pipe_fds[0]);
+    close(pipe_fds[1]);
+    close(fd);
+    return 0;
+}
+
+static int test_sendfile_to_socket() {
+    int fd = create_file(FILE_SIZE);
+    int client_fd, server_fd;
+    if (fd < 0 || connect_sockets(&client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    if (sendfile(client_fd, fd, NULL, FILE_SIZE) != FILE_SIZE) {
+        THROW_ERROR("sendfile to a socket failed");
+    }
+    char buf[FILE_SIZE];
+    if (read_all(server_fd, buf, FILE_SIZE) < 0 || memcmp(buf, file_data, FILE_SIZE) != 0) {
+        THROW_ERROR("incorrect data sent to the socket");
+    }
+    close(client_fd);
+    close(server_fd);
+    close(fd);
+    return 0;
+}
+
+static int test_sendfile_to_nonblocking_socket() {
+    int fd = create_file(LARGE_FILE_SIZE);
+    int client_fd, server_fd;
+    if (fd < 0 || connect_sockets(&client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    int flags = fcntl(client_fd, F_GETFL);
+    if (fcntl(client_fd, F_SETFL, flags | O_NONBLOCK) < 0) {
+        THROW_ERROR("failed to set the socket nonblocking");
+    }
+
+    // The socket buffer cannot hold the whole file, so the send is short
+    ssize_t len = sendfile(client_fd, fd, NULL, LARGE_FILE_SIZE);
+    if (len <= 0 || len >= LARGE_FILE_SIZE) {
+        THROW_ERROR("sendfile to a nonblocking socket should be short");
+    }
+    if (lseek(fd, 0, SEEK_CUR) != len) {
+        THROW_ERROR("the file position should be advanced by the bytes sent");
+    }
+    close(client_fd);
+    close(server_fd);
+    close(fd);
+    return 0;
+}
+
+static int test_sendfile_with_invalid_in_fd() {
+    int pipe_fds[2];
+    int out_fds[2];
+    if (pipe(pipe_fds) < 0 || pipe(out_fds) < 0) {
+        THROW_ERROR("failed to create pipes");
+    }
+    if (sendfile(out_fds[1], pipe_fds[0], NULL, 100) >= 0 || errno != EINVAL) {
+        THROW_ERROR("sendfile from a pipe should fail with EINVAL");
+    }
+    close(pipe_fds[0]);
+    close(pipe_fds[1]);
+    close(out_fds[0]);
+    close(out_fds[1]);
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_sendfile_to_pipe),
+    TEST_CASE(test_sendfile_to_socket),
+    TEST_CASE(test_sendfile_to_nonblocking_socket),
+    TEST_CASE(test_sendfile_with_invalid_in_fd),
+};
+
+int main() {
+    for (int i = 0; i < FILE_SIZE; i++) {
+        file_data[i] = (char)(i % 251);
+    }
+    int ret = test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+    unlink(FILE_PATH);
+    return ret;
+}
@@ -0,0 +1,285 @@
+#include <sys/socket.h>
+#include <sys/types.h>
+#include <sys/uio.h>
+#include <arpa/inet.h>
+#include <netinet/in.h>
+#include <errno.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define TCP_PORT            8826
+#define UDP_PORT            8827
+#define MAX_NSENDS          100
+
+// The message sent in three iovecs, and received in three iovecs of the
+// different sizes
+static const char *msg_parts[] = { "The message ", "spans three ", "iovecs." };
+#define MSG_LEN             31
+static const size_t recv_part_lens[] = { 5, 20, 6 };
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static void set_addr(struct sockaddr_in *addr, int port) {
+    memset(addr, 0, sizeof(*addr));
+    addr->sin_family = AF_INET;
+    addr->sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    addr->sin_port = htons(port);
+}
+
+static int connect_tcp_pair(int *client_fd, int *server_fd) {
+    struct sockaddr_in addr;
+    set_addr(&addr, TCP_PORT);
+    int listen_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (listen_fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    int reuse = 1;
+    setsockopt(listen_fd, SOL_SOCKET, SO_REUSEADDR, &reuse, sizeof(reuse));
+    if (bind(listen_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0 ||
+            listen(listen_fd, 1) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to listen");
+    }
+
+    *client_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (*client_fd < 0 || connect(*client_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to connect");
+    }
+    *server_fd = accept(listen_fd, NULL, NULL);
+    close(listen_fd);
+    if (*server_fd < 0) {
+        close(*client_fd);
+        THROW_ERROR("failed to accept");
+    }
+    return 0;
+}
+
+static int connect_udp_pair(int *send_fd, int *recv_fd) {
+    struct sockaddr_in addr;
+    set_addr(&addr, UDP_PORT);
+    *recv_fd = socket(AF_INET, SOCK_DGRAM, 0);
+    if (*recv_fd < 0 || bind(*recv_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        THROW_ERROR("failed to bind a UDP socket");
+    }
+    *send_fd = socket(AF_INET, SOCK_DGRAM, 0);
+    if (*send_fd < 0 || connect(*send_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        close(*recv_fd);
+        THROW_ERROR("failed to connect a UDP socket");
+    }
+    return 0;
+}
+
+static ssize_t send_msg_parts(int fd, int flags) {
+    struct iovec iov[3];
+    for (int i = 0; i < 3; i++) {
+        iov[i].iov_base = (void *)msg_parts[i];
+        iov[i].iov_len = strlen(msg_parts[i]);
+    }
+    struct msghdr msg = { .msg_iov = iov, .msg_iovlen = 3 };
+    return sendmsg(fd, &msg, flags);
+}
+
+// Receive into three iovecs, and join the received data in buf
+static ssize_t recv_msg_parts(int fd, char *buf, int flags, int *msg_flags) {
+    char parts[3][32];
+    struct iovec iov[3];
+    for (int i = 0; i < 3; i++) {
+        iov[i].iov_base = parts[i];
+        iov[i].iov_len = recv_part_lens[i];
+    }
+    // The flags are output only, which are set on return
+    struct msghdr msg = { .msg_iov = iov, .msg_iovlen = 3, .msg_flags = -1 };
+    ssize_t len = recvmsg(fd, &msg, flags);
+    if (len < 0) {
+        return len;
+    }
+    size_t copied = 0;
+    for (int i = 0; i < 3 && copied < len; i++) {
+        size_t part_len = len - copied < iov[i].iov_len ? len - copied : iov[i].iov_len;
+        memcpy(buf + copied, parts[i], part_len);
+        copied += part_len;
+    }
+    *msg_flags = msg.msg_flags;
+    return len;
+}
+
+static void get_msg(char *buf) {
+    buf[0] = '\0';
+    for (int i = 0; i < 3; i++) {
+        strcat(buf, msg_parts[i]);
+    }
+}
+
+static int __test_stream_iovs(int send_fd, int recv_fd) {
+    char msg[MSG_LEN + 1];
+    char buf[MSG_LEN + 1] = { 0 };
+    get_msg(msg);
+    if (send_msg_parts(send_fd, 0) != MSG_LEN) {
+        THROW_ERROR("failed to send the message in three iovecs");
+    }
+    // A stream may be received in pieces
+    size_t recvd = 0;
+    while (recvd < MSG_LEN) {
+        int msg_flags;
+        char piece[MSG_LEN];
+        ssize_t len = recv_msg_parts(recv_fd, piece, 0, &msg_flags);
+        if (len <= 0) {
+            THROW_ERROR("failed to receive the message in three iovecs");
+        }
+        if (msg_flags != 0) {
+            THROW_ERROR("no flags should be returned for a stream");
+        }
+        memcpy(buf + recvd, piece, len);
+        recvd += len;
+    }
+    if (strcmp(buf, msg) != 0) {
+        THROW_ERROR("the message is not reassembled correctly: %s", buf);
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for sendmsg and recvmsg with multiple iovecs
+// ============================================================================
+
+static int test_tcp_iovs() {
+    int client_fd, server_fd;
+    if (connect_tcp_pair(&client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    int ret = __test_stream_iovs(client_fd, server_fd);
+    if (ret == 0) {
+        ret = __test_stream_iovs(server_fd, client_fd);
+    }
+    close(client_fd);
+    close(server_fd);
+    return ret;
+}
+
+static int test_unix_socket_iovs() {
+    int fds[2];
+    if (socketpair(AF_UNIX, SOCK_STREAM, 0, fds) < 0) {
+        THROW_ERROR("failed to create a socket pair");
+    }
+    int ret = __test_stream_iovs(fds[0], fds[1]);
+    if (ret == 0) {
+        ret = __test_stream_iovs(fds[1], fds[0]);
+    }
+    close(fds[0]);
+    close(fds[1]);
+    return ret;
+}
+
+static int test_udp_one_datagram() {
+    int send_fd, recv_fd;
+    if (connect_udp_pair(&send_fd, &recv_fd) < 0) {
+        return -1;
+    }
+    int ret = -1;
+    char msg[MSG_LEN + 1];
+    char buf[MSG_LEN + 1] = { 0 };
+    get_msg(msg);
+
+    // Two datagrams are never received at once
+    if (send_msg_parts(send_fd, 0) != MSG_LEN || send(send_fd, "next", 4, 0) != 4) {
+        printf("\t\tERROR: failed to send the datagrams\n");
+        goto out;
+    }
+    int msg_flags;
+    if (recv_msg_parts(recv_fd, buf, 0, &msg_flags) != MSG_LEN || strcmp(buf, msg) != 0 ||
+            (msg_flags & MSG_TRUNC)) {
+        printf("\t\tERROR: the first datagram should be received alone and whole\n");
+        goto out;
+    }
+    if (recv_msg_parts(recv_fd, buf, 0, &msg_flags) != 4 || strncmp(buf, "next", 4) != 0) {
+        printf("\t\tERROR: the second datagram should be received alone\n");
+        goto out;
+    }
+    ret = 0;
+out:
+    close(send_fd);
+    close(recv_fd);
+    return ret;
+}
+
+static int test_udp_truncated() {
+    int send_fd, recv_fd;
+    if (connect_udp_pair(&send_fd, &recv_fd) < 0) {
+        return -1;
+    }
+    int ret = -1;
+    char long_msg[MSG_LEN + 10];
+    char buf[MSG_LEN + 10];
+    memset(long_msg, 'x', sizeof(long_msg));
+
+    // The rest of a truncated datagram is discarded
+    if (send(send_fd, long_msg, sizeof(long_msg), 0) != sizeof(long_msg) ||
+            send(send_fd, long_msg, sizeof(long_msg), 0) != sizeof(long_msg)) {
+        printf("\t\tERROR: failed to send the datagrams\n");
+        goto out;
+    }
+    int msg_flags;
+    if (recv_msg_parts(recv_fd, buf, 0, &msg_flags) != MSG_LEN || !(msg_flags & MSG_TRUNC)) {
+        printf("\t\tERROR: the datagram should be truncated with MSG_TRUNC\n");
+        goto out;
+    }
+    // With MSG_TRUNC, the real length of the datagram is returned
+    ssize_t len = recv_msg_parts(recv_fd, buf, MSG_TRUNC, &msg_flags);
+    if (len != sizeof(long_msg) || !(msg_flags & MSG_TRUNC) ||
+            memcmp(buf, long_msg, MSG_LEN) != 0) {
+        printf("\t\tERROR: the real length should be returned with MSG_TRUNC\n");
+        goto out;
+    }
+    ret = 0;
+out:
+    close(send_fd);
+    close(recv_fd);
+    return ret;
+}
+
+static int test_msg_nosignal() {
+    int client_fd, server_fd;
+    if (connect_tcp_pair(&client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    close(server_fd);
+    // The sends may succeed until the reset by the peer arrives, and then
+    // fail with EPIPE instead of raising SIGPIPE
+    int ret = -1;
+    for (int i = 0; i < MAX_NSENDS; i++) {
+        if (send_msg_parts(client_fd, MSG_NOSIGNAL) < 0) {
+            if (errno == EPIPE || errno == ECONNRESET) {
+                ret = 0;
+            } else {
+                printf("\t\tERROR: the send should fail with EPIPE\n");
+            }
+            break;
+        }
+        usleep(1000);
+    }
+    if (ret < 0) {
+        printf("\t\tERROR: the send to the closed peer should fail\n");
+    }
+    close(client_fd);
+    return ret;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_tcp_iovs),
+    TEST_CASE(test_unix_socket_iovs),
+    TEST_CASE(test_udp_one_datagram),
+    TEST_CASE(test_udp_truncated),
+    TEST_CASE(test_msg_nosignal),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,234 @@
+#define _GNU_SOURCE
+#include <sys/stat.h>
+#include <sys/types.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <grp.h>
+#include <pthread.h>
+#include <unistd.h>
+#include "test.h"
+
+#define USER_ID             1000
+#define OTHER_USER_ID       2000
+
+static const char *file_path = "/root/test_setuid.txt";
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int create_file(mode_t mode) {
+    int fd = open(file_path, O_CREAT | O_TRUNC | O_WRONLY, mode);
+    if (fd < 0) {
+        THROW_ERROR("failed to create the file");
+    }
+    close(fd);
+    // The mode given to open is masked by the umask
+    if (chmod(file_path, mode) < 0) {
+        THROW_ERROR("failed to chmod the file");
+    }
+    return 0;
+}
+
+static int check_uids(uid_t ruid, uid_t euid, uid_t suid) {
+    uid_t r, e, s;
+    if (getresuid(&r, &e, &s) < 0) {
+        THROW_ERROR("failed to get the uids");
+    }
+    if (r != ruid || e != euid || s != suid || getuid() != ruid || geteuid() != euid) {
+        THROW_ERROR("the uids are not as expected");
+    }
+    return 0;
+}
+
+static void *get_uid_func(void *arg) {
+    *(uid_t *)arg = getuid();
+    return NULL;
+}
+
+// ============================================================================
+// Test cases
+// ============================================================================
+
+static int test_initial_ids() {
+    gid_t rgid, egid, sgid;
+    if (check_uids(0, 0, 0) < 0) {
+        return -1;
+    }
+    if (getresgid(&rgid, &egid, &sgid) < 0 || rgid != 0 || egid != 0 || sgid != 0) {
+        THROW_ERROR("the gids should be 0");
+    }
+    return 0;
+}
+
+static int test_drop_euid_temporarily() {
+    if (create_file(0600) < 0) {
+        return -1;
+    }
+
+    if (setresuid(-1, USER_ID, -1) < 0) {
+        THROW_ERROR("failed to drop the euid");
+    }
+    if (check_uids(0, USER_ID, 0) < 0) {
+        return -1;
+    }
+    if (open(file_path, O_RDONLY) >= 0 || errno != EACCES) {
+        THROW_ERROR("the file of root should not be opened");
+    }
+    if (chown(file_path, USER_ID, USER_ID) == 0 || errno != EPERM) {
+        THROW_ERROR("chown should fail without privilege");
+    }
+    // The saved uid is 0, so the privilege can be regained
+    if (setresuid(-1, 0, -1) < 0) {
+        THROW_ERROR("failed to regain the euid");
+    }
+
+    int fd = open(file_path, O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the file after regaining the privilege");
+    }
+    close(fd);
+    unlink(file_path);
+    return 0;
+}
+
+static int test_owner_without_privilege() {
+    if (create_file(0600) < 0) {
+        return -1;
+    }
+    if (chown(file_path, USER_ID, USER_ID) < 0) {
+        THROW_ERROR("failed to chown the file");
+    }
+    struct stat stat_buf;
+    if (stat(file_path, &stat_buf) < 0 || stat_buf.st_uid != USER_ID ||
+            stat_buf.st_gid != USER_ID) {
+        THROW_ERROR("the owner is not changed");
+    }
+
+    if (setresuid(-1, USER_ID, -1) < 0) {
+        THROW_ERROR("failed to drop the euid");
+    }
+    int fd = open(file_path, O_RDWR);
+    if (fd < 0) {
+        THROW_ERROR("the owner should be able to open the file");
+    }
+    close(fd);
+    if (chmod(file_path, 0644) < 0) {
+        THROW_ERROR("the owner should be able to chmod the file");
+    }
+    if (chown(file_path, OTHER_USER_ID, -1) == 0 || errno != EPERM) {
+        THROW_ERROR("the owner should not be able to give the file away");
+    }
+    if (setresuid(-1, 0, -1) < 0) {
+        THROW_ERROR("failed to regain the euid");
+    }
+
+    unlink(file_path);
+    return 0;
+}
+
+static int test_root_with_files_of_others() {
+    if (create_file(0600) < 0) {
+        return -1;
+    }
+    if (chown(file_path, USER_ID, USER_ID) < 0) {
+        THROW_ERROR("failed to chown the file");
+    }
+
+    // The permission bits of the files of other users, e.g., the host files on
+    // hostfs, are bypassed by root
+    int fd = open(file_path, O_RDWR);
+    if (fd < 0) {
+        THROW_ERROR("root should be able to open the file of another user");
+    }
+    close(fd);
+    if (access(file_path, R_OK | W_OK) < 0) {
+        THROW_ERROR("the file of another user should be accessible to root");
+    }
+    if (chmod(file_path, 0644) < 0) {
+        THROW_ERROR("root should be able to chmod the file of another user");
+    }
+    fd = open(file_path, O_RDONLY | O_NOATIME);
+    if (fd < 0) {
+        THROW_ERROR("root should be able to open the file of another user with O_NOATIME");
+    }
+    close(fd);
+
+    unlink(file_path);
+    return 0;
+}
+
+// Must be the last test case, since the privilege is dropped for good
+static int test_drop_privilege_permanently() {
+    if (create_file(0644) < 0) {
+        return -1;
+    }
+
+    if (setgroups(0, NULL) < 0 || setgid(USER_ID) < 0 || setuid(USER_ID) < 0) {
+        THROW_ERROR("failed to drop the privilege");
+    }
+    if (check_uids(USER_ID, USER_ID, USER_ID) < 0) {
+        return -1;
+    }
+    if (getgid() != USER_ID || getegid() != USER_ID || getgroups(0, NULL) != 0) {
+        THROW_ERROR("the gids are not as expected");
+    }
+
+    if (setuid(0) == 0 || errno != EPERM) {
+        THROW_ERROR("the privilege should not be regained");
+    }
+    gid_t groups[] = { 0 };
+    if (setgroups(1, groups) == 0 || errno != EPERM) {
+        THROW_ERROR("setgroups should fail without privilege");
+    }
+    if (chown(file_path, USER_ID, USER_ID) == 0 || errno != EPERM) {
+        THROW_ERROR("chown should fail without privilege");
+    }
+    if (chmod(file_path, 0666) == 0 || errno != EPERM) {
+        THROW_ERROR("chmod should fail for the file of another user");
+    }
+    if (open(file_path, O_WRONLY) >= 0 || errno != EACCES) {
+        THROW_ERROR("the file of root should not be written");
+    }
+    // O_NOATIME is only allowed for the owner of the file
+    if (open(file_path, O_RDONLY | O_NOATIME) >= 0 || errno != EPERM) {
+        THROW_ERROR("the file of root should not be opened with O_NOATIME");
+    }
+    int fd = open(file_path, O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the file of root");
+    }
+    if (fcntl(fd, F_SETFL, O_NOATIME) == 0 || errno != EPERM) {
+        close(fd);
+        THROW_ERROR("O_NOATIME should not be set on the file of root");
+    }
+    close(fd);
+
+    // The uids are shared by the threads
+    uid_t thread_uid = 0;
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, get_uid_func, &thread_uid) != 0) {
+        THROW_ERROR("failed to create the thread");
+    }
+    pthread_join(thread, NULL);
+    if (thread_uid != USER_ID) {
+        THROW_ERROR("the thread should see the dropped uid");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_initial_ids),
+    TEST_CASE(test_drop_euid_temporarily),
+    TEST_CASE(test_owner_without_privilege),
+    TEST_CASE(test_root_with_files_of_others),
+    TEST_CASE(test_drop_privilege_permanently),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,577 @@
+#define _GNU_SOURCE
+#include <sys/socket.h>
+#include <sys/syscall.h>
+#include <netinet/in.h>
+#include <arpa/inet.h>
+#include <errno.h>
+#include <pthread.h>
+#include <sched.h>
+#include <signal.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static pid_t sys_gettid() {
+    return syscall(SYS_gettid);
+}
+
+static int sys_tkill(pid_t tid, int sig) {
+    return syscall(SYS_tkill, tid, sig);
+}
+
+static int sys_tgkill(pid_t tgid, pid_t tid, int sig) {
+    return syscall(SYS_tgkill, tgid, tid, sig);
+}
+
+static pid_t thread_tid;
+static pthread_mutex_t thread_mutex = PTHREAD_MUTEX_INITIALIZER;
+static pthread_cond_t thread_cond = PTHREAD_COND_INITIALIZER;
+static int thread_started = 0;
+static int thread_should_exit = 0;
+
+// The thread publishes its tid and then waits until it is told to exit
+static void *thread_func(void *arg) {
+    pthread_mutex_lock(&thread_mutex);
+    thread_tid = sys_gettid();
+    thread_started = 1;
+    pthread_cond_broadcast(&thread_cond);
+    while (!thread_should_exit) {
+        pthread_cond_wait(&thread_cond, &thread_mutex);
+    }
+    pthread_mutex_unlock(&thread_mutex);
+    return NULL;
+}
+
+static int start_thread(pthread_t *thread) {
+    thread_started = 0;
+    thread_should_exit = 0;
+    if (pthread_create(thread, NULL, thread_func, NULL) != 0) {
+        THROW_ERROR("failed to create a thread");
+    }
+    pthread_mutex_lock(&thread_mutex);
+    while (!thread_started) {
+        pthread_cond_wait(&thread_cond, &thread_mutex);
+    }
+    pthread_mutex_unlock(&thread_mutex);
+    return 0;
+}
+
+static void stop_thread(pthread_t thread) {
+    pthread_mutex_lock(&thread_mutex);
+    thread_should_exit = 1;
+    pthread_cond_broadcast(&thread_cond);
+    pthread_mutex_unlock(&thread_mutex);
+    pthread_join(thread, NULL);
+}
+
+// ============================================================================
+// Test cases for tkill and tgkill
+// ============================================================================
+
+static int test_tkill_null_signal() {
+    if (sys_tkill(sys_gettid(), 0) < 0) {
+        THROW_ERROR("tkill with the null signal to the current thread failed");
+    }
+    return 0;
+}
+
+static int test_tgkill_null_signal_to_thread() {
+    pthread_t thread;
+    if (start_thread(&thread) < 0) {
+        return -1;
+    }
+    int ret = sys_tgkill(getpid(), thread_tid, 0);
+    stop_thread(thread);
+    if (ret < 0) {
+        THROW_ERROR("tgkill with the null signal to a thread failed");
+    }
+    return 0;
+}
+
+static int test_tgkill_with_wrong_tgid() {
+    pthread_t thread;
+    if (start_thread(&thread) < 0) {
+        return -1;
+    }
+    // The thread is not in any other thread group
+    int ret = sys_tgkill(getpid() + 1000, thread_tid, 0);
+    int saved_errno = errno;
+    stop_thread(thread);
+    if (ret == 0 || saved_errno != ESRCH) {
+        THROW_ERROR("tgkill with a wrong tgid should fail with ESRCH");
+    }
+    return 0;
+}
+
+static int test_tkill_with_invalid_args() {
+    if (sys_tkill(sys_gettid(), 65) == 0 || errno != EINVAL) {
+        THROW_ERROR("tkill with an invalid signal should fail with EINVAL");
+    }
+    if (sys_tkill(0, 0) == 0 || errno != EINVAL) {
+        THROW_ERROR("tkill with a zero tid should fail with EINVAL");
+    }
+    if (sys_tkill(0x7fffffff, 0) == 0 || errno != ESRCH) {
+        THROW_ERROR("tkill to a nonexistent thread should fail with ESRCH");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for delivering signals to threads
+// ============================================================================
+
+#define MAX_WAIT_ROUNDS     100000
+
+static volatile sig_atomic_t handled_count;
+static volatile pid_t handled_tid;
+static volatile int handled_signo;
+
+static void handle_sigusr1(int signum, siginfo_t *info, void *ucontext) {
+    handled_count++;
+    handled_tid = sys_gettid();
+    handled_signo = info->si_signo;
+}
+
+static int set_sigusr1_handler_with_flags(int flags) {
+    handled_count = 0;
+    handled_tid = 0;
+    handled_signo = 0;
+    struct sigaction action;
+    memset(&action, 0, sizeof(action));
+    action.sa_sigaction = handle_sigusr1;
+    action.sa_flags = SA_SIGINFO | flags;
+    sigemptyset(&action.sa_mask);
+    if (sigaction(SIGUSR1, &action, NULL) < 0) {
+        THROW_ERROR("failed to set the handler of SIGUSR1");
+    }
+    return 0;
+}
+
+static int set_sigusr1_handler(void) {
+    return set_sigusr1_handler_with_flags(0);
+}
+
+static void reset_sigusr1_handler(void) {
+    signal(SIGUSR1, SIG_DFL);
+}
+
+// Wait by syscalls, at the exits of which the signals are delivered, until
+// the signal is handled or it takes too long
+static void wait_for_handled(void) {
+    for (int i = 0; i < MAX_WAIT_ROUNDS && handled_count == 0; i++) {
+        sched_yield();
+    }
+}
+
+static volatile pid_t spinning_tid;
+static volatile int spinning_should_block;
+static volatile int spinning_should_unblock;
+static volatile int spinning_should_exit;
+
+// The thread keeps making syscalls until it is told to exit, having SIGUSR1
+// blocked until told otherwise if spinning_should_block is set
+static void *spin_in_thread(void *arg) {
+    sigset_t set;
+    sigemptyset(&set);
+    sigaddset(&set, SIGUSR1);
+    if (spinning_should_block) {
+        pthread_sigmask(SIG_BLOCK, &set, NULL);
+    }
+    spinning_tid = sys_gettid();
+    int is_blocked = spinning_should_block;
+    while (!spinning_should_exit) {
+        if (is_blocked && spinning_should_unblock) {
+            pthread_sigmask(SIG_UNBLOCK, &set, NULL);
+            is_blocked = 0;
+        }
+        sched_yield();
+    }
+    return NULL;
+}
+
+static int start_spinning_thread(pthread_t *thread, int should_block) {
+    spinning_tid = 0;
+    spinning_should_block = should_block;
+    spinning_should_unblock = 0;
+    spinning_should_exit = 0;
+    if (pthread_create(thread, NULL, spin_in_thread, NULL) != 0) {
+        THROW_ERROR("failed to create a thread");
+    }
+    while (spinning_tid == 0) {
+        sched_yield();
+    }
+    return 0;
+}
+
+static void stop_spinning_thread(pthread_t thread) {
+    spinning_should_exit = 1;
+    pthread_join(thread, NULL);
+}
+
+static int test_tgkill_handled_by_thread() {
+    if (set_sigusr1_handler() < 0) {
+        return -1;
+    }
+    pthread_t thread;
+    if (start_spinning_thread(&thread, 0) < 0) {
+        reset_sigusr1_handler();
+        return -1;
+    }
+    int ret = sys_tgkill(getpid(), spinning_tid, SIGUSR1);
+    if (ret == 0) {
+        wait_for_handled();
+    }
+    stop_spinning_thread(thread);
+    reset_sigusr1_handler();
+
+    if (ret < 0) {
+        THROW_ERROR("failed to send SIGUSR1 to the thread");
+    }
+    // Only the thread that the signal is directed to runs the handler, once
+    if (handled_count != 1) {
+        THROW_ERROR("the handler should run once instead of %d times", handled_count);
+    }
+    if (handled_tid != spinning_tid) {
+        THROW_ERROR("the handler should run in the thread that SIGUSR1 is sent to");
+    }
+    if (handled_signo != SIGUSR1) {
+        THROW_ERROR("the handler should be given the siginfo of SIGUSR1");
+    }
+    return 0;
+}
+
+static int test_tkill_blocked_by_thread_mask() {
+    if (set_sigusr1_handler() < 0) {
+        return -1;
+    }
+    pthread_t thread;
+    if (start_spinning_thread(&thread, 1) < 0) {
+        reset_sigusr1_handler();
+        return -1;
+    }
+    int ret = sys_tkill(spinning_tid, SIGUSR1);
+    // The signal is kept pending while blocked by the thread
+    int count_when_blocked = -1;
+    if (ret == 0) {
+        wait_for_handled();
+        count_when_blocked = handled_count;
+        spinning_should_unblock = 1;
+        wait_for_handled();
+    }
+    stop_spinning_thread(thread);
+    reset_sigusr1_handler();
+
+    if (ret < 0) {
+        THROW_ERROR("failed to send SIGUSR1 to the thread");
+    }
+    if (count_when_blocked != 0) {
+        THROW_ERROR("the handler should not run while SIGUSR1 is blocked");
+    }
+    if (handled_count != 1 || handled_tid != spinning_tid) {
+        THROW_ERROR("the handler should run in the thread once SIGUSR1 is unblocked");
+    }
+    return 0;
+}
+
+static int test_tkill_ignored_signals() {
+    // SIGCHLD is ignored by default, and SIGUSR2 is ignored explicitly, both
+    // of which never terminate the process
+    if (signal(SIGUSR2, SIG_IGN) == SIG_ERR) {
+        THROW_ERROR("failed to ignore SIGUSR2");
+    }
+    int ret = 0;
+    if (sys_tkill(sys_gettid(), SIGCHLD) < 0 || sys_tkill(sys_gettid(), SIGUSR2) < 0) {
+        ret = -1;
+    }
+    signal(SIGUSR2, SIG_DFL);
+    if (ret < 0) {
+        THROW_ERROR("failed to send the ignored signals to the current thread");
+    }
+    return 0;
+}
+
+static int test_sigaction_of_unchangeable_signals() {
+    struct sigaction action;
+    memset(&action, 0, sizeof(action));
+    action.sa_handler = SIG_IGN;
+    if (sigaction(SIGKILL, &action, NULL) == 0 || errno != EINVAL) {
+        THROW_ERROR("changing the action of SIGKILL should fail with EINVAL");
+    }
+    if (sigaction(SIGSTOP, &action, NULL) == 0 || errno != EINVAL) {
+        THROW_ERROR("changing the action of SIGSTOP should fail with EINVAL");
+    }
+    return 0;
+}
+
+static char not_code[64];
+
+static int test_sigaction_with_invalid_handler() {
+    struct sigaction action;
+    memset(&action, 0, sizeof(action));
+    // A handler must be in the code of the program
+    action.sa_handler = (void (*)(int))not_code;
+    if (sigaction(SIGUSR1, &action, NULL) == 0 || errno != EINVAL) {
+        THROW_ERROR("setting a handler that is not code should fail with EINVAL");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for the syscalls interrupted by signals
+// ============================================================================
+
+// Make a TCP connection over the loopback, a read of which blocks until the
+// peer writes
+static int connect_loopback(int *reader_fd, int *writer_fd) {
+    int listen_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (listen_fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    socklen_t addr_len = sizeof(addr);
+    if (bind(listen_fd, (struct sockaddr *)&addr, addr_len) < 0 ||
+            listen(listen_fd, 1) < 0 ||
+            getsockname(listen_fd, (struct sockaddr *)&addr, &addr_len) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to listen on the loopback");
+    }
+    *writer_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (*writer_fd < 0 || connect(*writer_fd, (struct sockaddr *)&addr, addr_len) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to connect to the loopback");
+    }
+    *reader_fd = accept(listen_fd, NULL, NULL);
+    close(listen_fd);
+    if (*reader_fd < 0) {
+        close(*writer_fd);
+        THROW_ERROR("failed to accept the connection");
+    }
+    return 0;
+}
+
+static volatile pid_t reader_tid;
+static int writer_fd;
+
+// Interrupt the read of the reader by SIGUSR1, and then write to it
+static void *interrupt_and_write(void *arg) {
+    usleep(100 * 1000);
+    sys_tkill(reader_tid, SIGUSR1);
+    usleep(100 * 1000);
+    write(writer_fd, "x", 1);
+    return NULL;
+}
+
+static int read_with_signal(int sa_flags, ssize_t *read_ret, int *read_errno) {
+    int reader_fd;
+    if (connect_loopback(&reader_fd, &writer_fd) < 0) {
+        return -1;
+    }
+    if (set_sigusr1_handler_with_flags(sa_flags) < 0) {
+        close(reader_fd);
+        close(writer_fd);
+        return -1;
+    }
+    reader_tid = sys_gettid();
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, interrupt_and_write, NULL) != 0) {
+        reset_sigusr1_handler();
+        close(reader_fd);
+        close(writer_fd);
+        THROW_ERROR("failed to create a thread");
+    }
+    char buf[1];
+    *read_ret = read(reader_fd, buf, sizeof(buf));
+    *read_errno = errno;
+    pthread_join(thread, NULL);
+    reset_sigusr1_handler();
+    close(reader_fd);
+    close(writer_fd);
+    return 0;
+}
+
+static int test_read_interrupted_without_sa_restart() {
+    ssize_t read_ret;
+    int read_errno;
+    if (read_with_signal(0, &read_ret, &read_errno) < 0) {
+        return -1;
+    }
+    if (handled_count != 1) {
+        THROW_ERROR("the handler should run once instead of %d times", handled_count);
+    }
+    if (read_ret >= 0 || read_errno != EINTR) {
+        THROW_ERROR("the read should fail with EINTR");
+    }
+    return 0;
+}
+
+static int test_read_restarted_with_sa_restart() {
+    ssize_t read_ret;
+    int read_errno;
+    if (read_with_signal(SA_RESTART, &read_ret, &read_errno) < 0) {
+        return -1;
+    }
+    if (handled_count != 1) {
+        THROW_ERROR("the handler should run once instead of %d times", handled_count);
+    }
+    if (read_ret != 1) {
+        THROW_ERROR("the read should be restarted and read the data written later");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for sigprocmask
+// ============================================================================
+
+static int sigset_equals(const sigset_t *a, const sigset_t *b) {
+    for (int signum = 1; signum < NSIG; signum++) {
+        if (sigismember(a, signum) != sigismember(b, signum)) {
+            return 0;
+        }
+    }
+    return 1;
+}
+
+static int test_sigprocmask_old_mask() {
+    sigset_t set, old_set, cur_set;
+    sigemptyset(&set);
+    sigaddset(&set, SIGUSR1);
+    if (sigprocmask(SIG_SETMASK, &set, &old_set) < 0) {
+        THROW_ERROR("failed to set the signal mask");
+    }
+
+    sigemptyset(&set);
+    sigaddset(&set, SIGUSR2);
+    if (sigprocmask(SIG_BLOCK, &set, NULL) < 0) {
+        THROW_ERROR("failed to block SIGUSR2");
+    }
+    if (sigprocmask(SIG_UNBLOCK, NULL, &cur_set) < 0) {
+        THROW_ERROR("failed to get the signal mask");
+    }
+    if (!sigismember(&cur_set, SIGUSR1) || !sigismember(&cur_set, SIGUSR2)) {
+        THROW_ERROR("SIGUSR1 and SIGUSR2 should be blocked");
+    }
+
+    sigemptyset(&set);
+    sigaddset(&set, SIGUSR1);
+    if (sigprocmask(SIG_UNBLOCK, &set, NULL) < 0) {
+        THROW_ERROR("failed to unblock SIGUSR1");
+    }
+    if (sigprocmask(SIG_SETMASK, &old_set, &cur_set) < 0) {
+        THROW_ERROR("failed to restore the signal mask");
+    }
+    if (sigismember(&cur_set, SIGUSR1) || !sigismember(&cur_set, SIGUSR2)) {
+        THROW_ERROR("only SIGUSR2 should be blocked");
+    }
+    return 0;
+}
+
+static int test_sigprocmask_unblockable() {
+    sigset_t set, old_set, cur_set;
+    sigemptyset(&set);
+    sigaddset(&set, SIGKILL);
+    sigaddset(&set, SIGSTOP);
+    if (sigprocmask(SIG_BLOCK, &set, &old_set) < 0) {
+        THROW_ERROR("failed to block SIGKILL and SIGSTOP");
+    }
+    if (sigprocmask(SIG_SETMASK, &old_set, &cur_set) < 0) {
+        THROW_ERROR("failed to restore the signal mask");
+    }
+    if (sigismember(&cur_set, SIGKILL) || sigismember(&cur_set, SIGSTOP)) {
+        THROW_ERROR("SIGKILL and SIGSTOP should never be blocked");
+    }
+    return 0;
+}
+
+static sigset_t thread_mask_before;
+static sigset_t thread_mask_after;
+
+static void *change_mask_in_thread(void *arg) {
+    sigset_t set;
+    sigemptyset(&set);
+    sigaddset(&set, SIGUSR1);
+    pthread_sigmask(SIG_UNBLOCK, &set, &thread_mask_before);
+    pthread_sigmask(SIG_BLOCK, NULL, &thread_mask_after);
+    return NULL;
+}
+
+static int test_sigprocmask_per_thread() {
+    sigset_t set, old_set, cur_set;
+    sigemptyset(&set);
+    sigaddset(&set, SIGUSR1);
+    if (sigprocmask(SIG_BLOCK, &set, &old_set) < 0) {
+        THROW_ERROR("failed to block SIGUSR1");
+    }
+
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, change_mask_in_thread, NULL) != 0) {
+        THROW_ERROR("failed to create a thread");
+    }
+    pthread_join(thread, NULL);
+
+    if (sigprocmask(SIG_SETMASK, &old_set, &cur_set) < 0) {
+        THROW_ERROR("failed to restore the signal mask");
+    }
+    // The thread inherits the mask, but its change is not seen by others
+    if (!sigismember(&thread_mask_before, SIGUSR1)) {
+        THROW_ERROR("the signal mask should be inherited by the thread");
+    }
+    if (sigismember(&thread_mask_after, SIGUSR1)) {
+        THROW_ERROR("SIGUSR1 should be unblocked in the thread");
+    }
+    if (!sigismember(&cur_set, SIGUSR1)) {
+        THROW_ERROR("the signal mask of the main thread should not be changed");
+    }
+    return 0;
+}
+
+static int test_sigprocmask_with_invalid_how() {
+    sigset_t set, old_set, cur_set;
+    sigemptyset(&set);
+    if (sigprocmask(SIG_BLOCK, NULL, &old_set) < 0) {
+        THROW_ERROR("failed to get the signal mask");
+    }
+    if (sigprocmask(42, &set, NULL) == 0 || errno != EINVAL) {
+        THROW_ERROR("sigprocmask with an invalid how should fail with EINVAL");
+    }
+    if (sigprocmask(SIG_BLOCK, NULL, &cur_set) < 0) {
+        THROW_ERROR("failed to get the signal mask");
+    }
+    if (!sigset_equals(&old_set, &cur_set)) {
+        THROW_ERROR("the signal mask should not be changed by a failed call");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_tkill_null_signal),
+    TEST_CASE(test_tgkill_null_signal_to_thread),
+    TEST_CASE(test_tgkill_with_wrong_tgid),
+    TEST_CASE(test_tkill_with_invalid_args),
+    TEST_CASE(test_tgkill_handled_by_thread),
+    TEST_CASE(test_tkill_blocked_by_thread_mask),
+    TEST_CASE(test_tkill_ignored_signals),
+    TEST_CASE(test_sigaction_of_unchangeable_signals),
+    TEST_CASE(test_sigaction_with_invalid_handler),
+    TEST_CASE(test_read_interrupted_without_sa_restart),
+    TEST_CASE(test_read_restarted_with_sa_restart),
+    TEST_CASE(test_sigprocmask_old_mask),
+    TEST_CASE(test_sigprocmask_unblockable),
+    TEST_CASE(test_sigprocmask_per_thread),
+    TEST_CASE(test_sigprocmask_with_invalid_how),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
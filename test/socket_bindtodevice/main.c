@@ -0,0 +1,153 @@
+#include <sys/socket.h>
+#include <sys/types.h>
+#include <net/if.h>
+#include <errno.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+// The loopback device, which is always there
+#define LOOPBACK_DEVICE     "lo"
+#define UNKNOWN_DEVICE      "no_such_dev0"
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int bind_to_device(int fd, const char *name) {
+    return setsockopt(fd, SOL_SOCKET, SO_BINDTODEVICE, name, strlen(name));
+}
+
+static int get_bound_device(int fd, char *name, socklen_t *name_len) {
+    *name_len = IFNAMSIZ;
+    memset(name, 0, IFNAMSIZ);
+    if (getsockopt(fd, SOL_SOCKET, SO_BINDTODEVICE, name, name_len) < 0) {
+        THROW_ERROR("failed to get SO_BINDTODEVICE");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for SO_BINDTODEVICE
+// ============================================================================
+
+static int __test_bind_to_loopback(int fd) {
+    char name[IFNAMSIZ];
+    socklen_t name_len;
+    if (get_bound_device(fd, name, &name_len) < 0) {
+        return -1;
+    }
+    if (name_len != 0) {
+        THROW_ERROR("a new socket should not be bound to any device");
+    }
+
+    if (bind_to_device(fd, LOOPBACK_DEVICE) < 0) {
+        // The binding is not pretended when the host does not allow it
+        if (errno == EPERM) {
+            printf("\t\tSkip: the host does not allow SO_BINDTODEVICE\n");
+            return 0;
+        }
+        THROW_ERROR("failed to bind the socket to the loopback device");
+    }
+    if (get_bound_device(fd, name, &name_len) < 0) {
+        return -1;
+    }
+    if (name_len != strlen(LOOPBACK_DEVICE) + 1 || strcmp(name, LOOPBACK_DEVICE) != 0) {
+        THROW_ERROR("the bound device is not the loopback device");
+    }
+
+    // An empty name removes the binding
+    if (bind_to_device(fd, "") < 0) {
+        THROW_ERROR("failed to remove the binding");
+    }
+    if (get_bound_device(fd, name, &name_len) < 0) {
+        return -1;
+    }
+    if (name_len != 0) {
+        THROW_ERROR("the socket should not be bound to any device after the binding is removed");
+    }
+    return 0;
+}
+
+static int test_bind_to_loopback() {
+    int fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    int ret = __test_bind_to_loopback(fd);
+    close(fd);
+    return ret;
+}
+
+static int test_bind_to_unknown_device() {
+    int fd = socket(AF_INET, SOCK_DGRAM, 0);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    int ret = bind_to_device(fd, UNKNOWN_DEVICE);
+    int errno_ = errno;
+    close(fd);
+    if (ret == 0 || errno_ != ENODEV) {
+        THROW_ERROR("the binding to an unknown device should fail with ENODEV");
+    }
+    return 0;
+}
+
+static int test_bind_to_too_long_name() {
+    int fd = socket(AF_INET, SOCK_DGRAM, 0);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    // The name of IFNAMSIZ bytes is rejected, while Linux truncates it to a
+    // shorter one
+    char name[IFNAMSIZ + 1];
+    memset(name, 'a', IFNAMSIZ);
+    name[IFNAMSIZ] = 0;
+    int ret = bind_to_device(fd, name);
+    int errno_ = errno;
+    close(fd);
+    if (ret == 0 || errno_ != EINVAL) {
+        THROW_ERROR("the binding to a too long name should fail with EINVAL");
+    }
+    return 0;
+}
+
+static int test_get_bound_device_with_short_buffer() {
+    int fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    if (bind_to_device(fd, LOOPBACK_DEVICE) < 0) {
+        int errno_ = errno;
+        close(fd);
+        if (errno_ == EPERM) {
+            printf("\t\tSkip: the host does not allow SO_BINDTODEVICE\n");
+            return 0;
+        }
+        THROW_ERROR("failed to bind the socket to the loopback device");
+    }
+    char name[IFNAMSIZ];
+    socklen_t name_len = IFNAMSIZ - 1;
+    int ret = getsockopt(fd, SOL_SOCKET, SO_BINDTODEVICE, name, &name_len);
+    int errno_ = errno;
+    close(fd);
+    if (ret == 0 || errno_ != EINVAL) {
+        THROW_ERROR("the buffer shorter than IFNAMSIZ should fail with EINVAL");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_bind_to_loopback),
+    TEST_CASE(test_bind_to_unknown_device),
+    TEST_CASE(test_bind_to_too_long_name),
+    TEST_CASE(test_get_bound_device_with_short_buffer),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
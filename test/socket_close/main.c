@@ -0,0 +1,212 @@
+#include <sys/socket.h>
+#include <sys/types.h>
+#include <sys/un.h>
+#include <arpa/inet.h>
+#include <netinet/in.h>
+#include <errno.h>
+#include <pthread.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define TEST_PORT           8819
+#define UNIX_SOCKET_PATH    "/tmp/test_socket_close.sock"
+// The time to wait for the blocked thread to be blocked
+#define BLOCK_WAIT_US       (100 * 1000)
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int create_listen_socket() {
+    int listen_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (listen_fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    int reuse = 1;
+    if (setsockopt(listen_fd, SOL_SOCKET, SO_REUSEADDR, &reuse, sizeof(reuse)) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to set SO_REUSEADDR");
+    }
+
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    addr.sin_port = htons(TEST_PORT);
+    if (bind(listen_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to bind the socket");
+    }
+    if (listen(listen_fd, 1) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to listen on the socket");
+    }
+    return listen_fd;
+}
+
+// Connect to the listening socket, returning the accepted socket
+static int connect_to(int listen_fd, int *client_fd) {
+    *client_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (*client_fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    addr.sin_port = htons(TEST_PORT);
+    if (connect(*client_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        close(*client_fd);
+        THROW_ERROR("failed to connect to the socket");
+    }
+    int accepted_fd = accept(listen_fd, NULL, NULL);
+    if (accepted_fd < 0) {
+        close(*client_fd);
+        THROW_ERROR("failed to accept the connection");
+    }
+    return accepted_fd;
+}
+
+struct blocked_op {
+    int fd;
+    int ret;
+    int err;
+};
+
+static void *read_blocked(void *arg) {
+    struct blocked_op *op = (struct blocked_op *)arg;
+    char buf[16];
+    op->ret = read(op->fd, buf, sizeof(buf));
+    op->err = errno;
+    return NULL;
+}
+
+static void *accept_blocked(void *arg) {
+    struct blocked_op *op = (struct blocked_op *)arg;
+    op->ret = accept(op->fd, NULL, NULL);
+    op->err = errno;
+    return NULL;
+}
+
+// Close the fd while a thread is blocked on it, which must be interrupted
+static int close_while_blocked(int fd, void *(*blocked_func)(void *)) {
+    struct blocked_op op = { .fd = fd, .ret = 0, .err = 0 };
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, blocked_func, &op) != 0) {
+        THROW_ERROR("failed to create a thread");
+    }
+    usleep(BLOCK_WAIT_US);
+    if (close(fd) < 0) {
+        THROW_ERROR("failed to close the fd");
+    }
+    pthread_join(thread, NULL);
+
+    if (op.ret >= 0 || op.err != EBADF) {
+        THROW_ERROR("the blocked operation should fail with EBADF");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for closing sockets with blocked operations
+// ============================================================================
+
+static int test_close_with_blocked_read() {
+    int listen_fd = create_listen_socket();
+    if (listen_fd < 0) {
+        return -1;
+    }
+    int client_fd;
+    int accepted_fd = connect_to(listen_fd, &client_fd);
+    close(listen_fd);
+    if (accepted_fd < 0) {
+        return -1;
+    }
+
+    int ret = close_while_blocked(accepted_fd, read_blocked);
+    close(client_fd);
+    return ret;
+}
+
+static int test_close_with_blocked_accept() {
+    int listen_fd = create_listen_socket();
+    if (listen_fd < 0) {
+        return -1;
+    }
+    return close_while_blocked(listen_fd, accept_blocked);
+}
+
+static int test_close_unix_socket_with_blocked_accept() {
+    int listen_fd = socket(AF_UNIX, SOCK_STREAM, 0);
+    if (listen_fd < 0) {
+        THROW_ERROR("failed to create a unix socket");
+    }
+    struct sockaddr_un addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sun_family = AF_UNIX;
+    strncpy(addr.sun_path, UNIX_SOCKET_PATH, sizeof(addr.sun_path) - 1);
+    unlink(UNIX_SOCKET_PATH);
+    if (bind(listen_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0 ||
+            listen(listen_fd, 1) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to listen on the unix socket");
+    }
+    int ret = close_while_blocked(listen_fd, accept_blocked);
+    unlink(UNIX_SOCKET_PATH);
+    return ret;
+}
+
+static int test_close_dup_fd_with_blocked_read() {
+    int listen_fd = create_listen_socket();
+    if (listen_fd < 0) {
+        return -1;
+    }
+    int client_fd;
+    int accepted_fd = connect_to(listen_fd, &client_fd);
+    close(listen_fd);
+    if (accepted_fd < 0) {
+        return -1;
+    }
+
+    // The read is not interrupted, as the socket is still referred to by the
+    // duplicated fd
+    int dup_fd = dup(accepted_fd);
+    if (dup_fd < 0) {
+        THROW_ERROR("failed to dup the fd");
+    }
+    struct blocked_op op = { .fd = accepted_fd, .ret = 0, .err = 0 };
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, read_blocked, &op) != 0) {
+        THROW_ERROR("failed to create a thread");
+    }
+    usleep(BLOCK_WAIT_US);
+    close(accepted_fd);
+
+    const char msg[] = "hello";
+    if (write(client_fd, msg, sizeof(msg)) != sizeof(msg)) {
+        THROW_ERROR("failed to write to the socket");
+    }
+    pthread_join(thread, NULL);
+    close(dup_fd);
+    close(client_fd);
+    if (op.ret != sizeof(msg)) {
+        THROW_ERROR("the read should complete with the data written");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_close_with_blocked_read),
+    TEST_CASE(test_close_with_blocked_accept),
+    TEST_CASE(test_close_unix_socket_with_blocked_accept),
+    TEST_CASE(test_close_dup_fd_with_blocked_read),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,132 @@
+#include <sys/epoll.h>
+#include <sys/socket.h>
+#include <sys/types.h>
+#include <arpa/inet.h>
+#include <netinet/in.h>
+#include <errno.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+// The port that no one listens on, so that a connection to it is refused
+#define REFUSED_PORT        8822
+// The time to wait for a connect in the background to finish
+#define CONNECT_TIMEOUT_MS  1000
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int get_so_error(int fd) {
+    int error = -1;
+    socklen_t error_len = sizeof(error);
+    if (getsockopt(fd, SOL_SOCKET, SO_ERROR, &error, &error_len) < 0) {
+        THROW_ERROR("failed to get SO_ERROR");
+    }
+    if (error_len != sizeof(error)) {
+        THROW_ERROR("the length of SO_ERROR is not the size of int");
+    }
+    return error;
+}
+
+// ============================================================================
+// Test cases for SO_ERROR
+// ============================================================================
+
+static int test_so_error_of_new_socket() {
+    int fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    int error = get_so_error(fd);
+    close(fd);
+    if (error != 0) {
+        THROW_ERROR("a new socket should have no pending error");
+    }
+    return 0;
+}
+
+static int __test_nonblocking_connect_refused(int fd, int epfd) {
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    addr.sin_port = htons(REFUSED_PORT);
+    if (connect(fd, (struct sockaddr *)&addr, sizeof(addr)) == 0) {
+        THROW_ERROR("the connection should be refused");
+    }
+    if (errno == ECONNREFUSED) {
+        // The connect is refused at once, so no error is left pending
+        if (get_so_error(fd) != 0) {
+            THROW_ERROR("the error returned by connect should not be pending");
+        }
+        return 0;
+    }
+    if (errno != EINPROGRESS) {
+        THROW_ERROR("the nonblocking connect should be in progress");
+    }
+
+    struct epoll_event event = { .events = EPOLLOUT, .data = { .fd = fd } };
+    if (epoll_ctl(epfd, EPOLL_CTL_ADD, fd, &event) < 0) {
+        THROW_ERROR("failed to add the socket to the epoll");
+    }
+    memset(&event, 0, sizeof(event));
+    if (epoll_wait(epfd, &event, 1, CONNECT_TIMEOUT_MS) != 1) {
+        THROW_ERROR("the connect should finish in time");
+    }
+    if ((event.events & EPOLLERR) == 0) {
+        THROW_ERROR("the failed connect should be reported as EPOLLERR");
+    }
+
+    if (get_so_error(fd) != ECONNREFUSED) {
+        THROW_ERROR("SO_ERROR should be ECONNREFUSED");
+    }
+    if (get_so_error(fd) != 0) {
+        THROW_ERROR("SO_ERROR should be cleared once read");
+    }
+    return 0;
+}
+
+static int test_nonblocking_connect_refused() {
+    int fd = socket(AF_INET, SOCK_STREAM | SOCK_NONBLOCK, 0);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    int epfd = epoll_create1(0);
+    if (epfd < 0) {
+        close(fd);
+        THROW_ERROR("failed to create an epoll");
+    }
+    int ret = __test_nonblocking_connect_refused(fd, epfd);
+    close(epfd);
+    close(fd);
+    return ret;
+}
+
+static int test_so_error_of_unix_socket() {
+    int fds[2];
+    if (socketpair(AF_UNIX, SOCK_STREAM, 0, fds) < 0) {
+        THROW_ERROR("failed to create a socket pair");
+    }
+    int error = get_so_error(fds[0]);
+    close(fds[0]);
+    close(fds[1]);
+    if (error != 0) {
+        THROW_ERROR("a unix socket should have no pending error");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_so_error_of_new_socket),
+    TEST_CASE(test_nonblocking_connect_refused),
+    TEST_CASE(test_so_error_of_unix_socket),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
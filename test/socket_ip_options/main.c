@@ -0,0 +1,231 @@
+#include <sys/socket.h>
+#include <sys/types.h>
+#include <sys/uio.h>
+#include <arpa/inet.h>
+#include <netinet/in.h>
+#include <netinet/ip.h>
+#include <errno.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define UDP_PORT            8828
+// The DSCP of expedited forwarding
+#define TEST_TOS            (46 << 2)
+#define TEST_TTL            123
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int check_int_option(int fd, int level, int optname, int expected) {
+    int val = -1;
+    socklen_t len = sizeof(val);
+    if (getsockopt(fd, level, optname, &val, &len) < 0) {
+        THROW_ERROR("failed to get the option");
+    }
+    if (len != sizeof(val) || val != expected) {
+        THROW_ERROR("the option is %d instead of %d", val, expected);
+    }
+    return 0;
+}
+
+static int set_int_option(int fd, int level, int optname, int val) {
+    return setsockopt(fd, level, optname, &val, sizeof(val));
+}
+
+static int check_invalid_value(int fd, int level, int optname, int val) {
+    if (set_int_option(fd, level, optname, val) == 0 || errno != EINVAL) {
+        THROW_ERROR("the value %d should be rejected with EINVAL", val);
+    }
+    return 0;
+}
+
+static int __test_tos_and_ttl(int type) {
+    int fd = socket(AF_INET, type, 0);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    int ret = -1;
+    if (set_int_option(fd, IPPROTO_IP, IP_TOS, TEST_TOS) < 0 ||
+            set_int_option(fd, IPPROTO_IP, IP_TTL, TEST_TTL) < 0) {
+        printf("\t\tERROR: failed to set IP_TOS and IP_TTL\n");
+        goto out;
+    }
+    if (check_int_option(fd, IPPROTO_IP, IP_TOS, TEST_TOS) < 0 ||
+            check_int_option(fd, IPPROTO_IP, IP_TTL, TEST_TTL) < 0) {
+        goto out;
+    }
+    // The values may be given as bytes
+    unsigned char ttl = TEST_TTL + 1;
+    if (setsockopt(fd, IPPROTO_IP, IP_TTL, &ttl, sizeof(ttl)) < 0 ||
+            check_int_option(fd, IPPROTO_IP, IP_TTL, TEST_TTL + 1) < 0) {
+        printf("\t\tERROR: failed to set IP_TTL by a byte\n");
+        goto out;
+    }
+    // The values out of range are rejected, with the values set kept
+    if (check_invalid_value(fd, IPPROTO_IP, IP_TTL, 256) < 0 ||
+            check_invalid_value(fd, IPPROTO_IP, IP_TTL, 0) < 0 ||
+            check_invalid_value(fd, IPPROTO_IP, IP_TTL, -2) < 0 ||
+            check_int_option(fd, IPPROTO_IP, IP_TOS, TEST_TOS) < 0 ||
+            check_int_option(fd, IPPROTO_IP, IP_TTL, TEST_TTL + 1) < 0) {
+        goto out;
+    }
+    ret = 0;
+out:
+    close(fd);
+    return ret;
+}
+
+// ============================================================================
+// Test cases for the IP socket options
+// ============================================================================
+
+static int test_tcp_tos_and_ttl() {
+    return __test_tos_and_ttl(SOCK_STREAM);
+}
+
+static int test_udp_tos_and_ttl() {
+    return __test_tos_and_ttl(SOCK_DGRAM);
+}
+
+static int test_reset_ttl() {
+    int fd = socket(AF_INET, SOCK_DGRAM, 0);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    int default_ttl = -1;
+    socklen_t len = sizeof(default_ttl);
+    int ret = -1;
+    if (getsockopt(fd, IPPROTO_IP, IP_TTL, &default_ttl, &len) < 0 || default_ttl <= 0) {
+        printf("\t\tERROR: failed to get the default TTL\n");
+        goto out;
+    }
+    // A TTL of -1 is reset to the default
+    if (set_int_option(fd, IPPROTO_IP, IP_TTL, TEST_TTL) < 0 ||
+            set_int_option(fd, IPPROTO_IP, IP_TTL, -1) < 0 ||
+            check_int_option(fd, IPPROTO_IP, IP_TTL, default_ttl) < 0) {
+        printf("\t\tERROR: the TTL should be reset to the default\n");
+        goto out;
+    }
+    ret = 0;
+out:
+    close(fd);
+    return ret;
+}
+
+// Unlike Linux, which takes the low byte of any TOS, a negative TOS or one
+// larger than a byte is rejected
+static int test_invalid_tos() {
+    int fd = socket(AF_INET, SOCK_DGRAM, 0);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    int ret = 0;
+    if (check_invalid_value(fd, IPPROTO_IP, IP_TOS, -1) < 0 ||
+            check_invalid_value(fd, IPPROTO_IP, IP_TOS, 256) < 0) {
+        ret = -1;
+    }
+    close(fd);
+    return ret;
+}
+
+static int test_ipv6_tclass_and_hops() {
+    int fd = socket(AF_INET6, SOCK_DGRAM, 0);
+    if (fd < 0) {
+        THROW_ERROR("failed to create an IPv6 socket");
+    }
+    int ret = -1;
+    if (set_int_option(fd, IPPROTO_IPV6, IPV6_TCLASS, TEST_TOS) < 0 ||
+            set_int_option(fd, IPPROTO_IPV6, IPV6_UNICAST_HOPS, TEST_TTL) < 0) {
+        printf("\t\tERROR: failed to set IPV6_TCLASS and IPV6_UNICAST_HOPS\n");
+        goto out;
+    }
+    if (check_int_option(fd, IPPROTO_IPV6, IPV6_TCLASS, TEST_TOS) < 0 ||
+            check_int_option(fd, IPPROTO_IPV6, IPV6_UNICAST_HOPS, TEST_TTL) < 0) {
+        goto out;
+    }
+    if (check_invalid_value(fd, IPPROTO_IPV6, IPV6_TCLASS, 256) < 0 ||
+            check_invalid_value(fd, IPPROTO_IPV6, IPV6_UNICAST_HOPS, 256) < 0 ||
+            check_invalid_value(fd, IPPROTO_IPV6, IPV6_UNICAST_HOPS, -2) < 0) {
+        goto out;
+    }
+    ret = 0;
+out:
+    close(fd);
+    return ret;
+}
+
+static int test_recv_ttl() {
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    addr.sin_port = htons(UDP_PORT);
+
+    int recv_fd = socket(AF_INET, SOCK_DGRAM, 0);
+    int send_fd = socket(AF_INET, SOCK_DGRAM, 0);
+    int ret = -1;
+    if (recv_fd < 0 || send_fd < 0 ||
+            bind(recv_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        printf("\t\tERROR: failed to create the UDP sockets\n");
+        goto out;
+    }
+    if (set_int_option(recv_fd, IPPROTO_IP, IP_RECVTTL, 1) < 0 ||
+            check_int_option(recv_fd, IPPROTO_IP, IP_RECVTTL, 1) < 0 ||
+            set_int_option(send_fd, IPPROTO_IP, IP_TTL, TEST_TTL) < 0) {
+        printf("\t\tERROR: failed to set IP_RECVTTL and IP_TTL\n");
+        goto out;
+    }
+    if (sendto(send_fd, "ping", 4, 0, (struct sockaddr *)&addr, sizeof(addr)) != 4) {
+        printf("\t\tERROR: failed to send the datagram\n");
+        goto out;
+    }
+
+    // The TTL of the datagram is received as an IP_TTL control message
+    char buf[16];
+    char control[CMSG_SPACE(sizeof(int))];
+    struct iovec iov = { .iov_base = buf, .iov_len = sizeof(buf) };
+    struct msghdr msg = {
+        .msg_iov = &iov,
+        .msg_iovlen = 1,
+        .msg_control = control,
+        .msg_controllen = sizeof(control),
+    };
+    if (recvmsg(recv_fd, &msg, 0) != 4) {
+        printf("\t\tERROR: failed to receive the datagram\n");
+        goto out;
+    }
+    struct cmsghdr *cmsg = CMSG_FIRSTHDR(&msg);
+    if (cmsg == NULL || cmsg->cmsg_level != IPPROTO_IP || cmsg->cmsg_type != IP_TTL ||
+            *(int *)CMSG_DATA(cmsg) != TEST_TTL) {
+        printf("\t\tERROR: the TTL should be received as a control message\n");
+        goto out;
+    }
+    ret = 0;
+out:
+    if (recv_fd >= 0) {
+        close(recv_fd);
+    }
+    if (send_fd >= 0) {
+        close(send_fd);
+    }
+    return ret;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_tcp_tos_and_ttl),
+    TEST_CASE(test_udp_tos_and_ttl),
+    TEST_CASE(test_reset_ttl),
+    TEST_CASE(test_invalid_tos),
+    TEST_CASE(test_ipv6_tclass_and_hops),
+    TEST_CASE(test_recv_ttl),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
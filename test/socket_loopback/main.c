@@ -0,0 +1,663 @@
+#define _GNU_SOURCE
+#include <sys/epoll.h>
+#include <sys/socket.h>
+#include <sys/syscall.h>
+#include <sys/types.h>
+#include <sys/uio.h>
+#include <arpa/inet.h>
+#include <netinet/in.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <poll.h>
+#include <pthread.h>
+#include <signal.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define LISTEN_PORT         8824
+#define UDP_PORT            8825
+// The time for data sent by the peer to arrive
+#define WAIT_TIMEOUT_MS     1000
+
+static int listen_fd = -1;
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int create_listen_socket() {
+    int fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    int reuse = 1;
+    setsockopt(fd, SOL_SOCKET, SO_REUSEADDR, &reuse, sizeof(reuse));
+
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_ANY);
+    addr.sin_port = htons(LISTEN_PORT);
+    if (bind(fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        close(fd);
+        THROW_ERROR("failed to bind the socket");
+    }
+    if (listen(fd, 4) < 0) {
+        close(fd);
+        THROW_ERROR("failed to listen on the socket");
+    }
+    return fd;
+}
+
+// Connect a client to the listen socket by 127.0.0.1, and accept it
+static int connect_pair(int *client_fd, int *server_fd) {
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    addr.sin_port = htons(LISTEN_PORT);
+
+    *client_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (*client_fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    if (connect(*client_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        close(*client_fd);
+        THROW_ERROR("failed to connect");
+    }
+    *server_fd = accept(listen_fd, NULL, NULL);
+    if (*server_fd < 0) {
+        close(*client_fd);
+        THROW_ERROR("failed to accept");
+    }
+    return 0;
+}
+
+static void close_pair(int client_fd, int server_fd) {
+    close(client_fd);
+    close(server_fd);
+}
+
+static volatile int signal_count;
+
+static void handle_signal(int signum) {
+    signal_count++;
+}
+
+// The handler is set without SA_RESTART, so that the interrupted syscalls
+// are not restarted
+static int set_signal_handler(int signum) {
+    struct sigaction sa = { 0 };
+    sa.sa_handler = handle_signal;
+    if (sigaction(signum, &sa, NULL) < 0) {
+        THROW_ERROR("failed to set the signal handler");
+    }
+    signal_count = 0;
+    return 0;
+}
+
+static void *signal_after_sleep(void *arg) {
+    pid_t tid = *(pid_t *)arg;
+    usleep(100 * 1000);
+    syscall(SYS_tkill, tid, SIGUSR1);
+    return NULL;
+}
+
+static int send_and_recv(int send_fd, int recv_fd, const char *msg) {
+    size_t len = strlen(msg);
+    if (send(send_fd, msg, len, 0) != len) {
+        THROW_ERROR("failed to send");
+    }
+    char buf[64] = { 0 };
+    if (recv(recv_fd, buf, len, MSG_WAITALL) != len) {
+        THROW_ERROR("failed to receive all the data");
+    }
+    if (memcmp(buf, msg, len) != 0) {
+        THROW_ERROR("the data received is not the data sent");
+    }
+    return 0;
+}
+
+static int wait_readable(int fd) {
+    struct pollfd pollfd = { .fd = fd, .events = POLLIN };
+    if (poll(&pollfd, 1, WAIT_TIMEOUT_MS) != 1 || (pollfd.revents & POLLIN) == 0) {
+        THROW_ERROR("the socket should be readable");
+    }
+    return 0;
+}
+
+static void *recv_thread_func(void *arg) {
+    int fd = *(int *)arg;
+    static char buf[16];
+    ssize_t len = recv(fd, buf, sizeof(buf), MSG_WAITALL);
+    return (void *)len;
+}
+
+// ============================================================================
+// Test cases for TCP connections over the loopback
+// ============================================================================
+
+static int __test_send_and_recv(int client_fd, int server_fd) {
+    if (send_and_recv(client_fd, server_fd, "hello from the client") < 0 ||
+            send_and_recv(server_fd, client_fd, "hello from the server") < 0) {
+        return -1;
+    }
+
+    char buf0[4], buf1[8];
+    struct iovec iov[2] = {
+        { .iov_base = buf0, .iov_len = sizeof(buf0) },
+        { .iov_base = buf1, .iov_len = sizeof(buf1) },
+    };
+    if (write(client_fd, "0123456789ab", 12) != 12) {
+        THROW_ERROR("failed to write");
+    }
+    if (wait_readable(server_fd) < 0) {
+        return -1;
+    }
+    char peeked[4];
+    if (recv(server_fd, peeked, sizeof(peeked), MSG_PEEK) != sizeof(peeked) ||
+            memcmp(peeked, "0123", 4) != 0) {
+        THROW_ERROR("failed to peek the data");
+    }
+    if (readv(server_fd, iov, 2) != 12) {
+        THROW_ERROR("failed to read all the data into the buffers");
+    }
+    if (memcmp(buf0, "0123", 4) != 0 || memcmp(buf1, "456789ab", 8) != 0) {
+        THROW_ERROR("the data read is not the data written");
+    }
+    return 0;
+}
+
+static int test_send_and_recv() {
+    int client_fd, server_fd;
+    if (connect_pair(&client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    int ret = __test_send_and_recv(client_fd, server_fd);
+    close_pair(client_fd, server_fd);
+    return ret;
+}
+
+static int __test_names(int client_fd, int server_fd) {
+    struct sockaddr_in client_name, client_peer, server_name, server_peer;
+    socklen_t len = sizeof(client_name);
+    if (getsockname(client_fd, (struct sockaddr *)&client_name, &len) < 0 ||
+            getpeername(client_fd, (struct sockaddr *)&client_peer, &len) < 0 ||
+            getsockname(server_fd, (struct sockaddr *)&server_name, &len) < 0 ||
+            getpeername(server_fd, (struct sockaddr *)&server_peer, &len) < 0) {
+        THROW_ERROR("failed to get the names");
+    }
+    if (client_name.sin_addr.s_addr != htonl(INADDR_LOOPBACK) ||
+            server_name.sin_addr.s_addr != htonl(INADDR_LOOPBACK)) {
+        THROW_ERROR("the names should be of 127.0.0.1");
+    }
+    if (ntohs(client_peer.sin_port) != LISTEN_PORT || ntohs(server_name.sin_port) != LISTEN_PORT) {
+        THROW_ERROR("the server should be at the listen port");
+    }
+    if (client_name.sin_port != server_peer.sin_port ||
+            client_name.sin_addr.s_addr != server_peer.sin_addr.s_addr) {
+        THROW_ERROR("the peer of the server should be the client");
+    }
+    return 0;
+}
+
+static int test_names() {
+    int client_fd, server_fd;
+    if (connect_pair(&client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    int ret = __test_names(client_fd, server_fd);
+    close_pair(client_fd, server_fd);
+    return ret;
+}
+
+static int __test_shutdown(int client_fd, int server_fd) {
+    if (shutdown(client_fd, SHUT_WR) < 0) {
+        THROW_ERROR("failed to shut down the client");
+    }
+    char buf[16];
+    if (wait_readable(server_fd) < 0) {
+        return -1;
+    }
+    if (recv(server_fd, buf, sizeof(buf), 0) != 0) {
+        THROW_ERROR("the server should read the end of file");
+    }
+    // The client can still receive after shutting down for writing
+    if (send_and_recv(server_fd, client_fd, "still open") < 0) {
+        return -1;
+    }
+    if (set_signal_handler(SIGPIPE) < 0) {
+        return -1;
+    }
+    if (send(client_fd, "x", 1, MSG_NOSIGNAL) >= 0 || errno != EPIPE) {
+        THROW_ERROR("the send after the shutdown should fail with EPIPE");
+    }
+    if (signal_count != 0) {
+        THROW_ERROR("the send with MSG_NOSIGNAL should not raise SIGPIPE");
+    }
+    // Without MSG_NOSIGNAL, both the send and the write raise SIGPIPE
+    if (send(client_fd, "x", 1, 0) >= 0 || errno != EPIPE || signal_count != 1) {
+        THROW_ERROR("the send after the shutdown should raise SIGPIPE");
+    }
+    if (write(client_fd, "x", 1) >= 0 || errno != EPIPE || signal_count != 2) {
+        THROW_ERROR("the write after the shutdown should raise SIGPIPE");
+    }
+    signal(SIGPIPE, SIG_DFL);
+    return 0;
+}
+
+static int test_shutdown() {
+    int client_fd, server_fd;
+    if (connect_pair(&client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    int ret = __test_shutdown(client_fd, server_fd);
+    close_pair(client_fd, server_fd);
+    return ret;
+}
+
+static int __test_eof_after_peer_closed(int client_fd) {
+    char buf[16];
+    if (wait_readable(client_fd) < 0) {
+        return -1;
+    }
+    if (recv(client_fd, buf, sizeof(buf), 0) != 0) {
+        THROW_ERROR("the client should read the end of file");
+    }
+    return 0;
+}
+
+static int test_eof_after_peer_closed() {
+    int client_fd, server_fd;
+    if (connect_pair(&client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    close(server_fd);
+    int ret = __test_eof_after_peer_closed(client_fd);
+    close(client_fd);
+    return ret;
+}
+
+static int test_send_before_accept() {
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    addr.sin_port = htons(LISTEN_PORT);
+    int client_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (client_fd < 0 || connect(client_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        THROW_ERROR("failed to connect");
+    }
+    if (send(client_fd, "early", 5, 0) != 5) {
+        THROW_ERROR("failed to send");
+    }
+    close(client_fd);
+
+    int server_fd = accept(listen_fd, NULL, NULL);
+    if (server_fd < 0) {
+        THROW_ERROR("failed to accept");
+    }
+    char buf[16];
+    ssize_t len = recv(server_fd, buf, sizeof(buf), MSG_WAITALL);
+    close(server_fd);
+    if (len != 5 || memcmp(buf, "early", 5) != 0) {
+        THROW_ERROR("the data sent before the accept should be received");
+    }
+    return 0;
+}
+
+static int __test_nonblocking_recv(int client_fd, int server_fd) {
+    char buf[16];
+    if (recv(server_fd, buf, sizeof(buf), MSG_DONTWAIT) >= 0 || errno != EAGAIN) {
+        THROW_ERROR("the receive without data should fail with EAGAIN");
+    }
+    int flags = fcntl(server_fd, F_GETFL);
+    if (fcntl(server_fd, F_SETFL, flags | O_NONBLOCK) < 0) {
+        THROW_ERROR("failed to set O_NONBLOCK");
+    }
+    if (read(server_fd, buf, sizeof(buf)) >= 0 || errno != EAGAIN) {
+        THROW_ERROR("the nonblocking read without data should fail with EAGAIN");
+    }
+    return 0;
+}
+
+static int test_nonblocking_recv() {
+    int client_fd, server_fd;
+    if (connect_pair(&client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    int ret = __test_nonblocking_recv(client_fd, server_fd);
+    close_pair(client_fd, server_fd);
+    return ret;
+}
+
+static int __test_blocking_recv(int client_fd, int server_fd) {
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, recv_thread_func, &server_fd) != 0) {
+        THROW_ERROR("failed to create a thread");
+    }
+    // Send in pieces, which are all waited for by MSG_WAITALL
+    usleep(10 * 1000);
+    send(client_fd, "01234567", 8, 0);
+    usleep(10 * 1000);
+    send(client_fd, "89abcdef", 8, 0);
+    void *len;
+    pthread_join(thread, &len);
+    if ((ssize_t)len != 16) {
+        THROW_ERROR("the blocking receive should wait for all the data");
+    }
+    return 0;
+}
+
+static int test_blocking_recv() {
+    int client_fd, server_fd;
+    if (connect_pair(&client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    int ret = __test_blocking_recv(client_fd, server_fd);
+    close_pair(client_fd, server_fd);
+    return ret;
+}
+
+static int __test_blocking_recv_interrupted(int client_fd, int server_fd) {
+    if (set_signal_handler(SIGUSR1) < 0) {
+        return -1;
+    }
+    pid_t tid = syscall(SYS_gettid);
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, signal_after_sleep, &tid) != 0) {
+        THROW_ERROR("failed to create a thread");
+    }
+    // The blocked receive fails with EINTR as nothing is received
+    char buf[16];
+    ssize_t len = recv(server_fd, buf, sizeof(buf), 0);
+    int err = errno;
+    pthread_join(thread, NULL);
+    if (len >= 0 || err != EINTR || signal_count != 1) {
+        THROW_ERROR("the blocked receive should fail with EINTR");
+    }
+
+    // A receive interrupted after some data is received returns the data
+    if (send(client_fd, "01234567", 8, 0) != 8 ||
+            pthread_create(&thread, NULL, signal_after_sleep, &tid) != 0) {
+        THROW_ERROR("failed to send or create a thread");
+    }
+    len = recv(server_fd, buf, sizeof(buf), MSG_WAITALL);
+    pthread_join(thread, NULL);
+    signal(SIGUSR1, SIG_DFL);
+    if (len != 8 || signal_count != 2) {
+        THROW_ERROR("the interrupted receive should return the data received");
+    }
+    return 0;
+}
+
+static int test_blocking_recv_interrupted() {
+    int client_fd, server_fd;
+    if (connect_pair(&client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    int ret = __test_blocking_recv_interrupted(client_fd, server_fd);
+    close_pair(client_fd, server_fd);
+    return ret;
+}
+
+static int __test_epoll(int client_fd, int server_fd, int epfd) {
+    struct epoll_event event = { .events = EPOLLIN | EPOLLET, .data = { .fd = server_fd } };
+    if (epoll_ctl(epfd, EPOLL_CTL_ADD, server_fd, &event) < 0) {
+        THROW_ERROR("failed to add the socket to the epoll");
+    }
+    if (epoll_wait(epfd, &event, 1, 0) != 0) {
+        THROW_ERROR("the socket without data should not be ready");
+    }
+    if (send(client_fd, "x", 1, 0) != 1) {
+        THROW_ERROR("failed to send");
+    }
+    memset(&event, 0, sizeof(event));
+    if (epoll_wait(epfd, &event, 1, WAIT_TIMEOUT_MS) != 1 || event.data.fd != server_fd ||
+            (event.events & EPOLLIN) == 0) {
+        THROW_ERROR("the socket with data should be ready");
+    }
+    // Edge-triggered, so it is not reported again until more data comes
+    if (epoll_wait(epfd, &event, 1, 0) != 0) {
+        THROW_ERROR("the edge-triggered socket should be reported only once");
+    }
+    char c;
+    if (recv(server_fd, &c, 1, 0) != 1) {
+        THROW_ERROR("failed to receive");
+    }
+    return 0;
+}
+
+static int test_epoll() {
+    int client_fd, server_fd;
+    if (connect_pair(&client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    int epfd = epoll_create1(0);
+    if (epfd < 0) {
+        close_pair(client_fd, server_fd);
+        THROW_ERROR("failed to create an epoll");
+    }
+    int ret = __test_epoll(client_fd, server_fd, epfd);
+    close(epfd);
+    close_pair(client_fd, server_fd);
+    return ret;
+}
+
+static int test_peek_with_large_buf() {
+    int client_fd, server_fd;
+    if (connect_pair(&client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    int ret = -1;
+    static char buf[1024 * 1024];
+    if (send(client_fd, "abc", 3, 0) != 3 || wait_readable(server_fd) < 0) {
+        printf("\t\tERROR: failed to send\n");
+        goto out;
+    }
+    // Only the data to read is peeked, however large the buffer is
+    if (recv(server_fd, buf, sizeof(buf), MSG_PEEK) != 3 ||
+            recv(server_fd, buf, sizeof(buf), 0) != 3 || memcmp(buf, "abc", 3) != 0) {
+        printf("\t\tERROR: the data peeked is not the data sent\n");
+        goto out;
+    }
+    ret = 0;
+out:
+    close_pair(client_fd, server_fd);
+    return ret;
+}
+
+// ============================================================================
+// Test cases for UDP sockets over the loopback
+// ============================================================================
+
+static int create_udp_socket(uint16_t port) {
+    int fd = socket(AF_INET, SOCK_DGRAM, 0);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a UDP socket");
+    }
+    struct sockaddr_in addr = {
+        .sin_family = AF_INET,
+        .sin_port = htons(port),
+        .sin_addr.s_addr = htonl(INADDR_LOOPBACK),
+    };
+    if (bind(fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        close(fd);
+        THROW_ERROR("failed to bind the UDP socket");
+    }
+    return fd;
+}
+
+static int __test_udp_send_and_recv(int recv_fd, int send_fd) {
+    struct sockaddr_in addr = {
+        .sin_family = AF_INET,
+        .sin_port = htons(UDP_PORT),
+        .sin_addr.s_addr = htonl(INADDR_LOOPBACK),
+    };
+    if (sendto(send_fd, "hello", 5, 0, (struct sockaddr *)&addr, sizeof(addr)) != 5 ||
+            sendto(send_fd, "world!", 6, 0, (struct sockaddr *)&addr, sizeof(addr)) != 6) {
+        THROW_ERROR("failed to send the datagrams");
+    }
+    if (wait_readable(recv_fd) < 0) {
+        return -1;
+    }
+
+    // The datagram is left by a peek
+    char buf[16] = { 0 };
+    if (recv(recv_fd, buf, sizeof(buf), MSG_PEEK) != 5 || memcmp(buf, "hello", 5) != 0) {
+        THROW_ERROR("the datagram peeked is not the first one sent");
+    }
+    struct sockaddr_in src_addr, send_addr;
+    socklen_t src_addr_len = sizeof(src_addr), send_addr_len = sizeof(send_addr);
+    if (recvfrom(recv_fd, buf, sizeof(buf), 0, (struct sockaddr *)&src_addr,
+                 &src_addr_len) != 5 || memcmp(buf, "hello", 5) != 0) {
+        THROW_ERROR("the datagram received is not the first one sent");
+    }
+    if (getsockname(send_fd, (struct sockaddr *)&send_addr, &send_addr_len) < 0 ||
+            src_addr_len != sizeof(src_addr) || src_addr.sin_port != send_addr.sin_port) {
+        THROW_ERROR("the source of the datagram should be the sending socket");
+    }
+
+    // The rest of a datagram that does not fit is discarded
+    if (recv(recv_fd, buf, 3, MSG_TRUNC) != 6 || memcmp(buf, "wor", 3) != 0) {
+        THROW_ERROR("the datagram should be truncated to the buffer");
+    }
+    if (recv(recv_fd, buf, sizeof(buf), MSG_DONTWAIT) >= 0 || errno != EAGAIN) {
+        THROW_ERROR("no datagram should be left");
+    }
+    return 0;
+}
+
+static int test_udp_send_and_recv() {
+    int recv_fd = create_udp_socket(UDP_PORT);
+    if (recv_fd < 0) {
+        return -1;
+    }
+    int send_fd = create_udp_socket(0);
+    if (send_fd < 0) {
+        close(recv_fd);
+        return -1;
+    }
+    int ret = __test_udp_send_and_recv(recv_fd, send_fd);
+    close(send_fd);
+    close(recv_fd);
+    return ret;
+}
+
+static int __test_udp_connected(int recv_fd, int peer_fd, int other_fd) {
+    struct sockaddr_in addr;
+    socklen_t addr_len = sizeof(addr);
+    if (getsockname(peer_fd, (struct sockaddr *)&addr, &addr_len) < 0 ||
+            connect(recv_fd, (struct sockaddr *)&addr, addr_len) < 0) {
+        THROW_ERROR("failed to connect to the peer");
+    }
+    addr_len = sizeof(addr);
+    if (getsockname(recv_fd, (struct sockaddr *)&addr, &addr_len) < 0 ||
+            connect(peer_fd, (struct sockaddr *)&addr, addr_len) < 0) {
+        THROW_ERROR("failed to connect back");
+    }
+
+    // The datagrams from the sockets other than the peer are dropped
+    if (sendto(other_fd, "other", 5, 0, (struct sockaddr *)&addr, addr_len) != 5 ||
+            send(peer_fd, "peer", 4, 0) != 4) {
+        THROW_ERROR("failed to send the datagrams");
+    }
+    char buf[16] = { 0 };
+    if (wait_readable(recv_fd) < 0 || read(recv_fd, buf, sizeof(buf)) != 4 ||
+            memcmp(buf, "peer", 4) != 0) {
+        THROW_ERROR("only the datagram from the peer should be received");
+    }
+    if (recv(recv_fd, buf, sizeof(buf), MSG_DONTWAIT) >= 0 || errno != EAGAIN) {
+        THROW_ERROR("the datagram from the other socket should be dropped");
+    }
+    return 0;
+}
+
+static int test_udp_connected() {
+    int fds[3] = { -1, -1, -1 };
+    int ret = -1;
+    if ((fds[0] = create_udp_socket(UDP_PORT)) < 0 || (fds[1] = create_udp_socket(0)) < 0 ||
+            (fds[2] = create_udp_socket(0)) < 0) {
+        goto out;
+    }
+    ret = __test_udp_connected(fds[0], fds[1], fds[2]);
+out:
+    for (int i = 0; i < 3; i++) {
+        if (fds[i] >= 0) {
+            close(fds[i]);
+        }
+    }
+    return ret;
+}
+
+static int __test_udp_blocking_recv(int recv_fd, int send_fd) {
+    struct pollfd pollfd = { .fd = recv_fd, .events = POLLIN };
+    if (poll(&pollfd, 1, 0) != 0) {
+        THROW_ERROR("the socket without datagrams should not be readable");
+    }
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, recv_thread_func, &recv_fd) != 0) {
+        THROW_ERROR("failed to create a thread");
+    }
+    // The receive that blocks is woken by the datagram sent later
+    usleep(10 * 1000);
+    struct sockaddr_in addr = {
+        .sin_family = AF_INET,
+        .sin_port = htons(UDP_PORT),
+        .sin_addr.s_addr = htonl(INADDR_LOOPBACK),
+    };
+    sendto(send_fd, "0123", 4, 0, (struct sockaddr *)&addr, sizeof(addr));
+    void *len;
+    pthread_join(thread, &len);
+    if ((ssize_t)len != 4) {
+        THROW_ERROR("the blocking receive should get the datagram");
+    }
+    return 0;
+}
+
+static int test_udp_blocking_recv() {
+    int recv_fd = create_udp_socket(UDP_PORT);
+    if (recv_fd < 0) {
+        return -1;
+    }
+    int send_fd = create_udp_socket(0);
+    if (send_fd < 0) {
+        close(recv_fd);
+        return -1;
+    }
+    int ret = __test_udp_blocking_recv(recv_fd, send_fd);
+    close(send_fd);
+    close(recv_fd);
+    return ret;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_send_and_recv),
+    TEST_CASE(test_names),
+    TEST_CASE(test_shutdown),
+    TEST_CASE(test_eof_after_peer_closed),
+    TEST_CASE(test_send_before_accept),
+    TEST_CASE(test_nonblocking_recv),
+    TEST_CASE(test_blocking_recv),
+    TEST_CASE(test_blocking_recv_interrupted),
+    TEST_CASE(test_epoll),
+    TEST_CASE(test_peek_with_large_buf),
+    TEST_CASE(test_udp_send_and_recv),
+    TEST_CASE(test_udp_connected),
+    TEST_CASE(test_udp_blocking_recv),
+};
+
+int main() {
+    listen_fd = create_listen_socket();
+    if (listen_fd < 0) {
+        return -1;
+    }
+    int ret = test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+    close(listen_fd);
+    return ret;
+}
@@ -0,0 +1,197 @@
+#include <sys/epoll.h>
+#include <sys/socket.h>
+#include <sys/types.h>
+#include <arpa/inet.h>
+#include <netinet/in.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define LISTEN_PORT         8823
+// The time to wait for a connect in the background to finish
+#define CONNECT_TIMEOUT_MS  1000
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int create_listen_socket() {
+    int fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    int reuse = 1;
+    setsockopt(fd, SOL_SOCKET, SO_REUSEADDR, &reuse, sizeof(reuse));
+
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    addr.sin_port = htons(LISTEN_PORT);
+    if (bind(fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        close(fd);
+        THROW_ERROR("failed to bind the socket");
+    }
+    if (listen(fd, 4) < 0) {
+        close(fd);
+        THROW_ERROR("failed to listen on the socket");
+    }
+    return fd;
+}
+
+static int set_nonblocking(int fd) {
+    int flags = fcntl(fd, F_GETFL);
+    if (flags < 0 || fcntl(fd, F_SETFL, flags | O_NONBLOCK) < 0) {
+        THROW_ERROR("failed to set O_NONBLOCK");
+    }
+    return 0;
+}
+
+static int connect_to_listen_port(int fd) {
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    addr.sin_port = htons(LISTEN_PORT);
+    return connect(fd, (struct sockaddr *)&addr, sizeof(addr));
+}
+
+static int wait_writable(int fd) {
+    int epfd = epoll_create1(0);
+    if (epfd < 0) {
+        THROW_ERROR("failed to create an epoll");
+    }
+    struct epoll_event event = { .events = EPOLLOUT, .data = { .fd = fd } };
+    if (epoll_ctl(epfd, EPOLL_CTL_ADD, fd, &event) < 0) {
+        close(epfd);
+        THROW_ERROR("failed to add the socket to the epoll");
+    }
+    memset(&event, 0, sizeof(event));
+    int ret = epoll_wait(epfd, &event, 1, CONNECT_TIMEOUT_MS);
+    close(epfd);
+    if (ret != 1) {
+        THROW_ERROR("the connect should finish in time");
+    }
+    if ((event.events & EPOLLOUT) == 0 || (event.events & EPOLLERR) != 0) {
+        THROW_ERROR("the connected socket should be writable without errors");
+    }
+    return 0;
+}
+
+static int get_so_error(int fd) {
+    int error = -1;
+    socklen_t error_len = sizeof(error);
+    if (getsockopt(fd, SOL_SOCKET, SO_ERROR, &error, &error_len) < 0) {
+        THROW_ERROR("failed to get SO_ERROR");
+    }
+    return error;
+}
+
+// ============================================================================
+// Test cases for nonblocking connect
+// ============================================================================
+
+static int __test_nonblocking_connect(int listen_fd, int fd) {
+    if (set_nonblocking(fd) < 0) {
+        return -1;
+    }
+    if (connect_to_listen_port(fd) < 0) {
+        if (errno != EINPROGRESS) {
+            THROW_ERROR("the nonblocking connect should be in progress");
+        }
+        // The connect may be done by now, in which case the result of the
+        // connect is returned, i.e., 0, or it is already connected
+        if (connect_to_listen_port(fd) < 0 && errno != EALREADY && errno != EISCONN) {
+            THROW_ERROR("the second connect should fail with EALREADY");
+        }
+    }
+
+    if (wait_writable(fd) < 0) {
+        return -1;
+    }
+    if (get_so_error(fd) != 0) {
+        THROW_ERROR("the connected socket should have no pending error");
+    }
+
+    struct sockaddr_in peer_addr;
+    socklen_t peer_addr_len = sizeof(peer_addr);
+    if (getpeername(fd, (struct sockaddr *)&peer_addr, &peer_addr_len) < 0) {
+        THROW_ERROR("failed to get the peer name");
+    }
+    if (peer_addr.sin_family != AF_INET || ntohs(peer_addr.sin_port) != LISTEN_PORT ||
+            peer_addr.sin_addr.s_addr != htonl(INADDR_LOOPBACK)) {
+        THROW_ERROR("the peer name is not the address of the listen socket");
+    }
+
+    int conn_fd = accept(listen_fd, NULL, NULL);
+    if (conn_fd < 0) {
+        THROW_ERROR("failed to accept the connection");
+    }
+    close(conn_fd);
+    return 0;
+}
+
+static int test_nonblocking_connect() {
+    int listen_fd = create_listen_socket();
+    if (listen_fd < 0) {
+        return -1;
+    }
+    int fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (fd < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to create a socket");
+    }
+    int ret = __test_nonblocking_connect(listen_fd, fd);
+    close(fd);
+    close(listen_fd);
+    return ret;
+}
+
+static int __test_connect_again_after_connected(int fd) {
+    if (connect_to_listen_port(fd) < 0 && errno != EINPROGRESS) {
+        THROW_ERROR("the nonblocking connect should be in progress");
+    }
+    if (wait_writable(fd) < 0) {
+        return -1;
+    }
+    // The first connect after the connect in the background is done may
+    // return its result, i.e., 0
+    if (connect_to_listen_port(fd) < 0 && errno != EISCONN) {
+        THROW_ERROR("the connect after the connection is done should not fail");
+    }
+    if (connect_to_listen_port(fd) == 0 || errno != EISCONN) {
+        THROW_ERROR("the connect of a connected socket should fail with EISCONN");
+    }
+    return 0;
+}
+
+static int test_connect_again_after_connected() {
+    int listen_fd = create_listen_socket();
+    if (listen_fd < 0) {
+        return -1;
+    }
+    int fd = socket(AF_INET, SOCK_STREAM | SOCK_NONBLOCK, 0);
+    if (fd < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to create a socket");
+    }
+    int ret = __test_connect_again_after_connected(fd);
+    close(fd);
+    close(listen_fd);
+    return ret;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_nonblocking_connect),
+    TEST_CASE(test_connect_again_after_connected),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,210 @@
+#include <sys/ioctl.h>
+#include <sys/socket.h>
+#include <sys/types.h>
+#include <arpa/inet.h>
+#include <netinet/in.h>
+#include <errno.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define TEST_PORT           8821
+// The time to wait for the data sent to arrive
+#define ARRIVE_WAIT_US      (100 * 1000)
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+// Make a connection, returning the accepted socket and the client socket
+static int connect_pair(int *accepted_fd, int *client_fd) {
+    int listen_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (listen_fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    int reuse = 1;
+    if (setsockopt(listen_fd, SOL_SOCKET, SO_REUSEADDR, &reuse, sizeof(reuse)) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to set SO_REUSEADDR");
+    }
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    addr.sin_port = htons(TEST_PORT);
+    if (bind(listen_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0 ||
+            listen(listen_fd, 1) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to listen on the socket");
+    }
+
+    *client_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (*client_fd < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to create a socket");
+    }
+    if (connect(*client_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        close(*client_fd);
+        close(listen_fd);
+        THROW_ERROR("failed to connect to the socket");
+    }
+    *accepted_fd = accept(listen_fd, NULL, NULL);
+    close(listen_fd);
+    if (*accepted_fd < 0) {
+        close(*client_fd);
+        THROW_ERROR("failed to accept the connection");
+    }
+    return 0;
+}
+
+static int check_at_mark(int fd, int expected_at_mark) {
+    int at_mark = -1;
+    if (ioctl(fd, SIOCATMARK, &at_mark) < 0) {
+        THROW_ERROR("failed to ioctl SIOCATMARK");
+    }
+    if (at_mark != expected_at_mark) {
+        THROW_ERROR("SIOCATMARK reports %d, but %d is expected", at_mark, expected_at_mark);
+    }
+    return 0;
+}
+
+// Send "ab", then the urgent byte "!", then "cd"
+static int send_with_oob(int fd, int use_sendmsg) {
+    if (send(fd, "ab", 2, 0) != 2) {
+        THROW_ERROR("failed to send the normal data");
+    }
+    if (use_sendmsg) {
+        struct iovec iov = { .iov_base = "!", .iov_len = 1 };
+        struct msghdr msg;
+        memset(&msg, 0, sizeof(msg));
+        msg.msg_iov = &iov;
+        msg.msg_iovlen = 1;
+        if (sendmsg(fd, &msg, MSG_OOB) != 1) {
+            THROW_ERROR("failed to sendmsg the out-of-band data");
+        }
+    } else if (send(fd, "!", 1, MSG_OOB) != 1) {
+        THROW_ERROR("failed to send the out-of-band data");
+    }
+    if (send(fd, "cd", 2, 0) != 2) {
+        THROW_ERROR("failed to send the normal data");
+    }
+    usleep(ARRIVE_WAIT_US);
+    return 0;
+}
+
+// ============================================================================
+// Test cases for out-of-band data
+// ============================================================================
+
+static int __test_oob(int accepted_fd, int client_fd, int use_sendmsg) {
+    char buf[16] = { 0 };
+
+    if (send_with_oob(client_fd, use_sendmsg) < 0) {
+        return -1;
+    }
+    if (check_at_mark(accepted_fd, 0) < 0) {
+        return -1;
+    }
+
+    // The out-of-band byte is received with MSG_OOB
+    if (use_sendmsg) {
+        struct iovec iov = { .iov_base = buf, .iov_len = sizeof(buf) };
+        struct msghdr msg;
+        memset(&msg, 0, sizeof(msg));
+        msg.msg_iov = &iov;
+        msg.msg_iovlen = 1;
+        if (recvmsg(accepted_fd, &msg, MSG_OOB) != 1 || buf[0] != '!') {
+            THROW_ERROR("failed to recvmsg the out-of-band data");
+        }
+    } else if (recv(accepted_fd, buf, sizeof(buf), MSG_OOB) != 1 || buf[0] != '!') {
+        THROW_ERROR("failed to recv the out-of-band data");
+    }
+
+    // A read stops at the mark, and the out-of-band byte is not inline
+    memset(buf, 0, sizeof(buf));
+    if (read(accepted_fd, buf, sizeof(buf)) != 2 || strcmp(buf, "ab") != 0) {
+        THROW_ERROR("the read should stop at the mark");
+    }
+    if (check_at_mark(accepted_fd, 1) < 0) {
+        return -1;
+    }
+    memset(buf, 0, sizeof(buf));
+    if (read(accepted_fd, buf, sizeof(buf)) != 2 || strcmp(buf, "cd") != 0) {
+        THROW_ERROR("the out-of-band byte should not be inline");
+    }
+    return 0;
+}
+
+static int test_send_and_recv_oob() {
+    int accepted_fd, client_fd;
+    if (connect_pair(&accepted_fd, &client_fd) < 0) {
+        return -1;
+    }
+    int ret = __test_oob(accepted_fd, client_fd, 0);
+    close(accepted_fd);
+    close(client_fd);
+    return ret;
+}
+
+static int test_sendmsg_and_recvmsg_oob() {
+    int accepted_fd, client_fd;
+    if (connect_pair(&accepted_fd, &client_fd) < 0) {
+        return -1;
+    }
+    int ret = __test_oob(accepted_fd, client_fd, 1);
+    close(accepted_fd);
+    close(client_fd);
+    return ret;
+}
+
+static int __test_oob_inline(int accepted_fd, int client_fd) {
+    char buf[16] = { 0 };
+    int oob_inline = 1;
+
+    if (setsockopt(accepted_fd, SOL_SOCKET, SO_OOBINLINE, &oob_inline,
+                   sizeof(oob_inline)) < 0) {
+        THROW_ERROR("failed to set SO_OOBINLINE");
+    }
+    if (send_with_oob(client_fd, 0) < 0) {
+        return -1;
+    }
+    if (recv(accepted_fd, buf, sizeof(buf), MSG_OOB) >= 0 || errno != EINVAL) {
+        THROW_ERROR("recv with MSG_OOB should fail with EINVAL for the inline data");
+    }
+    if (read(accepted_fd, buf, sizeof(buf)) != 2 || strcmp(buf, "ab") != 0) {
+        THROW_ERROR("the read should stop at the mark");
+    }
+    if (check_at_mark(accepted_fd, 1) < 0) {
+        return -1;
+    }
+    memset(buf, 0, sizeof(buf));
+    if (read(accepted_fd, buf, sizeof(buf)) != 3 || strcmp(buf, "!cd") != 0) {
+        THROW_ERROR("the out-of-band byte should be inline");
+    }
+    return 0;
+}
+
+static int test_oob_inline() {
+    int accepted_fd, client_fd;
+    if (connect_pair(&accepted_fd, &client_fd) < 0) {
+        return -1;
+    }
+    int ret = __test_oob_inline(accepted_fd, client_fd);
+    close(accepted_fd);
+    close(client_fd);
+    return ret;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_send_and_recv_oob),
+    TEST_CASE(test_sendmsg_and_recvmsg_oob),
+    TEST_CASE(test_oob_inline),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,325 @@
+#include <sys/epoll.h>
+#include <sys/socket.h>
+#include <sys/types.h>
+#include <arpa/inet.h>
+#include <netinet/in.h>
+#include <errno.h>
+#include <pthread.h>
+#include <signal.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define TEST_PORT           8820
+// The time to wait for the blocked thread to be blocked
+#define BLOCK_WAIT_US       (100 * 1000)
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int create_listen_socket() {
+    int listen_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (listen_fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    int reuse = 1;
+    if (setsockopt(listen_fd, SOL_SOCKET, SO_REUSEADDR, &reuse, sizeof(reuse)) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to set SO_REUSEADDR");
+    }
+
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    addr.sin_port = htons(TEST_PORT);
+    if (bind(listen_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to bind the socket");
+    }
+    if (listen(listen_fd, 1) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to listen on the socket");
+    }
+    return listen_fd;
+}
+
+// Make a connection, returning the accepted socket and the client socket
+static int connect_pair(int *accepted_fd, int *client_fd) {
+    int listen_fd = create_listen_socket();
+    if (listen_fd < 0) {
+        return -1;
+    }
+    *client_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (*client_fd < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to create a socket");
+    }
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    addr.sin_port = htons(TEST_PORT);
+    if (connect(*client_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        close(*client_fd);
+        close(listen_fd);
+        THROW_ERROR("failed to connect to the socket");
+    }
+    *accepted_fd = accept(listen_fd, NULL, NULL);
+    close(listen_fd);
+    if (*accepted_fd < 0) {
+        close(*client_fd);
+        THROW_ERROR("failed to accept the connection");
+    }
+    return 0;
+}
+
+struct blocked_read {
+    int fd;
+    int ret;
+};
+
+// A write after shutting down for writing raises SIGPIPE, which is counted
+static volatile int sigpipe_count = 0;
+
+static void handle_sigpipe(int signum) {
+    sigpipe_count++;
+}
+
+static void *read_blocked(void *arg) {
+    struct blocked_read *op = (struct blocked_read *)arg;
+    char buf[16];
+    op->ret = read(op->fd, buf, sizeof(buf));
+    return NULL;
+}
+
+// ============================================================================
+// Test cases for shutdown
+// ============================================================================
+
+static int __test_shutdown_write(int accepted_fd, int client_fd) {
+    const char request[] = "request";
+    const char reply[] = "reply";
+    char buf[16];
+
+    // The peer reads the request to the end of file after the half-close
+    if (write(client_fd, request, sizeof(request)) != sizeof(request)) {
+        THROW_ERROR("failed to write the request");
+    }
+    if (shutdown(client_fd, SHUT_WR) < 0) {
+        THROW_ERROR("failed to shut down for writing");
+    }
+    if (read(accepted_fd, buf, sizeof(buf)) != sizeof(request) ||
+            strcmp(buf, request) != 0) {
+        THROW_ERROR("failed to read the request");
+    }
+    if (read(accepted_fd, buf, sizeof(buf)) != 0) {
+        THROW_ERROR("the peer should see the end of file");
+    }
+
+    // The reverse direction still works
+    if (write(accepted_fd, reply, sizeof(reply)) != sizeof(reply)) {
+        THROW_ERROR("failed to write the reply");
+    }
+    if (read(client_fd, buf, sizeof(buf)) != sizeof(reply) || strcmp(buf, reply) != 0) {
+        THROW_ERROR("failed to read the reply after the half-close");
+    }
+
+    int old_count = sigpipe_count;
+    if (write(client_fd, request, sizeof(request)) >= 0 || errno != EPIPE) {
+        THROW_ERROR("write after shutting down for writing should fail with EPIPE");
+    }
+    if (send(client_fd, request, sizeof(request), 0) >= 0 || errno != EPIPE) {
+        THROW_ERROR("send after shutting down for writing should fail with EPIPE");
+    }
+    if (sigpipe_count != old_count + 2) {
+        THROW_ERROR("write and send after shutting down should raise SIGPIPE");
+    }
+    return 0;
+}
+
+static int test_shutdown_write() {
+    int accepted_fd, client_fd;
+    if (connect_pair(&accepted_fd, &client_fd) < 0) {
+        return -1;
+    }
+    int ret = __test_shutdown_write(accepted_fd, client_fd);
+    close(accepted_fd);
+    close(client_fd);
+    return ret;
+}
+
+static int __test_shutdown_read(int accepted_fd, int client_fd) {
+    const char msg[] = "hello";
+    char buf[16];
+
+    if (shutdown(client_fd, SHUT_RD) < 0) {
+        THROW_ERROR("failed to shut down for reading");
+    }
+    // The incoming data is discarded
+    if (write(accepted_fd, msg, sizeof(msg)) != sizeof(msg)) {
+        THROW_ERROR("failed to write to the socket");
+    }
+    if (read(client_fd, buf, sizeof(buf)) != 0) {
+        THROW_ERROR("read after shutting down for reading should return 0");
+    }
+    if (recv(client_fd, buf, sizeof(buf), 0) != 0) {
+        THROW_ERROR("recv after shutting down for reading should return 0");
+    }
+
+    // Writing still works
+    if (write(client_fd, msg, sizeof(msg)) != sizeof(msg)) {
+        THROW_ERROR("failed to write after shutting down for reading");
+    }
+    if (read(accepted_fd, buf, sizeof(buf)) != sizeof(msg)) {
+        THROW_ERROR("failed to read the data written after shutting down for reading");
+    }
+    return 0;
+}
+
+static int test_shutdown_read() {
+    int accepted_fd, client_fd;
+    if (connect_pair(&accepted_fd, &client_fd) < 0) {
+        return -1;
+    }
+    int ret = __test_shutdown_read(accepted_fd, client_fd);
+    close(accepted_fd);
+    close(client_fd);
+    return ret;
+}
+
+static int __test_shutdown_rdwr(int accepted_fd, int client_fd) {
+    const char msg[] = "hello";
+    char buf[16];
+
+    if (shutdown(client_fd, SHUT_RDWR) < 0) {
+        THROW_ERROR("failed to shut down for reading and writing");
+    }
+    if (read(client_fd, buf, sizeof(buf)) != 0) {
+        THROW_ERROR("read after shutting down should return 0");
+    }
+    if (write(client_fd, msg, sizeof(msg)) >= 0 || errno != EPIPE) {
+        THROW_ERROR("write after shutting down should fail with EPIPE");
+    }
+    if (read(accepted_fd, buf, sizeof(buf)) != 0) {
+        THROW_ERROR("the peer should see the end of file");
+    }
+    return 0;
+}
+
+static int test_shutdown_rdwr() {
+    int accepted_fd, client_fd;
+    if (connect_pair(&accepted_fd, &client_fd) < 0) {
+        return -1;
+    }
+    int ret = __test_shutdown_rdwr(accepted_fd, client_fd);
+    close(accepted_fd);
+    close(client_fd);
+    return ret;
+}
+
+static int test_shutdown_wakes_blocked_read() {
+    int accepted_fd, client_fd;
+    if (connect_pair(&accepted_fd, &client_fd) < 0) {
+        return -1;
+    }
+
+    struct blocked_read op = { .fd = client_fd, .ret = -1 };
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, read_blocked, &op) != 0) {
+        THROW_ERROR("failed to create a thread");
+    }
+    usleep(BLOCK_WAIT_US);
+    if (shutdown(client_fd, SHUT_RD) < 0) {
+        THROW_ERROR("failed to shut down for reading");
+    }
+    pthread_join(thread, NULL);
+    close(accepted_fd);
+    close(client_fd);
+    if (op.ret != 0) {
+        THROW_ERROR("the blocked read should return 0 after the shutdown");
+    }
+    return 0;
+}
+
+static int test_epoll_rdhup_after_peer_shutdown_write() {
+    int accepted_fd, client_fd;
+    if (connect_pair(&accepted_fd, &client_fd) < 0) {
+        return -1;
+    }
+    int ret = -1;
+    int ep_fd = epoll_create1(0);
+    if (ep_fd < 0) {
+        close(accepted_fd);
+        close(client_fd);
+        THROW_ERROR("failed to create an epoll");
+    }
+    struct epoll_event event = { .events = EPOLLIN | EPOLLRDHUP, .data.fd = accepted_fd };
+    if (epoll_ctl(ep_fd, EPOLL_CTL_ADD, accepted_fd, &event) < 0) {
+        printf("\t\tERROR: failed to add the socket to the epoll\n");
+        goto out;
+    }
+    if (shutdown(client_fd, SHUT_WR) < 0) {
+        printf("\t\tERROR: failed to shut down for writing\n");
+        goto out;
+    }
+    if (epoll_wait(ep_fd, &event, 1, 1000) != 1 || (event.events & EPOLLRDHUP) == 0) {
+        printf("\t\tERROR: EPOLLRDHUP should be reported after the peer shuts down for writing\n");
+        goto out;
+    }
+    ret = 0;
+out:
+    close(ep_fd);
+    close(accepted_fd);
+    close(client_fd);
+    return ret;
+}
+
+static int test_shutdown_unconnected() {
+    int fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    int ret = shutdown(fd, SHUT_WR);
+    int err = errno;
+    close(fd);
+    if (ret >= 0 || err != ENOTCONN) {
+        THROW_ERROR("shutdown of an unconnected socket should fail with ENOTCONN");
+    }
+    return 0;
+}
+
+static int test_shutdown_with_invalid_how() {
+    int accepted_fd, client_fd;
+    if (connect_pair(&accepted_fd, &client_fd) < 0) {
+        return -1;
+    }
+    int ret = shutdown(client_fd, 3);
+    int err = errno;
+    close(accepted_fd);
+    close(client_fd);
+    if (ret >= 0 || err != EINVAL) {
+        THROW_ERROR("shutdown with an invalid how should fail with EINVAL");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_shutdown_write),
+    TEST_CASE(test_shutdown_read),
+    TEST_CASE(test_shutdown_rdwr),
+    TEST_CASE(test_shutdown_wakes_blocked_read),
+    TEST_CASE(test_epoll_rdhup_after_peer_shutdown_write),
+    TEST_CASE(test_shutdown_unconnected),
+    TEST_CASE(test_shutdown_with_invalid_how),
+};
+
+int main() {
+    signal(SIGPIPE, handle_sigpipe);
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
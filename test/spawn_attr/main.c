@@ -0,0 +1,184 @@
+#include <sys/stat.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#ifndef SYS_spawn_with_attr
+#define SYS_spawn_with_attr 362
+#endif
+
+// The attributes of spawn, which are an extension of Occlum to posix_spawn
+#define SPAWN_SETCWD        1
+#define SPAWN_SETUMASK      2
+
+typedef struct {
+    unsigned int flags;
+    unsigned int umask;
+    const char *cwd;
+} spawn_attr_t;
+
+#define CHILD_CWD           "/root"
+#define CHILD_UMASK         0027
+#define CHILD_FILE_NAME     "test_spawn_attr.txt"
+#define CHILD_FILE_PATH     CHILD_CWD "/" CHILD_FILE_NAME
+#define MODE_MASK           0777
+// The symlinks are made on host by the Makefile, as there is no symlink
+// syscall: spawn_attr_cwd_link -> /root and spawn_attr_link -> /bin/spawn_attr
+#define CWD_SYMLINK_PATH    "/host/spawn_attr_cwd_link"
+#define ELF_SYMLINK_PATH    "/host/spawn_attr_link"
+
+static const char *child_argv[] = { "spawn_attr", "child", NULL };
+static const char *symlink_child_argv[] = { "spawn_attr", "symlink_child", NULL };
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int spawn_with_attr(int *child_pid, const spawn_attr_t *attr) {
+    return syscall(SYS_spawn_with_attr, child_pid, "/bin/spawn_attr", child_argv, NULL, NULL, attr);
+}
+
+static int wait_for_child(int child_pid) {
+    int status;
+    if (wait4(child_pid, &status, 0, NULL) < 0) {
+        THROW_ERROR("failed to wait4 the child process");
+    }
+    if (!WIFEXITED(status) || WEXITSTATUS(status) != 0) {
+        THROW_ERROR("test cases in child failed");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for spawn attributes
+// ============================================================================
+
+static int test_spawn_with_cwd_and_umask() {
+    unlink(CHILD_FILE_PATH);
+    spawn_attr_t attr = {
+        .flags = SPAWN_SETCWD | SPAWN_SETUMASK,
+        .umask = CHILD_UMASK,
+        .cwd = CHILD_CWD,
+    };
+    int child_pid;
+    if (spawn_with_attr(&child_pid, &attr) < 0) {
+        THROW_ERROR("failed to spawn a child process");
+    }
+    if (wait_for_child(child_pid) < 0) {
+        return -1;
+    }
+
+    struct stat stat_buf;
+    if (stat(CHILD_FILE_PATH, &stat_buf) < 0) {
+        THROW_ERROR("the file should be created in the cwd of the child");
+    }
+    mode_t expected_mode = 0666 & ~CHILD_UMASK;
+    if ((stat_buf.st_mode & MODE_MASK) != expected_mode) {
+        THROW_ERROR("incorrect file mode %o, expected %o", stat_buf.st_mode & MODE_MASK,
+                    expected_mode);
+    }
+    unlink(CHILD_FILE_PATH);
+    return 0;
+}
+
+static int test_spawn_with_symlinks() {
+    unlink(CHILD_FILE_PATH);
+    // Both the symlinks to the ELF and to the cwd are followed
+    spawn_attr_t attr = { .flags = SPAWN_SETCWD, .cwd = CWD_SYMLINK_PATH };
+    int child_pid;
+    int ret = syscall(SYS_spawn_with_attr, &child_pid, ELF_SYMLINK_PATH, symlink_child_argv,
+                      NULL, NULL, &attr);
+    if (ret < 0) {
+        THROW_ERROR("failed to spawn a child process with the symlinks");
+    }
+    if (wait_for_child(child_pid) < 0) {
+        return -1;
+    }
+
+    struct stat stat_buf;
+    if (stat(CHILD_FILE_PATH, &stat_buf) < 0) {
+        THROW_ERROR("the file should be created in the target of the cwd symlink");
+    }
+    unlink(CHILD_FILE_PATH);
+    return 0;
+}
+
+static int test_spawn_with_invalid_cwd() {
+    int child_pid;
+    spawn_attr_t attr = { .flags = SPAWN_SETCWD, .cwd = "/nonexistent_dir" };
+    if (spawn_with_attr(&child_pid, &attr) == 0 || errno != ENOENT) {
+        THROW_ERROR("spawn with a nonexistent cwd should fail with ENOENT");
+    }
+    attr.cwd = "/bin/spawn_attr";
+    if (spawn_with_attr(&child_pid, &attr) == 0 || errno != ENOTDIR) {
+        THROW_ERROR("spawn with a cwd that is not a directory should fail with ENOTDIR");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Child test cases
+// ============================================================================
+
+static int test_child_umask() {
+    mode_t mask = umask(0);
+    umask(mask);
+    if (mask != CHILD_UMASK) {
+        THROW_ERROR("incorrect umask %o, expected %o", mask, CHILD_UMASK);
+    }
+    return 0;
+}
+
+static int test_child_cwd() {
+    char cwd[128];
+    if (getcwd(cwd, sizeof(cwd)) == NULL) {
+        THROW_ERROR("failed to get the cwd");
+    }
+    if (strcmp(cwd, CHILD_CWD) != 0) {
+        THROW_ERROR("incorrect cwd %s, expected %s", cwd, CHILD_CWD);
+    }
+    return 0;
+}
+
+static int test_child_create_file() {
+    int fd = open(CHILD_FILE_NAME, O_WRONLY | O_CREAT | O_TRUNC, 0666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file in the cwd");
+    }
+    close(fd);
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_spawn_with_cwd_and_umask),
+    TEST_CASE(test_spawn_with_symlinks),
+    TEST_CASE(test_spawn_with_invalid_cwd),
+};
+
+static test_case_t child_test_cases[] = {
+    TEST_CASE(test_child_umask),
+    TEST_CASE(test_child_cwd),
+    TEST_CASE(test_child_create_file),
+};
+
+static test_case_t symlink_child_test_cases[] = {
+    TEST_CASE(test_child_create_file),
+};
+
+int main(int argc, const char *argv[]) {
+    if (argc > 1 && strcmp(argv[1], "child") == 0) {
+        return test_suite_run(child_test_cases, ARRAY_SIZE(child_test_cases));
+    }
+    if (argc > 1 && strcmp(argv[1], "symlink_child") == 0) {
+        return test_suite_run(symlink_child_test_cases, ARRAY_SIZE(symlink_child_test_cases));
+    }
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
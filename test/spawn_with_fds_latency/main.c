@@ -0,0 +1,79 @@
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <sys/time.h>
+#include <fcntl.h>
+#include <unistd.h>
+#include <stdio.h>
+#include <spawn.h>
+
+#define NREPEATS 1000
+#define NFDS 4096
+
+// Return the average latency of spawn/exit in us, or -1 on error
+static long spawn_latency() {
+    struct timeval tv_start, tv_end;
+
+    gettimeofday(&tv_start, NULL);
+    for (unsigned long i = 0; i < NREPEATS; i++) {
+        int child_pid, status;
+        if (posix_spawn(&child_pid, "/bin/empty", NULL, NULL, NULL, NULL) <0) {
+            printf("ERROR: failed to spawn (# of repeats = %lu)\n", i);
+            return -1;
+        }
+        if (wait4(-1, &status, 0, NULL) < 0) {
+            printf("ERROR: failed to wait4 (# of repeats = %lu)\n", i);
+            return -1;
+        }
+        if (status != 0) {
+            printf("ERROR: child process exits with error\n");
+            return -1;
+        }
+    }
+    gettimeofday(&tv_end, NULL);
+
+    suseconds_t total_us = (tv_end.tv_sec - tv_start.tv_sec) * 1000000UL +
+                         + (tv_end.tv_usec - tv_start.tv_usec);
+    return total_us / NREPEATS;
+}
+
+int main(int argc, const char* argv[]) {
+    // The baseline without the fds to clone
+    long latency = spawn_latency();
+    if (latency < 0) {
+        return -1;
+    }
+    printf("Latency of spawn/exit with no extra open fds = %ld us\n", latency);
+
+    // Half of the fds are inherited by the children and the others are not
+    int fds[NFDS];
+    for (int i = 0; i < NFDS; i++) {
+        int flags = (i % 2 == 0) ? O_RDONLY : (O_RDONLY | O_CLOEXEC);
+        fds[i] = open("/dev/null", flags);
+        if (fds[i] < 0) {
+            printf("ERROR: failed to open /dev/null (# of fds = %d)\n", i);
+            return -1;
+        }
+    }
+    latency = spawn_latency();
+    if (latency < 0) {
+        return -1;
+    }
+    printf("Latency of spawn/exit with %d open fds, half of them O_CLOEXEC = %ld us\n",
+           NFDS, latency);
+
+    // All the fds are cloned for the children, which is what the spawn used
+    // to do before closing the O_CLOEXEC ones
+    for (int i = 1; i < NFDS; i += 2) {
+        if (fcntl(fds[i], F_SETFD, 0) < 0) {
+            printf("ERROR: failed to clear FD_CLOEXEC\n");
+            return -1;
+        }
+    }
+    latency = spawn_latency();
+    if (latency < 0) {
+        return -1;
+    }
+    printf("Latency of spawn/exit with %d open fds, none of them O_CLOEXEC = %ld us\n",
+           NFDS, latency);
+    return 0;
+}
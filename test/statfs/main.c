@@ -0,0 +1,111 @@
+#include <sys/vfs.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <unistd.h>
+#include "test.h"
+
+// The magic numbers of the file systems, which must be in sync with
+// src/libos/src/fs/fs_ops/statfs.rs
+#define SEFS_MAGIC      0x53454653
+#define HOSTFS_MAGIC    0x00c0ffee
+#define RAMFS_MAGIC     0x858458f6
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int check_statfs(const struct statfs *buf, long expected_type) {
+    if (buf->f_type != expected_type) {
+        THROW_ERROR("unexpected f_type");
+    }
+    if (buf->f_bsize <= 0) {
+        THROW_ERROR("f_bsize must be positive");
+    }
+    if (buf->f_bfree > buf->f_blocks || buf->f_bavail > buf->f_blocks) {
+        THROW_ERROR("the free blocks must not exceed the total blocks");
+    }
+    if (buf->f_ffree > buf->f_files) {
+        THROW_ERROR("the free file nodes must not exceed the total file nodes");
+    }
+    if (buf->f_namelen <= 0) {
+        THROW_ERROR("f_namelen must be positive");
+    }
+    return 0;
+}
+
+static int __test_statfs(const char *path, long expected_type) {
+    struct statfs buf;
+    if (statfs(path, &buf) < 0) {
+        THROW_ERROR("failed to statfs");
+    }
+    return check_statfs(&buf, expected_type);
+}
+
+// ============================================================================
+// Test cases for statfs
+// ============================================================================
+
+static int test_statfs_on_sefs() {
+    return __test_statfs("/bin", SEFS_MAGIC);
+}
+
+static int test_statfs_on_hostfs() {
+    return __test_statfs("/host", HOSTFS_MAGIC);
+}
+
+static int test_statfs_on_ramfs() {
+    return __test_statfs("/tmp", RAMFS_MAGIC);
+}
+
+static int test_statfs_with_nonexistent_path() {
+    struct statfs buf;
+    if (statfs("/bin/nonexistent_file", &buf) == 0 || errno != ENOENT) {
+        THROW_ERROR("statfs on a nonexistent path should fail with ENOENT");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for fstatfs
+// ============================================================================
+
+static int test_fstatfs() {
+    const char *file_path = "/root/test_fstatfs.txt";
+    int fd = open(file_path, O_RDWR | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+
+    struct statfs fbuf, buf;
+    if (fstatfs(fd, &fbuf) < 0) {
+        THROW_ERROR("failed to fstatfs");
+    }
+    close(fd);
+    if (check_statfs(&fbuf, SEFS_MAGIC) < 0) {
+        THROW_ERROR("unexpected result of fstatfs");
+    }
+    if (statfs(file_path, &buf) < 0) {
+        THROW_ERROR("failed to statfs");
+    }
+    if (buf.f_type != fbuf.f_type || buf.f_blocks != fbuf.f_blocks) {
+        THROW_ERROR("the results of statfs and fstatfs do not match");
+    }
+    unlink(file_path);
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_statfs_on_sefs),
+    TEST_CASE(test_statfs_on_hostfs),
+    TEST_CASE(test_statfs_on_ramfs),
+    TEST_CASE(test_statfs_with_nonexistent_path),
+    TEST_CASE(test_fstatfs),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
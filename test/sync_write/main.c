@@ -0,0 +1,164 @@
+#include <sys/stat.h>
+#include <sys/types.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+// The file written by the run that crashes, which is done by the Makefile
+// before the test
+#define CRASH_FILE_PATH     "/root/test_sync_write_crash.txt"
+#define FILE_PATH           "/root/test_sync_write.txt"
+#define BUF_SIZE            (64 * 1024)
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static char write_buf[BUF_SIZE];
+static char read_buf[BUF_SIZE];
+
+static void fill_buf(char *buf, size_t len, char c) {
+    for (size_t i = 0; i < len; i++) {
+        buf[i] = c + (i % 26);
+    }
+}
+
+static int check_file_content(const char *path, const char *expected, size_t len) {
+    int fd = open(path, O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the file to check");
+    }
+    memset(read_buf, 0, sizeof(read_buf));
+    ssize_t read_len = read(fd, read_buf, sizeof(read_buf));
+    close(fd);
+    if (read_len != len || memcmp(read_buf, expected, len) != 0) {
+        THROW_ERROR("the content of the file is not the one written");
+    }
+    return 0;
+}
+
+static int check_status_flags(int fd, int flags) {
+    int fl = fcntl(fd, F_GETFL);
+    if (fl < 0) {
+        THROW_ERROR("failed to get the status flags");
+    }
+    if ((fl & O_SYNC) != (flags & O_SYNC) || (fl & O_DSYNC) != (flags & O_DSYNC)) {
+        THROW_ERROR("the sync flags are not reported correctly: %#o", fl);
+    }
+    return 0;
+}
+
+// Write the first half of the file with O_SYNC, and the second half with
+// O_DSYNC, and then crash with the file open, so that nothing is synced but
+// the writes themselves
+static int write_and_crash() {
+    fill_buf(write_buf, BUF_SIZE, 'a');
+    int sync_fd = open(CRASH_FILE_PATH, O_WRONLY | O_CREAT | O_TRUNC | O_SYNC, 00666);
+    int dsync_fd = open(CRASH_FILE_PATH, O_WRONLY | O_DSYNC);
+    if (sync_fd < 0 || dsync_fd < 0) {
+        THROW_ERROR("failed to open the file to write");
+    }
+    if (write(sync_fd, write_buf, BUF_SIZE / 2) != BUF_SIZE / 2) {
+        THROW_ERROR("failed to write with O_SYNC");
+    }
+    if (pwrite(dsync_fd, write_buf + BUF_SIZE / 2, BUF_SIZE / 2, BUF_SIZE / 2) !=
+            BUF_SIZE / 2) {
+        THROW_ERROR("failed to write with O_DSYNC");
+    }
+    __builtin_trap();
+    return 0;
+}
+
+// ============================================================================
+// Test cases for O_SYNC and O_DSYNC
+// ============================================================================
+
+static int test_data_survives_crash() {
+    fill_buf(write_buf, BUF_SIZE, 'a');
+    if (check_file_content(CRASH_FILE_PATH, write_buf, BUF_SIZE) < 0) {
+        return -1;
+    }
+    unlink(CRASH_FILE_PATH);
+    return 0;
+}
+
+static int __test_sync_write(int flags) {
+    int fd = open(FILE_PATH, O_RDWR | O_CREAT | O_TRUNC | flags, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the file");
+    }
+    if (check_status_flags(fd, flags) < 0) {
+        close(fd);
+        return -1;
+    }
+    fill_buf(write_buf, BUF_SIZE, 'A');
+    if (write(fd, write_buf, BUF_SIZE / 2) != BUF_SIZE / 2 ||
+            pwrite(fd, write_buf + BUF_SIZE / 2, BUF_SIZE / 2, BUF_SIZE / 2) != BUF_SIZE / 2) {
+        close(fd);
+        THROW_ERROR("failed to write the file");
+    }
+    close(fd);
+    if (check_file_content(FILE_PATH, write_buf, BUF_SIZE) < 0) {
+        return -1;
+    }
+    unlink(FILE_PATH);
+    return 0;
+}
+
+static int test_o_sync_write() {
+    return __test_sync_write(O_SYNC);
+}
+
+static int test_o_dsync_write() {
+    return __test_sync_write(O_DSYNC);
+}
+
+// Unlike Linux, which ignores O_SYNC and O_DSYNC in F_SETFL, they can be set
+// and cleared by F_SETFL
+static int test_setfl() {
+    int fd = open(FILE_PATH, O_RDWR | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the file");
+    }
+    int ret = -1;
+    if (check_status_flags(fd, 0) < 0) {
+        goto out;
+    }
+    if (fcntl(fd, F_SETFL, O_DSYNC) < 0 || check_status_flags(fd, O_DSYNC) < 0) {
+        printf("\t\tERROR: O_DSYNC should be set by F_SETFL\n");
+        goto out;
+    }
+    if (fcntl(fd, F_SETFL, O_SYNC) < 0 || check_status_flags(fd, O_SYNC) < 0) {
+        printf("\t\tERROR: O_SYNC should be set by F_SETFL\n");
+        goto out;
+    }
+    if (fcntl(fd, F_SETFL, 0) < 0 || check_status_flags(fd, 0) < 0) {
+        printf("\t\tERROR: O_SYNC should be cleared by F_SETFL\n");
+        goto out;
+    }
+    ret = 0;
+out:
+    close(fd);
+    unlink(FILE_PATH);
+    return ret;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_data_survives_crash),
+    TEST_CASE(test_o_sync_write),
+    TEST_CASE(test_o_dsync_write),
+    TEST_CASE(test_setfl),
+};
+
+int main(int argc, const char *argv[]) {
+    if (argc > 1 && strcmp(argv[1], "write_and_crash") == 0) {
+        return write_and_crash();
+    }
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
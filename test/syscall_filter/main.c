@@ -0,0 +1,224 @@
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <errno.h>
+#include <pthread.h>
+#include <signal.h>
+#include <spawn.h>
+#include <stdint.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define SYS_set_syscall_filter      361
+
+#define SYSCALL_FILTER_RET_ALLOW    0x7fff0000U
+#define SYSCALL_FILTER_RET_ERRNO    0x00050000U
+#define SYSCALL_FILTER_RET_KILL     0x80000000U
+
+struct syscall_filter_rule {
+    uint32_t nr;
+    int32_t arg_index;
+    uint64_t arg_min;
+    uint64_t arg_max;
+    uint32_t action;
+};
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int set_syscall_filter(struct syscall_filter_rule *rules, size_t num_rules,
+                              uint32_t default_action) {
+    return syscall(SYS_set_syscall_filter, rules, num_rules, default_action);
+}
+
+static int deny_syscall(uint32_t nr, uint32_t action) {
+    struct syscall_filter_rule rule = {
+        .nr = nr,
+        .arg_index = -1,
+        .action = action,
+    };
+    if (set_syscall_filter(&rule, 1, SYSCALL_FILTER_RET_ALLOW) < 0) {
+        THROW_ERROR("failed to set the syscall filter");
+    }
+    return 0;
+}
+
+static int is_getppid_denied(void) {
+    return syscall(SYS_getppid) < 0 && errno == EPERM;
+}
+
+static void *thread_func(void *arg) {
+    int *denied = (int *)arg;
+    *denied = is_getppid_denied();
+    return NULL;
+}
+
+// Run in the child process, which inherits the filters of the parent
+static int child_main(void) {
+    if (!is_getppid_denied()) {
+        return 1;
+    }
+    if (deny_syscall(SYS_getpgid, SYSCALL_FILTER_RET_KILL) < 0) {
+        return 1;
+    }
+    // The child should be killed here
+    syscall(SYS_getpgid, 0);
+    return 2;
+}
+
+static void *kill_in_thread(void *arg) {
+    if (deny_syscall(SYS_getpgid, SYSCALL_FILTER_RET_KILL) == 0) {
+        syscall(SYS_getpgid, 0);
+    }
+    return NULL;
+}
+
+// Run in the child process, whose main thread keeps making syscalls while
+// another thread is killed by the filter, which kills the main thread, too
+static int child_main_with_threads(void) {
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, kill_in_thread, NULL) != 0) {
+        return 1;
+    }
+    for (int i = 0; i < 5000; i++) {
+        usleep(1000);
+    }
+    return 2;
+}
+
+// ============================================================================
+// Test cases for syscall filters
+// ============================================================================
+
+static int test_invalid_filter() {
+    struct syscall_filter_rule rule = {
+        .nr = SYS_getppid,
+        .arg_index = 6,
+        .action = SYSCALL_FILTER_RET_ALLOW,
+    };
+    if (set_syscall_filter(&rule, 1, SYSCALL_FILTER_RET_ALLOW) == 0 || errno != EINVAL) {
+        THROW_ERROR("an invalid argument index should fail with EINVAL");
+    }
+    if (set_syscall_filter(NULL, 0, 0x12340000) == 0 || errno != EINVAL) {
+        THROW_ERROR("an invalid action should fail with EINVAL");
+    }
+    return 0;
+}
+
+static int test_errno_action() {
+    if (deny_syscall(SYS_getppid, SYSCALL_FILTER_RET_ERRNO | EPERM) < 0) {
+        return -1;
+    }
+    if (!is_getppid_denied()) {
+        THROW_ERROR("the denied syscall should fail with the errno");
+    }
+    if (syscall(SYS_getpid) < 0) {
+        THROW_ERROR("other syscalls should be allowed");
+    }
+    return 0;
+}
+
+static int test_arg_range() {
+    struct syscall_filter_rule rule = {
+        .nr = SYS_dup,
+        .arg_index = 0,
+        .arg_min = 100,
+        .arg_max = 200,
+        .action = SYSCALL_FILTER_RET_ERRNO | EACCES,
+    };
+    if (set_syscall_filter(&rule, 1, SYSCALL_FILTER_RET_ALLOW) < 0) {
+        THROW_ERROR("failed to set the syscall filter");
+    }
+    int fd = dup(0);
+    if (fd < 0) {
+        THROW_ERROR("the syscall with an argument out of the range should be allowed");
+    }
+    close(fd);
+    if (dup(150) >= 0 || errno != EACCES) {
+        THROW_ERROR("the syscall with an argument in the range should be denied");
+    }
+    return 0;
+}
+
+static int test_filter_cannot_be_relaxed() {
+    struct syscall_filter_rule rule = {
+        .nr = SYS_getppid,
+        .arg_index = -1,
+        .action = SYSCALL_FILTER_RET_ALLOW,
+    };
+    if (set_syscall_filter(&rule, 1, SYSCALL_FILTER_RET_ALLOW) < 0) {
+        THROW_ERROR("failed to set the syscall filter");
+    }
+    if (!is_getppid_denied()) {
+        THROW_ERROR("a new filter should not allow a denied syscall");
+    }
+    return 0;
+}
+
+static int test_filter_inherited_by_thread() {
+    int denied = 0;
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, thread_func, &denied) != 0) {
+        THROW_ERROR("failed to create a thread");
+    }
+    pthread_join(thread, NULL);
+    if (!denied) {
+        THROW_ERROR("the filter should be inherited by the thread");
+    }
+    return 0;
+}
+
+static int test_filter_inherited_by_child_and_kill() {
+    int child_pid, status;
+    char *child_argv[] = {"syscall_filter", "child", NULL};
+    if (posix_spawn(&child_pid, "/bin/syscall_filter", NULL, NULL, child_argv, NULL) < 0) {
+        THROW_ERROR("failed to spawn a child process");
+    }
+    if (wait4(child_pid, &status, 0, NULL) < 0) {
+        THROW_ERROR("failed to wait4 the child process");
+    }
+    if (!WIFSIGNALED(status) || WTERMSIG(status) != SIGSYS) {
+        THROW_ERROR("the child should be killed by the filter (status = %d)", status);
+    }
+    return 0;
+}
+
+static int test_kill_whole_process() {
+    int child_pid, status;
+    char *child_argv[] = {"syscall_filter", "child_with_threads", NULL};
+    if (posix_spawn(&child_pid, "/bin/syscall_filter", NULL, NULL, child_argv, NULL) < 0) {
+        THROW_ERROR("failed to spawn a child process");
+    }
+    if (wait4(child_pid, &status, 0, NULL) < 0) {
+        THROW_ERROR("failed to wait4 the child process");
+    }
+    if (!WIFSIGNALED(status) || WTERMSIG(status) != SIGSYS) {
+        THROW_ERROR("the whole child should be killed by the filter (status = %d)", status);
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_invalid_filter),
+    TEST_CASE(test_errno_action),
+    TEST_CASE(test_arg_range),
+    TEST_CASE(test_filter_cannot_be_relaxed),
+    TEST_CASE(test_filter_inherited_by_thread),
+    TEST_CASE(test_filter_inherited_by_child_and_kill),
+    TEST_CASE(test_kill_whole_process),
+};
+
+int main(int argc, const char *argv[]) {
+    if (argc > 1 && strcmp(argv[1], "child") == 0) {
+        return child_main();
+    }
+    if (argc > 1 && strcmp(argv[1], "child_with_threads") == 0) {
+        return child_main_with_threads();
+    }
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,169 @@
+#include <sys/socket.h>
+#include <netinet/in.h>
+#include <netinet/tcp.h>
+#include <arpa/inet.h>
+#include <pthread.h>
+#include <time.h>
+#include <unistd.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#define KB                  (1024UL)
+#define MB                  (1024UL * 1024UL)
+
+#define NREPEATS            10000
+#define TOTAL_BYTES         (512 * MB)
+#define BUF_SIZE            (64 * KB)
+#define SERVER_PORT         8825
+
+// The connections to 127.0.0.1 are looped back inside the enclave, while the
+// ones to the other loopback addresses go through the host
+#define ENCLAVE_LOOPBACK    "127.0.0.1"
+#define HOST_LOOPBACK       "127.0.0.2"
+
+static char buf[BUF_SIZE];
+
+static unsigned long elapsed_ns(const struct timespec *start, const struct timespec *end) {
+    return (end->tv_sec - start->tv_sec) * 1000000000UL + (end->tv_nsec - start->tv_nsec);
+}
+
+static int create_listen_socket() {
+    int listen_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (listen_fd < 0) {
+        printf("ERROR: failed to create a socket\n");
+        return -1;
+    }
+    int reuse = 1;
+    setsockopt(listen_fd, SOL_SOCKET, SO_REUSEADDR, &reuse, sizeof(reuse));
+
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_port = htons(SERVER_PORT);
+    addr.sin_addr.s_addr = htonl(INADDR_ANY);
+    if (bind(listen_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0 ||
+            listen(listen_fd, 1) < 0) {
+        printf("ERROR: failed to listen\n");
+        return -1;
+    }
+    return listen_fd;
+}
+
+static int connect_pair(int listen_fd, const char *ip, int *client_fd, int *server_fd) {
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_port = htons(SERVER_PORT);
+    addr.sin_addr.s_addr = inet_addr(ip);
+
+    *client_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (*client_fd < 0 || connect(*client_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        printf("ERROR: failed to connect\n");
+        return -1;
+    }
+    *server_fd = accept(listen_fd, NULL, NULL);
+    if (*server_fd < 0) {
+        printf("ERROR: failed to accept\n");
+        return -1;
+    }
+    int one = 1;
+    setsockopt(*client_fd, IPPROTO_TCP, TCP_NODELAY, &one, sizeof(one));
+    setsockopt(*server_fd, IPPROTO_TCP, TCP_NODELAY, &one, sizeof(one));
+    return 0;
+}
+
+static void *echo_thread_func(void *arg) {
+    int fd = *(int *)arg;
+    char c;
+    while (recv(fd, &c, 1, 0) == 1) {
+        if (send(fd, &c, 1, 0) != 1) {
+            break;
+        }
+    }
+    return NULL;
+}
+
+static void *sink_thread_func(void *arg) {
+    int fd = *(int *)arg;
+    static char sink_buf[BUF_SIZE];
+    while (read(fd, sink_buf, sizeof(sink_buf)) > 0) {
+    }
+    return NULL;
+}
+
+static int bench_latency(int listen_fd, const char *name, const char *ip) {
+    int client_fd, server_fd;
+    if (connect_pair(listen_fd, ip, &client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    pthread_t echo_thread;
+    if (pthread_create(&echo_thread, NULL, echo_thread_func, &server_fd) != 0) {
+        printf("ERROR: failed to create the echo thread\n");
+        return -1;
+    }
+
+    struct timespec start, end;
+    char c = 'x';
+    clock_gettime(CLOCK_MONOTONIC, &start);
+    for (unsigned long i = 0; i < NREPEATS; i++) {
+        if (send(client_fd, &c, 1, 0) != 1 || recv(client_fd, &c, 1, 0) != 1) {
+            printf("ERROR: failed to send or receive the echo\n");
+            return -1;
+        }
+    }
+    clock_gettime(CLOCK_MONOTONIC, &end);
+    printf("Round-trip latency (%s) = %lu ns\n", name, elapsed_ns(&start, &end) / NREPEATS);
+
+    close(client_fd);
+    pthread_join(echo_thread, NULL);
+    close(server_fd);
+    return 0;
+}
+
+static int bench_throughput(int listen_fd, const char *name, const char *ip) {
+    int client_fd, server_fd;
+    if (connect_pair(listen_fd, ip, &client_fd, &server_fd) < 0) {
+        return -1;
+    }
+    pthread_t sink_thread;
+    if (pthread_create(&sink_thread, NULL, sink_thread_func, &server_fd) != 0) {
+        printf("ERROR: failed to create the sink thread\n");
+        return -1;
+    }
+
+    struct timespec start, end;
+    clock_gettime(CLOCK_MONOTONIC, &start);
+    for (unsigned long sent = 0; sent < TOTAL_BYTES;) {
+        ssize_t len = write(client_fd, buf, sizeof(buf));
+        if (len <= 0) {
+            printf("ERROR: failed to write\n");
+            return -1;
+        }
+        sent += len;
+    }
+    close(client_fd);
+    pthread_join(sink_thread, NULL);
+    clock_gettime(CLOCK_MONOTONIC, &end);
+    close(server_fd);
+
+    double secs = elapsed_ns(&start, &end) / 1e9;
+    printf("Throughput (%s) = %.2f MB/s\n", name, TOTAL_BYTES / secs / MB);
+    return 0;
+}
+
+int main(int argc, const char *argv[]) {
+    int listen_fd = create_listen_socket();
+    if (listen_fd < 0) {
+        return -1;
+    }
+    int ret = 0;
+    if (bench_latency(listen_fd, "in-enclave loopback", ENCLAVE_LOOPBACK) < 0 ||
+            bench_latency(listen_fd, "host", HOST_LOOPBACK) < 0 ||
+            bench_throughput(listen_fd, "in-enclave loopback", ENCLAVE_LOOPBACK) < 0 ||
+            bench_throughput(listen_fd, "host", HOST_LOOPBACK) < 0) {
+        ret = -1;
+    }
+    close(listen_fd);
+    return ret;
+}
@@ -62,9 +62,15 @@ $(BUILD_DIR)/test/obj/$(TEST_NAME)/%.o: %.cc
 # Test
 #############################################################################
 
+# The Occlum instance that the test runs in, which is shared by the tests,
+# unless a test needs a configuration of its own
+INSTANCE_DIR ?= $(BUILD_DIR)/test
+# The command that the test is run by, if any
+TEST_RUNNER ?=
+
 test:
-	@cd $(BUILD_DIR)/test && \
-		$(BUILD_DIR)/bin/occlum run /bin/$(TEST_NAME) $(BIN_ARGS)
+	@cd $(INSTANCE_DIR) && \
+		$(TEST_RUNNER) $(BUILD_DIR)/bin/occlum run /bin/$(TEST_NAME) $(BIN_ARGS)
 
 test-native:
 	@LD_LIBRARY_PATH=/usr/local/occlum/lib cd $(IMAGE_DIR) && ./bin/$(TEST_NAME) $(BIN_ARGS)
@@ -1,4 +1,5 @@
 #include <sys/time.h>
+#include <errno.h>
 #include <time.h>
 #include "test.h"
 
@@ -29,6 +30,69 @@ int test_clock_gettime() {
     return 0;
 }
 
+int test_clock_gettime_monotonic() {
+    clockid_t clocks[] = {
+        CLOCK_MONOTONIC,
+        CLOCK_MONOTONIC_RAW,
+        CLOCK_BOOTTIME,
+        CLOCK_PROCESS_CPUTIME_ID,
+        CLOCK_THREAD_CPUTIME_ID,
+    };
+    for (int i = 0; i < ARRAY_SIZE(clocks); i++) {
+        struct timespec prev, curr;
+        if (clock_gettime(clocks[i], &prev)) {
+            THROW_ERROR("clock_gettime(%d, ...) failed", clocks[i]);
+        }
+        for (int j = 0; j < 100; j++) {
+            if (clock_gettime(clocks[i], &curr)) {
+                THROW_ERROR("clock_gettime(%d, ...) failed", clocks[i]);
+            }
+            if (curr.tv_sec < prev.tv_sec ||
+                    (curr.tv_sec == prev.tv_sec && curr.tv_nsec < prev.tv_nsec)) {
+                THROW_ERROR("clock %d goes backwards", clocks[i]);
+            }
+            prev = curr;
+        }
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for clock_getres
+// ============================================================================
+
+int test_clock_getres() {
+    clockid_t clocks[] = {
+        CLOCK_REALTIME,
+        CLOCK_MONOTONIC,
+        CLOCK_MONOTONIC_RAW,
+        CLOCK_BOOTTIME,
+        CLOCK_PROCESS_CPUTIME_ID,
+        CLOCK_THREAD_CPUTIME_ID,
+    };
+    for (int i = 0; i < ARRAY_SIZE(clocks); i++) {
+        struct timespec res;
+        if (clock_getres(clocks[i], &res)) {
+            THROW_ERROR("clock_getres(%d, ...) failed", clocks[i]);
+        }
+        if (res.tv_sec == 0 && res.tv_nsec == 0) {
+            THROW_ERROR("clock_getres(%d, ...) returns zero resolution", clocks[i]);
+        }
+        if (clock_getres(clocks[i], NULL)) {
+            THROW_ERROR("clock_getres(%d, NULL) failed", clocks[i]);
+        }
+    }
+    return 0;
+}
+
+int test_clock_getres_with_invalid_clockid() {
+    struct timespec res;
+    if (clock_getres(1000, &res) != -1 || errno != EINVAL) {
+        THROW_ERROR("clock_getres with an invalid clock id should fail with EINVAL");
+    }
+    return 0;
+}
+
 // ============================================================================
 // Test suite
 // ============================================================================
@@ -36,6 +100,9 @@ int test_clock_gettime() {
 static test_case_t test_cases[] = {
     TEST_CASE(test_gettimeofday),
     TEST_CASE(test_clock_gettime),
+    TEST_CASE(test_clock_gettime_monotonic),
+    TEST_CASE(test_clock_getres),
+    TEST_CASE(test_clock_getres_with_invalid_clockid),
 };
 
 int main() {
@@ -1,80 +1,259 @@
-#include <sys/types.h>
+#include <sys/resource.h>
 #include <sys/stat.h>
+#include <sys/types.h>
+#include <errno.h>
 #include <fcntl.h>
-#include <unistd.h>
+#include <signal.h>
 #include <string.h>
-#include <stdio.h>
+#include <unistd.h>
+#include "test.h"
 
-int main(int argc, const char* argv[]) {
-    const char* FILE_NAME = "root/test_filesystem_truncate.txt";
-    const int TRUNC_LEN = 256;
-    const int TRUNC_LEN1 = 128;
-    const int MODE_MASK = 0777;
+#define SEFS_FILE_PATH      "/root/test_filesystem_truncate.txt"
+#define RAMFS_FILE_PATH     "/tmp/test_filesystem_truncate.txt"
 
-    int ret;
+#define TRUNC_LEN           256
+#define TRUNC_LEN1          128
+#define MODE_MASK           0777
 
-    int flags = O_WRONLY | O_CREAT| O_TRUNC;
-    int mode = 00666;
-    int fd = open(FILE_NAME, flags, mode);
-    if (fd < 0) {
-        printf("failed to open a file for write\n");
-        return fd;
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int check_file_size(const char *path, off_t expected_size) {
+    struct stat stat_buf;
+    if (stat(path, &stat_buf) < 0) {
+        THROW_ERROR("failed to stat the file");
+    }
+    if (stat_buf.st_size != expected_size) {
+        THROW_ERROR("incorrect file size %ld, expected %ld", stat_buf.st_size, expected_size);
+    }
+    return 0;
+}
+
+static int check_zeros(int fd, off_t offset, size_t len) {
+    char buf[512];
+    while (len > 0) {
+        size_t read_len = MIN(len, sizeof(buf));
+        memset(buf, 'x', read_len);
+        if (pread(fd, buf, read_len, offset) != read_len) {
+            THROW_ERROR("failed to read the file");
+        }
+        for (size_t i = 0; i < read_len; i++) {
+            if (buf[i] != 0) {
+                THROW_ERROR("the extended range should be filled with zeros");
+            }
+        }
+        offset += read_len;
+        len -= read_len;
     }
+    return 0;
+}
+
+static int __test_grow_reads_zeros(const char *path) {
+    static char data[8192];
+    memset(data, 'a', sizeof(data));
 
-    if (access(FILE_NAME, F_OK) < 0) {
-        printf("cannot access the new file\n");
+    int fd = open(path, O_RDWR | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+    if (write(fd, data, sizeof(data)) != sizeof(data)) {
+        THROW_ERROR("failed to write the file");
+    }
+    // Shrink to the middle of a block, then grow beyond the old size
+    if (ftruncate(fd, 100) < 0 || ftruncate(fd, 3 * sizeof(data)) < 0) {
+        THROW_ERROR("failed to truncate the file");
+    }
+    if (check_file_size(path, 3 * sizeof(data)) < 0) {
+        return -1;
+    }
+    char buf[100];
+    if (pread(fd, buf, sizeof(buf), 0) != sizeof(buf) || memcmp(buf, data, sizeof(buf)) != 0) {
+        THROW_ERROR("the data before the truncated size should be kept");
+    }
+    if (check_zeros(fd, 100, 3 * sizeof(data) - 100) < 0) {
         return -1;
     }
+    close(fd);
+    unlink(path);
+    return 0;
+}
 
-    ret = ftruncate(fd, TRUNC_LEN);
-    if (ret < 0) {
-        printf("failed to ftruncate the file\n");
-        return ret;
+static int __test_shrink_reads_eof(const char *path) {
+    static char data[8192];
+    memset(data, 'a', sizeof(data));
+
+    int fd = open(path, O_RDWR | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+    if (write(fd, data, sizeof(data)) != sizeof(data)) {
+        THROW_ERROR("failed to write the file");
+    }
+    if (ftruncate(fd, 100) < 0) {
+        THROW_ERROR("failed to truncate the file");
+    }
+    // A read stops at the new size, and a read beyond it reads nothing
+    char buf[200];
+    if (pread(fd, buf, sizeof(buf), 0) != 100) {
+        THROW_ERROR("the read should stop at the truncated size");
+    }
+    if (pread(fd, buf, sizeof(buf), 100) != 0 || pread(fd, buf, sizeof(buf), 4096) != 0) {
+        THROW_ERROR("the read beyond the truncated size should read nothing");
+    }
+    // Truncating to zero leaves nothing to read
+    if (ftruncate(fd, 0) < 0) {
+        THROW_ERROR("failed to truncate the file");
+    }
+    if (pread(fd, buf, sizeof(buf), 0) != 0) {
+        THROW_ERROR("the read of an empty file should read nothing");
+    }
+    close(fd);
+    unlink(path);
+    return 0;
+}
+
+// ============================================================================
+// Test cases for truncate
+// ============================================================================
+
+static int test_truncate() {
+    int mode = 00666;
+    // Clear the umask so that the file is created with exactly the given mode
+    mode_t old_umask = umask(0);
+    int fd = open(SEFS_FILE_PATH, O_WRONLY | O_CREAT | O_TRUNC, mode);
+    umask(old_umask);
+    if (fd < 0) {
+        THROW_ERROR("failed to open a file for write");
+    }
+    if (ftruncate(fd, TRUNC_LEN) < 0) {
+        THROW_ERROR("failed to ftruncate the file");
     }
 
     struct stat stat_buf;
-    ret = fstat(fd, &stat_buf);
-    if (ret < 0) {
-        printf("failed to fstat the file\n");
-        return ret;
+    if (fstat(fd, &stat_buf) < 0) {
+        THROW_ERROR("failed to fstat the file");
+    }
+    if (stat_buf.st_size != TRUNC_LEN) {
+        THROW_ERROR("incorrect file size %ld, expected %d", stat_buf.st_size, TRUNC_LEN);
+    }
+    if ((stat_buf.st_mode & MODE_MASK) != mode) {
+        THROW_ERROR("incorrect file mode %o, expected %o", stat_buf.st_mode & MODE_MASK, mode);
     }
+    if ((stat_buf.st_mode & S_IFMT) != S_IFREG) {
+        THROW_ERROR("incorrect file type %o, expected %o", stat_buf.st_mode & S_IFMT, S_IFREG);
+    }
+    close(fd);
 
-    int file_size = stat_buf.st_size;
-    if (file_size != TRUNC_LEN) {
-        printf("Incorrect file size %d. Expected %d\n", file_size, TRUNC_LEN);
-        return -1;
+    if (truncate(SEFS_FILE_PATH, TRUNC_LEN1) < 0) {
+        THROW_ERROR("failed to truncate the file");
+    }
+    return check_file_size(SEFS_FILE_PATH, TRUNC_LEN1);
+}
+
+static int test_grow_reads_zeros_on_sefs() {
+    return __test_grow_reads_zeros(SEFS_FILE_PATH);
+}
+
+static int test_grow_reads_zeros_on_ramfs() {
+    return __test_grow_reads_zeros(RAMFS_FILE_PATH);
+}
+
+static int test_shrink_reads_eof_on_sefs() {
+    return __test_shrink_reads_eof(SEFS_FILE_PATH);
+}
+
+static int test_shrink_reads_eof_on_ramfs() {
+    return __test_shrink_reads_eof(RAMFS_FILE_PATH);
+}
+
+static int test_ftruncate_read_only_fd() {
+    int fd = open(SEFS_FILE_PATH, O_RDONLY | O_CREAT, 00666);
+    if (fd < 0) {
+        THROW_ERROR("failed to open a file for read");
+    }
+    if (ftruncate(fd, TRUNC_LEN) == 0 || errno != EBADF) {
+        THROW_ERROR("ftruncate on a read-only fd should fail with EBADF");
     }
-    int file_mode = stat_buf.st_mode & MODE_MASK;
-	if (file_mode != mode) {
-		printf("Incorrect file mode %o. Expected %o\n", file_mode, mode);
-		return -1;
-	}
-	int file_type = stat_buf.st_mode & S_IFMT;
-	if (file_type != S_IFREG) {
-		printf("Incorrect file type %o. Expected %o\n", file_type, S_IFREG);
-		return -1;
-	}
+    close(fd);
+    return 0;
+}
 
+static int test_truncate_read_only_file() {
+    unlink(SEFS_FILE_PATH);
+    int fd = open(SEFS_FILE_PATH, O_RDONLY | O_CREAT | O_EXCL, 00444);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a read-only file");
+    }
     close(fd);
+    if (truncate(SEFS_FILE_PATH, TRUNC_LEN) == 0 || errno != EACCES) {
+        THROW_ERROR("truncate on a read-only file should fail with EACCES");
+    }
+    unlink(SEFS_FILE_PATH);
+    return 0;
+}
+
+static int test_truncate_with_fsize_limit() {
+    const rlim_t limit = 4096;
+    struct rlimit old_rlim, rlim;
+    if (getrlimit(RLIMIT_FSIZE, &old_rlim) < 0) {
+        THROW_ERROR("failed to get RLIMIT_FSIZE");
+    }
+    // On Linux, SIGXFSZ terminates the process by default
+    signal(SIGXFSZ, SIG_IGN);
+    rlim.rlim_cur = limit;
+    rlim.rlim_max = old_rlim.rlim_max;
+    if (setrlimit(RLIMIT_FSIZE, &rlim) < 0) {
+        THROW_ERROR("failed to set RLIMIT_FSIZE");
+    }
+
+    int ret = 0;
+    int fd = open(SEFS_FILE_PATH, O_WRONLY | O_CREAT | O_TRUNC, 00666);
+    if (fd < 0) {
+        ret = -1;
+        printf("\t\tERROR: failed to create a file\n");
+    } else if (ftruncate(fd, limit) < 0) {
+        ret = -1;
+        printf("\t\tERROR: ftruncate to the limit should succeed\n");
+    } else if (ftruncate(fd, limit + 1) == 0 || errno != EFBIG) {
+        ret = -1;
+        printf("\t\tERROR: ftruncate beyond the limit should fail with EFBIG\n");
+    } else if (truncate(SEFS_FILE_PATH, limit + 1) == 0 || errno != EFBIG) {
+        ret = -1;
+        printf("\t\tERROR: truncate beyond the limit should fail with EFBIG\n");
+    }
+    if (fd >= 0) {
+        close(fd);
+    }
 
-	ret = truncate(FILE_NAME, TRUNC_LEN1);
-	if (ret < 0) {
-		printf("failed to truncate the file\n");
-		return ret;
-	}
-
-	ret = stat(FILE_NAME, &stat_buf);
-	if (ret < 0) {
-		printf("failed to stat the file\n");
-		return ret;
-	}
-
-	file_size = stat_buf.st_size;
-	if (file_size != TRUNC_LEN1) {
-		printf("Incorrect file size %d. Expected %d\n", file_size, TRUNC_LEN1);
-		return -1;
-	}
-
-    printf("(f)truncate, (f)stat test successful\n");
+    if (setrlimit(RLIMIT_FSIZE, &old_rlim) < 0) {
+        THROW_ERROR("failed to restore RLIMIT_FSIZE");
+    }
+    if (ret < 0) {
+        return -1;
+    }
+    if (check_file_size(SEFS_FILE_PATH, limit) < 0) {
+        return -1;
+    }
+    unlink(SEFS_FILE_PATH);
     return 0;
 }
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_truncate),
+    TEST_CASE(test_grow_reads_zeros_on_sefs),
+    TEST_CASE(test_grow_reads_zeros_on_ramfs),
+    TEST_CASE(test_shrink_reads_eof_on_sefs),
+    TEST_CASE(test_shrink_reads_eof_on_ramfs),
+    TEST_CASE(test_ftruncate_read_only_fd),
+    TEST_CASE(test_truncate_read_only_file),
+    TEST_CASE(test_truncate_with_fsize_limit),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,130 @@
+#include <sys/stat.h>
+#include <sys/types.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <unistd.h>
+#include "test.h"
+
+#ifndef O_TMPFILE
+#define O_TMPFILE           (020000000 | O_DIRECTORY)
+#endif
+
+#define TEST_DIR            "/root"
+#define FILE_PATH           TEST_DIR "/test_umask_file.txt"
+#define NODE_PATH           TEST_DIR "/test_umask_node.txt"
+#define DIR_PATH            TEST_DIR "/test_umask_dir"
+#define TEST_UMASK          0022
+#define MODE_MASK           0777
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int check_mode(const char *path, mode_t expected_mode) {
+    struct stat stat_buf;
+    if (stat(path, &stat_buf) < 0) {
+        THROW_ERROR("failed to stat %s", path);
+    }
+    if ((stat_buf.st_mode & MODE_MASK) != expected_mode) {
+        THROW_ERROR("incorrect mode %o of %s, expected %o", stat_buf.st_mode & MODE_MASK,
+                    path, expected_mode);
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for umask
+// ============================================================================
+
+static int test_umask() {
+    mode_t old_mask = umask(TEST_UMASK);
+    if (umask(0077) != TEST_UMASK) {
+        THROW_ERROR("umask should return the previous mask");
+    }
+    if (umask(old_mask) != 0077) {
+        THROW_ERROR("umask should return the previous mask");
+    }
+    return 0;
+}
+
+static int test_open_with_umask() {
+    mode_t old_mask = umask(TEST_UMASK);
+    int fd = open(FILE_PATH, O_WRONLY | O_CREAT | O_TRUNC, 0666);
+    umask(old_mask);
+    if (fd < 0) {
+        THROW_ERROR("failed to create a file");
+    }
+    close(fd);
+    if (check_mode(FILE_PATH, 0644) < 0) {
+        return -1;
+    }
+    unlink(FILE_PATH);
+    return 0;
+}
+
+static int test_mkdir_with_umask() {
+    mode_t old_mask = umask(TEST_UMASK);
+    int ret = mkdir(DIR_PATH, 0777);
+    umask(old_mask);
+    if (ret < 0) {
+        THROW_ERROR("failed to create a directory");
+    }
+    if (check_mode(DIR_PATH, 0755) < 0) {
+        return -1;
+    }
+    rmdir(DIR_PATH);
+    return 0;
+}
+
+static int test_mknod_with_umask() {
+    mode_t old_mask = umask(TEST_UMASK);
+    int ret = mknod(NODE_PATH, S_IFREG | 0666, 0);
+    umask(old_mask);
+    if (ret < 0) {
+        THROW_ERROR("failed to create a regular file by mknod");
+    }
+    if (check_mode(NODE_PATH, 0644) < 0) {
+        return -1;
+    }
+    unlink(NODE_PATH);
+    return 0;
+}
+
+static int test_tmpfile_with_umask() {
+    mode_t old_mask = umask(TEST_UMASK);
+    int fd = open(TEST_DIR, O_TMPFILE | O_RDWR, 0666);
+    umask(old_mask);
+    if (fd < 0) {
+        THROW_ERROR("failed to create an unnamed temporary file");
+    }
+    struct stat stat_buf;
+    if (fstat(fd, &stat_buf) < 0) {
+        THROW_ERROR("failed to fstat the temporary file");
+    }
+    if ((stat_buf.st_mode & MODE_MASK) != 0644) {
+        THROW_ERROR("incorrect mode %o of the temporary file, expected %o",
+                    stat_buf.st_mode & MODE_MASK, 0644);
+    }
+    char buf[] = "tmpfile";
+    if (write(fd, buf, sizeof(buf)) != sizeof(buf)) {
+        THROW_ERROR("failed to write the temporary file");
+    }
+    close(fd);
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_umask),
+    TEST_CASE(test_open_with_umask),
+    TEST_CASE(test_mkdir_with_umask),
+    TEST_CASE(test_mknod_with_umask),
+    TEST_CASE(test_tmpfile_with_umask),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
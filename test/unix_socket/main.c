@@ -2,6 +2,7 @@
 #include <sys/wait.h>
 #include <sys/socket.h>
 #include <sys/un.h>
+#include <errno.h>
 #include <unistd.h>
 #include <stdlib.h>
 #include <stdio.h>
@@ -174,10 +175,143 @@ int test_socketpair_inter_process() {
     return test_connected_sockets_inter_process(create_connceted_sockets_default);
 }
 
+#define SOCK_PATH           "unix_socket_name_path"
+// An abstract name starts with a NUL and may contain NULs
+#define SOCK_ABSTRACT_NAME  "\0unix\0socket"
+#define FAMILY_LEN          sizeof(sa_family_t)
+
+int check_name(int fd, int is_peer, const char *expected_path, socklen_t expected_len) {
+    struct sockaddr_un addr;
+    memset(&addr, 0, sizeof(addr));
+    socklen_t addr_len = sizeof(addr);
+    int ret = is_peer ? getpeername(fd, (struct sockaddr *)&addr, &addr_len)
+                      : getsockname(fd, (struct sockaddr *)&addr, &addr_len);
+    if (ret < 0) {
+        THROW_ERROR("failed to get the name of the socket");
+    }
+    if (addr.sun_family != AF_UNIX || addr_len != expected_len) {
+        THROW_ERROR("incorrect name length %d, expected %d", addr_len, expected_len);
+    }
+    if (memcmp(addr.sun_path, expected_path, expected_len - FAMILY_LEN) != 0) {
+        THROW_ERROR("incorrect name of the socket");
+    }
+    return 0;
+}
+
+int create_named_sockets(int *sockets, const char *name, socklen_t addr_len) {
+    struct sockaddr_un addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sun_family = AF_UNIX;
+    memcpy(addr.sun_path, name, addr_len - FAMILY_LEN);
+
+    // sockets: the listening, the client and the accepted socket
+    sockets[0] = socket(AF_UNIX, SOCK_STREAM, 0);
+    if (sockets[0] < 0 || bind(sockets[0], (struct sockaddr *)&addr, addr_len) < 0 ||
+            listen(sockets[0], 5) < 0) {
+        THROW_ERROR("failed to create a listening socket");
+    }
+    sockets[1] = socket(AF_UNIX, SOCK_STREAM, 0);
+    if (sockets[1] < 0 || connect(sockets[1], (struct sockaddr *)&addr, addr_len) < 0) {
+        THROW_ERROR("failed to connect");
+    }
+    sockets[2] = accept(sockets[0], NULL, NULL);
+    if (sockets[2] < 0) {
+        THROW_ERROR("failed to accept");
+    }
+    return 0;
+}
+
+int check_named_sockets(const char *name, socklen_t addr_len) {
+    int sockets[3];
+    if (create_named_sockets(sockets, name, addr_len) < 0) {
+        return -1;
+    }
+    int ret = 0;
+    if (check_name(sockets[0], 0, name, addr_len) < 0 ||
+            check_name(sockets[1], 0, "", FAMILY_LEN) < 0 ||
+            check_name(sockets[1], 1, name, addr_len) < 0 ||
+            check_name(sockets[2], 0, name, addr_len) < 0 ||
+            check_name(sockets[2], 1, "", FAMILY_LEN) < 0) {
+        ret = -1;
+    }
+    for (int i = 0; i < 3; i++) {
+        close(sockets[i]);
+    }
+    return ret;
+}
+
+int test_pathname_socket_name() {
+    // The returned length includes the terminating NUL of the path
+    const char path[] = SOCK_PATH;
+    return check_named_sockets(path, FAMILY_LEN + sizeof(path));
+}
+
+int test_abstract_socket_name() {
+    // The returned length excludes the terminating NUL of the C string
+    const char name[] = SOCK_ABSTRACT_NAME;
+    return check_named_sockets(name, FAMILY_LEN + sizeof(name) - 1);
+}
+
+int test_unnamed_socket_name() {
+    int sockets[2];
+    if (socketpair(AF_UNIX, SOCK_STREAM, 0, sockets) < 0) {
+        THROW_ERROR("failed to create a socket pair");
+    }
+    int ret = 0;
+    for (int i = 0; i < 2; i++) {
+        if (check_name(sockets[i], 0, "", FAMILY_LEN) < 0 ||
+                check_name(sockets[i], 1, "", FAMILY_LEN) < 0) {
+            ret = -1;
+        }
+    }
+    close(sockets[0]);
+    close(sockets[1]);
+
+    int fd = socket(AF_UNIX, SOCK_STREAM, 0);
+    struct sockaddr_un addr;
+    socklen_t addr_len = sizeof(addr);
+    if (getpeername(fd, (struct sockaddr *)&addr, &addr_len) == 0 || errno != ENOTCONN) {
+        THROW_ERROR("getpeername on an unconnected socket should fail with ENOTCONN");
+    }
+    close(fd);
+    return ret;
+}
+
+int test_socket_name_with_short_buffer() {
+    struct sockaddr_un addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sun_family = AF_UNIX;
+    strcpy(addr.sun_path, SOCK_PATH);
+    int fd = socket(AF_UNIX, SOCK_STREAM, 0);
+    if (fd < 0 || bind(fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        THROW_ERROR("failed to bind");
+    }
+
+    struct sockaddr_un short_addr;
+    memset(&short_addr, 0, sizeof(short_addr));
+    socklen_t short_len = FAMILY_LEN + 4;
+    if (getsockname(fd, (struct sockaddr *)&short_addr, &short_len) < 0) {
+        THROW_ERROR("failed to getsockname");
+    }
+    // The full length is returned, while only the bytes that fit are copied
+    if (short_len != FAMILY_LEN + strlen(SOCK_PATH) + 1) {
+        THROW_ERROR("the full length of the name should be returned");
+    }
+    if (strncmp(short_addr.sun_path, SOCK_PATH, 4) != 0 || short_addr.sun_path[4] != '\0') {
+        THROW_ERROR("the name should be truncated to the buffer");
+    }
+    close(fd);
+    return 0;
+}
+
 static test_case_t test_cases[] = {
     TEST_CASE(test_unix_socket_inter_process),
     TEST_CASE(test_socketpair_inter_process),
     TEST_CASE(test_multiple_socketpairs),
+    TEST_CASE(test_pathname_socket_name),
+    TEST_CASE(test_abstract_socket_name),
+    TEST_CASE(test_unnamed_socket_name),
+    TEST_CASE(test_socket_name_with_short_buffer),
 };
 
 int main(int argc, const char* argv[]) {
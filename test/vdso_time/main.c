@@ -0,0 +1,129 @@
+#include <sys/time.h>
+#include <time.h>
+#include <unistd.h>
+#include "test.h"
+#include "../../src/libos/include/occlum_vdso_time.h"
+
+// The maximum difference allowed between the fast path and the slow path
+#define MAX_DIFF_NS         (10 * 1000 * 1000)
+
+static long timespec_diff_ns(const struct timespec *a, const struct timespec *b) {
+    return (a->tv_sec - b->tv_sec) * 1000000000L + (a->tv_nsec - b->tv_nsec);
+}
+
+// ============================================================================
+// Test cases for the vDSO time page
+// ============================================================================
+
+int test_vdso_time_page() {
+    if (occlum_vdso_time_data() == NULL) {
+        THROW_ERROR("AT_OCCLUM_VDSO_TIME is not set");
+    }
+
+    // Give the LibOS some time to calibrate the TSC frequency
+    struct timespec ts;
+    syscall(__NR_clock_gettime, CLOCK_REALTIME, &ts);
+    syscall(__NR_clock_gettime, CLOCK_MONOTONIC, &ts);
+    usleep(20 * 1000);
+    syscall(__NR_clock_gettime, CLOCK_MONOTONIC, &ts);
+
+    if (!occlum_vdso_time_data()->valid) {
+        printf("\t\tWARNING: the fast path is not available on this platform\n");
+    }
+    return 0;
+}
+
+static int check_clock(clockid_t clockid) {
+    struct timespec slow_before, fast, slow_after;
+    if (syscall(__NR_clock_gettime, clockid, &slow_before) < 0) {
+        THROW_ERROR("clock_gettime failed");
+    }
+    if (occlum_vdso_clock_gettime(clockid, &fast) < 0) {
+        THROW_ERROR("occlum_vdso_clock_gettime failed");
+    }
+    if (syscall(__NR_clock_gettime, clockid, &slow_after) < 0) {
+        THROW_ERROR("clock_gettime failed");
+    }
+    if (timespec_diff_ns(&fast, &slow_before) < -MAX_DIFF_NS ||
+            timespec_diff_ns(&slow_after, &fast) < -MAX_DIFF_NS) {
+        THROW_ERROR("the time from the fast path is inconsistent with the slow path");
+    }
+    return 0;
+}
+
+int test_vdso_clock_gettime() {
+    for (int i = 0; i < 100; i++) {
+        if (check_clock(CLOCK_REALTIME) < 0 || check_clock(CLOCK_MONOTONIC) < 0) {
+            return -1;
+        }
+    }
+    return 0;
+}
+
+int test_vdso_clock_gettime_after_refresh() {
+    // Let the bases in the time page be old enough to be refreshed
+    for (int i = 0; i < 3; i++) {
+        usleep(150 * 1000);
+        if (check_clock(CLOCK_REALTIME) < 0 || check_clock(CLOCK_MONOTONIC) < 0) {
+            return -1;
+        }
+    }
+    return 0;
+}
+
+int test_vdso_clock_gettime_monotonic() {
+    struct timespec prev, curr;
+    if (occlum_vdso_clock_gettime(CLOCK_MONOTONIC, &prev) < 0) {
+        THROW_ERROR("occlum_vdso_clock_gettime failed");
+    }
+    for (int i = 0; i < 10000; i++) {
+        if (occlum_vdso_clock_gettime(CLOCK_MONOTONIC, &curr) < 0) {
+            THROW_ERROR("occlum_vdso_clock_gettime failed");
+        }
+        if (timespec_diff_ns(&curr, &prev) < -MAX_DIFF_NS) {
+            THROW_ERROR("CLOCK_MONOTONIC goes backwards");
+        }
+        prev = curr;
+    }
+    return 0;
+}
+
+int test_vdso_gettimeofday() {
+    struct timeval slow, fast;
+    if (syscall(__NR_gettimeofday, &slow, NULL) < 0) {
+        THROW_ERROR("gettimeofday failed");
+    }
+    if (occlum_vdso_gettimeofday(&fast, NULL) < 0) {
+        THROW_ERROR("occlum_vdso_gettimeofday failed");
+    }
+    long diff_us = (fast.tv_sec - slow.tv_sec) * 1000000L + (fast.tv_usec - slow.tv_usec);
+    if (diff_us < -MAX_DIFF_NS / 1000 || diff_us > MAX_DIFF_NS / 1000) {
+        THROW_ERROR("the time from the fast path is inconsistent with the slow path");
+    }
+    return 0;
+}
+
+int test_vdso_fallback() {
+    struct timespec ts;
+    if (occlum_vdso_clock_gettime(CLOCK_BOOTTIME, &ts) < 0) {
+        THROW_ERROR("occlum_vdso_clock_gettime should fall back to the syscall");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_vdso_time_page),
+    TEST_CASE(test_vdso_clock_gettime),
+    TEST_CASE(test_vdso_clock_gettime_after_refresh),
+    TEST_CASE(test_vdso_clock_gettime_monotonic),
+    TEST_CASE(test_vdso_gettimeofday),
+    TEST_CASE(test_vdso_fallback),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
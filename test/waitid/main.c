@@ -0,0 +1,184 @@
+#include <sys/types.h>
+#include <sys/wait.h>
+#include <errno.h>
+#include <signal.h>
+#include <spawn.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+// Spawn a child that sleeps for `sleep_ms` milliseconds and then exits with
+// `exit_code`
+static int spawn_child(int exit_code, int sleep_ms) {
+    char exit_code_str[16];
+    char sleep_ms_str[16];
+    snprintf(exit_code_str, sizeof(exit_code_str), "%d", exit_code);
+    snprintf(sleep_ms_str, sizeof(sleep_ms_str), "%d", sleep_ms);
+
+    int child_pid;
+    const char *child_argv[5] = { "waitid", "child", exit_code_str, sleep_ms_str, NULL };
+    if (posix_spawn(&child_pid, "/bin/waitid", NULL, NULL, (char *const *)child_argv,
+                    NULL) < 0) {
+        THROW_ERROR("failed to spawn a child process");
+    }
+    return child_pid;
+}
+
+static int run_child(int exit_code, int sleep_ms) {
+    usleep(sleep_ms * 1000);
+    return exit_code;
+}
+
+static int check_exited_siginfo(siginfo_t *info, int child_pid, int exit_code) {
+    if (info->si_signo != SIGCHLD) {
+        THROW_ERROR("si_signo is not SIGCHLD");
+    }
+    if (info->si_code != CLD_EXITED) {
+        THROW_ERROR("si_code is not CLD_EXITED");
+    }
+    if (info->si_pid != child_pid) {
+        THROW_ERROR("si_pid is not the pid of the child");
+    }
+    if (info->si_status != exit_code) {
+        THROW_ERROR("si_status is not the exit code of the child");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for waitid
+// ============================================================================
+
+static int test_waitid_exited() {
+    int child_pid = spawn_child(7, 0);
+    if (child_pid < 0) {
+        return -1;
+    }
+
+    siginfo_t info;
+    memset(&info, 0, sizeof(info));
+    if (waitid(P_PID, child_pid, &info, WEXITED) < 0) {
+        THROW_ERROR("failed to waitid the child process");
+    }
+    return check_exited_siginfo(&info, child_pid, 7);
+}
+
+static int test_waitid_all() {
+    int child_pid = spawn_child(0, 0);
+    if (child_pid < 0) {
+        return -1;
+    }
+
+    siginfo_t info;
+    memset(&info, 0, sizeof(info));
+    if (waitid(P_ALL, 0, &info, WEXITED) < 0) {
+        THROW_ERROR("failed to waitid the child process");
+    }
+    return check_exited_siginfo(&info, child_pid, 0);
+}
+
+static int test_waitid_nowait() {
+    int child_pid = spawn_child(3, 0);
+    if (child_pid < 0) {
+        return -1;
+    }
+
+    siginfo_t info;
+    memset(&info, 0, sizeof(info));
+    if (waitid(P_PID, child_pid, &info, WEXITED | WNOWAIT) < 0) {
+        THROW_ERROR("failed to waitid the child process");
+    }
+    if (check_exited_siginfo(&info, child_pid, 3) < 0) {
+        return -1;
+    }
+
+    // The child is still reapable
+    int status;
+    if (wait4(child_pid, &status, 0, NULL) != child_pid) {
+        THROW_ERROR("failed to wait4 the child process after WNOWAIT");
+    }
+    if (!WIFEXITED(status) || WEXITSTATUS(status) != 3) {
+        THROW_ERROR("the wait status of the child is wrong");
+    }
+    return 0;
+}
+
+static int test_waitid_nohang() {
+    int child_pid = spawn_child(5, 200);
+    if (child_pid < 0) {
+        return -1;
+    }
+
+    siginfo_t info;
+    memset(&info, 0xff, sizeof(info));
+    if (waitid(P_PID, child_pid, &info, WEXITED | WNOHANG) < 0) {
+        THROW_ERROR("failed to waitid the child process with WNOHANG");
+    }
+    if (info.si_pid != 0) {
+        THROW_ERROR("si_pid should be cleared when the child has not exited");
+    }
+
+    memset(&info, 0, sizeof(info));
+    if (waitid(P_PID, child_pid, &info, WEXITED) < 0) {
+        THROW_ERROR("failed to waitid the child process");
+    }
+    return check_exited_siginfo(&info, child_pid, 5);
+}
+
+static int test_wait4_nohang() {
+    int child_pid = spawn_child(0, 200);
+    if (child_pid < 0) {
+        return -1;
+    }
+
+    int status;
+    if (wait4(child_pid, &status, WNOHANG, NULL) != 0) {
+        THROW_ERROR("wait4 with WNOHANG should return 0 when the child has not exited");
+    }
+    if (wait4(child_pid, &status, 0, NULL) != child_pid) {
+        THROW_ERROR("failed to wait4 the child process");
+    }
+    return 0;
+}
+
+static int test_waitid_without_children() {
+    siginfo_t info;
+    if (waitid(P_ALL, 0, &info, WEXITED) == 0 || errno != ECHILD) {
+        THROW_ERROR("waitid without children should fail with ECHILD");
+    }
+    return 0;
+}
+
+static int test_waitid_with_invalid_options() {
+    siginfo_t info;
+    if (waitid(P_ALL, 0, &info, WNOHANG) == 0 || errno != EINVAL) {
+        THROW_ERROR("waitid without any state change to wait for should fail with EINVAL");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_waitid_exited),
+    TEST_CASE(test_waitid_all),
+    TEST_CASE(test_waitid_nowait),
+    TEST_CASE(test_waitid_nohang),
+    TEST_CASE(test_wait4_nohang),
+    TEST_CASE(test_waitid_without_children),
+    TEST_CASE(test_waitid_with_invalid_options),
+};
+
+int main(int argc, const char *argv[]) {
+    if (argc > 3 && strcmp(argv[1], "child") == 0) {
+        return run_child(atoi(argv[2]), atoi(argv[3]));
+    }
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}